@@ -0,0 +1,80 @@
+//! Dynamic shell completion: argument values are listed from the repository
+//! each time the shell asks, so new tags, remotes and manifests show up immediately.
+
+use clap_complete::engine::CompletionCandidate;
+use std::path::Path;
+use vekt_core::refs;
+use vekt_core::releases;
+use vekt_core::storage::VektConfig;
+use vekt_core::utils::find_manifest_files;
+
+fn candidates(names: Vec<String>, help: &'static str) -> Vec<CompletionCandidate> {
+    names
+        .into_iter()
+        .map(|name| CompletionCandidate::new(name).help(Some(help.into())))
+        .collect()
+}
+
+pub fn remotes() -> Vec<CompletionCandidate> {
+    let names = VektConfig::load()
+        .map(|config| config.remote_names())
+        .unwrap_or_default();
+    candidates(names, "remote")
+}
+
+/// Remotes and remote groups, which a push accepts alike
+pub fn push_remotes() -> Vec<CompletionCandidate> {
+    let mut names = remotes();
+    if let Ok(config) = VektConfig::load() {
+        names.extend(candidates(
+            config.remote_groups.into_keys().collect(),
+            "remote group",
+        ));
+    }
+    names
+}
+
+/// `*.vekt.json` files below the current directory, as relative paths
+pub fn manifests() -> Vec<CompletionCandidate> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let names = find_manifest_files(&cwd)
+        .unwrap_or_default()
+        .iter()
+        .map(|path| {
+            path.strip_prefix(&cwd)
+                .unwrap_or(Path::new(path))
+                .display()
+                .to_string()
+        })
+        .collect();
+    candidates(names, "manifest")
+}
+
+pub fn tags() -> Vec<CompletionCandidate> {
+    candidates(refs::tag_names(), "tag")
+}
+
+pub fn branches() -> Vec<CompletionCandidate> {
+    candidates(refs::branch_names(), "branch")
+}
+
+pub fn releases() -> Vec<CompletionCandidate> {
+    candidates(releases::release_names(), "release")
+}
+
+/// Anything `resolve_manifest` accepts: manifest files, tags and branches
+pub fn manifest_refs() -> Vec<CompletionCandidate> {
+    let mut all = manifests();
+    all.extend(tags());
+    all.extend(branches());
+    all
+}
+
+/// What `push` accepts: manifest files and tags
+pub fn pushable() -> Vec<CompletionCandidate> {
+    let mut all = manifests();
+    all.extend(tags());
+    all
+}
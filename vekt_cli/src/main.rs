@@ -1,12 +1,55 @@
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
-use vekt_core::ModelArchiver;
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 use vekt_core::SafetensorFile;
-use vekt_core::remote::RemoteClient;
-use vekt_core::utils::{LockFile, find_vekt_root, get_store_path};
+use vekt_core::audit::{self, AuditEntry, TransferDigest, UploadVerification};
+use vekt_core::backup;
+use vekt_core::bundle;
+use vekt_core::cache;
+use vekt_core::cancel::{self, CancellationToken};
+use vekt_core::checkout::{self, Materialized};
+use vekt_core::commits;
+use vekt_core::config::{self, ConfigScope};
+#[cfg(unix)]
+use vekt_core::daemon::{self, DaemonRequest, DaemonResponse};
+use vekt_core::errors::{VektError, exit_code};
+use vekt_core::filter;
+use vekt_core::follow::{self, FollowConfig, FollowKind, Subscription, UpdateStatus};
+use vekt_core::hooks;
+use vekt_core::hub::{HUB_SCHEME, HubClient, HubRepo};
+use vekt_core::import::{Importer, PointerKind};
+use vekt_core::index::IndexStatus;
+use vekt_core::listing::{self, ListFilter, ManifestState, SortKey};
+use vekt_core::merge;
+use vekt_core::meta::{self, MetaFilter};
+use vekt_core::object_store::{TOKEN_ENV, is_http_url};
+use vekt_core::pins;
+use vekt_core::quantize::QuantScheme;
+use vekt_core::refcount::{self, RefCounts};
+use vekt_core::refs;
+use vekt_core::releases::{self, Release};
+use vekt_core::remote::{BlobRepair, RemoteClient, RemoteCredentials};
+use vekt_core::resize;
+use vekt_core::serve::BlobServer;
+use vekt_core::sparse::{self, SparseSpec};
+use vekt_core::storage::VektManifest;
+use vekt_core::swap::{self, SwapEvent};
+use vekt_core::tensor_filter::{TensorFilter, parse_ranges};
+use vekt_core::three_way;
+use vekt_core::tombstones;
+use vekt_core::transaction::Transaction;
+use vekt_core::utils::{LockFile, find_vekt_root, get_store_path, parse_duration, parse_size};
+use vekt_core::validation::{ServerSideEncryption, VerifyPolicy, parse_s3_url};
+use vekt_core::watch::{WatchOptions, watch_checkpoints};
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::engine::ArgValueCandidates;
+use clap_complete::env::{CompleteEnv, Shells};
+use tracing_subscriber::EnvFilter;
+
+mod complete;
 
 #[derive(Parser)]
 #[command(name = "vekt")]
@@ -14,6 +57,28 @@ use clap::{Parser, Subcommand};
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(about = "Git for Tensors", long_about = None)]
 struct Cli {
+    /// Wait up to this long (e.g. 30s, 5m) for another vekt process to release the
+    /// repository lock instead of failing immediately
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+    lock_wait: Option<Duration>,
+
+    /// Cancel add, restore, push and pull if they are still running after this long
+    /// (e.g. 90s, 2h), cleaning up like Ctrl-C does
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Log more on stderr: -v adds every blob written and transferred, -vv everything
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Only log warnings and errors on stderr
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Log as text or as JSON lines
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text", value_parser = ["text", "json"])]
+    log_format: String,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,51 +87,913 @@ struct Cli {
 enum Commands {
     Init,
     Add {
+        /// Model file, or `-` to read a safetensors file from stdin
         path: PathBuf,
+        /// Name of the manifest, `<NAME>.vekt.json`; required when reading from stdin
+        #[arg(long)]
+        name: Option<String>,
+        /// Compress tensors no storage rule matches with zstd, whatever `compression`
+        /// is configured to
         #[arg(long, default_value = "false")]
         compress: bool,
+        /// Ignore .vekt/index and re-hash every tensor
+        #[arg(long)]
+        rehash: bool,
+        /// Also track known sidecar files (config.json, tokenizer.json, ...) found next to the model
+        #[arg(long)]
+        sidecars: bool,
+        /// Track an extra non-tensor file with the model (repeatable)
+        #[arg(long = "attach", value_name = "FILE")]
+        attach: Vec<PathBuf>,
+        /// Upload blobs to this remote while the model is still being hashed,
+        /// then publish the manifest
+        #[arg(long, value_name = "REMOTE", add = ArgValueCandidates::new(complete::remotes))]
+        push: Option<String>,
+        /// Mark the manifest as ephemeral: once its TTL passes, gc reclaims its blobs
+        #[arg(long)]
+        ephemeral: bool,
+        /// Lifetime of an ephemeral manifest, e.g. 12h or 30d (default: 7d)
+        #[arg(long, value_name = "DURATION", requires = "ephemeral", value_parser = parse_duration)]
+        ttl: Option<Duration>,
+        /// Record min/max/mean/norm and NaN/Inf counts of every float tensor
+        #[arg(long)]
+        stats: bool,
+        /// Record stats and check them for NaN/Inf, all-zero and drifting tensors, as
+        /// the `checks` config says
+        #[arg(long)]
+        check: bool,
+        /// Flag tensors whose norm moved far from this manifest's (file, tag or branch)
+        #[arg(long, value_name = "MANIFEST", requires = "check", add = ArgValueCandidates::new(complete::manifest_refs))]
+        check_against: Option<String>,
+        /// Report what would be hashed and written without changing anything
+        #[arg(long, conflicts_with = "push")]
+        dry_run: bool,
     },
     Restore {
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
         path: PathBuf,
+        /// Where to write the model (default: next to the manifest); `-` streams it to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only restore tensors whose names contain one of these comma-separated terms
         #[arg(long)]
         layers: Option<String>,
+        /// Only restore tensors matching this regex (repeatable; combines with --layers)
+        #[arg(long, value_name = "REGEX")]
+        include_regex: Vec<String>,
+        /// Only restore these transformer blocks (model.layers.N, h.N, ...), e.g. 0..16 or 0..=7,24.. (repeatable)
+        #[arg(long, value_name = "RANGES")]
+        layers_range: Vec<String>,
+        /// Only restore the tensors at these positions of the original file, e.g. 0..100
+        #[arg(long, value_name = "RANGES")]
+        index_range: Vec<String>,
+        /// Skip tensors whose names contain one of these comma-separated terms
+        #[arg(long, value_name = "TERMS")]
+        exclude: Vec<String>,
+        /// Skip tensors matching this regex (repeatable)
+        #[arg(long, value_name = "REGEX")]
+        exclude_regex: Vec<String>,
+        /// Merge a LoRA adapter manifest into the restored weights
+        #[arg(long, value_name = "ADAPTER", add = ArgValueCandidates::new(complete::manifest_refs))]
+        apply_adapter: Option<String>,
+        /// Write the adapter tensors next to the base weights instead of merging
+        #[arg(long, requires = "apply_adapter")]
+        side_by_side: bool,
+        /// Blob verification: always, never, sampled or sampled:<size> (default: config, else always)
+        #[arg(long)]
+        verify: Option<VerifyPolicy>,
+        /// Rebuild the file that was added byte for byte, checked against the checksum
+        /// recorded at add time
+        #[arg(long, conflicts_with_all = [
+            "layers", "include_regex", "layers_range", "index_range", "exclude", "exclude_regex",
+            "apply_adapter", "from",
+        ])]
+        exact: bool,
+        /// Fetch the manifest from this remote and stream the blobs straight into the
+        /// output, without them having to be in the local store
+        #[arg(long, value_name = "REMOTE", conflicts_with = "apply_adapter", add = ArgValueCandidates::new(complete::remotes))]
+        from: Option<String>,
+        /// Keep the blobs downloaded by --from in the local store
+        #[arg(long, requires = "from")]
+        cache: bool,
+    },
+    /// Manage LoRA adapters layered on a base model
+    Adapter {
+        #[command(subcommand)]
+        action: AdapterCommand,
     },
     Diff {
-        old: PathBuf,
-        new: PathBuf,
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        old: String,
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        new: String,
+        /// Write a standalone HTML report to this path
+        #[arg(long, value_name = "OUT")]
+        html: Option<PathBuf>,
+    },
+    /// Show the tensor statistics recorded by `add --stats`
+    Stats {
+        /// Manifest file, tag, or branch
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        /// Only list tensors with NaN or infinite values
+        #[arg(long)]
+        non_finite: bool,
+    },
+    Tag {
+        #[arg(add = ArgValueCandidates::new(complete::tags))]
+        name: Option<String>,
+        /// Manifest file, tag, or manifest hash to tag
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: Option<String>,
+        #[arg(short, long)]
+        delete: bool,
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Protect a manifest's blobs from gc and cache eviction; lists pins without one
+    Pin {
+        /// Manifest file, tag, or branch to pin
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: Option<String>,
+    },
+    /// Remove the pin of a manifest file or tag
+    Unpin {
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+    },
+    /// Only pull and restore tensors matching these patterns (.vekt/sparse); shows
+    /// the patterns without any
+    Sparse {
+        /// Tensor name patterns with * and ?; a leading ! leaves tensors out
+        patterns: Vec<String>,
+        /// Pull and restore every tensor again
+        #[arg(long, conflicts_with = "patterns")]
+        disable: bool,
+    },
+    /// Stop tracking a manifest file or tag; `gc --removed` then collects its blobs
+    Rm {
+        /// Manifest file or tag to remove
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        /// Keep the model file, only forget the manifest
+        #[arg(long)]
+        cached: bool,
+    },
+    /// Resize a tensor, reusing overlapping data and initializing new elements
+    PatchResize {
+        /// Manifest file, tag, or branch to patch
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        tensor: String,
+        /// New shape, e.g. 32064,4096
+        #[arg(long)]
+        shape: String,
+        /// zeros, mean (new rows = mean of existing rows), or file:<raw bytes>
+        #[arg(long, default_value = "zeros")]
+        init: String,
+        /// Where to write the patched manifest (defaults to the input manifest file)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rename tensors by a JSON file of regex rules, keeping their blobs
+    Remap {
+        /// Manifest file, tag, or branch to remap
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        /// JSON object of patterns to replacements, e.g. {"^transformer\\.h\\.": "model.layers."}
+        #[arg(long, value_name = "FILE")]
+        rules: PathBuf,
+        /// Where to write the remapped manifest (defaults to the input manifest file)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Store a quantized variant of a model, linked to the full-precision manifest
+    Quantize {
+        /// Manifest file, tag, or branch to quantize
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        /// q8_0 (8-bit) or q4_0 (4-bit), with one F16 scale per 32 values
+        #[arg(long, default_value = "q8_0")]
+        scheme: String,
+        /// Where to write the quantized manifest (default: <name>.<scheme>.vekt.json)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Merge models tensor by tensor (model soups, interpolation)
+    Merge {
+        /// Manifest files, tags, or branches to merge; the first decides the layout
+        #[arg(required = true, num_args = 2.., add = ArgValueCandidates::new(complete::manifest_refs))]
+        models: Vec<String>,
+        /// average, linear or slerp
+        #[arg(long, default_value = "average")]
+        method: String,
+        /// Per-model weights for average, e.g. 0.3,0.7
+        #[arg(long)]
+        weights: Option<String>,
+        /// Interpolation factor for linear and slerp (0 = first model, 1 = second)
+        #[arg(short, long, default_value_t = 0.5)]
+        t: f64,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Merge the tensor changes two versions made to a common base manifest
+    MergeManifests {
+        /// Version both sides started from
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        base: String,
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        ours: String,
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        theirs: String,
+        /// Where to write the result (default: ours, when it is a manifest file)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Resolve conflicts with this side's version instead of failing
+        #[arg(long, value_parser = ["ours", "theirs"])]
+        favor: Option<String>,
+    },
+    /// Record the current manifests as a commit on the checked-out branch
+    Commit {
+        #[arg(short, long)]
+        message: String,
+        /// Manifests to commit (defaults to every manifest in the repository)
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        files: Vec<PathBuf>,
+    },
+    Branch {
+        #[arg(add = ArgValueCandidates::new(complete::branches))]
+        name: Option<String>,
+        /// Branch or commit to start from (defaults to HEAD)
+        #[arg(add = ArgValueCandidates::new(complete::branches))]
+        start: Option<String>,
+        #[arg(short, long)]
+        delete: bool,
+    },
+    Switch {
+        #[arg(add = ArgValueCandidates::new(complete::branches))]
+        branch: String,
+        /// Create the branch at HEAD before switching
+        #[arg(short, long)]
+        create: bool,
+        /// Discard uncommitted manifest changes
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Write the models of a tag, branch or commit, with their attachments, into a
+    /// directory
+    Checkout {
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        spec: String,
+        #[arg(long, value_name = "DIR")]
+        into: PathBuf,
+        /// Overwrite files in the directory that no checkout wrote
+        #[arg(short, long)]
+        force: bool,
+    },
+    Log {
+        /// Branch or commit to start from (defaults to HEAD)
+        #[arg(add = ArgValueCandidates::new(complete::branches))]
+        rev: Option<String>,
+        /// Draw the ancestry graph
+        #[arg(long)]
+        graph: bool,
+        /// Show history of every branch
+        #[arg(long)]
+        all: bool,
+    },
+    Attach {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Store a single file under a different name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    Detach {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: PathBuf,
+        name: String,
+    },
+    /// Set, show and remove user metadata such as run IDs, licenses, eval scores and
+    /// model cards
+    Meta {
+        #[command(subcommand)]
+        action: MetaCommand,
+    },
+    /// List manifests with their size, tensor count, age and tags: local files,
+    /// tracked by HEAD's commit or not, and with --remote those only pushed there
+    List {
+        /// KEY=VALUE (the value may use * and ?), or KEY for manifests that have it.
+        /// Repeat to require several.
+        #[arg(long = "filter", value_name = "KEY[=VALUE]")]
+        filters: Vec<MetaFilter>,
+        /// Only manifests in this state. Repeat to keep several.
+        #[arg(long, value_parser = ["tracked", "modified", "untracked", "remote"])]
+        state: Vec<String>,
+        /// Also list manifests pushed to this remote that no local file matches
+        #[arg(long, add = ArgValueCandidates::new(complete::remotes))]
+        remote: Option<String>,
+        #[arg(long, default_value = "name", value_parser = ["name", "size", "tensors", "modified"])]
+        sort: String,
+        #[arg(long)]
+        reverse: bool,
+        #[arg(long)]
+        json: bool,
     },
     Remote {
         #[command(subcommand)]
         action: RemoteCommand,
     },
+    /// Read and change settings in .vekt/config.json and the user config
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Import the safetensors files a git repository tracks with git-lfs, from
+    /// .git/lfs or the LFS server, writing a manifest for each
+    ImportLfs {
+        /// The git repository holding the pointer files
+        repo: PathBuf,
+        /// Directory the manifests are written to, mirroring the repository layout
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// LFS server to fetch missing objects from, instead of the one derived from
+        /// the origin remote. Credentials may be given in the URL.
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Import the safetensors files a repository tracks with DVC, from its cache or
+    /// remote, writing a manifest for each
+    ImportDvc {
+        /// The DVC repository holding the .dvc files and dvc.lock
+        repo: PathBuf,
+        /// Directory the manifests are written to, mirroring the repository layout
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// DVC remote (s3://, http(s):// or a directory) to fetch missing objects
+        /// from, instead of the repository's default remote
+        #[arg(long)]
+        url: Option<String>,
+    },
     Pull {
-        #[arg(default_value = "origin")]
+        /// Remote name, or a Hugging Face Hub model as hf://org/repo[@revision]
+        #[arg(default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
         remote: String,
+        /// Where manifests of models pulled from the Hugging Face Hub are written
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Report what would be downloaded without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     Push {
-        #[arg(default_value = "origin")]
+        /// Remote, or a group from remote_groups in the config to mirror to
+        /// (default: origin)
+        #[arg(add = ArgValueCandidates::new(complete::push_remotes))]
+        remote: Option<String>,
+        /// Manifest files or tags to push (default: all manifests here and all tags)
+        #[arg(add = ArgValueCandidates::new(complete::pushable))]
+        refs: Vec<String>,
+        /// Mirror to every configured remote; all arguments are then refs
+        #[arg(long)]
+        all_remotes: bool,
+        /// Upload only blobs that aren't in this previously pushed manifest (file,
+        /// tag or commit), without asking the remote about the others
+        #[arg(long, value_name = "MANIFEST", add = ArgValueCandidates::new(complete::pushable))]
+        since: Option<String>,
+        /// Like --since, against one listing of the remote's blobs
+        #[arg(long, conflicts_with = "since")]
+        since_remote: bool,
+        /// Report what would be uploaded without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Overwrite manifests locked on the remote by someone else, or changed there
+        /// since they were last pushed or pulled here
+        #[arg(long)]
+        force: bool,
+    },
+    /// Lock a manifest on a remote so nobody else can push it; lists locks without one
+    Lock {
+        /// Manifest file to lock
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: Option<PathBuf>,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
         remote: String,
     },
+    /// Release a manifest lock taken with `vekt lock`
+    Unlock {
+        /// Manifest file to unlock
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: PathBuf,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        /// Release a lock held by someone else
+        #[arg(long)]
+        force: bool,
+    },
     Status,
-    Gc,
+    Gc {
+        /// Only collect blobs of manifests removed with `vekt rm`
+        #[arg(long)]
+        removed: bool,
+        /// Report what would be deleted without deleting it
+        #[arg(long)]
+        dry_run: bool,
+        /// Read every manifest again instead of trusting the refcount database, and
+        /// rebuild it
+        #[arg(long, conflicts_with = "removed")]
+        full: bool,
+    },
+    /// Keep the blob store under a size cap by evicting least-recently-used blobs
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Maintain the blob index (.vekt/blob-index.json) that add, gc and status consult
+    Index {
+        #[command(subcommand)]
+        action: IndexCommand,
+    },
+    Watch {
+        dir: PathBuf,
+        #[arg(long, default_value = "*.safetensors")]
+        pattern: String,
+        /// Push each snapshot to this remote after adding it
+        #[arg(long, add = ArgValueCandidates::new(complete::remotes))]
+        push: Option<String>,
+        /// Seconds a file must stay unchanged before it is added
+        #[arg(long, default_value = "2")]
+        settle: u64,
+        #[arg(long)]
+        recursive: bool,
+    },
+    /// Publish several manifests together as one immutable release
+    Release {
+        #[command(subcommand)]
+        action: ReleaseCommand,
+    },
+    /// Follow a remote release (or tag with --tag); lists follows without a pattern
+    Follow {
+        /// Release or tag name; `*` and `?` match any newer version
+        pattern: Option<String>,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        /// Follow tags instead of releases
+        #[arg(long)]
+        tag: bool,
+        /// Directory to materialize the followed model into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Stop following the pattern
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Pull and restore the newest version of every followed release or tag
+    Update {
+        /// Print one JSON result per follow instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Restore into a versioned directory and atomically repoint its `current` symlink
+    Swap {
+        /// Manifest file, tag, or branch to deploy
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        target: PathBuf,
+        /// Shell command run after the swap with VEKT_SWAP_* variables set
+        #[arg(long)]
+        hook: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report store size, deduplication and what each manifest keeps alive
+    Du {
+        /// Number of largest blobs to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Also count manifests held by tags, branches and releases
+        #[arg(long)]
+        all: bool,
+        /// Only report what gc would reclaim, from the refcount database
+        #[arg(long, conflicts_with = "all")]
+        reclaimable: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show which tensors and blobs manifests share, e.g. a base model and its
+    /// fine-tunes, and how much storing them once saves
+    DedupReport {
+        /// Manifest files, tags or branches
+        #[arg(num_args = 2.., required = true, add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifests: Vec<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Snapshot repository metadata into an archive, or reattach one to this store
+    Backup {
+        #[command(subcommand)]
+        action: BackupCommand,
+    },
+    /// Pack manifests and their blobs into a single file for air-gapped transfer, or
+    /// ingest one into this store
+    Bundle {
+        #[command(subcommand)]
+        action: BundleCommand,
+    },
+    /// Print a completion script that also completes remotes, manifests and tags.
+    /// Load it with e.g. `source <(vekt completion bash)`.
+    Completion {
+        /// bash, elvish, fish, powershell or zsh
+        shell: String,
+    },
+    /// Install Git hooks that re-add tracked models on commit and push their weights
+    /// to a vekt remote on `git push`
+    InstallHooks {
+        /// vekt remote the pre-push hook pushes to
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        /// Replace existing hooks not written by vekt (kept as <hook>.orig)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Git clean filter: store the safetensors file on stdin and print its pointer
+    FilterClean,
+    /// Git smudge filter: print the file the pointer on stdin stands for
+    FilterSmudge,
+    /// Serve this repository over HTTP so other machines can use it as a remote.
+    /// Set VEKT_TOKEN to require it as a bearer token.
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+    /// Serve add/restore/push/pull/status over a Unix socket, one JSON request per
+    /// line, running them one at a time from this process
+    #[cfg(unix)]
+    Daemon {
+        /// Socket to listen on (default: .vekt/daemon.sock)
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Check local blobs of a manifest and download whatever is missing or corrupt.
+    /// Large tensors only download their corrupt ranges.
+    Repair {
+        /// Manifest file, tag, or branch
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+    },
+    /// Check that a manifest still rebuilds the file it was added from, or that a
+    /// file on disk is that file
+    Verify {
+        /// Manifest file, tag, or branch
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        /// Hash this file instead of rebuilding the original from the store
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    VerifyRemote {
+        #[arg(default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        /// Portion of remote blobs to verify, e.g. "5%" or "0.05"
+        #[arg(long, default_value = "5%")]
+        sample: String,
+        /// Seed for the sample selection; reuse it to reproduce a run
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdapterCommand {
+    /// Record the base model an adapter manifest applies to
+    Register {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        adapter: PathBuf,
+        /// Base manifest file, tag, or branch
+        #[arg(long, add = ArgValueCandidates::new(complete::manifest_refs))]
+        base: String,
+    },
+    /// Restore the registered base model with the adapter merged in
+    Restore {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        adapter: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Write the adapter tensors next to the base weights instead of merging
+        #[arg(long)]
+        side_by_side: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Remove least-recently-used blobs until the store fits; pinned and tagged models are kept
+    Evict {
+        /// Size to shrink the store to, e.g. 20GiB (default: max_store_size in the config)
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Re-read every blob's size and creation time from the store
+    Rebuild,
+}
+
+#[derive(Subcommand)]
+enum ReleaseCommand {
+    /// Upload every blob, then publish the release object in one step
+    Create {
+        name: String,
+        #[arg(long, num_args = 1.., required = true, add = ArgValueCandidates::new(complete::manifests))]
+        manifests: Vec<PathBuf>,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        /// Replace a published release with different contents
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// List releases (published ones with --remote)
+    List {
+        #[arg(long, add = ArgValueCandidates::new(complete::remotes))]
+        remote: Option<String>,
+    },
+    Show {
+        #[arg(add = ArgValueCandidates::new(complete::releases))]
+        name: String,
+    },
+    /// Download a release and write its manifests into a directory
+    Pull {
+        name: String,
+        #[arg(long, default_value = "origin", add = ArgValueCandidates::new(complete::remotes))]
+        remote: String,
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Write config, refs, commits, releases and manifest objects to a .tar.zst archive
+    Create {
+        output: PathBuf,
+        /// Also store every blob referenced by stored or working-tree manifests, for a
+        /// fully self-contained backup
+        #[arg(long)]
+        blobs: bool,
+    },
+    /// Restore metadata from an archive into this repository
+    Restore {
+        archive: PathBuf,
+        /// Fetch blobs missing from the local store from this remote
+        #[arg(long, add = ArgValueCandidates::new(complete::remotes))]
+        remote: Option<String>,
+        /// Restore over existing refs and commits
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BundleCommand {
+    /// Write the manifests and every blob they reference to a .vektbundle archive
+    Create {
+        output: PathBuf,
+        /// Manifest files, tags, commits or manifest object hashes
+        #[arg(required = true, add = ArgValueCandidates::new(complete::manifests))]
+        manifests: Vec<String>,
+    },
+    /// Add a bundle's blobs to this store and write its manifests
+    Unpack {
+        bundle: PathBuf,
+        /// Directory the manifests are written to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Replace existing manifests with different content
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MetaCommand {
+    /// Set a key to a value, or to the contents of a file with --meta-file
+    Set {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: PathBuf,
+        key: String,
+        #[arg(required_unless_present = "meta_file")]
+        value: Option<String>,
+        /// Read the value from a file, e.g. model_card.md
+        #[arg(long, conflicts_with = "value")]
+        meta_file: Option<PathBuf>,
+    },
+    /// Print one key's value, or every key of a manifest file, tag or commit
+    Get {
+        #[arg(add = ArgValueCandidates::new(complete::manifest_refs))]
+        manifest: String,
+        key: Option<String>,
+        #[arg(long, conflicts_with = "key")]
+        json: bool,
+    },
+    /// Remove a key
+    Unset {
+        #[arg(add = ArgValueCandidates::new(complete::manifests))]
+        manifest: PathBuf,
+        key: String,
+    },
 }
 
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective value of a key, e.g. concurrency or remotes.origin
+    Get { key: String },
+    /// Set a key in the repository config (the user config with --global)
+    Set {
+        key: String,
+        /// JSON (32, true, [...]) or a plain string
+        value: String,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Remove a key from the repository config (the user config with --global)
+    Unset {
+        key: String,
+        #[arg(long)]
+        global: bool,
+    },
+    /// List every set key with its effective value
+    List {
+        /// Show which layer (user, repo or env) each value comes from
+        #[arg(long)]
+        show_origin: bool,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// Parsed once per run, so the size of `Add` doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum RemoteCommand {
-    Add { name: String, url: String },
+    Add {
+        name: String,
+        /// s3://bucket, optionally with ?endpoint=...&region=...&path-style=true, or the
+        /// http(s):// address of a machine running `vekt serve`
+        url: String,
+        /// Endpoint of an S3-compatible store (MinIO, Ceph, R2), e.g. https://minio.internal:9000
+        #[arg(long)]
+        endpoint: Option<String>,
+        #[arg(long)]
+        region: Option<String>,
+        /// Address the bucket as <endpoint>/<bucket>, as most on-prem stores expect
+        #[arg(long)]
+        path_style: bool,
+        /// S3 storage class to upload blobs with, e.g. STANDARD_IA or GLACIER_IR
+        #[arg(long)]
+        storage_class: Option<String>,
+        /// Server-side encryption to request on every upload: AES256 or aws:kms
+        #[arg(long, value_parser = ["AES256", "aws:kms"])]
+        sse: Option<String>,
+        /// KMS key ID or ARN to encrypt uploads with; implies --sse aws:kms
+        #[arg(long, value_name = "KEY")]
+        sse_kms_key_id: Option<String>,
+        /// Tag to set on every uploaded object, e.g. project=llm (repeatable)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+        /// AWS profile to take this remote's credentials from
+        #[arg(long)]
+        profile: Option<String>,
+        /// Credentials file to read instead of ~/.aws/credentials
+        #[arg(long, value_name = "PATH")]
+        credentials_file: Option<PathBuf>,
+        /// Environment variable holding the access key ID, instead of AWS_ACCESS_KEY_ID
+        #[arg(long, value_name = "VAR")]
+        access_key_env: Option<String>,
+        /// Environment variable holding the secret key, instead of AWS_SECRET_ACCESS_KEY
+        #[arg(long, value_name = "VAR")]
+        secret_key_env: Option<String>,
+        /// Environment variable holding the token of an http(s):// remote, instead of VEKT_TOKEN
+        #[arg(long, value_name = "VAR")]
+        token_env: Option<String>,
+    },
     List,
-    Remove { name: String },
+    Remove {
+        #[arg(add = ArgValueCandidates::new(complete::remotes))]
+        name: String,
+    },
+}
+
+/// Parses a `--tag` such as `project=llm`
+fn parse_tag(tag: &str) -> Result<(String, String), String> {
+    tag.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("'{}' is not KEY=VALUE", tag))
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+async fn main() {
+    // Answers the shell's completion requests (`COMPLETE=<shell> vekt ...`) and exits
+    CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        // --help and --version also arrive here and keep exiting with 0
+        if !e.use_stderr() {
+            e.exit();
+        }
+        let _ = e.print();
+        std::process::exit(exit_code::USAGE);
+    });
+
+    init_logging(cli.verbose, cli.quiet, &cli.log_format);
+    if let Some(wait) = cli.lock_wait {
+        LockFile::set_wait(wait);
+    }
+    if is_cancellable(&cli.command) {
+        watch_for_cancel(cli.timeout);
+    }
+
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", e);
+        let code = e
+            .downcast_ref::<VektError>()
+            .map_or(exit_code::FAILURE, VektError::exit_code);
+        std::process::exit(code);
+    }
+}
+
+/// Sends vekt's log events to stderr, leaving stdout to command output.
+/// `VEKT_LOG` takes a filter such as `vekt_core=trace,reqwest=debug` and overrides
+/// the level picked by -v and -q.
+fn init_logging(verbose: u8, quiet: bool, format: &str) {
+    let level = match (quiet, verbose) {
+        (true, _) => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_env("VEKT_LOG")
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,vekt={level},vekt_core={level}")));
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    if format == "json" {
+        logger.json().init();
+    } else {
+        logger.without_time().with_target(false).init();
+    }
+}
+
+/// Cancelled by Ctrl-C or `--timeout`; long operations stop at the next tensor or
+/// blob, clean up and return [`VektError::Cancelled`], releasing the lock on the way
+static CANCEL: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
 
+/// Commands that watch [`CANCEL`]. Others keep the default Ctrl-C behaviour.
+fn is_cancellable(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Add { .. }
+            | Commands::Restore { .. }
+            | Commands::Push { .. }
+            | Commands::Pull { .. }
+    )
+}
+
+fn watch_for_cancel(timeout: Option<Duration>) {
+    if let Some(timeout) = timeout {
+        cancel::cancel_after(&CANCEL, timeout);
+    }
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\nCancelling... (press Ctrl-C again to exit immediately)");
+            CANCEL.cancel();
+            let _ = tokio::signal::ctrl_c().await;
+            std::process::exit(exit_code::CANCELLED);
+        }
+    });
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     // Check if repository is initialized for all commands except Init
-    if !matches!(cli.command, Commands::Init) && find_vekt_root().is_none() {
+    if !matches!(
+        cli.command,
+        Commands::Init | Commands::Completion { .. } | Commands::Config { .. }
+    ) && find_vekt_root().is_none()
+    {
         eprintln!("Error: Not a vekt repository (or any parent up to mount point)");
         eprintln!("Run 'vekt init' first to initialize a repository.");
-        std::process::exit(1);
+        std::process::exit(exit_code::NOT_A_REPO);
+    }
+    if let Ok(config) = vekt_core::storage::VektConfig::load() {
+        vekt_core::utils::set_store_path(config.store_path());
+        vekt_core::direct_io::set_enabled(config.direct_io);
+        config.hash_algorithm.unwrap_or_default().set_current();
     }
 
     match &cli.command {
@@ -100,197 +1027,1786 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Use 'vekt add <model.safetensors>' to start tracking a model.");
         }
 
-        Commands::Add { path, compress } => {
+        Commands::Add {
+            path,
+            name,
+            compress,
+            rehash,
+            sidecars,
+            attach,
+            push,
+            ephemeral,
+            ttl,
+            stats,
+            check,
+            check_against,
+            dry_run,
+        } => {
             let _lock = LockFile::lock()?;
+            let stats = if *check {
+                Some(StatsPass {
+                    policy: Some(
+                        vekt_core::storage::VektConfig::load()?
+                            .checks
+                            .unwrap_or_default(),
+                    ),
+                    reference: check_against
+                        .as_deref()
+                        .map(refs::resolve_manifest)
+                        .transpose()?,
+                })
+            } else if *stats {
+                Some(StatsPass::default())
+            } else {
+                None
+            };
 
-            let path_str = path.to_str().unwrap();
+            let ttl = ephemeral.then(|| ttl.unwrap_or(vekt_core::ephemeral::DEFAULT_TTL));
+            let annotate = |manifest: &mut VektManifest| match ttl {
+                Some(ttl) => manifest.mark_ephemeral(ttl),
+                None => manifest.clear_ephemeral(),
+            };
 
-            print!("Adding file: {} ... ", path_str);
+            let mut extra_files = attach.clone();
+            if *sidecars {
+                let dir = match path.parent() {
+                    Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+                    _ => PathBuf::from("."),
+                };
+                extra_files.extend(vekt_core::attachments::discover_sidecars(&dir));
+            }
 
-            let file = SafetensorFile::open(path_str)?;
-            let manifest = file.process(true)?;
-            let manifest_json = serde_json::to_string_pretty(&manifest)?;
+            if *dry_run {
+                if path.as_os_str() == "-" {
+                    return Err("--dry-run can't read a model from stdin".into());
+                }
+                print_add_plan(&vekt_core::plan::plan_add(path, &extra_files)?);
+                return Ok(());
+            }
 
-            let output_path = path.with_extension("vekt.json");
-            let mut output_file = File::create(&output_path)?;
+            let options = AddOptions {
+                rehash: *rehash,
+                compress: *compress,
+            };
+            match push {
+                None => {
+                    add_model(
+                        path,
+                        name.as_deref(),
+                        options,
+                        stats.as_ref(),
+                        &extra_files,
+                        &|_| {},
+                        annotate,
+                    )?;
+                }
+                Some(remote) => {
+                    let client = Arc::new(connect_remote(remote).await?);
+                    println!("Pushing to remote '{}' while adding", remote);
 
-            output_file.write_all(manifest_json.as_bytes())?;
+                    // Blobs are queued for upload the moment they are stored, so hashing
+                    // and uploading overlap instead of running back to back
+                    let (sender, receiver) = futures::channel::mpsc::unbounded();
+                    let uploader = tokio::spawn({
+                        let client = Arc::clone(&client);
+                        async move { client.push_blob_stream(receiver).await }
+                    });
+                    let added = tokio::task::block_in_place(|| {
+                        add_model(
+                            path,
+                            name.as_deref(),
+                            options,
+                            stats.as_ref(),
+                            &extra_files,
+                            &|hash| {
+                                let _ = sender.unbounded_send(hash.to_string());
+                            },
+                            annotate,
+                        )
+                    });
+                    drop(sender);
+                    let uploaded = uploader.await?;
+                    let (manifest, manifest_path) = added?;
+                    uploaded?;
 
-            println!("Done! Manifest saved to {}", output_path.to_str().unwrap());
+                    // The manifest goes up last, once everything it references is there
+                    let name = manifest_path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    client.push_manifest(&manifest, &name).await?;
+                    println!("Successfully pushed {}", name);
+                }
+            }
 
             let store_loc = get_store_path();
             println!("Blobs stored in {}", store_loc.to_str().unwrap());
-
-            if *compress {
-                println!("Note: Compression is enabled but not yet fully integrated. Coming soon!");
+            if let Some(ttl) = ttl {
+                println!(
+                    "Ephemeral: gc may reclaim its blobs after {}s",
+                    ttl.as_secs()
+                );
             }
         }
 
-        Commands::Diff { old, new } => {
-            let old_file = File::open(old)?;
-            let new_file = File::open(new)?;
-
-            let old_manifest: vekt_core::storage::VektManifest =
-                serde_json::from_reader(std::io::BufReader::new(old_file))?;
-            let new_manifest: vekt_core::storage::VektManifest =
-                serde_json::from_reader(std::io::BufReader::new(new_file))?;
+        Commands::Diff { old, new, html } => {
+            let old_manifest = refs::resolve_manifest(old)?;
+            let new_manifest = refs::resolve_manifest(new)?;
 
-            old_manifest.print_diff(&new_manifest);
+            match html {
+                Some(out) => {
+                    let report =
+                        vekt_core::report::render_html_diff(&old_manifest, &new_manifest, old, new);
+                    std::fs::write(out, report)?;
+                    println!("Diff report written to {}", out.display());
+                }
+                None => old_manifest.print_diff(&new_manifest),
+            }
         }
 
-        Commands::Restore { path, layers } => {
-            let file = File::open(path).expect("Failed to open manifest file");
-            let reader = std::io::BufReader::new(file);
-            let manifest: vekt_core::storage::VektManifest =
-                serde_json::from_reader(reader).expect("Failed to parse manifest JSON");
-
-            let output_path = if let Some(file_name) = path.file_name() {
-                let name_str = file_name.to_string_lossy();
-
-                let stem = name_str.replace(".vekt.json", "").replace(".json", "");
-                path.with_file_name(format!("{}.safetensors", stem))
-            } else {
-                PathBuf::from("restored_model.safetensors")
-            };
-
-            println!("Restoring to {:?}...", output_path);
-            if let Some(l) = layers {
-                println!("Partial restore: filtering layers containing '{}'", l);
+        Commands::Stats {
+            manifest: spec,
+            non_finite,
+        } => {
+            let manifest = refs::resolve_manifest(spec)?;
+            let mut missing = 0;
+            println!(
+                "{:<48} {:>6} {:>12} {:>12} {:>12} {:>12} {:>6} {:>6}",
+                "tensor", "dtype", "min", "max", "mean", "norm", "nan", "inf"
+            );
+            for (name, tensor) in &manifest.tensors {
+                let Some(stats) = &tensor.stats else {
+                    missing += 1;
+                    continue;
+                };
+                if *non_finite && !stats.has_non_finite() {
+                    continue;
+                }
+                println!(
+                    "{:<48} {:>6} {:>12.4e} {:>12.4e} {:>12.4e} {:>12.4e} {:>6} {:>6}",
+                    name,
+                    tensor.dtype,
+                    stats.min,
+                    stats.max,
+                    stats.mean,
+                    stats.norm,
+                    stats.nan_count,
+                    stats.inf_count
+                );
             }
-
-            match manifest.restore(&output_path, layers.as_deref()) {
-                Ok(_) => println!("Restoration complete!"),
-                Err(e) => eprintln!("Error: {}", e),
+            let poisoned = manifest
+                .tensors
+                .values()
+                .filter(|t| t.stats.is_some_and(|s| s.has_non_finite()))
+                .count();
+            if poisoned > 0 {
+                println!("{} tensors contain NaN or infinite values", poisoned);
+            }
+            if missing > 0 {
+                println!(
+                    "{} tensors have no stats; re-add with --stats to record them (float tensors only)",
+                    missing
+                );
             }
         }
 
-        Commands::Pull { remote } => {
-            let _lock = LockFile::lock()?;
-            let config = vekt_core::storage::VektConfig::load()?;
-            if let Some(url) = config.remotes.get(remote) {
-                println!("Pulling from remote '{}' at URL '{}'", remote, url);
-
-                let client = RemoteClient::new(url)?;
-
-                // Validate access before attempting operations
-                println!("Validating S3 bucket access...");
-                if let Err(e) = client.validate_access().await {
-                    eprintln!("Failed to validate S3 access: {}", e);
-                    return Err(e.into());
-                }
-
-                let paths = std::fs::read_dir(".")?;
-
-                for entry in paths {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str())
-                        && name.ends_with(".vekt.json")
-                    {
-                        println!("Processing manifest: {}", name);
-                        match client.pull(name).await {
-                            Ok(manifest) => {
-                                // Update local manifest file
-                                let json = serde_json::to_string_pretty(&manifest)?;
-                                let mut f = File::create(&path)?;
-                                f.write_all(json.as_bytes())?;
-                                println!("Successfully updated {}", name);
-                            }
-                            Err(e) => eprintln!("Failed to pull {}: {}", name, e),
-                        }
+        Commands::Restore {
+            path,
+            layers,
+            include_regex,
+            layers_range,
+            index_range,
+            exclude,
+            exclude_regex,
+            apply_adapter,
+            side_by_side,
+            output,
+            verify,
+            exact,
+            from,
+            cache,
+        } => {
+            let mut filter = TensorFilter::layers(layers.as_deref());
+            for pattern in include_regex {
+                filter = filter.include_regex(pattern)?;
+            }
+            for spec in layers_range {
+                for range in parse_ranges(spec)? {
+                    filter = filter.layer_range(range);
+                }
+            }
+            for spec in index_range {
+                for range in parse_ranges(spec)? {
+                    filter = filter.index_range(range);
+                }
+            }
+            for terms in exclude {
+                filter = filter.exclude(terms);
+            }
+            for pattern in exclude_regex {
+                filter = filter.exclude_regex(pattern)?;
+            }
+            if let Some(remote) = from {
+                let _lock = if *cache {
+                    Some(LockFile::lock()?)
+                } else {
+                    None
+                };
+                restore_from_remote(remote, path, output.as_deref(), &filter, *cache).await?;
+                return Ok(());
+            }
+            if output.as_deref() == Some(Path::new("-")) {
+                let manifest = restore_manifest(path, apply_adapter.as_deref(), *side_by_side)?;
+                let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+                let streamed = if *exact {
+                    manifest.restore_exact_to_writer(&mut stdout).map(|_| ())
+                } else {
+                    sparse_filter(&manifest, &filter).and_then(|filter| {
+                        manifest.restore_to_writer(&mut stdout, &filter, verify_policy(*verify)?)
+                    })
+                }
+                .and_then(|()| Ok(stdout.flush()?));
+                match streamed {
+                    // The reader went away, e.g. `| head`; nothing left to report
+                    Err(VektError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+                        return Ok(());
                     }
+                    result => result?,
                 }
-            } else {
-                println!("Remote '{}' not found", remote);
+                if !manifest.attachments.is_empty() {
+                    eprintln!(
+                        "Skipped {} attachments; restore to a file to write them",
+                        manifest.attachments.len()
+                    );
+                }
+                return Ok(());
             }
+            restore_model(
+                path,
+                output.as_deref(),
+                &filter,
+                apply_adapter.as_deref(),
+                *side_by_side,
+                *verify,
+                *exact,
+            )?;
         }
-        Commands::Push { remote } => {
-            let _lock = LockFile::lock()?;
-            let config = vekt_core::storage::VektConfig::load()?;
-            if let Some(url) = config.remotes.get(remote) {
-                println!("Pushing to remote '{}' at URL '{}'", remote, url);
-
-                let client = RemoteClient::new(url)?;
 
-                // Validate access before attempting operations
-                println!("Validating S3 bucket access...");
-                if let Err(e) = client.validate_access().await {
-                    eprintln!("Failed to validate S3 access: {}", e);
-                    return Err(e.into());
+        Commands::Tag {
+            name,
+            manifest,
+            delete,
+            force,
+        } => match (name, manifest) {
+            (None, _) => {
+                for (tag, hash) in refs::list_tags()? {
+                    println!("{} -> {}", tag, &hash[..8.min(hash.len())]);
                 }
+            }
+            (Some(name), _) if *delete => {
+                let _lock = LockFile::lock()?;
+                refs::delete_tag(name)?;
+                println!("Deleted tag '{}'", name);
+            }
+            (Some(name), Some(spec)) => {
+                let _lock = LockFile::lock()?;
+                let manifest = refs::resolve_manifest(spec)?;
+                let hash = refs::create_tag(name, &manifest, *force)?;
+                println!("Tagged {} as '{}' ({})", spec, name, &hash[..8]);
+            }
+            (Some(name), None) => {
+                let hash = refs::read_tag(name)?;
+                println!("{} -> {}", name, hash);
+            }
+        },
 
-                let paths = std::fs::read_dir(".")?;
+        Commands::Pin { manifest: None } => {
+            for pin in pins::list_pins()? {
+                println!("{} ({})", pin.spec, &pin.hash[..8.min(pin.hash.len())]);
+            }
+        }
 
-                for entry in paths {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str())
-                        && name.ends_with(".vekt.json")
-                    {
-                        println!("Pushing manifest: {}", name);
+        Commands::Pin {
+            manifest: Some(spec),
+        } => {
+            let _lock = LockFile::lock()?;
+            let pin = pins::pin(spec)?;
+            println!("Pinned {} ({})", spec, &pin.hash[..8]);
+        }
 
-                        // Load manifest
-                        let f = File::open(&path)?;
-                        let reader = std::io::BufReader::new(f);
-                        let manifest: vekt_core::storage::VektManifest =
-                            serde_json::from_reader(reader)?;
+        Commands::Unpin { manifest: spec } => {
+            let _lock = LockFile::lock()?;
+            let removed = pins::unpin(spec)?;
+            println!("Unpinned {} ({} pins removed)", spec, removed);
+        }
 
-                        match client.push(&manifest, name).await {
-                            Ok(_) => println!("Successfully pushed {}", name),
-                            Err(e) => eprintln!("Failed to push {}: {}", name, e),
-                        }
-                    }
-                }
+        Commands::Sparse { disable: true, .. } => {
+            if SparseSpec::remove()? {
+                println!("Sparse spec removed; pull fetches every tensor again");
             } else {
-                println!("Remote '{}' not found", remote);
+                println!("Not sparse");
             }
         }
-        Commands::Status => {
-            let config = vekt_core::storage::VektConfig::load()?;
-            println!("vekt Configuration Status:");
-            println!("Remotes:");
-            for (name, url) in &config.remotes {
-                println!("  {} -> {}", name, url);
+
+        Commands::Sparse { patterns, .. } if patterns.is_empty() => match SparseSpec::load()? {
+            Some(spec) => {
+                for pattern in spec.patterns() {
+                    println!("{}", pattern);
+                }
+            }
+            None => println!("Not sparse"),
+        },
+
+        Commands::Sparse { patterns, .. } => {
+            let _lock = LockFile::lock()?;
+            SparseSpec::save(patterns)?;
+            println!(
+                "Pull and restore now only fetch tensors matching {} patterns",
+                patterns.len()
+            );
+        }
+
+        Commands::Rm {
+            manifest: spec,
+            cached,
+        } => {
+            let _lock = LockFile::lock()?;
+            let removal = tombstones::remove(spec, *cached)?;
+            if let Some(model) = &removal.model {
+                println!("Removed {}", model.display());
+            }
+            println!(
+                "Removed {} ({} blobs left for `vekt gc --removed`)",
+                spec,
+                removal.tombstone.blobs.len()
+            );
+        }
+
+        Commands::PatchResize {
+            manifest: spec,
+            tensor,
+            shape,
+            init,
+            output,
+        } => {
+            let _lock = LockFile::lock()?;
+            let output = match output {
+                Some(out) => out.clone(),
+                None if Path::new(spec).is_file() => PathBuf::from(spec),
+                None => {
+                    eprintln!("Error: --output is required when patching a tag or branch");
+                    std::process::exit(1);
+                }
+            };
+            let manifest = refs::resolve_manifest(spec)?;
+            let new_shape = resize::parse_shape(shape)?;
+            let init: resize::ResizeInit = init.parse()?;
+            let patched = manifest.patch_resize(tensor, &new_shape, &init)?;
+
+            if let Some(change) = vekt_core::diff::ShapeChange::between(
+                tensor,
+                &manifest.tensors[tensor.as_str()].shape,
+                &new_shape,
+            ) {
+                println!("{}: {}", tensor, change.describe());
+            }
+            patched.save(&output)?;
+            println!("Patched manifest written to {}", output.display());
+        }
+
+        Commands::Remap {
+            manifest: spec,
+            rules,
+            output,
+        } => {
+            let _lock = LockFile::lock()?;
+            let output = match output {
+                Some(out) => out.clone(),
+                None if Path::new(spec).is_file() => PathBuf::from(spec),
+                None => {
+                    eprintln!("Error: --output is required when remapping a tag or branch");
+                    std::process::exit(1);
+                }
+            };
+            let manifest = refs::resolve_manifest(spec)?;
+            let rules = vekt_core::remap::RenameRules::load(rules)?;
+            let (remapped, renamed) = manifest.remap(&rules)?;
+            for rename in &renamed {
+                println!("  > {} -> {}", rename.old, rename.new);
+            }
+            remapped.save(&output)?;
+            println!(
+                "Renamed {} tensors; remapped manifest written to {}",
+                renamed.len(),
+                output.display()
+            );
+        }
+
+        Commands::Quantize {
+            manifest: spec,
+            scheme,
+            output,
+        } => {
+            let _lock = LockFile::lock()?;
+            let scheme: QuantScheme = scheme.parse()?;
+            let output = output.clone().unwrap_or_else(|| {
+                let stem = match Path::new(spec).file_name() {
+                    Some(name) if Path::new(spec).is_file() => Path::new(spec)
+                        .with_file_name(name.to_string_lossy().trim_end_matches(".vekt.json")),
+                    _ => PathBuf::from(spec.replace('/', "_")),
+                };
+                PathBuf::from(format!("{}.{}.vekt.json", stem.display(), scheme))
+            });
+            let manifest = refs::resolve_manifest(spec)?;
+            let (quantized, summary) = manifest.quantize(scheme)?;
+            quantized.save(&output)?;
+            println!(
+                "Quantized {} tensors to {} ({} -> {}), kept {} as they were",
+                summary.quantized.len(),
+                scheme,
+                vekt_core::usage::format_bytes(summary.bytes_before),
+                vekt_core::usage::format_bytes(summary.bytes_after),
+                summary.kept.len()
+            );
+            println!("Quantized manifest written to {}", output.display());
+        }
+
+        Commands::Merge {
+            models,
+            method,
+            weights,
+            t,
+            output,
+        } => {
+            let _lock = LockFile::lock()?;
+            let weights = weights.as_deref().map(merge::parse_weights).transpose()?;
+            let method = merge::MergeMethod::parse(method, weights, *t)?;
+            let manifests = models
+                .iter()
+                .map(|spec| refs::resolve_manifest(spec))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            println!("Merging {} models ({})...", models.len(), method.describe());
+            let (merged, report) = merge::merge_manifests(&manifests, &method)?;
+            merged.save(output)?;
+            println!(
+                "Merged {} tensors, reused {} identical, copied {} from {}",
+                report.merged.len(),
+                report.shared.len(),
+                report.copied.len(),
+                models[0]
+            );
+            println!("Merged manifest written to {}", output.display());
+        }
+
+        Commands::MergeManifests {
+            base,
+            ours,
+            theirs,
+            output,
+            favor,
+        } => {
+            let _lock = LockFile::lock()?;
+            let output = match output {
+                Some(output) => output.clone(),
+                None if Path::new(ours).is_file() => PathBuf::from(ours),
+                None => {
+                    return Err(format!("'{}' is not a manifest file; pass --output", ours).into());
+                }
+            };
+            let favor = favor.as_deref().map(str::parse).transpose()?;
+            let merge = three_way::merge_three_way(
+                &refs::resolve_manifest(base)?,
+                &refs::resolve_manifest(ours)?,
+                &refs::resolve_manifest(theirs)?,
+                favor,
+            )?;
+
+            println!(
+                "Merged {} tensors changed in {} and {} changed in {}",
+                merge.from_ours.len(),
+                ours,
+                merge.from_theirs.len(),
+                theirs
+            );
+            for conflict in &merge.conflicts {
+                println!("CONFLICT {}", conflict);
             }
+            if !merge.conflicts.is_empty() {
+                match favor {
+                    Some(side) => println!(
+                        "Resolved {} conflicts with {}'s version",
+                        merge.conflicts.len(),
+                        if side == three_way::Favor::Ours {
+                            ours
+                        } else {
+                            theirs
+                        }
+                    ),
+                    None => {
+                        return Err(VektError::ConflictDetected(format!(
+                            "{} conflicting entries; resolve them or pass --favor ours|theirs",
+                            merge.conflicts.len()
+                        ))
+                        .into());
+                    }
+                }
+            }
+            merge.manifest.save(&output)?;
+            println!("Merged manifest written to {}", output.display());
         }
 
-        Commands::Gc => {
+        Commands::Commit { message, files } => {
+            let _lock = LockFile::lock()?;
+            let hash = commits::commit(files, message)?;
+            let branch = match refs::read_head()? {
+                refs::Head::Branch(name) => name,
+                refs::Head::Detached(_) => "detached HEAD".to_string(),
+            };
+            println!("[{} {}] {}", branch, commits::short_hash(&hash), message);
+        }
+
+        Commands::Branch {
+            name,
+            start,
+            delete,
+        } => match name {
+            None => {
+                let head = refs::read_head()?;
+                for (branch, hash) in refs::list_branches()? {
+                    let marker = if head == refs::Head::Branch(branch.clone()) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!("{} {} {}", marker, branch, commits::short_hash(&hash));
+                }
+            }
+            Some(name) if *delete => {
+                let _lock = LockFile::lock()?;
+                if refs::read_head()? == refs::Head::Branch(name.clone()) {
+                    eprintln!("Error: Cannot delete the checked-out branch '{}'", name);
+                    std::process::exit(1);
+                }
+                refs::delete_branch(name)?;
+                println!("Deleted branch '{}'", name);
+            }
+            Some(name) => {
+                let _lock = LockFile::lock()?;
+                let hash = commits::create_branch(name, start.as_deref())?;
+                println!(
+                    "Created branch '{}' at {}",
+                    name,
+                    commits::short_hash(&hash)
+                );
+            }
+        },
+
+        Commands::Switch {
+            branch,
+            create,
+            force,
+        } => {
             let _lock = LockFile::lock()?;
+            if *create {
+                match refs::head_commit()? {
+                    Some(_) => {
+                        commits::create_branch(branch, None)?;
+                    }
+                    // Nothing committed yet: just move the unborn HEAD
+                    None => {
+                        refs::write_head(&refs::Head::Branch(branch.clone()))?;
+                        println!("Switched to a new branch '{}'", branch);
+                        return Ok(());
+                    }
+                }
+            }
+            let summary = commits::switch_branch(branch, *force)?;
+            for path in &summary.written {
+                println!("  updated {}", path);
+            }
+            for path in &summary.removed {
+                println!("  removed {}", path);
+            }
+            println!("Switched to branch '{}'", branch);
+            if !summary.written.is_empty() {
+                println!("Run 'vekt restore <manifest>' to materialize weights.");
+            }
+        }
+        Commands::Checkout { spec, into, force } => {
+            let _lock = LockFile::lock()?;
+            let summary = checkout::checkout(spec, into, *force)?;
+            let mut linked = 0;
+            for (file, how) in &summary.files {
+                match how {
+                    Materialized::Unchanged => continue,
+                    Materialized::Reflinked | Materialized::Hardlinked => linked += 1,
+                    Materialized::Written => {}
+                }
+                println!("  {:?} {}", how, file);
+            }
+            for file in &summary.removed {
+                println!("  removed {}", file);
+            }
+            println!(
+                "Checked out {} into {} ({} files, {} linked from the store)",
+                spec,
+                summary.checkout.dir.display(),
+                summary.files.len(),
+                linked
+            );
+        }
+
+        Commands::Log { rev, graph, all } => {
+            let tips = if *all {
+                commits::all_tips()?
+            } else {
+                vec![commits::resolve_commit(rev.as_deref().unwrap_or("HEAD"))?]
+            };
+            let decorations = commits::branch_decorations()?;
+            print!("{}", commits::render_log(&tips, *graph, &decorations)?);
+        }
+
+        Commands::Attach {
+            manifest: manifest_path,
+            files,
+            name,
+        } => {
+            let _lock = LockFile::lock()?;
+            if name.is_some() && files.len() > 1 {
+                eprintln!("Error: --name can only be used when attaching a single file");
+                std::process::exit(1);
+            }
+
+            let mut manifest = VektManifest::load(manifest_path)?;
+            for file in files {
+                let attached = manifest.attach_file(file, name.as_deref())?;
+                println!("Attached {} as '{}'", file.display(), attached);
+            }
+            manifest.save(manifest_path)?;
+        }
+
+        Commands::Detach {
+            manifest: manifest_path,
+            name,
+        } => {
+            let _lock = LockFile::lock()?;
+            let mut manifest = VektManifest::load(manifest_path)?;
+            if manifest.detach(name).is_some() {
+                manifest.save(manifest_path)?;
+                println!("Detached '{}'", name);
+            } else {
+                println!("Attachment '{}' not found", name);
+            }
+        }
+
+        Commands::ImportLfs { repo, output, url } => {
+            let _lock = LockFile::lock()?;
+            let importer = Importer::new(PointerKind::Lfs, repo).with_url(url.clone());
+            import_pointers(&importer, repo, output).await?;
+        }
+
+        Commands::ImportDvc { repo, output, url } => {
+            let _lock = LockFile::lock()?;
+            let importer = Importer::new(PointerKind::Dvc, repo).with_url(url.clone());
+            import_pointers(&importer, repo, output).await?;
+        }
+
+        Commands::Pull {
+            remote,
+            output,
+            dry_run,
+        } if remote.starts_with(HUB_SCHEME) => {
+            if *dry_run {
+                return Err("--dry-run isn't supported for Hugging Face Hub pulls".into());
+            }
+            let _lock = LockFile::lock()?;
+            pull_from_hub(remote, output).await?;
+        }
+
+        Commands::Pull {
+            remote,
+            dry_run: true,
+            ..
+        } => {
+            let client = connect_remote(remote).await?;
+            let manifests = pull_targets(&client).await?;
+            let plan = vekt_core::plan::plan_download(&manifests.iter().collect::<Vec<_>>())?;
+            print_transfer_plan("download", &plan);
+        }
+        Commands::Pull { remote, .. } => {
+            let _lock = LockFile::lock()?;
+            pull_remote(remote).await?;
+        }
+        Commands::Push {
+            remote,
+            refs: args,
+            all_remotes,
+            since,
+            since_remote,
+            dry_run: true,
+            ..
+        } => {
+            let (remotes, specs) = push_remotes(remote.as_deref(), args, *all_remotes)?;
+            let baseline = PushBaseline::from_args(since.as_deref(), *since_remote)?;
+            let (manifest_paths, tags) = push_targets(&specs)?;
+            let mut manifests = Vec::new();
+            for path in manifest_paths {
+                manifests.push(VektManifest::load(&path)?);
+            }
+            for (_, hash) in tags {
+                manifests.push(refs::load_manifest_object(&hash)?);
+            }
+            let manifests: Vec<_> = manifests.iter().collect();
+            for remote in &remotes {
+                let client = connect_remote(remote).await?;
+                if remotes.len() > 1 {
+                    println!("{}:", remote);
+                }
+                match &baseline {
+                    Some(baseline) => {
+                        let known = baseline.known_blobs(&client).await?;
+                        let plan = vekt_core::plan::plan_delta(&manifests, &known);
+                        print_delta_plan(&plan, manifests.len());
+                    }
+                    None => print_transfer_plan("upload", &client.plan_upload(&manifests).await?),
+                }
+            }
+        }
+        Commands::Push {
+            remote,
+            refs: args,
+            all_remotes,
+            since,
+            since_remote,
+            force,
+            ..
+        } => {
+            let (remotes, specs) = push_remotes(remote.as_deref(), args, *all_remotes)?;
+            let baseline = PushBaseline::from_args(since.as_deref(), *since_remote)?;
+            let _lock = LockFile::lock()?;
+            match remotes.as_slice() {
+                [remote] => {
+                    push_remote(remote, &specs, baseline.as_ref(), *force).await?;
+                }
+                remotes => push_mirrored(remotes, &specs, baseline.as_ref(), *force).await?,
+            }
+        }
+        Commands::Lock {
+            manifest: None,
+            remote,
+        } => {
+            let client = connect_remote(remote).await?;
+            let now = vekt_core::ephemeral::now_secs();
+            for lock in client.list_locks().await? {
+                println!(
+                    "{} locked by {} ({} ago)",
+                    lock.manifest,
+                    lock.owner,
+                    format_age(now.saturating_sub(lock.locked_at))
+                );
+            }
+        }
+        Commands::Lock {
+            manifest: Some(manifest),
+            remote,
+        } => {
+            let name = manifest_name(manifest)?;
+            let client = connect_remote(remote).await?;
+            let lock = client
+                .lock_manifest(&name, &vekt_core::remote::lock_owner())
+                .await?;
+            println!("Locked {} on '{}' as {}", name, remote, lock.owner);
+        }
+        Commands::Unlock {
+            manifest,
+            remote,
+            force,
+        } => {
+            let name = manifest_name(manifest)?;
+            let client = connect_remote(remote).await?;
+            client
+                .unlock_manifest(&name, &vekt_core::remote::lock_owner(), *force)
+                .await?;
+            println!("Unlocked {} on '{}'", name, remote);
+        }
+        Commands::Status => {
+            let config = vekt_core::storage::VektConfig::load()?;
+            println!("vekt Configuration Status:");
+            println!("Remotes:");
+            for (name, url) in &config.remotes {
+                println!("  {} -> {}", name, url);
+            }
+            let checkouts = checkout::list_checkouts()?;
+            if !checkouts.is_empty() {
+                println!("Checkouts:");
+            }
+            for checkout in &checkouts {
+                let commit = checkout
+                    .commit
+                    .as_deref()
+                    .map(|hash| format!(" ({})", commits::short_hash(hash)))
+                    .unwrap_or_default();
+                let modified = checkout.modified();
+                let state = if modified.is_empty() {
+                    String::new()
+                } else {
+                    format!(", {} files modified", modified.len())
+                };
+                println!(
+                    "  {} -> {}{}{}",
+                    checkout.dir.display(),
+                    checkout.spec,
+                    commit,
+                    state
+                );
+            }
+        }
+
+        Commands::Gc {
+            removed,
+            dry_run: true,
+            full,
+        } => {
+            let _lock = LockFile::lock()?;
+            let root = find_vekt_root().unwrap_or_else(|| PathBuf::from("."));
+            let plan = if *removed {
+                vekt_core::gc::plan_gc_removed(&root)?
+            } else if *full {
+                vekt_core::gc::plan_gc_full(&root)?
+            } else {
+                vekt_core::gc::plan_gc(&root)?
+            };
+            for (path, size) in &plan.delete {
+                println!(
+                    "Would delete {} ({})",
+                    path.display(),
+                    vekt_core::usage::format_bytes(*size)
+                );
+            }
+            for path in &plan.expired {
+                println!("Would remove expired manifest {}", path.display());
+            }
+            println!(
+                "Dry run: would delete {} blob files ({}), keep {}, remove {} expired manifests",
+                plan.delete.len(),
+                vekt_core::usage::format_bytes(plan.bytes()),
+                plan.kept,
+                plan.expired.len()
+            );
+            if plan.interrupted > 0 {
+                println!(
+                    "Would clean up after {} interrupted add(s)",
+                    plan.interrupted
+                );
+            }
+            if !plan.tombstones.is_empty() {
+                println!("Would settle {} removed manifest(s)", plan.tombstones.len());
+            }
+        }
+        Commands::Gc { removed, full, .. } => {
+            // run_gc takes the repository lock itself
             println!(
                 "Running Garbage Collection on {}...",
                 get_store_path().display()
             );
 
-            let root = find_vekt_root().unwrap_or_else(|| PathBuf::from("."));
-            match vekt_core::gc::run_gc(&root) {
-                Ok(stats) => {
-                    println!(
-                        "GC Complete. Deleted: {}, Kept: {}",
-                        stats.deleted, stats.kept
-                    );
+            let root = find_vekt_root().unwrap_or_else(|| PathBuf::from("."));
+            let stats = if *removed {
+                vekt_core::gc::run_gc_removed(&root)?
+            } else if *full {
+                vekt_core::gc::run_gc_full(&root)?
+            } else {
+                vekt_core::gc::run_gc(&root)?
+            };
+            println!(
+                "GC Complete. Deleted: {}, Kept: {}, Expired manifests: {}",
+                stats.deleted, stats.kept, stats.expired
+            );
+            if stats.recovered > 0 {
+                println!("Cleaned up after {} interrupted add(s)", stats.recovered);
+            }
+            if stats.settled > 0 {
+                println!("Settled {} removed manifest(s)", stats.settled);
+            }
+        }
+
+        Commands::Cache {
+            action: CacheCommand::Evict { max_size },
+        } => {
+            let max_size = match max_size {
+                Some(size) => parse_size(size)?,
+                None => vekt_core::storage::VektConfig::load()?
+                    .max_store_size()?
+                    .ok_or("No size given; pass --max-size or set max_store_size in the config")?,
+            };
+            let on_remote = blobs_on_remotes().await;
+            let _lock = LockFile::lock()?;
+            let eviction = cache::evict(max_size, &HashSet::new(), &on_remote)?;
+            println!(
+                "Evicted {} blobs, freeing {}. Store is now {}",
+                eviction.evicted,
+                vekt_core::usage::format_bytes(eviction.freed),
+                vekt_core::usage::format_bytes(eviction.remaining)
+            );
+            if eviction.remaining > max_size {
+                println!(
+                    "The rest is used by pinned or tagged models, or by manifests whose blobs no remote has, and can't be evicted"
+                );
+            }
+        }
+
+        Commands::Index {
+            action: IndexCommand::Rebuild,
+        } => {
+            let index = vekt_core::blob_index::BlobIndex::rebuild()?;
+            println!(
+                "Indexed {} blobs ({})",
+                index.blob_count(),
+                vekt_core::usage::format_bytes(index.total_size())
+            );
+        }
+
+        Commands::Watch {
+            dir,
+            pattern,
+            push,
+            settle,
+            recursive,
+        } => {
+            let client = match push {
+                Some(remote) => {
+                    let config = vekt_core::storage::VektConfig::load()?;
+                    let Some(url) = config.remotes.get(remote) else {
+                        println!("Remote '{}' not found", remote);
+                        return Ok(());
+                    };
+                    Some(
+                        RemoteClient::with_credentials(url, &config.remote_credentials(remote))?
+                            .with_concurrency(config.concurrency()),
+                    )
+                }
+                None => None,
+            };
+
+            let options = WatchOptions {
+                pattern: pattern.clone(),
+                settle: Duration::from_secs(*settle),
+                recursive: *recursive,
+            };
+            println!(
+                "Watching {} for '{}' (Ctrl-C to stop)...",
+                dir.display(),
+                pattern
+            );
+
+            let handle = tokio::runtime::Handle::current();
+            tokio::task::block_in_place(|| {
+                watch_checkpoints(dir, &options, |event| {
+                    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+                        let _lock = LockFile::lock()?;
+                        let (manifest, manifest_path) = add_model(
+                            &event.path,
+                            None,
+                            AddOptions::default(),
+                            None,
+                            &[],
+                            &|_| {},
+                            |manifest| {
+                                // A step carried over from the previous snapshot would be wrong
+                                match event.step {
+                                    Some(step) => manifest
+                                        .metadata
+                                        .insert("step".to_string(), step.to_string()),
+                                    None => manifest.metadata.remove("step"),
+                                };
+                            },
+                        )?;
+                        if let Some(step) = event.step {
+                            println!("Snapshot recorded at step {}", step);
+                        }
+
+                        if let Some(client) = &client {
+                            let name = manifest_path
+                                .file_name()
+                                .map(|n| n.to_string_lossy().into_owned())
+                                .unwrap_or_default();
+                            handle.block_on(client.push(&manifest, &name))?;
+                        }
+                        Ok(())
+                    })();
+
+                    // A half-written or broken checkpoint must not stop the watch
+                    if let Err(e) = result {
+                        eprintln!("Failed to snapshot {}: {}", event.path.display(), e);
+                    }
+                    Ok(())
+                })
+            })?;
+        }
+
+        Commands::InstallHooks { remote, force } => {
+            let config = vekt_core::storage::VektConfig::load()?;
+            if !config.remotes.contains_key(remote) {
+                return Err(VektError::RemoteNotFound(remote.clone()).into());
+            }
+            let vekt_root = find_vekt_root()
+                .ok_or(VektError::RepoNotFound)?
+                .canonicalize()?;
+            let hooks_dir = hooks::hooks_dir(&std::env::current_dir()?)?;
+            for hook in hooks::install_hooks(&hooks_dir, &vekt_root, remote, *force)? {
+                println!("Installed {}", hook.display());
+            }
+            println!(
+                "Commits now re-add tracked models; git push pushes their weights to '{}'",
+                remote
+            );
+        }
+        Commands::FilterClean => {
+            let _lock = LockFile::lock()?;
+            filter::clean(std::io::stdin().lock(), std::io::stdout().lock())?;
+        }
+        Commands::FilterSmudge => {
+            filter::smudge(std::io::stdin().lock(), std::io::stdout().lock())?;
+        }
+        Commands::Serve { addr } => {
+            let token = std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty());
+            let server = BlobServer::new(token.clone())?;
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            println!(
+                "Serving {} on http://{}{}",
+                find_vekt_root().unwrap_or_default().display(),
+                listener.local_addr()?,
+                if token.is_some() {
+                    " (token required)"
+                } else {
+                    ""
+                }
+            );
+            server.run(listener).await?;
+        }
+        #[cfg(unix)]
+        Commands::Daemon { socket } => {
+            let path = socket.clone().unwrap_or_else(daemon::socket_path);
+            if path.exists() {
+                if tokio::net::UnixStream::connect(&path).await.is_ok() {
+                    return Err(
+                        format!("A daemon is already listening on {}", path.display()).into(),
+                    );
+                }
+                // Left behind by a daemon that didn't shut down cleanly
+                std::fs::remove_file(&path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            println!("vekt daemon listening on {}", path.display());
+            let served = daemon::serve(listener, handle_daemon_request).await;
+            let _ = std::fs::remove_file(&path);
+            served?;
+        }
+        Commands::Repair { manifest, remote } => {
+            let _lock = LockFile::lock()?;
+            let config = vekt_core::storage::VektConfig::load()?;
+            let Some(url) = config.remotes.get(remote) else {
+                println!("Remote '{}' not found", remote);
+                return Ok(());
+            };
+            let manifest = refs::resolve_manifest(manifest)?;
+            let client = RemoteClient::with_credentials(url, &config.remote_credentials(remote))?
+                .with_concurrency(config.concurrency());
+
+            let repairs = client.repair(&manifest).await?;
+            let mut repaired = 0;
+            for (hash, repair) in &repairs {
+                match repair {
+                    BlobRepair::Intact => continue,
+                    BlobRepair::Ranges { ranges, bytes } => println!(
+                        "Repaired {}: {} corrupt range(s), {} bytes downloaded",
+                        &hash[..8],
+                        ranges.len(),
+                        bytes
+                    ),
+                    BlobRepair::Whole { bytes } => {
+                        println!("Repaired {}: {} bytes downloaded", &hash[..8], bytes)
+                    }
+                }
+                repaired += 1;
+            }
+            println!(
+                "Checked {} blobs, repaired {} from '{}'",
+                repairs.len(),
+                repaired,
+                remote
+            );
+        }
+        Commands::Verify { manifest, file } => {
+            let resolved = refs::resolve_manifest(manifest)?;
+            let (checksum, what) = match file {
+                Some(file) => (resolved.verify_file(file)?, file.display().to_string()),
+                None => (
+                    resolved.verify_checksum()?,
+                    format!("'{}' still rebuilds", manifest),
+                ),
+            };
+            println!(
+                "OK: {} the file that was added ({} bytes, blake3 {})",
+                if file.is_some() {
+                    format!("{} is", what)
+                } else {
+                    what
+                },
+                checksum.size,
+                checksum.hash
+            );
+        }
+        Commands::VerifyRemote {
+            remote,
+            sample,
+            seed,
+        } => {
+            let config = vekt_core::storage::VektConfig::load()?;
+            let Some(url) = config.remotes.get(remote) else {
+                println!("Remote '{}' not found", remote);
+                return Ok(());
+            };
+
+            let fraction = vekt_core::sampling::parse_sample_fraction(sample)?;
+            let seed = seed.unwrap_or_else(rand::random);
+            println!(
+                "Verifying {:.2}% sample of remote '{}' at URL '{}'",
+                fraction * 100.0,
+                remote,
+                url
+            );
+
+            let client = RemoteClient::with_credentials(url, &config.remote_credentials(remote))?
+                .with_concurrency(config.concurrency());
+            let report = client.verify_sample(fraction, seed).await?;
+            report.print_summary();
+
+            if !report.failed.is_empty() {
+                std::process::exit(exit_code::CORRUPTION);
+            }
+        }
+
+        Commands::Adapter { action } => match action {
+            AdapterCommand::Register { adapter, base } => {
+                let _lock = LockFile::lock()?;
+                let mut manifest = VektManifest::load(adapter)?;
+                let base_manifest = refs::resolve_manifest(base)?;
+                let hash = manifest.register_adapter_base(&base_manifest)?;
+                manifest.save(adapter)?;
+                println!(
+                    "Registered {} as base of {} ({})",
+                    base,
+                    adapter.display(),
+                    &hash[..8]
+                );
+            }
+            AdapterCommand::Restore {
+                adapter,
+                output,
+                side_by_side,
+            } => {
+                let adapter_manifest = VektManifest::load(adapter)?;
+                let Some(base) = adapter_manifest.adapter_base()? else {
+                    eprintln!(
+                        "Error: {} has no registered base. Run 'vekt adapter register' first.",
+                        adapter.display()
+                    );
+                    std::process::exit(1);
+                };
+                let manifest = layer_adapter(&base, &adapter_manifest, *side_by_side)?;
+                manifest.restore(output, None)?;
+                println!("Restored base + adapter to {}", output.display());
+            }
+        },
+
+        Commands::Release { action } => match action {
+            ReleaseCommand::Create {
+                name,
+                manifests,
+                remote,
+                force,
+            } => {
+                let _lock = LockFile::lock()?;
+                let client = connect_remote(remote).await?;
+                let release = Release::create(name, manifests)?;
+                client.push_release(&release, *force).await?;
+                release.save()?;
+            }
+            ReleaseCommand::List { remote: None } => {
+                for release in releases::list_releases()? {
+                    println!("{} ({} manifests)", release.name, release.manifests.len());
+                }
+            }
+            ReleaseCommand::List {
+                remote: Some(remote),
+            } => {
+                let client = connect_remote(remote).await?;
+                for name in client.list_releases().await? {
+                    println!("{}", name);
+                }
+            }
+            ReleaseCommand::Show { name } => {
+                let release = Release::load(name)?;
+                println!("Release {}", release.name);
+                for (manifest, hash) in &release.manifests {
+                    println!("  {} {}", &hash[..8.min(hash.len())], manifest);
+                }
+            }
+            ReleaseCommand::Pull {
+                name,
+                remote,
+                output,
+            } => {
+                let _lock = LockFile::lock()?;
+                let client = connect_remote(remote).await?;
+                let release = client.pull_release(name).await?;
+                std::fs::create_dir_all(output)?;
+                for path in release.checkout(output)? {
+                    println!("Wrote {}", path.display());
+                }
+            }
+        },
+
+        Commands::Backup { action } => match action {
+            BackupCommand::Create { output, blobs } => {
+                let info = backup::create_backup(output, *blobs)?;
+                println!(
+                    "Backed up {} metadata files and {} blobs to {}",
+                    info.metadata_files,
+                    info.blobs,
+                    output.display()
+                );
+            }
+            BackupCommand::Restore {
+                archive,
+                remote,
+                force,
+            } => {
+                let summary = backup::restore_backup(archive, *force)?;
+                println!(
+                    "Restored {} metadata files and {} blobs",
+                    summary.metadata_files, summary.blobs
+                );
+                if summary.missing_blobs == 0 {
+                    return Ok(());
+                }
+                let Some(remote) = remote else {
+                    println!(
+                        "{} blobs referenced by {} manifests are not in the local store. \
+                         Rerun with --remote <name> to fetch them.",
+                        summary.missing_blobs,
+                        summary.incomplete.len()
+                    );
+                    return Ok(());
+                };
+                let _lock = LockFile::lock()?;
+                let client = connect_remote(remote).await?;
+                for (_, manifest) in &summary.incomplete {
+                    client.download_blobs(manifest).await?;
+                }
+                println!(
+                    "Fetched {} missing blobs from '{}'",
+                    summary.missing_blobs, remote
+                );
+            }
+        },
+
+        Commands::Bundle { action } => match action {
+            BundleCommand::Create { output, manifests } => {
+                let manifests = manifests
+                    .iter()
+                    .map(|spec| Ok((bundle_manifest_name(spec), refs::resolve_manifest(spec)?)))
+                    .collect::<Result<Vec<_>, VektError>>()?;
+                let info = bundle::create_bundle(output, &manifests)?;
+                println!(
+                    "Bundled {} manifests and {} blobs ({}) into {}",
+                    info.manifests.len(),
+                    info.blobs,
+                    vekt_core::usage::format_bytes(info.blob_bytes),
+                    output.display()
+                );
+            }
+            BundleCommand::Unpack {
+                bundle: archive,
+                output,
+                force,
+            } => {
+                let summary = bundle::unpack_bundle(archive, output, *force)?;
+                println!(
+                    "Added {} blobs ({} already present)",
+                    summary.blobs, summary.existing_blobs
+                );
+                for path in &summary.manifests {
+                    println!("Wrote {}", path.display());
+                }
+            }
+        },
+
+        Commands::Swap {
+            manifest,
+            target,
+            hook,
+            json,
+        } => {
+            let manifest = refs::resolve_manifest(manifest)?;
+            let result = swap::swap(&manifest, target, hook.as_deref())?;
+            if *json {
+                println!("{}", serde_json::to_string(&result)?);
+            } else {
+                match result.event {
+                    SwapEvent::Swapped => println!(
+                        "Swapped {} -> {}",
+                        target.join(swap::CURRENT_LINK).display(),
+                        result.version
+                    ),
+                    SwapEvent::Unchanged => println!("{} is already current", result.version),
+                    SwapEvent::RolledBack => {}
+                }
+            }
+            if let Some(error) = &result.error {
+                eprintln!(
+                    "Verification failed, kept {}: {}",
+                    result.previous.as_deref().unwrap_or("no version"),
+                    error
+                );
+                std::process::exit(exit_code::CORRUPTION);
+            }
+        }
+
+        Commands::Completion { shell } => {
+            let shells = Shells::builtins();
+            let completer = shells.completer(shell).ok_or_else(|| {
+                format!(
+                    "Unknown shell '{}'. Use one of: {}",
+                    shell,
+                    shells.names().collect::<Vec<_>>().join(", ")
+                )
+            })?;
+            let exe = std::env::current_exe()?;
+            completer.write_registration(
+                "COMPLETE",
+                "vekt",
+                "vekt",
+                &exe.to_string_lossy(),
+                &mut std::io::stdout(),
+            )?;
+        }
+
+        Commands::Du {
+            top,
+            all,
+            reclaimable,
+            json,
+        } => {
+            let root = commits::repo_root();
+            let reclaimable_now =
+                RefCounts::open(&root)?.reclaimable(&vekt_core::blob_index::BlobIndex::open()?)?;
+            if *reclaimable {
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&reclaimable_now)?);
+                } else {
+                    println!(
+                        "Reclaimable by gc: {} in {} blobs",
+                        vekt_core::usage::format_bytes(reclaimable_now.bytes),
+                        reclaimable_now.blobs
+                    );
+                }
+                return Ok(());
+            }
+            let mut manifests = Vec::new();
+            for path in vekt_core::utils::find_manifest_files(&root)? {
+                let label = path
+                    .strip_prefix(&root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                manifests.push((label, VektManifest::load(&path)?));
+            }
+            if *all {
+                for (name, hash) in refs::list_tags()? {
+                    manifests.push((format!("tag:{}", name), refs::load_manifest_object(&hash)?));
+                }
+                for (name, hash) in refs::list_branches()? {
+                    for (path, object) in commits::load_commit(&hash)?.manifests {
+                        let label = format!("branch:{}:{}", name, path);
+                        manifests.push((label, refs::load_manifest_object(&object)?));
+                    }
+                }
+                for release in releases::list_releases()? {
+                    for (name, _, manifest) in release.load_manifests()? {
+                        manifests.push((format!("release:{}:{}", release.name, name), manifest));
+                    }
+                }
+            }
+
+            let mut report = vekt_core::usage::compute_usage(&manifests, *top)?;
+            report.reclaimable = Some(reclaimable_now);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                report.print_summary();
+            }
+        }
+        Commands::DedupReport { manifests, json } => {
+            let mut loaded = Vec::new();
+            for spec in manifests {
+                loaded.push((spec.clone(), refs::resolve_manifest(spec)?));
+            }
+            let report = vekt_core::usage::compute_dedup(&loaded)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                report.print_summary();
+            }
+        }
+
+        Commands::Follow {
+            pattern,
+            remote,
+            tag,
+            output,
+            remove,
+        } => {
+            let mut config = FollowConfig::load()?;
+            match pattern {
+                None => {
+                    for sub in &config.subscriptions {
+                        let kind = if sub.kind == FollowKind::Tag {
+                            "tag"
+                        } else {
+                            "release"
+                        };
+                        println!(
+                            "{} {} from '{}' -> {} (current: {})",
+                            kind,
+                            sub.pattern,
+                            sub.remote,
+                            sub.output.display(),
+                            sub.current.as_deref().unwrap_or("none")
+                        );
+                    }
+                }
+                Some(pattern) if *remove => {
+                    if config.unfollow(pattern) == 0 {
+                        eprintln!("Error: Not following '{}'", pattern);
+                        std::process::exit(1);
+                    }
+                    config.save()?;
+                    println!("Stopped following '{}'", pattern);
+                }
+                Some(pattern) => {
+                    let kind = if *tag {
+                        FollowKind::Tag
+                    } else {
+                        FollowKind::Release
+                    };
+                    config.follow(Subscription {
+                        remote: remote.clone(),
+                        kind,
+                        pattern: pattern.clone(),
+                        output: output.clone(),
+                        current: None,
+                        current_hash: None,
+                    });
+                    config.save()?;
+                    println!(
+                        "Following '{}' on '{}'. Run 'vekt update' to fetch it.",
+                        pattern, remote
+                    );
+                }
+            }
+        }
+
+        Commands::Update { json } => {
+            let _lock = LockFile::lock()?;
+            let mut config = FollowConfig::load()?;
+            let mut failed = false;
+            for sub in config.subscriptions.iter_mut() {
+                let outcome = match connect_remote(&sub.remote).await {
+                    Ok(client) => follow::update(sub, &client)
+                        .await
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                match outcome {
+                    Ok(result) if *json => println!("{}", serde_json::to_string(&result)?),
+                    Ok(result) => {
+                        let status = match result.status {
+                            UpdateStatus::Updated => "updated",
+                            UpdateStatus::Unchanged => "unchanged",
+                        };
+                        println!("{}: {} ({})", result.pattern, status, result.version);
+                    }
+                    Err(e) => {
+                        failed = true;
+                        if *json {
+                            let error = serde_json::json!({
+                                "remote": sub.remote,
+                                "pattern": sub.pattern,
+                                "status": "error",
+                                "error": e,
+                            });
+                            println!("{}", error);
+                        } else {
+                            eprintln!("{}: error: {}", sub.pattern, e);
+                        }
+                    }
+                }
+            }
+            config.save()?;
+            if failed {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Meta { action } => match action {
+            MetaCommand::Set {
+                manifest: manifest_path,
+                key,
+                value,
+                meta_file,
+            } => {
+                let _lock = LockFile::lock()?;
+                let value = match meta_file {
+                    Some(file) => std::fs::read_to_string(file)?,
+                    None => value.clone().unwrap_or_default(),
+                };
+                let mut manifest = VektManifest::load(manifest_path)?;
+                meta::set(&mut manifest, key, &value)?;
+                manifest.save(manifest_path)?;
+                println!("Set '{}' on {}", key, manifest_path.display());
+            }
+            MetaCommand::Get {
+                manifest: spec,
+                key,
+                json,
+            } => {
+                let manifest = refs::resolve_manifest(spec)?;
+                match key {
+                    Some(key) => match manifest.metadata.get(key) {
+                        Some(value) => println!("{}", value),
+                        None => {
+                            eprintln!("Error: '{}' is not set on {}", key, spec);
+                            std::process::exit(1);
+                        }
+                    },
+                    None if *json => {
+                        println!("{}", serde_json::to_string_pretty(&manifest.metadata)?)
+                    }
+                    None => {
+                        for (key, value) in &manifest.metadata {
+                            println!("{}={}", key, value);
+                        }
+                    }
+                }
+            }
+            MetaCommand::Unset {
+                manifest: manifest_path,
+                key,
+            } => {
+                let _lock = LockFile::lock()?;
+                let mut manifest = VektManifest::load(manifest_path)?;
+                if meta::unset(&mut manifest, key)?.is_none() {
+                    eprintln!("Error: '{}' is not set on {}", key, manifest_path.display());
+                    std::process::exit(1);
+                }
+                manifest.save(manifest_path)?;
+                println!("Removed '{}' from {}", key, manifest_path.display());
+            }
+        },
+
+        Commands::List {
+            filters,
+            state,
+            remote,
+            sort,
+            reverse,
+            json,
+        } => {
+            let root = commits::repo_root();
+            let mut listed = listing::list_local(&root)?;
+            if let Some(remote) = remote {
+                let client = connect_remote(remote).await?;
+                let pushed = client.list_manifests().await?;
+                let tags = client.list_tags().await?;
+                listed.extend(listing::remote_only(&listed, pushed, tags)?);
+            }
+            let filter = ListFilter {
+                metadata: filters.clone(),
+                states: state
+                    .iter()
+                    .filter_map(|s| ManifestState::parse(s))
+                    .collect(),
+            };
+            listed.retain(|entry| filter.matches(entry));
+            listing::sort(
+                &mut listed,
+                SortKey::parse(sort).unwrap_or_default(),
+                *reverse,
+            );
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&listed)?);
+                return Ok(());
+            }
+            let now = vekt_core::ephemeral::now_secs();
+            for entry in &listed {
+                let age = match entry.modified {
+                    Some(modified) => format_age(now.saturating_sub(modified)),
+                    None => "-".to_string(),
+                };
+                let mut line = format!(
+                    "{:<9} {:>10} {:>6} {:>4}  {}",
+                    entry.state.as_str(),
+                    vekt_core::usage::format_bytes(entry.size),
+                    entry.tensors,
+                    age,
+                    entry.name
+                );
+                if !entry.tags.is_empty() {
+                    line.push_str(&format!(" ({})", entry.tags.join(", ")));
                 }
-                Err(e) => eprintln!("GC Failed: {}", e),
+                let shown: Vec<String> = filters
+                    .iter()
+                    .filter_map(|filter| {
+                        let value = entry.metadata.get(&filter.key)?;
+                        Some(format!("{}={}", filter.key, value))
+                    })
+                    .collect();
+                if !shown.is_empty() {
+                    line.push_str(&format!("\t{}", shown.join(" ")));
+                }
+                println!("{}", line);
             }
         }
 
+        Commands::Config { action } => match action {
+            ConfigCommand::Get { key } => match config::get(key)? {
+                Some(value) => println!("{}", config_value(&value)),
+                None => std::process::exit(1),
+            },
+            ConfigCommand::Set { key, value, global } => {
+                config::set(config_scope(*global), key, config::parse_value(value))?;
+            }
+            ConfigCommand::Unset { key, global } => {
+                if !config::unset(config_scope(*global), key)? {
+                    eprintln!("Error: '{}' is not set", key);
+                    std::process::exit(1);
+                }
+            }
+            ConfigCommand::List { show_origin, json } => {
+                let entries = config::list()?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
+                    return Ok(());
+                }
+                for entry in &entries {
+                    if *show_origin {
+                        print!("{}\t", entry.scope);
+                    }
+                    println!("{}={}", entry.key, config_value(&entry.value));
+                }
+            }
+        },
+
         // Remote management commands
         Commands::Remote { action } => {
-            let mut config = vekt_core::storage::VektConfig::load()?;
+            // Changes go to the repository config only
+            let mut config = vekt_core::storage::VektConfig::load_repo()?;
 
             match action {
-                RemoteCommand::Add { name, url } => {
+                RemoteCommand::Add {
+                    name,
+                    url,
+                    endpoint,
+                    region,
+                    path_style,
+                    storage_class,
+                    sse,
+                    sse_kms_key_id,
+                    tags,
+                    profile,
+                    credentials_file,
+                    access_key_env,
+                    secret_key_env,
+                    token_env,
+                } => {
+                    let url = if is_http_url(url) {
+                        // Another machine running `vekt serve`
+                        if endpoint.is_some()
+                            || region.is_some()
+                            || *path_style
+                            || storage_class.is_some()
+                            || sse.is_some()
+                            || sse_kms_key_id.is_some()
+                            || !tags.is_empty()
+                        {
+                            return Err("--endpoint, --region, --path-style, --storage-class, \
+                                 --sse, --sse-kms-key-id and --tag only apply to s3:// remotes"
+                                .into());
+                        }
+                        url.trim_end_matches('/').to_string()
+                    } else {
+                        let mut location = parse_s3_url(url)?;
+                        if let Some(endpoint) = endpoint {
+                            location.endpoint = Some(endpoint.trim_end_matches('/').to_string());
+                        }
+                        if let Some(region) = region {
+                            location.region = Some(region.clone());
+                        }
+                        location.path_style |= *path_style;
+                        if let Some(storage_class) = storage_class {
+                            location.storage_class = Some(storage_class.clone());
+                        }
+                        location.encryption = match (sse.as_deref(), sse_kms_key_id) {
+                            (Some("AES256"), Some(_)) => {
+                                return Err("--sse-kms-key-id needs --sse aws:kms".into());
+                            }
+                            (_, Some(key)) => Some(ServerSideEncryption::Kms(Some(key.clone()))),
+                            (Some("AES256"), None) => Some(ServerSideEncryption::Aes256),
+                            (Some(_), None) => Some(ServerSideEncryption::Kms(None)),
+                            (None, None) => location.encryption,
+                        };
+                        location.tags.extend(tags.iter().cloned());
+                        // Round-trip so flag values get the same validation as the URL
+                        parse_s3_url(&location.to_string())?.to_string()
+                    };
                     config.add_remote(name.clone(), url.clone());
+                    let credentials = RemoteCredentials {
+                        profile: profile.clone(),
+                        credentials_file: credentials_file.clone(),
+                        access_key_env: access_key_env.clone(),
+                        secret_key_env: secret_key_env.clone(),
+                        token_env: token_env.clone(),
+                    };
+                    if credentials.is_default() {
+                        config.credentials.remove(name);
+                    } else {
+                        config.credentials.insert(name.clone(), credentials);
+                    }
                     config.save()?;
                     println!("Added remote '{}' with URL '{}'", name, url);
                 }
                 RemoteCommand::List => {
+                    let config = vekt_core::storage::VektConfig::load()?;
                     println!("Configured remotes:");
                     for (name, url) in &config.remotes {
-                        println!("{} -> {}", name, url);
+                        match config.credentials.get(name) {
+                            Some(credentials) => {
+                                println!("{} -> {} (credentials: {})", name, url, credentials)
+                            }
+                            None => println!("{} -> {}", name, url),
+                        }
+                    }
+                    for (group, members) in &config.remote_groups {
+                        println!("{} -> group of {}", group, members.join(", "));
                     }
                 }
                 RemoteCommand::Remove { name } => {
                     if config.remotes.remove(name).is_some() {
+                        config.credentials.remove(name);
                         config.save()?;
                         println!("Removed remote '{}'", name);
                     } else {
@@ -302,3 +2818,1125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+fn config_scope(global: bool) -> ConfigScope {
+    if global {
+        ConfigScope::User
+    } else {
+        ConfigScope::Repo
+    }
+}
+
+/// Strings print bare, anything else as JSON
+fn config_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Combines a base model with a LoRA adapter, merged or side by side
+fn layer_adapter(
+    base: &VektManifest,
+    adapter: &VektManifest,
+    side_by_side: bool,
+) -> Result<VektManifest, Box<dyn std::error::Error>> {
+    if side_by_side {
+        return Ok(base.with_adapter_side_by_side(adapter)?);
+    }
+    let scale = vekt_core::adapter::lora_scale(adapter)?;
+    println!("Merging LoRA adapter (scale {})...", scale);
+    Ok(base.apply_lora(adapter, scale)?)
+}
+
+/// The manifest `path` names, with `apply_adapter` layered on it
+fn restore_manifest(
+    path: &Path,
+    apply_adapter: Option<&str>,
+    side_by_side: bool,
+) -> Result<VektManifest, Box<dyn std::error::Error>> {
+    let manifest = refs::resolve_manifest(&path.to_string_lossy())?;
+    match apply_adapter {
+        Some(spec) => layer_adapter(&manifest, &refs::resolve_manifest(spec)?, side_by_side),
+        None => Ok(manifest),
+    }
+}
+
+/// `filter` narrowed to the tensors `.vekt/sparse` wants, warning when that leaves
+/// some of `manifest`'s out of the restore
+fn sparse_filter(
+    manifest: &VektManifest,
+    filter: &TensorFilter,
+) -> Result<TensorFilter, VektError> {
+    let excluded = manifest.sparse_excluded()?;
+    match SparseSpec::load()? {
+        Some(spec) if excluded > 0 => {
+            eprintln!(
+                "warning: .vekt/sparse leaves {} of {} tensors out of the restored file; \
+                 'vekt sparse --disable' restores every tensor",
+                excluded,
+                manifest.tensors.len()
+            );
+            Ok(filter.clone().sparse(spec))
+        }
+        _ => Ok(filter.clone()),
+    }
+}
+
+/// `verify` if given, else the configured policy
+fn verify_policy(verify: Option<VerifyPolicy>) -> Result<VerifyPolicy, VektError> {
+    match verify {
+        Some(policy) => Ok(policy),
+        None => Ok(vekt_core::storage::VektConfig::load()?
+            .verify
+            .unwrap_or_default()),
+    }
+}
+
+/// Restores a manifest file, tag or branch to `output`, else next to it (or into the
+/// current directory for refs), optionally with an adapter applied. Returns the
+/// restored file.
+fn restore_model(
+    path: &Path,
+    output: Option<&Path>,
+    filter: &TensorFilter,
+    apply_adapter: Option<&str>,
+    side_by_side: bool,
+    verify: Option<VerifyPolicy>,
+    exact: bool,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let policy = verify_policy(verify)?;
+    let manifest = restore_manifest(path, apply_adapter, side_by_side)?;
+
+    // Models ingested from other formats restore to their original format
+    let extension = manifest
+        .layout
+        .as_ref()
+        .map_or("safetensors", |layout| layout.extension());
+    let output_path = if let Some(output) = output {
+        output.to_path_buf()
+    } else if !path.is_file() {
+        // Restoring a tag: name the output after it in the current directory
+        let tag = path.to_string_lossy().replace('/', "_");
+        PathBuf::from(format!("{}.{}", tag, extension))
+    } else if let Some(file_name) = path.file_name() {
+        let name_str = file_name.to_string_lossy();
+
+        let stem = name_str.replace(".vekt.json", "").replace(".json", "");
+        path.with_file_name(format!("{}.{}", stem, extension))
+    } else {
+        PathBuf::from(format!("restored_model.{}", extension))
+    };
+
+    let filter = if exact {
+        filter.clone()
+    } else {
+        sparse_filter(&manifest, filter)?
+    };
+    println!("Restoring to {:?}...", output_path);
+    if !filter.is_empty() {
+        let kept = manifest
+            .tensors
+            .iter()
+            .filter(|(name, tensor)| filter.matches(name, tensor.index));
+        println!(
+            "Partial restore: {} of {} tensors",
+            kept.count(),
+            manifest.tensors.len()
+        );
+    }
+
+    if exact {
+        let checksum = manifest.restore_exact(&output_path)?;
+        println!(
+            "Checksum verified: {} bytes, blake3 {}",
+            checksum.size, checksum.hash
+        );
+    } else {
+        manifest.restore_cancellable(&output_path, &filter, policy, &CANCEL)?;
+    }
+    println!("Restoration complete!");
+    cache::touch(manifest.referenced_hashes())?;
+
+    if !manifest.attachments.is_empty() {
+        let dir = output_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let count = manifest.restore_attachments(&dir)?;
+        println!("Restored {} attachments to {}", count, dir.display());
+    }
+    Ok(output_path)
+}
+
+/// Restores a manifest or tag straight from a remote, downloading only the blobs that
+/// aren't in the local store and keeping them only with `cache`
+async fn restore_from_remote(
+    remote: &str,
+    path: &Path,
+    output: Option<&Path>,
+    filter: &TensorFilter,
+    cache: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect_remote(remote).await?;
+    let name = path.strip_prefix("./").unwrap_or(path).to_string_lossy();
+    let manifest = client.fetch_manifest(&name).await?;
+
+    if output == Some(Path::new("-")) {
+        let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+        let streamed = client
+            .restore_thin_to_writer(&manifest, &mut stdout, filter, cache)
+            .await
+            .and_then(|restore| Ok(stdout.flush().map(|_| restore)?));
+        match streamed {
+            Err(VektError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => {
+                result?;
+            }
+        }
+        return Ok(());
+    }
+
+    let output_path = match output {
+        Some(output) => output.to_path_buf(),
+        None => {
+            let stem = name.replace(".vekt.json", "").replace(".json", "");
+            PathBuf::from(format!("{}.safetensors", stem.replace('/', "_")))
+        }
+    };
+    println!("Restoring from '{}' to {:?}...", remote, output_path);
+    let restore = client
+        .restore_thin(&manifest, &output_path, filter, cache)
+        .await?;
+    println!(
+        "Restoration complete! {} blobs downloaded, {} read from the local store ({})",
+        restore.downloaded,
+        restore.local,
+        vekt_core::usage::format_bytes(restore.bytes)
+    );
+    if cache {
+        enforce_store_size(manifest.referenced_hashes())?;
+    }
+
+    if !manifest.attachments.is_empty() {
+        let dir = output_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        let count = client
+            .restore_thin_attachments(&manifest, &dir, cache)
+            .await?;
+        println!("Restored {} attachments to {}", count, dir.display());
+    }
+    Ok(())
+}
+
+/// Pulls every manifest in the current directory and every tag from a configured
+/// remote. Individual failures don't stop the others; the first one is returned.
+async fn pull_remote(remote: &str) -> Result<TransferDigest, Box<dyn std::error::Error>> {
+    let config = vekt_core::storage::VektConfig::load()?;
+    let Some(url) = config.remotes.get(remote) else {
+        return Err(VektError::RemoteNotFound(remote.to_string()).into());
+    };
+    println!("Pulling from remote '{}' at URL '{}'", remote, url);
+
+    let client = RemoteClient::with_credentials(url, &config.remote_credentials(remote))?
+        .with_concurrency(config.concurrency())
+        .with_cancel(CANCEL.clone());
+
+    // Validate access before attempting operations
+    println!("Validating S3 bucket access...");
+    if let Err(e) = client.validate_access().await {
+        eprintln!("Failed to validate S3 access: {}", e);
+        return Err(e.into());
+    }
+
+    // Keep going past failed items, but report the first failure's exit code
+    let mut failure: Option<VektError> = None;
+    let mut transfer = TransferDigest::new();
+    let mut pulled = HashSet::new();
+    let paths = std::fs::read_dir(".")?;
+
+    for entry in paths {
+        if CANCEL.is_cancelled() {
+            break;
+        }
+        let entry = entry?;
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|n| n.to_str())
+            && name.ends_with(".vekt.json")
+        {
+            println!("Processing manifest: {}", name);
+            match client.pull(name).await {
+                Ok(manifest) => {
+                    // Update local manifest file
+                    manifest.save(&path)?;
+                    transfer.add_wanted(&manifest)?;
+                    pulled.extend(sparse::wanted_blobs(&manifest)?.into_iter().cloned());
+                    println!("Successfully updated {}", name);
+                }
+                Err(e) => {
+                    eprintln!("Failed to pull {}: {}", name, e);
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    for (tag, hash) in client.list_tags().await? {
+        if CANCEL.is_cancelled() {
+            break;
+        }
+        // Tags already up to date still count towards the checksum
+        if refs::read_tag(&tag).is_ok_and(|local| local == hash) {
+            transfer.add_wanted(&refs::load_manifest_object(&hash)?)?;
+            continue;
+        }
+        let result = match client.pull_tag(&tag, &hash).await {
+            Ok(manifest) => refs::write_tag(&tag, &hash, false).map(|_| manifest),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(manifest) => {
+                transfer.add_wanted(&manifest)?;
+                pulled.extend(sparse::wanted_blobs(&manifest)?.into_iter().cloned());
+                println!("Fetched tag {} -> {}", tag, &hash[..8.min(hash.len())])
+            }
+            Err(e) => {
+                eprintln!("Failed to pull tag {}: {}", tag, e);
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    record_transfer("pull", remote, &transfer, None)?;
+    enforce_store_size(&pulled)?;
+    cancel::check(&CANCEL, "pull")?;
+    if let Some(e) = failure {
+        return Err(e.into());
+    }
+    Ok(transfer)
+}
+
+/// Records that the `used` blobs were just used and, when `max_store_size` is
+/// configured, evicts least-recently-used blobs beyond it. The `used` blobs are kept.
+/// The caller holds the repository lock.
+fn enforce_store_size<'a>(
+    used: impl IntoIterator<Item = &'a String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let used: HashSet<String> = used.into_iter().cloned().collect();
+    cache::touch(&used)?;
+    let Some(max_size) = vekt_core::storage::VektConfig::load()?.max_store_size()? else {
+        return Ok(());
+    };
+    if cache::store_size()? <= max_size {
+        return Ok(());
+    }
+    let handle = tokio::runtime::Handle::current();
+    let on_remote = tokio::task::block_in_place(|| handle.block_on(blobs_on_remotes()));
+    let eviction = cache::evict(max_size, &used, &on_remote)?;
+    if eviction.evicted > 0 {
+        println!(
+            "Evicted {} least-recently-used blobs ({}) to stay under {}",
+            eviction.evicted,
+            vekt_core::usage::format_bytes(eviction.freed),
+            vekt_core::usage::format_bytes(max_size)
+        );
+    }
+    Ok(())
+}
+
+/// Blobs any configured remote has, so eviction can drop referenced blobs that can
+/// be pulled again. Remotes that can't be listed are skipped with a warning.
+async fn blobs_on_remotes() -> HashSet<String> {
+    let mut hashes = HashSet::new();
+    let Ok(config) = vekt_core::storage::VektConfig::load() else {
+        return hashes;
+    };
+    for (name, url) in &config.remotes {
+        let listed = match RemoteClient::with_credentials(url, &config.remote_credentials(name)) {
+            Ok(client) => client.blob_hashes().await,
+            Err(e) => Err(e),
+        };
+        match listed {
+            Ok(listed) => hashes.extend(listed),
+            Err(e) => eprintln!(
+                "warning: failed to list blobs on remote '{}': {}. Its blobs are kept locally.",
+                name, e
+            ),
+        }
+    }
+    hashes
+}
+
+/// Pushes manifests and tags (all of them when `specs` is empty) to a configured
+/// remote, only uploading blobs outside `baseline` if one is given. Individual
+/// failures don't stop the others; the first one is returned.
+async fn push_remote(
+    remote: &str,
+    specs: &[String],
+    baseline: Option<&PushBaseline>,
+    force: bool,
+) -> Result<TransferDigest, Box<dyn std::error::Error>> {
+    let config = vekt_core::storage::VektConfig::load()?;
+    let Some(url) = config.remotes.get(remote) else {
+        return Err(VektError::RemoteNotFound(remote.to_string()).into());
+    };
+    println!("Pushing to remote '{}' at URL '{}'", remote, url);
+
+    let client = RemoteClient::with_credentials(url, &config.remote_credentials(remote))?
+        .with_concurrency(config.concurrency())
+        .with_cancel(CANCEL.clone())
+        .with_force(force);
+
+    // Validate access before attempting operations
+    println!("Validating S3 bucket access...");
+    if let Err(e) = client.validate_access().await {
+        eprintln!("Failed to validate S3 access: {}", e);
+        return Err(e.into());
+    }
+
+    let mut delta = match baseline {
+        Some(baseline) => Some(DeltaPush {
+            known: baseline.known_blobs(&client).await?,
+            ..Default::default()
+        }),
+        None => None,
+    };
+
+    let (manifest_paths, tags) = push_targets(specs)?;
+    let mut failure: Option<VektError> = None;
+    let mut transfer = TransferDigest::new();
+    for path in manifest_paths {
+        if CANCEL.is_cancelled() {
+            break;
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        println!("Pushing manifest: {}", name);
+
+        let manifest = VektManifest::load(&path)?;
+
+        let pushed = match delta.as_mut() {
+            Some(delta) => delta.push_manifest(&client, &manifest, &name).await,
+            None => client.push(&manifest, &name).await,
+        };
+        match pushed {
+            Ok(_) => {
+                transfer.add(&manifest)?;
+                println!("Successfully pushed {}", name)
+            }
+            Err(e) => {
+                eprintln!("Failed to push {}: {}", name, e);
+                failure.get_or_insert(e);
+            }
+        }
+    }
+
+    for (tag, hash) in tags {
+        if CANCEL.is_cancelled() {
+            break;
+        }
+        let result = match refs::load_manifest_object(&hash) {
+            Ok(manifest) => match delta.as_mut() {
+                Some(delta) => match delta.push_blobs(&client, &manifest, &tag).await {
+                    Ok(()) => client.publish_tag(&tag, &hash, &manifest).await,
+                    Err(e) => Err(e),
+                },
+                None => client.push_tag(&tag, &hash, &manifest).await,
+            }
+            .map(|_| manifest),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(manifest) => transfer.add(&manifest)?,
+            Err(e) => {
+                eprintln!("Failed to push tag {}: {}", tag, e);
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(delta) = delta {
+        println!(
+            "Uploaded {} new blobs ({}); {} blobs already on the remote, saved {}",
+            delta.uploaded,
+            vekt_core::usage::format_bytes(delta.uploaded_bytes),
+            delta.reused,
+            vekt_core::usage::format_bytes(delta.saved_bytes)
+        );
+    }
+    record_transfer(
+        "push",
+        remote,
+        &transfer,
+        Some(client.upload_verification()),
+    )?;
+    cancel::check(&CANCEL, "push")?;
+    if let Some(e) = failure {
+        return Err(e.into());
+    }
+    Ok(transfer)
+}
+
+/// Remotes a push goes to, with the refs it pushes. With `all` every configured
+/// remote is a target and `remote`, if given, is the first ref.
+fn push_remotes(
+    remote: Option<&str>,
+    refs: &[String],
+    all: bool,
+) -> Result<(Vec<String>, Vec<String>), Box<dyn std::error::Error>> {
+    let config = vekt_core::storage::VektConfig::load()?;
+    if !all {
+        let remotes = config.resolve_remotes(remote.unwrap_or("origin"))?;
+        return Ok((remotes, refs.to_vec()));
+    }
+    let remotes = config.remote_names();
+    if remotes.is_empty() {
+        return Err("No remotes configured. Add one with 'vekt remote add'".into());
+    }
+    let specs = remote
+        .map(str::to_string)
+        .into_iter()
+        .chain(refs.iter().cloned());
+    Ok((remotes, specs.collect()))
+}
+
+/// Pushes to each of `remotes` in turn and reports how each went. A remote that
+/// fails doesn't stop the others; the first failure is returned once all were tried.
+async fn push_mirrored(
+    remotes: &[String],
+    specs: &[String],
+    baseline: Option<&PushBaseline>,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut outcomes = Vec::new();
+    for remote in remotes {
+        if CANCEL.is_cancelled() {
+            break;
+        }
+        outcomes.push((remote, push_remote(remote, specs, baseline, force).await));
+    }
+
+    println!("\nMirrored to {} remotes:", remotes.len());
+    let mut failure = None;
+    let mut failed = 0;
+    for (remote, outcome) in outcomes {
+        match outcome {
+            Ok(transfer) => println!(
+                "  {}: ok ({} manifests, {} blobs)",
+                remote,
+                transfer.manifest_count(),
+                transfer.blob_count()
+            ),
+            Err(e) => {
+                println!("  {}: failed: {}", remote, e);
+                failed += 1;
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    cancel::check(&CANCEL, "push")?;
+    if let Some(e) = failure {
+        eprintln!("Push failed on {} of {} remotes", failed, remotes.len());
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// What `push --since` takes a remote to hold already
+enum PushBaseline {
+    /// The blobs of a manifest pushed before, and the file name it was pushed as
+    Manifest {
+        spec: String,
+        manifest: Box<VektManifest>,
+        name: Option<String>,
+    },
+    /// Whatever one listing of the remote's blobs shows
+    Listing,
+}
+
+impl PushBaseline {
+    fn from_args(
+        since: Option<&str>,
+        since_remote: bool,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        Ok(match since {
+            Some(spec) => Some(PushBaseline::Manifest {
+                spec: spec.to_string(),
+                manifest: Box::new(refs::resolve_manifest(spec)?),
+                name: Path::new(spec)
+                    .is_file()
+                    .then(|| Path::new(spec).file_name())
+                    .flatten()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            }),
+            None if since_remote => Some(PushBaseline::Listing),
+            None => None,
+        })
+    }
+
+    /// Blobs the remote is known to hold. A baseline manifest the remote doesn't
+    /// have proves nothing, so the remote's listing is used instead.
+    async fn known_blobs(&self, client: &RemoteClient) -> Result<HashSet<String>, VektError> {
+        match self {
+            PushBaseline::Manifest {
+                spec,
+                manifest,
+                name,
+            } => {
+                if client.has_manifest(manifest, name.as_deref()).await? {
+                    return Ok(manifest.referenced_hashes().cloned().collect());
+                }
+                eprintln!(
+                    "warning: the remote doesn't have '{}'; comparing against its blob listing instead",
+                    spec
+                );
+                client.blob_hashes().await
+            }
+            PushBaseline::Listing => client.blob_hashes().await,
+        }
+    }
+}
+
+/// Running totals of a delta push. `known` grows with every manifest pushed, so
+/// blobs they share are uploaded and counted once.
+#[derive(Default)]
+struct DeltaPush {
+    known: HashSet<String>,
+    uploaded: usize,
+    uploaded_bytes: u64,
+    reused: usize,
+    saved_bytes: u64,
+}
+
+impl DeltaPush {
+    /// Pushes the manifest file `name`, checking it can be pushed before uploading
+    /// any of its blobs
+    async fn push_manifest(
+        &mut self,
+        client: &RemoteClient,
+        manifest: &VektManifest,
+        name: &str,
+    ) -> Result<(), VektError> {
+        let plan = client.push_since(manifest, name, &self.known).await?;
+        self.record(plan);
+        Ok(())
+    }
+
+    async fn push_blobs(
+        &mut self,
+        client: &RemoteClient,
+        manifest: &VektManifest,
+        name: &str,
+    ) -> Result<(), VektError> {
+        let plan = client.push_blobs_since(manifest, name, &self.known).await?;
+        self.record(plan);
+        Ok(())
+    }
+
+    fn record(&mut self, plan: vekt_core::plan::DeltaPlan) {
+        self.uploaded += plan.blobs.len();
+        self.uploaded_bytes += plan.bytes();
+        self.reused += plan.reused;
+        self.saved_bytes += plan.saved_bytes;
+        self.known
+            .extend(plan.blobs.into_iter().map(|blob| blob.hash));
+    }
+}
+
+/// Manifest files and (tag, manifest object hash) pairs to push
+type PushTargets = (Vec<PathBuf>, Vec<(String, String)>);
+
+/// Manifest files and tags a push of `specs` covers: all manifests here and all tags
+/// when `specs` is empty
+fn push_targets(specs: &[String]) -> Result<PushTargets, Box<dyn std::error::Error>> {
+    let mut manifest_paths = Vec::new();
+    let mut tags = Vec::new();
+    if specs.is_empty() {
+        for entry in std::fs::read_dir(".")? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with(".vekt.json"))
+            {
+                manifest_paths.push(path);
+            }
+        }
+        tags = refs::list_tags()?;
+    } else {
+        for spec in specs {
+            if Path::new(spec).is_file() {
+                manifest_paths.push(PathBuf::from(spec));
+            } else {
+                tags.push((spec.clone(), refs::read_tag(spec)?));
+            }
+        }
+    }
+    Ok((manifest_paths, tags))
+}
+
+/// Manifests a pull from `client` would fetch: the remote versions of the manifests
+/// here and every remote tag. Nothing is stored locally.
+async fn pull_targets(
+    client: &RemoteClient,
+) -> Result<Vec<VektManifest>, Box<dyn std::error::Error>> {
+    let mut manifests = Vec::new();
+    for entry in std::fs::read_dir(".")? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().and_then(|n| n.to_str())
+            && name.ends_with(".vekt.json")
+        {
+            match client.fetch_manifest(name).await {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => eprintln!("Failed to fetch {}: {}", name, e),
+            }
+        }
+    }
+    for (_, hash) in client.list_tags().await? {
+        manifests.push(client.peek_tag(&hash).await?);
+    }
+    Ok(manifests)
+}
+
+fn print_add_plan(plan: &vekt_core::plan::AddPlan) {
+    for blob in &plan.new_blobs {
+        println!(
+            "Would write {} ({})",
+            blob.hash,
+            vekt_core::usage::format_bytes(blob.size)
+        );
+    }
+    println!(
+        "Dry run: would hash {} of {} tensors ({}) and write {} new blobs ({}); {} already stored",
+        plan.hashed,
+        plan.tensors,
+        vekt_core::usage::format_bytes(plan.hashed_bytes),
+        plan.new_blobs.len(),
+        vekt_core::usage::format_bytes(plan.new_bytes()),
+        plan.existing
+    );
+}
+
+fn print_transfer_plan(verb: &str, plan: &vekt_core::plan::TransferPlan) {
+    for blob in &plan.blobs {
+        println!(
+            "Would {} {} ({})",
+            verb,
+            blob.hash,
+            vekt_core::usage::format_bytes(blob.size)
+        );
+    }
+    println!(
+        "Dry run: would {} {} blobs ({}) for {} manifests; {} already there",
+        verb,
+        plan.blobs.len(),
+        vekt_core::usage::format_bytes(plan.bytes()),
+        plan.manifests,
+        plan.present
+    );
+}
+
+fn print_delta_plan(plan: &vekt_core::plan::DeltaPlan, manifests: usize) {
+    for blob in &plan.blobs {
+        println!(
+            "Would upload {} ({})",
+            blob.hash,
+            vekt_core::usage::format_bytes(blob.size)
+        );
+    }
+    println!(
+        "Dry run: would upload {} blobs ({}) for {} manifests; {} already on the remote, saving {}",
+        plan.blobs.len(),
+        vekt_core::usage::format_bytes(plan.bytes()),
+        manifests,
+        plan.reused,
+        vekt_core::usage::format_bytes(plan.saved_bytes)
+    );
+}
+
+/// Runs one daemon request the way the matching command would, taking the repository
+/// lock for operations that change the store
+#[cfg(unix)]
+async fn handle_daemon_request(request: DaemonRequest) -> DaemonResponse {
+    let result: Result<serde_json::Value, Box<dyn std::error::Error>> = async {
+        Ok(match request {
+            DaemonRequest::Add { path, rehash } => {
+                let _lock = LockFile::lock()?;
+                // Hashing runs off the async workers so other clients keep being served
+                let (manifest, manifest_path) = tokio::task::block_in_place(|| {
+                    let options = AddOptions {
+                        rehash,
+                        ..Default::default()
+                    };
+                    add_model(&path, None, options, None, &[], &|_| {}, |m| {
+                        m.clear_ephemeral()
+                    })
+                })?;
+                serde_json::json!({
+                    "manifest": manifest_path,
+                    "tensors": manifest.tensors.len(),
+                    "total_size": manifest.total_size,
+                })
+            }
+            DaemonRequest::Restore { manifest, layers } => {
+                let output = tokio::task::block_in_place(|| {
+                    restore_model(
+                        Path::new(&manifest),
+                        None,
+                        &TensorFilter::layers(layers.as_deref()),
+                        None,
+                        false,
+                        None,
+                        false,
+                    )
+                })?;
+                serde_json::json!({ "output": output })
+            }
+            DaemonRequest::Push { remote, refs } => {
+                let _lock = LockFile::lock()?;
+                transfer_summary(&push_remote(&remote, &refs, None, false).await?)
+            }
+            DaemonRequest::Pull { remote } => {
+                let _lock = LockFile::lock()?;
+                transfer_summary(&pull_remote(&remote).await?)
+            }
+            DaemonRequest::Status => {
+                let config = vekt_core::storage::VektConfig::load()?;
+                serde_json::json!({ "remotes": config.remotes })
+            }
+            DaemonRequest::Ping | DaemonRequest::Shutdown => {
+                serde_json::json!({ "version": env!("CARGO_PKG_VERSION") })
+            }
+        })
+    }
+    .await;
+    match result {
+        Ok(value) => DaemonResponse::success(value),
+        Err(e) => {
+            let code = e
+                .downcast_ref::<VektError>()
+                .map_or(exit_code::FAILURE, VektError::exit_code);
+            DaemonResponse::failure(e, code)
+        }
+    }
+}
+
+/// File name a manifest spec is unpacked to from a bundle: the file's own name, or
+/// the ref with separators replaced, e.g. `main:model.vekt.json` -> `main_model.vekt.json`
+fn bundle_manifest_name(spec: &str) -> String {
+    let path = Path::new(spec);
+    if path.is_file()
+        && let Some(name) = path.file_name().map(|n| n.to_string_lossy())
+        && name.ends_with(".vekt.json")
+    {
+        return name.into_owned();
+    }
+    let stem = spec
+        .trim_end_matches(".vekt.json")
+        .replace(['/', '\\', ':'], "_")
+        .trim_start_matches('.')
+        .to_string();
+    format!("{}.vekt.json", stem)
+}
+
+#[cfg(unix)]
+fn transfer_summary(transfer: &TransferDigest) -> serde_json::Value {
+    serde_json::json!({
+        "checksum": transfer.digest(),
+        "manifests": transfer.manifest_count(),
+        "blobs": transfer.blob_count(),
+    })
+}
+
+/// Prints the end-to-end checksum of a push or pull and records it in the audit log,
+/// with how the remote confirmed a push's uploads
+fn record_transfer(
+    operation: &str,
+    remote: &str,
+    transfer: &TransferDigest,
+    uploads: Option<UploadVerification>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entry = AuditEntry::new(operation, remote, transfer);
+    entry.uploads = uploads;
+    println!(
+        "Checksum: {} ({} manifests, {} blobs)",
+        entry.digest,
+        transfer.manifest_count(),
+        entry.blobs
+    );
+    if let Some(uploads) = uploads.filter(|u| u.verified + u.unconfirmed > 0) {
+        println!(
+            "Uploads verified by the remote: {} of {}",
+            uploads.verified,
+            uploads.verified + uploads.unconfirmed
+        );
+    }
+    audit::record(&entry)?;
+    Ok(())
+}
+
+/// Name a manifest file is pushed under: its file name
+fn manifest_name(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("'{}' is not a manifest file", path.display()).into())
+}
+
+/// Rough age for listings, in the largest unit that fits
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Looks up a configured remote and checks that its bucket is reachable
+async fn connect_remote(name: &str) -> Result<RemoteClient, Box<dyn std::error::Error>> {
+    let config = vekt_core::storage::VektConfig::load()?;
+    let Some(url) = config.remotes.get(name) else {
+        return Err(format!("Remote '{}' not found", name).into());
+    };
+    let client = RemoteClient::with_credentials(url, &config.remote_credentials(name))?
+        .with_concurrency(config.concurrency())
+        .with_cancel(CANCEL.clone());
+    client.validate_access().await?;
+    Ok(client)
+}
+
+/// Re-encodes the manifest's blobs as the config's `storage_rules` ask, with zstd as
+/// the fallback `compression` when `compress`
+fn apply_storage_rules(
+    manifest: &VektManifest,
+    previous: Option<&VektManifest>,
+    compress: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = vekt_core::storage::VektConfig::load()?;
+    if compress {
+        config.compression = Some(vekt_core::encoding::BlobEncoding::Zstd);
+    }
+    let rules = config.storage_rules();
+    if !rules.is_empty() {
+        let rewritten = vekt_core::encoding::apply_storage_rules(manifest, &rules, previous)?;
+        if rewritten > 0 {
+            print!("re-encoded {} blobs ... ", rewritten);
+        }
+    }
+    Ok(())
+}
+
+/// Adds a safetensors file and writes its manifest next to it, keeping attachments and
+/// metadata from a previous manifest. `annotate` can adjust the manifest before it is saved.
+/// `on_blob` sees every blob the manifest references once it is in the store.
+/// Streams every safetensors file of a Hub model into the store and writes one
+/// manifest per file under `output`, with the repository's sidecars attached
+async fn pull_from_hub(url: &str, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = HubRepo::parse(url)?;
+    let client = HubClient::from_env()?;
+    let snapshot = client.resolve(&repo).await?;
+    println!(
+        "Pulling {} at {} ({})",
+        snapshot.repo,
+        repo.revision,
+        &snapshot.sha[..8.min(snapshot.sha.len())]
+    );
+
+    let files: Vec<String> = snapshot.safetensors().cloned().collect();
+    if files.is_empty() {
+        return Err(VektError::InvalidSafetensor(format!(
+            "{} has no .safetensors files",
+            snapshot.repo
+        ))
+        .into());
+    }
+    let mut sidecars = Vec::new();
+    for name in snapshot.sidecars() {
+        sidecars.push((name.clone(), client.download(&snapshot, name).await?));
+    }
+
+    for file in &files {
+        print!("Downloading {} ... ", file);
+        std::io::stdout().flush()?;
+        let mut manifest = client.pull_safetensors(&snapshot, file).await?;
+        for (name, data) in &sidecars {
+            manifest.attach_bytes(name, data)?;
+        }
+        let manifest_path = output.join(file).with_extension("vekt.json");
+        let previous = VektManifest::load(&manifest_path).ok();
+        apply_storage_rules(&manifest, previous.as_ref(), false)?;
+        if let Some(parent) = manifest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        manifest.save(&manifest_path)?;
+        println!(
+            "{} tensors, saved {}",
+            manifest.tensors.len(),
+            manifest_path.display()
+        );
+    }
+    if !sidecars.is_empty() {
+        println!("Attached {} sidecar files", sidecars.len());
+    }
+    Ok(())
+}
+
+/// Imports every safetensors file `importer` finds and writes its manifest under
+/// `output`, at the file's path in the repository
+async fn import_pointers(
+    importer: &Importer,
+    repo: &Path,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pointers = importer.pointers().await?;
+    let kind = importer.kind().name();
+    if pointers.is_empty() {
+        return Err(format!(
+            "No {} pointers to .safetensors files found in {}",
+            kind,
+            repo.display()
+        )
+        .into());
+    }
+    println!("Importing {} files tracked by {}", pointers.len(), kind);
+    for pointer in &pointers {
+        print!("Importing {} ... ", pointer.path);
+        std::io::stdout().flush()?;
+        // A file that fails verification leaves no blobs behind
+        let transaction = Transaction::begin("import")?;
+        let manifest = importer.import(pointer, &|_| {}).await?;
+        let manifest_path = output.join(&pointer.path).with_extension("vekt.json");
+        let previous = VektManifest::load(&manifest_path).ok();
+        apply_storage_rules(&manifest, previous.as_ref(), false)?;
+        if let Some(parent) = manifest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        manifest.save(&manifest_path)?;
+        refcount::note_saved(&manifest_path, &manifest);
+        transaction.commit()?;
+        println!(
+            "{} tensors, saved {}",
+            manifest.tensors.len(),
+            manifest_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Statistics `add --stats` records, and the checks `add --check` runs on them
+#[derive(Default)]
+struct StatsPass {
+    /// `None` only records the statistics
+    policy: Option<vekt_core::checks::CheckPolicy>,
+    /// Manifest whose tensor norms new ones are compared against
+    reference: Option<VektManifest>,
+}
+
+/// How `vekt add` hashes and stores a model
+#[derive(Default, Clone, Copy)]
+struct AddOptions {
+    /// Ignore .vekt/index and re-hash every tensor
+    rehash: bool,
+    /// Compress tensors no storage rule matches, as `compression zstd` would
+    compress: bool,
+}
+
+/// Adds the model at `path`, or a safetensors file streamed from stdin when `path`
+/// is `-`, and saves its manifest as `<name>.vekt.json` if given, else next to the model.
+/// With `stats`, float tensors are decoded once more to record their statistics, and
+/// a failed check stops the add before the manifest is saved.
+fn add_model(
+    path: &Path,
+    name: Option<&str>,
+    options: AddOptions,
+    stats: Option<&StatsPass>,
+    extra_files: &[PathBuf],
+    on_blob: &(dyn Fn(&str) + Sync),
+    annotate: impl FnOnce(&mut VektManifest),
+) -> Result<(VektManifest, PathBuf), Box<dyn std::error::Error>> {
+    let path_str = path.to_str().unwrap();
+    let from_stdin = path_str == "-";
+    if from_stdin && name.is_none() {
+        return Err("Reading a model from stdin needs --name to name its manifest".into());
+    }
+
+    print!(
+        "Adding file: {} ... ",
+        if from_stdin { "<stdin>" } else { path_str }
+    );
+    // Any failure below removes the blobs written so far; the caller holds the lock
+    let transaction = Transaction::begin("add")?;
+
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut manifest = if from_stdin {
+        vekt_core::stream::ingest_reader(std::io::stdin().lock(), on_blob)?
+    } else if matches!(extension.as_str(), "onnx" | "npz" | "npy" | "index") {
+        vekt_core::async_api::process_file(path, true)?
+    } else if options.rehash {
+        SafetensorFile::open(path_str)?
+            .with_cancel(CANCEL.clone())
+            .process_streaming(true, &HashMap::new(), on_blob)?
+    } else {
+        let (manifest, status) =
+            vekt_core::index::process_with_index_cancellable(path, true, on_blob, &CANCEL)?;
+        match status {
+            IndexStatus::Unchanged => print!("unchanged since last add ... "),
+            IndexStatus::Partial { rehashed, reused } => {
+                print!("re-hashed {} tensors, reused {} ... ", rehashed, reused)
+            }
+            IndexStatus::Full => {}
+        }
+        manifest
+    };
+    let output_path = match name {
+        Some(name) => PathBuf::from(format!("{}.vekt.json", name.trim_end_matches(".vekt.json"))),
+        None => path.with_extension("vekt.json"),
+    };
+
+    // Re-adding a model keeps the files and annotations previously recorded for it
+    let previous = VektManifest::load(&output_path).ok();
+    if let Some(previous) = &previous {
+        manifest.attachments = previous.attachments.clone();
+        manifest.metadata = previous.metadata.clone();
+    }
+    if let Some(stats) = stats {
+        let decoded = manifest.compute_stats(previous.as_ref())?;
+        print!("computed stats of {} tensors ... ", decoded);
+        if let Some(policy) = &stats.policy {
+            let report =
+                vekt_core::checks::check_manifest(&manifest, stats.reference.as_ref(), policy);
+            print!("checked {} tensors ... ", report.checked);
+            for finding in report.warnings() {
+                eprintln!("\nwarning: {}: {}", finding.tensor, finding.issue);
+            }
+            report.into_result()?;
+        }
+    }
+    apply_storage_rules(&manifest, previous.as_ref(), options.compress)?;
+
+    for file in extra_files {
+        manifest.attach_file(file, None)?;
+    }
+    annotate(&mut manifest);
+    // Tensors were reported while hashing; this covers attachments and other formats
+    for hash in manifest.referenced_hashes() {
+        on_blob(hash);
+    }
+
+    manifest.save(&output_path)?;
+    refcount::note_saved(&output_path, &manifest);
+    transaction.commit()?;
+    enforce_store_size(manifest.referenced_hashes())?;
+
+    println!("Done! Manifest saved to {}", output_path.to_str().unwrap());
+    if !extra_files.is_empty() {
+        println!("Tracked {} sidecar files with the model", extra_files.len());
+    }
+
+    Ok((manifest, output_path))
+}
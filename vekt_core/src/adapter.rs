@@ -0,0 +1,296 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::numeric::{decode_floats, encode_floats, is_float_dtype};
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::validation::verify_blob_hash;
+use rayon::prelude::*;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Metadata key holding the manifest object hash of an adapter's base model
+pub const ADAPTER_BASE_KEY: &str = "adapter_base";
+
+/// Attachment PEFT writes next to adapter weights; holds `r` and `lora_alpha`
+pub const ADAPTER_CONFIG: &str = "adapter_config.json";
+
+static LORA_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_lora_regex() -> &'static Regex {
+    // PEFT naming: base_model.model.<target>.lora_A[.<adapter name>].weight
+    LORA_REGEX.get_or_init(|| {
+        Regex::new(r"^(?:base_model\.model\.)?(.+)\.lora_([AB])(?:\.[^.]+)?\.weight$").unwrap()
+    })
+}
+
+/// A LoRA A/B pair and the base tensor it updates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoraPair {
+    /// Base tensor name, e.g. `model.layers.0.self_attn.q_proj.weight`
+    pub target: String,
+    /// `[rank, in_features]`
+    pub a: String,
+    /// `[out_features, rank]`
+    pub b: String,
+}
+
+/// Finds every complete LoRA A/B pair in an adapter manifest
+pub fn find_lora_pairs(adapter: &VektManifest) -> Result<Vec<LoraPair>> {
+    let mut halves: BTreeMap<String, (Option<String>, Option<String>)> = BTreeMap::new();
+    for name in adapter.tensors.keys() {
+        if let Some(caps) = get_lora_regex().captures(name) {
+            let entry = halves.entry(format!("{}.weight", &caps[1])).or_default();
+            match &caps[2] {
+                "A" => entry.0 = Some(name.clone()),
+                _ => entry.1 = Some(name.clone()),
+            }
+        }
+    }
+
+    halves
+        .into_iter()
+        .map(|(target, pair)| match pair {
+            (Some(a), Some(b)) => Ok(LoraPair { target, a, b }),
+            _ => Err(VektError::InvalidManifest(format!(
+                "Adapter has only one of lora_A/lora_B for {}",
+                target
+            ))),
+        })
+        .collect()
+}
+
+/// LoRA scaling factor `lora_alpha / r`, read from the adapter's `adapter_config.json`
+/// attachment or `lora_alpha`/`r` metadata. Defaults to 1.0 when neither is present.
+pub fn lora_scale(adapter: &VektManifest) -> Result<f64> {
+    let (alpha, rank) = if let Some(attachment) = adapter.attachments.get(ADAPTER_CONFIG) {
+        let data = blobs::read_blob(&attachment.hash)?;
+        verify_blob_hash(&data, &attachment.hash)?;
+        let config: serde_json::Value = serde_json::from_slice(&data)?;
+        (config["lora_alpha"].as_f64(), config["r"].as_f64())
+    } else {
+        let parse = |key: &str| adapter.metadata.get(key).and_then(|v| v.parse().ok());
+        (parse("lora_alpha"), parse("r"))
+    };
+    match (alpha, rank) {
+        (Some(alpha), Some(rank)) if rank > 0.0 => Ok(alpha / rank),
+        _ => Ok(1.0),
+    }
+}
+
+fn read_floats(manifest: &VektManifest, name: &str) -> Result<Vec<f64>> {
+    let tensor = &manifest.tensors[name];
+    let data = blobs::read_blob(&tensor.hash)?;
+    verify_blob_hash(&data, &tensor.hash)?;
+    decode_floats(&data, &tensor.dtype)
+}
+
+/// Checks that every LoRA pair targets a base tensor with matching dimensions
+fn check_pairs(base: &VektManifest, adapter: &VektManifest) -> Result<Vec<LoraPair>> {
+    let pairs = find_lora_pairs(adapter)?;
+    if pairs.is_empty() {
+        return Err(VektError::InvalidManifest(
+            "No lora_A/lora_B tensors found in adapter".to_string(),
+        ));
+    }
+    for pair in &pairs {
+        let target = base.tensors.get(&pair.target).ok_or_else(|| {
+            VektError::InvalidManifest(format!(
+                "Adapter targets '{}', which is not in the base model",
+                pair.target
+            ))
+        })?;
+        let (a, b) = (&adapter.tensors[&pair.a], &adapter.tensors[&pair.b]);
+        let fits = target.shape.len() == 2
+            && a.shape.len() == 2
+            && b.shape.len() == 2
+            && a.shape[0] == b.shape[1]
+            && b.shape[0] == target.shape[0]
+            && a.shape[1] == target.shape[1];
+        if !fits {
+            return Err(VektError::InvalidManifest(format!(
+                "LoRA shapes A {:?} / B {:?} don't fit '{}' {:?}",
+                a.shape, b.shape, pair.target, target.shape
+            )));
+        }
+    }
+    Ok(pairs)
+}
+
+impl VektManifest {
+    /// Returns a manifest with the adapter folded in: `W += scale * B @ A` for every
+    /// LoRA pair. Merged tensors are stored as new blobs in the base tensor's dtype;
+    /// all untouched tensors keep sharing the base model's blobs.
+    pub fn apply_lora(&self, adapter: &VektManifest, scale: f64) -> Result<VektManifest> {
+        let pairs = check_pairs(self, adapter)?;
+        let mut merged = VektManifest {
+            checksum: None,
+            ..self.clone()
+        };
+
+        for pair in pairs {
+            let target = &self.tensors[&pair.target];
+            if !is_float_dtype(&target.dtype) {
+                return Err(VektError::InvalidManifest(format!(
+                    "Cannot apply LoRA to {} tensor '{}'",
+                    target.dtype, pair.target
+                )));
+            }
+            let (rank, cols) = (
+                adapter.tensors[&pair.a].shape[0],
+                adapter.tensors[&pair.a].shape[1],
+            );
+            let a = read_floats(adapter, &pair.a)?;
+            let b = read_floats(adapter, &pair.b)?;
+            let mut weights = read_floats(self, &pair.target)?;
+
+            weights
+                .par_chunks_mut(cols)
+                .enumerate()
+                .for_each(|(row, out)| {
+                    for k in 0..rank {
+                        let coefficient = scale * b[row * rank + k];
+                        if coefficient == 0.0 {
+                            continue;
+                        }
+                        for (w, a_value) in out.iter_mut().zip(&a[k * cols..(k + 1) * cols]) {
+                            *w += coefficient * a_value;
+                        }
+                    }
+                });
+
+            let (hash, _) =
+                blobs::save_blob_deduplicated(&encode_floats(&weights, &target.dtype)?)?;
+            let entry = merged.tensors.get_mut(&pair.target).unwrap();
+            entry.hash = hash;
+            entry.stats = None;
+        }
+        Ok(merged)
+    }
+
+    /// Returns a manifest containing the base tensors plus the adapter's tensors under
+    /// their own names, for runtimes that apply LoRA themselves
+    pub fn with_adapter_side_by_side(&self, adapter: &VektManifest) -> Result<VektManifest> {
+        check_pairs(self, adapter)?;
+        let mut combined = VektManifest {
+            checksum: None,
+            ..self.clone()
+        };
+        let offset = self
+            .tensors
+            .values()
+            .map(|t| t.index + 1)
+            .max()
+            .unwrap_or(0);
+        for (name, tensor) in &adapter.tensors {
+            if combined.tensors.contains_key(name) {
+                return Err(VektError::InvalidManifest(format!(
+                    "Adapter tensor '{}' collides with a base tensor",
+                    name
+                )));
+            }
+            let mut tensor = tensor.clone();
+            tensor.index += offset;
+            combined.tensors.insert(name.clone(), tensor);
+        }
+        Ok(combined)
+    }
+
+    /// Registers `base` as the model this adapter applies to. The base manifest is
+    /// snapshotted so its blobs stay reachable even if the base manifest file moves.
+    pub fn register_adapter_base(&mut self, base: &VektManifest) -> Result<String> {
+        check_pairs(base, self)?;
+        let hash = refs::store_manifest_object(base)?;
+        self.metadata
+            .insert(ADAPTER_BASE_KEY.to_string(), hash.clone());
+        Ok(hash)
+    }
+
+    /// The registered base model of an adapter manifest, if any
+    pub fn adapter_base(&self) -> Result<Option<VektManifest>> {
+        match self.metadata.get(ADAPTER_BASE_KEY) {
+            Some(hash) => Ok(Some(refs::load_manifest_object(hash)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ManifestTensor;
+    use indexmap::IndexMap;
+
+    fn manifest(tensors: &[(&str, Vec<usize>)]) -> VektManifest {
+        VektManifest {
+            tensors: tensors
+                .iter()
+                .enumerate()
+                .map(|(i, (name, shape))| {
+                    let tensor = ManifestTensor {
+                        shape: shape.clone(),
+                        dtype: "F32".to_string(),
+                        hash: format!("hash{}", i),
+                        index: i,
+                        extra: IndexMap::new(),
+                        ranges: None,
+                        stats: None,
+                    };
+                    (name.to_string(), tensor)
+                })
+                .collect(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: 0,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_find_lora_pairs() {
+        let adapter = manifest(&[
+            (
+                "base_model.model.layers.0.q_proj.lora_A.weight",
+                vec![4, 16],
+            ),
+            (
+                "base_model.model.layers.0.q_proj.lora_B.weight",
+                vec![16, 4],
+            ),
+            ("layers.1.v_proj.lora_A.default.weight", vec![4, 16]),
+            ("layers.1.v_proj.lora_B.default.weight", vec![8, 4]),
+        ]);
+        let pairs = find_lora_pairs(&adapter).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].target, "layers.0.q_proj.weight");
+        assert_eq!(pairs[1].target, "layers.1.v_proj.weight");
+
+        let base = manifest(&[
+            ("layers.0.q_proj.weight", vec![16, 16]),
+            ("layers.1.v_proj.weight", vec![8, 16]),
+        ]);
+        assert!(check_pairs(&base, &adapter).is_ok());
+        let wrong = manifest(&[
+            ("layers.0.q_proj.weight", vec![16, 16]),
+            ("layers.1.v_proj.weight", vec![16, 16]),
+        ]);
+        assert!(check_pairs(&wrong, &adapter).is_err());
+
+        let lonely = manifest(&[("layers.0.q_proj.lora_A.weight", vec![4, 16])]);
+        assert!(find_lora_pairs(&lonely).is_err());
+    }
+
+    #[test]
+    fn test_lora_scale_from_metadata() {
+        let mut adapter = manifest(&[]);
+        assert_eq!(lora_scale(&adapter).unwrap(), 1.0);
+        adapter
+            .metadata
+            .insert("lora_alpha".to_string(), "32".to_string());
+        adapter.metadata.insert("r".to_string(), "8".to_string());
+        assert_eq!(lora_scale(&adapter).unwrap(), 4.0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::errors::{Result, VektError};
+use crate::storage::VektManifest;
+use crate::tensor_filter::TensorFilter;
+use crate::utils::rename_replace;
+use crate::validation::VerifyPolicy;
+use crate::{ModelArchiver, NumpyFile, OnnxFile, SafetensorFile, TfCheckpoint};
+use std::path::{Path, PathBuf};
+
+/// Runs blocking work, such as hashing or blob IO, on tokio's blocking thread pool so
+/// it doesn't stall the async worker threads
+pub async fn run_blocking<T, F>(work: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| VektError::Io(std::io::Error::other(e)))?
+}
+
+/// Ingests the model at `path`, picking the format from its extension: ONNX, NumPy
+/// (`.npz`/`.npy`), TensorFlow checkpoints (`.index`), else safetensors
+pub fn process_file(path: &Path, save_blobs: bool) -> Result<VektManifest> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "onnx" => OnnxFile::open(path)?.process(save_blobs),
+        "npz" | "npy" => NumpyFile::open(path)?.process(save_blobs),
+        "index" => TfCheckpoint::open(path)?.process(save_blobs),
+        _ => {
+            let path_str = path.to_str().ok_or_else(|| {
+                VektError::InvalidSafetensor(format!("Path is not valid UTF-8: {}", path.display()))
+            })?;
+            SafetensorFile::open(path_str)?.process(save_blobs)
+        }
+    }
+}
+
+/// [`process_file`] for async callers: the file is hashed on the blocking pool
+pub async fn process_async(path: impl Into<PathBuf>, save_blobs: bool) -> Result<VektManifest> {
+    let path = path.into();
+    run_blocking(move || process_file(&path, save_blobs)).await
+}
+
+impl VektManifest {
+    /// [`load`](Self::load) with `tokio::fs`
+    pub async fn load_async(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = tokio::fs::read(path).await.map_err(|e| {
+            VektError::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to open manifest {}: {}", path.display(), e),
+            ))
+        })?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            VektError::InvalidManifest(format!(
+                "Failed to parse manifest {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// [`save`](Self::save) with `tokio::fs`, equally atomic
+    pub async fn save_async(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        tokio::io::AsyncWriteExt::write_all(&mut file, self.to_json()?.as_bytes()).await?;
+        file.sync_all().await?;
+        let path = path.to_path_buf();
+        run_blocking(move || Ok(rename_replace(&tmp_path, &path)?)).await?;
+        Ok(())
+    }
+
+    /// [`restore_filtered`](Self::restore_filtered) on the blocking pool, so several
+    /// restores can run while the runtime keeps serving other tasks
+    pub async fn restore_async(
+        &self,
+        output_path: impl Into<PathBuf>,
+        filter: TensorFilter,
+        policy: VerifyPolicy,
+    ) -> Result<()> {
+        let manifest = self.clone();
+        let output_path = output_path.into();
+        run_blocking(move || manifest.restore_filtered(&output_path, &filter, policy)).await
+    }
+}
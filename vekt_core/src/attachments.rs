@@ -0,0 +1,141 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::storage::{ManifestAttachment, VektManifest};
+use crate::utils::write_file_atomic;
+use crate::validation::{validate_path_safe, verify_blob_hash};
+use std::path::{Path, PathBuf};
+
+/// Attachments are meant for configs, tokenizers and licenses, not for weights
+pub const MAX_ATTACHMENT_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Non-tensor files that usually sit next to safetensors weights and are needed
+/// to actually load the model
+pub const KNOWN_SIDECAR_FILES: &[&str] = &[
+    "config.json",
+    "generation_config.json",
+    "tokenizer.json",
+    "tokenizer_config.json",
+    "special_tokens_map.json",
+    "added_tokens.json",
+    "tokenizer.model",
+    "vocab.json",
+    "vocab.txt",
+    "merges.txt",
+    "preprocessor_config.json",
+    "README.md",
+    "LICENSE",
+];
+
+/// Returns the known sidecar files present in `dir`, in `KNOWN_SIDECAR_FILES` order
+pub fn discover_sidecars(dir: &Path) -> Vec<PathBuf> {
+    KNOWN_SIDECAR_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Validates an attachment name: a relative path without traversal
+fn validate_attachment_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(VektError::InvalidManifest(
+            "Attachment name cannot be empty".to_string(),
+        ));
+    }
+    validate_path_safe(name)
+}
+
+fn too_large(what: &str, size: u64) -> VektError {
+    VektError::InvalidManifest(format!(
+        "Attachment {} is {} bytes; attachments are limited to {} bytes",
+        what, size, MAX_ATTACHMENT_SIZE
+    ))
+}
+
+impl VektManifest {
+    /// Stores `path` as a content-addressed blob and records it as an attachment.
+    /// The attachment is named after the file unless `name` is given.
+    pub fn attach_file(&mut self, path: &Path, name: Option<&str>) -> Result<String> {
+        let name = match name {
+            Some(n) => n.to_string(),
+            None => path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .ok_or_else(|| {
+                    VektError::InvalidManifest(format!(
+                        "Cannot derive attachment name from {}",
+                        path.display()
+                    ))
+                })?,
+        };
+        validate_attachment_name(&name)?;
+
+        let size = std::fs::metadata(path)?.len();
+        if size > MAX_ATTACHMENT_SIZE {
+            return Err(too_large(&path.display().to_string(), size));
+        }
+
+        let data = std::fs::read(path)?;
+        self.attach_bytes(&name, &data)?;
+        Ok(name)
+    }
+
+    /// Stores `data` as a blob and records it as the attachment `name`
+    pub fn attach_bytes(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        validate_attachment_name(name)?;
+        if data.len() as u64 > MAX_ATTACHMENT_SIZE {
+            return Err(too_large(name, data.len() as u64));
+        }
+
+        let (hash, _) = blobs::save_blob_deduplicated(data)?;
+        self.attachments.insert(
+            name.to_string(),
+            ManifestAttachment {
+                hash,
+                size: data.len() as u64,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes an attachment from the manifest. The blob stays until GC collects it.
+    pub fn detach(&mut self, name: &str) -> Option<ManifestAttachment> {
+        self.attachments.remove(name)
+    }
+
+    /// Writes every attachment into `dir`, verifying each blob before it is written.
+    /// Returns the number of files materialized.
+    pub fn restore_attachments(&self, dir: &Path) -> Result<usize> {
+        self.restore_attachments_with(dir, |name, hash| {
+            if !blobs::blob_exists(hash) {
+                return Err(VektError::BlobNotFound(format!(
+                    "Blob {} not found for attachment '{}'",
+                    hash, name
+                )));
+            }
+            let data = blobs::read_blob(hash)?;
+            verify_blob_hash(&data, hash)?;
+            Ok(data)
+        })
+    }
+
+    /// Writes every attachment into `dir` with the verified data `read` returns for
+    /// its name and hash
+    pub(crate) fn restore_attachments_with(
+        &self,
+        dir: &Path,
+        mut read: impl FnMut(&str, &str) -> Result<Vec<u8>>,
+    ) -> Result<usize> {
+        for (name, attachment) in &self.attachments {
+            validate_attachment_name(name)?;
+            let data = read(name, &attachment.hash)?;
+
+            let target = dir.join(name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_file_atomic(&target, &data)?;
+        }
+        Ok(self.attachments.len())
+    }
+}
@@ -0,0 +1,137 @@
+use crate::blobs;
+use crate::ephemeral::now_secs;
+use crate::errors::Result;
+use crate::refs;
+use crate::sparse;
+use crate::storage::VektManifest;
+use crate::utils::get_vekt_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A single checksum over a set of manifests and every blob they reference, so two
+/// parties can compare one line to confirm they hold identical model versions.
+///
+/// The digest is the blake3 hash of `manifest <hash>\n` for each manifest object hash,
+/// then `blob <hash>\n` for each blob hash, both in sorted order. It depends only on
+/// content: which blobs happened to be transferred or already present doesn't matter.
+#[derive(Debug, Default, Clone)]
+pub struct TransferDigest {
+    manifests: BTreeSet<String>,
+    blobs: BTreeSet<String>,
+}
+
+impl TransferDigest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a manifest and the blobs it references
+    pub fn add(&mut self, manifest: &VektManifest) -> Result<()> {
+        let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(manifest)?);
+        self.manifests.insert(hash);
+        self.blobs.extend(manifest.referenced_hashes().cloned());
+        Ok(())
+    }
+
+    /// Adds a manifest and the blobs of it a pull fetches, leaving out tensors a
+    /// [sparse](crate::sparse) repository doesn't want
+    pub fn add_wanted(&mut self, manifest: &VektManifest) -> Result<()> {
+        let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(manifest)?);
+        self.manifests.insert(hash);
+        self.blobs
+            .extend(sparse::wanted_blobs(manifest)?.into_iter().cloned());
+        Ok(())
+    }
+
+    pub fn manifest_count(&self) -> usize {
+        self.manifests.len()
+    }
+
+    pub fn blob_count(&self) -> usize {
+        self.blobs.len()
+    }
+
+    pub fn digest(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for hash in &self.manifests {
+            hasher.update(format!("manifest {}\n", hash).as_bytes());
+        }
+        for hash in &self.blobs {
+            hasher.update(format!("blob {}\n", hash).as_bytes());
+        }
+        hex::encode(hasher.finalize().as_bytes())
+    }
+}
+
+/// One completed push or pull in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    /// `push` or `pull`
+    pub operation: String,
+    pub remote: String,
+    /// [`TransferDigest::digest`] of everything transferred
+    pub digest: String,
+    /// Manifest object hashes
+    pub manifests: Vec<String>,
+    pub blobs: usize,
+    /// How the remote confirmed the blobs a push uploaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploads: Option<UploadVerification>,
+}
+
+/// Blobs a push uploaded that the remote confirmed it received intact, by echoing
+/// their checksum or with a matching ETag, and those it couldn't confirm. Uploads the
+/// remote reported receiving differently fail the push instead.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UploadVerification {
+    pub verified: usize,
+    pub unconfirmed: usize,
+}
+
+impl AuditEntry {
+    pub fn new(operation: &str, remote: &str, transfer: &TransferDigest) -> Self {
+        AuditEntry {
+            time: now_secs(),
+            operation: operation.to_string(),
+            remote: remote.to_string(),
+            digest: transfer.digest(),
+            manifests: transfer.manifests.iter().cloned().collect(),
+            blobs: transfer.blob_count(),
+            uploads: None,
+        }
+    }
+}
+
+/// Append-only log of transfers (.vekt/audit.log), one JSON entry per line
+pub fn audit_log_path() -> PathBuf {
+    get_vekt_dir().join("audit.log")
+}
+
+pub fn record(entry: &AuditEntry) -> Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_log_path())?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Entries of the audit log, oldest first
+pub fn read_log() -> Result<Vec<AuditEntry>> {
+    let path = audit_log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
@@ -0,0 +1,331 @@
+use crate::blobs;
+use crate::encoding::BlobEncoding;
+use crate::errors::{Result, VektError};
+use crate::hash::HashAlgorithm;
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{
+    LockFile, find_manifest_files, get_vekt_dir, rename_replace, write_file_atomic,
+};
+use crate::validation::validate_path_safe;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Archive format version written into `backup.json`
+pub const BACKUP_VERSION: u32 = 1;
+
+const INFO_ENTRY: &str = "backup.json";
+const METADATA_PREFIX: &str = "vekt/";
+const BLOBS_PREFIX: &str = "blobs/";
+
+/// Largest metadata file a restore reads into memory. Blobs are streamed and have
+/// no limit.
+const MAX_METADATA_SIZE: u64 = 256 << 20;
+
+/// Top-level entries of `.vekt` that are never part of the metadata snapshot
+const EXCLUDED: &[&str] = &["blobs", "lock"];
+
+/// Directories whose contents make up a repository's history
+const HISTORY_DIRS: &[&str] = &["manifests", "refs", "commits", "releases"];
+
+/// First entry of every backup archive
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub metadata_files: usize,
+    /// Blobs stored in the archive, 0 for metadata-only backups
+    pub blobs: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreSummary {
+    pub metadata_files: usize,
+    /// Blobs written from the archive
+    pub blobs: usize,
+    /// Manifests whose blobs are not in the local store after the restore
+    pub incomplete: Vec<(String, VektManifest)>,
+    pub missing_blobs: usize,
+}
+
+/// Metadata files below `.vekt`, as `/`-separated relative paths
+fn metadata_files(vekt_dir: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    collect_files(vekt_dir, vekt_dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|e| VektError::Io(std::io::Error::other(e)))?;
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if EXCLUDED.contains(&name.as_str()) || name.ends_with(".tmp") {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if path.is_file() {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+fn append<W: Write>(builder: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Streams a stored blob into the archive, decoded
+fn append_blob<W: Write>(builder: &mut tar::Builder<W>, hash: &str) -> Result<()> {
+    // The header needs the size up front, and only raw blobs have it on disk
+    let size = match blobs::find_blob(hash) {
+        Some((path, BlobEncoding::Raw)) => fs::metadata(path)?.len(),
+        _ => io::copy(&mut blobs::open_blob(hash)?, &mut io::sink())?,
+    };
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(
+        &mut header,
+        format!("{}{}", BLOBS_PREFIX, hash),
+        blobs::open_blob(hash)?.take(size),
+    )?;
+    Ok(())
+}
+
+/// Reads a metadata entry, refusing ones larger than [`MAX_METADATA_SIZE`]
+/// whatever their header claims
+fn read_metadata<R: Read>(entry: R, name: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    entry.take(MAX_METADATA_SIZE + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_METADATA_SIZE {
+        return Err(VektError::InvalidManifest(format!(
+            "Backup entry {} is larger than {} bytes",
+            name, MAX_METADATA_SIZE
+        )));
+    }
+    Ok(data)
+}
+
+/// Writes `.vekt` metadata (config, refs, commits, releases, manifest objects, index)
+/// to a zstd-compressed tar archive at `output`. Blob data is left out unless
+/// `include_blobs` is set, so the archive stays small enough to keep many copies.
+pub fn create_backup(output: &Path, include_blobs: bool) -> Result<BackupInfo> {
+    // Hold the lock so refs and the objects they point to are captured consistently
+    let _lock = LockFile::lock()?;
+    let vekt_dir = get_vekt_dir();
+    let files = metadata_files(&vekt_dir)?;
+    let hashes = if include_blobs {
+        referenced_blobs(vekt_dir.parent().unwrap_or(Path::new(".")))?
+    } else {
+        BTreeSet::new()
+    };
+
+    let info = BackupInfo {
+        version: BACKUP_VERSION,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        metadata_files: files.len(),
+        blobs: hashes.iter().filter(|h| blobs::blob_exists(h)).count(),
+    };
+
+    let tmp_path = output.with_extension("tmp");
+    let encoder = zstd::Encoder::new(BufWriter::new(File::create(&tmp_path)?), 3)
+        .map_err(|e| VektError::CompressionError(e.to_string()))?;
+    let mut builder = tar::Builder::new(encoder);
+    append(&mut builder, INFO_ENTRY, &serde_json::to_vec_pretty(&info)?)?;
+    for name in &files {
+        let data = fs::read(vekt_dir.join(name))?;
+        append(&mut builder, &format!("{}{}", METADATA_PREFIX, name), &data)?;
+    }
+    for hash in hashes.iter().filter(|h| blobs::blob_exists(h)) {
+        append_blob(&mut builder, hash)?;
+    }
+    let mut writer = builder
+        .into_inner()?
+        .finish()
+        .map_err(|e| VektError::CompressionError(e.to_string()))?;
+    writer.flush()?;
+    drop(writer);
+    rename_replace(&tmp_path, output)?;
+    Ok(info)
+}
+
+/// True when the repository already has history a restore would mix with
+fn has_history(vekt_dir: &Path) -> Result<bool> {
+    if vekt_dir.join("HEAD").exists() {
+        return Ok(true);
+    }
+    for dir in HISTORY_DIRS {
+        let mut files = Vec::new();
+        let path = vekt_dir.join(dir);
+        if path.is_dir() {
+            collect_files(vekt_dir, &path, &mut files)?;
+        }
+        if !files.is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Reattaches the metadata in a backup archive to this repository's blob store.
+///
+/// Refuses to run over existing history unless `force` is set, in which case files
+/// from the archive replace local ones with the same path. Blobs in the archive are
+/// verified and written only when missing locally.
+pub fn restore_backup(archive: &Path, force: bool) -> Result<RestoreSummary> {
+    let _lock = LockFile::lock()?;
+    let vekt_dir = get_vekt_dir();
+    if !force && has_history(&vekt_dir)? {
+        return Err(VektError::ConflictDetected(
+            "Repository already has refs or commits. Use --force to restore over them".to_string(),
+        ));
+    }
+
+    let decoder = zstd::Decoder::new(BufReader::new(File::open(archive)?))
+        .map_err(|e| VektError::DecompressionError(e.to_string()))?;
+    let mut tar = tar::Archive::new(decoder);
+    let mut summary = RestoreSummary::default();
+    let mut seen_info = false;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+
+        if !seen_info {
+            if name != INFO_ENTRY {
+                return Err(VektError::InvalidManifest(format!(
+                    "{} is not a vekt backup",
+                    archive.display()
+                )));
+            }
+            let data = read_metadata(&mut entry, &name)?;
+            let info: BackupInfo = serde_json::from_slice(&data)?;
+            if info.version > BACKUP_VERSION {
+                return Err(VektError::InvalidManifest(format!(
+                    "Backup format {} is newer than this vekt supports ({})",
+                    info.version, BACKUP_VERSION
+                )));
+            }
+            seen_info = true;
+        } else if let Some(relative) = name.strip_prefix(METADATA_PREFIX) {
+            validate_path_safe(relative)?;
+            let top = relative.split('/').next().unwrap_or_default();
+            if EXCLUDED.contains(&top) {
+                continue;
+            }
+            let path = vekt_dir.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let data = read_metadata(&mut entry, &name)?;
+            write_file_atomic(&path, &data)?;
+            summary.metadata_files += 1;
+        } else if let Some(hash) = name.strip_prefix(BLOBS_PREFIX)
+            && !blobs::blob_exists(hash)
+        {
+            // Stored under the archive's own hash, whichever algorithm made it
+            let (actual, _) =
+                blobs::write_blob_from_reader_as(&mut entry, HashAlgorithm::of(hash))?;
+            if actual != hash {
+                return Err(VektError::HashMismatch {
+                    expected: hash.to_string(),
+                    actual,
+                });
+            }
+            summary.blobs += 1;
+        }
+    }
+    if !seen_info {
+        return Err(VektError::InvalidManifest(format!(
+            "{} is an empty backup",
+            archive.display()
+        )));
+    }
+
+    let (incomplete, missing) = missing_blobs()?;
+    summary.incomplete = incomplete;
+    summary.missing_blobs = missing;
+    Ok(summary)
+}
+
+/// Every stored manifest object, keyed by hash
+fn manifest_objects() -> Result<Vec<(String, VektManifest)>> {
+    let dir = refs::manifest_objects_dir();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut objects = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let hash = entry?.file_name().to_string_lossy().into_owned();
+        if hash.ends_with(".tmp") {
+            continue;
+        }
+        objects.push((hash.clone(), refs::load_manifest_object(&hash)?));
+    }
+    objects.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(objects)
+}
+
+/// Blobs of every stored manifest object and of the manifests in the working tree
+fn referenced_blobs(root: &Path) -> Result<BTreeSet<String>> {
+    let mut hashes = BTreeSet::new();
+    for (_, manifest) in manifest_objects()? {
+        hashes.extend(manifest.referenced_hashes().cloned());
+    }
+    for path in find_manifest_files(root)? {
+        match VektManifest::load(&path) {
+            Ok(manifest) => hashes.extend(manifest.referenced_hashes().cloned()),
+            Err(e) => warn!(
+                "Failed to parse manifest at {}: {}. Its blobs are left out of the backup.",
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(hashes)
+}
+
+/// Manifest objects that reference blobs absent from the local store, and the
+/// number of distinct blobs missing. Pulling those manifests from a remote
+/// completes the restore.
+pub fn missing_blobs() -> Result<(Vec<(String, VektManifest)>, usize)> {
+    let mut missing = BTreeSet::new();
+    let mut incomplete = Vec::new();
+    for (hash, manifest) in manifest_objects()? {
+        let mut any = false;
+        for blob in manifest.referenced_hashes() {
+            if !blobs::blob_exists(blob) {
+                missing.insert(blob.clone());
+                any = true;
+            }
+        }
+        if any {
+            incomplete.push((hash, manifest));
+        }
+    }
+    Ok((incomplete, missing.len()))
+}
@@ -0,0 +1,361 @@
+use crate::errors::{Result, VektError};
+use crate::hash;
+use crate::utils::{get_store_path, get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+
+/// One file in the blob store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedBlob {
+    /// Size on disk
+    pub size: u64,
+    /// Seconds since the epoch when the file was written
+    pub created: u64,
+}
+
+/// Every file in the blob store with its size and creation time, kept at
+/// `.vekt/blob-index.json` so dedup checks, gc, eviction and usage don't stat each
+/// blob. Writes and deletes in the store append to `.vekt/blob-index.log`, which
+/// [`open`](Self::open) replays instead of listing the store. Each entry notes the
+/// store directory's modification time, so files added or removed behind vekt's
+/// back show up as a newer one and the store is listed again. Files rewritten in
+/// place keep their old size until [`rebuild`](Self::rebuild).
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BlobIndex {
+    /// By store file name: `<hash>`, or `<hash>.<encoding>` for encoded blobs
+    files: BTreeMap<String, IndexedBlob>,
+    /// Modification time of the store directory, in nanoseconds since the epoch,
+    /// as of the last listing or log entry
+    #[serde(default)]
+    store_modified: u64,
+}
+
+fn index_path() -> PathBuf {
+    get_vekt_dir().join("blob-index.json")
+}
+
+fn log_path() -> PathBuf {
+    get_vekt_dir().join("blob-index.log")
+}
+
+/// Held while the log is folded into the saved index
+fn compaction_lock_path() -> PathBuf {
+    get_vekt_dir().join("blob-index.lock")
+}
+
+/// Log entries past which [`open`](BlobIndex::open) folds the log into the index
+const COMPACT_AFTER: usize = 4096;
+
+/// A compaction lock older than this was left by a process that died
+const STALE_LOCK_SECS: u64 = 60;
+
+/// Notes that store file `path` was just written, so the index picks it up
+/// without listing the store
+pub(crate) fn record_written(path: &Path) {
+    let Some(name) = store_file_name(path) else {
+        return;
+    };
+    match fs::metadata(path) {
+        Ok(metadata) => append_to_log(&format!(
+            "+ {} {} {} {}\n",
+            name,
+            metadata.len(),
+            created(&metadata),
+            store_modified()
+        )),
+        Err(e) => warn!("Failed to index {}: {}", path.display(), e),
+    }
+}
+
+/// Notes that store file `path` was just deleted
+pub(crate) fn record_removed(path: &Path) {
+    if let Some(name) = store_file_name(path) {
+        append_to_log(&format!("- {} {}\n", name, store_modified()));
+    }
+}
+
+/// Notes that the store directory changed without any blob changing, e.g. a temp
+/// file was removed, so that isn't mistaken for a change behind vekt's back
+pub(crate) fn record_unchanged() {
+    append_to_log(&format!("= {}\n", store_modified()));
+}
+
+fn store_modified() -> u64 {
+    fs::metadata(get_store_path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn store_file_name(path: &Path) -> Option<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.ends_with(".tmp"))
+}
+
+fn created(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Each entry goes out in one append, so entries from concurrent writers don't
+/// interleave
+fn append_to_log(entry: &str) {
+    let appended = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path())
+        .and_then(|mut log| log.write_all(entry.as_bytes()));
+    if let Err(e) = appended {
+        warn!("Failed to update the blob index: {}", e);
+    }
+}
+
+/// Only one process folds the log into the index at a time, or one could save an
+/// index without the entries another moved aside
+struct CompactionLock(PathBuf);
+
+impl CompactionLock {
+    fn acquire() -> Option<Self> {
+        let path = compaction_lock_path();
+        let stale = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age.as_secs() > STALE_LOCK_SECS);
+        if stale {
+            let _ = fs::remove_file(&path);
+        }
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .ok()
+            .map(|_| CompactionLock(path))
+    }
+}
+
+impl Drop for CompactionLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl BlobIndex {
+    /// Loads the index and replays the log of what was written and deleted since
+    /// it was saved. Lists the store when there is no index yet, or the store
+    /// changed since the last entry.
+    pub fn open() -> Result<Self> {
+        let Some(mut index) = Self::load() else {
+            return Self::relist();
+        };
+        let entries = index.replay(&log_path())?;
+        if index.store_modified != store_modified() {
+            return Self::relist();
+        }
+        if entries > COMPACT_AFTER {
+            match Self::compact(false) {
+                Ok(Some(compacted)) => index = compacted,
+                Ok(None) => {}
+                Err(e) => warn!("Failed to save the blob index: {}", e),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Like [`open`](Self::open), but never saves or compacts the index, so plans and
+    /// dry runs leave it as they found it
+    pub fn read() -> Result<Self> {
+        if let Some(mut index) = Self::load() {
+            index.replay(&log_path())?;
+            if index.store_modified == store_modified() {
+                return Ok(index);
+            }
+        }
+        let mut index = BlobIndex::default();
+        index.list_store()?;
+        Ok(index)
+    }
+
+    /// Re-reads the size and creation time of every file in the store and saves the
+    /// result, discarding what was indexed before
+    pub fn rebuild() -> Result<Self> {
+        Self::compact(true)?.ok_or_else(|| {
+            VektError::Io(std::io::Error::other(format!(
+                "The blob index is being saved by another process; if none is running, remove {}",
+                compaction_lock_path().display()
+            )))
+        })
+    }
+
+    /// Lists the store and saves the result
+    fn relist() -> Result<Self> {
+        match Self::compact(true)? {
+            Some(index) => Ok(index),
+            // Another process is saving it; list the store for this one
+            None => {
+                let mut index = BlobIndex::default();
+                index.list_store()?;
+                Ok(index)
+            }
+        }
+    }
+
+    fn load() -> Option<Self> {
+        fs::read(index_path())
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    /// Applies the entries of the log at `path`. Returns how many there were.
+    fn replay(&mut self, path: &Path) -> Result<usize> {
+        let log = match fs::read_to_string(path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = 0;
+        for line in log.lines() {
+            let fields: Vec<&str> = line.split(' ').collect();
+            let modified = match fields[..] {
+                ["+", name, size, created, modified] => {
+                    let (Ok(size), Ok(created)) = (size.parse(), created.parse()) else {
+                        continue;
+                    };
+                    self.files
+                        .insert(name.to_string(), IndexedBlob { size, created });
+                    modified
+                }
+                ["-", name, modified] => {
+                    self.files.remove(name);
+                    modified
+                }
+                ["=", modified] => modified,
+                // Cut short by a crash mid-append
+                _ => continue,
+            };
+            if let Ok(modified) = modified.parse() {
+                self.store_modified = modified;
+            }
+            entries += 1;
+        }
+        Ok(entries)
+    }
+
+    /// Saves the index with the log folded in, or rebuilt from a listing of the
+    /// store if `from_store`, and starts a new log. The log is moved aside first:
+    /// entries appended meanwhile go to the new one and are replayed on top.
+    /// Returns `None` if another process is already doing it.
+    fn compact(from_store: bool) -> Result<Option<Self>> {
+        let Some(_lock) = CompactionLock::acquire() else {
+            return Ok(None);
+        };
+        let aside = get_vekt_dir().join(format!("blob-index.{:016x}.log", rand::random::<u64>()));
+        match fs::rename(log_path(), &aside) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let mut index = BlobIndex::default();
+        let built = match from_store {
+            true => index.list_store(),
+            false => {
+                index = Self::load().unwrap_or_default();
+                index.replay(&aside).map(|_| ())
+            }
+        }
+        .and_then(|()| index.save());
+        if let Err(e) = built {
+            // Put the entries back so they aren't lost
+            if let Ok(entries) = fs::read_to_string(&aside) {
+                append_to_log(&entries);
+            }
+            let _ = fs::remove_file(&aside);
+            return Err(e);
+        }
+        let _ = fs::remove_file(&aside);
+        index.replay(&log_path())?;
+        Ok(Some(index))
+    }
+
+    fn list_store(&mut self) -> Result<()> {
+        self.files.clear();
+        // Taken first, so anything that changes during the listing lists it again
+        self.store_modified = store_modified();
+        let store = get_store_path();
+        if !store.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(&store)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(".tmp") {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                // Deleted between listing and stat
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.files.insert(
+                name,
+                IndexedBlob {
+                    size: metadata.len(),
+                    created: created(&metadata),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_file_atomic(&index_path(), &serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the store has blob `hash`, in any encoding
+    pub fn contains(&self, hash: &str) -> bool {
+        let name = hash::file_name(hash);
+        self.files
+            .range::<str, _>((Bound::Included(name.as_ref()), Bound::Unbounded))
+            .next()
+            .is_some_and(|(found, _)| hash::from_file_name(found) == hash)
+    }
+
+    /// Store files with the hash each holds
+    pub fn files(&self) -> impl Iterator<Item = (&str, Cow<'_, str>, &IndexedBlob)> {
+        self.files
+            .iter()
+            .map(|(name, blob)| (name.as_str(), hash::from_file_name(name), blob))
+    }
+
+    /// Size on disk of each stored blob, by hash
+    pub fn sizes(&self) -> HashMap<String, u64> {
+        let mut sizes = HashMap::new();
+        for (_, hash, blob) in self.files() {
+            *sizes.entry(hash.into_owned()).or_default() += blob.size;
+        }
+        sizes
+    }
+
+    /// Size of the whole store on disk
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|blob| blob.size).sum()
+    }
+
+    /// Number of distinct blobs
+    pub fn blob_count(&self) -> usize {
+        self.sizes().len()
+    }
+}
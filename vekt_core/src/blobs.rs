@@ -1,24 +1,61 @@
-use crate::utils::get_store_path;
+use crate::blob_index;
+use crate::direct_io;
+use crate::encoding::{self, BlobEncoding};
+use crate::hash::{self, HashAlgorithm};
+use crate::transaction;
+use crate::utils::{get_store_path, rename_replace};
 use std::fs::{self, File};
 use std::io::Write;
 /// Blob storage module - Single source of truth for all blob operations
 use std::path::PathBuf;
+use tracing::debug;
 
-/// Computes the blake3 hash of data and returns it as a hex string
+/// Computes the content hash of a blob with the [current](HashAlgorithm::current)
+/// algorithm, as a hex string tagged with the algorithm
 /// Single source of truth for hash computation
 pub fn compute_blob_hash(data: &[u8]) -> String {
-    let hash = blake3::hash(data);
-    hex::encode(hash.as_bytes())
+    HashAlgorithm::current().hash(data)
+}
+
+/// Computes the blake3 hash that names manifest objects and commits, whatever
+/// algorithm blobs are hashed with
+pub fn compute_object_hash(data: &[u8]) -> String {
+    HashAlgorithm::Blake3.hash(data)
+}
+
+/// Hashes a file in chunks, without loading it into memory
+pub fn hash_file(path: &std::path::Path) -> std::io::Result<String> {
+    hash_file_as(path, HashAlgorithm::current())
+}
+
+/// [`hash_file`] with a given algorithm, e.g. to check a file against a hash
+pub fn hash_file_as(path: &std::path::Path, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize())
 }
 
 /// Returns the full path to a blob given its hash
 pub fn get_blob_path(hash: &str) -> PathBuf {
-    get_store_path().join(hash)
+    get_store_path().join(hash::file_name(hash).as_ref())
+}
+
+/// The file holding a blob and its encoding: `<hash>` for raw blobs, or
+/// `<hash>.<ext>` for encoded ones
+pub fn find_blob(hash: &str) -> Option<(PathBuf, BlobEncoding)> {
+    let raw = get_blob_path(hash);
+    if raw.exists() {
+        return Some((raw, BlobEncoding::Raw));
+    }
+    BlobEncoding::ENCODED.into_iter().find_map(|encoding| {
+        let path = raw.with_extension(encoding.extension()?);
+        path.exists().then_some((path, encoding))
+    })
 }
 
-/// Checks if a blob exists in storage
+/// Checks if a blob exists in storage, in any encoding
 pub fn blob_exists(hash: &str) -> bool {
-    get_blob_path(hash).exists()
+    find_blob(hash).is_some()
 }
 
 /// Atomically writes data to a blob file using temp file + rename pattern
@@ -26,31 +63,111 @@ pub fn blob_exists(hash: &str) -> bool {
 /// Single source of truth for blob writing
 pub fn write_blob_atomic(data: &[u8]) -> std::io::Result<String> {
     let hash = compute_blob_hash(data);
-    let blob_path = get_blob_path(&hash);
+    write_blob_with_hash(data, &hash)?;
+    Ok(hash)
+}
+
+/// Like [`write_blob_atomic`] for data whose hash is already known, so it isn't
+/// hashed again. `hash` must be the hash of `data` in the current algorithm, as
+/// [`compute_blob_hash`] makes it. Returns whether the blob was newly written.
+pub fn write_blob_with_hash(data: &[u8], hash: &str) -> std::io::Result<bool> {
+    let blob_path = get_blob_path(hash);
 
     // Skip if already exists (deduplication)
-    if blob_path.exists() {
-        return Ok(hash);
+    if blob_exists(hash) {
+        debug!(hash = %hash, "Blob already stored");
+        return Ok(false);
     }
 
     // Ensure blobs directory exists
     let store_path = get_store_path();
     fs::create_dir_all(&store_path)?;
 
-    // Atomic write: temp file + rename
-    let tmp_path = blob_path.with_extension("tmp");
-    let mut f = File::create(&tmp_path)?;
+    // Atomic write: temp file + rename. Writers of the same blob may race, so each
+    // gets its own temp file
+    let tmp_path = blob_path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+    transaction::record(&tmp_path);
+    let mut f = direct_io::create(&tmp_path)?;
     f.write_all(data)?;
-    f.sync_all()?;
-    fs::rename(tmp_path, blob_path)?;
+    f.finish()?.sync_all()?;
+    finalize_blob(&tmp_path, hash)?;
+    debug!(hash = %hash, size = data.len(), "Wrote blob");
 
-    Ok(hash)
+    Ok(true)
+}
+
+/// Moves a fully written temp file into the store as blob `hash`. A blob that is
+/// already there has the same content, so the temp file is just removed. That also
+/// covers Windows, where a blob that a reader has mapped can't be replaced.
+pub fn finalize_blob(tmp_path: &std::path::Path, hash: &str) -> std::io::Result<()> {
+    if !blob_exists(hash) {
+        let blob_path = get_blob_path(hash);
+        match rename_replace(tmp_path, &blob_path) {
+            Ok(()) => {
+                transaction::record(&blob_path);
+                blob_index::record_written(&blob_path);
+                return Ok(());
+            }
+            Err(_) if blob_exists(hash) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    fs::remove_file(tmp_path)?;
+    blob_index::record_unchanged();
+    Ok(())
 }
 
-/// Reads a blob from storage given its hash
+/// Streams `reader` into the store, hashing as it goes. Returns the hash and the
+/// number of bytes read.
+pub fn write_blob_from_reader<R: std::io::Read>(reader: R) -> std::io::Result<(String, u64)> {
+    write_blob_from_reader_as(reader, HashAlgorithm::current())
+}
+
+/// [`write_blob_from_reader`] with a given algorithm, e.g. to import a blob under
+/// the hash it was exported with
+pub fn write_blob_from_reader_as<R: std::io::Read>(
+    mut reader: R,
+    algorithm: HashAlgorithm,
+) -> std::io::Result<(String, u64)> {
+    let store_path = get_store_path();
+    fs::create_dir_all(&store_path)?;
+    let tmp_path = store_path.join(format!("stream-{:016x}.tmp", rand::random::<u64>()));
+    transaction::record(&tmp_path);
+
+    let written = (|| {
+        let mut file = direct_io::create(&tmp_path)?;
+        let mut hasher = algorithm.hasher();
+        let mut buffer = vec![0u8; 1 << 20];
+        let mut size = 0u64;
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            file.write_all(&buffer[..n])?;
+            size += n as u64;
+        }
+        file.finish()?.sync_all()?;
+        Ok((hasher.finalize(), size))
+    })();
+    let (hash, size) = match written {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+
+    finalize_blob(&tmp_path, &hash)?;
+    debug!(hash = %hash, size, "Wrote blob");
+    Ok((hash, size))
+}
+
+/// Reads a blob from storage given its hash, decoding it if it is stored encoded
 pub fn read_blob(hash: &str) -> std::io::Result<Vec<u8>> {
-    let blob_path = get_blob_path(hash);
-    std::fs::read(&blob_path).map_err(|e| {
+    let (blob_path, encoding) = find_blob(hash).unwrap_or((get_blob_path(hash), BlobEncoding::Raw));
+    encoding::decode(&blob_path, encoding).map_err(|e| {
         std::io::Error::new(
             e.kind(),
             format!(
@@ -63,17 +180,23 @@ pub fn read_blob(hash: &str) -> std::io::Result<Vec<u8>> {
     })
 }
 
+/// Opens a blob for reading as it is consumed, so it never has to fit in memory.
+/// Raw and zstd blobs are streamed from their file; other encodings are decoded up
+/// front.
+pub fn open_blob(hash: &str) -> std::io::Result<Box<dyn std::io::Read + Send>> {
+    match find_blob(hash) {
+        Some((path, BlobEncoding::Raw)) => Ok(Box::new(std::io::BufReader::new(File::open(path)?))),
+        Some((path, BlobEncoding::Zstd)) => Ok(Box::new(zstd::Decoder::new(File::open(path)?)?)),
+        _ => Ok(Box::new(std::io::Cursor::new(read_blob(hash)?))),
+    }
+}
+
 /// Saves a blob only if it doesn't already exist (deduplication)
 /// Returns the hash and whether it was newly written
 pub fn save_blob_deduplicated(data: &[u8]) -> std::io::Result<(String, bool)> {
     let hash = compute_blob_hash(data);
-    let existed = blob_exists(&hash);
-
-    if !existed {
-        write_blob_atomic(data)?;
-    }
-
-    Ok((hash, !existed))
+    let written = write_blob_with_hash(data, &hash)?;
+    Ok((hash, written))
 }
 
 #[cfg(test)]
@@ -89,6 +212,17 @@ mod tests {
         assert_eq!(hash1.len(), 64, "Blake3 hash should be 64 hex chars");
     }
 
+    #[test]
+    fn test_parallel_hash_matches_blake3() {
+        let data: Vec<u8> = (0..3 * hash::PARALLEL_HASH_THRESHOLD + 17)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+        assert_eq!(
+            compute_blob_hash(&data),
+            blake3::hash(&data).to_hex().to_string()
+        );
+    }
+
     #[test]
     fn test_blob_deduplication() {
         let data = b"unique test data for dedup";
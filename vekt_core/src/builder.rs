@@ -0,0 +1,327 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::hash;
+use crate::storage::{ManifestTensor, Placement, RawTensorMetaData, VektManifest};
+use crate::utils::tensor_byte_size;
+use crate::validation::validate_tensor_name;
+use crate::writer::ALIGNMENT;
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+impl Placement {
+    /// Lays out `tensor` after those already placed, as
+    /// [`SafetensorWriter`](crate::SafetensorWriter) does
+    fn append(&mut self, name: &str, tensor: &ManifestTensor) -> Result<()> {
+        let size = tensor.byte_size()?;
+        let data_offsets = match self.placed.get(&tensor.hash) {
+            Some(offsets) => *offsets,
+            None => {
+                let start = self.data_end.next_multiple_of(ALIGNMENT);
+                self.data_end = start + size;
+                self.placed
+                    .insert(tensor.hash.clone(), (start, self.data_end));
+                (start, self.data_end)
+            }
+        };
+        let meta = RawTensorMetaData {
+            shape: tensor.shape.clone(),
+            dtype: tensor.dtype.clone(),
+            data_offsets,
+            extra: tensor.extra.clone(),
+        };
+        // `"name":{...}`, after a comma unless it's the first entry
+        self.header_len += serde_json::to_string(name)?.len()
+            + 1
+            + serde_json::to_string(&meta)?.len()
+            + usize::from(self.tensors > 0);
+        self.tensors += 1;
+        self.total_size = 8 + "{}".len() + self.header_len + self.data_end;
+        Ok(())
+    }
+}
+
+/// Reads exactly `remaining` bytes from `inner`, failing on a short or long read
+/// so a tensor of the wrong size never reaches the store
+struct ExactReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return match self.inner.read(&mut [0u8; 1])? {
+                0 => Ok(0),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "more data than the tensor's shape and dtype need",
+                )),
+            };
+        }
+        let want = buf.len().min(self.remaining as usize);
+        let n = self.inner.read(&mut buf[..want])?;
+        if n == 0 && want > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{} bytes short of the tensor's shape and dtype",
+                    self.remaining
+                ),
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Programmatic construction and editing of manifests, for tools that produce
+/// weights in Rust and want to skip writing a safetensors file first.
+///
+/// Every method keeps the manifest valid: names are checked, tensor data must match
+/// its shape and dtype, indices stay `0..n` in insertion order and `total_size` is
+/// the size of the safetensors file a restore would write.
+///
+/// ```no_run
+/// use vekt_core::storage::VektManifest;
+///
+/// let mut manifest = VektManifest::new();
+/// manifest
+///     .add_tensor("bias", vec![2], "F32", &[0u8; 8])?
+///     .add_tensor("weight", vec![2, 2], "F32", &[0u8; 16])?
+///     .set_metadata("step", "100");
+/// manifest.save(std::path::Path::new("model.vekt.json"))?;
+/// # Ok::<(), vekt_core::errors::VektError>(())
+/// ```
+impl VektManifest {
+    /// An empty manifest at the current version
+    pub fn new() -> Self {
+        VektManifest {
+            tensors: BTreeMap::new(),
+            version: Self::CURRENT_VERSION.to_string(),
+            total_size: 8 + "{}".len(),
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Placement::default(),
+        }
+    }
+
+    /// Stores `data` as a blob and appends it as tensor `name`
+    pub fn add_tensor(
+        &mut self,
+        name: &str,
+        shape: Vec<usize>,
+        dtype: &str,
+        data: &[u8],
+    ) -> Result<&mut Self> {
+        self.check_new_tensor(name)?;
+        check_size(name, &shape, dtype, data.len() as u64)?;
+        let (hash, _) = blobs::save_blob_deduplicated(data)?;
+        self.insert_tensor(name, shape, dtype, hash)
+    }
+
+    /// Like [`add_tensor`](Self::add_tensor), streaming the data from `reader`
+    /// into the store instead of holding it in memory
+    pub fn add_tensor_from_reader<R: Read>(
+        &mut self,
+        name: &str,
+        shape: Vec<usize>,
+        dtype: &str,
+        reader: R,
+    ) -> Result<&mut Self> {
+        self.check_new_tensor(name)?;
+        let needed = tensor_byte_size(&shape, dtype)? as u64;
+        let reader = ExactReader {
+            inner: reader,
+            remaining: needed,
+        };
+        let (hash, _) = blobs::write_blob_from_reader(reader).map_err(|e| {
+            VektError::TensorCorruption(format!("Tensor '{}': {} {:?}: {}", name, dtype, shape, e))
+        })?;
+        self.insert_tensor(name, shape, dtype, hash)
+    }
+
+    /// Appends a tensor whose data is already in the blob store
+    pub fn add_tensor_blob(
+        &mut self,
+        name: &str,
+        shape: Vec<usize>,
+        dtype: &str,
+        hash: &str,
+    ) -> Result<&mut Self> {
+        self.check_new_tensor(name)?;
+        tensor_byte_size(&shape, dtype)?;
+        if !blobs::blob_exists(hash) {
+            return Err(VektError::BlobNotFound(format!(
+                "Blob {} not found for tensor '{}'",
+                hash, name
+            )));
+        }
+        let size = blobs::read_blob(hash)?.len() as u64;
+        check_size(name, &shape, dtype, size)?;
+        self.insert_tensor(name, shape, dtype, hash.to_string())
+    }
+
+    /// Removes a tensor and closes the gap in the indices. The blob stays until GC
+    /// collects it.
+    pub fn remove_tensor(&mut self, name: &str) -> Result<Option<ManifestTensor>> {
+        self.check_mutable()?;
+        let Some(removed) = self.tensors.remove(name) else {
+            return Ok(None);
+        };
+        for tensor in self.tensors.values_mut() {
+            if tensor.index > removed.index {
+                tensor.index -= 1;
+            }
+        }
+        // The checksum describes the file the tensors came from, which they no longer match
+        self.checksum = None;
+        self.recompute_total_size()?;
+        Ok(Some(removed))
+    }
+
+    /// Renames a tensor, keeping its position
+    pub fn rename_tensor(&mut self, from: &str, to: &str) -> Result<&mut Self> {
+        self.check_mutable()?;
+        validate_tensor_name(to)?;
+        if self.tensors.contains_key(to) {
+            return Err(VektError::InvalidTensorName(format!(
+                "Duplicate tensor '{}'",
+                to
+            )));
+        }
+        let tensor = self
+            .tensors
+            .remove(from)
+            .ok_or_else(|| VektError::InvalidTensorName(format!("No tensor named '{}'", from)))?;
+        self.tensors.insert(to.to_string(), tensor);
+        self.checksum = None;
+        self.recompute_total_size()?;
+        Ok(self)
+    }
+
+    /// Sets a free-form annotation
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> &mut Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Recomputes `total_size` from the tensors, as the size of the safetensors file
+    /// a restore writes. Manifests with a source layout keep their original size.
+    pub fn recompute_total_size(&mut self) -> Result<usize> {
+        if let Some(layout) = &self.layout {
+            self.total_size =
+                (layout.main.size + layout.external.values().map(|f| f.size).sum::<u64>()) as usize;
+            return Ok(self.total_size);
+        }
+        let mut tensors: Vec<_> = self.tensors.iter().collect();
+        tensors.sort_by_key(|(_, t)| t.index);
+        let mut placement = Placement::default();
+        for (name, tensor) in tensors {
+            validate_tensor_name(name)?;
+            placement.append(name, tensor)?;
+        }
+        placement.total_size = 8 + "{}".len() + placement.header_len + placement.data_end;
+        self.total_size = placement.total_size;
+        self.placement = placement;
+        Ok(self.total_size)
+    }
+
+    /// Checks the invariants the mutation methods maintain, e.g. for manifests
+    /// edited by hand or by other tools
+    pub fn validate(&self) -> Result<()> {
+        let mut seen = vec![false; self.tensors.len()];
+        for (name, tensor) in &self.tensors {
+            validate_tensor_name(name)?;
+            if !hash::is_hash(&tensor.hash) {
+                return Err(VektError::InvalidManifest(format!(
+                    "Tensor '{}' has an invalid hash '{}'",
+                    name, tensor.hash
+                )));
+            }
+            match seen.get_mut(tensor.index) {
+                Some(slot) if !*slot => *slot = true,
+                _ => {
+                    return Err(VektError::InvalidManifest(format!(
+                        "Tensor '{}' has index {}; indices must be 0..{} without repeats",
+                        name,
+                        tensor.index,
+                        self.tensors.len()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_mutable(&self) -> Result<()> {
+        match &self.layout {
+            Some(layout) => Err(VektError::InvalidManifest(format!(
+                "Manifest rebuilds a {} file; its tensors can't be edited",
+                layout.format
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn check_new_tensor(&self, name: &str) -> Result<()> {
+        self.check_mutable()?;
+        validate_tensor_name(name)?;
+        if self.tensors.contains_key(name) {
+            return Err(VektError::InvalidTensorName(format!(
+                "Duplicate tensor '{}'",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Appends a tensor whose size was already checked against its data
+    fn insert_tensor(
+        &mut self,
+        name: &str,
+        shape: Vec<usize>,
+        dtype: &str,
+        hash: String,
+    ) -> Result<&mut Self> {
+        let tensor = ManifestTensor {
+            shape,
+            dtype: dtype.to_string(),
+            hash,
+            index: self.tensors.len(),
+            extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
+        };
+        let current = self.placement.tensors == self.tensors.len()
+            && self.placement.total_size == self.total_size;
+        if !current {
+            self.recompute_total_size()?;
+        }
+        self.placement.append(name, &tensor)?;
+        self.total_size = self.placement.total_size;
+        self.tensors.insert(name.to_string(), tensor);
+        self.checksum = None;
+        Ok(self)
+    }
+}
+
+/// Checks that `size` bytes of data fit a tensor of `shape` and `dtype`
+fn check_size(name: &str, shape: &[usize], dtype: &str, size: u64) -> Result<()> {
+    let needed = tensor_byte_size(shape, dtype)?;
+    if needed as u64 != size {
+        return Err(VektError::TensorCorruption(format!(
+            "Tensor '{}': {} {:?} needs {} bytes, got {}",
+            name, dtype, shape, needed, size
+        )));
+    }
+    Ok(())
+}
+
+impl Default for VektManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
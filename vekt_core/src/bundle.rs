@@ -0,0 +1,256 @@
+use crate::blobs;
+use crate::ephemeral::now_secs;
+use crate::errors::{Result, VektError};
+use crate::hash::HashAlgorithm;
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{LockFile, rename_replace, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bundle format version written into `bundle.json`
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Conventional extension of bundle files
+pub const BUNDLE_EXTENSION: &str = "vektbundle";
+
+const INFO_ENTRY: &str = "bundle.json";
+const BLOBS_PREFIX: &str = "blobs/";
+const OBJECTS_PREFIX: &str = "manifest-objects/";
+const MANIFESTS_PREFIX: &str = "manifests/";
+
+/// A manifest carried by a bundle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundledManifest {
+    /// File name the manifest is unpacked to, e.g. `model.vekt.json`
+    pub name: String,
+    /// Hash of its manifest object
+    pub hash: String,
+}
+
+/// First entry of every bundle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleInfo {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub manifests: Vec<BundledManifest>,
+    pub blobs: usize,
+    /// Decoded size of all blobs
+    pub blob_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct UnpackSummary {
+    /// Manifest files written
+    pub manifests: Vec<PathBuf>,
+    /// Blobs added to the store
+    pub blobs: usize,
+    /// Blobs the store already had
+    pub existing_blobs: usize,
+}
+
+fn append<W: Write>(builder: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Writes a self-contained, zstd-compressed tar archive with `manifests` (file name
+/// and manifest) and every blob they reference, for carrying models to machines
+/// without access to any remote. Fails if a referenced blob is missing locally.
+pub fn create_bundle(output: &Path, manifests: &[(String, VektManifest)]) -> Result<BundleInfo> {
+    let mut hashes = BTreeSet::new();
+    let mut bundled = Vec::new();
+    let mut objects = Vec::new();
+    for (name, manifest) in manifests {
+        validate_bundle_name(name)?;
+        let bytes = refs::manifest_object_bytes(manifest)?;
+        let hash = blobs::compute_object_hash(&bytes);
+        hashes.extend(manifest.referenced_hashes().cloned());
+        bundled.push(BundledManifest {
+            name: name.clone(),
+            hash: hash.clone(),
+        });
+        objects.push((hash, bytes));
+    }
+    if let Some(missing) = hashes.iter().find(|h| !blobs::blob_exists(h)) {
+        return Err(VektError::BlobNotFound(format!(
+            "Blob {} is not in the local store; pull it before bundling",
+            missing
+        )));
+    }
+
+    let tmp_path = output.with_extension("tmp");
+    let encoder = zstd::Encoder::new(BufWriter::new(File::create(&tmp_path)?), 3)
+        .map_err(|e| VektError::CompressionError(e.to_string()))?;
+    let mut builder = tar::Builder::new(encoder);
+
+    // Blob sizes are only known once written, so the info entry counts them up front
+    let mut blob_bytes = 0;
+    for hash in &hashes {
+        blob_bytes += blob_size(hash)?;
+    }
+    let info = BundleInfo {
+        version: BUNDLE_VERSION,
+        created_at: now_secs(),
+        manifests: bundled,
+        blobs: hashes.len(),
+        blob_bytes,
+    };
+    append(&mut builder, INFO_ENTRY, &serde_json::to_vec_pretty(&info)?)?;
+
+    // Blobs first and manifests last, so an interrupted unpack never leaves a
+    // manifest behind whose blobs didn't make it
+    for hash in &hashes {
+        let name = format!("{}{}", BLOBS_PREFIX, hash);
+        let raw = blobs::get_blob_path(hash);
+        if raw.is_file() {
+            builder.append_file(&name, &mut File::open(&raw)?)?;
+        } else {
+            append(&mut builder, &name, &blobs::read_blob(hash)?)?;
+        }
+    }
+    for (hash, bytes) in &objects {
+        append(&mut builder, &format!("{}{}", OBJECTS_PREFIX, hash), bytes)?;
+    }
+    for ((_, manifest), entry) in manifests.iter().zip(&info.manifests) {
+        append(
+            &mut builder,
+            &format!("{}{}", MANIFESTS_PREFIX, entry.name),
+            manifest.to_json()?.as_bytes(),
+        )?;
+    }
+
+    let mut writer = builder
+        .into_inner()?
+        .finish()
+        .map_err(|e| VektError::CompressionError(e.to_string()))?;
+    writer.flush()?;
+    drop(writer);
+    rename_replace(&tmp_path, output)?;
+    Ok(info)
+}
+
+/// Reads the info entry of a bundle without unpacking it
+pub fn read_bundle_info(bundle: &Path) -> Result<BundleInfo> {
+    let decoder = zstd::Decoder::new(BufReader::new(File::open(bundle)?))
+        .map_err(|e| VektError::DecompressionError(e.to_string()))?;
+    let mut tar = tar::Archive::new(decoder);
+    let mut entry = tar
+        .entries()?
+        .next()
+        .ok_or_else(|| not_a_bundle(bundle))??;
+    if entry.path()?.to_string_lossy() != INFO_ENTRY {
+        return Err(not_a_bundle(bundle));
+    }
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    let info: BundleInfo = serde_json::from_slice(&data)?;
+    if info.version > BUNDLE_VERSION {
+        return Err(VektError::InvalidManifest(format!(
+            "Bundle format {} is newer than this vekt supports ({})",
+            info.version, BUNDLE_VERSION
+        )));
+    }
+    Ok(info)
+}
+
+/// Ingests a bundle: blobs are verified and added to the store, manifest objects
+/// are imported and manifest files written to `output_dir`. Existing manifest files
+/// with different content are only replaced with `force`.
+pub fn unpack_bundle(bundle: &Path, output_dir: &Path, force: bool) -> Result<UnpackSummary> {
+    let _lock = LockFile::lock()?;
+    let info = read_bundle_info(bundle)?;
+    // Refuse before writing anything rather than after the blobs are in
+    for entry in &info.manifests {
+        validate_bundle_name(&entry.name)?;
+        let path = output_dir.join(&entry.name);
+        if !force
+            && let Ok(existing) = VektManifest::load(&path)
+            && blobs::compute_object_hash(&refs::manifest_object_bytes(&existing)?) != entry.hash
+        {
+            return Err(VektError::ConflictDetected(format!(
+                "{} already exists with different content. Use --force to replace it",
+                path.display()
+            )));
+        }
+    }
+
+    let decoder = zstd::Decoder::new(BufReader::new(File::open(bundle)?))
+        .map_err(|e| VektError::DecompressionError(e.to_string()))?;
+    let mut tar = tar::Archive::new(decoder);
+    let mut summary = UnpackSummary::default();
+    for entry in tar.entries()?.skip(1) {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if let Some(hash) = name.strip_prefix(BLOBS_PREFIX) {
+            if blobs::blob_exists(hash) {
+                summary.existing_blobs += 1;
+                continue;
+            }
+            let (actual, _) =
+                blobs::write_blob_from_reader_as(&mut entry, HashAlgorithm::of(hash))?;
+            if actual != hash {
+                return Err(VektError::HashMismatch {
+                    expected: hash.to_string(),
+                    actual,
+                });
+            }
+            summary.blobs += 1;
+        } else if let Some(hash) = name.strip_prefix(OBJECTS_PREFIX) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            refs::import_manifest_object(&data, hash)?;
+        } else if let Some(file_name) = name.strip_prefix(MANIFESTS_PREFIX) {
+            let Some(bundled) = info.manifests.iter().find(|m| m.name == file_name) else {
+                continue;
+            };
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            // The file must be the manifest the bundle's index names
+            let manifest: VektManifest = serde_json::from_slice(&data)?;
+            let actual = blobs::compute_object_hash(&refs::manifest_object_bytes(&manifest)?);
+            if actual != bundled.hash {
+                return Err(VektError::HashMismatch {
+                    expected: bundled.hash.clone(),
+                    actual,
+                });
+            }
+            fs::create_dir_all(output_dir)?;
+            let path = output_dir.join(file_name);
+            write_file_atomic(&path, &data)?;
+            summary.manifests.push(path);
+        }
+    }
+    Ok(summary)
+}
+
+/// Decoded size of a blob, without decoding it when it is stored raw
+fn blob_size(hash: &str) -> Result<u64> {
+    let raw = blobs::get_blob_path(hash);
+    if raw.is_file() {
+        return Ok(fs::metadata(raw)?.len());
+    }
+    Ok(blobs::read_blob(hash)?.len() as u64)
+}
+
+fn validate_bundle_name(name: &str) -> Result<()> {
+    if !name.ends_with(".vekt.json") || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(VektError::InvalidManifest(format!(
+            "'{}' is not a manifest file name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn not_a_bundle(bundle: &Path) -> VektError {
+    VektError::InvalidManifest(format!("{} is not a vekt bundle", bundle.display()))
+}
@@ -0,0 +1,143 @@
+use crate::blob_index::{self, BlobIndex};
+use crate::commits;
+use crate::encoding;
+use crate::ephemeral::now_secs;
+use crate::errors::Result;
+use crate::pins;
+use crate::refcount::RefCounts;
+use crate::refs;
+use crate::utils::{get_store_path, get_vekt_dir, write_file_atomic};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// What [`evict`] removed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    pub evicted: usize,
+    /// Bytes freed on disk
+    pub freed: u64,
+    /// Size of the blob store afterwards
+    pub remaining: u64,
+}
+
+/// Last time each blob was used, in seconds since the epoch, at `.vekt/access.json`.
+/// Blobs missing from it count as last used when their file was written.
+fn access_path() -> PathBuf {
+    get_vekt_dir().join("access.json")
+}
+
+fn load_access() -> BTreeMap<String, u64> {
+    fs::read(access_path())
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save_access(access: &BTreeMap<String, u64>) -> Result<()> {
+    write_file_atomic(&access_path(), &serde_json::to_vec(access)?)?;
+    Ok(())
+}
+
+/// Records that `hashes` were just used, e.g. added, pulled or restored, so
+/// eviction removes them last
+pub fn touch<'a>(hashes: impl IntoIterator<Item = &'a String>) -> Result<()> {
+    let mut access = load_access();
+    let now = now_secs();
+    for hash in hashes {
+        access.insert(hash.clone(), now);
+    }
+    save_access(&access)
+}
+
+/// Blobs eviction never removes: everything pinned or reachable from a tag
+pub fn protected_hashes() -> Result<HashSet<String>> {
+    let mut hashes = pins::pinned_hashes()?;
+    for (name, hash) in refs::list_tags()? {
+        match refs::load_manifest_object(&hash) {
+            Ok(manifest) => hashes.extend(manifest.referenced_hashes().cloned()),
+            Err(e) => warn!(
+                "Failed to load manifest for tag '{}': {}. Its blobs are not protected.",
+                name, e
+            ),
+        }
+    }
+    Ok(hashes)
+}
+
+/// Size of the blob store in bytes
+pub fn store_size() -> Result<u64> {
+    if !get_store_path().exists() {
+        return Ok(0);
+    }
+    Ok(BlobIndex::open()?.total_size())
+}
+
+/// Removes least-recently-used blobs until the store is at most `max_size` bytes.
+/// [Protected](protected_hashes) blobs, those in `keep`, and the blobs those and any
+/// other stored blob are built from are kept, so the store can stay above
+/// `max_size`. Blobs gc treats as referenced (working-tree manifests, commits,
+/// releases) are only evicted when they are in `on_remote`, the blobs a remote is
+/// known to have, so every evicted blob can be pulled again. Only call this while
+/// holding the repository lock.
+pub fn evict(
+    max_size: u64,
+    keep: &HashSet<String>,
+    on_remote: &HashSet<String>,
+) -> Result<Eviction> {
+    let store = get_store_path();
+    if !store.exists() {
+        return Ok(Eviction::default());
+    }
+
+    // Encoded blobs are stored as <hash>.<encoding>, so one hash can have a few files
+    let index = BlobIndex::open()?;
+    let mut files: HashMap<String, (Vec<PathBuf>, u64, u64)> = HashMap::new();
+    for (name, hash, indexed) in index.files() {
+        let blob = files.entry(hash.to_string()).or_default();
+        blob.0.push(store.join(name));
+        blob.1 += indexed.size;
+        blob.2 = blob.2.max(indexed.created);
+    }
+    let mut total = index.total_size();
+
+    let mut keep = encoding::with_dependencies(keep.iter().chain(&protected_hashes()?))?;
+    for hash in files.keys() {
+        keep.extend(encoding::dependencies(hash)?);
+    }
+
+    let refcounts = RefCounts::open(&commits::repo_root())?;
+    let mut access = load_access();
+    let mut candidates: Vec<(u64, &String)> = files
+        .iter()
+        .filter(|(hash, _)| {
+            !keep.contains(*hash) && (refcounts.count(hash) == 0 || on_remote.contains(*hash))
+        })
+        .map(|(hash, (_, _, modified))| (access.get(hash).copied().unwrap_or(*modified), hash))
+        .collect();
+    candidates.sort();
+
+    let mut eviction = Eviction::default();
+    for (_, hash) in candidates {
+        if total <= max_size {
+            break;
+        }
+        let (paths, size, _) = &files[hash];
+        for path in paths {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => blob_index::record_removed(path),
+            }
+        }
+        access.remove(hash);
+        total -= size;
+        eviction.evicted += 1;
+        eviction.freed += size;
+    }
+    eviction.remaining = total;
+    if eviction.evicted > 0 {
+        save_access(&access)?;
+    }
+    Ok(eviction)
+}
@@ -0,0 +1,49 @@
+use crate::errors::{Result, VektError};
+use std::future::Future;
+use std::time::Duration;
+
+/// Lets a Ctrl-C handler or an embedding application abort long operations.
+/// Cancelled operations stop at the next tensor or blob, remove the files they were
+/// writing and fail with [`VektError::Cancelled`].
+pub use tokio_util::sync::CancellationToken;
+
+/// Fails with [`VektError::Cancelled`] once `token` is cancelled
+pub fn check(token: &CancellationToken, what: &str) -> Result<()> {
+    if token.is_cancelled() {
+        return Err(cancelled(what));
+    }
+    Ok(())
+}
+
+/// Runs `operation` until it completes or `token` is cancelled, whichever is first.
+/// The operation is dropped mid-flight on cancellation, so it must not leave
+/// anything behind that its caller can't clean up.
+pub async fn until_cancelled<T>(
+    token: &CancellationToken,
+    what: &str,
+    operation: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match token.run_until_cancelled(operation).await {
+        Some(result) => result,
+        None => Err(cancelled(what)),
+    }
+}
+
+fn cancelled(what: &str) -> VektError {
+    VektError::Cancelled(what.to_string())
+}
+
+/// Cancels `token` once `timeout` has passed, giving every operation watching it a
+/// deadline. Must be called within a tokio runtime.
+pub fn cancel_after(token: &CancellationToken, timeout: Duration) {
+    let token = token.clone();
+    tokio::spawn(async move {
+        if token
+            .run_until_cancelled(tokio::time::sleep(timeout))
+            .await
+            .is_some()
+        {
+            token.cancel();
+        }
+    });
+}
@@ -0,0 +1,359 @@
+//! Materializes the models of a tag, branch or commit into a directory, and remembers
+//! which ref each directory holds so `vekt status` can report it.
+//!
+//! Attachments are whole blobs, so they are reflinked from the store where the
+//! filesystem supports it, hardlinked where it doesn't, and only copied as a last
+//! resort. Weights are assembled from many blobs and always written, but checking out
+//! into the same directory again only rewrites models whose manifest changed.
+//!
+//! Records live in `.vekt/checkouts/<hash of the directory>`, one file each.
+use crate::blobs;
+use crate::commits;
+use crate::encoding::BlobEncoding;
+use crate::ephemeral::now_secs;
+use crate::errors::{Result, VektError};
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use crate::validation::{validate_path_safe, verify_blob_hash};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Manifest name a single model (a tag, manifest file or object) is checked out as,
+/// so its weights land in `model.safetensors`
+pub const SINGLE_MODEL_NAME: &str = "model.vekt.json";
+
+/// A directory materialized with [`checkout`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkout {
+    /// The directory, absolute
+    pub dir: PathBuf,
+    /// The ref as given: a tag, branch, commit or manifest
+    pub spec: String,
+    /// The commit it resolved to, for branches and commits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Manifest name -> manifest object hash
+    pub manifests: BTreeMap<String, String>,
+    /// Files written, relative to `dir`, with their size
+    pub files: BTreeMap<String, u64>,
+    /// Seconds since the epoch
+    pub checked_out_at: u64,
+}
+
+impl Checkout {
+    /// Files that are missing or no longer have the size they were written with
+    pub fn modified(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|(name, size)| {
+                fs::metadata(self.dir.join(name)).map_or(true, |m| m.len() != **size)
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+/// How a file got into the directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Materialized {
+    Written,
+    /// A copy-on-write clone of the stored blob
+    Reflinked,
+    /// The stored blob itself, made read-only
+    Hardlinked,
+    /// Left as the previous checkout wrote it
+    Unchanged,
+}
+
+/// Outcome of [`checkout`]
+#[derive(Debug)]
+pub struct CheckoutSummary {
+    pub checkout: Checkout,
+    /// Files by path relative to the directory
+    pub files: Vec<(String, Materialized)>,
+    /// Files of the previous checkout the new ref doesn't have, now deleted
+    pub removed: Vec<String>,
+}
+
+fn checkouts_dir() -> PathBuf {
+    get_vekt_dir().join("checkouts")
+}
+
+fn record_path(dir: &Path) -> PathBuf {
+    checkouts_dir().join(blobs::compute_object_hash(dir.to_string_lossy().as_bytes()))
+}
+
+/// What was last checked out into `dir`, if anything
+pub fn read_checkout(dir: &Path) -> Result<Option<Checkout>> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    match fs::read(record_path(&dir)) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Every recorded checkout whose directory still exists, by directory
+pub fn list_checkouts() -> Result<Vec<Checkout>> {
+    let mut checkouts = Vec::new();
+    let dir = checkouts_dir();
+    if !dir.is_dir() {
+        return Ok(checkouts);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(".tmp") {
+            continue;
+        }
+        let checkout: Checkout = serde_json::from_slice(&fs::read(&path)?)?;
+        if checkout.dir.is_dir() {
+            checkouts.push(checkout);
+        }
+    }
+    checkouts.sort_by(|a, b| a.dir.cmp(&b.dir));
+    Ok(checkouts)
+}
+
+/// Manifests to check out, by name in the checkout
+type Models = Vec<(String, VektManifest)>;
+
+/// The manifests `spec` names and the commit it resolved to. A tag or manifest is one
+/// model; a branch or commit is every model it tracks.
+fn resolve(spec: &str) -> Result<(Option<String>, Models)> {
+    let is_tag = refs::validate_ref_name(spec).is_ok() && refs::tags_dir().join(spec).is_file();
+    if !Path::new(spec).is_file()
+        && !is_tag
+        && !spec.contains(':')
+        && let Ok(hash) = commits::resolve_commit(spec)
+    {
+        let mut manifests = Vec::new();
+        for (name, object) in commits::load_commit(&hash)?.manifests {
+            manifests.push((name, refs::load_manifest_object(&object)?));
+        }
+        return Ok((Some(hash), manifests));
+    }
+    let manifest = refs::resolve_manifest(spec)?;
+    Ok((None, vec![(SINGLE_MODEL_NAME.to_string(), manifest)]))
+}
+
+/// Files a model named `name` is checked out as: its weights, any other files of its
+/// source format, and its attachments, with the attachment blobs
+fn model_files(name: &str, manifest: &VektManifest) -> Result<(String, Vec<(String, String)>)> {
+    validate_path_safe(name)?;
+    let stem = name.strip_suffix(".vekt.json").unwrap_or(name);
+    let extension = manifest
+        .layout
+        .as_ref()
+        .map_or("safetensors", |layout| layout.extension());
+    let weights = format!("{}.{}", stem, extension);
+    let parent = Path::new(name).parent().unwrap_or(Path::new(""));
+    let mut attachments = Vec::new();
+    for (attachment, entry) in &manifest.attachments {
+        validate_path_safe(attachment)?;
+        let path = parent.join(attachment).to_string_lossy().into_owned();
+        attachments.push((path, entry.hash.clone()));
+    }
+    Ok((weights, attachments))
+}
+
+/// Writes every model `spec` names into `dir`, creating it if needed, and records the
+/// checkout. Files a previous checkout into `dir` wrote but the new ref doesn't have
+/// are deleted. Refuses to overwrite files no checkout wrote unless `force`.
+pub fn checkout(spec: &str, dir: &Path, force: bool) -> Result<CheckoutSummary> {
+    let (commit, models) = resolve(spec)?;
+    fs::create_dir_all(dir)?;
+    let dir = dir.canonicalize()?;
+    let previous = read_checkout(&dir)?;
+    let previous_files = previous.as_ref().map(|p| &p.files);
+    let unmodified = |name: &str| {
+        previous_files
+            .and_then(|files| files.get(name))
+            .is_some_and(|size| fs::metadata(dir.join(name)).is_ok_and(|m| m.len() == *size))
+    };
+
+    let mut planned = Vec::new();
+    let mut manifests = BTreeMap::new();
+    for (name, manifest) in &models {
+        let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(manifest)?);
+        let (weights, attachments) = model_files(name, manifest)?;
+        let mut files = vec![weights.clone()];
+        if let Some(layout) = &manifest.layout {
+            for external in layout.external.keys() {
+                validate_path_safe(external)?;
+                let path = layout.external_path(Path::new(&weights), external);
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+        let same_model = previous
+            .as_ref()
+            .is_some_and(|p| p.manifests.get(name) == Some(&hash));
+        let keep = same_model && files.iter().all(|file| unmodified(file));
+        planned.push((manifest, weights, files, attachments, keep));
+        manifests.insert(name.clone(), hash);
+    }
+
+    let wanted: Vec<&String> = planned
+        .iter()
+        .flat_map(|(_, _, files, attachments, _)| {
+            files.iter().chain(attachments.iter().map(|(path, _)| path))
+        })
+        .collect();
+    let clobbered: Vec<&str> = wanted
+        .iter()
+        .filter(|file| {
+            dir.join(file).exists() && !previous_files.is_some_and(|f| f.contains_key(**file))
+        })
+        .map(|file| file.as_str())
+        .collect();
+    if !clobbered.is_empty() && !force {
+        return Err(VektError::ConflictDetected(format!(
+            "{} already exist in {} and weren't written by a checkout. Use --force to overwrite them",
+            clobbered.join(", "),
+            dir.display()
+        )));
+    }
+
+    let mut summary = CheckoutSummary {
+        checkout: Checkout {
+            dir: dir.clone(),
+            spec: spec.to_string(),
+            commit,
+            manifests,
+            files: BTreeMap::new(),
+            checked_out_at: now_secs(),
+        },
+        files: Vec::new(),
+        removed: Vec::new(),
+    };
+    if let Some(previous) = &previous {
+        for name in previous.files.keys() {
+            if !wanted.contains(&name) && remove_if_exists(&dir.join(name))? {
+                summary.removed.push(name.clone());
+            }
+        }
+    }
+
+    for (manifest, weights, files, attachments, keep) in planned {
+        if keep {
+            summary
+                .files
+                .extend(files.into_iter().map(|f| (f, Materialized::Unchanged)));
+        } else {
+            let target = dir.join(&weights);
+            for file in &files {
+                remove_if_exists(&dir.join(file))?;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            manifest.restore(&target, None)?;
+            summary
+                .files
+                .extend(files.into_iter().map(|f| (f, Materialized::Written)));
+        }
+        for (path, hash) in attachments {
+            let target = dir.join(&path);
+            let how = if unmodified(&path) && target_matches(&target, &hash) {
+                Materialized::Unchanged
+            } else {
+                materialize_blob(&hash, &target)?
+            };
+            summary.files.push((path, how));
+        }
+    }
+
+    for (file, _) in &summary.files {
+        let size = fs::metadata(dir.join(file))?.len();
+        summary.checkout.files.insert(file.clone(), size);
+    }
+    fs::create_dir_all(checkouts_dir())?;
+    write_file_atomic(
+        &record_path(&dir),
+        &serde_json::to_vec_pretty(&summary.checkout)?,
+    )?;
+    Ok(summary)
+}
+
+fn remove_if_exists(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether `target` already holds blob `hash`
+fn target_matches(target: &Path, hash: &str) -> bool {
+    fs::read(target).is_ok_and(|data| verify_blob_hash(&data, hash).is_ok())
+}
+
+/// Puts blob `hash` at `target`: a reflink or hardlink of a raw stored blob where
+/// possible, else a verified copy
+fn materialize_blob(hash: &str, target: &Path) -> Result<Materialized> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    remove_if_exists(target)?;
+    if let Some((stored, BlobEncoding::Raw)) = blobs::find_blob(hash) {
+        if reflink(&stored, target).is_ok() {
+            return Ok(Materialized::Reflinked);
+        }
+        if hardlink(&stored, target).is_ok() {
+            return Ok(Materialized::Hardlinked);
+        }
+    }
+    if !blobs::blob_exists(hash) {
+        return Err(VektError::BlobNotFound(format!(
+            "Blob {} not found for {}",
+            hash,
+            target.display()
+        )));
+    }
+    let data = blobs::read_blob(hash)?;
+    verify_blob_hash(&data, hash)?;
+    write_file_atomic(target, &data)?;
+    Ok(Materialized::Written)
+}
+
+/// Clones `from` to `to` sharing its extents, on filesystems that support it
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = fs::File::open(from)?;
+    let target = fs::File::create(to)?;
+    // SAFETY: both descriptors are open for the duration of the call
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } == 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    drop(target);
+    let _ = fs::remove_file(to);
+    Err(error)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Links `to` to the stored blob `from`. The blob must never change, so the file is
+/// made read-only; editing it in place would change the store.
+#[cfg(unix)]
+fn hardlink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::hard_link(from, to)?;
+    let mode = fs::metadata(to)?.permissions().mode();
+    fs::set_permissions(to, fs::Permissions::from_mode(mode & !0o222))
+}
+
+/// Read-only files can't be deleted on Windows, which gc would have to do
+#[cfg(not(unix))]
+fn hardlink(_from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
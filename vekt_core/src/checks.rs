@@ -0,0 +1,254 @@
+use crate::errors::{Result, VektError};
+use crate::stats::TensorStats;
+use crate::storage::VektManifest;
+use serde::{Deserialize, Serialize};
+
+/// What a failed check does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckAction {
+    /// Stop the add; nothing is recorded
+    Fail,
+    /// Report the finding and carry on
+    Warn,
+    /// Don't run the check
+    Off,
+}
+
+/// Sanity checks `add --check` runs on tensor statistics, set under `checks` in the
+/// config, e.g. `{"all_zero": "fail", "max_norm_ratio": 4}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckPolicy {
+    /// Tensors with NaN or infinite values
+    #[serde(default = "CheckPolicy::fail")]
+    pub non_finite: CheckAction,
+    /// Tensors whose values are all zero
+    #[serde(default = "CheckPolicy::warn")]
+    pub all_zero: CheckAction,
+    /// Tensors whose norm moved more than `max_norm_ratio` from the reference's
+    #[serde(default = "CheckPolicy::warn")]
+    pub drift: CheckAction,
+    /// How many times larger or smaller than in the reference a tensor's norm may be
+    #[serde(default = "CheckPolicy::default_max_norm_ratio")]
+    pub max_norm_ratio: f64,
+}
+
+impl CheckPolicy {
+    fn fail() -> CheckAction {
+        CheckAction::Fail
+    }
+
+    fn warn() -> CheckAction {
+        CheckAction::Warn
+    }
+
+    fn default_max_norm_ratio() -> f64 {
+        10.0
+    }
+}
+
+impl Default for CheckPolicy {
+    fn default() -> Self {
+        CheckPolicy {
+            non_finite: Self::fail(),
+            all_zero: Self::warn(),
+            drift: Self::warn(),
+            max_norm_ratio: Self::default_max_norm_ratio(),
+        }
+    }
+}
+
+/// What a check found wrong with a tensor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum CheckIssue {
+    NonFinite { nan_count: u64, inf_count: u64 },
+    AllZero,
+    Drift { reference_norm: f64, norm: f64 },
+}
+
+impl std::fmt::Display for CheckIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckIssue::NonFinite {
+                nan_count,
+                inf_count,
+            } => write!(f, "{} NaN and {} infinite values", nan_count, inf_count),
+            CheckIssue::AllZero => write!(f, "all values are zero"),
+            CheckIssue::Drift {
+                reference_norm,
+                norm,
+            } => write!(
+                f,
+                "norm {:.4e} is {:.1}x the reference's {:.4e}",
+                norm,
+                norm / reference_norm,
+                reference_norm
+            ),
+        }
+    }
+}
+
+/// A failed check on one tensor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckFinding {
+    pub tensor: String,
+    #[serde(flatten)]
+    pub issue: CheckIssue,
+    pub action: CheckAction,
+}
+
+/// Result of [`check_manifest`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckReport {
+    pub findings: Vec<CheckFinding>,
+    /// Tensors that were checked
+    pub checked: usize,
+    /// Float tensors skipped because no statistics were recorded for them
+    pub missing_stats: usize,
+}
+
+impl CheckReport {
+    /// Findings whose policy is to fail
+    pub fn failures(&self) -> impl Iterator<Item = &CheckFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.action == CheckAction::Fail)
+    }
+
+    /// Findings whose policy is to warn
+    pub fn warnings(&self) -> impl Iterator<Item = &CheckFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.action == CheckAction::Warn)
+    }
+
+    /// Errors with every failure, if there are any
+    pub fn into_result(self) -> Result<Self> {
+        let failures: Vec<String> = self
+            .failures()
+            .map(|finding| format!("{}: {}", finding.tensor, finding.issue))
+            .collect();
+        if failures.is_empty() {
+            Ok(self)
+        } else {
+            Err(VektError::CheckFailed(failures.join("; ")))
+        }
+    }
+}
+
+/// Checks the statistics recorded in `manifest` (see
+/// [`VektManifest::compute_stats`]) against `policy`, and tensor norms against those
+/// recorded in `reference` when one is given
+pub fn check_manifest(
+    manifest: &VektManifest,
+    reference: Option<&VektManifest>,
+    policy: &CheckPolicy,
+) -> CheckReport {
+    let mut report = CheckReport::default();
+    for (name, tensor) in &manifest.tensors {
+        let Some(stats) = &tensor.stats else {
+            if crate::numeric::is_float_dtype(&tensor.dtype) {
+                report.missing_stats += 1;
+            }
+            continue;
+        };
+        report.checked += 1;
+        let reference = reference
+            .and_then(|reference| reference.tensors.get(name))
+            .and_then(|tensor| tensor.stats.as_ref());
+        for (issue, action) in issues(stats, reference, policy) {
+            report.findings.push(CheckFinding {
+                tensor: name.clone(),
+                issue,
+                action,
+            });
+        }
+    }
+    report
+}
+
+fn issues(
+    stats: &TensorStats,
+    reference: Option<&TensorStats>,
+    policy: &CheckPolicy,
+) -> Vec<(CheckIssue, CheckAction)> {
+    let mut issues = Vec::new();
+    if stats.has_non_finite() {
+        issues.push((
+            CheckIssue::NonFinite {
+                nan_count: stats.nan_count,
+                inf_count: stats.inf_count,
+            },
+            policy.non_finite,
+        ));
+    }
+    // Non-finite values aren't zero, so a tensor holding some isn't all zero
+    if !stats.has_non_finite() && stats.norm == 0.0 {
+        issues.push((CheckIssue::AllZero, policy.all_zero));
+    }
+    if let Some(reference) = reference.filter(|reference| reference.norm > 0.0) {
+        let ratio = stats.norm / reference.norm;
+        if ratio > policy.max_norm_ratio || ratio * policy.max_norm_ratio < 1.0 {
+            issues.push((
+                CheckIssue::Drift {
+                    reference_norm: reference.norm,
+                    norm: stats.norm,
+                },
+                policy.drift,
+            ));
+        }
+    }
+    issues.retain(|(_, action)| *action != CheckAction::Off);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(norm: f64, nan_count: u64) -> TensorStats {
+        TensorStats {
+            min: 0.0,
+            max: 0.0,
+            mean: 0.0,
+            norm,
+            nan_count,
+            inf_count: 0,
+            zero_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_issues_follow_policy() {
+        let policy = CheckPolicy::default();
+        assert!(issues(&stats(1.0, 0), Some(&stats(2.0, 0)), &policy).is_empty());
+        assert_eq!(
+            issues(&stats(1.0, 2), None, &policy),
+            vec![(
+                CheckIssue::NonFinite {
+                    nan_count: 2,
+                    inf_count: 0
+                },
+                CheckAction::Fail
+            )]
+        );
+        assert_eq!(
+            issues(&stats(0.0, 0), None, &policy),
+            vec![(CheckIssue::AllZero, CheckAction::Warn)]
+        );
+        assert_eq!(
+            issues(&stats(0.5, 0), Some(&stats(50.0, 0)), &policy).len(),
+            1
+        );
+
+        let lenient = CheckPolicy {
+            non_finite: CheckAction::Off,
+            ..Default::default()
+        };
+        assert!(issues(&stats(1.0, 2), None, &lenient).is_empty());
+        let parsed: CheckPolicy = serde_json::from_str(r#"{"all_zero": "fail"}"#).unwrap();
+        assert_eq!(parsed.all_zero, CheckAction::Fail);
+        assert_eq!(parsed.non_finite, CheckAction::Fail);
+    }
+}
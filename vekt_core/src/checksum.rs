@@ -0,0 +1,235 @@
+use crate::blobs;
+use crate::direct_io;
+use crate::errors::{Result, VektError};
+use crate::hub::StreamHeader;
+use crate::layout::{map_file, read_verified};
+use crate::storage::{FileChecksum, VektManifest};
+use crate::utils::rename_replace;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// Hash and size of the file at `path`
+pub fn file_checksum(path: &Path) -> Result<FileChecksum> {
+    Ok(bytes_checksum(&map_file(path)?))
+}
+
+/// Hash and size of a file already in memory
+pub fn bytes_checksum(data: &[u8]) -> FileChecksum {
+    FileChecksum {
+        hash: hash_parallel(data),
+        size: data.len() as u64,
+        skeleton: None,
+    }
+}
+
+/// Hash and size of the safetensors file in `data`, plus its skeleton: every byte
+/// outside the tensor data at `tensors` (file offsets), i.e. length prefix, header
+/// and padding, stored as a blob so the file can be rebuilt byte for byte
+pub fn safetensors_checksum(data: &[u8], tensors: Vec<Range<usize>>) -> Result<FileChecksum> {
+    let skeleton = safetensors_skeleton(data, tensors)?;
+    Ok(FileChecksum {
+        skeleton: Some(blobs::save_blob_deduplicated(&skeleton)?.0),
+        ..bytes_checksum(data)
+    })
+}
+
+/// The bytes of the safetensors file in `data` outside the tensor data at `tensors`
+pub(crate) fn safetensors_skeleton(data: &[u8], mut tensors: Vec<Range<usize>>) -> Result<Vec<u8>> {
+    tensors.sort_by_key(|range| range.start);
+    let mut skeleton = Vec::new();
+    let mut pos = 0;
+    for range in tensors {
+        if range.start < pos || range.end > data.len() {
+            return Err(VektError::InvalidSafetensor(format!(
+                "Tensor data at bytes {:?} overlaps another tensor or the end of the file",
+                range
+            )));
+        }
+        skeleton.extend_from_slice(&data[pos..range.start]);
+        pos = range.end;
+    }
+    skeleton.extend_from_slice(&data[pos..]);
+    Ok(skeleton)
+}
+
+fn hash_parallel(data: &[u8]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_rayon(data);
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Hashes and counts everything written through it
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: blake3::Hasher,
+    written: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: blake3::Hasher::new(),
+            written: 0,
+        }
+    }
+
+    /// The writer, and the hash and size of what went through it
+    pub fn finish(self) -> (W, FileChecksum) {
+        let checksum = FileChecksum {
+            hash: self.hasher.finalize().to_hex().to_string(),
+            size: self.written,
+            skeleton: None,
+        };
+        (self.inner, checksum)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl VektManifest {
+    /// Writes the file the manifest was made from, byte for byte: the main file of a
+    /// manifest with a source layout, else the safetensors file rebuilt around its
+    /// skeleton. Fails for manifests added before checksums were recorded.
+    pub fn write_original<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if let Some(layout) = &self.layout {
+            return layout.main.write_to(self, writer);
+        }
+        let skeleton_hash = self
+            .checksum
+            .as_ref()
+            .and_then(|c| c.skeleton.as_deref())
+            .ok_or_else(|| {
+                VektError::InvalidManifest(
+                    "Manifest has no skeleton of its original file; re-add the model to record one"
+                        .to_string(),
+                )
+            })?;
+        let skeleton = read_verified(skeleton_hash, "skeleton")?;
+        let header_len = u64::from_le_bytes(skeleton[..8].try_into().unwrap()) as usize;
+        let json = std::str::from_utf8(&skeleton[8..8 + header_len]).map_err(|_| {
+            VektError::ManifestCorrupted("Skeleton header is not valid UTF-8".to_string())
+        })?;
+        let header = StreamHeader::parse(json)?;
+
+        let data_start = header.data_start as usize;
+        writer.write_all(&skeleton[..data_start])?;
+        let mut cursor = data_start;
+        let mut prev_end = 0;
+        for (_, name, meta) in &header.tensors {
+            let (begin, end) = meta.data_offsets;
+            let gap = begin - prev_end;
+            let filler = skeleton.get(cursor..cursor + gap).ok_or_else(|| {
+                VektError::ManifestCorrupted(format!("Skeleton ends before '{}'", name))
+            })?;
+            writer.write_all(filler)?;
+            cursor += gap;
+
+            let tensor = self.tensors.get(name).ok_or_else(|| {
+                VektError::InvalidManifest(format!(
+                    "Original file has tensor '{}', which the manifest doesn't",
+                    name
+                ))
+            })?;
+            let data = read_verified(&tensor.hash, name)?;
+            if data.len() != end - begin {
+                return Err(VektError::TensorCorruption(format!(
+                    "Tensor '{}' is {} bytes, the original file had {}",
+                    name,
+                    data.len(),
+                    end - begin
+                )));
+            }
+            writer.write_all(&data)?;
+            prev_end = end;
+        }
+        writer.write_all(&skeleton[cursor..])?;
+        Ok(())
+    }
+
+    /// Rebuilds the original file without writing it anywhere and checks it against
+    /// the recorded checksum
+    pub fn verify_checksum(&self) -> Result<FileChecksum> {
+        self.restore_exact_to_writer(&mut io::sink())
+    }
+
+    /// Checks that the file at `path` is the one the manifest was made from
+    pub fn verify_file(&self, path: &Path) -> Result<FileChecksum> {
+        let expected = self.recorded_checksum()?;
+        let actual = file_checksum(path)?;
+        expected.verify(&actual)?;
+        Ok(actual)
+    }
+
+    /// Restores the original file to `path`, checked against the recorded checksum
+    /// before it replaces anything there. Files a source layout references, such as
+    /// ONNX external data, are written alongside as usual; the checksum covers the
+    /// main file.
+    pub fn restore_exact(&self, path: &Path) -> Result<FileChecksum> {
+        self.recorded_checksum()?;
+        if self.layout.is_some() {
+            self.restore_layout(path)?;
+            return self.verify_file(path);
+        }
+
+        let tmp_path = path.with_extension("exact.tmp");
+        let written = (|| {
+            let mut writer = direct_io::create(&tmp_path)?;
+            let checksum = self.restore_exact_to_writer(&mut writer)?;
+            writer.finish()?.sync_all()?;
+            Ok(checksum)
+        })();
+        if written.is_ok() {
+            rename_replace(&tmp_path, path)?;
+        } else {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        written
+    }
+
+    /// Writes the original file to `writer`, failing at the end if it doesn't match
+    /// the recorded checksum
+    pub fn restore_exact_to_writer<W: Write>(&self, writer: &mut W) -> Result<FileChecksum> {
+        let expected = self.recorded_checksum()?;
+        let mut writer = HashingWriter::new(writer);
+        self.write_original(&mut writer)?;
+        let (_, actual) = writer.finish();
+        expected.verify(&actual)?;
+        Ok(actual)
+    }
+
+    fn recorded_checksum(&self) -> Result<&FileChecksum> {
+        self.checksum.as_ref().ok_or_else(|| {
+            VektError::InvalidManifest(
+                "Manifest has no checksum of its original file; re-add the model to record one"
+                    .to_string(),
+            )
+        })
+    }
+}
+
+impl FileChecksum {
+    /// Fails unless `actual` has the same hash and size
+    pub fn verify(&self, actual: &FileChecksum) -> Result<()> {
+        if actual.size != self.size || actual.hash != self.hash {
+            return Err(VektError::HashMismatch {
+                expected: format!("{} ({} bytes)", self.hash, self.size),
+                actual: format!("{} ({} bytes)", actual.hash, actual.size),
+            });
+        }
+        Ok(())
+    }
+}
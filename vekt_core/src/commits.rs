@@ -0,0 +1,455 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::refcount;
+use crate::refs::{self, Head};
+use crate::storage::VektManifest;
+use crate::utils::{find_manifest_files, get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A snapshot of every tracked manifest in the repository, linked to its parents.
+/// Commits only reference manifest objects, so all branches share one blob store.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Commit {
+    /// Root-relative manifest path (always `/`-separated) -> manifest object hash
+    pub manifests: BTreeMap<String, String>,
+    pub parents: Vec<String>,
+    pub message: String,
+    pub author: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Directory holding commit objects (.vekt/commits)
+pub fn commits_dir() -> PathBuf {
+    get_vekt_dir().join("commits")
+}
+
+/// The directory that contains `.vekt`; manifest paths in commits are relative to it
+pub fn repo_root() -> PathBuf {
+    let vekt_dir = get_vekt_dir();
+    vekt_dir
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+pub fn short_hash(hash: &str) -> &str {
+    &hash[..12.min(hash.len())]
+}
+
+pub fn store_commit(commit: &Commit) -> Result<String> {
+    let bytes = serde_json::to_vec_pretty(commit)?;
+    let hash = blobs::compute_object_hash(&bytes);
+    let dir = commits_dir();
+    let path = dir.join(&hash);
+    if !path.exists() {
+        fs::create_dir_all(&dir)?;
+        write_file_atomic(&path, &bytes)?;
+    }
+    Ok(hash)
+}
+
+pub fn load_commit(hash: &str) -> Result<Commit> {
+    let path = commits_dir().join(hash);
+    if !path.is_file() {
+        return Err(VektError::RefNotFound(format!("Commit {} not found", hash)));
+    }
+    let bytes = fs::read(&path)?;
+    crate::validation::verify_blob_hash(&bytes, hash)?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| VektError::ManifestCorrupted(format!("Commit {}: {}", hash, e)))
+}
+
+/// Resolves `HEAD`, a branch name, or a (possibly abbreviated) commit hash
+pub fn resolve_commit(spec: &str) -> Result<String> {
+    if spec == "HEAD" {
+        return refs::head_commit()?
+            .ok_or_else(|| VektError::RefNotFound("HEAD has no commits yet".to_string()));
+    }
+    if refs::validate_ref_name(spec).is_ok()
+        && let Some(hash) = refs::read_branch(spec)?
+    {
+        return Ok(hash);
+    }
+    if spec.len() >= 4 && spec.chars().all(|c| c.is_ascii_hexdigit()) {
+        let dir = commits_dir();
+        if dir.join(spec).is_file() {
+            return Ok(spec.to_string());
+        }
+        let mut matches = Vec::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let name = entry?.file_name().to_string_lossy().into_owned();
+                if name.starts_with(spec) && !name.ends_with(".tmp") {
+                    matches.push(name);
+                }
+            }
+        }
+        match matches.len() {
+            1 => return Ok(matches.remove(0)),
+            0 => {}
+            _ => {
+                return Err(VektError::RefNotFound(format!(
+                    "Commit prefix '{}' is ambiguous",
+                    spec
+                )));
+            }
+        }
+    }
+    Err(VektError::RefNotFound(format!(
+        "'{}' is not a branch or commit",
+        spec
+    )))
+}
+
+/// Turns `path` into the `/`-separated form used as a key in commits
+pub(crate) fn relative_key(root: &Path, path: &Path) -> Result<String> {
+    let root = root.canonicalize()?;
+    let absolute = path.canonicalize()?;
+    let relative = absolute.strip_prefix(&root).map_err(|_| {
+        VektError::InvalidManifest(format!(
+            "{} is outside the repository at {}",
+            path.display(),
+            root.display()
+        ))
+    })?;
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Hash the manifest at `path` would get as an object, or `None` if it doesn't exist
+fn working_manifest_hash(path: &Path) -> Result<Option<String>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let manifest = VektManifest::load(path)?;
+    let bytes = refs::manifest_object_bytes(&manifest)?;
+    Ok(Some(blobs::compute_object_hash(&bytes)))
+}
+
+/// Author recorded when none is given: the login name of the current user
+pub(crate) fn default_author() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Records the given manifests (or every manifest under the repository root when
+/// `paths` is empty) as a new commit on top of HEAD, and advances HEAD.
+///
+/// Explicit paths update only those entries of the parent snapshot; an empty list
+/// snapshots the working tree as-is, so deleted manifests drop out of the commit.
+pub fn commit(paths: &[PathBuf], message: &str) -> Result<String> {
+    let root = repo_root();
+    let parent = refs::head_commit()?;
+    let parent_manifests = match &parent {
+        Some(hash) => load_commit(hash)?.manifests,
+        None => BTreeMap::new(),
+    };
+
+    let mut manifests = if paths.is_empty() {
+        BTreeMap::new()
+    } else {
+        parent_manifests.clone()
+    };
+    let targets = if paths.is_empty() {
+        find_manifest_files(&root)?
+    } else {
+        paths.to_vec()
+    };
+    let mut objects = Vec::new();
+    for path in &targets {
+        let manifest = VektManifest::load(path)?;
+        let hash = refs::store_manifest_object(&manifest)?;
+        manifests.insert(relative_key(&root, path)?, hash.clone());
+        objects.push((hash, manifest));
+    }
+
+    if manifests.is_empty() {
+        return Err(VektError::NothingToCommit("no manifests found".to_string()));
+    }
+    if parent.is_some() && manifests == parent_manifests {
+        return Err(VektError::NothingToCommit("HEAD".to_string()));
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let commit = Commit {
+        manifests,
+        parents: parent.into_iter().collect(),
+        message: message.to_string(),
+        author: default_author(),
+        timestamp,
+    };
+    let hash = store_commit(&commit)?;
+
+    match refs::read_head()? {
+        Head::Branch(name) => refs::write_branch(&name, &hash)?,
+        Head::Detached(_) => refs::write_head(&Head::Detached(hash.clone()))?,
+    }
+    refcount::note_objects(objects.iter().map(|(hash, manifest)| (hash, manifest)));
+    Ok(hash)
+}
+
+/// Creates branch `name` at `start` (a branch or commit), or at HEAD when omitted
+pub fn create_branch(name: &str, start: Option<&str>) -> Result<String> {
+    if refs::read_branch(name)?.is_some() {
+        return Err(VektError::RefExists(name.to_string()));
+    }
+    let hash = resolve_commit(start.unwrap_or("HEAD"))?;
+    refs::write_branch(name, &hash)?;
+    Ok(hash)
+}
+
+/// Outcome of [`switch_branch`]
+#[derive(Debug, Default)]
+pub struct SwitchSummary {
+    pub written: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Checks out branch `name`: writes its manifests into the working tree, removes
+/// manifests tracked by the current commit but absent from the target, and points
+/// HEAD at the branch. Refuses to overwrite uncommitted manifest edits unless `force`.
+///
+/// Only manifests change; blobs are shared, so `vekt restore` materializes weights.
+pub fn switch_branch(name: &str, force: bool) -> Result<SwitchSummary> {
+    let target_hash = refs::read_branch(name)?
+        .ok_or_else(|| VektError::RefNotFound(format!("Branch '{}' not found", name)))?;
+    let target = load_commit(&target_hash)?.manifests;
+    let current = match refs::head_commit()? {
+        Some(hash) => load_commit(&hash)?.manifests,
+        None => BTreeMap::new(),
+    };
+
+    let root = repo_root();
+    let paths: BTreeSet<&String> = current.keys().chain(target.keys()).collect();
+    let mut working = HashMap::new();
+    let mut dirty = Vec::new();
+    for path in paths {
+        let hash = working_manifest_hash(&root.join(path))?;
+        let recorded = current.get(path);
+        let wanted = target.get(path);
+        if hash.as_ref() != recorded && hash.as_ref() != wanted {
+            dirty.push(path.clone());
+        }
+        working.insert(path.clone(), hash);
+    }
+    if !dirty.is_empty() && !force {
+        return Err(VektError::UncommittedChanges(dirty.join(", ")));
+    }
+
+    let mut summary = SwitchSummary::default();
+    for (path, hash) in &target {
+        if working.get(path).and_then(|h| h.as_ref()) == Some(hash) {
+            continue;
+        }
+        let manifest = refs::load_manifest_object(hash)?;
+        let destination = root.join(path);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        manifest.save(&destination)?;
+        summary.written.push(path.clone());
+    }
+    for path in current.keys() {
+        if !target.contains_key(path) {
+            let destination = root.join(path);
+            if destination.is_file() {
+                fs::remove_file(destination)?;
+            }
+            summary.removed.push(path.clone());
+        }
+    }
+
+    refs::write_head(&Head::Branch(name.to_string()))?;
+    Ok(summary)
+}
+
+/// Every commit reachable from `tips`, children before parents and otherwise
+/// newest first
+pub fn walk_history(tips: &[String]) -> Result<Vec<(String, Commit)>> {
+    let mut commits: HashMap<String, Commit> = HashMap::new();
+    let mut stack: Vec<String> = tips.to_vec();
+    while let Some(hash) = stack.pop() {
+        if commits.contains_key(&hash) {
+            continue;
+        }
+        let commit = load_commit(&hash)?;
+        stack.extend(commit.parents.iter().cloned());
+        commits.insert(hash, commit);
+    }
+
+    // Kahn's algorithm over child -> parent edges, preferring the newest ready commit
+    let mut children: HashMap<&str, usize> = HashMap::new();
+    for commit in commits.values() {
+        for parent in &commit.parents {
+            *children.entry(parent.as_str()).or_default() += 1;
+        }
+    }
+    let mut ready: Vec<&String> = commits
+        .keys()
+        .filter(|h| !children.contains_key(h.as_str()))
+        .collect();
+    let mut order = Vec::with_capacity(commits.len());
+    while !ready.is_empty() {
+        ready.sort_by_key(|h| (commits[*h].timestamp, (*h).clone()));
+        let hash = ready.pop().unwrap();
+        order.push(hash.clone());
+        for parent in &commits[hash].parents {
+            let remaining = children.get_mut(parent.as_str()).unwrap();
+            *remaining -= 1;
+            if *remaining == 0 {
+                ready.push(commits.get_key_value(parent).unwrap().0);
+            }
+        }
+    }
+
+    Ok(order
+        .into_iter()
+        .map(|hash| {
+            let commit = commits[&hash].clone();
+            (hash, commit)
+        })
+        .collect())
+}
+
+fn graph_line(lanes: &[Option<String>], marks: &[(usize, char)]) -> String {
+    let width = lanes.len().max(1) * 2;
+    let mut chars = vec![' '; width];
+    for (i, lane) in lanes.iter().enumerate() {
+        if lane.is_some() {
+            chars[i * 2] = '|';
+        }
+    }
+    for (pos, c) in marks {
+        if *pos < chars.len() {
+            chars[*pos] = *c;
+        }
+    }
+    chars.into_iter().collect::<String>().trim_end().to_string()
+}
+
+/// Renders history reachable from `tips`, one commit per line, decorated with the
+/// branch names in `decorations`. With `graph`, an ASCII lane diagram like
+/// `git log --graph` is drawn to the left of each commit.
+pub fn render_log(
+    tips: &[String],
+    graph: bool,
+    decorations: &HashMap<String, Vec<String>>,
+) -> Result<String> {
+    let history = walk_history(tips)?;
+    let mut out = String::new();
+    // Each lane holds the commit expected next in that column
+    let mut lanes: Vec<Option<String>> = Vec::new();
+
+    for (hash, commit) in &history {
+        let mut label = short_hash(hash).to_string();
+        if let Some(names) = decorations.get(hash) {
+            let _ = write!(label, " ({})", names.join(", "));
+        }
+        let summary = commit.message.lines().next().unwrap_or("");
+
+        if !graph {
+            let _ = writeln!(out, "{} {}", label, summary);
+            continue;
+        }
+
+        let column = match lanes.iter().position(|l| l.as_deref() == Some(hash)) {
+            Some(i) => i,
+            None => match lanes.iter().position(Option::is_none) {
+                Some(i) => {
+                    lanes[i] = Some(hash.clone());
+                    i
+                }
+                None => {
+                    lanes.push(Some(hash.clone()));
+                    lanes.len() - 1
+                }
+            },
+        };
+
+        // Lanes that were also waiting for this commit converge into it
+        let merging: Vec<usize> = lanes
+            .iter()
+            .enumerate()
+            .filter(|(i, l)| *i != column && l.as_deref() == Some(hash))
+            .map(|(i, _)| i)
+            .collect();
+        if !merging.is_empty() {
+            for i in &merging {
+                lanes[*i] = None;
+            }
+            let marks: Vec<(usize, char)> = merging.iter().map(|i| (i * 2 - 1, '/')).collect();
+            let _ = writeln!(out, "{}", graph_line(&lanes, &marks));
+        }
+
+        let _ = writeln!(
+            out,
+            "{} {} {}",
+            graph_line(&lanes, &[(column * 2, '*')]),
+            label,
+            summary
+        );
+
+        lanes[column] = commit.parents.first().cloned();
+        let mut forks = Vec::new();
+        for parent in commit.parents.iter().skip(1) {
+            if lanes.iter().any(|l| l.as_deref() == Some(parent)) {
+                continue;
+            }
+            lanes.push(Some(parent.clone()));
+            forks.push(((lanes.len() - 1) * 2 - 1, '\\'));
+        }
+        if !forks.is_empty() {
+            let _ = writeln!(out, "{}", graph_line(&lanes, &forks));
+        }
+        while lanes.last().is_some_and(Option::is_none) {
+            lanes.pop();
+        }
+    }
+    Ok(out)
+}
+
+/// Commit hashes of every branch and of a detached HEAD
+pub fn all_tips() -> Result<Vec<String>> {
+    let mut tips: Vec<String> = refs::list_branches()?
+        .into_iter()
+        .map(|(_, hash)| hash)
+        .collect();
+    if let Head::Detached(hash) = refs::read_head()? {
+        tips.push(hash);
+    }
+    let mut seen = HashSet::new();
+    tips.retain(|h| seen.insert(h.clone()));
+    Ok(tips)
+}
+
+/// Maps commit hashes to the branch names (and `HEAD`) pointing at them
+pub fn branch_decorations() -> Result<HashMap<String, Vec<String>>> {
+    let mut decorations: HashMap<String, Vec<String>> = HashMap::new();
+    let head = refs::read_head()?;
+    for (name, hash) in refs::list_branches()? {
+        let label = match &head {
+            Head::Branch(current) if *current == name => format!("HEAD -> {}", name),
+            _ => name,
+        };
+        decorations.entry(hash).or_default().push(label);
+    }
+    if let Head::Detached(hash) = head {
+        decorations
+            .entry(hash)
+            .or_default()
+            .insert(0, "HEAD".to_string());
+    }
+    Ok(decorations)
+}
@@ -1,4 +1,5 @@
 use crate::errors::{Result, VektError};
+use crate::utils::rename_replace;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::Path;
@@ -50,8 +51,9 @@ pub fn save_blob_with_compression(
     file.write_all(&[if compressed { 1u8 } else { 0u8 }])?;
     file.write_all(&final_data)?;
     file.sync_all()?;
+    drop(file);
 
-    fs::rename(tmp_path, blob_path)?;
+    rename_replace(&tmp_path, blob_path)?;
 
     Ok(compressed)
 }
@@ -0,0 +1,366 @@
+//! Layered configuration. Settings come from, in increasing precedence:
+//!
+//! 1. the user config, `$XDG_CONFIG_HOME/vekt/config.json` (by default
+//!    `~/.config/vekt/config.json`)
+//! 2. the repository config, `.vekt/config.json`
+//! 3. environment variables such as `VEKT_CONCURRENCY` or `VEKT_REMOTE_ORIGIN`
+//!
+//! Objects such as `remotes` are merged key by key, so a repository can add remotes
+//! to the ones every repository gets; any other value replaces the lower layer's.
+//! Keys are dotted paths into the JSON, e.g. `remotes.origin` or
+//! `credentials.origin.profile`.
+
+use crate::errors::{Result, VektError};
+use crate::storage::VektConfig;
+use crate::utils::{ensure_vekt_dir, find_vekt_root, write_file_atomic};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Top-level keys of [`VektConfig`]. Anything else is rejected by `set`, so typos
+/// don't silently do nothing.
+pub const KEYS: &[&str] = &[
+    "remotes",
+    "verify",
+    "storage_rules",
+    "remote_groups",
+    "credentials",
+    "max_store_size",
+    "direct_io",
+    "hash_algorithm",
+    "compression",
+    "concurrency",
+    "store_path",
+    "checks",
+];
+
+/// Environment variables that override a key, besides `VEKT_REMOTE_<NAME>` for
+/// `remotes.<name>`
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("VEKT_VERIFY", "verify"),
+    ("VEKT_MAX_STORE_SIZE", "max_store_size"),
+    ("VEKT_HASH_ALGORITHM", "hash_algorithm"),
+    ("VEKT_COMPRESSION", "compression"),
+    ("VEKT_CONCURRENCY", "concurrency"),
+    ("VEKT_STORE_PATH", "store_path"),
+];
+
+const REMOTE_ENV_PREFIX: &str = "VEKT_REMOTE_";
+
+/// Where a setting comes from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigScope {
+    User,
+    Repo,
+    Env,
+}
+
+impl fmt::Display for ConfigScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigScope::User => "user",
+            ConfigScope::Repo => "repo",
+            ConfigScope::Env => "env",
+        })
+    }
+}
+
+/// One set key: its effective value and the layer it comes from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: Value,
+    pub scope: ConfigScope,
+}
+
+/// `$XDG_CONFIG_HOME/vekt/config.json`, falling back to `~/.config` and, on
+/// Windows, `%APPDATA%`
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("vekt").join("config.json"))
+}
+
+/// `.vekt/config.json` of the enclosing repository, or of the current directory
+/// outside one
+pub fn repo_config_path() -> Result<PathBuf> {
+    let root = match find_vekt_root() {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    Ok(root.join(".vekt").join("config.json"))
+}
+
+fn file_layer(path: &std::path::Path) -> Result<Map<String, Value>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Map::new()),
+        Err(e) => {
+            return Err(VektError::Io(std::io::Error::other(format!(
+                "Failed to open config file at {}: {}",
+                path.display(),
+                e
+            ))));
+        }
+    };
+    match serde_json::from_slice(&bytes) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(VektError::InvalidConfig(format!(
+            "Config file at {} is not a JSON object",
+            path.display()
+        ))),
+        Err(e) => Err(VektError::InvalidConfig(format!(
+            "Failed to parse config file at {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn env_layer() -> Map<String, Value> {
+    let mut layer = Map::new();
+    for (var, key) in ENV_OVERRIDES {
+        if let Ok(value) = std::env::var(var) {
+            layer.insert(key.to_string(), parse_value(&value));
+        }
+    }
+    let mut remotes = Map::new();
+    for (var, url) in std::env::vars() {
+        if let Some(name) = var.strip_prefix(REMOTE_ENV_PREFIX)
+            && !name.is_empty()
+        {
+            remotes.insert(name.to_ascii_lowercase(), Value::String(url));
+        }
+    }
+    if !remotes.is_empty() {
+        layer.insert("remotes".to_string(), Value::Object(remotes));
+    }
+    layer
+}
+
+/// The settings one layer holds, without the others
+pub fn read_layer(scope: ConfigScope) -> Result<Map<String, Value>> {
+    match scope {
+        ConfigScope::User => match user_config_path() {
+            Some(path) => file_layer(&path),
+            None => Ok(Map::new()),
+        },
+        ConfigScope::Repo => file_layer(&repo_config_path()?),
+        ConfigScope::Env => Ok(env_layer()),
+    }
+}
+
+fn write_layer(scope: ConfigScope, layer: Map<String, Value>) -> Result<()> {
+    let path = match scope {
+        ConfigScope::User => user_config_path().ok_or_else(|| {
+            VektError::Io(std::io::Error::other(
+                "No user config directory; set XDG_CONFIG_HOME or HOME",
+            ))
+        })?,
+        ConfigScope::Repo => repo_config_path()?,
+        ConfigScope::Env => {
+            return Err(VektError::InvalidConfig(
+                "Environment overrides can't be written".to_string(),
+            ));
+        }
+    };
+    if let Some(dir) = path.parent() {
+        match scope {
+            ConfigScope::Repo => ensure_vekt_dir(dir)?,
+            _ => fs::create_dir_all(dir)?,
+        }
+    }
+    let json = serde_json::to_string_pretty(&Value::Object(layer))?;
+    write_file_atomic(&path, json.as_bytes()).map_err(|e| {
+        VektError::Io(std::io::Error::other(format!(
+            "Failed to write config file {}: {}",
+            path.display(),
+            e
+        )))
+    })?;
+    Ok(())
+}
+
+/// Merges `upper` into `lower`: objects key by key, anything else replaced
+fn merge(lower: &mut Map<String, Value>, upper: Map<String, Value>) {
+    for (key, value) in upper {
+        match (lower.get_mut(&key), value) {
+            (Some(Value::Object(lower)), Value::Object(upper)) => merge(lower, upper),
+            (_, value) => {
+                lower.insert(key, value);
+            }
+        }
+    }
+}
+
+/// All layers merged by precedence
+pub fn merged() -> Result<Map<String, Value>> {
+    let mut config = Map::new();
+    for scope in [ConfigScope::User, ConfigScope::Repo, ConfigScope::Env] {
+        merge(&mut config, read_layer(scope)?);
+    }
+    Ok(config)
+}
+
+/// Parses a value given on the command line or in the environment: JSON when it is
+/// (`32`, `true`, `[...]`), a plain string otherwise (`zstd`, `s3://bucket`)
+pub fn parse_value(input: &str) -> Value {
+    serde_json::from_str(input).unwrap_or_else(|_| Value::String(input.to_string()))
+}
+
+fn split_key(key: &str) -> Result<Vec<&str>> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(VektError::InvalidConfig(format!(
+            "Invalid config key '{}'",
+            key
+        )));
+    }
+    if !KEYS.contains(&parts[0]) {
+        return Err(VektError::InvalidConfig(format!(
+            "Unknown config key '{}'. Known keys: {}",
+            parts[0],
+            KEYS.join(", ")
+        )));
+    }
+    Ok(parts)
+}
+
+fn lookup<'a>(config: &'a Map<String, Value>, parts: &[&str]) -> Option<&'a Value> {
+    let (first, rest) = parts.split_first()?;
+    rest.iter()
+        .try_fold(config.get(*first)?, |value, part| value.get(*part))
+}
+
+/// Effective value of `key`, `None` when no layer sets it
+pub fn get(key: &str) -> Result<Option<Value>> {
+    let parts = split_key(key)?;
+    Ok(lookup(&merged()?, &parts).cloned())
+}
+
+/// Checks that `layer` still deserializes into a valid config
+fn validate(layer: &Map<String, Value>) -> Result<()> {
+    let config: VektConfig = serde_json::from_value(Value::Object(layer.clone()))
+        .map_err(|e| VektError::InvalidConfig(e.to_string()))?;
+    config.max_store_size()?;
+    Ok(())
+}
+
+/// Sets `key` in the user or repository config. Fails without writing anything if
+/// the value doesn't fit the key.
+pub fn set(scope: ConfigScope, key: &str, value: Value) -> Result<()> {
+    let parts = split_key(key)?;
+    let mut layer = read_layer(scope)?;
+    let (last, parents) = parts.split_last().expect("keys have at least one part");
+    let mut map = &mut layer;
+    for part in parents {
+        let entry = map
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        map = entry.as_object_mut().expect("just made an object");
+    }
+    map.insert(last.to_string(), value);
+    validate(&layer)?;
+    write_layer(scope, layer)
+}
+
+/// Removes `key` from the user or repository config. Returns whether it was set.
+pub fn unset(scope: ConfigScope, key: &str) -> Result<bool> {
+    let parts = split_key(key)?;
+    let mut layer = read_layer(scope)?;
+    let (last, parents) = parts.split_last().expect("keys have at least one part");
+    let mut map = &mut layer;
+    for part in parents {
+        match map.get_mut(*part).and_then(Value::as_object_mut) {
+            Some(inner) => map = inner,
+            None => return Ok(false),
+        }
+    }
+    if map.remove(*last).is_none() {
+        return Ok(false);
+    }
+    write_layer(scope, layer)?;
+    Ok(true)
+}
+
+/// Collects the leaves of `value` under their dotted keys. Empty objects are left
+/// out; arrays are leaves.
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, out);
+            }
+        }
+        value => out.push((prefix.to_string(), value.clone())),
+    }
+}
+
+/// Every set key with its effective value and the layer it comes from, sorted by key
+pub fn list() -> Result<Vec<ConfigEntry>> {
+    let mut entries: Vec<ConfigEntry> = Vec::new();
+    for scope in [ConfigScope::User, ConfigScope::Repo, ConfigScope::Env] {
+        let mut leaves = Vec::new();
+        flatten("", &Value::Object(read_layer(scope)?), &mut leaves);
+        for (key, value) in leaves {
+            // A higher layer replaces a lower one's value, and a whole subtree when
+            // either side isn't an object
+            entries.retain(|entry| {
+                entry.key != key
+                    && !entry.key.starts_with(&format!("{}.", key))
+                    && !key.starts_with(&format!("{}.", entry.key))
+            });
+            entries.push(ConfigEntry { key, value, scope });
+        }
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_and_flatten() {
+        let mut lower = json!({"remotes": {"a": "s3://a"}, "verify": "never"})
+            .as_object()
+            .unwrap()
+            .clone();
+        let upper = json!({"remotes": {"b": "s3://b"}, "verify": "always"})
+            .as_object()
+            .unwrap()
+            .clone();
+        merge(&mut lower, upper);
+        assert_eq!(
+            Value::Object(lower.clone()),
+            json!({"remotes": {"a": "s3://a", "b": "s3://b"}, "verify": "always"})
+        );
+
+        let mut leaves = Vec::new();
+        flatten("", &Value::Object(lower), &mut leaves);
+        let keys: Vec<&str> = leaves.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["remotes.a", "remotes.b", "verify"]);
+
+        assert_eq!(parse_value("32"), json!(32));
+        assert_eq!(parse_value("zstd"), json!("zstd"));
+        assert!(split_key("remotes.origin").is_ok());
+        assert!(split_key("remote.origin").is_err());
+        assert!(split_key("remotes..origin").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::errors::{Result, VektError, exit_code};
+use crate::utils::get_vekt_dir;
+use futures::channel::{mpsc, oneshot};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Default socket of `vekt daemon` (.vekt/daemon.sock)
+pub fn socket_path() -> PathBuf {
+    get_vekt_dir().join("daemon.sock")
+}
+
+/// One operation sent to the daemon, as a JSON object on its own line, e.g.
+/// `{"op":"add","path":"/ckpt/model.safetensors"}`. Relative paths are resolved
+/// against the daemon's working directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum DaemonRequest {
+    Add {
+        path: PathBuf,
+        #[serde(default)]
+        rehash: bool,
+    },
+    Restore {
+        /// Manifest file, tag or branch
+        manifest: String,
+        #[serde(default)]
+        layers: Option<String>,
+    },
+    Push {
+        remote: String,
+        /// Manifest files or tags; empty pushes everything, like `vekt push`
+        #[serde(default)]
+        refs: Vec<String>,
+    },
+    Pull {
+        remote: String,
+    },
+    Status,
+    Ping,
+    /// Stops the daemon after answering
+    Shutdown,
+}
+
+/// The daemon's answer to one request, on its own line. Failures carry the exit code
+/// the same command would have exited with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DaemonResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+impl DaemonResponse {
+    pub fn success(result: serde_json::Value) -> Self {
+        DaemonResponse {
+            ok: true,
+            result: Some(result),
+            error: None,
+            exit_code: None,
+        }
+    }
+
+    pub fn failure(error: impl ToString, code: i32) -> Self {
+        DaemonResponse {
+            ok: false,
+            result: None,
+            error: Some(error.to_string()),
+            exit_code: Some(code),
+        }
+    }
+}
+
+impl From<VektError> for DaemonResponse {
+    fn from(e: VektError) -> Self {
+        DaemonResponse::failure(&e, e.exit_code())
+    }
+}
+
+type Queued = (DaemonRequest, oneshot::Sender<DaemonResponse>);
+
+/// Accepts connections on `listener` and runs their requests through `handler` one at
+/// a time, in arrival order, so a single process issues every operation. Any number
+/// of clients may be connected; each gets its answers in the order it asked. Returns
+/// after a [`DaemonRequest::Shutdown`].
+///
+/// `handler` runs on the calling task, so its future doesn't have to be `Send`.
+pub async fn serve<H, F>(listener: UnixListener, mut handler: H) -> Result<()>
+where
+    H: FnMut(DaemonRequest) -> F,
+    F: Future<Output = DaemonResponse>,
+{
+    let (sender, mut queue) = mpsc::channel::<Queued>(64);
+    let acceptor = tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(connection(stream, sender.clone()));
+        }
+    });
+
+    while let Some((request, reply)) = queue.next().await {
+        let shutdown = request == DaemonRequest::Shutdown;
+        let response = match shutdown {
+            true => DaemonResponse::success(serde_json::Value::Null),
+            false => handler(request).await,
+        };
+        let _ = reply.send(response);
+        if shutdown {
+            break;
+        }
+    }
+    acceptor.abort();
+    Ok(())
+}
+
+/// Reads requests from one client and writes back each answer once it is handled
+async fn connection(stream: UnixStream, mut queue: mpsc::Sender<Queued>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<DaemonRequest>(&line) {
+            Ok(request) => {
+                let (reply, answer) = oneshot::channel();
+                if queue.send((request, reply)).await.is_err() {
+                    break;
+                }
+                match answer.await {
+                    Ok(response) => response,
+                    Err(_) => break,
+                }
+            }
+            Err(e) => DaemonResponse::failure(format!("Invalid request: {}", e), exit_code::USAGE),
+        };
+        let Ok(mut json) = serde_json::to_string(&response) else {
+            break;
+        };
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Client for a running `vekt daemon`
+pub struct DaemonClient {
+    lines: tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
+    writer: tokio::net::unix::OwnedWriteHalf,
+}
+
+impl DaemonClient {
+    pub async fn connect(path: &Path) -> Result<Self> {
+        let (reader, writer) = UnixStream::connect(path).await?.into_split();
+        Ok(DaemonClient {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        })
+    }
+
+    /// Sends one request and waits for its answer
+    pub async fn request(&mut self, request: &DaemonRequest) -> Result<DaemonResponse> {
+        let mut json = serde_json::to_string(request)?;
+        json.push('\n');
+        self.writer.write_all(json.as_bytes()).await?;
+        let line = self.lines.next_line().await?.ok_or_else(|| {
+            VektError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Daemon closed the connection",
+            ))
+        })?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::storage::VektManifest;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 #[derive(Debug)]
 pub struct TensorDiff {
@@ -7,6 +7,104 @@ pub struct TensorDiff {
     pub removed: Vec<String>,
     pub modified: Vec<String>,
     pub unchanged: Vec<String>,
+    /// Modified tensors whose shape changed, e.g. an embedding after a vocab resize
+    pub resized: Vec<ShapeChange>,
+    /// Tensors whose data moved to another name; they are in neither `added` nor
+    /// `removed`
+    pub renamed: Vec<TensorRename>,
+}
+
+/// A tensor that only changed its name, e.g. `transformer.h.0.attn.weight` ->
+/// `model.layers.0.attn.weight`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorRename {
+    pub old: String,
+    pub new: String,
+}
+
+/// A single dimension that grew or shrank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DimChange {
+    pub dim: usize,
+    pub old: usize,
+    pub new: usize,
+}
+
+impl DimChange {
+    pub fn delta(&self) -> i64 {
+        self.new as i64 - self.old as i64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeChange {
+    pub name: String,
+    pub old_shape: Vec<usize>,
+    pub new_shape: Vec<usize>,
+    /// Per-dimension changes; empty when the rank itself changed
+    pub dims: Vec<DimChange>,
+}
+
+impl ShapeChange {
+    /// Compares two shapes, returning `None` when they are identical
+    pub fn between(name: &str, old_shape: &[usize], new_shape: &[usize]) -> Option<Self> {
+        if old_shape == new_shape {
+            return None;
+        }
+        let dims = if old_shape.len() == new_shape.len() {
+            old_shape
+                .iter()
+                .zip(new_shape)
+                .enumerate()
+                .filter(|(_, (old, new))| old != new)
+                .map(|(dim, (old, new))| DimChange {
+                    dim,
+                    old: *old,
+                    new: *new,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Some(ShapeChange {
+            name: name.to_string(),
+            old_shape: old_shape.to_vec(),
+            new_shape: new_shape.to_vec(),
+            dims,
+        })
+    }
+
+    pub fn rank_changed(&self) -> bool {
+        self.old_shape.len() != self.new_shape.len()
+    }
+
+    /// Human-readable description such as `dim 0: 32000 -> 32064 (+64)`
+    pub fn describe(&self) -> String {
+        if self.rank_changed() {
+            return format!(
+                "rank {} -> {} ({:?} -> {:?})",
+                self.old_shape.len(),
+                self.new_shape.len(),
+                self.old_shape,
+                self.new_shape
+            );
+        }
+        self.dims
+            .iter()
+            .map(|d| {
+                let verb = if d.new > d.old { "grew" } else { "shrank" };
+                format!(
+                    "dim {} {}: {} -> {} ({:+})",
+                    d.dim,
+                    verb,
+                    d.old,
+                    d.new,
+                    d.delta()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 #[derive(Debug)]
@@ -33,6 +131,7 @@ impl VektManifest {
         let mut removed = Vec::new();
         let mut modified = Vec::new();
         let mut unchanged = Vec::new();
+        let mut resized = Vec::new();
 
         let self_keys: HashSet<_> = self.tensors.keys().collect();
         let other_keys: HashSet<_> = other.tensors.keys().collect();
@@ -47,6 +146,8 @@ impl VektManifest {
             removed.push((*key).clone());
         }
 
+        let renamed = pair_renames(self, other, &mut removed, &mut added);
+
         // Find modified and unchanged tensors
         for key in self_keys.intersection(&other_keys) {
             let self_tensor = &self.tensors[*key];
@@ -54,6 +155,11 @@ impl VektManifest {
 
             if self_tensor.hash != other_tensor.hash {
                 modified.push((*key).clone());
+                if let Some(change) =
+                    ShapeChange::between(key, &self_tensor.shape, &other_tensor.shape)
+                {
+                    resized.push(change);
+                }
             } else {
                 unchanged.push((*key).clone());
             }
@@ -70,6 +176,8 @@ impl VektManifest {
                 removed,
                 modified,
                 unchanged,
+                resized,
+                renamed,
             },
             size_change,
             storage_savings,
@@ -112,6 +220,18 @@ impl VektManifest {
             }
         }
 
+        if !diff.renamed.is_empty() {
+            println!("\nRenamed Tensors ({}):", diff.renamed.len());
+            for rename in &diff.renamed {
+                println!(
+                    "  > {} -> {} [hash: {}]",
+                    rename.old,
+                    rename.new,
+                    &other.tensors[&rename.new].hash[..8]
+                );
+            }
+        }
+
         if !diff.modified.is_empty() {
             println!("\nModified Tensors ({}):", diff.modified.len());
             for name in &diff.modified {
@@ -129,6 +249,13 @@ impl VektManifest {
             }
         }
 
+        if !diff.resized.is_empty() {
+            println!("\nResized Tensors ({}):", diff.resized.len());
+            for change in &diff.resized {
+                println!("  ~ {}: {}", change.name, change.describe());
+            }
+        }
+
         println!("\nUnchanged Tensors: {}", diff.unchanged.len());
 
         let sign = if comparison.size_change >= 0 { "+" } else { "" };
@@ -159,6 +286,59 @@ impl VektManifest {
     }
 }
 
+/// Takes removed and added tensors with the same data, shape and dtype out of
+/// `removed` and `added` and pairs them up as renames. A removed tensor is paired
+/// with the candidate sharing the longest name suffix (`.0.attn.weight`), the first
+/// in name order on ties; unpaired tensors stay added or removed.
+fn pair_renames(
+    old: &VektManifest,
+    new: &VektManifest,
+    removed: &mut Vec<String>,
+    added: &mut Vec<String>,
+) -> Vec<TensorRename> {
+    removed.sort();
+    added.sort();
+    let mut candidates: BTreeMap<_, Vec<&String>> = BTreeMap::new();
+    for name in added.iter() {
+        let tensor = &new.tensors[name];
+        candidates
+            .entry((&tensor.hash, &tensor.shape, &tensor.dtype))
+            .or_default()
+            .push(name);
+    }
+
+    let mut renamed = Vec::new();
+    for name in removed.iter() {
+        let tensor = &old.tensors[name];
+        let Some(names) = candidates.get_mut(&(&tensor.hash, &tensor.shape, &tensor.dtype)) else {
+            continue;
+        };
+        let best = (0..names.len())
+            .rev()
+            .max_by_key(|&i| common_suffix(name, names[i]));
+        if let Some(i) = best {
+            renamed.push(TensorRename {
+                old: name.clone(),
+                new: names.remove(i).clone(),
+            });
+        }
+    }
+
+    let old_names: HashSet<&String> = renamed.iter().map(|r| &r.old).collect();
+    let new_names: HashSet<&String> = renamed.iter().map(|r| &r.new).collect();
+    removed.retain(|name| !old_names.contains(name));
+    added.retain(|name| !new_names.contains(name));
+    renamed
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.bytes()
+        .rev()
+        .zip(b.bytes().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
 fn calculate_storage_savings(old: &VektManifest, new: &VektManifest) -> StorageSavings {
     let mut old_hashes = HashSet::new();
     let mut new_hashes = HashSet::new();
@@ -208,6 +388,8 @@ mod tests {
             hash: hash.to_string(),
             index: 0,
             extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
         }
     }
 
@@ -222,14 +404,24 @@ mod tests {
 
         let old_manifest = VektManifest {
             tensors: old_tensors,
-            version: "1.0".to_string(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
             total_size: 100,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
         };
 
         let new_manifest = VektManifest {
             tensors: new_tensors,
-            version: "1.0".to_string(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
             total_size: 200,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
         };
 
         let comparison = old_manifest.diff(&new_manifest);
@@ -237,4 +429,79 @@ mod tests {
         assert_eq!(comparison.tensor_diff.removed.len(), 0);
         assert_eq!(comparison.tensor_diff.unchanged.len(), 1);
     }
+
+    #[test]
+    fn test_diff_reports_resized_dims() {
+        let mut old_tensors = BTreeMap::new();
+        old_tensors.insert(
+            "embed".to_string(),
+            create_test_tensor("hash1", vec![32000, 4096]),
+        );
+        let mut new_tensors = BTreeMap::new();
+        new_tensors.insert(
+            "embed".to_string(),
+            create_test_tensor("hash2", vec![32064, 4096]),
+        );
+
+        let manifest = |tensors| VektManifest {
+            tensors,
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: 0,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
+        };
+        let comparison = manifest(old_tensors).diff(&manifest(new_tensors));
+        let resized = &comparison.tensor_diff.resized;
+        assert_eq!(resized.len(), 1);
+        assert_eq!(
+            resized[0].dims,
+            vec![DimChange {
+                dim: 0,
+                old: 32000,
+                new: 32064
+            }]
+        );
+        assert_eq!(resized[0].describe(), "dim 0 grew: 32000 -> 32064 (+64)");
+    }
+
+    #[test]
+    fn test_diff_pairs_renames_by_hash() {
+        let manifest = |tensors: Vec<(&str, &str)>| VektManifest {
+            tensors: tensors
+                .into_iter()
+                .map(|(name, hash)| (name.to_string(), create_test_tensor(hash, vec![4])))
+                .collect(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: 0,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
+        };
+        let old = manifest(vec![
+            ("transformer.h.0.w", "hash1"),
+            ("transformer.h.1.w", "hash2"),
+            ("dropped", "hash3"),
+        ]);
+        let new = manifest(vec![
+            ("model.layers.0.w", "hash1"),
+            ("model.layers.1.w", "hash4"),
+            ("extra", "hash1"),
+        ]);
+
+        let diff = old.diff(&new).tensor_diff;
+        assert_eq!(
+            diff.renamed,
+            vec![TensorRename {
+                old: "transformer.h.0.w".to_string(),
+                new: "model.layers.0.w".to_string(),
+            }]
+        );
+        assert_eq!(diff.added, vec!["extra", "model.layers.1.w"]);
+        assert_eq!(diff.removed, vec!["dropped", "transformer.h.1.w"]);
+    }
 }
@@ -0,0 +1,168 @@
+//! Opt-in direct I/O for blob writes and restores on Linux. Files are opened with
+//! `O_DIRECT` and written with positioned writes from aligned buffers, so ingesting
+//! a checkpoint of hundreds of gigabytes doesn't copy every byte through the page
+//! cache. Elsewhere, and on filesystems that reject `O_DIRECT` (tmpfs, some network
+//! filesystems), files are written through a buffer as usual.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set to `1` to turn direct I/O on, like `"direct_io": true` in `.vekt/config.json`
+pub const DIRECT_IO_ENV: &str = "VEKT_DIRECT_IO";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns direct I/O on or off for this process
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether files are written with direct I/O
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed) || std::env::var(DIRECT_IO_ENV).as_deref() == Ok("1")
+}
+
+/// A new file being written front to back, directly when [`enabled`]. Call
+/// [`finish`](Self::finish) once everything is written: a direct writer holds the
+/// last partial block until then.
+pub struct FileWriter {
+    inner: Inner,
+}
+
+enum Inner {
+    Buffered(BufWriter<File>),
+    #[cfg(target_os = "linux")]
+    Direct(linux::DirectWriter),
+}
+
+/// Creates (or truncates) `path` for writing
+pub fn create(path: &Path) -> io::Result<FileWriter> {
+    #[cfg(target_os = "linux")]
+    if enabled()
+        && let Some(writer) = linux::DirectWriter::create(path)?
+    {
+        return Ok(FileWriter {
+            inner: Inner::Direct(writer),
+        });
+    }
+    Ok(FileWriter {
+        inner: Inner::Buffered(BufWriter::new(File::create(path)?)),
+    })
+}
+
+impl FileWriter {
+    /// Writes out whatever is still buffered and returns the file, at its final length
+    pub fn finish(self) -> io::Result<File> {
+        match self.inner {
+            Inner::Buffered(writer) => writer.into_inner().map_err(|e| e.into_error()),
+            #[cfg(target_os = "linux")]
+            Inner::Direct(writer) => writer.finish(),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Buffered(writer) => writer.write(buf),
+            #[cfg(target_os = "linux")]
+            Inner::Direct(writer) => writer.write(buf),
+        }
+    }
+
+    /// Flushes a buffered writer. A direct writer can only write whole blocks before
+    /// [`finish`](Self::finish), so it keeps the partial block it holds.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Buffered(writer) => writer.flush(),
+            #[cfg(target_os = "linux")]
+            Inner::Direct(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::fs::{FileExt, OpenOptionsExt};
+    use std::path::Path;
+
+    /// Offsets, lengths and buffer addresses of `O_DIRECT` writes are multiples of
+    /// this. 4 KiB covers the logical block size of every common device.
+    const ALIGN: usize = 4096;
+
+    /// Bytes handed to the kernel per write
+    const BUFFER_SIZE: usize = 8 << 20;
+
+    pub(super) struct DirectWriter {
+        file: File,
+        /// Over-allocated so an aligned `BUFFER_SIZE` window fits; see `start`
+        buffer: Vec<u8>,
+        start: usize,
+        filled: usize,
+        /// File offset the buffer is written at
+        offset: u64,
+    }
+
+    impl DirectWriter {
+        /// Opens `path` for direct writes, or returns `None` if its filesystem
+        /// doesn't support them
+        pub(super) fn create(path: &Path) -> io::Result<Option<Self>> {
+            let file = match OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .custom_flags(libc::O_DIRECT)
+                .open(path)
+            {
+                Ok(file) => file,
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let buffer = vec![0; BUFFER_SIZE + ALIGN];
+            let start = buffer.as_ptr().align_offset(ALIGN);
+            Ok(Some(DirectWriter {
+                file,
+                buffer,
+                start,
+                filled: 0,
+                offset: 0,
+            }))
+        }
+
+        pub(super) fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            let n = data.len().min(BUFFER_SIZE - self.filled);
+            let at = self.start + self.filled;
+            self.buffer[at..at + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            if self.filled == BUFFER_SIZE {
+                self.write_buffer(BUFFER_SIZE)?;
+                self.offset += BUFFER_SIZE as u64;
+                self.filled = 0;
+            }
+            Ok(n)
+        }
+
+        /// Writes the last partial block padded to a whole one, then cuts the
+        /// padding off again
+        pub(super) fn finish(mut self) -> io::Result<File> {
+            let len = self.offset + self.filled as u64;
+            if self.filled > 0 {
+                let padded = self.filled.next_multiple_of(ALIGN);
+                let at = self.start + self.filled;
+                self.buffer[at..self.start + padded].fill(0);
+                self.write_buffer(padded)?;
+                self.file.set_len(len)?;
+            }
+            Ok(self.file)
+        }
+
+        fn write_buffer(&self, len: usize) -> io::Result<()> {
+            self.file
+                .write_all_at(&self.buffer[self.start..self.start + len], self.offset)
+        }
+    }
+}
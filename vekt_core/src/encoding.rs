@@ -0,0 +1,319 @@
+use crate::blob_index;
+use crate::blobs;
+use crate::compression::compress_blob;
+use crate::errors::{Result, VektError};
+use crate::storage::VektManifest;
+use crate::utils::write_file_atomic;
+use crate::validation::verify_blob_hash;
+use crate::watch::matches_pattern;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::Path;
+
+/// Chunk size of [`BlobEncoding::Chunked`] blobs
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+const HASH_LEN: usize = 64;
+
+/// How a blob is kept on disk. Blobs are always addressed by the hash of their
+/// decoded bytes, so the encoding can change without touching any manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobEncoding {
+    /// The bytes as they are, at `blobs/<hash>`
+    #[default]
+    Raw,
+    /// zstd-compressed, at `blobs/<hash>.zst`
+    Zstd,
+    /// Split into chunk blobs listed at `blobs/<hash>.chunks`, so a new version of a
+    /// large tensor with a few changed rows only stores the chunks that differ
+    Chunked,
+    /// zstd-compressed XOR against a base blob of the same size, at `blobs/<hash>.delta`
+    Delta,
+}
+
+impl BlobEncoding {
+    pub const ENCODED: [BlobEncoding; 3] = [
+        BlobEncoding::Zstd,
+        BlobEncoding::Chunked,
+        BlobEncoding::Delta,
+    ];
+
+    /// Store file extension, `None` for raw blobs
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            BlobEncoding::Raw => None,
+            BlobEncoding::Zstd => Some("zst"),
+            BlobEncoding::Chunked => Some("chunks"),
+            BlobEncoding::Delta => Some("delta"),
+        }
+    }
+}
+
+/// Picks the storage encoding for tensors by name and/or dtype. The first matching
+/// rule in the config's `storage_rules` wins.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageRule {
+    /// Tensor name pattern with `*` and `?` wildcards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tensors: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtype: Option<String>,
+    pub encoding: BlobEncoding,
+}
+
+impl StorageRule {
+    pub fn matches(&self, name: &str, dtype: &str) -> bool {
+        self.tensors
+            .as_deref()
+            .is_none_or(|pattern| matches_pattern(pattern, name))
+            && self
+                .dtype
+                .as_deref()
+                .is_none_or(|d| d.eq_ignore_ascii_case(dtype))
+    }
+}
+
+/// Encoding of the first rule matching the tensor; raw when none does
+pub fn encoding_for(rules: &[StorageRule], name: &str, dtype: &str) -> BlobEncoding {
+    rules
+        .iter()
+        .find(|rule| rule.matches(name, dtype))
+        .map_or(BlobEncoding::Raw, |rule| rule.encoding)
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkList {
+    size: u64,
+    chunks: Vec<String>,
+}
+
+fn invalid(path: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Encoded blob {} is corrupt: {}", path.display(), reason),
+    )
+}
+
+/// Splits a delta file into its base hash and compressed XOR
+fn split_delta<'a>(path: &Path, data: &'a [u8]) -> io::Result<(String, &'a [u8])> {
+    if data.len() < HASH_LEN + 1 || data[HASH_LEN] != b'\n' {
+        return Err(invalid(path, "missing base hash"));
+    }
+    let base =
+        std::str::from_utf8(&data[..HASH_LEN]).map_err(|_| invalid(path, "bad base hash"))?;
+    Ok((base.to_string(), &data[HASH_LEN + 1..]))
+}
+
+/// Reads the file of an encoded blob back into the original bytes
+pub(crate) fn decode(path: &Path, encoding: BlobEncoding) -> io::Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    match encoding {
+        BlobEncoding::Raw => Ok(data),
+        BlobEncoding::Zstd => zstd::decode_all(data.as_slice()),
+        BlobEncoding::Chunked => {
+            let list: ChunkList =
+                serde_json::from_slice(&data).map_err(|e| invalid(path, &e.to_string()))?;
+            let mut out = Vec::with_capacity(list.size as usize);
+            for chunk in &list.chunks {
+                out.extend_from_slice(&blobs::read_blob(chunk)?);
+            }
+            if out.len() as u64 != list.size {
+                return Err(invalid(path, "chunks do not add up to the blob size"));
+            }
+            Ok(out)
+        }
+        BlobEncoding::Delta => {
+            let (base, diff) = split_delta(path, &data)?;
+            let mut out = blobs::read_blob(&base)?;
+            let diff = zstd::decode_all(diff)?;
+            if diff.len() != out.len() {
+                return Err(invalid(path, "size differs from its base"));
+            }
+            out.iter_mut().zip(&diff).for_each(|(a, b)| *a ^= b);
+            Ok(out)
+        }
+    }
+}
+
+/// Other blobs an encoded blob is built from: its chunks, or its delta base
+pub fn dependencies(hash: &str) -> Result<Vec<String>> {
+    match blobs::find_blob(hash) {
+        Some((path, BlobEncoding::Chunked)) => {
+            let list: ChunkList = serde_json::from_slice(&std::fs::read(&path)?)?;
+            Ok(list.chunks)
+        }
+        Some((path, BlobEncoding::Delta)) => {
+            let data = std::fs::read(&path)?;
+            Ok(vec![split_delta(&path, &data)?.0])
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// `hashes` plus every blob they are built from, transitively
+pub fn with_dependencies<'a>(
+    hashes: impl IntoIterator<Item = &'a String>,
+) -> Result<HashSet<String>> {
+    let mut all: HashSet<String> = HashSet::new();
+    let mut pending: Vec<String> = hashes.into_iter().cloned().collect();
+    while let Some(hash) = pending.pop() {
+        if all.insert(hash.clone()) {
+            pending.extend(dependencies(&hash)?);
+        }
+    }
+    Ok(all)
+}
+
+/// Rewrites the stored blob `hash` (whose bytes are `data`) in `encoding` and
+/// removes its other forms. Falls back to a simpler encoding when the requested one
+/// doesn't apply: zstd that doesn't shrink the data, chunking a blob smaller than
+/// one chunk, or a delta without a same-sized base. Returns the encoding used.
+pub fn store_encoded(
+    hash: &str,
+    data: &[u8],
+    encoding: BlobEncoding,
+    base: Option<&str>,
+) -> Result<BlobEncoding> {
+    let raw_path = blobs::get_blob_path(hash);
+    let contents = match encoding {
+        BlobEncoding::Raw => None,
+        BlobEncoding::Zstd => Some(compress_blob(data)?).filter(|c| c.len() < data.len()),
+        BlobEncoding::Chunked if data.len() > CHUNK_SIZE => {
+            let mut chunks = Vec::new();
+            for chunk in data.chunks(CHUNK_SIZE) {
+                chunks.push(blobs::save_blob_deduplicated(chunk)?.0);
+            }
+            let list = ChunkList {
+                size: data.len() as u64,
+                chunks,
+            };
+            Some(serde_json::to_vec(&list)?)
+        }
+        BlobEncoding::Chunked => None,
+        BlobEncoding::Delta => match delta_root(hash, base)? {
+            Some((root, base_data)) if base_data.len() == data.len() => {
+                let diff: Vec<u8> = data.iter().zip(&base_data).map(|(a, b)| a ^ b).collect();
+                let mut contents = root.into_bytes();
+                contents.push(b'\n');
+                contents.extend_from_slice(&compress_blob(&diff)?);
+                Some(contents)
+            }
+            _ => return store_encoded(hash, data, BlobEncoding::Zstd, None),
+        },
+    };
+
+    // Write the new form before removing the old one, so the blob never disappears
+    let used = match contents {
+        Some(contents) => {
+            let path = raw_path.with_extension(encoding.extension().unwrap());
+            write_file_atomic(&path, &contents)?;
+            blob_index::record_written(&path);
+            encoding
+        }
+        None => {
+            if !raw_path.exists() {
+                write_file_atomic(&raw_path, data)?;
+                blob_index::record_written(&raw_path);
+            }
+            BlobEncoding::Raw
+        }
+    };
+    for other in BlobEncoding::ENCODED {
+        if other != used {
+            let path = raw_path.with_extension(other.extension().unwrap());
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+                blob_index::record_removed(&path);
+            }
+        }
+    }
+    if used != BlobEncoding::Raw && raw_path.exists() {
+        std::fs::remove_file(&raw_path)?;
+        blob_index::record_removed(&raw_path);
+    }
+    Ok(used)
+}
+
+/// The blob a delta for `hash` is taken against, and its bytes. Deltas are always
+/// against a non-delta blob so reading one never walks a chain.
+fn delta_root(hash: &str, base: Option<&str>) -> Result<Option<(String, Vec<u8>)>> {
+    let Some(mut base) = base.filter(|b| *b != hash).map(str::to_string) else {
+        return Ok(None);
+    };
+    if let Some((_, BlobEncoding::Delta)) = blobs::find_blob(&base) {
+        base = dependencies(&base)?.remove(0);
+    }
+    if base == hash || !blobs::blob_exists(&base) {
+        return Ok(None);
+    }
+    Ok(Some((base.clone(), blobs::read_blob(&base)?)))
+}
+
+/// Moves the blobs of `manifest`'s tensors into the encodings `rules` pick for them.
+/// Delta bases come from the tensor with the same name in `previous`, typically the
+/// last version of the same model. Returns how many blobs were rewritten.
+pub fn apply_storage_rules(
+    manifest: &VektManifest,
+    rules: &[StorageRule],
+    previous: Option<&VektManifest>,
+) -> Result<usize> {
+    // One decision per blob; tensors sharing data share the first tensor's rule
+    let mut planned: BTreeMap<&str, (BlobEncoding, Option<&str>)> = BTreeMap::new();
+    let mut tensors: Vec<_> = manifest.tensors.iter().collect();
+    tensors.sort_by_key(|(_, t)| t.index);
+    for (name, tensor) in tensors {
+        let encoding = encoding_for(rules, name, &tensor.dtype);
+        let base = previous
+            .and_then(|p| p.tensors.get(name))
+            .map(|t| t.hash.as_str());
+        planned.entry(&tensor.hash).or_insert((encoding, base));
+    }
+
+    let rewritten = planned
+        .into_par_iter()
+        .map(|(hash, (encoding, base))| -> Result<usize> {
+            let current = blobs::find_blob(hash).map(|(_, e)| e).ok_or_else(|| {
+                VektError::BlobNotFound(format!("Blob {} not found to re-encode", hash))
+            })?;
+            if current == encoding {
+                return Ok(0);
+            }
+            let data = blobs::read_blob(hash)?;
+            verify_blob_hash(&data, hash)?;
+            let used = store_encoded(hash, &data, encoding, base)?;
+            Ok(usize::from(used != current))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rewritten.into_iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matching() {
+        let rules: Vec<StorageRule> = serde_json::from_str(
+            r#"[
+                {"tensors": "*.qweight", "encoding": "raw"},
+                {"tensors": "*embed_tokens*", "encoding": "chunked"},
+                {"tensors": "lora_*", "dtype": "F16", "encoding": "delta"},
+                {"encoding": "zstd"}
+            ]"#,
+        )
+        .unwrap();
+        let pick = |name, dtype| encoding_for(&rules, name, dtype);
+        assert_eq!(pick("layers.0.qweight", "I32"), BlobEncoding::Raw);
+        assert_eq!(
+            pick("model.embed_tokens.weight", "BF16"),
+            BlobEncoding::Chunked
+        );
+        assert_eq!(pick("lora_A.weight", "f16"), BlobEncoding::Delta);
+        assert_eq!(pick("lora_A.weight", "F32"), BlobEncoding::Zstd);
+        assert_eq!(encoding_for(&[], "x", "F32"), BlobEncoding::Raw);
+        assert_eq!(BlobEncoding::Delta.extension(), Some("delta"));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::storage::VektManifest;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Metadata key holding the expiry of an ephemeral manifest, in seconds since the epoch
+pub const EXPIRES_AT_KEY: &str = "expires_at";
+
+/// TTL used when `--ephemeral` is given without `--ttl`
+pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Seconds since the Unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl VektManifest {
+    /// Marks the manifest as a weak reference: once `ttl` has passed, `vekt gc` no
+    /// longer keeps its blobs alive and deletes the manifest file
+    pub fn mark_ephemeral(&mut self, ttl: Duration) {
+        let expires_at = now_secs().saturating_add(ttl.as_secs());
+        self.metadata
+            .insert(EXPIRES_AT_KEY.to_string(), expires_at.to_string());
+    }
+
+    /// Makes the manifest permanent again
+    pub fn clear_ephemeral(&mut self) {
+        self.metadata.remove(EXPIRES_AT_KEY);
+    }
+
+    /// Expiry of an ephemeral manifest, `None` for regular ones
+    pub fn expires_at(&self) -> Option<u64> {
+        self.metadata.get(EXPIRES_AT_KEY)?.parse().ok()
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
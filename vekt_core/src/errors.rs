@@ -17,6 +17,9 @@ pub enum VektError {
     #[error("Lock file exists. Another vekt operation is in progress")]
     LockExists,
 
+    #[error("Timed out after {0}s waiting for another vekt operation to release the lock")]
+    LockTimeout(u64),
+
     #[error("Invalid safetensors file: {0}")]
     InvalidSafetensor(String),
 
@@ -47,6 +50,9 @@ pub enum VektError {
     #[error("Invalid manifest: {0}")]
     InvalidManifest(String),
 
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
     #[error("Path traversal detected: {0}")]
     PathTraversal(String),
 
@@ -68,8 +74,124 @@ pub enum VektError {
     #[error("Git operation failed: {0}")]
     GitError(String),
 
+    #[error("Invalid sample size: {0}")]
+    InvalidSample(String),
+
+    #[error("Watch error: {0}")]
+    WatchError(String),
+
+    #[error("Ref not found: {0}")]
+    RefNotFound(String),
+
+    #[error("Invalid ref name: '{0}'")]
+    InvalidRefName(String),
+
+    #[error("Ref '{0}' already exists. Use --force to move it")]
+    RefExists(String),
+
+    #[error("Nothing to commit: manifests match {0}")]
+    NothingToCommit(String),
+
+    #[error("Manifest '{manifest}' is locked on the remote by {owner}. Use --force to override")]
+    ManifestLocked { manifest: String, owner: String },
+
+    #[error(
+        "Remote manifest '{0}' changed since it was last pushed or pulled here. Pull it first or use --force to overwrite it"
+    )]
+    NotFastForward(String),
+
+    #[error("Uncommitted manifest changes would be overwritten: {0}. Commit them or use --force")]
+    UncommittedChanges(String),
+
     #[error("Operation cancelled: {0}")]
     Cancelled(String),
+
+    #[error("Invalid duration '{0}'. Use e.g. 90s, 30m, 12h or 7d")]
+    InvalidDuration(String),
+
+    #[error("Invalid size '{0}'. Use e.g. 500MB, 20GiB or 1T")]
+    InvalidSize(String),
+
+    #[error("Invalid tensor pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+
+    #[error("Unsupported dtype '{0}'")]
+    UnsupportedDtype(String),
+
+    #[error("Archived blobs must be restored first: {0}")]
+    ArchivedBlobs(String),
+
+    #[error("Sanity checks failed: {0}")]
+    CheckFailed(String),
+
+    #[error("Transfer incomplete: {completed} of {total} blobs done. {reason}")]
+    PartialTransfer {
+        completed: usize,
+        total: usize,
+        reason: String,
+    },
+}
+
+/// Process exit codes of the `vekt` CLI. Scripts can rely on these staying stable.
+pub mod exit_code {
+    /// Any failure without a more specific code
+    pub const FAILURE: i32 = 1;
+    /// Not inside a vekt repository
+    pub const NOT_A_REPO: i32 = 2;
+    /// Another vekt process holds the repository lock
+    pub const LOCK_HELD: i32 = 3;
+    /// Stored or restored data failed hash or format verification
+    pub const CORRUPTION: i32 = 4;
+    /// Remote credentials are missing or were rejected
+    pub const REMOTE_AUTH: i32 = 5;
+    /// A push or pull stopped after transferring some blobs; rerunning resumes it
+    pub const PARTIAL_TRANSFER: i32 = 6;
+    /// A ref, blob, remote or tensor that was asked for doesn't exist
+    pub const NOT_FOUND: i32 = 7;
+    /// Refused to overwrite existing state without `--force`
+    pub const CONFLICT: i32 = 8;
+    /// Remote blobs are in an archive storage class; rerun once they are restored
+    pub const ARCHIVED: i32 = 9;
+    /// Invalid command-line arguments
+    pub const USAGE: i32 = 64;
+    /// Cancelled by Ctrl-C or `--timeout`, after cleaning up
+    pub const CANCELLED: i32 = 130;
+}
+
+impl VektError {
+    /// The CLI exit code for this error class, see [`exit_code`]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VektError::RepoNotFound => exit_code::NOT_A_REPO,
+            VektError::LockExists | VektError::LockTimeout(_) | VektError::StaleLock(_) => {
+                exit_code::LOCK_HELD
+            }
+            VektError::InvalidSafetensor(_)
+            | VektError::TensorCorruption(_)
+            | VektError::HashMismatch { .. }
+            | VektError::DecompressionError(_)
+            | VektError::ManifestCorrupted(_)
+            | VektError::UnsupportedDtype(_)
+            | VektError::CheckFailed(_) => exit_code::CORRUPTION,
+            VektError::CredentialError(_) => exit_code::REMOTE_AUTH,
+            VektError::PartialTransfer { .. } => exit_code::PARTIAL_TRANSFER,
+            VektError::BlobNotFound(_)
+            | VektError::RemoteNotFound(_)
+            | VektError::RefNotFound(_) => exit_code::NOT_FOUND,
+            VektError::RepoAlreadyExists
+            | VektError::RefExists(_)
+            | VektError::ConflictDetected(_)
+            | VektError::ManifestLocked { .. }
+            | VektError::NotFastForward(_)
+            | VektError::UncommittedChanges(_) => exit_code::CONFLICT,
+            VektError::ArchivedBlobs(_) => exit_code::ARCHIVED,
+            VektError::Cancelled(_) => exit_code::CANCELLED,
+            _ => exit_code::FAILURE,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, VektError>;
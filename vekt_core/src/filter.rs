@@ -0,0 +1,218 @@
+use crate::errors::{Result, VektError};
+use crate::hub::StreamHeader;
+use crate::layout::{FileLayout, SourceLayout, map_file};
+use crate::refs;
+use crate::storage::{ManifestTensor, VektManifest};
+use crate::utils::get_vekt_dir;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+
+/// First line of every pointer file
+pub const POINTER_VERSION: &str = "version vekt-pointer/1";
+
+/// Set to `1` to check out pointer files as they are, e.g. in a clone whose store
+/// doesn't have the weights yet
+pub const SKIP_SMUDGE_ENV: &str = "VEKT_SKIP_SMUDGE";
+
+/// Pointer files are never larger than this; anything bigger is real content
+const MAX_POINTER_SIZE: usize = 1024;
+
+/// What Git stores in place of a safetensors file: the hash of a manifest object
+/// that rebuilds the file byte for byte, and the file's size.
+///
+/// ```text
+/// version vekt-pointer/1
+/// manifest 5b2f...
+/// size 4943163616
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub manifest: String,
+    pub size: u64,
+}
+
+impl Pointer {
+    /// Parses a pointer file; `None` for anything else
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let mut lines = std::str::from_utf8(data).ok()?.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let manifest = lines.next()?.strip_prefix("manifest ")?;
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        if manifest.len() != 64 || !manifest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Pointer {
+            manifest: manifest.to_string(),
+            size,
+        })
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", POINTER_VERSION)?;
+        writeln!(f, "manifest {}", self.manifest)?;
+        writeln!(f, "size {}", self.size)
+    }
+}
+
+/// Git clean filter: stores the safetensors file read from `input` and writes its
+/// pointer to `output`. Input that already is a pointer passes through unchanged.
+pub fn clean<R: Read, W: Write>(mut input: R, mut output: W) -> Result<Pointer> {
+    let mut head = Vec::new();
+    input
+        .by_ref()
+        .take(MAX_POINTER_SIZE as u64 + 1)
+        .read_to_end(&mut head)?;
+    if let Some(pointer) = Pointer::parse(&head) {
+        output.write_all(pointer.to_string().as_bytes())?;
+        return Ok(pointer);
+    }
+
+    // Spool to disk so the file can be mapped instead of held in memory
+    let tmp_path = get_vekt_dir().join(format!("filter-{}.tmp", std::process::id()));
+    let spooled = (|| -> Result<VektManifest> {
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        file.write_all(&head)?;
+        io::copy(&mut input, &mut file)?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        ingest(&map_file(&tmp_path)?)
+    })();
+    let _ = fs::remove_file(&tmp_path);
+    let manifest = spooled?;
+
+    let pointer = Pointer {
+        manifest: refs::store_manifest_object(&manifest)?,
+        size: manifest.total_size as u64,
+    };
+    output.write_all(pointer.to_string().as_bytes())?;
+    Ok(pointer)
+}
+
+/// Git smudge filter: writes the file a pointer read from `input` stands for.
+/// Anything that isn't a pointer passes through unchanged.
+pub fn smudge<R: Read, W: Write>(mut input: R, mut output: W) -> Result<()> {
+    let mut head = Vec::new();
+    input
+        .by_ref()
+        .take(MAX_POINTER_SIZE as u64 + 1)
+        .read_to_end(&mut head)?;
+    let pointer = match Pointer::parse(&head) {
+        Some(pointer) if std::env::var(SKIP_SMUDGE_ENV).as_deref() != Ok("1") => pointer,
+        _ => {
+            output.write_all(&head)?;
+            io::copy(&mut input, &mut output)?;
+            return Ok(());
+        }
+    };
+
+    let manifest = refs::load_manifest_object(&pointer.manifest)?;
+    let layout = manifest.layout.as_ref().ok_or_else(|| {
+        VektError::InvalidManifest(format!(
+            "Manifest {} was not written by the clean filter",
+            pointer.manifest
+        ))
+    })?;
+    let mut writer = BufWriter::new(output);
+    layout.main.write_to(&manifest, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Stores a safetensors file as tensor blobs plus a skeleton holding the header
+/// exactly as written, so the smudged file is identical to the one committed
+fn ingest(data: &[u8]) -> Result<VektManifest> {
+    let invalid = |message: &str| VektError::InvalidSafetensor(message.to_string());
+    let header_len = data
+        .get(..8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid("File too small"))?;
+    let json = data
+        .get(8..8usize.saturating_add(header_len))
+        .ok_or_else(|| invalid("Header length exceeds file size"))?;
+    let json = std::str::from_utf8(json).map_err(|_| invalid("Header is not valid UTF-8"))?;
+    let header = StreamHeader::parse(json)?;
+
+    let data_start = header.data_start as usize;
+    let regions = header
+        .tensors
+        .iter()
+        .map(|(_, name, meta)| {
+            let (start, end) = meta.data_offsets;
+            let range = data_start
+                .checked_add(start)
+                .zip(data_start.checked_add(end));
+            match range {
+                Some((start, end)) if start <= end && end <= data.len() => {
+                    Ok((start..end, name.clone()))
+                }
+                _ => Err(invalid(&format!(
+                    "Tensor '{}' data offsets {:?} are outside the file",
+                    name, meta.data_offsets
+                ))),
+            }
+        })
+        .collect::<Result<_>>()?;
+    let (main, hashes) = FileLayout::split_hashed(data, regions, true)?;
+    let hashes: BTreeMap<String, String> = hashes.into_iter().collect();
+
+    let tensors = header
+        .tensors
+        .into_iter()
+        .map(|(index, name, meta)| {
+            let tensor = ManifestTensor {
+                shape: meta.shape,
+                dtype: meta.dtype,
+                hash: hashes[&name].clone(),
+                index,
+                extra: meta.extra,
+                ranges: None,
+                stats: None,
+            };
+            (name, tensor)
+        })
+        .collect();
+    Ok(VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: data.len(),
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: Some(SourceLayout {
+            format: "safetensors".to_string(),
+            main,
+            external: BTreeMap::new(),
+        }),
+        placement: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let pointer = Pointer {
+            manifest: "ab".repeat(32),
+            size: 4096,
+        };
+        let text = pointer.to_string();
+        assert!(text.starts_with(POINTER_VERSION));
+        assert_eq!(Pointer::parse(text.as_bytes()), Some(pointer));
+
+        assert_eq!(
+            Pointer::parse(b"version vekt-pointer/1\nmanifest abc\nsize 1\n"),
+            None
+        );
+        assert_eq!(Pointer::parse(b"\x10\x00\x00\x00\x00\x00\x00\x00{}"), None);
+    }
+}
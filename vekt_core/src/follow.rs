@@ -0,0 +1,266 @@
+use crate::errors::{Result, VektError};
+use crate::refs;
+use crate::remote::RemoteClient;
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use crate::watch::matches_pattern;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a subscription tracks on the remote
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowKind {
+    Release,
+    Tag,
+}
+
+/// A remote release or tag pattern this repository follows
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub remote: String,
+    pub kind: FollowKind,
+    /// Name or glob (`*`, `?`) matched against release/tag names
+    pub pattern: String,
+    /// Directory the followed manifests and weights are materialized into
+    pub output: PathBuf,
+    /// Name of the materialized release/tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    /// Release object or manifest hash that was materialized, to detect moved tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FollowConfig {
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+}
+
+fn follow_path() -> PathBuf {
+    get_vekt_dir().join("follow.json")
+}
+
+impl FollowConfig {
+    pub fn load() -> Result<Self> {
+        let path = follow_path();
+        if !path.is_file() {
+            return Ok(FollowConfig::default());
+        }
+        serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+            VektError::InvalidManifest(format!("Failed to parse {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        write_file_atomic(&follow_path(), &json)?;
+        Ok(())
+    }
+
+    /// Adds a subscription, replacing any existing one for the same remote, kind and pattern
+    pub fn follow(&mut self, subscription: Subscription) {
+        self.subscriptions.retain(|s| {
+            !(s.remote == subscription.remote
+                && s.kind == subscription.kind
+                && s.pattern == subscription.pattern)
+        });
+        self.subscriptions.push(subscription);
+    }
+
+    /// Removes subscriptions with `pattern`; returns how many were removed
+    pub fn unfollow(&mut self, pattern: &str) -> usize {
+        let before = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.pattern != pattern);
+        before - self.subscriptions.len()
+    }
+}
+
+/// Compares names so that embedded numbers order numerically (`v2` < `v10`)
+pub fn version_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<(bool, &str)> {
+        let mut out = Vec::new();
+        let mut start = 0;
+        let bytes = s.as_bytes();
+        for i in 1..=bytes.len() {
+            if i == bytes.len() || bytes[i].is_ascii_digit() != bytes[start].is_ascii_digit() {
+                out.push((bytes[start].is_ascii_digit(), &s[start..i]));
+                start = i;
+            }
+        }
+        out
+    }
+    if a.is_empty() || b.is_empty() {
+        return a.cmp(b);
+    }
+    for (x, y) in chunks(a).into_iter().zip(chunks(b)) {
+        let ordering = match (x, y) {
+            ((true, x), (true, y)) => {
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            ((_, x), (_, y)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Machine-readable outcome of [`update`]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateStatus {
+    Updated,
+    Unchanged,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UpdateResult {
+    pub remote: String,
+    pub kind: FollowKind,
+    pub pattern: String,
+    pub status: UpdateStatus,
+    /// Release or tag name now materialized
+    pub version: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// Writes a manifest next to its restored weights and attachments
+fn materialize(manifest: &VektManifest, dir: &Path, stem: &str) -> Result<Vec<PathBuf>> {
+    let manifest_path = dir.join(format!("{}.vekt.json", stem));
+    let weights_path = dir.join(format!("{}.safetensors", stem));
+    manifest.save(&manifest_path)?;
+    manifest.restore(&weights_path, None)?;
+    manifest.restore_attachments(dir)?;
+    Ok(vec![manifest_path, weights_path])
+}
+
+/// Checks the remote for the newest release/tag matching `subscription` and, if it
+/// differs from what was materialized last time, pulls and restores it.
+///
+/// Releases are ordered by creation time; tags by name in version order, so
+/// `v1.10` beats `v1.9`. A tag that moved to a new manifest also counts as updated.
+pub async fn update(
+    subscription: &mut Subscription,
+    client: &RemoteClient,
+) -> Result<UpdateResult> {
+    let (version, hash) = match subscription.kind {
+        FollowKind::Release => {
+            let mut candidates = Vec::new();
+            for name in client.list_releases().await? {
+                if matches_pattern(&subscription.pattern, &name)
+                    && let Some(release) = client.get_release(&name).await?
+                {
+                    candidates.push(release);
+                }
+            }
+            let newest = candidates
+                .into_iter()
+                .max_by(|a, b| {
+                    a.created_at
+                        .cmp(&b.created_at)
+                        .then_with(|| version_cmp(&a.name, &b.name))
+                })
+                .ok_or_else(|| no_match(subscription))?;
+            let hash = crate::blobs::compute_object_hash(&newest.to_bytes()?);
+            (newest.name, hash)
+        }
+        FollowKind::Tag => client
+            .list_tags()
+            .await?
+            .into_iter()
+            .filter(|(name, _)| matches_pattern(&subscription.pattern, name))
+            .max_by(|(a, _), (b, _)| version_cmp(a, b))
+            .ok_or_else(|| no_match(subscription))?,
+    };
+
+    let mut result = UpdateResult {
+        remote: subscription.remote.clone(),
+        kind: subscription.kind,
+        pattern: subscription.pattern.clone(),
+        status: UpdateStatus::Unchanged,
+        version: version.clone(),
+        files: Vec::new(),
+    };
+    if subscription.current.as_ref() == Some(&version)
+        && subscription.current_hash.as_ref() == Some(&hash)
+    {
+        return Ok(result);
+    }
+
+    fs::create_dir_all(&subscription.output)?;
+    match subscription.kind {
+        FollowKind::Release => {
+            let release = client.pull_release(&version).await?;
+            for (name, _, manifest) in release.load_manifests()? {
+                let stem = name
+                    .trim_end_matches(".vekt.json")
+                    .trim_end_matches(".json");
+                result
+                    .files
+                    .extend(materialize(&manifest, &subscription.output, stem)?);
+            }
+        }
+        FollowKind::Tag => {
+            let manifest = client.pull_tag(&version, &hash).await?;
+            refs::write_tag(&version, &hash, true)?;
+            let stem = version.replace('/', "_");
+            result
+                .files
+                .extend(materialize(&manifest, &subscription.output, &stem)?);
+        }
+    }
+
+    subscription.current = Some(version);
+    subscription.current_hash = Some(hash);
+    result.status = UpdateStatus::Updated;
+    Ok(result)
+}
+
+fn no_match(subscription: &Subscription) -> VektError {
+    let kind = match subscription.kind {
+        FollowKind::Release => "release",
+        FollowKind::Tag => "tag",
+    };
+    VektError::RefNotFound(format!(
+        "No {} matching '{}' on remote '{}'",
+        kind, subscription.pattern, subscription.remote
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_cmp() {
+        assert_eq!(version_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(version_cmp("v2", "v10"), Ordering::Less);
+        assert_eq!(version_cmp("prod", "prod"), Ordering::Equal);
+        assert_eq!(version_cmp("rc", "rc2"), Ordering::Less);
+        assert_eq!(version_cmp("", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_follow_replaces_same_pattern() {
+        let subscription = |output: &str| Subscription {
+            remote: "origin".to_string(),
+            kind: FollowKind::Release,
+            pattern: "v2024.*".to_string(),
+            output: PathBuf::from(output),
+            current: None,
+            current_hash: None,
+        };
+        let mut config = FollowConfig::default();
+        config.follow(subscription("a"));
+        config.follow(subscription("b"));
+        assert_eq!(config.subscriptions.len(), 1);
+        assert_eq!(config.subscriptions[0].output, PathBuf::from("b"));
+        assert_eq!(config.unfollow("v2024.*"), 1);
+    }
+}
@@ -1,232 +1,159 @@
-use crate::errors::{Result, VektError};
-use crate::storage::VektManifest;
+use crate::blob_index::{self, BlobIndex};
+use crate::encoding;
+use crate::errors::Result;
+use crate::refcount::RefCounts;
+use crate::tombstones;
+use crate::transaction;
 use crate::utils::{LockFile, get_store_path};
+use serde::Serialize;
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufRead, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct GcStats {
     pub deleted: usize,
     pub kept: usize,
+    /// Ephemeral manifest files removed because their TTL ran out
+    pub expired: usize,
+    /// Interrupted adds whose journals and temp files were cleaned up
+    pub recovered: usize,
+    /// Tombstones of removed manifests that were cleared
+    pub settled: usize,
 }
 
-pub fn run_gc(root_path: &Path) -> Result<GcStats> {
-    // CRITICAL: Acquire lock for entire GC operation to prevent race conditions
-    // This ensures no other vekt operations can modify manifests or blobs during GC
-    let _lock = LockFile::lock()?;
+/// What gc would delete, worked out without deleting anything
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct GcPlan {
+    /// Store files of unreferenced blobs, with their size on disk
+    pub delete: Vec<(PathBuf, u64)>,
+    pub kept: usize,
+    /// Ephemeral manifest files whose TTL ran out
+    pub expired: Vec<PathBuf>,
+    /// Interrupted adds whose journals and temp files would be cleaned up
+    pub interrupted: usize,
+    /// Tombstones of removed manifests this sweep settles
+    pub tombstones: Vec<PathBuf>,
+}
 
-    let store_path = get_store_path();
-    if !store_path.exists() {
-        return Ok(GcStats {
-            deleted: 0,
-            kept: 0,
-        });
+impl GcPlan {
+    /// Bytes deleting the planned blobs would free
+    pub fn bytes(&self) -> u64 {
+        self.delete.iter().map(|(_, size)| size).sum()
     }
 
-    let mut referenced_hashes = HashSet::new();
-
-    // Scan working tree
-    scan_manifests(root_path, &mut referenced_hashes)?;
-
-    // Scan git history
-    scan_git_history(root_path, &mut referenced_hashes)?;
-
-    let mut stats = GcStats {
-        deleted: 0,
-        kept: 0,
-    };
-
-    for entry in std::fs::read_dir(&store_path)? {
-        let entry = entry?;
-        let path = entry.path();
-        if let Some(hash) = path.file_name().and_then(|n| n.to_str()) {
-            // Skip temporary files
-            if hash.ends_with(".tmp") {
-                continue;
-            }
-            if !referenced_hashes.contains(hash) {
-                std::fs::remove_file(&path)?;
-                stats.deleted += 1;
-            } else {
-                stats.kept += 1;
+    /// Deletes what the plan lists. Only call this while holding the repository lock
+    /// the plan was made under.
+    pub fn execute(self) -> Result<GcStats> {
+        // Adds that died mid-way left their blobs unreferenced; the sweep below
+        // finishes their rollback
+        let recovered = transaction::recover_journals()?;
+        for path in &self.expired {
+            std::fs::remove_file(path)?;
+        }
+        for (path, _) in &self.delete {
+            match std::fs::remove_file(path) {
+                // The index can list a file that was removed since it was last listed
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => blob_index::record_removed(path),
             }
         }
+        for path in &self.tombstones {
+            std::fs::remove_file(path)?;
+        }
+        Ok(GcStats {
+            deleted: self.delete.len(),
+            kept: self.kept,
+            expired: self.expired.len(),
+            recovered,
+            settled: self.tombstones.len(),
+        })
     }
-
-    Ok(stats)
 }
 
-fn scan_manifests(dir: &Path, hashes: &mut HashSet<String>) -> Result<()> {
-    if dir.is_dir() {
-        for entry in std::fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                let name = path.file_name().unwrap_or_default().to_string_lossy();
-                // Ignore common build/hidden dirs to avoid scanning too much or loops
-                if name == ".git" || name == ".vekt" || name == "target" || name == "node_modules" {
-                    continue;
-                }
-                scan_manifests(&path, hashes)?;
-            } else if let Some(name) = path.file_name().and_then(|n| n.to_str())
-                && name.ends_with(".vekt.json")
-            {
-                let f = File::open(&path)?;
-                let reader = std::io::BufReader::new(f);
-                match serde_json::from_reader::<_, VektManifest>(reader) {
-                    Ok(manifest) => {
-                        for tensor in manifest.tensors.values() {
-                            hashes.insert(tensor.hash.clone());
-                        }
-                    }
-                    Err(e) => {
-                        // Log corrupted manifests but continue GC
-                        // This prevents partial failures from blocking cleanup
-                        eprintln!(
-                            "Warning: Failed to parse manifest at {}: {}. Skipping this manifest.",
-                            path.display(),
-                            e
-                        );
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
+pub fn run_gc(root_path: &Path) -> Result<GcStats> {
+    // CRITICAL: Acquire lock for entire GC operation to prevent race conditions
+    // This ensures no other vekt operations can modify manifests or blobs during GC
+    let _lock = LockFile::lock()?;
+    plan_sweep(root_path, None, false, true)?.execute()
 }
 
-fn scan_git_history(repo_root: &Path, hashes: &mut HashSet<String>) -> Result<()> {
-    // Check if this is a git repository
-    let git_dir = repo_root.join(".git");
-    if !git_dir.exists() {
-        return Ok(());
-    }
+/// Like [`run_gc`], but only deletes unreferenced blobs of manifests removed with
+/// `vekt rm`
+pub fn run_gc_removed(root_path: &Path) -> Result<GcStats> {
+    let _lock = LockFile::lock()?;
+    let removed = encoding::with_dependencies(&tombstones::tombstoned_hashes()?)?;
+    plan_sweep(root_path, Some(&removed), false, true)?.execute()
+}
 
-    // Use git rev-list with --all and --reflog to catch unreachable commits
-    // --reflog includes commits that may have been deleted/force-pushed
-    let rev_list_output = std::process::Command::new("git")
-        .arg("-C")
-        .arg(repo_root)
-        .arg("rev-list")
-        .arg("--all")
-        .arg("--reflog")
-        .arg("--objects")
-        .output()
-        .map_err(|e| VektError::GitError(format!("Failed to run git rev-list: {}", e)))?;
+/// Like [`run_gc`], but reads every manifest again instead of trusting the refcount
+/// database, and rebuilds it
+pub fn run_gc_full(root_path: &Path) -> Result<GcStats> {
+    let _lock = LockFile::lock()?;
+    plan_sweep(root_path, None, true, true)?.execute()
+}
 
-    if !rev_list_output.status.success() {
-        let stderr = String::from_utf8_lossy(&rev_list_output.stderr);
-        eprintln!(
-            "Warning: git rev-list failed: {}. Skipping git history scan.",
-            stderr
-        );
-        return Ok(());
-    }
+/// Works out what [`run_gc`] would delete, without writing anything, not even the
+/// refcount database or blob index. Hold the repository lock so nothing changes
+/// between planning and executing.
+pub fn plan_gc(root_path: &Path) -> Result<GcPlan> {
+    plan_sweep(root_path, None, false, false)
+}
 
-    let objects = String::from_utf8_lossy(&rev_list_output.stdout);
+/// Works out what [`run_gc_removed`] would delete
+pub fn plan_gc_removed(root_path: &Path) -> Result<GcPlan> {
+    let removed = encoding::with_dependencies(&tombstones::tombstoned_hashes()?)?;
+    plan_sweep(root_path, Some(&removed), false, false)
+}
 
-    // Collect all object SHAs that are .vekt.json files
-    let mut manifest_objects = Vec::new();
-    for line in objects.lines() {
-        // Format: "<sha> <path>" or just "<sha>" for commits
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() == 2 {
-            let (sha, path) = (parts[0], parts[1]);
-            if path.ends_with(".vekt.json") {
-                manifest_objects.push(sha.to_string());
-            }
-        }
-    }
+/// Works out what [`run_gc_full`] would delete
+pub fn plan_gc_full(root_path: &Path) -> Result<GcPlan> {
+    plan_sweep(root_path, None, true, false)
+}
 
-    if manifest_objects.is_empty() {
-        return Ok(());
+/// Plans deleting every unreferenced blob, or only those in `candidates`. Reads only
+/// manifests that changed since the refcount database was last reconciled, or all of
+/// them when `full`. Saves what it reconciled only when `persist`.
+fn plan_sweep(
+    root_path: &Path,
+    candidates: Option<&HashSet<String>>,
+    full: bool,
+    persist: bool,
+) -> Result<GcPlan> {
+    let mut plan = GcPlan {
+        interrupted: transaction::interrupted_journals()?,
+        ..Default::default()
+    };
+    let store_path = get_store_path();
+    if !store_path.exists() {
+        return Ok(plan);
     }
+    plan.tombstones = tombstones::list_tombstones()?
+        .into_iter()
+        .map(|tombstone| tombstones::tombstones_dir().join(tombstone.hash))
+        .collect();
+
+    let refcounts = match (persist, full) {
+        (false, full) => RefCounts::read(root_path, full)?,
+        (true, true) => RefCounts::rebuild(root_path)?,
+        (true, false) => RefCounts::open(root_path)?,
+    };
+    // Expired ephemeral manifests hold nothing
+    plan.expired = refcounts.expired(root_path);
 
-    // Use git cat-file --batch for efficient streaming of multiple objects
-    let mut cat_file = std::process::Command::new("git")
-        .arg("-C")
-        .arg(repo_root)
-        .arg("cat-file")
-        .arg("--batch")
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| VektError::GitError(format!("Failed to spawn git cat-file: {}", e)))?;
-
-    // Write all SHAs to stdin
-    if let Some(mut stdin) = cat_file.stdin.take() {
-        use std::io::Write;
-        for sha in &manifest_objects {
-            if let Err(e) = writeln!(stdin, "{}", sha) {
-                eprintln!("Warning: Failed to write SHA to git cat-file: {}", e);
-                break;
-            }
+    let index = match persist {
+        true => BlobIndex::open()?,
+        false => BlobIndex::read()?,
+    };
+    let unreferenced = refcounts.unreferenced(&index)?;
+    for (name, hash, blob) in index.files() {
+        if candidates.is_some_and(|candidates| !candidates.contains(hash.as_ref())) {
+            continue;
         }
-        // Close stdin to signal we're done
-        drop(stdin);
-    }
-
-    // Read the batched output
-    if let Some(stdout) = cat_file.stdout.take() {
-        let mut reader = std::io::BufReader::new(stdout);
-
-        loop {
-            // Read header line: "<sha> <type> <size>"
-            let mut header_line = String::new();
-            let bytes_read = reader.read_line(&mut header_line).map_err(|e| {
-                VektError::GitError(format!("Failed to read git cat-file output: {}", e))
-            })?;
-            if bytes_read == 0 {
-                break; // EOF
-            }
-
-            let parts: Vec<&str> = header_line.split_whitespace().collect();
-            if parts.len() != 3 {
-                // Handle missing objects gracefully
-                if header_line.contains("missing") {
-                    // Skip the trailing newline for missing objects
-                    let mut newline = [0u8; 1];
-                    let _ = reader.read_exact(&mut newline);
-                }
-                continue;
-            }
-
-            let size: usize = match parts[2].parse() {
-                Ok(s) => s,
-                Err(_) => {
-                    eprintln!("Warning: Invalid size in git cat-file output: {}", parts[2]);
-                    continue;
-                }
-            };
-
-            // Read exactly 'size' bytes (the actual file content)
-            let mut content = vec![0u8; size];
-            if let Err(e) = reader.read_exact(&mut content) {
-                eprintln!("Warning: Failed to read git object content: {}", e);
-                break;
-            }
-
-            // Read the trailing newline that git cat-file adds after each object
-            let mut newline = [0u8; 1];
-            let _ = reader.read_exact(&mut newline);
-
-            // Try to parse as manifest - handle encoding issues gracefully
-            match serde_json::from_slice::<VektManifest>(&content) {
-                Ok(manifest) => {
-                    for tensor in manifest.tensors.values() {
-                        hashes.insert(tensor.hash.clone());
-                    }
-                }
-                Err(e) => {
-                    // Log but don't fail - file might be corrupted or not valid JSON
-                    eprintln!("Warning: Failed to parse git object as manifest: {}", e);
-                }
-            }
+        if unreferenced.contains(hash.as_ref()) {
+            plan.delete.push((store_path.join(name), blob.size));
+        } else {
+            plan.kept += 1;
         }
     }
 
-    let _ = cat_file.wait();
-    Ok(())
+    Ok(plan)
 }
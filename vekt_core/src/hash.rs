@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Prefix of SHA-256 hashes. BLAKE3 hashes are bare hex, as they always were.
+pub const SHA256_PREFIX: &str = "sha256:";
+
+/// How SHA-256 hashes are spelled in file names, where `:` isn't allowed everywhere
+const SHA256_FILE_PREFIX: &str = "sha256-";
+
+/// Data at least this large is hashed on all cores. Below it, spreading the work
+/// costs more than it saves.
+pub const PARALLEL_HASH_THRESHOLD: usize = 1 << 20;
+
+/// Algorithm of the content hashes that address blobs. Each hash says which
+/// algorithm made it, so a store can hold blobs of both and every blob is verified
+/// with its own algorithm. Manifest objects and commits are always named by BLAKE3.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+impl HashAlgorithm {
+    /// The algorithm new blobs are hashed with in this process
+    pub fn current() -> Self {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => HashAlgorithm::Sha256,
+            _ => HashAlgorithm::Blake3,
+        }
+    }
+
+    /// Hashes new blobs with `self` from now on, e.g. as `hash_algorithm` in the
+    /// config asks
+    pub fn set_current(self) {
+        CURRENT.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// The algorithm that made `hash`
+    pub fn of(hash: &str) -> Self {
+        if hash.starts_with(SHA256_PREFIX) {
+            HashAlgorithm::Sha256
+        } else {
+            HashAlgorithm::Blake3
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn hasher(self) -> ContentHasher {
+        match self {
+            HashAlgorithm::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => ContentHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// The tagged hash of `data`
+    pub fn hash(self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+/// Incremental hashing with either algorithm
+pub enum ContentHasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+}
+
+impl ContentHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            // Splits the blake3 tree across rayon workers, so one huge tensor no
+            // longer hashes on a single core. The hash is the same either way.
+            #[cfg(feature = "native")]
+            ContentHasher::Blake3(hasher) if data.len() >= PARALLEL_HASH_THRESHOLD => {
+                hasher.update_rayon(data);
+            }
+            ContentHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            ContentHasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The tagged hash of everything fed in
+    pub fn finalize(self) -> String {
+        match self {
+            ContentHasher::Blake3(hasher) => hex::encode(hasher.finalize().as_bytes()),
+            ContentHasher::Sha256(hasher) => {
+                format!("{}{}", SHA256_PREFIX, hex::encode(hasher.finalize()))
+            }
+        }
+    }
+}
+
+impl Write for ContentHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Whether `s` is a content hash of either algorithm
+pub fn is_hash(s: &str) -> bool {
+    let hex = s.strip_prefix(SHA256_PREFIX).unwrap_or(s);
+    hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Name of the store file of blob `hash`, before any encoding extension
+pub fn file_name(hash: &str) -> Cow<'_, str> {
+    match hash.strip_prefix(SHA256_PREFIX) {
+        Some(hex) => Cow::Owned(format!("{}{}", SHA256_FILE_PREFIX, hex)),
+        None => Cow::Borrowed(hash),
+    }
+}
+
+/// The hash a store file holds: the reverse of [`file_name`], ignoring the encoding
+/// extension of encoded blobs (`<hash>.<encoding>`)
+pub fn from_file_name(name: &str) -> Cow<'_, str> {
+    let stem = name.split('.').next().unwrap_or(name);
+    match stem.strip_prefix(SHA256_FILE_PREFIX) {
+        Some(hex) => Cow::Owned(format!("{}{}", SHA256_PREFIX, hex)),
+        None => Cow::Borrowed(stem),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tagged_hashes_round_trip() {
+        let data = b"abc";
+        let sha = HashAlgorithm::Sha256.hash(data);
+        assert_eq!(
+            sha,
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let blake = HashAlgorithm::Blake3.hash(data);
+        assert_eq!(blake, blake3::hash(data).to_hex().to_string());
+
+        for hash in [&sha, &blake] {
+            assert!(is_hash(hash));
+            assert_eq!(from_file_name(&format!("{}.zst", file_name(hash))), *hash);
+            assert_eq!(HashAlgorithm::of(hash).hash(data), *hash);
+        }
+        assert!(!file_name(&sha).contains(':'));
+        assert!(!is_hash("sha256:abc"));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::errors::{Result, VektError};
+use crate::storage::RawHeader;
+use crate::validation;
+
+/// Bytes at the start of a safetensors file needed to parse its header: the 8-byte
+/// length prefix plus the header it announces. `prefix` needs only the first 8 bytes,
+/// so callers reading a file remotely or in a browser know how much to fetch.
+pub fn header_size(prefix: &[u8]) -> Result<usize> {
+    let len_bytes: [u8; 8] = prefix
+        .get(0..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| VektError::InvalidSafetensor("File too small".to_string()))?;
+    usize::try_from(u64::from_le_bytes(len_bytes))
+        .ok()
+        .and_then(|len| len.checked_add(8))
+        .ok_or_else(|| VektError::InvalidSafetensor("Invalid header length".to_string()))
+}
+
+/// Parses and validates the header of the safetensors file starting at `bytes`,
+/// which only has to hold the first [`header_size`] bytes of it. Returns the header
+/// and its length without the prefix.
+pub fn parse_header(bytes: &[u8]) -> Result<(RawHeader, usize)> {
+    let size = header_size(bytes)?;
+    if size > bytes.len() {
+        return Err(VektError::InvalidSafetensor(
+            "Header length exceeds file size".to_string(),
+        ));
+    }
+
+    let header_json_str = std::str::from_utf8(&bytes[8..size])
+        .map_err(|e| VektError::InvalidSafetensor(format!("Header is not valid UTF-8: {}", e)))?;
+    let header: RawHeader = serde_json::from_str(header_json_str)
+        .map_err(|e| VektError::InvalidSafetensor(format!("Invalid header JSON: {}", e)))?;
+    for (name, meta) in &header {
+        validation::validate_tensor_meta(name, meta)?;
+    }
+    Ok((header, size - 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header_from_prefix() {
+        let json = br#"{"w":{"dtype":"F32","shape":[2],"data_offsets":[0,8]}}"#;
+        let mut bytes = (json.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(json);
+        assert_eq!(header_size(&bytes[..8]).unwrap(), bytes.len());
+
+        let (header, len) = parse_header(&bytes).unwrap();
+        assert_eq!(len, json.len());
+        assert_eq!(header["w"].shape, vec![2]);
+        assert!(parse_header(&bytes[..bytes.len() - 1]).is_err());
+        assert!(header_size(&bytes[..4]).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::errors::{Result, VektError};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Second line of every hook written by [`install_hooks`], so they can be replaced
+/// on the next install without `--force`
+pub const HOOK_MARKER: &str = "# Installed by vekt install-hooks";
+
+/// Model files `vekt add` can track, by extension
+const MODEL_EXTENSIONS: [&str; 5] = ["safetensors", "onnx", "npz", "npy", "index"];
+
+/// The hooks directory of the Git repository containing `work_tree`, honouring
+/// `core.hooksPath` and linked worktrees
+pub fn hooks_dir(work_tree: &Path) -> Result<PathBuf> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(work_tree)
+        .args(["rev-parse", "--path-format=absolute", "--git-path", "hooks"])
+        .output()
+        .map_err(|e| VektError::GitError(format!("Failed to run git rev-parse: {}", e)))?;
+    if !output.status.success() {
+        return Err(VektError::GitError(format!(
+            "{} is not in a Git repository: {}",
+            work_tree.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Hook that re-adds every model whose manifest is tracked by Git and stages the
+/// updated manifests, so a commit always records the weights on disk
+pub fn pre_commit_script(vekt_root: &Path) -> String {
+    let candidates = MODEL_EXTENSIONS
+        .iter()
+        .map(|ext| format!("\"$base.{}\"", ext))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"#!/bin/sh
+{marker}
+# Re-adds every model whose manifest is tracked, and stages the updated manifests
+VEKT="${{VEKT:-vekt}}"
+export VEKT_ROOT="${{VEKT_ROOT:-{root}}}"
+
+git ls-files -- '*.vekt.json' | while IFS= read -r manifest; do
+    base="${{manifest%.vekt.json}}"
+    for model in {candidates}; do
+        if [ -f "$model" ]; then
+            "$VEKT" add "$model" > /dev/null || exit 1
+            git add -- "$manifest" || exit 1
+        fi
+    done
+done || exit 1
+"#,
+        marker = HOOK_MARKER,
+        root = escape_double_quoted(&vekt_root.display().to_string()),
+        candidates = candidates,
+    )
+}
+
+/// Hook that pushes the weights of every tracked manifest to the vekt `remote` before
+/// Git pushes the commits that reference them
+pub fn pre_push_script(vekt_root: &Path, remote: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+{marker}
+# Pushes the weights of every tracked manifest before the commits referencing them
+VEKT="${{VEKT:-vekt}}"
+export VEKT_ROOT="${{VEKT_ROOT:-{root}}}"
+
+if [ -n "$(git ls-files -- '*.vekt.json')" ]; then
+    git ls-files -z -- '*.vekt.json' | xargs -0 "$VEKT" push "{remote}" || exit 1
+fi
+"#,
+        marker = HOOK_MARKER,
+        root = escape_double_quoted(&vekt_root.display().to_string()),
+        remote = escape_double_quoted(remote),
+    )
+}
+
+/// Writes the `pre-commit` and `pre-push` hooks into `hooks_dir`, linking the Git
+/// repository to the vekt repository at `vekt_root` and the vekt remote `remote`.
+/// Hooks not written by vekt are only replaced with `force`, after being kept as
+/// `<hook>.orig`. Returns the hooks written.
+pub fn install_hooks(
+    hooks_dir: &Path,
+    vekt_root: &Path,
+    remote: &str,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let hooks = [
+        ("pre-commit", pre_commit_script(vekt_root)),
+        ("pre-push", pre_push_script(vekt_root, remote)),
+    ];
+
+    // Check every hook before touching any, so a refusal leaves nothing half done
+    for (name, _) in &hooks {
+        let path = hooks_dir.join(name);
+        if let Ok(existing) = fs::read_to_string(&path)
+            && !existing.contains(HOOK_MARKER)
+            && !force
+        {
+            return Err(VektError::ConflictDetected(format!(
+                "{} already exists and wasn't written by vekt. Use --force to replace it \
+                 (it is kept as {}.orig)",
+                path.display(),
+                name
+            )));
+        }
+    }
+
+    fs::create_dir_all(hooks_dir)?;
+    let mut written = Vec::new();
+    for (name, script) in hooks {
+        let path = hooks_dir.join(name);
+        if let Ok(existing) = fs::read_to_string(&path)
+            && !existing.contains(HOOK_MARKER)
+        {
+            fs::rename(&path, hooks_dir.join(format!("{}.orig", name)))?;
+        }
+        fs::write(&path, script)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// `value` for use inside double quotes in a shell script
+fn escape_double_quoted(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+}
@@ -0,0 +1,571 @@
+use crate::attachments::KNOWN_SIDECAR_FILES;
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::ranges::{RangeHasher, RangeHashes};
+use crate::storage::{ManifestTensor, RawTensorMetaData, VektManifest};
+use crate::transaction;
+use crate::utils::{get_store_path, get_vekt_dir, write_file_atomic};
+use crate::validation::{validate_path_safe, validate_tensor_meta};
+use indexmap::IndexMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Prefix of model references on the Hugging Face Hub, e.g. `hf://org/repo@main`
+pub const HUB_SCHEME: &str = "hf://";
+
+/// Manifest metadata key recording the repository and commit a model was pulled from
+pub const SOURCE_KEY: &str = "source";
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+const DEFAULT_REVISION: &str = "main";
+
+/// Connection drops are retried this many times, resuming where the last attempt stopped
+const MAX_ATTEMPTS: usize = 5;
+
+/// A model repository at a branch, tag or commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubRepo {
+    pub repo: String,
+    pub revision: String,
+}
+
+impl HubRepo {
+    /// Parses `hf://org/repo[@revision]`; the revision defaults to `main`
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid = || {
+            VektError::InvalidRemoteUrl(format!(
+                "'{}'. Expected {}org/repo[@revision]",
+                url, HUB_SCHEME
+            ))
+        };
+        let rest = url.strip_prefix(HUB_SCHEME).ok_or_else(invalid)?;
+        let (repo, revision) = match rest.split_once('@') {
+            Some((repo, revision)) => (repo, revision),
+            None => (rest, DEFAULT_REVISION),
+        };
+
+        let valid_part = |part: &str| {
+            !part.is_empty()
+                && part != "."
+                && part != ".."
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        let parts: Vec<&str> = repo.split('/').collect();
+        if parts.len() != 2 || !parts.iter().all(|p| valid_part(p)) {
+            return Err(invalid());
+        }
+        if revision.is_empty() || revision.chars().any(|c| c.is_whitespace() || c == '@') {
+            return Err(invalid());
+        }
+        Ok(HubRepo {
+            repo: repo.to_string(),
+            revision: revision.to_string(),
+        })
+    }
+}
+
+/// The files of a repository at one resolved commit
+#[derive(Debug, Clone)]
+pub struct HubSnapshot {
+    pub repo: String,
+    pub sha: String,
+    pub files: Vec<String>,
+}
+
+impl HubSnapshot {
+    pub fn safetensors(&self) -> impl Iterator<Item = &String> {
+        self.files.iter().filter(|f| f.ends_with(".safetensors"))
+    }
+
+    /// Known sidecar files (configs, tokenizers, license) at the top of the repository
+    pub fn sidecars(&self) -> impl Iterator<Item = &String> {
+        self.files
+            .iter()
+            .filter(|f| KNOWN_SIDECAR_FILES.contains(&f.as_str()))
+    }
+
+    /// Value recorded under [`SOURCE_KEY`], pinned to the commit
+    pub fn source(&self) -> String {
+        format!("{}{}@{}", HUB_SCHEME, self.repo, self.sha)
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelInfo {
+    sha: String,
+    #[serde(default)]
+    siblings: Vec<Sibling>,
+}
+
+#[derive(Deserialize)]
+struct Sibling {
+    rfilename: String,
+}
+
+/// Tensors of a partly downloaded file, saved after each one so an interrupted
+/// pull picks up at the next tensor instead of starting over
+#[derive(Serialize, Deserialize, Default)]
+struct PullProgress {
+    header: String,
+    /// (tensor name, hash) in file order
+    done: Vec<(String, String)>,
+    /// Range hashes of the large tensors in `done`
+    #[serde(default)]
+    ranges: BTreeMap<String, RangeHashes>,
+}
+
+/// A parsed safetensors header, tensors sorted by where their data starts
+pub(crate) struct StreamHeader {
+    /// Offset of the first data byte, i.e. 8 + header length
+    pub data_start: u64,
+    pub tensors: Vec<(usize, String, RawTensorMetaData)>,
+}
+
+impl StreamHeader {
+    pub fn parse(json: &str) -> Result<Self> {
+        let invalid = |e: String| VektError::InvalidSafetensor(format!("Invalid header: {}", e));
+        let mut entries: IndexMap<String, serde_json::Value> =
+            serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        // Free-form file metadata, not a tensor
+        entries.shift_remove("__metadata__");
+
+        let mut tensors = Vec::with_capacity(entries.len());
+        for (index, (name, value)) in entries.into_iter().enumerate() {
+            let meta: RawTensorMetaData =
+                serde_json::from_value(value).map_err(|e| invalid(format!("'{}': {}", name, e)))?;
+            validate_tensor_meta(&name, &meta)?;
+            tensors.push((index, name, meta));
+        }
+        tensors.sort_by_key(|(_, _, meta)| meta.data_offsets.0);
+
+        let mut end = 0;
+        for (_, name, meta) in &tensors {
+            let (start, stop) = meta.data_offsets;
+            if start < end || stop < start {
+                return Err(invalid(format!("'{}' overlaps another tensor", name)));
+            }
+            end = stop;
+        }
+        Ok(StreamHeader {
+            data_start: 8 + json.len() as u64,
+            tensors,
+        })
+    }
+
+    pub fn total_size(&self) -> u64 {
+        let end = self.tensors.last().map_or(0, |(_, _, m)| m.data_offsets.1);
+        self.data_start + end as u64
+    }
+}
+
+/// Reads a response body in exact-sized pieces
+struct Body {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl Body {
+    /// Up to `max` bytes; fails if the body ends first
+    async fn next(&mut self, max: usize) -> Result<&[u8]> {
+        if self.pos == self.buffer.len() {
+            let chunk = self
+                .response
+                .chunk()
+                .await
+                .map_err(|e| VektError::RemoteError(format!("Download interrupted: {}", e)))?
+                .ok_or_else(|| {
+                    VektError::RemoteError("Download ended before the file did".to_string())
+                })?;
+            self.buffer = chunk.to_vec();
+            self.pos = 0;
+        }
+        let end = self.buffer.len().min(self.pos + max);
+        let piece = &self.buffer[self.pos..end];
+        self.pos = end;
+        Ok(piece)
+    }
+
+    async fn read_exact(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let piece = self.next(len - out.len()).await?;
+            out.extend_from_slice(piece);
+        }
+        Ok(out)
+    }
+
+    async fn skip(&mut self, mut len: u64) -> Result<()> {
+        while len > 0 {
+            len -= self.next(len.min(usize::MAX as u64) as usize).await?.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+fn http_error(e: reqwest::Error) -> VektError {
+    VektError::RemoteError(format!("Hugging Face Hub request failed: {}", e))
+}
+
+/// Percent-encodes everything except unreserved characters and, if `keep_slash`, `/`
+fn encode(value: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric()
+            || matches!(byte, b'-' | b'_' | b'.' | b'~')
+            || (keep_slash && byte == b'/')
+        {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Token from `HF_TOKEN`, or the one saved by `huggingface-cli login`
+fn token_from_env() -> Option<String> {
+    if let Some(token) = std::env::var("HF_TOKEN")
+        .ok()
+        .filter(|t| !t.trim().is_empty())
+    {
+        return Some(token.trim().to_string());
+    }
+    let hf_home = std::env::var_os("HF_HOME").map(PathBuf::from).or_else(|| {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache/huggingface"))
+    })?;
+    let token = std::fs::read_to_string(hf_home.join("token")).ok()?;
+    Some(token.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// Downloads models from the Hugging Face Hub straight into the blob store.
+/// Nothing is written to the Hugging Face cache.
+pub struct HubClient {
+    http: reqwest::Client,
+    endpoint: String,
+    token: Option<String>,
+}
+
+impl HubClient {
+    /// Client for `HF_ENDPOINT` (default huggingface.co), authenticated with
+    /// `HF_TOKEN` or the token saved by `huggingface-cli login`, if any
+    pub fn from_env() -> Result<Self> {
+        let endpoint = std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_ENDPOINT.into());
+        Self::new(&endpoint, token_from_env())
+    }
+
+    pub fn new(endpoint: &str, token: Option<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(concat!("vekt/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(http_error)?;
+        Ok(HubClient {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    async fn get(&self, url: &str, range_start: u64) -> Result<reqwest::Response> {
+        let mut request = self.http.get(url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if range_start > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", range_start));
+        }
+        let response = request.send().await.map_err(http_error)?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(VektError::CredentialError(format!(
+                    "Hugging Face Hub refused {} ({}). Set HF_TOKEN for gated or private models",
+                    url,
+                    response.status()
+                )))
+            }
+            StatusCode::NOT_FOUND => Err(VektError::RemoteNotFound(format!(
+                "{} (not found on the Hugging Face Hub)",
+                url
+            ))),
+            status => Err(VektError::RemoteError(format!(
+                "Hugging Face Hub returned {} for {}",
+                status, url
+            ))),
+        }
+    }
+
+    /// Resolves the revision to a commit and lists the files in it
+    pub async fn resolve(&self, repo: &HubRepo) -> Result<HubSnapshot> {
+        let url = format!(
+            "{}/api/models/{}/revision/{}",
+            self.endpoint,
+            repo.repo,
+            encode(&repo.revision, false)
+        );
+        let body = self.get(&url, 0).await?.bytes().await.map_err(http_error)?;
+        let info: ModelInfo = serde_json::from_slice(&body)?;
+        Ok(HubSnapshot {
+            repo: repo.repo.clone(),
+            sha: info.sha,
+            files: info.siblings.into_iter().map(|s| s.rfilename).collect(),
+        })
+    }
+
+    fn file_url(&self, snapshot: &HubSnapshot, file: &str) -> String {
+        format!(
+            "{}/{}/resolve/{}/{}",
+            self.endpoint,
+            snapshot.repo,
+            snapshot.sha,
+            encode(file, true)
+        )
+    }
+
+    /// Downloads a small file, e.g. a sidecar, into memory
+    pub async fn download(&self, snapshot: &HubSnapshot, file: &str) -> Result<Vec<u8>> {
+        let response = self.get(&self.file_url(snapshot, file), 0).await?;
+        Ok(response.bytes().await.map_err(http_error)?.to_vec())
+    }
+
+    /// Streams a safetensors file into the blob store, one tensor at a time, and
+    /// returns its manifest. Dropped connections are resumed with range requests,
+    /// and a pull that was killed continues from its last stored tensor when rerun.
+    pub async fn pull_safetensors(
+        &self,
+        snapshot: &HubSnapshot,
+        file: &str,
+    ) -> Result<VektManifest> {
+        validate_path_safe(file)?;
+        let key = format!("{}@{}:{}", snapshot.repo, snapshot.sha, file);
+        let progress_path = get_vekt_dir().join("hub").join(format!(
+            "{}.json",
+            blobs::compute_object_hash(key.as_bytes())
+        ));
+        let mut progress: PullProgress = std::fs::read(&progress_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        // A stored tensor whose blob was collected since is fetched again, along with
+        // every tensor after it
+        if let Some(missing) = progress
+            .done
+            .iter()
+            .position(|(_, hash)| !blobs::blob_exists(hash))
+        {
+            for (name, _) in progress.done.drain(missing..) {
+                progress.ranges.remove(&name);
+            }
+        }
+
+        let url = self.file_url(snapshot, file);
+        let mut attempt = 1;
+        let header = loop {
+            match self
+                .stream_tensors(&url, &mut progress, &progress_path)
+                .await
+            {
+                Ok(header) => break header,
+                Err(VektError::RemoteError(reason)) if attempt < MAX_ATTEMPTS => {
+                    warn!(
+                        "{} (attempt {}/{}), resuming after {} tensors",
+                        reason,
+                        attempt,
+                        MAX_ATTEMPTS,
+                        progress.done.len()
+                    );
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let hashes: BTreeMap<String, String> = progress.done.into_iter().collect();
+        let mut ranges = progress.ranges;
+        let mut tensors = BTreeMap::new();
+        for (index, name, meta) in &header.tensors {
+            let hash = hashes.get(name).ok_or_else(|| {
+                VektError::InvalidSafetensor(format!(
+                    "Tensor '{}' of {} was never downloaded",
+                    name, file
+                ))
+            })?;
+            tensors.insert(
+                name.clone(),
+                ManifestTensor {
+                    shape: meta.shape.clone(),
+                    dtype: meta.dtype.clone(),
+                    hash: hash.clone(),
+                    index: *index,
+                    extra: meta.extra.clone(),
+                    ranges: ranges.remove(name),
+                    stats: None,
+                },
+            );
+        }
+        let _ = std::fs::remove_file(&progress_path);
+
+        let mut metadata = BTreeMap::new();
+        metadata.insert(SOURCE_KEY.to_string(), snapshot.source());
+        Ok(VektManifest {
+            tensors,
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: header.total_size() as usize,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata,
+            layout: None,
+            placement: Default::default(),
+        })
+    }
+
+    /// One download attempt, starting after the tensors already in `progress`
+    async fn stream_tensors(
+        &self,
+        url: &str,
+        progress: &mut PullProgress,
+        progress_path: &std::path::Path,
+    ) -> Result<StreamHeader> {
+        let resume = if progress.header.is_empty() {
+            None
+        } else {
+            Some(StreamHeader::parse(&progress.header)?)
+        };
+        let start = match &resume {
+            Some(header) => {
+                let resumed = progress.done.len().min(header.tensors.len());
+                let end = match resumed {
+                    0 => 0,
+                    n => header.tensors[n - 1].2.data_offsets.1,
+                };
+                header.data_start + end as u64
+            }
+            None => 0,
+        };
+
+        let response = self.get(url, start).await?;
+        let ranged = response.status() == StatusCode::PARTIAL_CONTENT;
+        let mut body = Body {
+            response,
+            buffer: Vec::new(),
+            pos: 0,
+        };
+        // Servers that ignore the range send the whole file again
+        if !ranged {
+            body.skip(start).await?;
+        }
+
+        let header = match resume {
+            Some(header) => header,
+            None => {
+                let len = u64::from_le_bytes(body.read_exact(8).await?.try_into().unwrap());
+                let json =
+                    String::from_utf8(body.read_exact(len as usize).await?).map_err(|e| {
+                        VektError::InvalidSafetensor(format!("Header is not valid UTF-8: {}", e))
+                    })?;
+                let header = StreamHeader::parse(&json)?;
+                progress.header = json;
+                progress.done.clear();
+                header
+            }
+        };
+
+        let mut pos = start - header.data_start.min(start);
+        for (_, name, meta) in header.tensors.iter().skip(progress.done.len()) {
+            let (begin, end) = meta.data_offsets;
+            body.skip(begin as u64 - pos).await?;
+            let (hash, ranges) = store_tensor(&mut body, (end - begin) as u64).await?;
+            pos = end as u64;
+
+            progress.done.push((name.clone(), hash));
+            if let Some(ranges) = ranges {
+                progress.ranges.insert(name.clone(), ranges);
+            }
+            if let Some(parent) = progress_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_file_atomic(progress_path, &serde_json::to_vec(progress)?)?;
+        }
+        Ok(header)
+    }
+}
+
+/// Copies the next `len` bytes of `body` into the blob store, hashing as they arrive
+async fn store_tensor(body: &mut Body, len: u64) -> Result<(String, Option<RangeHashes>)> {
+    let store = get_store_path();
+    tokio::fs::create_dir_all(&store).await?;
+    let tmp_path = store.join(format!("hub-{:016x}.tmp", rand::random::<u64>()));
+    transaction::record(&tmp_path);
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    let mut hasher = RangeHasher::new(len);
+
+    let mut remaining = len;
+    let copied: Result<()> = async {
+        while remaining > 0 {
+            let piece = body.next(remaining.min(usize::MAX as u64) as usize).await?;
+            hasher.update(piece);
+            file.write_all(piece).await?;
+            remaining -= piece.len() as u64;
+        }
+        file.sync_all().await?;
+        Ok(())
+    }
+    .await;
+    drop(file);
+    if let Err(e) = copied {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(e);
+    }
+
+    let (hash, ranges) = hasher.finalize();
+    blobs::finalize_blob(&tmp_path, &hash)?;
+    Ok((hash, ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hub_repo() {
+        let repo = HubRepo::parse("hf://org/model").unwrap();
+        assert_eq!(repo.repo, "org/model");
+        assert_eq!(repo.revision, "main");
+        let repo = HubRepo::parse("hf://org/model.v2@refs/pr/1").unwrap();
+        assert_eq!(repo.revision, "refs/pr/1");
+        assert_eq!(encode(&repo.revision, false), "refs%2Fpr%2F1");
+
+        assert!(HubRepo::parse("s3://org/model").is_err());
+        assert!(HubRepo::parse("hf://model").is_err());
+        assert!(HubRepo::parse("hf://org/../model").is_err());
+        assert!(HubRepo::parse("hf://org/model@").is_err());
+    }
+
+    #[test]
+    fn test_stream_header_order() {
+        let header = StreamHeader::parse(
+            r#"{"__metadata__":{"format":"pt"},"b":{"dtype":"F32","shape":[1],"data_offsets":[4,8]},"a":{"dtype":"F32","shape":[1],"data_offsets":[0,4]}}"#,
+        )
+        .unwrap();
+        let names: Vec<_> = header
+            .tensors
+            .iter()
+            .map(|(i, n, _)| (*i, n.as_str()))
+            .collect();
+        assert_eq!(names, [(1, "a"), (0, "b")]);
+        assert_eq!(header.total_size(), header.data_start + 8);
+
+        assert!(
+            StreamHeader::parse(
+                r#"{"a":{"dtype":"F32","shape":[1],"data_offsets":[0,4]},"b":{"dtype":"F32","shape":[1],"data_offsets":[2,6]}}"#
+            )
+            .is_err()
+        );
+    }
+}
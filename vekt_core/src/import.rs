@@ -0,0 +1,833 @@
+//! Migration off git-lfs and DVC. The safetensors files a repository tracks with
+//! either are found through their pointer files, read from the local LFS or DVC
+//! cache or a local DVC remote, or downloaded from the repository's remote, and
+//! streamed into the blob store as they are read, checked against the hash the
+//! pointer records.
+use crate::errors::{Result, VektError};
+use crate::hub::SOURCE_KEY;
+use crate::object_store::{HttpStore, ObjectStore, is_http_url, response_reader};
+use crate::remote::RemoteClient;
+use crate::storage::VektManifest;
+use crate::stream::ingest_reader;
+use crate::validation::validate_path_safe;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::SyncIoBridge;
+
+/// Pointer files are a few lines; anything larger is a checked-out file
+const MAX_POINTER_SIZE: u64 = 1024;
+
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// Which tool tracks the files being imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerKind {
+    Lfs,
+    Dvc,
+}
+
+impl PointerKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PointerKind::Lfs => "git-lfs",
+            PointerKind::Dvc => "dvc",
+        }
+    }
+
+    fn algorithm(self) -> &'static str {
+        match self {
+            PointerKind::Lfs => "sha256",
+            PointerKind::Dvc => "md5",
+        }
+    }
+}
+
+/// A safetensors file tracked by git-lfs or DVC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub kind: PointerKind,
+    /// Path of the tracked file relative to the repository, `/`-separated
+    pub path: String,
+    /// SHA-256 of the file for git-lfs, MD5 for DVC
+    pub oid: String,
+    pub size: Option<u64>,
+}
+
+impl Pointer {
+    /// Value recorded under [`SOURCE_KEY`], e.g. `git-lfs:sha256:<oid>`
+    pub fn source(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.kind.name(),
+            self.kind.algorithm(),
+            self.oid
+        )
+    }
+}
+
+/// Parses a git-lfs pointer file into the object's SHA-256 and size
+pub fn parse_lfs_pointer(text: &str) -> Option<(String, u64)> {
+    let mut lines = text.lines();
+    if !lines
+        .next()?
+        .starts_with("version https://git-lfs.github.com/spec/")
+    {
+        return None;
+    }
+    let (mut oid, mut size) = (None, None);
+    for line in lines {
+        match line.split_once(' ') {
+            Some(("oid", value)) => {
+                oid = value
+                    .strip_prefix("sha256:")
+                    .filter(|hash| is_hex(hash, 64))
+                    .map(str::to_string)
+            }
+            Some(("size", value)) => size = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some((oid?, size?))
+}
+
+/// The `outs` of a `.dvc` file or `dvc.lock` as (path, md5, size). Only the YAML DVC
+/// writes is understood: block lists of `key: value` maps.
+pub fn parse_dvc_outs(text: &str) -> Vec<(String, String, Option<u64>)> {
+    let mut outs = Vec::new();
+    let mut entry: Option<BTreeMap<String, String>> = None;
+    let flush = |entry: &mut Option<BTreeMap<String, String>>, outs: &mut Vec<_>| {
+        if let Some(entry) = entry.take()
+            && let (Some(path), Some(md5)) = (entry.get("path"), entry.get("md5"))
+        {
+            let size = entry.get("size").and_then(|s| s.parse().ok());
+            outs.push((path.clone(), md5.clone(), size));
+        }
+    };
+    // Indent of `outs:` and of its list items
+    let mut section: Option<(usize, Option<usize>)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        if let Some((outs_at, item_at)) = section {
+            match trimmed.strip_prefix("- ") {
+                Some(rest) if indent >= outs_at && item_at.is_none_or(|at| at == indent) => {
+                    flush(&mut entry, &mut outs);
+                    section = Some((outs_at, Some(indent)));
+                    entry = Some(BTreeMap::new());
+                    yaml_pair(rest, entry.as_mut());
+                    continue;
+                }
+                _ if item_at.is_some_and(|at| indent == at + 2) => {
+                    yaml_pair(trimmed, entry.as_mut());
+                    continue;
+                }
+                // Nested deeper in an entry
+                _ if indent > outs_at => continue,
+                _ => {
+                    flush(&mut entry, &mut outs);
+                    section = None;
+                }
+            }
+        }
+        if trimmed.trim_end() == "outs:" {
+            section = Some((indent, None));
+        }
+    }
+    flush(&mut entry, &mut outs);
+    outs
+}
+
+fn yaml_pair(line: &str, entry: Option<&mut BTreeMap<String, String>>) {
+    let (Some(entry), Some((key, value))) = (entry, line.split_once(':')) else {
+        return;
+    };
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    if !value.is_empty() {
+        entry.insert(key.trim().to_string(), value.to_string());
+    }
+}
+
+/// Sections of a git- or DVC-style INI file. Section names are normalised to e.g.
+/// `remote "origin"`, whichever quoting the file uses; keys are lowercase.
+fn read_ini(path: &Path) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut sections: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let Ok(text) = fs::read_to_string(path) else {
+        return sections;
+    };
+    let mut current = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = name.trim().trim_matches('\'').to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches('"');
+            sections
+                .entry(current.clone())
+                .or_default()
+                .insert(key.trim().to_ascii_lowercase(), value.to_string());
+        }
+    }
+    sections
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// `<md5>` or `<md5>.dir`, the name DVC gives directory listings
+fn is_dvc_hash(value: &str) -> bool {
+    is_hex(value.strip_suffix(".dir").unwrap_or(value), 32)
+}
+
+/// Where DVC keeps an object under a cache or remote: the 3.x layout, then 2.x
+fn dvc_keys(md5: &str) -> [String; 2] {
+    [
+        format!("files/md5/{}/{}", &md5[..2], &md5[2..]),
+        format!("{}/{}", &md5[..2], &md5[2..]),
+    ]
+}
+
+/// `path` relative to `root`, `/`-separated
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Files under `dir` that `keep` accepts, skipping hidden, build and dependency
+/// directories
+fn walk(dir: &Path, keep: &dyn Fn(&str) -> bool, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                walk(&path, keep, found)?;
+            }
+        } else if keep(&name) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The repository's git directory, following the `.git` file of a worktree to the
+/// repository it shares LFS objects with
+fn git_dir(repo: &Path) -> Option<PathBuf> {
+    let dot_git = repo.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let text = fs::read_to_string(&dot_git).ok()?;
+    let dir = repo.join(text.strip_prefix("gitdir:")?.trim());
+    match fs::read_to_string(dir.join("commondir")) {
+        Ok(common) => Some(dir.join(common.trim())),
+        Err(_) => Some(dir),
+    }
+}
+
+/// Hashes a file as it is read, the way the pointer's tool does
+enum ContentHasher {
+    Sha256(Sha256),
+    Md5(md5::Context),
+}
+
+impl ContentHasher {
+    fn new(kind: PointerKind) -> Self {
+        match kind {
+            PointerKind::Lfs => ContentHasher::Sha256(Sha256::new()),
+            PointerKind::Dvc => ContentHasher::Md5(md5::Context::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::Sha256(hasher) => hasher.update(data),
+            ContentHasher::Md5(context) => context.consume(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            ContentHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            ContentHasher::Md5(context) => format!("{:x}", context.finalize()),
+        }
+    }
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: ContentHasher,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Fails unless `actual`, a hash of the kind `pointer` records, is the one it names
+fn check_hash(pointer: &Pointer, actual: &str) -> Result<()> {
+    let expected = pointer.oid.strip_suffix(".dir").unwrap_or(&pointer.oid);
+    if actual != expected {
+        return Err(VektError::HashMismatch {
+            expected: pointer.source(),
+            actual: format!("{}:{}", pointer.kind.algorithm(), actual),
+        });
+    }
+    Ok(())
+}
+
+/// Ingests `reader` and fails if it isn't the file `pointer` names
+fn ingest_verified(
+    reader: impl Read,
+    pointer: &Pointer,
+    on_blob: &dyn Fn(&str),
+) -> Result<VektManifest> {
+    let mut reader = HashingReader {
+        inner: BufReader::new(reader),
+        hasher: ContentHasher::new(pointer.kind),
+    };
+    let manifest = ingest_reader(&mut reader, on_blob)?;
+    io::copy(&mut reader, &mut io::sink())?;
+    check_hash(pointer, &reader.hasher.finish())?;
+    Ok(manifest)
+}
+
+#[derive(Deserialize)]
+struct DvcDirEntry {
+    md5: String,
+    relpath: String,
+    size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchObject>,
+}
+
+#[derive(Deserialize)]
+struct BatchObject {
+    oid: String,
+    actions: Option<BatchActions>,
+    error: Option<BatchError>,
+}
+
+#[derive(Deserialize)]
+struct BatchActions {
+    download: Option<BatchAction>,
+}
+
+#[derive(Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct BatchError {
+    code: u16,
+    message: String,
+}
+
+/// Where an object's bytes are read from
+enum Object {
+    /// Already on disk, in a cache or a local DVC remote
+    File(PathBuf),
+    /// Read as it arrives, without landing on disk first
+    Download(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+impl Object {
+    /// A blocking reader over the object, for use inside `block_in_place`
+    fn into_reader(self) -> Result<Box<dyn Read>> {
+        Ok(match self {
+            Object::File(path) => Box::new(File::open(path)?),
+            Object::Download(download) => Box::new(SyncIoBridge::new(download)),
+        })
+    }
+
+    async fn read_all(self) -> Result<Vec<u8>> {
+        match self {
+            Object::File(path) => Ok(tokio::fs::read(path).await?),
+            Object::Download(mut download) => {
+                let mut data = Vec::new();
+                download.read_to_end(&mut data).await?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// Where missing DVC objects are fetched from
+enum DvcRemote {
+    Dir(PathBuf),
+    Store { store: ObjectStore, prefix: String },
+}
+
+fn lfs_error(e: reqwest::Error) -> VektError {
+    VektError::RemoteError(format!("LFS request failed: {}", e))
+}
+
+/// Imports the safetensors files a git-lfs or DVC repository tracks
+pub struct Importer {
+    kind: PointerKind,
+    repo: PathBuf,
+    url: Option<String>,
+}
+
+impl Importer {
+    pub fn new(kind: PointerKind, repo: &Path) -> Self {
+        Importer {
+            kind,
+            repo: repo.to_path_buf(),
+            url: None,
+        }
+    }
+
+    pub fn kind(&self) -> PointerKind {
+        self.kind
+    }
+
+    /// Fetches files missing from the local cache from `url`, an LFS server or a DVC
+    /// remote, instead of the one the repository is configured with
+    pub fn with_url(mut self, url: Option<String>) -> Self {
+        self.url = url;
+        self
+    }
+
+    /// The safetensors files the repository tracks, sorted by path. git-lfs files
+    /// that are checked out in full have no pointer left to read and are skipped.
+    pub async fn pointers(&self) -> Result<Vec<Pointer>> {
+        let mut pointers = match self.kind {
+            PointerKind::Lfs => self.lfs_pointers()?,
+            PointerKind::Dvc => self.dvc_pointers().await?,
+        };
+        pointers.sort_by(|a, b| a.path.cmp(&b.path));
+        pointers.dedup_by(|a, b| a.path == b.path);
+        Ok(pointers)
+    }
+
+    /// Streams the file `pointer` names into the blob store and returns its manifest,
+    /// with the pointer recorded under [`SOURCE_KEY`]. A file that doesn't match the
+    /// pointer's hash fails the import. Downloads are ingested as they arrive, so
+    /// this needs a multi-threaded runtime.
+    pub async fn import(&self, pointer: &Pointer, on_blob: &dyn Fn(&str)) -> Result<VektManifest> {
+        validate_path_safe(&pointer.path)?;
+        let reader = self.object(pointer).await?.into_reader()?;
+        let mut manifest =
+            tokio::task::block_in_place(|| ingest_verified(reader, pointer, on_blob))?;
+        manifest
+            .metadata
+            .insert(SOURCE_KEY.to_string(), pointer.source());
+        Ok(manifest)
+    }
+
+    fn lfs_pointers(&self) -> Result<Vec<Pointer>> {
+        let mut files = Vec::new();
+        walk(
+            &self.repo,
+            &|name| name.ends_with(".safetensors"),
+            &mut files,
+        )?;
+        let mut pointers = Vec::new();
+        for path in files {
+            if path.metadata()?.len() > MAX_POINTER_SIZE {
+                continue;
+            }
+            let Some((oid, size)) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| parse_lfs_pointer(&text))
+            else {
+                continue;
+            };
+            pointers.push(Pointer {
+                kind: PointerKind::Lfs,
+                path: relative(&self.repo, &path),
+                oid,
+                size: Some(size),
+            });
+        }
+        Ok(pointers)
+    }
+
+    async fn dvc_pointers(&self) -> Result<Vec<Pointer>> {
+        let mut files = Vec::new();
+        let is_dvc_file = |name: &str| name.ends_with(".dvc") || name == "dvc.lock";
+        walk(&self.repo, &is_dvc_file, &mut files)?;
+        files.sort();
+
+        let mut pointers = Vec::new();
+        for file in files {
+            // Paths in a .dvc file or dvc.lock are relative to its directory
+            let dir = relative(&self.repo, file.parent().unwrap_or(&self.repo));
+            for (path, md5, size) in parse_dvc_outs(&fs::read_to_string(&file)?) {
+                if !is_dvc_hash(&md5) {
+                    continue;
+                }
+                let path = path.trim_start_matches("./").trim_end_matches('/');
+                let path = match dir.is_empty() {
+                    true => path.to_string(),
+                    false => format!("{}/{}", dir, path),
+                };
+                let out = Pointer {
+                    kind: PointerKind::Dvc,
+                    path,
+                    oid: md5,
+                    size,
+                };
+                if !out.oid.ends_with(".dir") {
+                    if out.path.ends_with(".safetensors") {
+                        pointers.push(out);
+                    }
+                    continue;
+                }
+                // A tracked directory: its listing names the files in it
+                let listing = self.object(&out).await?.read_all().await?;
+                check_hash(&out, &format!("{:x}", md5::compute(&listing)))?;
+                let entries: Vec<DvcDirEntry> = serde_json::from_slice(&listing)?;
+                pointers.extend(
+                    entries
+                        .into_iter()
+                        .filter(|e| e.relpath.ends_with(".safetensors") && is_dvc_hash(&e.md5))
+                        .map(|e| Pointer {
+                            kind: PointerKind::Dvc,
+                            path: format!("{}/{}", out.path, e.relpath),
+                            oid: e.md5,
+                            size: e.size,
+                        }),
+                );
+            }
+        }
+        Ok(pointers)
+    }
+
+    /// The object from the local cache, or else from the remote
+    async fn object(&self, pointer: &Pointer) -> Result<Object> {
+        if let Some(path) = self.cached(pointer) {
+            return Ok(Object::File(path));
+        }
+        match self.kind {
+            PointerKind::Lfs => self.fetch_lfs(pointer).await,
+            PointerKind::Dvc => self.fetch_dvc(pointer).await,
+        }
+    }
+
+    fn cached(&self, pointer: &Pointer) -> Option<PathBuf> {
+        let oid = &pointer.oid;
+        let candidates = match self.kind {
+            PointerKind::Lfs => {
+                let objects = git_dir(&self.repo)?.join("lfs").join("objects");
+                vec![objects.join(&oid[..2]).join(&oid[2..4]).join(oid)]
+            }
+            PointerKind::Dvc => {
+                let cache = self.dvc_cache_dir();
+                dvc_keys(oid).iter().map(|key| cache.join(key)).collect()
+            }
+        };
+        candidates.into_iter().find(|path| path.is_file())
+    }
+
+    /// `.dvc/config` overlaid with `.dvc/config.local`
+    fn dvc_config(&self) -> BTreeMap<String, BTreeMap<String, String>> {
+        let mut config = read_ini(&self.repo.join(".dvc").join("config"));
+        for (section, values) in read_ini(&self.repo.join(".dvc").join("config.local")) {
+            config.entry(section).or_default().extend(values);
+        }
+        config
+    }
+
+    fn dvc_cache_dir(&self) -> PathBuf {
+        let dvc_dir = self.repo.join(".dvc");
+        match self.dvc_config().get("cache").and_then(|c| c.get("dir")) {
+            Some(dir) => dvc_dir.join(dir),
+            None => dvc_dir.join("cache"),
+        }
+    }
+
+    fn dvc_remote(&self) -> Result<DvcRemote> {
+        let config = self.dvc_config();
+        let (url, endpoint) = match &self.url {
+            Some(url) => (url.clone(), None),
+            None => {
+                let name = config
+                    .get("core")
+                    .and_then(|core| core.get("remote"))
+                    .ok_or_else(|| {
+                        VektError::InvalidConfig(format!(
+                            "{} has no default DVC remote. Pass one with --url",
+                            self.repo.display()
+                        ))
+                    })?;
+                let remote = config
+                    .get(&format!("remote \"{}\"", name))
+                    .and_then(|remote| remote.get("url").map(|url| (remote, url)));
+                let Some((remote, url)) = remote else {
+                    return Err(VektError::RemoteNotFound(format!(
+                        "DVC remote '{}' has no url",
+                        name
+                    )));
+                };
+                // Local remotes in the config are relative to .dvc
+                let url = match url.contains("://") {
+                    true => url.clone(),
+                    false => self.repo.join(".dvc").join(url).display().to_string(),
+                };
+                (url, remote.get("endpointurl").cloned())
+            }
+        };
+
+        if let Some(location) = url.strip_prefix("s3://") {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            let bucket_url = match endpoint {
+                Some(endpoint) => format!("s3://{}?endpoint={}", bucket, endpoint),
+                None => format!("s3://{}", bucket),
+            };
+            return Ok(DvcRemote::Store {
+                store: RemoteClient::new(&bucket_url)?.into_store(),
+                prefix: prefix.trim_matches('/').to_string(),
+            });
+        }
+        if is_http_url(&url) {
+            return Ok(DvcRemote::Store {
+                store: ObjectStore::Http(HttpStore::new(&url, None)),
+                prefix: String::new(),
+            });
+        }
+        if url.contains("://") {
+            return Err(VektError::InvalidRemoteUrl(format!(
+                "'{}'. DVC remotes on s3://, http(s):// or a local directory can be imported from",
+                url
+            )));
+        }
+        Ok(DvcRemote::Dir(PathBuf::from(url)))
+    }
+
+    /// The object on the DVC remote. A local remote is read in place.
+    async fn fetch_dvc(&self, pointer: &Pointer) -> Result<Object> {
+        let not_found = || {
+            VektError::RemoteNotFound(format!(
+                "{} (md5 {}) is in neither the DVC cache nor the remote",
+                pointer.path, pointer.oid
+            ))
+        };
+        match self.dvc_remote()? {
+            DvcRemote::Dir(dir) => {
+                let path = dvc_keys(&pointer.oid)
+                    .iter()
+                    .map(|key| dir.join(key))
+                    .find(|path| path.is_file())
+                    .ok_or_else(not_found)?;
+                Ok(Object::File(path))
+            }
+            DvcRemote::Store { store, prefix } => {
+                let mut found = None;
+                for key in dvc_keys(&pointer.oid) {
+                    let key = match prefix.is_empty() {
+                        true => key,
+                        false => format!("{}/{}", prefix, key),
+                    };
+                    if store.exists(&key).await {
+                        found = Some(key);
+                        break;
+                    }
+                }
+                let key = found.ok_or_else(not_found)?;
+                let download = store.get_reader(&key).await.map_err(|e| {
+                    VektError::RemoteError(format!("Failed to fetch {}: {}", pointer.path, e))
+                })?;
+                Ok(Object::Download(download))
+            }
+        }
+    }
+
+    /// The LFS server: `lfs.url` from the git config or `.lfsconfig`, else derived
+    /// from the origin remote as git-lfs does
+    fn lfs_endpoint(&self) -> Result<String> {
+        if let Some(url) = &self.url {
+            return Ok(url.clone());
+        }
+        let git_config = git_dir(&self.repo)
+            .map(|dir| read_ini(&dir.join("config")))
+            .unwrap_or_default();
+        let lfs_config = read_ini(&self.repo.join(".lfsconfig"));
+        if let Some(url) = [&git_config, &lfs_config]
+            .into_iter()
+            .find_map(|config| config.get("lfs")?.get("url"))
+        {
+            return Ok(url.clone());
+        }
+        let origin = git_config
+            .get("remote \"origin\"")
+            .and_then(|remote| remote.get("url"))
+            .ok_or_else(|| {
+                VektError::InvalidConfig(format!(
+                    "{} has no origin remote to fetch LFS objects from. Pass the LFS server with --url",
+                    self.repo.display()
+                ))
+            })?;
+        if !is_http_url(origin) {
+            return Err(VektError::InvalidRemoteUrl(format!(
+                "'{}'. Only LFS servers over HTTP(S) are supported; pass one with --url",
+                origin
+            )));
+        }
+        let origin = origin.trim_end_matches('/');
+        Ok(match origin.ends_with(".git") {
+            true => format!("{}/info/lfs", origin),
+            false => format!("{}.git/info/lfs", origin),
+        })
+    }
+
+    /// Asks the LFS batch API where the object is, then starts downloading it
+    async fn fetch_lfs(&self, pointer: &Pointer) -> Result<Object> {
+        let endpoint = self.lfs_endpoint()?;
+        let mut url = reqwest::Url::parse(&endpoint)
+            .map_err(|e| VektError::InvalidRemoteUrl(format!("'{}': {}", endpoint, e)))?;
+        // Credentials in the URL are sent as basic auth to the batch API
+        let user = url.username().to_string();
+        let password = url.password().map(str::to_string);
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        let http = reqwest::Client::builder()
+            .user_agent(concat!("vekt/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(lfs_error)?;
+        let body = serde_json::json!({
+            "operation": "download",
+            "transfers": ["basic"],
+            "objects": [{ "oid": pointer.oid, "size": pointer.size }],
+        });
+        let mut request = http
+            .post(format!(
+                "{}/objects/batch",
+                url.as_str().trim_end_matches('/')
+            ))
+            .header(reqwest::header::ACCEPT, LFS_MEDIA_TYPE)
+            .header(reqwest::header::CONTENT_TYPE, LFS_MEDIA_TYPE)
+            .body(serde_json::to_vec(&body)?);
+        if !user.is_empty() {
+            request = request.basic_auth(user, password);
+        }
+        let response = request.send().await.map_err(lfs_error)?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(VektError::CredentialError(format!(
+                    "LFS server {} refused the request ({}). Put credentials in the --url",
+                    url,
+                    response.status()
+                )));
+            }
+            status => {
+                return Err(VektError::RemoteError(format!(
+                    "LFS server {} returned {}",
+                    url, status
+                )));
+            }
+        }
+        let batch: BatchResponse =
+            serde_json::from_slice(&response.bytes().await.map_err(lfs_error)?)?;
+        let object = batch
+            .objects
+            .into_iter()
+            .find(|object| object.oid == pointer.oid)
+            .ok_or_else(|| {
+                VektError::RemoteError(format!("LFS server didn't answer for {}", pointer.path))
+            })?;
+        if let Some(error) = object.error {
+            let reason = format!("{} on the LFS server: {}", pointer.path, error.message);
+            return Err(match error.code {
+                404 | 410 => VektError::RemoteNotFound(reason),
+                _ => VektError::RemoteError(reason),
+            });
+        }
+        let download = object
+            .actions
+            .and_then(|actions| actions.download)
+            .ok_or_else(|| {
+                VektError::RemoteError(format!(
+                    "LFS server offered no download for {}",
+                    pointer.path
+                ))
+            })?;
+
+        let mut request = http.get(&download.href);
+        for (name, value) in &download.header {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(lfs_error)?;
+        Ok(Object::Download(response_reader(response)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pointers() {
+        let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 12345\n",
+            oid
+        );
+        assert_eq!(parse_lfs_pointer(&pointer), Some((oid.to_string(), 12345)));
+        assert_eq!(parse_lfs_pointer("\u{0}\u{0}binary"), None);
+        assert_eq!(
+            parse_lfs_pointer(
+                "version https://git-lfs.github.com/spec/v1\noid sha256:zz\nsize 1\n"
+            ),
+            None
+        );
+
+        let dvc = "outs:\n- md5: 0123456789abcdef0123456789abcdef\n  size: 42\n  hash: md5\n  path: model.safetensors\n";
+        assert_eq!(
+            parse_dvc_outs(dvc),
+            vec![(
+                "model.safetensors".to_string(),
+                "0123456789abcdef0123456789abcdef".to_string(),
+                Some(42)
+            )]
+        );
+
+        let lock = "schema: '2.0'\nstages:\n  train:\n    cmd: python train.py\n    deps:\n    - path: data.csv\n      md5: ffffffffffffffffffffffffffffffff\n    outs:\n    - path: out/model.safetensors\n      hash: md5\n      md5: \"00000000000000000000000000000000\"\n      size: 7\n      meta:\n        path: ignored\n    - path: out/weights\n      md5: 11111111111111111111111111111111.dir\n  eval:\n    cmd: python eval.py\n";
+        assert_eq!(
+            parse_dvc_outs(lock),
+            vec![
+                (
+                    "out/model.safetensors".to_string(),
+                    "00000000000000000000000000000000".to_string(),
+                    Some(7)
+                ),
+                (
+                    "out/weights".to_string(),
+                    "11111111111111111111111111111111.dir".to_string(),
+                    None
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use crate::SafetensorFile;
+use crate::blobs;
+use crate::cancel::{self, CancellationToken};
+use crate::checksum;
+use crate::errors::{Result, VektError};
+use crate::hash::HashAlgorithm;
+use crate::plan::PlannedBlob;
+use crate::ranges::RangeHashes;
+use crate::storage::{FileChecksum, VektManifest};
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Files are fingerprinted in fixed-size chunks so a change can be localized
+/// to the tensors that overlap it.
+pub const INDEX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Cached hashing state for a single tensor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedTensor {
+    /// Absolute byte range of the tensor data within the file
+    pub range: (usize, usize),
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ranges: Option<RangeHashes>,
+}
+
+/// Cached state for one added file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexEntry {
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub file_hash: String,
+    pub chunk_size: usize,
+    pub chunk_hashes: Vec<String>,
+    pub tensors: BTreeMap<String, IndexedTensor>,
+    #[serde(default)]
+    pub checksum: Option<FileChecksum>,
+}
+
+/// The `.vekt/index` file: absolute file path -> cached hashing state
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct VektIndex {
+    pub entries: BTreeMap<String, IndexEntry>,
+}
+
+/// How much work an indexed add had to do
+#[derive(Debug, PartialEq, Eq)]
+pub enum IndexStatus {
+    /// Size and mtime matched the index; nothing was read
+    Unchanged,
+    /// File content was re-read; only tensors in changed chunks were re-hashed
+    Partial { rehashed: usize, reused: usize },
+    /// No usable index entry; every tensor was hashed
+    Full,
+}
+
+impl VektIndex {
+    pub(crate) fn path() -> PathBuf {
+        get_vekt_dir().join("index")
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(VektIndex::default());
+        }
+        let file = File::open(&path)?;
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| {
+            VektError::InvalidManifest(format!(
+                "Failed to parse index at {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        write_file_atomic(&Self::path(), json.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Returns (size, mtime seconds, mtime nanoseconds) for a file
+fn file_stamp(path: &Path) -> Result<(u64, u64, u32)> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((metadata.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+/// Hashes `data` in `INDEX_CHUNK_SIZE` pieces in parallel
+pub fn compute_chunk_hashes(data: &[u8]) -> Vec<String> {
+    data.par_chunks(INDEX_CHUNK_SIZE)
+        .map(blobs::compute_object_hash)
+        .collect()
+}
+
+/// Combines chunk hashes into a single file-level hash
+pub fn combine_chunk_hashes(chunk_hashes: &[String]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for hash in chunk_hashes {
+        hasher.update(hash.as_bytes());
+    }
+    hex::encode(hasher.finalize().as_bytes())
+}
+
+impl IndexEntry {
+    fn stamp_matches(&self, stamp: (u64, u64, u32)) -> bool {
+        (self.size, self.mtime_secs, self.mtime_nanos) == stamp
+    }
+
+    /// Returns hashes that can be reused for tensors of `file`: the tensor must sit at
+    /// the same absolute range as before and every chunk it overlaps must be unchanged.
+    fn reusable_hashes(
+        &self,
+        file: &SafetensorFile,
+        chunk_hashes: &[String],
+    ) -> HashMap<String, String> {
+        if self.chunk_size != INDEX_CHUNK_SIZE {
+            return HashMap::new();
+        }
+
+        let chunk_unchanged = |i: usize| self.chunk_hashes.get(i) == chunk_hashes.get(i);
+        let data_start = file.header_len + 8;
+
+        file.header
+            .iter()
+            .filter_map(|(name, meta)| {
+                let cached = self.tensors.get(name)?;
+                let range = (
+                    data_start + meta.data_offsets.0,
+                    data_start + meta.data_offsets.1,
+                );
+                if cached.range != range {
+                    return None;
+                }
+                let first = range.0 / INDEX_CHUNK_SIZE;
+                let last = range.1.saturating_sub(1).max(range.0) / INDEX_CHUNK_SIZE;
+                (first..=last)
+                    .all(chunk_unchanged)
+                    .then(|| (name.clone(), cached.hash.clone()))
+            })
+            .collect()
+    }
+
+    /// Whether the indexed hashes were made with the algorithm blobs are hashed with
+    /// now. Entries from before a switch are ignored, so every tensor gets rehashed.
+    fn uses_current_algorithm(&self) -> bool {
+        let current = HashAlgorithm::current();
+        self.tensors
+            .values()
+            .all(|tensor| HashAlgorithm::of(&tensor.hash) == current)
+    }
+
+    /// Copies cached range hashes onto tensors whose hash came from the index
+    /// instead of being computed
+    fn restore_ranges(&self, manifest: &mut VektManifest) {
+        for (name, tensor) in manifest.tensors.iter_mut() {
+            if tensor.ranges.is_none()
+                && let Some(cached) = self.tensors.get(name)
+                && cached.hash == tensor.hash
+            {
+                tensor.ranges = cached.ranges.clone();
+            }
+        }
+    }
+}
+
+/// Rebuilds a manifest from the file header and the indexed hashes without reading
+/// tensor data. Returns `None` if the index doesn't cover the header exactly.
+fn manifest_from_index(
+    file: &SafetensorFile,
+    entry: &IndexEntry,
+    on_blob: &(dyn Fn(&str) + Sync),
+) -> Option<VektManifest> {
+    let known: HashMap<String, String> = file
+        .header
+        .keys()
+        .map(|name| Some((name.clone(), entry.tensors.get(name)?.hash.clone())))
+        .collect::<Option<_>>()?;
+    if known.len() != entry.tensors.len() {
+        return None;
+    }
+    // Every hash is known, so no tensor is re-read; missing blobs are still detected
+    // and rewritten from the file.
+    let mut manifest = file.process_tensors(true, &known, on_blob).ok()?;
+    entry.restore_ranges(&mut manifest);
+    // Entries written before checksums were recorded take the slow path once
+    manifest.checksum = Some(entry.checksum.clone()?);
+    Some(manifest)
+}
+
+/// Hashes a safetensors file the way [`process_with_index`] would, reusing indexed
+/// hashes, but writes neither blobs nor the index. Returns the manifest, the names
+/// of the tensors whose data had to be read, and the file's skeleton blob.
+pub(crate) fn hash_with_index(path: &Path) -> Result<(VektManifest, Vec<String>, PlannedBlob)> {
+    let key = std::fs::canonicalize(path)?.to_string_lossy().into_owned();
+    let path_str = path.to_str().ok_or_else(|| {
+        VektError::InvalidSafetensor(format!("Path is not valid UTF-8: {}", path.display()))
+    })?;
+
+    let index = VektIndex::load()?;
+    let file = SafetensorFile::open(path_str)?;
+    let known = match index
+        .entries
+        .get(&key)
+        .filter(|entry| entry.uses_current_algorithm())
+    {
+        Some(entry) if entry.stamp_matches(file_stamp(path)?) => file
+            .header
+            .keys()
+            .filter_map(|name| Some((name.clone(), entry.tensors.get(name)?.hash.clone())))
+            .collect(),
+        Some(entry) => entry.reusable_hashes(&file, &compute_chunk_hashes(&file.mmap)),
+        None => HashMap::new(),
+    };
+    let manifest = file.process_tensors(false, &known, &|_| {})?;
+    let hashed = file
+        .header
+        .keys()
+        .filter(|name| !known.contains_key(*name))
+        .cloned()
+        .collect();
+
+    let data_start = file.header_len + 8;
+    let tensors = file
+        .header
+        .values()
+        .map(|meta| data_start + meta.data_offsets.0..data_start + meta.data_offsets.1);
+    let skeleton = checksum::safetensors_skeleton(&file.mmap, tensors.collect())?;
+    let skeleton = PlannedBlob {
+        hash: blobs::compute_blob_hash(&skeleton),
+        size: skeleton.len() as u64,
+    };
+    Ok((manifest, hashed, skeleton))
+}
+
+/// Adds a safetensors file, consulting and updating `.vekt/index` to avoid
+/// re-hashing data that has not changed since the last add.
+pub fn process_with_index(path: &Path, save_blobs: bool) -> Result<(VektManifest, IndexStatus)> {
+    process_with_index_streaming(path, save_blobs, &|_| {})
+}
+
+/// [`process_with_index`] that reports each blob as it lands in the store, see
+/// [`SafetensorFile::process_streaming`]
+pub fn process_with_index_streaming(
+    path: &Path,
+    save_blobs: bool,
+    on_blob: &(dyn Fn(&str) + Sync),
+) -> Result<(VektManifest, IndexStatus)> {
+    process_with_index_cancellable(path, save_blobs, on_blob, &CancellationToken::new())
+}
+
+/// [`process_with_index_streaming`] that stops once `cancel` is cancelled
+pub fn process_with_index_cancellable(
+    path: &Path,
+    save_blobs: bool,
+    on_blob: &(dyn Fn(&str) + Sync),
+    cancel: &CancellationToken,
+) -> Result<(VektManifest, IndexStatus)> {
+    let key = std::fs::canonicalize(path)?.to_string_lossy().into_owned();
+    let path_str = path.to_str().ok_or_else(|| {
+        VektError::InvalidSafetensor(format!("Path is not valid UTF-8: {}", path.display()))
+    })?;
+
+    let mut index = VektIndex::load()?;
+    let stamp = file_stamp(path)?;
+    let file = SafetensorFile::open(path_str)?.with_cancel(cancel.clone());
+    let previous = index
+        .entries
+        .get(&key)
+        .filter(|entry| entry.uses_current_algorithm());
+
+    // Fast path: untouched file, nothing to read
+    if save_blobs
+        && let Some(entry) = previous
+        && entry.stamp_matches(stamp)
+        && let Some(manifest) = manifest_from_index(&file, entry, on_blob)
+    {
+        return Ok((manifest, IndexStatus::Unchanged));
+    }
+
+    cancel::check(cancel, "add")?;
+    let chunk_hashes = compute_chunk_hashes(&file.mmap);
+    let (manifest, status) = match previous {
+        Some(entry) => {
+            let known = entry.reusable_hashes(&file, &chunk_hashes);
+            let mut manifest = file.process_streaming(save_blobs, &known, on_blob)?;
+            entry.restore_ranges(&mut manifest);
+            let status = IndexStatus::Partial {
+                rehashed: manifest.tensors.len() - known.len(),
+                reused: known.len(),
+            };
+            (manifest, status)
+        }
+        None => (
+            file.process_streaming(save_blobs, &HashMap::new(), on_blob)?,
+            IndexStatus::Full,
+        ),
+    };
+
+    let data_start = file.header_len + 8;
+    let tensors = file
+        .header
+        .iter()
+        .map(|(name, meta)| {
+            let tensor = IndexedTensor {
+                range: (
+                    data_start + meta.data_offsets.0,
+                    data_start + meta.data_offsets.1,
+                ),
+                hash: manifest.tensors[name].hash.clone(),
+                ranges: manifest.tensors[name].ranges.clone(),
+            };
+            (name.clone(), tensor)
+        })
+        .collect();
+
+    index.entries.insert(
+        key,
+        IndexEntry {
+            size: stamp.0,
+            mtime_secs: stamp.1,
+            mtime_nanos: stamp.2,
+            file_hash: combine_chunk_hashes(&chunk_hashes),
+            chunk_size: INDEX_CHUNK_SIZE,
+            chunk_hashes,
+            tensors,
+            checksum: manifest.checksum.clone(),
+        },
+    );
+    index.save()?;
+
+    Ok((manifest, status))
+}
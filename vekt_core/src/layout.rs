@@ -0,0 +1,238 @@
+#[cfg(feature = "native")]
+use crate::blobs;
+#[cfg(feature = "native")]
+use crate::errors::{Result, VektError};
+#[cfg(feature = "native")]
+use crate::storage::VektManifest;
+#[cfg(feature = "native")]
+use crate::validation::{validate_path_safe, verify_blob_hash};
+#[cfg(feature = "native")]
+use memmap2::Mmap;
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::fs::File;
+#[cfg(feature = "native")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "native")]
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Point in a skeleton where a tensor's bytes are put back
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Splice {
+    /// Offset in the skeleton, i.e. in the file with all tensor bytes cut out
+    pub offset: u64,
+    pub tensor: String,
+}
+
+/// One file of a non-safetensors model: everything except the tensor data, stored as
+/// a blob, plus where each tensor's bytes go. Restoring is byte-exact by construction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileLayout {
+    pub skeleton: String,
+    /// Size of the original file
+    pub size: u64,
+    pub splices: Vec<Splice>,
+}
+
+/// Source format of TensorFlow checkpoints, whose main file is the `.index`
+pub const TF_CHECKPOINT_FORMAT: &str = "tf_checkpoint";
+
+/// How to rebuild the original files of a model ingested from another format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceLayout {
+    /// Source format, e.g. `onnx`
+    pub format: String,
+    pub main: FileLayout,
+    /// Extra files next to the main file (e.g. ONNX external data), by relative path.
+    /// For TensorFlow checkpoints the key is the suffix after the checkpoint prefix.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub external: BTreeMap<String, FileLayout>,
+}
+
+impl SourceLayout {
+    pub fn skeleton_hashes(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.main.skeleton).chain(self.external.values().map(|f| &f.skeleton))
+    }
+
+    /// File extension of the main file
+    pub fn extension(&self) -> &str {
+        match self.format.as_str() {
+            TF_CHECKPOINT_FORMAT => "index",
+            format => format,
+        }
+    }
+
+    /// Where an external file goes when the main file is restored to `main`.
+    /// Checkpoint shards follow the prefix of the index file, so they are renamed with it.
+    pub fn external_path(&self, main: &Path, name: &str) -> PathBuf {
+        match self.format.as_str() {
+            TF_CHECKPOINT_FORMAT => main.with_extension(name),
+            _ => main.parent().unwrap_or(Path::new(".")).join(name),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl FileLayout {
+    /// Cuts the `regions` (byte range -> tensor name) out of `data`; the remaining
+    /// skeleton is stored as a blob when `save_blobs` is set. Regions must not overlap.
+    pub fn split(
+        data: &[u8],
+        mut regions: Vec<(Range<usize>, String)>,
+        save_blobs: bool,
+    ) -> Result<Self> {
+        regions.sort_by_key(|(range, _)| range.start);
+        let mut skeleton = Vec::with_capacity(data.len());
+        let mut splices = Vec::with_capacity(regions.len());
+        let mut pos = 0;
+        for (range, tensor) in regions {
+            if range.start < pos || range.start > range.end || range.end > data.len() {
+                return Err(VektError::InvalidManifest(format!(
+                    "Tensor '{}' data at {:?} overlaps another tensor or the end of file",
+                    tensor, range
+                )));
+            }
+            skeleton.extend_from_slice(&data[pos..range.start]);
+            splices.push(Splice {
+                offset: skeleton.len() as u64,
+                tensor,
+            });
+            pos = range.end;
+        }
+        skeleton.extend_from_slice(&data[pos..]);
+
+        let hash = if save_blobs {
+            blobs::save_blob_deduplicated(&skeleton)?.0
+        } else {
+            blobs::compute_blob_hash(&skeleton)
+        };
+        Ok(FileLayout {
+            skeleton: hash,
+            size: data.len() as u64,
+            splices,
+        })
+    }
+
+    /// [`split`](Self::split), also hashing (and storing) each region in parallel.
+    /// Returns the layout and (tensor name, hash) for every region.
+    pub fn split_hashed(
+        data: &[u8],
+        regions: Vec<(Range<usize>, String)>,
+        save_blobs: bool,
+    ) -> Result<(Self, Vec<(String, String)>)> {
+        // Checks the regions before any of them is stored
+        let layout = Self::split(data, regions.clone(), save_blobs)?;
+        let hashes = regions
+            .par_iter()
+            .map(|(range, name)| -> Result<(String, String)> {
+                let bytes = data.get(range.clone()).ok_or_else(|| {
+                    VektError::InvalidManifest(format!(
+                        "Tensor '{}' data at {:?} is outside the file ({} bytes)",
+                        name,
+                        range,
+                        data.len()
+                    ))
+                })?;
+                let hash = if save_blobs {
+                    blobs::save_blob_deduplicated(bytes)?.0
+                } else {
+                    blobs::compute_blob_hash(bytes)
+                };
+                Ok((name.clone(), hash))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((layout, hashes))
+    }
+
+    /// Writes the original file, reading skeleton and tensors from the blob store
+    pub fn write_to<W: Write>(&self, manifest: &VektManifest, writer: &mut W) -> Result<()> {
+        let skeleton = read_verified(&self.skeleton, "skeleton")?;
+        let mut pos = 0;
+        let mut written = 0u64;
+        for splice in &self.splices {
+            let tensor = manifest.tensors.get(&splice.tensor).ok_or_else(|| {
+                VektError::InvalidManifest(format!(
+                    "Layout references unknown tensor '{}'",
+                    splice.tensor
+                ))
+            })?;
+            let offset = splice.offset as usize;
+            if offset < pos || offset > skeleton.len() {
+                return Err(VektError::ManifestCorrupted(format!(
+                    "Splice for '{}' at {} is out of order",
+                    splice.tensor, offset
+                )));
+            }
+            writer.write_all(&skeleton[pos..offset])?;
+            let data = read_verified(&tensor.hash, &splice.tensor)?;
+            writer.write_all(&data)?;
+            written += (offset - pos + data.len()) as u64;
+            pos = offset;
+        }
+        writer.write_all(&skeleton[pos..])?;
+        written += (skeleton.len() - pos) as u64;
+
+        if written != self.size {
+            return Err(VektError::TensorCorruption(format!(
+                "Rebuilt file is {} bytes, expected {}",
+                written, self.size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Maps a source file for reading
+#[cfg(feature = "native")]
+pub(crate) fn map_file(path: &Path) -> Result<Mmap> {
+    let file = File::open(path)?;
+    // Same assumption as SafetensorFile: the file doesn't change while mapped
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+#[cfg(feature = "native")]
+pub(crate) fn read_verified(hash: &str, what: &str) -> Result<Vec<u8>> {
+    if !blobs::blob_exists(hash) {
+        return Err(VektError::BlobNotFound(format!(
+            "Blob {} not found for '{}'",
+            hash, what
+        )));
+    }
+    let data = blobs::read_blob(hash)?;
+    verify_blob_hash(&data, hash)?;
+    Ok(data)
+}
+
+#[cfg(feature = "native")]
+fn write_file(layout: &FileLayout, manifest: &VektManifest, path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    layout.write_to(manifest, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+impl VektManifest {
+    /// Rebuilds the original model files: the main file at `path` and any external
+    /// files next to it. Only valid for manifests that carry a [`SourceLayout`].
+    pub fn restore_layout(&self, path: &Path) -> Result<()> {
+        let layout = self.layout.as_ref().ok_or_else(|| {
+            VektError::InvalidManifest("Manifest has no source layout to restore".to_string())
+        })?;
+        write_file(&layout.main, self, path)?;
+
+        for (name, file) in &layout.external {
+            validate_path_safe(name)?;
+            let target = layout.external_path(path, name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_file(file, self, &target)?;
+        }
+        Ok(())
+    }
+}
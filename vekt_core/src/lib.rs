@@ -1,20 +1,155 @@
+#[cfg(feature = "native")]
+pub mod adapter;
+#[cfg(feature = "native")]
+pub mod async_api;
+#[cfg(feature = "native")]
+pub mod attachments;
+#[cfg(feature = "native")]
+pub mod audit;
+#[cfg(feature = "native")]
+pub mod backup;
+#[cfg(feature = "native")]
+pub mod blob_index;
+#[cfg(feature = "native")]
 pub mod blobs;
+#[cfg(feature = "native")]
+pub mod builder;
+#[cfg(feature = "native")]
+pub mod bundle;
+#[cfg(feature = "native")]
+pub mod cache;
+#[cfg(feature = "native")]
+pub mod cancel;
+#[cfg(feature = "native")]
+pub mod checkout;
+pub mod checks;
+#[cfg(feature = "native")]
+pub mod checksum;
+#[cfg(feature = "native")]
+pub mod commits;
+#[cfg(feature = "native")]
 pub mod compression;
+#[cfg(feature = "native")]
+pub mod config;
+#[cfg(all(unix, feature = "native"))]
+pub mod daemon;
 pub mod diff;
+#[cfg(feature = "native")]
+pub mod direct_io;
+#[cfg(feature = "native")]
+pub mod encoding;
+#[cfg(feature = "native")]
+pub mod ephemeral;
 pub mod errors;
+#[cfg(feature = "native")]
+pub mod filter;
+#[cfg(feature = "native")]
+pub mod follow;
+#[cfg(feature = "native")]
 pub mod gc;
+pub mod hash;
+pub mod header;
+#[cfg(feature = "native")]
+pub mod hooks;
+#[cfg(feature = "native")]
+pub mod hub;
+#[cfg(feature = "native")]
+pub mod import;
+#[cfg(feature = "native")]
+pub mod index;
+pub mod layout;
+#[cfg(feature = "native")]
+pub mod listing;
+#[cfg(feature = "native")]
+pub mod merge;
+#[cfg(feature = "native")]
+pub mod meta;
+pub mod numeric;
+#[cfg(feature = "native")]
+pub mod numpy;
+#[cfg(feature = "native")]
+pub mod object_store;
+#[cfg(feature = "native")]
+pub mod onnx;
+#[cfg(feature = "native")]
+pub mod pins;
+#[cfg(feature = "native")]
+pub mod plan;
+#[cfg(feature = "native")]
+pub(crate) mod protobuf;
+#[cfg(feature = "native")]
+pub mod quantize;
+pub mod ranges;
+#[cfg(feature = "native")]
+pub mod refcount;
+#[cfg(feature = "native")]
+pub mod refs;
+#[cfg(feature = "native")]
+pub mod releases;
+#[cfg(feature = "native")]
+pub mod remap;
+#[cfg(feature = "native")]
 pub mod remote;
+#[cfg(feature = "native")]
+pub mod report;
+#[cfg(feature = "native")]
+pub mod resize;
+pub mod sampling;
+#[cfg(feature = "native")]
+pub mod serve;
+#[cfg(feature = "native")]
+pub mod sparse;
+pub mod stats;
 pub mod storage;
+#[cfg(feature = "native")]
+pub mod stream;
+#[cfg(feature = "native")]
+pub mod swap;
+#[cfg(feature = "native")]
+pub mod tensor_filter;
+#[cfg(feature = "native")]
+pub mod tensorflow;
+#[cfg(feature = "native")]
+pub mod three_way;
+#[cfg(feature = "native")]
+pub mod tombstones;
+#[cfg(feature = "native")]
+pub mod transaction;
+#[cfg(feature = "native")]
+pub mod upstream;
+#[cfg(feature = "native")]
+pub mod usage;
 pub mod utils;
 pub mod validation;
+#[cfg(feature = "native")]
+pub mod watch;
+#[cfg(feature = "native")]
+pub mod writer;
 
+#[cfg(feature = "native")]
+pub use numpy::NumpyFile;
+#[cfg(feature = "native")]
+pub use onnx::OnnxFile;
+#[cfg(feature = "native")]
+pub use tensorflow::TfCheckpoint;
+#[cfg(feature = "native")]
+pub use writer::SafetensorWriter;
+
+#[cfg(feature = "native")]
 use crate::errors::{Result, VektError};
+#[cfg(feature = "native")]
+use cancel::CancellationToken;
+#[cfg(feature = "native")]
 use memmap2::Mmap;
+#[cfg(feature = "native")]
 use rayon::prelude::*;
-use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::collections::{BTreeMap, HashMap};
 
+#[cfg(feature = "native")]
 use storage::{ManifestTensor, RawHeader, VektManifest};
 
+#[cfg(feature = "native")]
 pub trait ModelArchiver {
     fn process(&self, save_blobs: bool) -> Result<VektManifest>;
     fn restore(
@@ -24,20 +159,31 @@ pub trait ModelArchiver {
     ) -> Result<()>;
 }
 
+#[cfg(feature = "native")]
 pub struct SafetensorFile {
     pub header: RawHeader,
     pub mmap: Mmap,
     pub header_len: usize,
+    cancel: CancellationToken,
 }
 
+#[cfg(feature = "native")]
 impl SafetensorFile {
     pub fn new(mmap: Mmap, header: RawHeader, header_len: usize) -> Self {
         SafetensorFile {
             header,
             mmap,
             header_len,
+            cancel: CancellationToken::new(),
         }
     }
+
+    /// Makes processing stop with [`VektError::Cancelled`] once `token` is cancelled.
+    /// Blobs already stored stay in the store until `gc` finds them unreferenced.
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
     pub fn open(path: &str) -> Result<Self> {
         // Open the file and create a memory-mapped buffer
         use std::fs::File;
@@ -51,45 +197,79 @@ impl SafetensorFile {
         // Standard in this domain (huggingface/safetensors does this).
         let mmap = unsafe { Mmap::map(&file)? };
 
-        // Read the header length (first 8 bytes)
-        let header_len_bytes = &mmap[0..8];
-        let header_len = usize::from_le_bytes(
-            header_len_bytes
-                .try_into()
-                .map_err(|_| VektError::InvalidSafetensor("Invalid header length".to_string()))?,
-        );
-
-        // Read the header JSON
-        if 8 + header_len > mmap.len() {
-            return Err(VektError::InvalidSafetensor(
-                "Header length exceeds file size".to_string(),
-            ));
-        }
-
-        let header_json_bytes = &mmap[8..8 + header_len];
-        let header_json_str = std::str::from_utf8(header_json_bytes).map_err(|e| {
-            VektError::InvalidSafetensor(format!("Header is not valid UTF-8: {}", e))
-        })?;
-        let header: RawHeader = serde_json::from_str(header_json_str)
-            .map_err(|e| VektError::InvalidSafetensor(format!("Invalid header JSON: {}", e)))?;
-
+        let (header, header_len) = header::parse_header(&mmap)?;
         Ok(SafetensorFile::new(mmap, header, header_len))
     }
 }
 
-impl ModelArchiver for SafetensorFile {
-    fn process(&self, save_blobs: bool) -> Result<VektManifest> {
+#[cfg(feature = "native")]
+impl SafetensorFile {
+    /// Processes the file like [`ModelArchiver::process`], but trusts the hashes in
+    /// `known_hashes` (tensor name -> hash) instead of re-reading those tensors.
+    ///
+    /// Callers must only pass hashes for tensors whose bytes are known to be unchanged.
+    pub fn process_with_known_hashes(
+        &self,
+        save_blobs: bool,
+        known_hashes: &HashMap<String, String>,
+    ) -> Result<VektManifest> {
+        self.process_streaming(save_blobs, known_hashes, &|_| {})
+    }
+
+    /// Like [`process_with_known_hashes`](Self::process_with_known_hashes), and calls
+    /// `on_blob` with each tensor's hash as soon as its blob is in the store, from
+    /// the worker thread that stored it. Lets uploads start before hashing finishes.
+    ///
+    /// With `save_blobs`, also records the checksum of the whole file.
+    pub fn process_streaming(
+        &self,
+        save_blobs: bool,
+        known_hashes: &HashMap<String, String>,
+        on_blob: &(dyn Fn(&str) + Sync),
+    ) -> Result<VektManifest> {
+        let mut manifest = self.process_tensors(save_blobs, known_hashes, on_blob)?;
+        if save_blobs {
+            cancel::check(&self.cancel, "add")?;
+            let data_start = self.header_len + 8;
+            let tensors = self
+                .header
+                .values()
+                .map(|meta| data_start + meta.data_offsets.0..data_start + meta.data_offsets.1);
+            manifest.checksum = Some(checksum::safetensors_checksum(
+                &self.mmap,
+                tensors.collect(),
+            )?);
+        }
+        Ok(manifest)
+    }
+
+    /// [`process_streaming`](Self::process_streaming) without the checksum, which
+    /// reads the whole file
+    pub(crate) fn process_tensors(
+        &self,
+        save_blobs: bool,
+        known_hashes: &HashMap<String, String>,
+        on_blob: &(dyn Fn(&str) + Sync),
+    ) -> Result<VektManifest> {
         let header_entries: Vec<(usize, &String, &storage::RawTensorMetaData)> = self
             .header
             .iter()
             .enumerate()
             .map(|(i, (k, v))| (i, k, v))
             .collect();
+        // The blob index and its write log answer every tensor's "already stored?"
+        // without listing the store
+        let stored = if save_blobs {
+            blob_index::BlobIndex::open()?
+        } else {
+            blob_index::BlobIndex::default()
+        };
 
-        // Step 1: Compute hashes in parallel (CPU Bound)
-        let processed_tensors: Vec<Result<(String, ManifestTensor, usize, usize)>> = header_entries
+        // Hash (CPU bound) and store (IO bound) each tensor in parallel
+        let processed_tensors: Vec<Result<(String, ManifestTensor)>> = header_entries
             .par_iter()
             .map(|(index, tensor_name, tensor_meta)| {
+                cancel::check(&self.cancel, "add")?;
                 let (start, end) = tensor_meta.data_offsets;
                 let absolute_start = self.header_len + 8 + start;
                 let absolute_end = self.header_len + 8 + end;
@@ -103,8 +283,26 @@ impl ModelArchiver for SafetensorFile {
                     )));
                 }
 
-                let data_slice = &self.mmap[absolute_start..absolute_end];
-                let hash_hex = blobs::compute_blob_hash(data_slice);
+                let data = &self.mmap[absolute_start..absolute_end];
+                let (mut hash_hex, ranges) = match known_hashes.get(*tensor_name) {
+                    Some(hash) => (hash.clone(), None),
+                    None => ranges::hash_with_ranges(data),
+                };
+
+                if save_blobs {
+                    if !stored.contains(&hash_hex) {
+                        if known_hashes.contains_key(*tensor_name) {
+                            // A reused tensor whose blob went missing. The indexed hash
+                            // isn't vouched for by these bytes, so the one they were
+                            // stored under is what the manifest records.
+                            (hash_hex, _) =
+                                blobs::save_blob_deduplicated(data).map_err(VektError::Io)?;
+                        } else {
+                            blobs::write_blob_with_hash(data, &hash_hex).map_err(VektError::Io)?;
+                        }
+                    }
+                    on_blob(&hash_hex);
+                }
 
                 Ok((
                     (*tensor_name).clone(),
@@ -113,45 +311,39 @@ impl ModelArchiver for SafetensorFile {
                         dtype: tensor_meta.dtype.clone(),
                         hash: hash_hex,
                         extra: tensor_meta.extra.clone(),
+                        ranges,
+                        stats: None,
                         index: *index,
                     },
-                    absolute_start,
-                    absolute_end,
                 ))
             })
             .collect();
 
         // Collect results and fail fast on error
         let mut results = BTreeMap::new();
-        let mut valid_entries = Vec::new();
-
         for res in processed_tensors {
-            let (name, tensor, start, end) = res?;
+            let (name, tensor) = res?;
             results.insert(name, tensor);
-            valid_entries.push((start, end));
-        }
-
-        // Step 2: Save blobs (IO Bound)
-        // We use try_for_each to handle errors, and par_iter to potentially parallelize IO
-        // (though disk IO is often better serialized or throttled, rayon handles this reasonably well)
-        if save_blobs {
-            valid_entries
-                .par_iter()
-                .try_for_each(|(start, end)| -> Result<()> {
-                    let data = &self.mmap[*start..*end];
-                    match blobs::save_blob_deduplicated(data) {
-                        Ok(_) => Ok(()),
-                        Err(e) => Err(VektError::Io(e)),
-                    }
-                })?;
         }
 
         Ok(VektManifest {
             tensors: results,
-            version: "1.0".to_string(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
             total_size: self.mmap.len(),
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
         })
     }
+}
+
+#[cfg(feature = "native")]
+impl ModelArchiver for SafetensorFile {
+    fn process(&self, save_blobs: bool) -> Result<VektManifest> {
+        self.process_with_known_hashes(save_blobs, &HashMap::new())
+    }
 
     fn restore(
         manifest: &VektManifest,
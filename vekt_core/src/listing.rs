@@ -0,0 +1,219 @@
+//! What `vekt list` shows: every manifest the repository knows about, whether a
+//! commit on HEAD tracks it, or it only exists on a remote, with its size, tensor
+//! count, last change and tags.
+use crate::blobs;
+use crate::commits;
+use crate::errors::Result;
+use crate::meta::MetaFilter;
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::find_manifest_files;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+
+/// Where a listed manifest lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestState {
+    /// A local file that HEAD's commit holds as it is
+    Tracked,
+    /// A local file HEAD's commit holds, changed since
+    Modified,
+    /// A local file no commit on HEAD holds
+    Untracked,
+    /// Only pushed to the remote being listed
+    Remote,
+}
+
+impl ManifestState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ManifestState::Tracked => "tracked",
+            ManifestState::Modified => "modified",
+            ManifestState::Untracked => "untracked",
+            ManifestState::Remote => "remote",
+        }
+    }
+
+    pub fn parse(state: &str) -> Option<Self> {
+        [
+            ManifestState::Tracked,
+            ManifestState::Modified,
+            ManifestState::Untracked,
+            ManifestState::Remote,
+        ]
+        .into_iter()
+        .find(|s| s.as_str() == state)
+    }
+}
+
+/// One manifest in a listing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListedManifest {
+    /// Path relative to the repository root, or the name it was pushed under
+    pub name: String,
+    pub state: ManifestState,
+    /// Hash of the manifest object
+    pub hash: String,
+    /// Size of the restored model
+    pub size: u64,
+    pub tensors: usize,
+    /// Unix time the local file was last written; `None` for remote manifests
+    pub modified: Option<u64>,
+    /// Tags pointing at exactly this manifest, local or on the remote
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ListedManifest {
+    fn new(name: String, state: ManifestState, hash: String, manifest: VektManifest) -> Self {
+        ListedManifest {
+            name,
+            state,
+            hash,
+            size: manifest.total_size as u64,
+            tensors: manifest.tensors.len(),
+            modified: None,
+            tags: Vec::new(),
+            metadata: manifest.metadata,
+        }
+    }
+}
+
+/// Order of a listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Tensors,
+    Modified,
+}
+
+impl SortKey {
+    pub fn parse(key: &str) -> Option<Self> {
+        match key {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "tensors" => Some(SortKey::Tensors),
+            "modified" => Some(SortKey::Modified),
+            _ => None,
+        }
+    }
+}
+
+/// Which manifests a listing keeps
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    /// Metadata every kept manifest matches
+    pub metadata: Vec<MetaFilter>,
+    /// Kept states; empty keeps all
+    pub states: Vec<ManifestState>,
+}
+
+impl ListFilter {
+    pub fn matches(&self, listed: &ListedManifest) -> bool {
+        (self.states.is_empty() || self.states.contains(&listed.state))
+            && self.metadata.iter().all(|f| f.matches(&listed.metadata))
+    }
+}
+
+/// Manifest files under `root`, with their state against HEAD's commit and the
+/// local tags pointing at them. Files that don't parse are skipped with a warning.
+pub fn list_local(root: &Path) -> Result<Vec<ListedManifest>> {
+    let committed = match refs::head_commit()? {
+        Some(hash) => commits::load_commit(&hash)?.manifests,
+        None => BTreeMap::new(),
+    };
+    let tags = tags_by_hash(refs::list_tags()?);
+
+    let mut listed = Vec::new();
+    for path in find_manifest_files(root)? {
+        let manifest = match VektManifest::load(&path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!(
+                    "Failed to parse manifest at {}: {}. Skipping this manifest.",
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(&manifest)?);
+        let name = commits::relative_key(root, &path)?;
+        let state = match committed.get(&name) {
+            Some(committed) if *committed == hash => ManifestState::Tracked,
+            Some(_) => ManifestState::Modified,
+            None => ManifestState::Untracked,
+        };
+        let mut entry = ListedManifest::new(name, state, hash, manifest);
+        entry.modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|age| age.as_secs());
+        entry.tags = tags.get(&entry.hash).cloned().unwrap_or_default();
+        listed.push(entry);
+    }
+    Ok(listed)
+}
+
+/// Manifests pushed to a remote that no local file matches. Files are pushed under
+/// their file name, so a pushed manifest matches a local file with that name that
+/// holds the same manifest; another version or an unrelated model pushed under the
+/// same name is listed.
+pub fn remote_only(
+    local: &[ListedManifest],
+    remote: Vec<(String, VektManifest)>,
+    remote_tags: Vec<(String, String)>,
+) -> Result<Vec<ListedManifest>> {
+    let local_files: HashSet<(&str, &str)> = local
+        .iter()
+        .map(|entry| {
+            let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
+            (file_name, entry.hash.as_str())
+        })
+        .collect();
+    let tags = tags_by_hash(remote_tags);
+    let mut listed = Vec::new();
+    for (name, manifest) in remote {
+        let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(&manifest)?);
+        if local_files.contains(&(name.as_str(), hash.as_str())) {
+            continue;
+        }
+        let mut entry = ListedManifest::new(name, ManifestState::Remote, hash, manifest);
+        entry.tags = tags.get(&entry.hash).cloned().unwrap_or_default();
+        listed.push(entry);
+    }
+    Ok(listed)
+}
+
+fn tags_by_hash(tags: Vec<(String, String)>) -> HashMap<String, Vec<String>> {
+    let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, hash) in tags {
+        by_hash.entry(hash).or_default().push(name);
+    }
+    by_hash
+}
+
+/// Sorts by `key`, largest, most or newest first except for names. Ties are broken
+/// by name.
+pub fn sort(listed: &mut [ListedManifest], key: SortKey, reverse: bool) {
+    listed.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Size => b.size.cmp(&a.size),
+            SortKey::Tensors => b.tensors.cmp(&a.tensors),
+            SortKey::Modified => b.modified.cmp(&a.modified),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    });
+    if reverse {
+        listed.reverse();
+    }
+}
@@ -0,0 +1,251 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::numeric::{decode_floats, encode_floats, is_float_dtype};
+use crate::storage::VektManifest;
+use crate::validation::verify_blob_hash;
+
+/// How matching tensors are combined
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeMethod {
+    /// Weighted element-wise mean of any number of models. Equal weights when `None`.
+    Average { weights: Option<Vec<f64>> },
+    /// `(1 - t) * a + t * b` for exactly two models
+    Linear { t: f64 },
+    /// Spherical interpolation between two models, treating each tensor as one vector
+    Slerp { t: f64 },
+}
+
+impl MergeMethod {
+    /// Builds a method from its CLI name plus optional weights / interpolation factor
+    pub fn parse(method: &str, weights: Option<Vec<f64>>, t: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&t) {
+            return Err(VektError::InvalidManifest(format!(
+                "Interpolation factor must be within [0, 1], got {}",
+                t
+            )));
+        }
+        match method {
+            "average" => Ok(MergeMethod::Average { weights }),
+            "linear" => Ok(MergeMethod::Linear { t }),
+            "slerp" => Ok(MergeMethod::Slerp { t }),
+            _ => Err(VektError::InvalidManifest(format!(
+                "Unknown merge method '{}'. Use average, linear or slerp",
+                method
+            ))),
+        }
+    }
+
+    /// Short description recorded in the merged manifest's metadata
+    pub fn describe(&self) -> String {
+        match self {
+            MergeMethod::Average { weights: None } => "average".to_string(),
+            MergeMethod::Average {
+                weights: Some(weights),
+            } => format!(
+                "average weights={}",
+                weights
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            MergeMethod::Linear { t } => format!("linear t={}", t),
+            MergeMethod::Slerp { t } => format!("slerp t={}", t),
+        }
+    }
+}
+
+/// Parses comma-separated weights such as `0.3,0.7`
+pub fn parse_weights(s: &str) -> Result<Vec<f64>> {
+    s.split(',')
+        .map(|w| {
+            w.trim()
+                .parse::<f64>()
+                .map_err(|_| VektError::InvalidManifest(format!("Invalid weights '{}'", s)))
+        })
+        .collect()
+}
+
+/// What happened to each tensor during a merge
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Tensors whose values were combined into a new blob
+    pub merged: Vec<String>,
+    /// Tensors identical in every model, reused without decoding
+    pub shared: Vec<String>,
+    /// Tensors taken unchanged from the first model (non-float or missing elsewhere)
+    pub copied: Vec<String>,
+}
+
+fn weighted_average(inputs: &[Vec<f64>], weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    let mut out = vec![0f64; inputs[0].len()];
+    for (values, weight) in inputs.iter().zip(weights) {
+        let scale = weight / total;
+        for (acc, v) in out.iter_mut().zip(values) {
+            *acc += scale * v;
+        }
+    }
+    out
+}
+
+fn slerp(a: &[f64], b: &[f64], t: f64) -> Vec<f64> {
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let (norm_a, norm_b) = (norm(a), norm(b));
+    let lerp = || {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (1.0 - t) * x + t * y)
+            .collect()
+    };
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return lerp();
+    }
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>() / (norm_a * norm_b);
+    let omega = dot.clamp(-1.0, 1.0).acos();
+    let sin_omega = omega.sin();
+    // Nearly parallel vectors: SLERP degenerates to linear interpolation
+    if sin_omega.abs() < 1e-6 {
+        return lerp();
+    }
+    let scale_a = ((1.0 - t) * omega).sin() / sin_omega;
+    let scale_b = (t * omega).sin() / sin_omega;
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| scale_a * x + scale_b * y)
+        .collect()
+}
+
+fn read_verified(hash: &str) -> Result<Vec<u8>> {
+    let data = blobs::read_blob(hash)?;
+    verify_blob_hash(&data, hash)?;
+    Ok(data)
+}
+
+/// Merges `models` tensor by tensor and writes the combined tensors as new blobs.
+///
+/// The first model decides which tensors the result contains. Float tensors present
+/// in every model with the same shape and dtype are combined; tensors that are
+/// identical everywhere are reused as-is; anything else is copied from the first model.
+pub fn merge_manifests(
+    models: &[VektManifest],
+    method: &MergeMethod,
+) -> Result<(VektManifest, MergeReport)> {
+    if models.len() < 2 {
+        return Err(VektError::InvalidManifest(
+            "Merging needs at least two models".to_string(),
+        ));
+    }
+    let weights = match method {
+        MergeMethod::Average { weights: Some(w) } => {
+            if w.len() != models.len() || w.iter().any(|x| *x < 0.0) || w.iter().sum::<f64>() <= 0.0
+            {
+                return Err(VektError::InvalidManifest(format!(
+                    "Expected {} non-negative weights with a positive sum, got {:?}",
+                    models.len(),
+                    w
+                )));
+            }
+            w.clone()
+        }
+        MergeMethod::Average { weights: None } => vec![1.0; models.len()],
+        MergeMethod::Linear { .. } | MergeMethod::Slerp { .. } => {
+            if models.len() != 2 {
+                return Err(VektError::InvalidManifest(format!(
+                    "{} merges exactly two models, got {}",
+                    method.describe(),
+                    models.len()
+                )));
+            }
+            Vec::new()
+        }
+    };
+
+    let base = &models[0];
+    let mut merged = VektManifest {
+        checksum: None,
+        ..base.clone()
+    };
+    let mut report = MergeReport::default();
+
+    for (name, tensor) in &base.tensors {
+        let others: Option<Vec<_>> = models[1..].iter().map(|m| m.tensors.get(name)).collect();
+        let Some(others) = others else {
+            report.copied.push(name.clone());
+            continue;
+        };
+        if others.iter().all(|o| o.hash == tensor.hash) {
+            report.shared.push(name.clone());
+            continue;
+        }
+        if let Some(other) = others
+            .iter()
+            .find(|o| o.shape != tensor.shape || o.dtype != tensor.dtype)
+        {
+            return Err(VektError::InvalidManifest(format!(
+                "Cannot merge '{}': {} {:?} vs {} {:?}",
+                name, tensor.dtype, tensor.shape, other.dtype, other.shape
+            )));
+        }
+        if !is_float_dtype(&tensor.dtype) {
+            report.copied.push(name.clone());
+            continue;
+        }
+
+        let mut inputs = vec![decode_floats(&read_verified(&tensor.hash)?, &tensor.dtype)?];
+        for other in &others {
+            inputs.push(decode_floats(&read_verified(&other.hash)?, &tensor.dtype)?);
+        }
+        let values = match method {
+            MergeMethod::Average { .. } => weighted_average(&inputs, &weights),
+            MergeMethod::Linear { t } => weighted_average(&inputs, &[1.0 - t, *t]),
+            MergeMethod::Slerp { t } => slerp(&inputs[0], &inputs[1], *t),
+        };
+
+        let (hash, _) = blobs::save_blob_deduplicated(&encode_floats(&values, &tensor.dtype)?)?;
+        let entry = merged.tensors.get_mut(name).unwrap();
+        entry.hash = hash;
+        entry.stats = None;
+        report.merged.push(name.clone());
+    }
+
+    merged
+        .metadata
+        .insert("merge".to_string(), method.describe());
+    Ok((merged, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_parse() {
+        assert_eq!(
+            MergeMethod::parse("slerp", None, 0.3).unwrap(),
+            MergeMethod::Slerp { t: 0.3 }
+        );
+        assert!(MergeMethod::parse("ties", None, 0.5).is_err());
+        assert!(MergeMethod::parse("linear", None, 1.5).is_err());
+        assert_eq!(parse_weights("0.3, 0.7").unwrap(), vec![0.3, 0.7]);
+    }
+
+    #[test]
+    fn test_weighted_average() {
+        let inputs = vec![vec![1.0, 2.0], vec![3.0, 6.0]];
+        assert_eq!(weighted_average(&inputs, &[1.0, 1.0]), vec![2.0, 4.0]);
+        assert_eq!(weighted_average(&inputs, &[3.0, 1.0]), vec![1.5, 3.0]);
+    }
+
+    #[test]
+    fn test_slerp() {
+        let a = [1.0, 0.0];
+        let b = [0.0, 1.0];
+        let mid = slerp(&a, &b, 0.5);
+        let expected = std::f64::consts::FRAC_1_SQRT_2;
+        assert!((mid[0] - expected).abs() < 1e-12 && (mid[1] - expected).abs() < 1e-12);
+        assert_eq!(slerp(&a, &b, 0.0), vec![1.0, 0.0]);
+        // Parallel inputs fall back to linear interpolation
+        assert_eq!(slerp(&[1.0, 1.0], &[2.0, 2.0], 0.5), vec![1.5, 1.5]);
+    }
+}
@@ -0,0 +1,92 @@
+//! User metadata on manifests: training run IDs, dataset hashes, licenses, eval
+//! scores, whole model cards. It is kept in the manifest's `metadata`, so it travels
+//! with the weights through push, pull, tags and commits.
+use crate::adapter::ADAPTER_BASE_KEY;
+use crate::ephemeral::EXPIRES_AT_KEY;
+use crate::errors::{Result, VektError};
+use crate::hub::SOURCE_KEY;
+use crate::quantize::{QUANT_SCHEME_KEY, QUANTIZED_FROM_KEY};
+use crate::storage::VektManifest;
+use crate::watch::matches_pattern;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Keys vekt itself writes and reads back, e.g. to keep an adapter's base model
+/// alive, so users can't set or remove them by hand
+pub const RESERVED_KEYS: [&str; 5] = [
+    EXPIRES_AT_KEY,
+    ADAPTER_BASE_KEY,
+    QUANTIZED_FROM_KEY,
+    QUANT_SCHEME_KEY,
+    SOURCE_KEY,
+];
+
+/// Keys are letters, digits and `_ - . / :`, so they can't be confused with the
+/// `=` of a filter, and can't be one of the [reserved](RESERVED_KEYS) keys
+pub fn validate_key(key: &str) -> Result<()> {
+    validate_key_chars(key)?;
+    if RESERVED_KEYS.contains(&key) {
+        return Err(VektError::InvalidMetadata(format!(
+            "'{}' is reserved for vekt's own use",
+            key
+        )));
+    }
+    Ok(())
+}
+
+fn validate_key_chars(key: &str) -> Result<()> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || "_-./:".contains(c);
+    if key.is_empty() || !key.chars().all(valid) {
+        return Err(VektError::InvalidMetadata(format!(
+            "'{}' is not a valid key. Use letters, digits and _ - . / :",
+            key
+        )));
+    }
+    Ok(())
+}
+
+/// Sets `key` to `value`, returning the value it replaced
+pub fn set(manifest: &mut VektManifest, key: &str, value: &str) -> Result<Option<String>> {
+    validate_key(key)?;
+    Ok(manifest.metadata.insert(key.to_string(), value.to_string()))
+}
+
+/// Removes `key`, returning its value
+pub fn unset(manifest: &mut VektManifest, key: &str) -> Result<Option<String>> {
+    validate_key(key)?;
+    Ok(manifest.metadata.remove(key))
+}
+
+/// A `vekt list --filter`: `key=value`, where the value may use `*` and `?`, or just
+/// `key` for manifests that have it at all. Reserved keys can be filtered on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl FromStr for MetaFilter {
+    type Err = VektError;
+
+    fn from_str(filter: &str) -> Result<Self> {
+        let (key, value) = match filter.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (filter, None),
+        };
+        validate_key_chars(key)?;
+        Ok(MetaFilter {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+impl MetaFilter {
+    pub fn matches(&self, metadata: &BTreeMap<String, String>) -> bool {
+        match (metadata.get(&self.key), &self.value) {
+            (Some(value), Some(pattern)) => matches_pattern(pattern, value),
+            (found, None) => found.is_some(),
+            (None, Some(_)) => false,
+        }
+    }
+}
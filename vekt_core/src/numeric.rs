@@ -0,0 +1,163 @@
+use crate::errors::{Result, VektError};
+
+/// Floating-point dtypes whose values can be decoded for arithmetic
+pub const FLOAT_DTYPES: &[&str] = &["F64", "F32", "F16", "BF16"];
+
+pub fn is_float_dtype(dtype: &str) -> bool {
+    FLOAT_DTYPES.contains(&dtype)
+}
+
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exp = ((half >> 10) & 0x1f) as u32;
+    let mant = (half & 0x3ff) as u32;
+    let bits = match exp {
+        0 if mant == 0 => sign,
+        // Subnormal: value is mant * 2^-24
+        0 => {
+            let value = mant as f32 * f32::from_bits(0x3380_0000);
+            return if sign != 0 { -value } else { value };
+        }
+        0x1f => sign | 0x7f80_0000 | (mant << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (mant << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Converts to IEEE half precision, rounding to nearest even
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        let nan = if mant != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exp = exp - 127 + 15;
+    if half_exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exp <= 0 {
+        if half_exp < -10 {
+            return sign;
+        }
+        let full = mant | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        let halfway = 1 << (shift - 1);
+        let remainder = full & ((1 << shift) - 1);
+        let mut rounded = full >> shift;
+        if remainder > halfway || (remainder == halfway && rounded & 1 == 1) {
+            rounded += 1;
+        }
+        return sign | rounded as u16;
+    }
+
+    // Rounding may carry into the exponent, which correctly yields infinity on overflow
+    let mut rounded = ((half_exp as u32) << 10) | (mant >> 13);
+    let remainder = mant & 0x1fff;
+    if remainder > 0x1000 || (remainder == 0x1000 && rounded & 1 == 1) {
+        rounded += 1;
+    }
+    sign | rounded as u16
+}
+
+pub fn bf16_to_f32(value: u16) -> f32 {
+    f32::from_bits((value as u32) << 16)
+}
+
+/// Converts to bfloat16, rounding to nearest even
+pub fn f32_to_bf16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    if value.is_nan() {
+        return ((bits >> 16) | 0x40) as u16;
+    }
+    ((bits + 0x7fff + ((bits >> 16) & 1)) >> 16) as u16
+}
+
+fn unsupported(dtype: &str) -> VektError {
+    VektError::InvalidManifest(format!(
+        "Arithmetic is only supported for {} tensors, not {}",
+        FLOAT_DTYPES.join("/"),
+        dtype
+    ))
+}
+
+/// Decodes little-endian tensor bytes into `f64` values
+pub fn decode_floats(data: &[u8], dtype: &str) -> Result<Vec<f64>> {
+    let values = match dtype {
+        "F64" => data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect(),
+        "F32" => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+            .collect(),
+        "F16" => data
+            .chunks_exact(2)
+            .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])) as f64)
+            .collect(),
+        "BF16" => data
+            .chunks_exact(2)
+            .map(|b| bf16_to_f32(u16::from_le_bytes([b[0], b[1]])) as f64)
+            .collect(),
+        _ => return Err(unsupported(dtype)),
+    };
+    Ok(values)
+}
+
+/// Encodes `f64` values back into little-endian bytes of `dtype`
+pub fn encode_floats(values: &[f64], dtype: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(crate::utils::tensor_byte_size(&[values.len()], dtype)?);
+    match dtype {
+        "F64" => values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+        "F32" => values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&(*v as f32).to_le_bytes())),
+        "F16" => values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&f32_to_f16(*v as f32).to_le_bytes())),
+        "BF16" => values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&f32_to_bf16(*v as f32).to_le_bytes())),
+        _ => return Err(unsupported(dtype)),
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_precision_roundtrip() {
+        for value in [0.0f32, 1.0, -2.5, 0.333_251_95, 65504.0] {
+            assert_eq!(f16_to_f32(f32_to_f16(value)), value);
+        }
+        // Smallest subnormal, 2^-24
+        assert_eq!(f32_to_f16(f32::from_bits(0x3380_0000)), 0x0001);
+        assert_eq!(f16_to_f32(0x0001), f32::from_bits(0x3380_0000));
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+        for value in [0.0f32, 1.0, -3.0, 0.5] {
+            assert_eq!(bf16_to_f32(f32_to_bf16(value)), value);
+        }
+        // 1 + 2^-8 is exactly halfway between two bf16 values and rounds to even
+        assert_eq!(f32_to_bf16(1.0 + 1.0 / 256.0), 0x3f80);
+    }
+
+    #[test]
+    fn test_decode_encode_floats() {
+        let values = vec![1.0, -0.5, 2.0];
+        for dtype in FLOAT_DTYPES {
+            let bytes = encode_floats(&values, dtype).unwrap();
+            assert_eq!(decode_floats(&bytes, dtype).unwrap(), values);
+        }
+        assert!(decode_floats(&[0u8; 4], "I32").is_err());
+    }
+}
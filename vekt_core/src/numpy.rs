@@ -0,0 +1,384 @@
+use crate::ModelArchiver;
+use crate::checksum::bytes_checksum;
+use crate::errors::{Result, VektError};
+use crate::layout::{FileLayout, SourceLayout, map_file};
+use crate::storage::{ManifestTensor, VektManifest};
+use indexmap::IndexMap;
+use memmap2::Mmap;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+const ZIP_MAGIC: &[u8] = b"PK";
+
+// Zip record signatures and fields, from the PKWARE APPNOTE
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END: u32 = 0x0605_4b50;
+const ZIP64_END: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+const ZIP_STORED: u16 = 0;
+const ZIP_SATURATED: u32 = 0xFFFF_FFFF;
+
+static NPY_FIELD_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_npy_field_regex() -> &'static Regex {
+    NPY_FIELD_REGEX
+        .get_or_init(|| Regex::new(r"'(\w+)'\s*:\s*('[^']*'|True|False|\([^)]*\))").unwrap())
+}
+
+/// Maps a NumPy dtype descriptor to the dtype names used in manifests
+pub fn npy_dtype(descr: &str) -> String {
+    let name = match descr {
+        "<f8" => "F64",
+        "<f4" => "F32",
+        "<f2" => "F16",
+        "<c8" => "C64",
+        "<i8" => "I64",
+        "<i4" => "I32",
+        "<i2" => "I16",
+        "|i1" => "I8",
+        "<u8" => "U64",
+        "<u4" => "U32",
+        "<u2" => "U16",
+        "|u1" => "U8",
+        "|b1" => "BOOL",
+        other => return format!("NPY_{}", other),
+    };
+    name.to_string()
+}
+
+fn malformed(reason: &str) -> VektError {
+    VektError::InvalidManifest(format!("Malformed NumPy file: {}", reason))
+}
+
+fn bytes_at(data: &[u8], pos: usize, len: usize) -> Result<&[u8]> {
+    pos.checked_add(len)
+        .and_then(|end| data.get(pos..end))
+        .ok_or_else(|| malformed("record runs past the end of the file"))
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(
+        bytes_at(data, pos, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(
+        bytes_at(data, pos, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(
+        bytes_at(data, pos, 8)?.try_into().unwrap(),
+    ))
+}
+
+/// The parts of a `.npy` header needed to locate and describe the array
+#[derive(Debug, Clone, PartialEq)]
+struct NpyHeader {
+    descr: String,
+    fortran_order: bool,
+    shape: Vec<usize>,
+    /// Offset of the array data from the start of the `.npy` bytes
+    data_start: usize,
+}
+
+/// Parses a `.npy` header. Returns `None` for object and structured arrays, whose
+/// bytes are not plain array data.
+fn parse_npy_header(data: &[u8]) -> Result<Option<NpyHeader>> {
+    if !data.starts_with(NPY_MAGIC) {
+        return Err(malformed("missing .npy magic"));
+    }
+    let (header_len, header_start) = match bytes_at(data, 6, 1)?[0] {
+        1 => (u16_at(data, 8)? as usize, 10),
+        2 | 3 => (u32_at(data, 8)? as usize, 12),
+        version => return Err(malformed(&format!("unsupported .npy version {}", version))),
+    };
+    let header = std::str::from_utf8(bytes_at(data, header_start, header_len)?)
+        .map_err(|_| malformed(".npy header is not text"))?;
+
+    let mut fields = BTreeMap::new();
+    for captures in get_npy_field_regex().captures_iter(header) {
+        fields.insert(captures[1].to_string(), captures[2].to_string());
+    }
+    let Some(descr) = fields
+        .get("descr")
+        .and_then(|d| d.strip_prefix('\'')?.strip_suffix('\''))
+    else {
+        return Ok(None);
+    };
+    if descr.contains('O') {
+        return Ok(None);
+    }
+    let shape = fields
+        .get("shape")
+        .ok_or_else(|| malformed(".npy header has no shape"))?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| malformed("bad .npy shape")))
+        .collect::<Result<Vec<usize>>>()?;
+
+    Ok(Some(NpyHeader {
+        descr: descr.to_string(),
+        fortran_order: fields.get("fortran_order").is_some_and(|v| v == "True"),
+        shape,
+        data_start: header_start + header_len,
+    }))
+}
+
+/// One member of a zip archive; `range` is its (possibly compressed) data in the file
+struct ZipMember {
+    name: String,
+    method: u16,
+    range: Range<usize>,
+}
+
+fn find_end_record(data: &[u8]) -> Result<usize> {
+    // The end record is 22 bytes plus a comment of up to 64 KiB
+    let last = data
+        .len()
+        .checked_sub(22)
+        .ok_or_else(|| malformed("too small for a zip archive"))?;
+    (last.saturating_sub(u16::MAX as usize)..=last)
+        .rev()
+        .find(|&pos| u32_at(data, pos).ok() == Some(ZIP_END))
+        .ok_or_else(|| malformed("zip end record not found"))
+}
+
+/// Lists the members of a zip archive from its central directory
+fn zip_members(data: &[u8]) -> Result<Vec<ZipMember>> {
+    let end = find_end_record(data)?;
+    let mut count = u16_at(data, end + 10)? as u64;
+    let mut pos = u32_at(data, end + 16)? as u64;
+    if count == u16::MAX as u64 || pos == ZIP_SATURATED as u64 {
+        let locator = end
+            .checked_sub(20)
+            .filter(|&p| u32_at(data, p).ok() == Some(ZIP64_LOCATOR))
+            .ok_or_else(|| malformed("zip64 locator not found"))?;
+        let record = u64_at(data, locator + 8)? as usize;
+        if u32_at(data, record)? != ZIP64_END {
+            return Err(malformed("zip64 end record not found"));
+        }
+        count = u64_at(data, record + 32)?;
+        pos = u64_at(data, record + 48)?;
+    }
+
+    let mut pos = pos as usize;
+    let mut members = Vec::new();
+    for _ in 0..count {
+        if u32_at(data, pos)? != ZIP_CENTRAL_HEADER {
+            return Err(malformed("bad zip central directory entry"));
+        }
+        let method = u16_at(data, pos + 10)?;
+        let mut size = u32_at(data, pos + 20)? as u64;
+        let uncompressed = u32_at(data, pos + 24)?;
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let mut offset = u32_at(data, pos + 42)? as u64;
+        let name = String::from_utf8_lossy(bytes_at(data, pos + 46, name_len)?).into_owned();
+
+        // Zip64 values are present only for the fields saturated above, in this order
+        let extra = pos + 46 + name_len;
+        let mut field = extra;
+        while field + 4 <= extra + extra_len {
+            let len = u16_at(data, field + 2)? as usize;
+            if u16_at(data, field)? == ZIP64_EXTRA {
+                let mut value = field + 4;
+                if uncompressed == ZIP_SATURATED {
+                    value += 8;
+                }
+                if size == ZIP_SATURATED as u64 {
+                    size = u64_at(data, value)?;
+                    value += 8;
+                }
+                if offset == ZIP_SATURATED as u64 {
+                    offset = u64_at(data, value)?;
+                }
+            }
+            field += 4 + len;
+        }
+
+        let local = offset as usize;
+        if u32_at(data, local)? != ZIP_LOCAL_HEADER {
+            return Err(malformed(&format!("bad local header for '{}'", name)));
+        }
+        let start =
+            local + 30 + u16_at(data, local + 26)? as usize + u16_at(data, local + 28)? as usize;
+        let range = start..start + size as usize;
+        bytes_at(data, start, size as usize)?;
+        members.push(ZipMember {
+            name,
+            method,
+            range,
+        });
+        pos = extra + extra_len + comment_len;
+    }
+    Ok(members)
+}
+
+/// Array name, header and absolute data range
+type Array = (String, NpyHeader, Range<usize>);
+
+/// A `.npy` array or `.npz` archive of arrays.
+///
+/// Array data in `.npy` files and uncompressed `.npz` members becomes one blob per
+/// array. Compressed members (`np.savez_compressed`) and object arrays stay in the
+/// skeleton, so they restore exactly but are not deduplicated.
+pub struct NumpyFile {
+    pub path: PathBuf,
+    pub mmap: Mmap,
+}
+
+impl NumpyFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(NumpyFile {
+            path: path.to_path_buf(),
+            mmap: map_file(path)?,
+        })
+    }
+
+    /// The source format, and each array with its absolute data range
+    fn arrays(&self) -> Result<(&'static str, Vec<Array>)> {
+        let data = &self.mmap[..];
+        if data.starts_with(NPY_MAGIC) {
+            let name = self
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "array".to_string());
+            let arrays = parse_npy_header(data)?
+                .map(|header| {
+                    let range = header.data_start..data.len();
+                    (name, header, range)
+                })
+                .into_iter()
+                .collect();
+            return Ok(("npy", arrays));
+        }
+        if !data.starts_with(ZIP_MAGIC) {
+            return Err(malformed("not a .npy or .npz file"));
+        }
+
+        let mut arrays = Vec::new();
+        for member in zip_members(data)? {
+            let Some(name) = member.name.strip_suffix(".npy") else {
+                continue;
+            };
+            if member.method != ZIP_STORED {
+                continue;
+            }
+            if let Some(header) = parse_npy_header(&data[member.range.clone()])? {
+                let start = member.range.start + header.data_start;
+                if start > member.range.end {
+                    return Err(malformed(&format!("'{}' header overruns its data", name)));
+                }
+                arrays.push((name.to_string(), header, start..member.range.end));
+            }
+        }
+        Ok(("npz", arrays))
+    }
+}
+
+impl ModelArchiver for NumpyFile {
+    fn process(&self, save_blobs: bool) -> Result<VektManifest> {
+        let (format, arrays) = self.arrays()?;
+
+        let mut tensors = BTreeMap::new();
+        let mut regions = Vec::new();
+        for (index, (name, header, range)) in arrays.into_iter().enumerate() {
+            let mut extra = IndexMap::new();
+            if header.fortran_order {
+                extra.insert("fortran_order".to_string(), serde_json::Value::Bool(true));
+            }
+            let tensor = ManifestTensor {
+                shape: header.shape,
+                dtype: npy_dtype(&header.descr),
+                hash: String::new(),
+                index,
+                extra,
+                ranges: None,
+                stats: None,
+            };
+            if tensors.insert(name.clone(), tensor).is_some() {
+                return Err(malformed(&format!("duplicate array '{}'", name)));
+            }
+            regions.push((range, name));
+        }
+
+        let (main, hashes) = FileLayout::split_hashed(&self.mmap, regions, save_blobs)?;
+        for (name, hash) in hashes {
+            tensors.get_mut(&name).unwrap().hash = hash;
+        }
+        Ok(VektManifest {
+            tensors,
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: self.mmap.len(),
+            checksum: save_blobs.then(|| bytes_checksum(&self.mmap)),
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: Some(SourceLayout {
+                format: format.to_string(),
+                main,
+                external: BTreeMap::new(),
+            }),
+            placement: Default::default(),
+        })
+    }
+
+    fn restore(manifest: &VektManifest, output_path: &Path, filter: Option<&str>) -> Result<()> {
+        manifest.restore(output_path, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npy(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut out = NPY_MAGIC.to_vec();
+        out.extend_from_slice(&[1, 0]);
+        out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn test_npy_header() {
+        let file = npy(
+            "{'descr': '<f4', 'fortran_order': True, 'shape': (2, 3), }\n",
+            &[0; 24],
+        );
+        let header = parse_npy_header(&file).unwrap().unwrap();
+        assert_eq!(header.descr, "<f4");
+        assert!(header.fortran_order);
+        assert_eq!(header.shape, vec![2, 3]);
+        assert_eq!(file.len() - header.data_start, 24);
+
+        let scalar = npy(
+            "{'descr': '|u1', 'fortran_order': False, 'shape': (), }",
+            &[7],
+        );
+        assert!(parse_npy_header(&scalar).unwrap().unwrap().shape.is_empty());
+
+        let objects = npy(
+            "{'descr': '|O', 'fortran_order': False, 'shape': (1,), }",
+            &[],
+        );
+        assert_eq!(parse_npy_header(&objects).unwrap(), None);
+        assert!(parse_npy_header(b"PK\x03\x04").is_err());
+
+        assert_eq!(npy_dtype("<f2"), "F16");
+        assert_eq!(npy_dtype(">f4"), "NPY_>f4");
+    }
+}
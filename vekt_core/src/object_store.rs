@@ -0,0 +1,701 @@
+use crate::validation::ServerSideEncryption;
+use futures::TryStreamExt;
+use s3::bucket::Bucket;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// Environment variable holding the bearer token for HTTP remotes and `vekt serve`
+pub const TOKEN_ENV: &str = "VEKT_TOKEN";
+
+/// Whether `url` points at a `vekt serve` instance rather than an S3 bucket
+pub fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// An object listed by [`ObjectStore::list`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+}
+
+/// Failure of a single store operation, with the HTTP status when there was a response
+#[derive(Debug)]
+pub(crate) struct StoreError {
+    pub status: Option<u16>,
+    message: String,
+}
+
+impl StoreError {
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self.status, Some(401 | 403)) && !self.is_archived()
+    }
+
+    /// S3 refuses reads of archived objects that haven't been restored with 403
+    /// `InvalidObjectState`
+    pub fn is_archived(&self) -> bool {
+        self.status == Some(403) && self.message.contains("InvalidObjectState")
+    }
+
+    /// Failures worth retrying: no response at all, timeouts, throttling and server
+    /// errors
+    pub fn is_transient(&self) -> bool {
+        match self.status {
+            None => true,
+            Some(status) => status >= 500 || matches!(status, 408 | 429),
+        }
+    }
+
+    /// A [`create_only`](PutOptions::create_only) upload found the object already there
+    pub fn is_precondition_failed(&self) -> bool {
+        self.status == Some(412)
+    }
+}
+
+/// Archive state of an object, from its metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ArchiveStatus {
+    pub storage_class: Option<String>,
+    /// A restore was requested and hasn't finished yet
+    pub restoring: bool,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<s3::error::S3Error> for StoreError {
+    fn from(e: s3::error::S3Error) -> Self {
+        let status = match &e {
+            s3::error::S3Error::HttpFailWithBody(status, _) => Some(*status),
+            _ => None,
+        };
+        StoreError {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for StoreError {
+    fn from(e: reqwest::Error) -> Self {
+        StoreError {
+            status: e.status().map(|s| s.as_u16()),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError {
+            status: None,
+            message: e.to_string(),
+        }
+    }
+}
+
+type StoreResult<T> = std::result::Result<T, StoreError>;
+
+/// Where a remote keeps its objects: an S3 bucket, or another machine running
+/// `vekt serve`. Keys are the same for both (`blobs/<hash>`, `manifests/<name>`, ...).
+pub(crate) enum ObjectStore {
+    S3(Box<Bucket>),
+    Http(HttpStore),
+}
+
+/// How an object is stored by S3: storage class, encryption and tags. `vekt serve`
+/// remotes ignore them, except for `create_only`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PutOptions<'a> {
+    pub storage_class: Option<&'a str>,
+    pub encryption: Option<&'a ServerSideEncryption>,
+    pub tags: Option<&'a BTreeMap<String, String>>,
+    /// Only upload if the object doesn't exist yet (`If-None-Match: *`), failing with
+    /// 412 otherwise. Checking and creating happen in one step on the store.
+    pub create_only: bool,
+}
+
+impl PutOptions<'_> {
+    /// Request headers asking for these options
+    fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(storage_class) = self.storage_class {
+            headers.push(("x-amz-storage-class", storage_class.to_string()));
+        }
+        match self.encryption {
+            Some(ServerSideEncryption::Aes256) => {
+                headers.push(("x-amz-server-side-encryption", "AES256".to_string()));
+            }
+            Some(ServerSideEncryption::Kms(key)) => {
+                headers.push(("x-amz-server-side-encryption", "aws:kms".to_string()));
+                if let Some(key) = key {
+                    headers.push((
+                        "x-amz-server-side-encryption-aws-kms-key-id",
+                        key.to_string(),
+                    ));
+                }
+            }
+            None => {}
+        }
+        if let Some(tags) = self.tags.filter(|tags| !tags.is_empty()) {
+            headers.push(("x-amz-tagging", tagging_header(tags)));
+        }
+        if self.create_only {
+            headers.push(("if-none-match", "*".to_string()));
+        }
+        headers
+    }
+}
+
+/// Tags as the URL query string `x-amz-tagging` takes
+fn tagging_header(tags: &BTreeMap<String, String>) -> String {
+    fn encode(s: &str) -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Header carrying the SHA-256 of an upload. S3 rejects the upload if the bytes it
+/// received don't match, and echoes the header back when they do.
+const CHECKSUM_SHA256: &str = "x-amz-checksum-sha256";
+
+/// Checksums of bytes being uploaded, to compare with what S3 says it received
+struct Checksums {
+    /// Base64, as [`CHECKSUM_SHA256`] takes it
+    sha256: String,
+    md5: String,
+}
+
+impl Checksums {
+    fn of(data: &[u8]) -> Self {
+        use base64::Engine;
+        use sha2::Digest;
+        Checksums {
+            sha256: base64::engine::general_purpose::STANDARD.encode(sha2::Sha256::digest(data)),
+            md5: format!("{:x}", md5::compute(data)),
+        }
+    }
+
+    /// Compares the response headers of an upload with what was sent: the echoed
+    /// SHA-256 if there is one, otherwise the ETag, which is the MD5 of the bytes
+    /// unless S3 encrypted them with KMS. Whether it did is read from the response's
+    /// `x-amz-server-side-encryption`, since a bucket's default encryption applies
+    /// without being asked for. Stores that return neither (e.g. S3-compatible ones
+    /// that ignore checksums, with KMS) leave the upload unconfirmed.
+    fn confirm(
+        &self,
+        key: &str,
+        headers: &HashMap<String, String>,
+        options: &PutOptions<'_>,
+    ) -> StoreResult<bool> {
+        let mismatch = |what: &str| StoreError {
+            status: None,
+            message: format!("{} of {} doesn't match what was sent", what, key),
+        };
+        if let Some(echoed) = headers.get(CHECKSUM_SHA256) {
+            return match *echoed == self.sha256 {
+                true => Ok(true),
+                false => Err(mismatch("Checksum reported by the store")),
+            };
+        }
+        let kms = matches!(options.encryption, Some(ServerSideEncryption::Kms(_)))
+            || headers
+                .get("x-amz-server-side-encryption")
+                .is_some_and(|sse| sse.starts_with("aws:kms"));
+        match headers.get("etag").map(|etag| etag.trim_matches('"')) {
+            Some(etag) if etag.eq_ignore_ascii_case(&self.md5) => Ok(true),
+            Some(etag) if !kms && etag.len() == 32 && hex::decode(etag).is_ok() => {
+                Err(mismatch("ETag"))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the ETag of an uploaded multipart part confirms its bytes. S3 has
+    /// already checked them against the `Content-MD5` sent with every part, and part
+    /// responses don't say how the bucket encrypts, so an ETag that isn't the MD5
+    /// (e.g. under default SSE-KMS) leaves the part unconfirmed instead of failing.
+    fn confirm_part(&self, etag: &str) -> bool {
+        etag.trim_matches('"').eq_ignore_ascii_case(&self.md5)
+    }
+}
+
+/// `vekt serve` only stores blobs whose content matches their hash
+fn is_verified_by_serve(key: &str) -> bool {
+    key.starts_with("blobs/")
+}
+
+/// Part size of multipart uploads
+const S3_PART_SIZE: usize = 16 << 20;
+
+/// Uploads `file` in parts, one after another. rust-s3's own streaming upload drops
+/// custom headers once it switches to multipart, so the options are set on the
+/// request that starts the upload here. Returns whether every part's ETag confirmed
+/// its bytes.
+async fn put_multipart(
+    bucket: &Bucket,
+    key: &str,
+    file: &mut File,
+    options: PutOptions<'_>,
+) -> StoreResult<bool> {
+    const CONTENT_TYPE: &str = "application/octet-stream";
+    let mut optioned = bucket.clone();
+    for (name, value) in options.headers() {
+        let value = value.parse().map_err(|_| StoreError {
+            status: None,
+            message: format!("Invalid {} '{}'", name, value),
+        })?;
+        optioned.extra_headers_mut().insert(name, value);
+    }
+    let upload = optioned
+        .initiate_multipart_upload(key, CONTENT_TYPE)
+        .await?;
+
+    let parts = async {
+        let mut parts = Vec::new();
+        let mut verified = true;
+        loop {
+            let mut chunk = Vec::with_capacity(S3_PART_SIZE);
+            (&mut *file)
+                .take(S3_PART_SIZE as u64)
+                .read_to_end(&mut chunk)
+                .await?;
+            if chunk.is_empty() {
+                return Ok::<_, StoreError>((parts, verified));
+            }
+            let number = parts.len() as u32 + 1;
+            let sent = Checksums::of(&chunk);
+            let part = bucket
+                .put_multipart_chunk(chunk, key, number, &upload.upload_id, CONTENT_TYPE)
+                .await?;
+            verified &= sent.confirm_part(&part.etag);
+            parts.push(part);
+        }
+    };
+    let completed = match parts.await {
+        Ok((parts, verified)) => bucket
+            .complete_multipart_upload(key, &upload.upload_id, parts)
+            .await
+            .map(|_| verified)
+            .map_err(StoreError::from),
+        Err(e) => Err(e),
+    };
+    if completed.is_err() {
+        let _ = bucket.abort_upload(key, &upload.upload_id).await;
+    }
+    completed
+}
+
+/// The body of `response` as a reader
+pub(crate) fn response_reader(response: reqwest::Response) -> Box<dyn AsyncRead + Send + Unpin> {
+    Box::new(StreamReader::new(
+        response.bytes_stream().map_err(std::io::Error::other),
+    ))
+}
+
+/// Client side of the `vekt serve` protocol: objects are `GET`, `HEAD` and `PUT` at
+/// `<base>/<key>`, and `GET <base>/<prefix>/` lists a prefix as JSON [`ObjectInfo`]s
+pub(crate) struct HttpStore {
+    base: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl HttpStore {
+    pub fn new(base: &str, token: Option<String>) -> Self {
+        HttpStore {
+            base: base.trim_end_matches('/').to_string(),
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, key: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}/{}", self.base, key));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> StoreResult<reqwest::Response> {
+        Ok(request.send().await?.error_for_status()?)
+    }
+}
+
+impl ObjectStore {
+    /// Name of the S3 bucket, `None` for `vekt serve` remotes
+    pub fn bucket_name(&self) -> Option<&str> {
+        match self {
+            ObjectStore::S3(bucket) => Some(&bucket.name),
+            ObjectStore::Http(_) => None,
+        }
+    }
+
+    /// Checks that the store is reachable with the configured credentials
+    pub async fn check_access(&self) -> StoreResult<()> {
+        match self {
+            ObjectStore::S3(bucket) => {
+                bucket.list("/".to_string(), Some("/".to_string())).await?;
+            }
+            ObjectStore::Http(http) => {
+                http.send(http.request(reqwest::Method::GET, "")).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn exists(&self, key: &str) -> bool {
+        match self {
+            ObjectStore::S3(bucket) => matches!(bucket.head_object(key).await, Ok((_, 200))),
+            ObjectStore::Http(http) => http
+                .send(http.request(reqwest::Method::HEAD, key))
+                .await
+                .is_ok(),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> StoreResult<Vec<u8>> {
+        match self {
+            ObjectStore::S3(bucket) => Ok(bucket.get_object(key).await?.bytes().to_vec()),
+            ObjectStore::Http(http) => {
+                let response = http.send(http.request(reqwest::Method::GET, key)).await?;
+                Ok(response.bytes().await?.to_vec())
+            }
+        }
+    }
+
+    /// Bytes `range` of an object, which must lie within it
+    pub async fn get_range(&self, key: &str, range: Range<u64>) -> StoreResult<Vec<u8>> {
+        let data = match self {
+            ObjectStore::S3(bucket) => bucket
+                .get_object_range(key, range.start, Some(range.end - 1))
+                .await?
+                .bytes()
+                .to_vec(),
+            ObjectStore::Http(http) => {
+                let request = http.request(reqwest::Method::GET, key).header(
+                    reqwest::header::RANGE,
+                    format!("bytes={}-{}", range.start, range.end - 1),
+                );
+                let response = http.send(request).await?;
+                let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+                let body = response.bytes().await?;
+                match partial {
+                    true => body.to_vec(),
+                    // The server ignored the range and sent the whole object
+                    false => body
+                        .get(range.start as usize..range.end as usize)
+                        .unwrap_or_default()
+                        .to_vec(),
+                }
+            }
+        };
+        if data.len() as u64 != range.end - range.start {
+            return Err(StoreError {
+                status: None,
+                message: format!(
+                    "Expected {} bytes of {}, got {}",
+                    range.end - range.start,
+                    key,
+                    data.len()
+                ),
+            });
+        }
+        Ok(data)
+    }
+
+    /// Streams an object, to be read as it arrives instead of buffered or written to
+    /// disk first
+    pub async fn get_reader(&self, key: &str) -> StoreResult<Box<dyn AsyncRead + Send + Unpin>> {
+        match self {
+            ObjectStore::S3(bucket) => Ok(Box::new(bucket.get_object_stream(key).await?)),
+            ObjectStore::Http(http) => {
+                let response = http.send(http.request(reqwest::Method::GET, key)).await?;
+                Ok(response_reader(response))
+            }
+        }
+    }
+
+    /// Streams an object into `file` without holding it in memory. With `offset`,
+    /// `file` already holds the object's first `offset` bytes and is positioned after
+    /// them, and only the rest is requested. Returns the offset the data written
+    /// starts at: `offset`, or 0 when the store sent the whole object instead and
+    /// `file` was truncated to hold it.
+    pub async fn get_to_file(&self, key: &str, file: &mut File, offset: u64) -> StoreResult<u64> {
+        match self {
+            ObjectStore::S3(bucket) if offset > 0 => {
+                match bucket
+                    .get_object_range_to_writer(key, offset, None, file)
+                    .await
+                    .map_err(StoreError::from)
+                {
+                    // Nothing is left past the end of the object
+                    Err(e) if e.status == Some(416) => Ok(offset),
+                    result => result.map(|_| offset),
+                }
+            }
+            ObjectStore::S3(bucket) => {
+                let mut stream = bucket.get_object_stream(key).await?;
+                tokio::io::copy(&mut stream, file).await?;
+                Ok(0)
+            }
+            ObjectStore::Http(http) => {
+                let mut request = http.request(reqwest::Method::GET, key);
+                if offset > 0 {
+                    request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+                }
+                let mut response = http.send(request).await?;
+                let start = match response.status() {
+                    reqwest::StatusCode::PARTIAL_CONTENT => offset,
+                    _ => {
+                        file.set_len(0).await?;
+                        file.seek(std::io::SeekFrom::Start(0)).await?;
+                        0
+                    }
+                };
+                while let Some(chunk) = response.chunk().await? {
+                    file.write_all(&chunk).await?;
+                }
+                Ok(start)
+            }
+        }
+    }
+
+    /// Uploads `data`. Returns whether the store confirmed it received exactly these
+    /// bytes; one that reports receiving different bytes fails the upload.
+    pub async fn put(&self, key: &str, data: &[u8], options: PutOptions<'_>) -> StoreResult<bool> {
+        match self {
+            ObjectStore::S3(bucket) => {
+                let sent = Checksums::of(data);
+                let mut request = bucket
+                    .put_object_builder(key, data)
+                    .with_header(CHECKSUM_SHA256, sent.sha256.clone())?;
+                for (name, value) in options.headers() {
+                    request = request.with_header(name, value)?;
+                }
+                let response = request.execute().await?;
+                sent.confirm(key, &response.headers(), &options)
+            }
+            ObjectStore::Http(http) => {
+                let mut request = http.request(reqwest::Method::PUT, key);
+                if options.create_only {
+                    request = request.header(reqwest::header::IF_NONE_MATCH, "*");
+                }
+                http.send(request.body(data.to_vec())).await?;
+                Ok(is_verified_by_serve(key))
+            }
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> StoreResult<()> {
+        match self {
+            ObjectStore::S3(bucket) => {
+                bucket.delete_object(key).await?;
+            }
+            ObjectStore::Http(http) => {
+                http.send(http.request(reqwest::Method::DELETE, key))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads the file at `path`, streaming it from disk. Returns whether the store
+    /// confirmed it received exactly the file's bytes, as [`put`](Self::put) does.
+    pub async fn put_file(
+        &self,
+        key: &str,
+        path: &Path,
+        options: PutOptions<'_>,
+    ) -> StoreResult<bool> {
+        let mut file = File::open(path).await?;
+        match self {
+            ObjectStore::S3(_) if file.metadata().await?.len() < S3_PART_SIZE as u64 => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).await?;
+                self.put(key, &data, options).await
+            }
+            ObjectStore::S3(bucket) => put_multipart(bucket, key, &mut file, options).await,
+            ObjectStore::Http(http) => {
+                let size = file.metadata().await?.len();
+                let body = reqwest::Body::wrap_stream(ReaderStream::new(file));
+                http.send(
+                    http.request(reqwest::Method::PUT, key)
+                        .header(reqwest::header::CONTENT_LENGTH, size)
+                        .body(body),
+                )
+                .await?;
+                Ok(is_verified_by_serve(key))
+            }
+        }
+    }
+
+    /// Storage class and restore state of an object. `None` when the store has no
+    /// archive tiers or the object can't be inspected.
+    pub async fn archive_status(&self, key: &str) -> Option<ArchiveStatus> {
+        match self {
+            ObjectStore::S3(bucket) => {
+                let (head, _) = bucket.head_object(key).await.ok()?;
+                Some(ArchiveStatus {
+                    storage_class: head.storage_class,
+                    // `x-amz-restore: ongoing-request="true"` while a restore runs
+                    restoring: head
+                        .restore
+                        .is_some_and(|restore| restore.contains("ongoing-request=\"true\"")),
+                })
+            }
+            ObjectStore::Http(_) => None,
+        }
+    }
+
+    /// Every object whose key starts with `prefix`, which ends with `/`
+    pub async fn list(&self, prefix: &str) -> StoreResult<Vec<ObjectInfo>> {
+        match self {
+            ObjectStore::S3(bucket) => {
+                let pages = bucket.list(prefix.to_string(), None).await?;
+                Ok(pages
+                    .into_iter()
+                    .flat_map(|page| page.contents)
+                    .map(|object| ObjectInfo {
+                        key: object.key,
+                        size: object.size,
+                    })
+                    .collect())
+            }
+            ObjectStore::Http(http) => {
+                let response = http
+                    .send(http.request(reqwest::Method::GET, prefix))
+                    .await?;
+                let objects = response.bytes().await?;
+                serde_json::from_slice(&objects).map_err(|e| StoreError {
+                    status: None,
+                    message: format!("Invalid listing of {}: {}", prefix, e),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archived_reads_are_not_auth_failures() {
+        let archived = StoreError::from(s3::error::S3Error::HttpFailWithBody(
+            403,
+            "<Error><Code>InvalidObjectState</Code></Error>".to_string(),
+        ));
+        assert!(archived.is_archived());
+        assert!(!archived.is_unauthorized());
+
+        let denied = StoreError::from(s3::error::S3Error::HttpFailWithBody(
+            403,
+            "<Error><Code>AccessDenied</Code></Error>".to_string(),
+        ));
+        assert!(!denied.is_archived());
+        assert!(denied.is_unauthorized());
+    }
+
+    #[test]
+    fn test_put_options_headers() {
+        let tags = BTreeMap::from([
+            ("project".to_string(), "llm".to_string()),
+            ("model".to_string(), "gpt 2/base".to_string()),
+        ]);
+        let encryption = ServerSideEncryption::Kms(Some("alias/models".to_string()));
+        let options = PutOptions {
+            storage_class: None,
+            encryption: Some(&encryption),
+            tags: Some(&tags),
+            create_only: false,
+        };
+        assert_eq!(
+            options.headers(),
+            vec![
+                ("x-amz-server-side-encryption", "aws:kms".to_string()),
+                (
+                    "x-amz-server-side-encryption-aws-kms-key-id",
+                    "alias/models".to_string()
+                ),
+                (
+                    "x-amz-tagging",
+                    "model=gpt%202%2Fbase&project=llm".to_string()
+                ),
+            ]
+        );
+        assert!(PutOptions::default().headers().is_empty());
+    }
+
+    #[test]
+    fn test_upload_checksums_confirm_received_bytes() {
+        let sent = Checksums::of(b"blob");
+        assert_eq!(sent.md5, format!("{:x}", md5::compute(b"blob")));
+        let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let plain = PutOptions::default();
+        let confirm = |pairs: &[(&str, &str)], options: &PutOptions| {
+            sent.confirm("blobs/x", &headers(pairs), options)
+        };
+
+        // An echoed checksum decides; otherwise an MD5 ETag does
+        assert!(confirm(&[(CHECKSUM_SHA256, &sent.sha256)], &plain).unwrap());
+        assert!(confirm(&[(CHECKSUM_SHA256, "AAAA")], &plain).is_err());
+        let etag = format!("\"{}\"", sent.md5);
+        assert!(confirm(&[("etag", &etag)], &plain).unwrap());
+        let other = format!("\"{:x}\"", md5::compute(b"blub"));
+        assert!(confirm(&[("etag", &other)], &plain).is_err());
+
+        // KMS and multipart ETags aren't MD5s of the bytes, so they confirm nothing
+        let kms = ServerSideEncryption::Kms(None);
+        let encrypted = PutOptions {
+            encryption: Some(&kms),
+            ..Default::default()
+        };
+        assert!(!confirm(&[("etag", &other)], &encrypted).unwrap());
+        assert!(!confirm(&[("etag", "\"abc-2\"")], &plain).unwrap());
+        assert!(!confirm(&[], &plain).unwrap());
+
+        // A bucket that encrypts with KMS by default says so in the response
+        let default_kms = [
+            ("etag", other.as_str()),
+            ("x-amz-server-side-encryption", "aws:kms"),
+        ];
+        assert!(!confirm(&default_kms, &plain).unwrap());
+        let default_aes = [
+            ("etag", other.as_str()),
+            ("x-amz-server-side-encryption", "AES256"),
+        ];
+        assert!(confirm(&default_aes, &plain).is_err());
+
+        // Parts never fail on their ETag; S3 checked their Content-MD5 already
+        assert!(sent.confirm_part(&etag));
+        assert!(!sent.confirm_part(&other));
+    }
+}
@@ -0,0 +1,290 @@
+use crate::ModelArchiver;
+use crate::checksum::bytes_checksum;
+use crate::errors::{Result, VektError};
+use crate::layout::{FileLayout, SourceLayout, map_file};
+use crate::protobuf::{Fields, Value};
+use crate::storage::{ManifestTensor, VektManifest};
+use crate::validation::validate_path_safe;
+use indexmap::IndexMap;
+use memmap2::Mmap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// Field numbers from onnx.proto
+const MODEL_GRAPH: u32 = 7;
+const GRAPH_INITIALIZER: u32 = 5;
+const TENSOR_DIMS: u32 = 1;
+const TENSOR_DATA_TYPE: u32 = 2;
+const TENSOR_FLOAT_DATA: u32 = 4;
+const TENSOR_NAME: u32 = 8;
+const TENSOR_RAW_DATA: u32 = 9;
+const TENSOR_DOUBLE_DATA: u32 = 10;
+const TENSOR_EXTERNAL_DATA: u32 = 13;
+const TENSOR_DATA_LOCATION: u32 = 14;
+const DATA_LOCATION_EXTERNAL: u64 = 1;
+
+/// Maps `TensorProto.DataType` to the dtype names used in manifests
+pub fn onnx_dtype(data_type: u64) -> String {
+    let name = match data_type {
+        1 => "F32",
+        2 => "U8",
+        3 => "I8",
+        4 => "U16",
+        5 => "I16",
+        6 => "I32",
+        7 => "I64",
+        9 => "BOOL",
+        10 => "F16",
+        11 => "F64",
+        12 => "U32",
+        13 => "U64",
+        14 => "C64",
+        16 => "BF16",
+        17 => "F8_E4M3",
+        19 => "F8_E5M2",
+        21 => "U4",
+        22 => "I4",
+        23 => "F4",
+        other => return format!("ONNX_{}", other),
+    };
+    name.to_string()
+}
+
+fn malformed(reason: &str) -> VektError {
+    VektError::InvalidManifest(format!("Malformed ONNX protobuf: {}", reason))
+}
+
+/// Where an initializer's bytes live
+#[derive(Debug, Clone, PartialEq)]
+enum TensorData {
+    /// `raw_data` or packed `float_data`/`double_data` inside the model file
+    Inline(Range<usize>),
+    External {
+        location: String,
+        offset: usize,
+        length: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Initializer {
+    name: String,
+    dims: Vec<usize>,
+    data_type: u64,
+    data: TensorData,
+}
+
+fn parse_dim(value: u64) -> Result<usize> {
+    usize::try_from(value as i64).map_err(|_| malformed("negative initializer dimension"))
+}
+
+fn parse_initializer(data: &[u8], range: Range<usize>) -> Result<Option<Initializer>> {
+    let mut name = None;
+    let mut dims = Vec::new();
+    let mut data_type = 0;
+    let mut inline = None;
+    let mut external_entries = HashMap::new();
+    let mut external = false;
+
+    for field in Fields::new(data, range) {
+        match field? {
+            (TENSOR_DIMS, Value::Varint(dim)) => dims.push(parse_dim(dim)?),
+            (TENSOR_DIMS, Value::Bytes(packed)) => {
+                let mut reader = Fields::new(data, packed);
+                while !reader.at_end() {
+                    dims.push(parse_dim(reader.varint()?)?);
+                }
+            }
+            (TENSOR_DATA_TYPE, Value::Varint(dtype)) => data_type = dtype,
+            (TENSOR_NAME, Value::Bytes(r)) => {
+                name = Some(
+                    std::str::from_utf8(&data[r])
+                        .map_err(|_| malformed("tensor name is not UTF-8"))?
+                        .to_string(),
+                )
+            }
+            (TENSOR_RAW_DATA | TENSOR_FLOAT_DATA | TENSOR_DOUBLE_DATA, Value::Bytes(r)) => {
+                inline = Some(r)
+            }
+            (TENSOR_EXTERNAL_DATA, Value::Bytes(entry)) => {
+                let (mut key, mut value) = (String::new(), String::new());
+                for field in Fields::new(data, entry) {
+                    if let (n @ (1 | 2), Value::Bytes(r)) = field? {
+                        let text = String::from_utf8_lossy(&data[r]).into_owned();
+                        if n == 1 { key = text } else { value = text }
+                    }
+                }
+                external_entries.insert(key, value);
+            }
+            (TENSOR_DATA_LOCATION, Value::Varint(location)) => {
+                external = location == DATA_LOCATION_EXTERNAL
+            }
+            _ => {}
+        }
+    }
+
+    let name = name.ok_or_else(|| malformed("initializer without a name"))?;
+    let data = if external {
+        let number = |key: &str| -> Result<Option<usize>> {
+            external_entries
+                .get(key)
+                .map(|v| {
+                    v.parse()
+                        .map_err(|_| malformed(&format!("bad external {} '{}'", key, v)))
+                })
+                .transpose()
+        };
+        TensorData::External {
+            location: external_entries
+                .get("location")
+                .cloned()
+                .ok_or_else(|| malformed(&format!("'{}' has no external location", name)))?,
+            offset: number("offset")?.unwrap_or(0),
+            length: number("length")?,
+        }
+    } else {
+        match inline {
+            // Empty tensors and ones stored as varints (int32_data, ...) stay in the skeleton
+            Some(r) if !r.is_empty() => TensorData::Inline(r),
+            _ => return Ok(None),
+        }
+    };
+    Ok(Some(Initializer {
+        name,
+        dims,
+        data_type,
+        data,
+    }))
+}
+
+/// Finds the initializers of the model's main graph
+fn parse_initializers(data: &[u8]) -> Result<Vec<Initializer>> {
+    let mut initializers = Vec::new();
+    for field in Fields::new(data, 0..data.len()) {
+        if let (MODEL_GRAPH, Value::Bytes(graph)) = field? {
+            for field in Fields::new(data, graph) {
+                if let (GRAPH_INITIALIZER, Value::Bytes(tensor)) = field?
+                    && let Some(initializer) = parse_initializer(data, tensor)?
+                {
+                    initializers.push(initializer);
+                }
+            }
+        }
+    }
+    Ok(initializers)
+}
+
+/// An ONNX model, optionally with external data files next to it
+pub struct OnnxFile {
+    pub path: PathBuf,
+    pub mmap: Mmap,
+}
+
+impl OnnxFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(OnnxFile {
+            path: path.to_path_buf(),
+            mmap: map_file(path)?,
+        })
+    }
+}
+
+impl ModelArchiver for OnnxFile {
+    fn process(&self, save_blobs: bool) -> Result<VektManifest> {
+        let initializers = parse_initializers(&self.mmap)?;
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+
+        let mut tensors = BTreeMap::new();
+        let mut main_regions = Vec::new();
+        let mut external_regions: BTreeMap<String, Vec<(Range<usize>, String)>> = BTreeMap::new();
+        for (index, init) in initializers.iter().enumerate() {
+            let tensor = ManifestTensor {
+                shape: init.dims.clone(),
+                dtype: onnx_dtype(init.data_type),
+                hash: String::new(),
+                index,
+                extra: IndexMap::new(),
+                ranges: None,
+                stats: None,
+            };
+            if tensors.insert(init.name.clone(), tensor).is_some() {
+                return Err(malformed(&format!("duplicate initializer '{}'", init.name)));
+            }
+            match &init.data {
+                TensorData::Inline(range) => main_regions.push((range.clone(), init.name.clone())),
+                TensorData::External {
+                    location,
+                    offset,
+                    length,
+                } => {
+                    validate_path_safe(location)?;
+                    // usize::MAX marks "to the end of the file", resolved once it is mapped
+                    let end = match length {
+                        Some(length) => offset.checked_add(*length).ok_or_else(|| {
+                            malformed(&format!(
+                                "external data of '{}' ends past any file",
+                                init.name
+                            ))
+                        })?,
+                        None => usize::MAX,
+                    };
+                    external_regions
+                        .entry(location.clone())
+                        .or_default()
+                        .push((*offset..end, init.name.clone()));
+                }
+            }
+        }
+
+        let (main, mut hashes) = FileLayout::split_hashed(&self.mmap, main_regions, save_blobs)?;
+        let mut total_size = self.mmap.len();
+        let mut external = BTreeMap::new();
+        for (location, mut regions) in external_regions {
+            let mmap = map_file(&dir.join(&location))?;
+            for (range, _) in regions.iter_mut() {
+                if range.end == usize::MAX {
+                    range.end = mmap.len();
+                }
+            }
+            let (layout, file_hashes) = FileLayout::split_hashed(&mmap, regions, save_blobs)?;
+            hashes.extend(file_hashes);
+            total_size += mmap.len();
+            external.insert(location, layout);
+        }
+
+        for (name, hash) in hashes {
+            tensors.get_mut(&name).unwrap().hash = hash;
+        }
+        Ok(VektManifest {
+            tensors,
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size,
+            checksum: save_blobs.then(|| bytes_checksum(&self.mmap)),
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: Some(SourceLayout {
+                format: "onnx".to_string(),
+                main,
+                external,
+            }),
+            placement: Default::default(),
+        })
+    }
+
+    fn restore(manifest: &VektManifest, output_path: &Path, filter: Option<&str>) -> Result<()> {
+        manifest.restore(output_path, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dtypes() {
+        assert_eq!(onnx_dtype(1), "F32");
+        assert_eq!(onnx_dtype(16), "BF16");
+        assert_eq!(onnx_dtype(8), "ONNX_8");
+    }
+}
@@ -0,0 +1,103 @@
+use crate::blobs;
+use crate::ephemeral::now_secs;
+use crate::errors::{Result, VektError};
+use crate::refs;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// A pinned manifest snapshot, stored at `.vekt/pins/<manifest hash>`. GC and cache
+/// eviction never delete the blobs it references.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    /// Hash of the pinned manifest object
+    #[serde(skip)]
+    pub hash: String,
+    /// Manifest file or tag that was pinned
+    pub spec: String,
+    /// Seconds since the epoch when it was pinned
+    pub pinned: u64,
+}
+
+/// Directory holding pins (.vekt/pins)
+pub fn pins_dir() -> PathBuf {
+    get_vekt_dir().join("pins")
+}
+
+/// Pins the manifest `spec` resolves to, as it is now: a manifest file changed later
+/// keeps its pinned snapshot protected until it is unpinned
+pub fn pin(spec: &str) -> Result<Pin> {
+    let manifest = refs::resolve_manifest(spec)?;
+    let hash = refs::store_manifest_object(&manifest)?;
+    let pin = Pin {
+        hash,
+        spec: spec.to_string(),
+        pinned: now_secs(),
+    };
+    fs::create_dir_all(pins_dir())?;
+    write_file_atomic(
+        &pins_dir().join(&pin.hash),
+        serde_json::to_string(&pin)?.as_bytes(),
+    )?;
+    Ok(pin)
+}
+
+/// Removes the pins made from `spec`, and the pin of the manifest it resolves to.
+/// Returns how many were removed.
+pub fn unpin(spec: &str) -> Result<usize> {
+    let current = match refs::resolve_manifest(spec) {
+        Ok(manifest) => Some(blobs::compute_object_hash(&refs::manifest_object_bytes(
+            &manifest,
+        )?)),
+        Err(_) => None,
+    };
+    let mut removed = 0;
+    for pin in list_pins()? {
+        if pin.spec == spec || current.as_ref() == Some(&pin.hash) {
+            fs::remove_file(pins_dir().join(&pin.hash))?;
+            removed += 1;
+        }
+    }
+    if removed == 0 {
+        return Err(VektError::RefNotFound(format!("'{}' is not pinned", spec)));
+    }
+    Ok(removed)
+}
+
+/// Lists pins, oldest first
+pub fn list_pins() -> Result<Vec<Pin>> {
+    let dir = pins_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut pins = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let hash = entry.file_name().to_string_lossy().into_owned();
+        if hash.ends_with(".tmp") {
+            continue;
+        }
+        let mut pin: Pin = serde_json::from_slice(&fs::read(entry.path())?)?;
+        pin.hash = hash;
+        pins.push(pin);
+    }
+    pins.sort_by(|a, b| a.pinned.cmp(&b.pinned).then_with(|| a.spec.cmp(&b.spec)));
+    Ok(pins)
+}
+
+/// Every blob referenced by a pinned manifest
+pub fn pinned_hashes() -> Result<HashSet<String>> {
+    let mut hashes = HashSet::new();
+    for pin in list_pins()? {
+        let manifest = refs::load_manifest_object(&pin.hash).map_err(|e| {
+            VektError::ManifestCorrupted(format!(
+                "Pinned manifest {} ({}) can't be loaded: {}",
+                pin.hash, pin.spec, e
+            ))
+        })?;
+        hashes.extend(manifest.referenced_hashes().cloned());
+    }
+    Ok(hashes)
+}
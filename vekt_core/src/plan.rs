@@ -0,0 +1,198 @@
+use crate::async_api;
+use crate::blobs;
+use crate::errors::Result;
+use crate::index;
+use crate::sparse;
+use crate::storage::VektManifest;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A blob an operation would write, upload or download
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedBlob {
+    pub hash: String,
+    /// Size of the blob's data. Manifests don't record the size of file skeletons
+    /// (headers and other non-tensor bytes), which count as 0 where they aren't stored.
+    pub size: u64,
+}
+
+/// What `add` would do: worked out by hashing the file, without writing blobs, the
+/// manifest or the index
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct AddPlan {
+    pub tensors: usize,
+    /// Tensors whose data would be read and hashed; the others' hashes come from
+    /// `.vekt/index`
+    pub hashed: usize,
+    pub hashed_bytes: u64,
+    /// Blobs the store doesn't have yet, tensors and attachments alike
+    pub new_blobs: Vec<PlannedBlob>,
+    /// Blobs the store already has
+    pub existing: usize,
+}
+
+impl AddPlan {
+    /// Bytes that would be written to the store
+    pub fn new_bytes(&self) -> u64 {
+        self.new_blobs.iter().map(|blob| blob.size).sum()
+    }
+}
+
+/// What a push or pull would transfer, worked out without transferring anything
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct TransferPlan {
+    pub manifests: usize,
+    /// Blobs the receiving side is missing, each once
+    pub blobs: Vec<PlannedBlob>,
+    /// Blobs the receiving side already has
+    pub present: usize,
+}
+
+impl TransferPlan {
+    /// Bytes that would be transferred
+    pub fn bytes(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.size).sum()
+    }
+}
+
+/// What a push would upload on top of blobs the remote is known to hold, worked out
+/// locally without asking the remote about each blob
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct DeltaPlan {
+    /// Blobs outside the known set, each once
+    pub blobs: Vec<PlannedBlob>,
+    /// Blobs the known set already covers
+    pub reused: usize,
+    /// Size of the reused blobs
+    pub saved_bytes: u64,
+}
+
+impl DeltaPlan {
+    /// Bytes that would be uploaded
+    pub fn bytes(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.size).sum()
+    }
+}
+
+/// Sizes of the blobs `manifest` records one for: tensors and attachments
+pub(crate) fn recorded_sizes(manifest: &VektManifest) -> HashMap<&String, u64> {
+    let mut sizes = HashMap::new();
+    for tensor in manifest.tensors.values() {
+        if let Ok(size) = tensor.byte_size() {
+            sizes.insert(&tensor.hash, size as u64);
+        }
+    }
+    for attachment in manifest.attachments.values() {
+        sizes.insert(&attachment.hash, attachment.size);
+    }
+    sizes
+}
+
+/// Sizes of every blob `manifests` reference. Those the manifests don't record
+/// (file skeletons) are looked up in the local store.
+pub(crate) fn local_sizes(manifests: &[&VektManifest]) -> BTreeMap<String, u64> {
+    let mut sizes = BTreeMap::new();
+    for manifest in manifests {
+        let recorded = recorded_sizes(manifest);
+        for hash in manifest.referenced_hashes() {
+            let size = match recorded.get(hash) {
+                Some(size) => *size,
+                None => blobs::find_blob(hash)
+                    .and_then(|(path, _)| std::fs::metadata(path).ok())
+                    .map_or(0, |metadata| metadata.len()),
+            };
+            sizes.insert(hash.clone(), size);
+        }
+    }
+    sizes
+}
+
+/// Splits the blobs of `manifests` into those in `known` and those a push has to
+/// upload
+pub fn plan_delta(manifests: &[&VektManifest], known: &HashSet<String>) -> DeltaPlan {
+    let mut plan = DeltaPlan::default();
+    for (hash, size) in local_sizes(manifests) {
+        if known.contains(&hash) {
+            plan.reused += 1;
+            plan.saved_bytes += size;
+        } else {
+            plan.blobs.push(PlannedBlob { hash, size });
+        }
+    }
+    plan
+}
+
+/// Plans adding the model at `path` with the files in `attachments`, as `vekt add`
+/// would, reusing hashes from `.vekt/index` for safetensors files
+pub fn plan_add(path: &Path, attachments: &[PathBuf]) -> Result<AddPlan> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let (manifest, hashed, skeleton) =
+        if matches!(extension.as_str(), "onnx" | "npz" | "npy" | "index") {
+            let manifest = async_api::process_file(path, false)?;
+            let names = manifest.tensors.keys().cloned().collect();
+            (manifest, names, None)
+        } else {
+            let (manifest, hashed, skeleton) = index::hash_with_index(path)?;
+            (manifest, hashed, Some(skeleton))
+        };
+
+    let mut plan = AddPlan {
+        tensors: manifest.tensors.len(),
+        hashed: hashed.len(),
+        ..Default::default()
+    };
+    for name in &hashed {
+        plan.hashed_bytes += manifest.tensors[name].byte_size()? as u64;
+    }
+
+    let mut blobs: BTreeMap<String, u64> = recorded_sizes(&manifest)
+        .into_iter()
+        .map(|(hash, size)| (hash.clone(), size))
+        .collect();
+    for hash in manifest.referenced_hashes() {
+        blobs.entry(hash.clone()).or_insert(0);
+    }
+    if let Some(skeleton) = skeleton {
+        blobs.insert(skeleton.hash, skeleton.size);
+    }
+    for file in attachments {
+        let size = std::fs::metadata(file)?.len();
+        blobs.insert(blobs::hash_file(file)?, size);
+    }
+    for (hash, size) in blobs {
+        if blobs::blob_exists(&hash) {
+            plan.existing += 1;
+        } else {
+            plan.new_blobs.push(PlannedBlob { hash, size });
+        }
+    }
+    Ok(plan)
+}
+
+/// Works out which blobs of `manifests` a pull would download: those missing from
+/// the local store, less any tensors a [sparse](crate::sparse) spec leaves out
+pub fn plan_download(manifests: &[&VektManifest]) -> Result<TransferPlan> {
+    let mut sizes = BTreeMap::new();
+    for manifest in manifests {
+        let recorded = recorded_sizes(manifest);
+        for hash in sparse::wanted_blobs(manifest)? {
+            sizes.insert(hash.clone(), recorded.get(hash).copied().unwrap_or(0));
+        }
+    }
+    let mut plan = TransferPlan {
+        manifests: manifests.len(),
+        ..Default::default()
+    };
+    for (hash, size) in sizes {
+        if blobs::blob_exists(&hash) {
+            plan.present += 1;
+        } else {
+            plan.blobs.push(PlannedBlob { hash, size });
+        }
+    }
+    Ok(plan)
+}
@@ -0,0 +1,130 @@
+//! Just enough protobuf wire-format decoding to locate tensor data in model files
+//! without a generated schema.
+
+use crate::errors::{Result, VektError};
+use std::ops::Range;
+
+/// A protobuf field value; length-delimited values are absolute ranges into the file
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Varint(u64),
+    Fixed,
+    Bytes(Range<usize>),
+}
+
+/// Iterates the fields of one protobuf message without decoding nested messages
+pub(crate) struct Fields<'a> {
+    data: &'a [u8],
+    range: Range<usize>,
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(data: &'a [u8], range: Range<usize>) -> Self {
+        let pos = range.start;
+        Fields { data, range, pos }
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .filter(|_| self.pos < self.range.end)
+                .ok_or_else(|| malformed("truncated varint"))?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("varint longer than 10 bytes"))
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos >= self.range.end
+    }
+
+    fn skip(&mut self, len: usize) -> Result<Range<usize>> {
+        let start = self.pos;
+        let end = start
+            .checked_add(len)
+            .filter(|end| *end <= self.range.end)
+            .ok_or_else(|| malformed("field runs past the end of its message"))?;
+        self.pos = end;
+        Ok(start..end)
+    }
+}
+
+impl Iterator for Fields<'_> {
+    type Item = Result<(u32, Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.range.end {
+            return None;
+        }
+        let field = (|| {
+            let key = self.varint()?;
+            let value = match key & 7 {
+                0 => Value::Varint(self.varint()?),
+                1 => {
+                    self.skip(8)?;
+                    Value::Fixed
+                }
+                2 => {
+                    let len = self.varint()? as usize;
+                    Value::Bytes(self.skip(len)?)
+                }
+                5 => {
+                    self.skip(4)?;
+                    Value::Fixed
+                }
+                wire => return Err(malformed(&format!("unsupported wire type {}", wire))),
+            };
+            Ok(((key >> 3) as u32, value))
+        })();
+        if field.is_err() {
+            // Stop after the first error instead of re-reading garbage
+            self.pos = self.range.end;
+        }
+        Some(field)
+    }
+}
+
+fn malformed(reason: &str) -> VektError {
+    VektError::InvalidManifest(format!("Malformed protobuf: {}", reason))
+}
+
+/// Reads a varint at `pos`, advancing it. For varint-framed formats outside protobuf.
+pub(crate) fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut fields = Fields::new(data, *pos..data.len());
+    let value = fields.varint()?;
+    *pos = fields.pos;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        // field 1 varint 150, field 2 bytes "hi"
+        let data = [0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i'];
+        let fields: Vec<_> = Fields::new(&data, 0..data.len())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            fields,
+            vec![(1, Value::Varint(150)), (2, Value::Bytes(5..7))]
+        );
+
+        let truncated = [0x12, 0x05, b'h'];
+        assert!(Fields::new(&truncated, 0..3).any(|f| f.is_err()));
+
+        let mut pos = 1;
+        assert_eq!(read_varint(&data, &mut pos).unwrap(), 150);
+        assert_eq!(pos, 3);
+    }
+}
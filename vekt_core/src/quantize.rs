@@ -0,0 +1,316 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::numeric::{decode_floats, f16_to_f32, f32_to_f16, is_float_dtype};
+use crate::refs;
+use crate::storage::{ManifestTensor, VektManifest};
+use crate::validation::verify_blob_hash;
+use indexmap::IndexMap;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Metadata key holding the manifest object hash of the full-precision model a
+/// quantized manifest was derived from
+pub const QUANTIZED_FROM_KEY: &str = "quantized_from";
+
+/// Metadata key holding the [`QuantScheme`] of a quantized manifest
+pub const QUANT_SCHEME_KEY: &str = "quant_scheme";
+
+/// Suffix of the tensor holding a quantized tensor's block scales
+pub const SCALE_SUFFIX: &str = "_scale";
+
+/// Values per block along the last dimension; each block has one F16 scale
+pub const BLOCK_SIZE: usize = 32;
+
+/// Symmetric block quantization schemes, after GGML's `q8_0` and `q4_0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantScheme {
+    /// 8-bit integers (`I8`)
+    Q8_0,
+    /// 4-bit integers, two per byte (`I4`)
+    Q4_0,
+}
+
+impl QuantScheme {
+    /// Dtype of the quantized values
+    pub fn dtype(self) -> &'static str {
+        match self {
+            QuantScheme::Q8_0 => "I8",
+            QuantScheme::Q4_0 => "I4",
+        }
+    }
+
+    /// Largest quantized magnitude; the block's largest value maps to it
+    fn max_level(self) -> f32 {
+        match self {
+            QuantScheme::Q8_0 => 127.0,
+            QuantScheme::Q4_0 => 7.0,
+        }
+    }
+}
+
+impl fmt::Display for QuantScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuantScheme::Q8_0 => write!(f, "q8_0"),
+            QuantScheme::Q4_0 => write!(f, "q4_0"),
+        }
+    }
+}
+
+impl FromStr for QuantScheme {
+    type Err = VektError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "q8_0" => Ok(QuantScheme::Q8_0),
+            "q4_0" => Ok(QuantScheme::Q4_0),
+            _ => Err(VektError::InvalidManifest(format!(
+                "Unknown quantization scheme '{}'. Use q8_0 or q4_0",
+                s
+            ))),
+        }
+    }
+}
+
+/// What [`VektManifest::quantize`] did
+#[derive(Debug, Default)]
+pub struct QuantizeSummary {
+    /// Tensors replaced by quantized values and scales
+    pub quantized: Vec<String>,
+    /// Tensors kept as they were: 1-D tensors and non-float dtypes
+    pub kept: Vec<String>,
+    /// Data bytes of the quantized tensors before and after, scales included
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Shape of the scale tensor: one scale per block of the last dimension
+fn scale_shape(shape: &[usize]) -> Vec<usize> {
+    let (last, rows) = shape.split_last().unwrap();
+    let mut scales = rows.to_vec();
+    scales.push(last.div_ceil(BLOCK_SIZE));
+    scales
+}
+
+/// Quantizes row-major `values` of `shape`. Returns the packed values and the F16
+/// scales, both little-endian.
+pub fn quantize_values(values: &[f64], shape: &[usize], scheme: QuantScheme) -> (Vec<u8>, Vec<u8>) {
+    let row_len = *shape.last().unwrap_or(&1);
+    let mut levels = Vec::with_capacity(values.len());
+    let mut scales = Vec::new();
+    for row in values.chunks(row_len.max(1)) {
+        for block in row.chunks(BLOCK_SIZE) {
+            let max = block.iter().fold(0f64, |max, v| max.max(v.abs())) as f32;
+            // Quantize with the scale as stored, so dequantizing inverts exactly
+            let scale = f16_to_f32(f32_to_f16(max / scheme.max_level()));
+            scales.extend_from_slice(&f32_to_f16(scale).to_le_bytes());
+            for value in block {
+                let level = match scale {
+                    0.0 => 0.0,
+                    _ => (*value as f32 / scale).round(),
+                };
+                levels.push(level.clamp(-scheme.max_level() - 1.0, scheme.max_level()) as i8);
+            }
+        }
+    }
+
+    let packed = match scheme {
+        QuantScheme::Q8_0 => levels.iter().map(|l| *l as u8).collect(),
+        // Low nibble first
+        QuantScheme::Q4_0 => levels
+            .chunks(2)
+            .map(|pair| {
+                let high = pair.get(1).copied().unwrap_or(0);
+                (pair[0] as u8 & 0x0f) | ((high as u8) << 4)
+            })
+            .collect(),
+    };
+    (packed, scales)
+}
+
+/// Inverse of [`quantize_values`], up to the quantization error
+pub fn dequantize_values(
+    packed: &[u8],
+    scales: &[u8],
+    shape: &[usize],
+    scheme: QuantScheme,
+) -> Vec<f64> {
+    let count: usize = shape.iter().product();
+    let levels: Vec<i8> = match scheme {
+        QuantScheme::Q8_0 => packed.iter().map(|b| *b as i8).collect(),
+        // Sign-extend each nibble
+        QuantScheme::Q4_0 => packed
+            .iter()
+            .flat_map(|b| [((b << 4) as i8) >> 4, (*b as i8) >> 4])
+            .collect(),
+    };
+    let scales: Vec<f32> = scales
+        .chunks_exact(2)
+        .map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+        .collect();
+
+    let row_len = *shape.last().unwrap_or(&1);
+    let blocks_per_row = row_len.div_ceil(BLOCK_SIZE);
+    (0..count)
+        .map(|i| {
+            let (row, col) = (i / row_len, i % row_len);
+            let scale = scales[row * blocks_per_row + col / BLOCK_SIZE];
+            (levels[i] as f32 * scale) as f64
+        })
+        .collect()
+}
+
+impl VektManifest {
+    /// Builds a quantized variant of this manifest. Every float tensor of rank 2 or
+    /// more is replaced by its quantized values, with the same name and shape, plus a
+    /// `<name>_scale` F16 tensor holding one scale per block of 32 values along the
+    /// last dimension. Other tensors keep sharing this manifest's blobs.
+    ///
+    /// The result records this manifest as its parent, see
+    /// [`quantized_from`](Self::quantized_from).
+    pub fn quantize(&self, scheme: QuantScheme) -> Result<(VektManifest, QuantizeSummary)> {
+        if self.layout.is_some() {
+            return Err(VektError::InvalidManifest(
+                "Quantization is only supported for safetensors models".to_string(),
+            ));
+        }
+        if self.metadata.contains_key(QUANT_SCHEME_KEY) {
+            return Err(VektError::InvalidManifest(
+                "Manifest is already quantized".to_string(),
+            ));
+        }
+
+        let mut ordered: Vec<(&String, &ManifestTensor)> = self.tensors.iter().collect();
+        ordered.sort_by_key(|(_, t)| t.index);
+        for (name, tensor) in &ordered {
+            let scale_name = format!("{}{}", name, SCALE_SUFFIX);
+            if quantizable(tensor) && self.tensors.contains_key(&scale_name) {
+                return Err(VektError::InvalidTensorName(format!(
+                    "Can't quantize '{}': its scales would replace tensor '{}'",
+                    name, scale_name
+                )));
+            }
+        }
+
+        let converted: Vec<Result<Vec<(String, ManifestTensor)>>> = ordered
+            .par_iter()
+            .map(|(name, tensor)| {
+                if !quantizable(tensor) {
+                    return Ok(vec![((*name).clone(), (*tensor).clone())]);
+                }
+                let data = blobs::read_blob(&tensor.hash)?;
+                verify_blob_hash(&data, &tensor.hash)?;
+                let values = decode_floats(&data, &tensor.dtype)?;
+                let (packed, scales) = quantize_values(&values, &tensor.shape, scheme);
+
+                let quantized = ManifestTensor {
+                    dtype: scheme.dtype().to_string(),
+                    hash: blobs::save_blob_deduplicated(&packed)?.0,
+                    ranges: None,
+                    stats: None,
+                    ..(*tensor).clone()
+                };
+                let scale = ManifestTensor {
+                    shape: scale_shape(&tensor.shape),
+                    dtype: "F16".to_string(),
+                    hash: blobs::save_blob_deduplicated(&scales)?.0,
+                    index: 0,
+                    extra: IndexMap::new(),
+                    ranges: None,
+                    stats: None,
+                };
+                Ok(vec![
+                    ((*name).clone(), quantized),
+                    (format!("{}{}", name, SCALE_SUFFIX), scale),
+                ])
+            })
+            .collect();
+
+        let mut summary = QuantizeSummary::default();
+        let mut tensors = BTreeMap::new();
+        for (original, result) in ordered.iter().zip(converted) {
+            let group = result?;
+            if group.len() == 1 {
+                summary.kept.push(original.0.clone());
+            } else {
+                summary.quantized.push(original.0.clone());
+                summary.bytes_before += original.1.byte_size()? as u64;
+                for (_, tensor) in &group {
+                    summary.bytes_after += tensor.byte_size()? as u64;
+                }
+            }
+            // Scales follow their tensor in the restored file
+            for (name, mut tensor) in group {
+                tensor.index = tensors.len();
+                tensors.insert(name, tensor);
+            }
+        }
+
+        let mut metadata = self.metadata.clone();
+        metadata.insert(
+            QUANTIZED_FROM_KEY.to_string(),
+            refs::store_manifest_object(self)?,
+        );
+        metadata.insert(QUANT_SCHEME_KEY.to_string(), scheme.to_string());
+        let mut quantized = VektManifest {
+            tensors,
+            checksum: None,
+            metadata,
+            ..self.clone()
+        };
+        quantized.recompute_total_size()?;
+        Ok((quantized, summary))
+    }
+
+    /// The full-precision model a quantized manifest was derived from, if any
+    pub fn quantized_from(&self) -> Result<Option<VektManifest>> {
+        match self.metadata.get(QUANTIZED_FROM_KEY) {
+            Some(hash) => Ok(Some(refs::load_manifest_object(hash)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn quantizable(tensor: &ManifestTensor) -> bool {
+    tensor.shape.len() >= 2 && is_float_dtype(&tensor.dtype)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_roundtrip() {
+        // Two rows of 40: each row has a full block and a partial one
+        let values: Vec<f64> = (0..80).map(|i| (i as f64 - 40.0) / 8.0).collect();
+        for scheme in [QuantScheme::Q8_0, QuantScheme::Q4_0] {
+            let (packed, scales) = quantize_values(&values, &[2, 40], scheme);
+            assert_eq!(scales.len(), 2 * 2 * 2);
+            let restored = dequantize_values(&packed, &scales, &[2, 40], scheme);
+            assert_eq!(restored.len(), values.len());
+            // Error is at most half a quantization step of the block's scale
+            let tolerance = 5.0 / scheme.max_level() as f64;
+            for (a, b) in values.iter().zip(&restored) {
+                assert!((a - b).abs() <= tolerance, "{:?}: {} vs {}", scheme, a, b);
+            }
+        }
+        assert_eq!(
+            quantize_values(&values, &[2, 40], QuantScheme::Q4_0)
+                .0
+                .len(),
+            40
+        );
+
+        // An all-zero block has scale zero and stays zero
+        let (packed, scales) = quantize_values(&[0.0; 4], &[1, 4], QuantScheme::Q8_0);
+        assert_eq!(
+            dequantize_values(&packed, &scales, &[1, 4], QuantScheme::Q8_0),
+            [0.0; 4]
+        );
+
+        assert_eq!("Q8_0".parse::<QuantScheme>().unwrap(), QuantScheme::Q8_0);
+        assert!("q2_k".parse::<QuantScheme>().is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::hash::{ContentHasher, HashAlgorithm};
+use blake3::hazmat::{
+    ChainingValue, HasherExt, Mode, left_subtree_len, merge_subtrees_non_root, merge_subtrees_root,
+};
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Tensors larger than this get per-range hashes in the manifest
+pub const RANGE_HASH_THRESHOLD: u64 = 256 << 20;
+
+/// Bytes per hashed range. A power of two, so every range is a whole subtree of the
+/// blake3 tree and the range hashes combine into the tensor hash.
+pub const RANGE_SIZE: u64 = 64 << 20;
+
+/// Hashes of fixed-size ranges of a large tensor. A corrupted copy can be checked
+/// range by range and only the ranges that differ downloaded again.
+///
+/// Each hash is the blake3 chaining value of its range as a subtree of the tensor's
+/// hash, not the plain blake3 hash of the range's bytes: see [`range_hash`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RangeHashes {
+    /// Bytes per range; the last range may be shorter
+    pub range_size: u64,
+    pub hashes: Vec<String>,
+}
+
+impl RangeHashes {
+    /// Byte range of range `index` in a tensor of `len` bytes
+    pub fn range(&self, index: usize, len: u64) -> Range<u64> {
+        let start = index as u64 * self.range_size;
+        start..(start + self.range_size).min(len)
+    }
+
+    /// Whether `bytes` are the contents of range `index`
+    pub fn verify_range(&self, index: usize, bytes: &[u8]) -> bool {
+        self.hashes
+            .get(index)
+            .is_some_and(|expected| *expected == range_hash(index as u64 * self.range_size, bytes))
+    }
+
+    /// Indices of the ranges of `data` that don't match, checked in parallel. Every
+    /// range is reported when `data` has the wrong length.
+    pub fn corrupt_ranges(&self, data: &[u8], len: u64) -> Vec<usize> {
+        if data.len() as u64 != len {
+            return (0..self.hashes.len()).collect();
+        }
+        #[cfg(feature = "native")]
+        let indices = (0..self.hashes.len()).into_par_iter();
+        #[cfg(not(feature = "native"))]
+        let indices = 0..self.hashes.len();
+        indices
+            .filter(|&i| {
+                let range = self.range(i, len);
+                !self.verify_range(i, &data[range.start as usize..range.end as usize])
+            })
+            .collect()
+    }
+}
+
+/// Hash of a range starting at byte `offset` (a multiple of [`RANGE_SIZE`])
+pub fn range_hash(offset: u64, bytes: &[u8]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.set_input_offset(offset);
+    hasher.update(bytes);
+    hex::encode(hasher.finalize_non_root())
+}
+
+/// Hashes `data`, splitting tensors above [`RANGE_HASH_THRESHOLD`] into ranges that
+/// are hashed in parallel. The hash is always the plain hash of `data`. Range hashes
+/// are parts of the blake3 tree, so blobs hashed with another algorithm get none.
+#[cfg(feature = "native")]
+pub fn hash_with_ranges(data: &[u8]) -> (String, Option<RangeHashes>) {
+    if (data.len() as u64) <= RANGE_HASH_THRESHOLD
+        || HashAlgorithm::current() != HashAlgorithm::Blake3
+    {
+        return (crate::blobs::compute_blob_hash(data), None);
+    }
+    let (hash, ranges) = hash_ranges(data, RANGE_SIZE);
+    (hash, Some(ranges))
+}
+
+/// Hashes `data` in ranges of `range_size` bytes, which must be a power of two of at
+/// least `blake3::CHUNK_LEN` and smaller than `data`
+#[cfg(feature = "native")]
+fn hash_ranges(data: &[u8], range_size: u64) -> (String, RangeHashes) {
+    let cvs: Vec<ChainingValue> = data
+        .par_chunks(range_size as usize)
+        .enumerate()
+        .map(|(i, range)| {
+            // Each range is itself split across idle workers, so a tensor with
+            // fewer ranges than cores still keeps every core busy
+            let mut hasher = blake3::Hasher::new();
+            hasher.set_input_offset(i as u64 * range_size);
+            hasher.update_rayon(range);
+            hasher.finalize_non_root()
+        })
+        .collect();
+    let hash = root_hash(&cvs, range_size, data.len() as u64);
+    (
+        hash,
+        RangeHashes {
+            range_size,
+            hashes: cvs.iter().map(hex::encode).collect(),
+        },
+    )
+}
+
+/// Combines range chaining values into the tensor hash, following the shape of the
+/// blake3 tree: the left subtree is always the largest power of two that fits
+fn root_hash(cvs: &[ChainingValue], range_size: u64, len: u64) -> String {
+    fn subtree(cvs: &[ChainingValue], range_size: u64, len: u64) -> ChainingValue {
+        if cvs.len() == 1 {
+            return cvs[0];
+        }
+        let left_len = left_subtree_len(len);
+        let split = (left_len / range_size) as usize;
+        merge_subtrees_non_root(
+            &subtree(&cvs[..split], range_size, left_len),
+            &subtree(&cvs[split..], range_size, len - left_len),
+            Mode::Hash,
+        )
+    }
+    let left_len = left_subtree_len(len);
+    let split = (left_len / range_size) as usize;
+    let root = merge_subtrees_root(
+        &subtree(&cvs[..split], range_size, left_len),
+        &subtree(&cvs[split..], range_size, len - left_len),
+        Mode::Hash,
+    );
+    hex::encode(root.as_bytes())
+}
+
+/// Incremental [`hash_with_ranges`] for data that arrives in pieces, e.g. a download.
+/// The total length must be known up front.
+pub struct RangeHasher {
+    len: u64,
+    range_size: u64,
+    pos: u64,
+    current: blake3::Hasher,
+    /// `None` when the data is too small for ranges
+    cvs: Option<Vec<ChainingValue>>,
+    /// Used instead when blobs aren't hashed with blake3
+    other: Option<ContentHasher>,
+}
+
+impl RangeHasher {
+    /// A hasher for the [current](HashAlgorithm::current) algorithm
+    pub fn new(len: u64) -> Self {
+        match HashAlgorithm::current() {
+            HashAlgorithm::Blake3 => Self::with_range_size(len, RANGE_SIZE, RANGE_HASH_THRESHOLD),
+            algorithm => RangeHasher {
+                cvs: None,
+                other: Some(algorithm.hasher()),
+                ..Self::with_range_size(len, RANGE_SIZE, RANGE_HASH_THRESHOLD)
+            },
+        }
+    }
+
+    fn with_range_size(len: u64, range_size: u64, threshold: u64) -> Self {
+        RangeHasher {
+            len,
+            range_size,
+            pos: 0,
+            current: blake3::Hasher::new(),
+            cvs: (len > threshold).then(Vec::new),
+            other: None,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if let Some(other) = &mut self.other {
+            other.update(data);
+            return;
+        }
+        let Some(cvs) = &mut self.cvs else {
+            self.current.update(data);
+            return;
+        };
+        while !data.is_empty() {
+            let room = self.range_size - self.pos % self.range_size;
+            let (piece, rest) = data.split_at((room as usize).min(data.len()));
+            self.current.update(piece);
+            self.pos += piece.len() as u64;
+            data = rest;
+            if self.pos.is_multiple_of(self.range_size) && self.pos < self.len {
+                cvs.push(self.current.finalize_non_root());
+                self.current = blake3::Hasher::new();
+                self.current.set_input_offset(self.pos);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> (String, Option<RangeHashes>) {
+        if let Some(other) = self.other {
+            return (other.finalize(), None);
+        }
+        match self.cvs {
+            None => (hex::encode(self.current.finalize().as_bytes()), None),
+            Some(mut cvs) => {
+                cvs.push(self.current.finalize_non_root());
+                let hash = root_hash(&cvs, self.range_size, self.len);
+                let ranges = RangeHashes {
+                    range_size: self.range_size,
+                    hashes: cvs.iter().map(hex::encode).collect(),
+                };
+                (hash, Some(ranges))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_range_hashes_combine_into_blake3() {
+        let range_size = 4 * blake3::CHUNK_LEN as u64;
+        for len in [
+            range_size + 1,
+            2 * range_size,
+            3 * range_size + 17,
+            7 * range_size,
+        ] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+            let expected = blake3::hash(&data).to_hex().to_string();
+
+            let (hash, ranges) = hash_ranges(&data, range_size);
+            assert_eq!(hash, expected, "len {}", len);
+            assert_eq!(ranges.hashes.len() as u64, len.div_ceil(range_size));
+
+            let mut streaming = RangeHasher::with_range_size(len, range_size, range_size);
+            for piece in data.chunks(1000) {
+                streaming.update(piece);
+            }
+            assert_eq!(streaming.finalize(), (expected, Some(ranges.clone())));
+
+            assert!(ranges.corrupt_ranges(&data, len).is_empty());
+            let mut corrupt = data.clone();
+            corrupt[range_size as usize] ^= 1;
+            assert_eq!(ranges.corrupt_ranges(&corrupt, len), [1]);
+        }
+
+        let small = b"small tensor";
+        assert_eq!(
+            hash_with_ranges(small),
+            (blake3::hash(small).to_hex().to_string(), None)
+        );
+    }
+}
@@ -0,0 +1,707 @@
+//! Which blobs each manifest keeps alive, kept at `.vekt/refcounts.json` so gc and
+//! `vekt du` only read manifests that changed since they last looked, instead of
+//! every manifest in the working tree and in history.
+//!
+//! The database is a cache of what the manifests say and is never trusted blindly:
+//! [`RefCounts::open`] reconciles it first. Working-tree manifests are re-read when
+//! their size or modification time changed, git history and manifest objects only for
+//! versions not seen before, and history is only listed again once a ref moved.
+//! `add`, `rm` and `commit` keep it current as they go, so that reconciling usually
+//! finds nothing to read.
+use crate::blob_index::BlobIndex;
+use crate::commits;
+use crate::encoding::{self, BlobEncoding};
+use crate::ephemeral::now_secs;
+use crate::errors::{Result, VektError};
+use crate::pins;
+use crate::refs;
+use crate::releases;
+use crate::storage::VektManifest;
+use crate::utils::{find_manifest_files, get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+
+/// Files modified this recently may change again within the same timestamp tick, so
+/// their stamp isn't trusted and they are re-read next time
+const RACY_SECS: u64 = 2;
+
+/// Size and modification time (in nanoseconds) of a manifest file when it was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    size: u64,
+    modified: u64,
+}
+
+impl Stamp {
+    /// The stamp of a file with `metadata`, unless it was modified too recently
+    fn of(metadata: &fs::Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        (modified.as_secs() + RACY_SECS <= now_secs()).then_some(Stamp {
+            size: metadata.len(),
+            modified: modified.as_nanos() as u64,
+        })
+    }
+}
+
+/// Something that keeps blobs alive
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Referrer {
+    blobs: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stamp: Option<Stamp>,
+    /// Ephemeral manifests hold nothing once this has passed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+}
+
+/// Space gc would free
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Reclaimable {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+/// How many referrers hold each blob: working-tree manifests, manifest versions in
+/// git history, manifest objects of tags, branches and releases, and pins
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RefCounts {
+    /// `file:<path under the root>`, `git:<object sha>`, `object:<manifest hash>` or
+    /// `pins`
+    referrers: BTreeMap<String, Referrer>,
+    /// Fingerprint of the tags, branch tips and releases the `object:` referrers were
+    /// last reconciled against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    objects_state: Option<String>,
+    /// Fingerprint of the git refs and reflogs the `git:` referrers were last
+    /// reconciled against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    git_state: Option<String>,
+    /// Blob -> number of referrers holding it, derived from `referrers`
+    #[serde(skip)]
+    counts: HashMap<String, usize>,
+}
+
+fn db_path() -> PathBuf {
+    get_vekt_dir().join("refcounts.json")
+}
+
+fn file_key(relative: &Path) -> String {
+    format!("file:{}", relative.to_string_lossy())
+}
+
+/// Blobs a working-tree manifest keeps alive: its own, and those of the base model of
+/// an adapter or the full-precision parent of a quantized variant. The flag is false
+/// when a base or parent couldn't be loaded, so the manifest is read again next time.
+fn manifest_blobs(path: &Path, manifest: &VektManifest) -> (BTreeSet<String>, bool) {
+    let mut blobs: BTreeSet<String> = manifest.referenced_hashes().cloned().collect();
+    let mut complete = true;
+    match manifest.adapter_base() {
+        Ok(Some(base)) => blobs.extend(base.referenced_hashes().cloned()),
+        Ok(None) => {}
+        Err(e) => {
+            complete = false;
+            warn!("Failed to load adapter base for {}: {}", path.display(), e)
+        }
+    }
+    match manifest.quantized_from() {
+        Ok(Some(parent)) => blobs.extend(parent.referenced_hashes().cloned()),
+        Ok(None) => {}
+        Err(e) => {
+            complete = false;
+            warn!(
+                "Failed to load quantization parent for {}: {}",
+                path.display(),
+                e
+            )
+        }
+    }
+    (blobs, complete)
+}
+
+/// Reads the working-tree manifest at `path`. One that can't be parsed holds nothing
+/// until it changes.
+fn read_file(path: &Path, metadata: &fs::Metadata) -> Result<Referrer> {
+    let reader = std::io::BufReader::new(fs::File::open(path)?);
+    let mut referrer = Referrer {
+        stamp: Stamp::of(metadata),
+        ..Default::default()
+    };
+    match serde_json::from_reader::<_, VektManifest>(reader) {
+        Ok(manifest) => {
+            let (blobs, complete) = manifest_blobs(path, &manifest);
+            referrer.blobs = blobs;
+            referrer.expires = manifest.expires_at();
+            if !complete {
+                referrer.stamp = None;
+            }
+        }
+        Err(e) => warn!(
+            "Failed to parse manifest at {}: {}. Skipping this manifest.",
+            path.display(),
+            e
+        ),
+    }
+    Ok(referrer)
+}
+
+impl RefCounts {
+    /// Loads the database and reconciles it with the manifests under `root` and in
+    /// the repository's history, reading only what changed. Builds it from scratch
+    /// when there is none. Saves it if anything changed.
+    pub fn open(root: &Path) -> Result<Self> {
+        let Some(mut refcounts) = Self::load() else {
+            return Self::rebuild(root);
+        };
+        if refcounts.reconcile(root)? {
+            refcounts.save_or_warn();
+        }
+        Ok(refcounts)
+    }
+
+    /// Like [`open`](Self::open), or [`rebuild`](Self::rebuild) when `full`, but never
+    /// saves, so plans and dry runs leave the database as they found it
+    pub fn read(root: &Path, full: bool) -> Result<Self> {
+        let mut refcounts = match full {
+            true => RefCounts::default(),
+            false => Self::load().unwrap_or_default(),
+        };
+        refcounts.reconcile(root)?;
+        Ok(refcounts)
+    }
+
+    /// Reads every manifest again and saves the result, discarding the database
+    pub fn rebuild(root: &Path) -> Result<Self> {
+        let mut refcounts = RefCounts::default();
+        refcounts.reconcile(root)?;
+        refcounts.save()?;
+        Ok(refcounts)
+    }
+
+    fn load() -> Option<Self> {
+        let bytes = fs::read(db_path()).ok()?;
+        let mut refcounts: RefCounts = serde_json::from_slice(&bytes).ok()?;
+        for referrer in refcounts.referrers.values() {
+            for blob in &referrer.blobs {
+                *refcounts.counts.entry(blob.clone()).or_default() += 1;
+            }
+        }
+        Some(refcounts)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_file_atomic(&db_path(), &serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Like the blob index, the database is only a cache, so failing to save it
+    /// doesn't fail the operation
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            warn!("Failed to save the refcount database: {}", e);
+        }
+    }
+
+    /// Number of referrers holding blob `hash` directly
+    pub fn count(&self, hash: &str) -> usize {
+        self.counts.get(hash).copied().unwrap_or(0)
+    }
+
+    /// Records what `key` holds. Returns whether anything changed.
+    fn set(&mut self, key: String, mut referrer: Referrer) -> bool {
+        if referrer
+            .expires
+            .is_some_and(|expires| expires <= now_secs())
+        {
+            referrer.blobs.clear();
+        }
+        if self.referrers.get(&key) == Some(&referrer) {
+            return false;
+        }
+        for blob in &referrer.blobs {
+            *self.counts.entry(blob.clone()).or_default() += 1;
+        }
+        if let Some(old) = self.referrers.insert(key, referrer) {
+            self.release(&old);
+        }
+        true
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        match self.referrers.remove(key) {
+            Some(old) => {
+                self.release(&old);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn release(&mut self, referrer: &Referrer) {
+        for blob in &referrer.blobs {
+            if let Some(count) = self.counts.get_mut(blob) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(blob);
+                }
+            }
+        }
+    }
+
+    /// Drops referrers with `prefix` whose key isn't in `keep`
+    fn retain(&mut self, prefix: &str, keep: &HashSet<String>) -> bool {
+        let gone: Vec<String> = self
+            .referrers
+            .keys()
+            .filter(|key| key.starts_with(prefix) && !keep.contains(*key))
+            .cloned()
+            .collect();
+        let mut changed = false;
+        for key in gone {
+            changed |= self.remove(&key);
+        }
+        changed
+    }
+
+    /// Returns whether anything changed
+    fn reconcile(&mut self, root: &Path) -> Result<bool> {
+        let mut changed = self.reconcile_files(root)?;
+        changed |= self.reconcile_objects()?;
+        changed |= self.reconcile_git(root)?;
+        let pins = Referrer {
+            blobs: pins::pinned_hashes()?.into_iter().collect(),
+            ..Default::default()
+        };
+        changed |= self.set("pins".to_string(), pins);
+
+        // Ephemeral manifests that expired since they were read
+        let now = now_secs();
+        let expired: Vec<(String, Referrer)> = self
+            .referrers
+            .iter()
+            .filter(|(_, r)| !r.blobs.is_empty() && r.expires.is_some_and(|e| e <= now))
+            .map(|(key, r)| (key.clone(), r.clone()))
+            .collect();
+        for (key, referrer) in expired {
+            changed |= self.set(key, referrer);
+        }
+        Ok(changed)
+    }
+
+    fn reconcile_files(&mut self, root: &Path) -> Result<bool> {
+        let mut changed = false;
+        let mut seen = HashSet::new();
+        for path in find_manifest_files(root)? {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let key = file_key(relative);
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let unchanged = self.referrers.get(&key).is_some_and(|referrer| {
+                referrer.stamp.is_some() && referrer.stamp == Stamp::of(&metadata)
+            });
+            if !unchanged {
+                changed |= self.set(key.clone(), read_file(&path, &metadata)?);
+            }
+            seen.insert(key);
+        }
+        Ok(self.retain("file:", &seen) | changed)
+    }
+
+    /// Manifest objects of tags, branch history and releases. Objects are immutable,
+    /// so each is only read the first time it shows up, and history is only walked
+    /// again when a tag, branch tip or release changed.
+    fn reconcile_objects(&mut self) -> Result<bool> {
+        let tags = refs::list_tags()?;
+        let tips = commits::all_tips()?;
+        let releases = releases::list_releases()?;
+        let mut state = blake3::Hasher::new();
+        for (name, hash) in &tags {
+            state.update(format!("tag {} {}\n", name, hash).as_bytes());
+        }
+        for tip in &tips {
+            state.update(format!("tip {}\n", tip).as_bytes());
+        }
+        for release in &releases {
+            for (name, hash) in &release.manifests {
+                state.update(format!("release {} {} {}\n", release.name, name, hash).as_bytes());
+            }
+        }
+        let state = state.finalize().to_hex().to_string();
+        if self.objects_state.as_ref() == Some(&state) {
+            return Ok(false);
+        }
+
+        let mut live: HashSet<String> = tags.into_iter().map(|(_, hash)| hash).collect();
+        for (_, commit) in commits::walk_history(&tips)? {
+            live.extend(commit.manifests.into_values());
+        }
+        for release in releases {
+            live.extend(release.manifests.into_values());
+        }
+
+        let mut changed = false;
+        let mut complete = true;
+        let mut keys = HashSet::new();
+        for hash in live {
+            let key = format!("object:{}", hash);
+            if !self.referrers.contains_key(&key) {
+                match refs::load_manifest_object(&hash) {
+                    Ok(manifest) => {
+                        let referrer = Referrer {
+                            blobs: manifest.referenced_hashes().cloned().collect(),
+                            ..Default::default()
+                        };
+                        changed |= self.set(key.clone(), referrer);
+                    }
+                    // Read again next time, in case it is still being written
+                    Err(e) => {
+                        complete = false;
+                        warn!(
+                            "Failed to load committed manifest {}: {}. Skipping it.",
+                            hash, e
+                        )
+                    }
+                }
+            }
+            keys.insert(key);
+        }
+        changed |= self.retain("object:", &keys);
+        let state = complete.then_some(state);
+        changed |= self.objects_state != state;
+        self.objects_state = state;
+        Ok(changed)
+    }
+
+    /// Every version of a manifest in git history, including unreachable commits the
+    /// reflog still holds. History is only listed again when a ref or reflog changed,
+    /// and only versions not seen before are read.
+    fn reconcile_git(&mut self, root: &Path) -> Result<bool> {
+        let state = git_state(root);
+        if state.is_some() && self.git_state == state {
+            return Ok(false);
+        }
+        let Some(shas) = git_manifest_objects(root)? else {
+            return Ok(false);
+        };
+        let new: Vec<&String> = shas
+            .iter()
+            .filter(|sha| !self.referrers.contains_key(&format!("git:{}", sha)))
+            .collect();
+        let mut changed = false;
+        let mut complete = true;
+        if !new.is_empty() {
+            let mut read = Vec::new();
+            read_git_objects(root, &new, |sha, content| read.push((sha, content)))?;
+            complete = read.len() == new.len();
+            // Versions git couldn't hand over are tried again next time; ones that
+            // aren't valid manifests hold nothing and aren't read again
+            for (sha, content) in read {
+                let mut referrer = Referrer::default();
+                match serde_json::from_slice::<VektManifest>(&content) {
+                    Ok(manifest) => {
+                        referrer.blobs = manifest.referenced_hashes().cloned().collect();
+                        referrer.expires = manifest.expires_at();
+                    }
+                    Err(e) => warn!("Failed to parse git object as manifest: {}", e),
+                }
+                changed |= self.set(format!("git:{}", sha), referrer);
+            }
+        }
+        let keys = shas.iter().map(|sha| format!("git:{}", sha)).collect();
+        changed |= self.retain("git:", &keys);
+        let state = state.filter(|_| complete);
+        changed |= self.git_state != state;
+        self.git_state = state;
+        Ok(changed)
+    }
+
+    /// Working-tree manifests under `root` whose TTL ran out
+    pub fn expired(&self, root: &Path) -> Vec<PathBuf> {
+        let now = now_secs();
+        self.referrers
+            .iter()
+            .filter(|(_, referrer)| referrer.expires.is_some_and(|e| e <= now))
+            .filter_map(|(key, _)| key.strip_prefix("file:"))
+            .map(|relative| root.join(relative))
+            .collect()
+    }
+
+    /// Blobs in `index` that nothing holds, either directly or as a chunk or delta
+    /// base of a blob something holds
+    pub fn unreferenced(&self, index: &BlobIndex) -> Result<HashSet<String>> {
+        let mut unreferenced: HashSet<String> = index
+            .files()
+            .filter(|(_, hash, _)| !self.counts.contains_key(hash.as_ref()))
+            .map(|(_, hash, _)| hash.into_owned())
+            .collect();
+        if unreferenced.is_empty() {
+            return Ok(unreferenced);
+        }
+
+        // Only chunked and delta blobs are built from others; the index tells which
+        // those are without looking at the rest
+        let built: HashSet<String> = index
+            .files()
+            .filter(|(name, _, _)| {
+                [BlobEncoding::Chunked, BlobEncoding::Delta]
+                    .into_iter()
+                    .filter_map(BlobEncoding::extension)
+                    .any(|ext| Path::new(name).extension().is_some_and(|e| e == ext))
+            })
+            .map(|(_, hash, _)| hash.into_owned())
+            .collect();
+        let mut pending: Vec<String> = built
+            .iter()
+            .filter(|hash| self.counts.contains_key(*hash))
+            .cloned()
+            .collect();
+        let mut seen = HashSet::new();
+        while let Some(hash) = pending.pop() {
+            if !seen.insert(hash.clone()) {
+                continue;
+            }
+            for dependency in encoding::dependencies(&hash)? {
+                unreferenced.remove(&dependency);
+                if built.contains(&dependency) {
+                    pending.push(dependency);
+                }
+            }
+        }
+        Ok(unreferenced)
+    }
+
+    /// What gc would free: the blobs of [`unreferenced`](Self::unreferenced)
+    pub fn reclaimable(&self, index: &BlobIndex) -> Result<Reclaimable> {
+        let unreferenced = self.unreferenced(index)?;
+        let mut reclaimable = Reclaimable {
+            blobs: unreferenced.len(),
+            bytes: 0,
+        };
+        for (_, hash, blob) in index.files() {
+            if unreferenced.contains(hash.as_ref()) {
+                reclaimable.bytes += blob.size;
+            }
+        }
+        Ok(reclaimable)
+    }
+}
+
+/// Applies `update` to the database if there is one and saves it. Without one there
+/// is nothing to keep current: the next [`RefCounts::open`] builds it.
+fn update(update: impl FnOnce(&mut RefCounts, &Path)) {
+    if let Some(mut refcounts) = RefCounts::load() {
+        update(&mut refcounts, &commits::repo_root());
+        refcounts.save_or_warn();
+    }
+}
+
+/// Key of the working-tree manifest at `path`, if it is under `root`
+fn key_under(root: &Path, path: &Path) -> Option<String> {
+    let root = root.canonicalize().ok()?;
+    let path = path.canonicalize().ok()?;
+    Some(file_key(path.strip_prefix(root).ok()?))
+}
+
+/// Records that `manifest` was just saved to `path`
+pub fn note_saved(path: &Path, manifest: &VektManifest) {
+    update(|refcounts, root| {
+        let (Some(key), Ok(metadata)) = (key_under(root, path), fs::metadata(path)) else {
+            return;
+        };
+        let (blobs, complete) = manifest_blobs(path, manifest);
+        let referrer = Referrer {
+            blobs,
+            stamp: Stamp::of(&metadata).filter(|_| complete),
+            expires: manifest.expires_at(),
+        };
+        refcounts.set(key, referrer);
+    });
+}
+
+/// Records that the manifest file at `path` was deleted. Call it before deleting, so
+/// the path still resolves.
+pub fn note_removed(path: &Path) {
+    update(|refcounts, root| {
+        if let Some(key) = key_under(root, path) {
+            refcounts.remove(&key);
+        }
+    });
+}
+
+/// Records manifest objects a new commit, tag or release made live
+pub fn note_objects<'a>(objects: impl IntoIterator<Item = (&'a String, &'a VektManifest)>) {
+    update(|refcounts, _| {
+        for (hash, manifest) in objects {
+            let referrer = Referrer {
+                blobs: manifest.referenced_hashes().cloned().collect(),
+                ..Default::default()
+            };
+            refcounts.set(format!("object:{}", hash), referrer);
+        }
+    });
+}
+
+/// Fingerprint of the refs and reflogs of the git repository at `repo_root`: the
+/// size and modification time of `HEAD`, `packed-refs` and every file under `refs`
+/// and `logs`. `None` when there is no `.git` directory to look at, e.g. in a
+/// worktree, so history is always listed.
+fn git_state(repo_root: &Path) -> Option<String> {
+    let git_dir = repo_root.join(".git");
+    if !git_dir.is_dir() {
+        return None;
+    }
+    let mut files = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    let mut pending = vec![git_dir.join("refs"), git_dir.join("logs")];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(entry.path()),
+                Ok(_) => files.push(entry.path()),
+                Err(_) => return None,
+            }
+        }
+    }
+    files.sort();
+    let mut state = blake3::Hasher::new();
+    for file in files {
+        let Ok(metadata) = fs::metadata(&file) else {
+            continue;
+        };
+        // A ref updated within the timestamp tick could change unnoticed
+        let stamp = Stamp::of(&metadata)?;
+        state.update(format!("{} {} {}\n", file.display(), stamp.size, stamp.modified).as_bytes());
+    }
+    Some(state.finalize().to_hex().to_string())
+}
+
+/// SHAs of every `.vekt.json` blob in the git history of `repo_root` (none outside a
+/// git repository), or `None` when git can't list them
+fn git_manifest_objects(repo_root: &Path) -> Result<Option<Vec<String>>> {
+    if !repo_root.join(".git").exists() {
+        return Ok(Some(Vec::new()));
+    }
+
+    // Use git rev-list with --all and --reflog to catch unreachable commits
+    // --reflog includes commits that may have been deleted/force-pushed
+    let rev_list_output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo_root)
+        .arg("rev-list")
+        .arg("--all")
+        .arg("--reflog")
+        .arg("--objects")
+        .output()
+        .map_err(|e| VektError::GitError(format!("Failed to run git rev-list: {}", e)))?;
+
+    if !rev_list_output.status.success() {
+        let stderr = String::from_utf8_lossy(&rev_list_output.stderr);
+        warn!(
+            "git rev-list failed: {}. Skipping git history scan.",
+            stderr
+        );
+        return Ok(None);
+    }
+
+    let objects = String::from_utf8_lossy(&rev_list_output.stdout);
+    let mut manifest_objects = BTreeSet::new();
+    for line in objects.lines() {
+        // Format: "<sha> <path>" or just "<sha>" for commits
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() == 2 && parts[1].ends_with(".vekt.json") {
+            manifest_objects.insert(parts[0].to_string());
+        }
+    }
+    Ok(Some(manifest_objects.into_iter().collect()))
+}
+
+/// Streams the contents of git objects `shas` to `found` with `git cat-file --batch`
+fn read_git_objects(
+    repo_root: &Path,
+    shas: &[&String],
+    mut found: impl FnMut(String, Vec<u8>),
+) -> Result<()> {
+    let mut cat_file = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo_root)
+        .arg("cat-file")
+        .arg("--batch")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| VektError::GitError(format!("Failed to spawn git cat-file: {}", e)))?;
+
+    // Write all SHAs to stdin
+    if let Some(mut stdin) = cat_file.stdin.take() {
+        use std::io::Write;
+        for sha in shas {
+            if let Err(e) = writeln!(stdin, "{}", sha) {
+                warn!("Failed to write SHA to git cat-file: {}", e);
+                break;
+            }
+        }
+        // Close stdin to signal we're done
+        drop(stdin);
+    }
+
+    // Read the batched output
+    if let Some(stdout) = cat_file.stdout.take() {
+        let mut reader = std::io::BufReader::new(stdout);
+
+        loop {
+            // Read header line: "<sha> <type> <size>"
+            let mut header_line = String::new();
+            let bytes_read = reader.read_line(&mut header_line).map_err(|e| {
+                VektError::GitError(format!("Failed to read git cat-file output: {}", e))
+            })?;
+            if bytes_read == 0 {
+                break; // EOF
+            }
+
+            let parts: Vec<&str> = header_line.split_whitespace().collect();
+            if parts.len() != 3 {
+                // Handle missing objects gracefully
+                if header_line.contains("missing") {
+                    // Skip the trailing newline for missing objects
+                    let mut newline = [0u8; 1];
+                    let _ = reader.read_exact(&mut newline);
+                }
+                continue;
+            }
+
+            let size: usize = match parts[2].parse() {
+                Ok(s) => s,
+                Err(_) => {
+                    warn!("Invalid size in git cat-file output: {}", parts[2]);
+                    continue;
+                }
+            };
+
+            // Read exactly 'size' bytes (the actual file content)
+            let mut content = vec![0u8; size];
+            if let Err(e) = reader.read_exact(&mut content) {
+                warn!("Failed to read git object content: {}", e);
+                break;
+            }
+
+            // Read the trailing newline that git cat-file adds after each object
+            let mut newline = [0u8; 1];
+            let _ = reader.read_exact(&mut newline);
+
+            found(parts[0].to_string(), content);
+        }
+    }
+
+    let _ = cat_file.wait();
+    Ok(())
+}
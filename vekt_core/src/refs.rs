@@ -0,0 +1,360 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static REF_NAME_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_ref_name_regex() -> &'static Regex {
+    REF_NAME_REGEX.get_or_init(|| Regex::new(r"^[A-Za-z0-9_][A-Za-z0-9._/-]*$").unwrap())
+}
+
+/// Validates a ref name such as `prod`, `v2.1-rc` or `team/nightly`
+pub fn validate_ref_name(name: &str) -> Result<()> {
+    if name.len() > 128
+        || !get_ref_name_regex().is_match(name)
+        || name.contains("..")
+        || name.contains("//")
+        || name.ends_with('/')
+        || name.ends_with(".lock")
+    {
+        return Err(VektError::InvalidRefName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Directory holding content-addressed manifest snapshots (.vekt/manifests)
+pub fn manifest_objects_dir() -> PathBuf {
+    get_vekt_dir().join("manifests")
+}
+
+/// Directory holding tags (.vekt/refs/tags)
+pub fn tags_dir() -> PathBuf {
+    get_vekt_dir().join("refs").join("tags")
+}
+
+/// Serializes a manifest the way it is stored as an object, byte for byte the same
+/// as its `.vekt.json` file
+pub fn manifest_object_bytes(manifest: &VektManifest) -> Result<Vec<u8>> {
+    Ok(manifest.to_json()?.into_bytes())
+}
+
+/// Stores an immutable snapshot of `manifest` and returns its hash
+pub fn store_manifest_object(manifest: &VektManifest) -> Result<String> {
+    let bytes = manifest_object_bytes(manifest)?;
+    let hash = blobs::compute_object_hash(&bytes);
+    let dir = manifest_objects_dir();
+    let path = dir.join(&hash);
+    if !path.exists() {
+        fs::create_dir_all(&dir)?;
+        write_file_atomic(&path, &bytes)?;
+    }
+    Ok(hash)
+}
+
+/// Stores raw manifest object bytes received from elsewhere (e.g. a remote),
+/// checking that they parse and hash to `expected_hash`
+pub fn import_manifest_object(bytes: &[u8], expected_hash: &str) -> Result<VektManifest> {
+    crate::validation::verify_blob_hash(bytes, expected_hash)?;
+    let manifest: VektManifest = serde_json::from_slice(bytes).map_err(|e| {
+        VektError::ManifestCorrupted(format!("Manifest object {}: {}", expected_hash, e))
+    })?;
+    let dir = manifest_objects_dir();
+    let path = dir.join(expected_hash);
+    if !path.exists() {
+        fs::create_dir_all(&dir)?;
+        write_file_atomic(&path, bytes)?;
+    }
+    Ok(manifest)
+}
+
+/// The stored bytes of a manifest object, checked against `hash`. They are sent
+/// as they are, since an object written by an older version may not serialize back
+/// to the same bytes.
+pub fn read_manifest_object_bytes(hash: &str) -> Result<Vec<u8>> {
+    let path = manifest_objects_dir().join(hash);
+    if !path.exists() {
+        return Err(VektError::RefNotFound(format!(
+            "Manifest object {} not found",
+            hash
+        )));
+    }
+    let bytes = fs::read(&path)?;
+    crate::validation::verify_blob_hash(&bytes, hash)?;
+    Ok(bytes)
+}
+
+/// Loads a manifest snapshot by hash, verifying its content
+pub fn load_manifest_object(hash: &str) -> Result<VektManifest> {
+    let bytes = read_manifest_object_bytes(hash)?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| VektError::ManifestCorrupted(format!("Manifest object {}: {}", hash, e)))
+}
+
+/// Returns the manifest hash a tag points to
+pub fn read_tag(name: &str) -> Result<String> {
+    validate_ref_name(name)?;
+    let path = tags_dir().join(name);
+    if !path.is_file() {
+        return Err(VektError::RefNotFound(format!("Tag '{}' not found", name)));
+    }
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+/// Points tag `name` at the manifest object `hash`.
+/// Fails if the tag already exists and points elsewhere, unless `force` is set.
+pub fn write_tag(name: &str, hash: &str, force: bool) -> Result<()> {
+    validate_ref_name(name)?;
+    if !force
+        && let Ok(existing) = read_tag(name)
+        && existing != hash
+    {
+        return Err(VektError::RefExists(name.to_string()));
+    }
+    let path = tags_dir().join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file_atomic(&path, format!("{}\n", hash).as_bytes())?;
+    Ok(())
+}
+
+/// Snapshots `manifest` and tags it as `name`. Returns the manifest hash.
+pub fn create_tag(name: &str, manifest: &VektManifest, force: bool) -> Result<String> {
+    validate_ref_name(name)?;
+    let hash = store_manifest_object(manifest)?;
+    write_tag(name, &hash, force)?;
+    Ok(hash)
+}
+
+pub fn delete_tag(name: &str) -> Result<()> {
+    validate_ref_name(name)?;
+    let path = tags_dir().join(name);
+    if !path.is_file() {
+        return Err(VektError::RefNotFound(format!("Tag '{}' not found", name)));
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn collect_refs(dir: &Path, prefix: &str, out: &mut Vec<(String, String)>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, file_name);
+        if path.is_dir() {
+            collect_refs(&path, &format!("{}/", name), out)?;
+        } else if !file_name.ends_with(".tmp") {
+            out.push((name, fs::read_to_string(&path)?.trim().to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Like `collect_refs`, but only walks the directory without reading ref files
+fn collect_ref_names(dir: &Path, prefix: &str, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, file_name);
+        if path.is_dir() {
+            collect_ref_names(&path, &format!("{}/", name), out);
+        } else if !file_name.ends_with(".tmp") {
+            out.push(name);
+        }
+    }
+}
+
+/// Sorted tag names. Cheap and infallible, for shell completion.
+pub fn tag_names() -> Vec<String> {
+    let mut names = Vec::new();
+    collect_ref_names(&tags_dir(), "", &mut names);
+    names.sort();
+    names
+}
+
+/// Lists all tags as (name, manifest hash), sorted by name
+pub fn list_tags() -> Result<Vec<(String, String)>> {
+    let mut tags = Vec::new();
+    collect_refs(&tags_dir(), "", &mut tags)?;
+    tags.sort();
+    Ok(tags)
+}
+
+/// Branch checked out when HEAD has never been set
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// Directory holding branches (.vekt/refs/heads)
+pub fn heads_dir() -> PathBuf {
+    get_vekt_dir().join("refs").join("heads")
+}
+
+fn head_path() -> PathBuf {
+    get_vekt_dir().join("HEAD")
+}
+
+/// What HEAD points at: a branch (possibly without commits yet) or a detached commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    Branch(String),
+    Detached(String),
+}
+
+pub fn read_head() -> Result<Head> {
+    let path = head_path();
+    if !path.is_file() {
+        return Ok(Head::Branch(DEFAULT_BRANCH.to_string()));
+    }
+    let content = fs::read_to_string(path)?;
+    let content = content.trim();
+    match content.strip_prefix("ref: refs/heads/") {
+        Some(branch) => Ok(Head::Branch(branch.to_string())),
+        None => Ok(Head::Detached(content.to_string())),
+    }
+}
+
+pub fn write_head(head: &Head) -> Result<()> {
+    let content = match head {
+        Head::Branch(name) => {
+            validate_ref_name(name)?;
+            format!("ref: refs/heads/{}\n", name)
+        }
+        Head::Detached(hash) => format!("{}\n", hash),
+    };
+    write_file_atomic(&head_path(), content.as_bytes())?;
+    Ok(())
+}
+
+/// Returns the commit a branch points to, or `None` if it doesn't exist yet
+pub fn read_branch(name: &str) -> Result<Option<String>> {
+    validate_ref_name(name)?;
+    let path = heads_dir().join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().to_string()))
+}
+
+pub fn write_branch(name: &str, commit: &str) -> Result<()> {
+    validate_ref_name(name)?;
+    let path = heads_dir().join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file_atomic(&path, format!("{}\n", commit).as_bytes())?;
+    Ok(())
+}
+
+pub fn delete_branch(name: &str) -> Result<()> {
+    validate_ref_name(name)?;
+    let path = heads_dir().join(name);
+    if !path.is_file() {
+        return Err(VektError::RefNotFound(format!(
+            "Branch '{}' not found",
+            name
+        )));
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Lists all branches as (name, commit hash), sorted by name
+pub fn list_branches() -> Result<Vec<(String, String)>> {
+    let mut branches = Vec::new();
+    collect_refs(&heads_dir(), "", &mut branches)?;
+    branches.sort();
+    Ok(branches)
+}
+
+/// Sorted branch names. Cheap and infallible, for shell completion.
+pub fn branch_names() -> Vec<String> {
+    let mut names = Vec::new();
+    collect_ref_names(&heads_dir(), "", &mut names);
+    names.sort();
+    names
+}
+
+/// The commit HEAD currently resolves to, if any
+pub fn head_commit() -> Result<Option<String>> {
+    match read_head()? {
+        Head::Branch(name) => read_branch(&name),
+        Head::Detached(hash) => Ok(Some(hash)),
+    }
+}
+
+/// Resolves anything that names a manifest: a path to a `.vekt.json` file, a tag,
+/// a manifest object hash, or a branch/commit (`main`, `HEAD:model.vekt.json`).
+/// Paths win over tags, and tags over branches of the same name.
+pub fn resolve_manifest(spec: &str) -> Result<VektManifest> {
+    let path = Path::new(spec);
+    if path.is_file() {
+        return VektManifest::load(path);
+    }
+    if validate_ref_name(spec).is_ok() && tags_dir().join(spec).is_file() {
+        return load_manifest_object(&read_tag(spec)?);
+    }
+    if spec.len() == 64
+        && spec.chars().all(|c| c.is_ascii_hexdigit())
+        && manifest_objects_dir().join(spec).is_file()
+    {
+        return load_manifest_object(spec);
+    }
+    // `<rev>:<path>` or a branch/commit whose snapshot holds a single manifest
+    let (rev, file) = match spec.split_once(':') {
+        Some((rev, file)) => (rev, Some(file)),
+        None => (spec, None),
+    };
+    if let Ok(commit_hash) = crate::commits::resolve_commit(rev) {
+        let commit = crate::commits::load_commit(&commit_hash)?;
+        let hash = match file {
+            Some(file) => commit.manifests.get(file).ok_or_else(|| {
+                VektError::RefNotFound(format!("'{}' is not tracked in {}", file, rev))
+            })?,
+            None if commit.manifests.len() == 1 => commit.manifests.values().next().unwrap(),
+            None => {
+                return Err(VektError::RefNotFound(format!(
+                    "{} tracks {} manifests; use '{}:<path>' to pick one",
+                    rev,
+                    commit.manifests.len(),
+                    rev
+                )));
+            }
+        };
+        return load_manifest_object(hash);
+    }
+    Err(VektError::RefNotFound(format!(
+        "'{}' is neither a manifest file nor a known tag, branch or commit",
+        spec
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_ref_name() {
+        assert!(validate_ref_name("v1.0").is_ok());
+        assert!(validate_ref_name("team/prod").is_ok());
+        assert!(validate_ref_name("v2.1-rc").is_ok());
+        assert!(validate_ref_name("../escape").is_err());
+        assert!(validate_ref_name("/abs").is_err());
+        assert!(validate_ref_name("-flag").is_err());
+        assert!(validate_ref_name("trailing/").is_err());
+        assert!(validate_ref_name("has space").is_err());
+        assert!(validate_ref_name("").is_err());
+    }
+}
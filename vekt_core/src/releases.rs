@@ -0,0 +1,162 @@
+use crate::errors::{Result, VektError};
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A named, immutable set of exact manifest versions that are published together
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Release {
+    pub name: String,
+    /// Manifest file name -> manifest object hash
+    pub manifests: BTreeMap<String, String>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+/// Directory holding local copies of release objects (.vekt/releases)
+pub fn releases_dir() -> PathBuf {
+    get_vekt_dir().join("releases")
+}
+
+/// Release names follow ref naming rules but stay flat (no `/`)
+pub fn validate_release_name(name: &str) -> Result<()> {
+    refs::validate_ref_name(name)?;
+    if name.contains('/') {
+        return Err(VektError::InvalidRefName(name.to_string()));
+    }
+    Ok(())
+}
+
+impl Release {
+    /// Snapshots every manifest in `paths` as a manifest object and groups them under
+    /// `name`. Manifests are keyed by file name, which must be unique within a release.
+    pub fn create(name: &str, paths: &[PathBuf]) -> Result<Self> {
+        validate_release_name(name)?;
+        if paths.is_empty() {
+            return Err(VektError::InvalidManifest(
+                "A release needs at least one manifest".to_string(),
+            ));
+        }
+
+        let mut manifests = BTreeMap::new();
+        for path in paths {
+            let file_name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .ok_or_else(|| {
+                    VektError::InvalidManifest(format!("Invalid manifest path {}", path.display()))
+                })?;
+            let manifest = VektManifest::load(path)?;
+            let hash = refs::store_manifest_object(&manifest)?;
+            if manifests.insert(file_name.clone(), hash).is_some() {
+                return Err(VektError::InvalidManifest(format!(
+                    "Two manifests named '{}' in one release",
+                    file_name
+                )));
+            }
+        }
+
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(Release {
+            name: name.to_string(),
+            manifests,
+            created_at,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| VektError::ManifestCorrupted(format!("Release object: {}", e)))
+    }
+
+    /// Records the release locally
+    pub fn save(&self) -> Result<()> {
+        validate_release_name(&self.name)?;
+        let path = releases_dir().join(&self.name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_file_atomic(&path, &self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        validate_release_name(name)?;
+        let path = releases_dir().join(name);
+        if !path.is_file() {
+            return Err(VektError::RefNotFound(format!(
+                "Release '{}' not found",
+                name
+            )));
+        }
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Loads every manifest in the release from the local manifest objects
+    pub fn load_manifests(&self) -> Result<Vec<(String, String, VektManifest)>> {
+        self.manifests
+            .iter()
+            .map(|(name, hash)| {
+                Ok((
+                    name.clone(),
+                    hash.clone(),
+                    refs::load_manifest_object(hash)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Writes every manifest of the release into `dir` under its file name
+    pub fn checkout(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut written = Vec::new();
+        for (name, _, manifest) in self.load_manifests()? {
+            crate::validation::validate_path_safe(&name)?;
+            let path = dir.join(&name);
+            manifest.save(&path)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// Sorted names of local releases, without parsing them
+pub fn release_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(releases_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(str::to_string))
+        .filter(|name| !name.ends_with(".tmp"))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Lists locally known releases, sorted by name
+pub fn list_releases() -> Result<Vec<Release>> {
+    let dir = releases_dir();
+    let mut releases = Vec::new();
+    if !dir.is_dir() {
+        return Ok(releases);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_tmp = path.to_string_lossy().ends_with(".tmp");
+        if path.is_file() && !is_tmp {
+            releases.push(Release::from_bytes(&fs::read(path)?)?);
+        }
+    }
+    releases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(releases)
+}
@@ -0,0 +1,105 @@
+use crate::diff::TensorRename;
+use crate::errors::{Result, VektError};
+use crate::storage::VektManifest;
+use crate::validation::validate_tensor_name;
+use indexmap::IndexMap;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Renaming scheme for tensors, read from a JSON object of regular expressions to
+/// replacements such as `{"^transformer\\.h\\.(\\d+)\\.": "model.layers.$1."}`.
+/// Rules are tried in file order and the first that matches a name renames it.
+#[derive(Debug, Clone)]
+pub struct RenameRules {
+    rules: Vec<(Regex, String)>,
+}
+
+impl RenameRules {
+    /// Parses rules from the JSON in `json`
+    pub fn from_json(json: &str) -> Result<Self> {
+        let raw: IndexMap<String, String> = serde_json::from_str(json)?;
+        let rules = raw
+            .into_iter()
+            .map(|(pattern, replacement)| {
+                Regex::new(&pattern)
+                    .map(|regex| (regex, replacement))
+                    .map_err(|e| VektError::InvalidPattern(format!("'{}': {}", pattern, e)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(RenameRules { rules })
+    }
+
+    /// Reads rules from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// The new name for `name`, or `None` if no rule matches
+    pub fn rename(&self, name: &str) -> Option<String> {
+        self.rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(name))
+            .map(|(regex, replacement)| regex.replace_all(name, replacement.as_str()).into_owned())
+    }
+}
+
+impl VektManifest {
+    /// Renames tensors by `rules` without touching their blobs, keeping their
+    /// positions. Returns the remapped manifest and the tensors that got a new name.
+    pub fn remap(&self, rules: &RenameRules) -> Result<(VektManifest, Vec<TensorRename>)> {
+        self.check_mutable()?;
+        let mut tensors = BTreeMap::new();
+        let mut renamed = Vec::new();
+        for (name, tensor) in &self.tensors {
+            let new_name = match rules.rename(name) {
+                Some(new_name) if new_name != *name => {
+                    validate_tensor_name(&new_name)?;
+                    renamed.push(TensorRename {
+                        old: name.clone(),
+                        new: new_name.clone(),
+                    });
+                    new_name
+                }
+                _ => name.clone(),
+            };
+            if tensors.insert(new_name.clone(), tensor.clone()).is_some() {
+                return Err(VektError::InvalidTensorName(format!(
+                    "Duplicate tensor '{}' after remapping",
+                    new_name
+                )));
+            }
+        }
+
+        let mut remapped = self.clone();
+        if !renamed.is_empty() {
+            remapped.tensors = tensors;
+            remapped.checksum = None;
+            remapped.recompute_total_size()?;
+        }
+        Ok((remapped, renamed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_renames() {
+        let rules = RenameRules::from_json(
+            r#"{"^transformer\\.h\\.(\\d+)\\.": "model.layers.$1.", "^transformer\\.": "model."}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.rename("transformer.h.12.attn.weight").as_deref(),
+            Some("model.layers.12.attn.weight")
+        );
+        assert_eq!(
+            rules.rename("transformer.wte.weight").as_deref(),
+            Some("model.wte.weight")
+        );
+        assert_eq!(rules.rename("lm_head.weight"), None);
+        assert!(RenameRules::from_json(r#"{"(": "x"}"#).is_err());
+    }
+}
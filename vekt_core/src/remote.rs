@@ -1,34 +1,299 @@
+use crate::audit::UploadVerification;
+use crate::blob_index;
 use crate::blobs;
+use crate::cancel::{self, CancellationToken};
+use crate::direct_io;
+use crate::encoding::BlobEncoding;
 use crate::errors::{Result, VektError};
+use crate::hash::HashAlgorithm;
+use crate::object_store::{HttpStore, ObjectStore, PutOptions, TOKEN_ENV, is_http_url};
+use crate::plan::{self, DeltaPlan, PlannedBlob, TransferPlan};
+use crate::ranges::RangeHashes;
+use crate::refs;
+use crate::releases::Release;
+use crate::sampling::{SampleReport, SampledBlob, weighted_sample};
+use crate::sparse;
 use crate::storage::VektManifest;
-use crate::validation::validate_s3_url;
-use futures::stream::{self, StreamExt};
+use crate::tensor_filter::TensorFilter;
+use crate::upstream;
+use crate::utils::{get_vekt_dir, rename_replace, write_file_atomic};
+use crate::validation::{ServerSideEncryption, VerifyPolicy, parse_s3_url, verify_blob_hash};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use s3::bucket::Bucket;
 use s3::creds::Credentials;
 use s3::region::Region;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use tokio::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+/// Where a remote's credentials come from, for pushing to several accounts from one
+/// machine. With nothing set the default AWS chain is used: environment variables,
+/// then the default profile of `~/.aws/credentials`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteCredentials {
+    /// Profile to read from the credentials file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Credentials file to read instead of `~/.aws/credentials`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials_file: Option<PathBuf>,
+    /// Environment variable holding the access key ID, instead of `AWS_ACCESS_KEY_ID`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_env: Option<String>,
+    /// Environment variable holding the secret key, instead of `AWS_SECRET_ACCESS_KEY`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key_env: Option<String>,
+    /// Environment variable holding the bearer token of an HTTP remote, instead of
+    /// `VEKT_TOKEN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+}
+
+impl RemoteCredentials {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Loads the credentials. Environment variable names take precedence over a
+    /// profile, so CI can override a developer's profile without editing the config.
+    pub fn load(&self) -> Result<Credentials> {
+        let creds = if self.access_key_env.is_some() || self.secret_key_env.is_some() {
+            Credentials::from_env_specific(
+                self.access_key_env.as_deref(),
+                self.secret_key_env.as_deref(),
+                None,
+                None,
+            )
+        } else if let Some(file) = &self.credentials_file {
+            Credentials::from_credentials_file(file, self.profile.as_deref())
+        } else if self.profile.is_some() {
+            Credentials::from_profile(self.profile.as_deref())
+        } else {
+            Credentials::default()
+        };
+        creds.map_err(|e| {
+            VektError::CredentialError(format!(
+                "Failed to load AWS credentials from {}: {}",
+                self, e
+            ))
+        })
+    }
+}
+
+impl std::fmt::Display for RemoteCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(token_env) = &self.token_env {
+            return write!(f, "token in ${}", token_env);
+        }
+        if self.access_key_env.is_some() || self.secret_key_env.is_some() {
+            write!(
+                f,
+                "${} and ${}",
+                self.access_key_env
+                    .as_deref()
+                    .unwrap_or("AWS_ACCESS_KEY_ID"),
+                self.secret_key_env
+                    .as_deref()
+                    .unwrap_or("AWS_SECRET_ACCESS_KEY")
+            )
+        } else if self.credentials_file.is_some() || self.profile.is_some() {
+            write!(
+                f,
+                "profile '{}' in {}",
+                self.profile.as_deref().unwrap_or("default"),
+                self.credentials_file
+                    .as_deref()
+                    .map_or("~/.aws/credentials".into(), |p| p.display().to_string())
+            )
+        } else {
+            write!(
+                f,
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, or ~/.aws/credentials"
+            )
+        }
+    }
+}
+
+/// Tries per blob upload before a push gives up on it
+const UPLOAD_ATTEMPTS: u32 = 3;
+
+/// Tries per blob download without progress before a pull gives up on it
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+
+/// Blobs transferred at once unless `concurrency` is configured
+pub const DEFAULT_CONCURRENCY: usize = 10;
+
+/// Blobs a thin restore downloads ahead of the one being written
+const THIN_RESTORE_PREFETCH: usize = 4;
+
+/// What a [thin restore](RemoteClient::restore_thin) wrote and where the data came from
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ThinRestore {
+    /// Bytes written to the output
+    pub bytes: u64,
+    /// Blobs downloaded from the remote
+    pub downloaded: usize,
+    /// Blobs read from the local store instead
+    pub local: usize,
+}
+
+/// A verified blob from [`RemoteClient::fetch_blob`]
+#[derive(Debug)]
+pub enum FetchedBlob {
+    /// Decoded from an encoded blob in the local store
+    Bytes(Vec<u8>),
+    /// A raw blob in the local store
+    Stored(PathBuf),
+    /// A download kept out of the store, removed when this is dropped
+    Downloaded(PathBuf),
+}
+
+impl FetchedBlob {
+    pub fn size(&self) -> std::io::Result<u64> {
+        match self {
+            FetchedBlob::Bytes(data) => Ok(data.len() as u64),
+            FetchedBlob::Stored(path) | FetchedBlob::Downloaded(path) => {
+                Ok(std::fs::metadata(path)?.len())
+            }
+        }
+    }
+
+    /// Reads the blob from where it is, without loading a file into memory
+    pub fn reader(&self) -> std::io::Result<Box<dyn Read + '_>> {
+        match self {
+            FetchedBlob::Bytes(data) => Ok(Box::new(data.as_slice())),
+            FetchedBlob::Stored(path) | FetchedBlob::Downloaded(path) => Ok(Box::new(
+                std::io::BufReader::new(std::fs::File::open(path)?),
+            )),
+        }
+    }
+
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            FetchedBlob::Bytes(data) => Ok(data.clone()),
+            FetchedBlob::Stored(path) | FetchedBlob::Downloaded(path) => std::fs::read(path),
+        }
+    }
+}
+
+impl Drop for FetchedBlob {
+    fn drop(&mut self) {
+        if let FetchedBlob::Downloaded(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Environment variable naming who takes manifest locks, instead of the login name
+pub const LOCK_OWNER_ENV: &str = "VEKT_LOCK_OWNER";
+
+/// Who this machine takes manifest locks as: `VEKT_LOCK_OWNER`, or the login name
+pub fn lock_owner() -> String {
+    std::env::var(LOCK_OWNER_ENV)
+        .ok()
+        .filter(|owner| !owner.trim().is_empty())
+        .unwrap_or_else(crate::commits::default_author)
+}
+
+/// A lock on a manifest name, kept on the remote at `locks/<manifest>`. While it is
+/// held, pushes of that manifest by anyone else are refused unless forced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestLock {
+    pub manifest: String,
+    pub owner: String,
+    /// Unix time the lock was taken
+    pub locked_at: u64,
+}
 
 pub struct RemoteClient {
-    bucket: Bucket,
+    store: ObjectStore,
+    /// URL the remote was opened with, identifying it in [`upstream`] records
+    url: String,
+    cancel: CancellationToken,
+    concurrency: usize,
+    /// S3 storage class blobs are uploaded with
+    storage_class: Option<String>,
+    /// Encryption requested on every upload
+    encryption: Option<ServerSideEncryption>,
+    /// Tags set on every uploaded object
+    tags: BTreeMap<String, String>,
+    /// Push manifests even when someone else holds their lock or they aren't a
+    /// fast-forward of the remote version
+    force: bool,
+    /// Blob uploads the remote did and didn't confirm
+    uploads: UploadCounts,
+}
+
+#[derive(Default)]
+struct UploadCounts {
+    verified: AtomicUsize,
+    unconfirmed: AtomicUsize,
+}
+
+impl UploadCounts {
+    fn record(&self, verified: bool) {
+        let count = match verified {
+            true => &self.verified,
+            false => &self.unconfirmed,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
 impl RemoteClient {
+    /// Client using the default AWS credential chain
     pub fn new(url: &str) -> Result<Self> {
+        Self::with_credentials(url, &RemoteCredentials::default())
+    }
+
+    pub fn with_credentials(url: &str, credentials: &RemoteCredentials) -> Result<Self> {
+        // Another machine running `vekt serve`
+        if is_http_url(url) {
+            let token = std::env::var(credentials.token_env.as_deref().unwrap_or(TOKEN_ENV)).ok();
+            return Ok(Self {
+                store: ObjectStore::Http(HttpStore::new(url, token)),
+                url: url.to_string(),
+                cancel: CancellationToken::new(),
+                concurrency: DEFAULT_CONCURRENCY,
+                storage_class: None,
+                encryption: None,
+                tags: BTreeMap::new(),
+                force: false,
+                uploads: UploadCounts::default(),
+            });
+        }
+
         // Validate S3 URL format
-        let bucket_name = validate_s3_url(url)?;
+        let location = parse_s3_url(url)?;
 
-        let region = std::env::var("AWS_REGION")
-            .ok()
-            .and_then(|r| Region::from_str(&r).ok())
-            .unwrap_or(Region::UsEast1);
+        let region_name = location
+            .region
+            .clone()
+            .or_else(|| std::env::var("AWS_REGION").ok());
+        // S3-compatible stores are reached through their own endpoint
+        let endpoint = location
+            .endpoint
+            .clone()
+            .or_else(|| std::env::var("AWS_ENDPOINT_URL").ok());
+        let region = match endpoint {
+            Some(endpoint) => Region::Custom {
+                region: region_name.unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint,
+            },
+            None => region_name
+                .and_then(|r| Region::from_str(&r).ok())
+                .unwrap_or(Region::UsEast1),
+        };
 
         // Validate credentials exist before proceeding
-        let creds = Credentials::default()
-            .map_err(|e| VektError::CredentialError(format!(
-                "Failed to load AWS credentials. Ensure AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY are set, or configure ~/.aws/credentials: {}",
-                e
-            )))?;
+        let creds = credentials.load()?;
 
         // Verify credentials are not empty
         if creds.access_key.is_none() || creds.secret_key.is_none() {
@@ -37,231 +302,1373 @@ impl RemoteClient {
             ));
         }
 
-        let bucket = *Bucket::new(&bucket_name, region, creds).map_err(|e| {
+        let mut bucket = Bucket::new(&location.bucket, region, creds).map_err(|e| {
             VektError::RemoteError(format!("Failed to initialize S3 bucket: {}", e))
         })?;
+        if location.path_style {
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self {
+            store: ObjectStore::S3(bucket),
+            url: url.to_string(),
+            cancel: CancellationToken::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            storage_class: location.storage_class,
+            encryption: location.encryption,
+            tags: location.tags,
+            force: false,
+            uploads: UploadCounts::default(),
+        })
+    }
+
+    /// The store behind the remote, for reading objects vekt didn't write
+    pub(crate) fn into_store(self) -> ObjectStore {
+        self.store
+    }
+
+    /// Makes blob transfers stop with [`VektError::Cancelled`] once `token` is
+    /// cancelled. Blobs in flight are abandoned; partial downloads are kept for the
+    /// next pull to resume.
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Blobs uploaded so far whose bytes the remote did and didn't confirm
+    pub fn upload_verification(&self) -> UploadVerification {
+        UploadVerification {
+            verified: self.uploads.verified.load(Ordering::Relaxed),
+            unconfirmed: self.uploads.unconfirmed.load(Ordering::Relaxed),
+        }
+    }
 
-        Ok(Self { bucket })
+    /// Transfers up to `concurrency` blobs at once
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Overwrites manifests locked by someone else, or changed on the remote since they
+    /// were last pushed or pulled here, instead of refusing to
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Tags of objects uploaded for the model or tag `name`: the remote's tags plus
+    /// `model` and `manifest-version`, the hash of the manifest object. Remotes without
+    /// tags don't tag uploads at all, so pushing to them never needs permission to tag
+    /// objects.
+    fn model_tags(&self, name: &str, manifest: &VektManifest) -> Result<BTreeMap<String, String>> {
+        let mut tags = self.tags.clone();
+        if !tags.is_empty() {
+            tags.entry("model".to_string())
+                .or_insert_with(|| name.to_string());
+            if !tags.contains_key("manifest-version") {
+                tags.insert(
+                    "manifest-version".to_string(),
+                    upstream::manifest_hash(manifest)?,
+                );
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Encryption and `tags` for an upload that isn't a blob
+    fn put_options<'a>(&'a self, tags: &'a BTreeMap<String, String>) -> PutOptions<'a> {
+        PutOptions {
+            storage_class: None,
+            encryption: self.encryption.as_ref(),
+            tags: Some(tags),
+            create_only: false,
+        }
     }
 
     /// Validates bucket access by attempting a list operation
     pub async fn validate_access(&self) -> Result<()> {
-        self.bucket
-            .list("/".to_string(), Some("/".to_string()))
-            .await
-            .map_err(|e| {
+        self.store.check_access().await.map_err(|e| {
+            if e.is_unauthorized() {
+                VektError::CredentialError(format!("Remote rejected the credentials: {}", e))
+            } else {
                 VektError::RemoteError(format!(
-                    "Failed to access S3 bucket. Check bucket name and permissions: {}",
+                    "Failed to access remote. Check bucket name and permissions: {}",
                     e
                 ))
+            }
+        })
+    }
+
+    /// Lists every blob stored on the remote together with its size
+    pub async fn list_blobs(&self) -> Result<Vec<SampledBlob>> {
+        let objects =
+            self.store.list("blobs/").await.map_err(|e| {
+                VektError::RemoteError(format!("Failed to list remote blobs: {}", e))
             })?;
+
+        Ok(objects
+            .into_iter()
+            .filter_map(|object| {
+                let hash = object.key.strip_prefix("blobs/")?;
+                if hash.is_empty() || hash.ends_with(".tmp") {
+                    return None;
+                }
+                Some(SampledBlob {
+                    hash: hash.to_string(),
+                    size: object.size,
+                })
+            })
+            .collect())
+    }
+
+    /// Downloads a remote blob and checks that its content matches its hash, hashing
+    /// it as it arrives rather than holding it in memory
+    pub async fn verify_blob(&self, hash: &str) -> Result<()> {
+        let remote_path = format!("blobs/{}", hash);
+        let download_error = |e: &dyn std::fmt::Display| {
+            VektError::RemoteError(format!("Failed to download blob {}: {}", hash, e))
+        };
+        let mut reader = self
+            .store
+            .get_reader(&remote_path)
+            .await
+            .map_err(|e| download_error(&e))?;
+        let mut hasher = HashAlgorithm::of(hash).hasher();
+        let mut buffer = vec![0u8; 1 << 20];
+        loop {
+            match reader
+                .read(&mut buffer)
+                .await
+                .map_err(|e| download_error(&e))?
+            {
+                0 => break,
+                n => hasher.update(&buffer[..n]),
+            }
+        }
+        let actual = hasher.finalize();
+        if actual != hash {
+            return Err(VektError::HashMismatch {
+                expected: hash.to_string(),
+                actual,
+            });
+        }
         Ok(())
     }
 
+    /// Verifies a random, size-weighted sample of the remote store.
+    ///
+    /// The same `seed` always selects the same blobs for an unchanged store, so a
+    /// failing run can be reproduced exactly.
+    pub async fn verify_sample(&self, fraction: f64, seed: u64) -> Result<SampleReport> {
+        let blobs = self.list_blobs().await?;
+        let sample = weighted_sample(&blobs, fraction, seed);
+
+        info!(
+            "Verifying {} of {} remote blobs...",
+            sample.len(),
+            blobs.len()
+        );
+
+        let results: Vec<(String, Result<()>)> = stream::iter(sample.iter())
+            .map(|blob| async move { (blob.hash.clone(), self.verify_blob(&blob.hash).await) })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut report = SampleReport {
+            seed,
+            total_blobs: blobs.len(),
+            total_bytes: blobs.iter().map(|b| b.size).sum(),
+            sampled_blobs: sample.len(),
+            sampled_bytes: sample.iter().map(|b| b.size).sum(),
+            failed: Vec::new(),
+        };
+
+        for (hash, res) in results {
+            match res {
+                Ok(()) => {}
+                Err(VektError::HashMismatch { .. }) => report.failed.push(hash),
+                Err(e) => return Err(e),
+            }
+        }
+        report.failed.sort();
+
+        Ok(report)
+    }
+
     pub async fn push(&self, manifest: &VektManifest, manifest_name: &str) -> Result<()> {
-        // Check for existing manifest and warn about conflicts
+        // Refuse before uploading anything rather than after the blobs
+        self.check_push(manifest, manifest_name).await?;
+        let tags = self.model_tags(manifest_name, manifest)?;
+        self.upload_blobs(manifest, &tags).await?;
+        self.upload_manifest(manifest, manifest_name).await
+    }
+
+    /// Like [`push`](Self::push), but only uploads the blobs outside `known` (see
+    /// [`push_blobs_since`](Self::push_blobs_since)). Returns what was uploaded and
+    /// what was skipped.
+    pub async fn push_since(
+        &self,
+        manifest: &VektManifest,
+        manifest_name: &str,
+        known: &HashSet<String>,
+    ) -> Result<DeltaPlan> {
+        self.check_push(manifest, manifest_name).await?;
+        let plan = self
+            .push_blobs_since(manifest, manifest_name, known)
+            .await?;
+        self.upload_manifest(manifest, manifest_name).await?;
+        Ok(plan)
+    }
+
+    /// Whether the remote holds `manifest`: as a manifest object, e.g. of a tag, or
+    /// as the manifest file `manifest_name`
+    pub async fn has_manifest(
+        &self,
+        manifest: &VektManifest,
+        manifest_name: Option<&str>,
+    ) -> Result<bool> {
+        let hash = upstream::manifest_hash(manifest)?;
+        if self
+            .store
+            .exists(&format!("manifest-objects/{}", hash))
+            .await
+        {
+            return Ok(true);
+        }
+        let Some(name) = manifest_name else {
+            return Ok(false);
+        };
+        if !self.store.exists(&format!("manifests/{}", name)).await {
+            return Ok(false);
+        }
+        Ok(upstream::manifest_hash(&self.download_manifest(name).await?)? == hash)
+    }
+
+    /// Uploads only the manifest file; its blobs must already be on the remote
+    pub async fn push_manifest(&self, manifest: &VektManifest, manifest_name: &str) -> Result<()> {
+        self.check_push(manifest, manifest_name).await?;
+        self.upload_manifest(manifest, manifest_name).await
+    }
+
+    async fn upload_manifest(&self, manifest: &VektManifest, manifest_name: &str) -> Result<()> {
         let manifest_path = format!("manifests/{}", manifest_name);
-        if let Ok((_, 200)) = self.bucket.head_object(&manifest_path).await {
-            eprintln!(
-                "Warning: Manifest '{}' already exists on remote. This will overwrite the existing version.",
+
+        // Upload manifest with atomic-like behavior (S3 PUT is atomic)
+        let json = manifest.to_json()?;
+
+        let tags = self.model_tags(manifest_name, manifest)?;
+        self.store
+            .put(&manifest_path, json.as_bytes(), self.put_options(&tags))
+            .await
+            .map_err(|e| {
+                VektError::RemoteError(format!(
+                    "Failed to upload manifest {}: {}",
+                    manifest_name, e
+                ))
+            })?;
+
+        upstream::write(
+            &self.url,
+            manifest_name,
+            &upstream::manifest_hash(manifest)?,
+        )?;
+        info!("Uploaded manifest {}", manifest_name);
+        Ok(())
+    }
+
+    /// Checks that pushing `manifest` as `manifest_name` neither overrides someone
+    /// else's lock nor discards changes made on the remote, unless forced
+    async fn check_push(&self, manifest: &VektManifest, manifest_name: &str) -> Result<()> {
+        self.check_lock(manifest_name).await?;
+        self.check_fast_forward(manifest, manifest_name).await
+    }
+
+    /// Fails with [`VektError::NotFastForward`] unless the remote has no manifest
+    /// `manifest_name`, already has `manifest`, or has the version last pushed or
+    /// pulled here, i.e. the one `manifest` was based on
+    async fn check_fast_forward(&self, manifest: &VektManifest, manifest_name: &str) -> Result<()> {
+        if !self
+            .store
+            .exists(&format!("manifests/{}", manifest_name))
+            .await
+        {
+            return Ok(());
+        }
+        let remote = match self.download_manifest(manifest_name).await {
+            Ok(remote) => Some(upstream::manifest_hash(&remote)?),
+            Err(_) if self.force => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(remote) = &remote
+            && (*remote == upstream::manifest_hash(manifest)?
+                || upstream::read(&self.url, manifest_name)?.as_ref() == Some(remote))
+        {
+            return Ok(());
+        }
+        if !self.force {
+            return Err(VektError::NotFastForward(manifest_name.to_string()));
+        }
+        warn!(
+            "Remote manifest '{}' changed since it was last pushed or pulled here; overwriting it",
+            manifest_name
+        );
+        Ok(())
+    }
+
+    /// Fails with [`VektError::ManifestLocked`] when someone other than
+    /// [`lock_owner`] holds the lock on `manifest_name`, unless forced
+    async fn check_lock(&self, manifest_name: &str) -> Result<()> {
+        let Some(lock) = self.manifest_lock(manifest_name).await? else {
+            return Ok(());
+        };
+        if lock.owner == lock_owner() {
+            return Ok(());
+        }
+        if !self.force {
+            return Err(VektError::ManifestLocked {
+                manifest: lock.manifest,
+                owner: lock.owner,
+            });
+        }
+        warn!(
+            "Manifest '{}' is locked by {}; overwriting it anyway",
+            manifest_name, lock.owner
+        );
+        Ok(())
+    }
+
+    /// Returns the lock held on `manifest_name`, if any
+    pub async fn manifest_lock(&self, manifest_name: &str) -> Result<Option<ManifestLock>> {
+        let path = format!("locks/{}", manifest_name);
+        if !self.store.exists(&path).await {
+            return Ok(None);
+        }
+        let bytes = self.store.get(&path).await.map_err(|e| {
+            VektError::RemoteError(format!("Failed to read lock on {}: {}", manifest_name, e))
+        })?;
+        serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+            VektError::RemoteError(format!("Invalid lock on {}: {}", manifest_name, e))
+        })
+    }
+
+    /// Locks `manifest_name` for `owner`. Taking a lock `owner` already holds is a
+    /// no-op; one held by anyone else fails with [`VektError::ManifestLocked`]. The
+    /// lock object is only created if it doesn't exist, so of two teammates locking
+    /// at once exactly one wins.
+    pub async fn lock_manifest(&self, manifest_name: &str, owner: &str) -> Result<ManifestLock> {
+        if let Some(lock) = self.manifest_lock(manifest_name).await? {
+            if lock.owner == owner {
+                return Ok(lock);
+            }
+            return Err(VektError::ManifestLocked {
+                manifest: lock.manifest,
+                owner: lock.owner,
+            });
+        }
+
+        let lock = ManifestLock {
+            manifest: manifest_name.to_string(),
+            owner: owner.to_string(),
+            locked_at: crate::ephemeral::now_secs(),
+        };
+        let options = PutOptions {
+            create_only: true,
+            ..self.put_options(&self.tags)
+        };
+        let path = format!("locks/{}", manifest_name);
+        match self
+            .store
+            .put(&path, &serde_json::to_vec_pretty(&lock)?, options)
+            .await
+        {
+            Ok(_) => Ok(lock),
+            // Someone else took it between the check and the upload
+            Err(e) if e.is_precondition_failed() => {
+                let owner = match self.manifest_lock(manifest_name).await? {
+                    Some(lock) => lock.owner,
+                    None => "someone else".to_string(),
+                };
+                Err(VektError::ManifestLocked {
+                    manifest: manifest_name.to_string(),
+                    owner,
+                })
+            }
+            Err(e) => Err(VektError::RemoteError(format!(
+                "Failed to lock {}: {}",
+                manifest_name, e
+            ))),
+        }
+    }
+
+    /// Releases the lock on `manifest_name`. Only `owner` may release it unless
+    /// `force` is set, e.g. for a lock left behind by a teammate who is away.
+    pub async fn unlock_manifest(
+        &self,
+        manifest_name: &str,
+        owner: &str,
+        force: bool,
+    ) -> Result<()> {
+        let Some(lock) = self.manifest_lock(manifest_name).await? else {
+            return Err(VektError::RefNotFound(format!(
+                "Manifest '{}' is not locked",
                 manifest_name
-            );
+            )));
+        };
+        if lock.owner != owner && !force {
+            return Err(VektError::ManifestLocked {
+                manifest: lock.manifest,
+                owner: lock.owner,
+            });
         }
+        self.store
+            .delete(&format!("locks/{}", manifest_name))
+            .await
+            .map_err(|e| {
+                VektError::RemoteError(format!("Failed to unlock {}: {}", manifest_name, e))
+            })
+    }
+
+    /// Lists the manifest locks held on the remote, by manifest name
+    pub async fn list_locks(&self) -> Result<Vec<ManifestLock>> {
+        let objects = self
+            .store
+            .list("locks/")
+            .await
+            .map_err(|e| VektError::RemoteError(format!("Failed to list locks: {}", e)))?;
+        let mut locks = Vec::new();
+        for object in objects {
+            let Some(name) = object.key.strip_prefix("locks/") else {
+                continue;
+            };
+            if let Some(lock) = self.manifest_lock(name).await? {
+                locks.push(lock);
+            }
+        }
+        locks.sort_by(|a, b| a.manifest.cmp(&b.manifest));
+        Ok(locks)
+    }
+
+    /// Uploads every blob the manifest references that the remote doesn't have yet
+    pub async fn push_blobs(&self, manifest: &VektManifest) -> Result<()> {
+        self.upload_blobs(manifest, &self.tags).await
+    }
+
+    async fn upload_blobs(
+        &self,
+        manifest: &VektManifest,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let hashes: BTreeSet<&String> = manifest.referenced_hashes().collect();
+        info!("Pushing {} blobs to remote...", hashes.len());
+        self.upload_blob_stream(stream::iter(hashes.into_iter().cloned()), tags)
+            .await
+    }
+
+    /// Uploads the blobs of the manifest `name` outside `known`, which must only hold
+    /// blobs the remote has (e.g. those of a manifest pushed before): they are skipped
+    /// without asking the remote. Returns what was uploaded and what was skipped.
+    pub async fn push_blobs_since(
+        &self,
+        manifest: &VektManifest,
+        name: &str,
+        known: &HashSet<String>,
+    ) -> Result<DeltaPlan> {
+        let plan = plan::plan_delta(&[manifest], known);
+        info!(
+            "Pushing {} blobs to remote, {} already there...",
+            plan.blobs.len(),
+            plan.reused
+        );
+        self.upload_blob_stream(
+            stream::iter(plan.blobs.iter().map(|blob| blob.hash.clone())),
+            &self.model_tags(name, manifest)?,
+        )
+        .await?;
+        Ok(plan)
+    }
+
+    /// Hashes of every blob on the remote, from a single listing
+    pub async fn blob_hashes(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .list_blobs()
+            .await?
+            .into_iter()
+            .map(|blob| blob.hash)
+            .collect())
+    }
 
-        println!("Pushing {} blobs to remote...", manifest.tensors.len());
+    /// Uploads blobs as their hashes arrive, so uploading can overlap with whatever
+    /// produces them (e.g. hashing a checkpoint). Each blob must be in the local store
+    /// by the time its hash is yielded; repeated hashes are uploaded once.
+    pub async fn push_blob_stream(&self, hashes: impl Stream<Item = String>) -> Result<()> {
+        self.upload_blob_stream(hashes, &self.tags).await
+    }
 
+    async fn upload_blob_stream(
+        &self,
+        hashes: impl Stream<Item = String>,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
         let mut uploaded = 0;
         let mut skipped = 0;
+        let mut total = 0;
+        let mut first_error = None;
 
-        let tasks = stream::iter(manifest.tensors.values())
-            .map(|tensor| {
-                let hash = tensor.hash.clone();
-                async move {
-                    let blob_path = blobs::get_blob_path(&hash);
-                    let remote_path = format!("blobs/{}", hash);
-
-                    // Check if blob already exists on remote (avoid re-upload)
-                    match self.bucket.head_object(&remote_path).await {
-                        Ok((_, 200)) => Ok::<(bool, String), VektError>((false, hash)),
-                        _ => {
-                            if !blob_path.exists() {
-                                return Err(VektError::BlobNotFound(format!(
-                                    "Blob {} not found locally for upload",
-                                    hash
-                                )));
-                            }
-
-                            let mut file = File::open(&blob_path).await.map_err(|e| {
-                                VektError::Io(std::io::Error::other(format!(
-                                    "Failed to open blob {}: {}",
-                                    hash, e
-                                )))
-                            })?;
-
-                            let response = self
-                                .bucket
-                                .put_object_stream(&mut file, &remote_path)
-                                .await
-                                .map_err(|e| {
-                                    VektError::RemoteError(format!(
-                                        "Failed to upload blob {}: {}",
-                                        hash, e
-                                    ))
-                                })?;
-
-                            if response.status_code() != 200 {
-                                return Err(VektError::RemoteError(format!(
-                                    "Failed to upload blob {}, status: {}",
-                                    hash,
-                                    response.status_code()
-                                )));
-                            }
-
-                            Ok((true, hash))
-                        }
-                    }
-                }
-            })
-            .buffer_unordered(10);
+        let mut seen = HashSet::new();
+        let mut tasks = std::pin::pin!(
+            hashes
+                .filter(move |hash| future::ready(seen.insert(hash.clone())))
+                .map(|hash| self.upload_blob(hash, tags))
+                .buffer_unordered(self.concurrency)
+        );
 
-        let results: Vec<_> = tasks.collect().await;
-        for res in results {
-            let (was_uploaded, hash) = res?;
-            if was_uploaded {
-                uploaded += 1;
-                println!("Uploaded blob {}", hash);
-            } else {
-                skipped += 1;
+        while let Some(res) = tasks.next().await {
+            total += 1;
+            match res {
+                Ok((true, hash)) => {
+                    uploaded += 1;
+                    debug!(hash = %hash, "Uploaded blob");
+                }
+                Ok((false, _)) => skipped += 1,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
             }
         }
+        if let Some(e) = first_error {
+            return Err(partial_transfer(uploaded + skipped, total, e));
+        }
 
-        println!(
+        info!(
             "Upload complete: {} uploaded, {} skipped (already on remote)",
             uploaded, skipped
         );
+        Ok(())
+    }
 
-        // Upload manifest with atomic-like behavior (S3 PUT is atomic)
-        let json = serde_json::to_string_pretty(manifest).map_err(VektError::Json)?;
+    /// Uploads one blob unless the remote already has it. Returns whether it was
+    /// uploaded, and the hash.
+    async fn upload_blob(
+        &self,
+        hash: String,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<(bool, String)> {
+        cancel::check(&self.cancel, "push")?;
+        let blob_path = blobs::get_blob_path(&hash);
+        let remote_path = format!("blobs/{}", hash);
 
-        self.bucket
-            .put_object(&manifest_path, json.as_bytes())
+        // Check if blob already exists on remote (avoid re-upload)
+        if self.store.exists(&remote_path).await {
+            debug!(hash = %hash, "Blob already on remote");
+            return Ok((false, hash));
+        }
+        if !blobs::blob_exists(&hash) {
+            return Err(VektError::BlobNotFound(format!(
+                "Blob {} not found locally for upload",
+                hash
+            )));
+        }
+
+        // Remotes always hold decoded bytes; local storage encodings stay local
+        let put = || async {
+            let options = PutOptions {
+                storage_class: self.storage_class.as_deref(),
+                ..self.put_options(tags)
+            };
+            let result = if blob_path.exists() {
+                self.store.put_file(&remote_path, &blob_path, options).await
+            } else {
+                let data = blobs::read_blob(&hash).map_err(|e| {
+                    VektError::Io(std::io::Error::other(format!(
+                        "Failed to read blob {}: {}",
+                        hash, e
+                    )))
+                })?;
+                self.store.put(&remote_path, &data, options).await
+            };
+            result.map_err(|e| {
+                VektError::RemoteError(format!("Failed to upload blob {}: {}", hash, e))
+            })
+        };
+        // Transient remote failures get a few more tries before the push fails
+        let upload = async {
+            let mut attempt = 1;
+            loop {
+                match put().await {
+                    Err(VektError::RemoteError(reason)) if attempt < UPLOAD_ATTEMPTS => {
+                        warn!(hash = %hash, attempt, reason = %reason, "Retrying blob upload");
+                        tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        };
+        // An abandoned PUT never becomes visible on the remote
+        let verified = cancel::until_cancelled(&self.cancel, "push", upload).await?;
+        self.uploads.record(verified);
+
+        Ok((true, hash))
+    }
+
+    /// Publishes a tag: blobs first, then the manifest object, and the ref last so
+    /// nobody can resolve the tag before everything it points to is uploaded
+    pub async fn push_tag(&self, name: &str, hash: &str, manifest: &VektManifest) -> Result<()> {
+        self.upload_blobs(manifest, &self.model_tags(name, manifest)?)
+            .await?;
+        self.publish_tag(name, hash, manifest).await
+    }
+
+    /// Uploads the manifest object and the ref of a tag; its blobs must already be on
+    /// the remote
+    pub async fn publish_tag(&self, name: &str, hash: &str, manifest: &VektManifest) -> Result<()> {
+        let tags = self.model_tags(name, manifest)?;
+        self.push_manifest_object(hash, &tags).await?;
+
+        self.store
+            .put(
+                &format!("refs/tags/{}", name),
+                hash.as_bytes(),
+                self.put_options(&tags),
+            )
+            .await
+            .map_err(|e| VektError::RemoteError(format!("Failed to upload tag {}: {}", name, e)))?;
+
+        info!("Pushed tag {} -> {}", name, &hash[..8.min(hash.len())]);
+        Ok(())
+    }
+
+    /// Uploads a manifest object unless the remote already has it
+    async fn push_manifest_object(
+        &self,
+        hash: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let object_path = format!("manifest-objects/{}", hash);
+        if !self.store.exists(&object_path).await {
+            let bytes = refs::read_manifest_object_bytes(hash)?;
+            self.store
+                .put(&object_path, &bytes, self.put_options(tags))
+                .await
+                .map_err(|e| {
+                    VektError::RemoteError(format!(
+                        "Failed to upload manifest object {}: {}",
+                        hash, e
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Fetches and stores a manifest object without downloading its blobs
+    async fn fetch_manifest_object(&self, hash: &str) -> Result<VektManifest> {
+        refs::import_manifest_object(&self.get_manifest_object(hash).await?, hash)
+    }
+
+    async fn get_manifest_object(&self, hash: &str) -> Result<Vec<u8>> {
+        let object_path = format!("manifest-objects/{}", hash);
+        self.store.get(&object_path).await.map_err(|e| {
+            VektError::RemoteError(format!(
+                "Failed to download manifest object {}: {}",
+                hash, e
+            ))
+        })
+    }
+
+    /// Reads the manifest a remote tag points to without storing anything locally,
+    /// checked against `hash` like [`pull_tag`](Self::pull_tag) does
+    pub async fn peek_tag(&self, hash: &str) -> Result<VektManifest> {
+        let bytes = self.get_manifest_object(hash).await?;
+        crate::validation::verify_blob_hash(&bytes, hash)?;
+        let manifest: VektManifest = serde_json::from_slice(&bytes).map_err(|e| {
+            VektError::ManifestCorrupted(format!("Manifest object {}: {}", hash, e))
+        })?;
+        manifest.validate_and_migrate()
+    }
+
+    /// Works out which blobs of `manifests` a push would upload, asking the remote
+    /// which it already has
+    pub async fn plan_upload(&self, manifests: &[&VektManifest]) -> Result<TransferPlan> {
+        let sizes = plan::local_sizes(manifests);
+        let present: Vec<(String, u64, bool)> = stream::iter(sizes)
+            .map(|(hash, size)| async move {
+                let exists = self.store.exists(&format!("blobs/{}", hash)).await;
+                (hash, size, exists)
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        let mut plan = TransferPlan {
+            manifests: manifests.len(),
+            ..Default::default()
+        };
+        for (hash, size, exists) in present {
+            if exists {
+                plan.present += 1;
+            } else {
+                plan.blobs.push(PlannedBlob { hash, size });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Returns the release published on the remote under `name`, if any
+    pub async fn get_release(&self, name: &str) -> Result<Option<Release>> {
+        let path = format!("releases/{}", name);
+        if !self.store.exists(&path).await {
+            return Ok(None);
+        }
+        let bytes = self.store.get(&path).await.map_err(|e| {
+            VektError::RemoteError(format!("Failed to download release {}: {}", name, e))
+        })?;
+        Ok(Some(Release::from_bytes(&bytes)?))
+    }
+
+    /// Publishes a release atomically: every blob and manifest object of every member
+    /// is uploaded first, and the release object is written last in a single PUT.
+    /// Consumers listing releases therefore never see a partially uploaded release.
+    ///
+    /// Published releases are immutable; an existing release with different contents
+    /// is only replaced when `force` is set.
+    pub async fn push_release(&self, release: &Release, force: bool) -> Result<()> {
+        if let Some(existing) = self.get_release(&release.name).await? {
+            if existing.manifests == release.manifests {
+                info!("Release {} is already published", release.name);
+                return Ok(());
+            }
+            if !force {
+                return Err(VektError::RefExists(format!("release {}", release.name)));
+            }
+        }
+
+        let members = release.load_manifests()?;
+        for (name, hash, manifest) in &members {
+            info!("Uploading {}...", name);
+            let tags = self.model_tags(name, manifest)?;
+            self.upload_blobs(manifest, &tags).await?;
+            self.push_manifest_object(hash, &tags).await?;
+        }
+
+        self.store
+            .put(
+                &format!("releases/{}", release.name),
+                &release.to_bytes()?,
+                self.put_options(&self.tags),
+            )
             .await
             .map_err(|e| {
-                VektError::RemoteError(format!(
-                    "Failed to upload manifest {}: {}",
-                    manifest_name, e
-                ))
+                VektError::RemoteError(format!("Failed to publish release {}: {}", release.name, e))
             })?;
-
-        println!("Uploaded manifest {}", manifest_name);
+        info!(
+            "Published release {} ({} manifests)",
+            release.name,
+            members.len()
+        );
         Ok(())
     }
 
+    /// Lists the names of releases published on the remote
+    pub async fn list_releases(&self) -> Result<Vec<String>> {
+        let objects = self
+            .store
+            .list("releases/")
+            .await
+            .map_err(|e| VektError::RemoteError(format!("Failed to list releases: {}", e)))?;
+        let mut names: Vec<String> = objects
+            .into_iter()
+            .filter_map(|object| object.key.strip_prefix("releases/").map(str::to_string))
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Downloads a release with all its manifest objects and blobs, and records it locally
+    pub async fn pull_release(&self, name: &str) -> Result<Release> {
+        let release = self.get_release(name).await?.ok_or_else(|| {
+            VektError::RefNotFound(format!("Release '{}' not found on remote", name))
+        })?;
+        for hash in release.manifests.values() {
+            let manifest = self.fetch_manifest_object(hash).await?;
+            self.download_blobs(&manifest).await?;
+        }
+        release.save()?;
+        Ok(release)
+    }
+
+    /// Lists tags published on the remote as (name, manifest hash)
+    pub async fn list_tags(&self) -> Result<Vec<(String, String)>> {
+        let objects =
+            self.store.list("refs/tags/").await.map_err(|e| {
+                VektError::RemoteError(format!("Failed to list remote tags: {}", e))
+            })?;
+
+        let mut tags = Vec::new();
+        for object in objects {
+            let Some(name) = object.key.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            let bytes = self.store.get(&object.key).await.map_err(|e| {
+                VektError::RemoteError(format!("Failed to read remote tag {}: {}", name, e))
+            })?;
+            let hash = String::from_utf8_lossy(&bytes).trim().to_string();
+            tags.push((name.to_string(), hash));
+        }
+        tags.sort();
+        Ok(tags)
+    }
+
+    /// Downloads a tagged manifest object and its blobs into the local store.
+    /// The local tag itself is left for the caller to write.
+    pub async fn pull_tag(&self, name: &str, hash: &str) -> Result<VektManifest> {
+        let manifest = self.fetch_manifest_object(hash).await.map_err(|e| {
+            VektError::RemoteError(format!("Failed to fetch tag '{}': {}", name, e))
+        })?;
+        self.download_blobs(&manifest).await?;
+        Ok(manifest)
+    }
+
+    /// Downloads a manifest and its blobs, and records the version pulled as the one
+    /// the next push of `manifest_name` must be based on
     pub async fn pull(&self, manifest_name: &str) -> Result<VektManifest> {
+        let manifest = self.download_manifest(manifest_name).await?;
+        self.download_blobs(&manifest).await?;
+        upstream::write(
+            &self.url,
+            manifest_name,
+            &upstream::manifest_hash(&manifest)?,
+        )?;
+        Ok(manifest)
+    }
+
+    async fn download_manifest(&self, manifest_name: &str) -> Result<VektManifest> {
         let manifest_path = format!("manifests/{}", manifest_name);
 
-        let response_data = self.bucket.get_object(&manifest_path).await.map_err(|e| {
+        let bytes = self.store.get(&manifest_path).await.map_err(|e| {
             VektError::RemoteError(format!(
                 "Failed to download manifest '{}': {}. Ensure the manifest exists on remote.",
                 manifest_name, e
             ))
         })?;
 
-        let bytes = response_data.bytes();
-        let manifest: VektManifest = serde_json::from_slice(bytes).map_err(|e| {
+        serde_json::from_slice(&bytes).map_err(|e| {
             VektError::InvalidManifest(format!(
                 "Failed to parse manifest '{}': {}",
                 manifest_name, e
             ))
+        })
+    }
+
+    /// Every manifest pushed to the remote, by name, without downloading blobs
+    pub async fn list_manifests(&self) -> Result<Vec<(String, VektManifest)>> {
+        let objects = self.store.list("manifests/").await.map_err(|e| {
+            VektError::RemoteError(format!("Failed to list remote manifests: {}", e))
         })?;
+        let names: Vec<String> = objects
+            .into_iter()
+            .filter_map(|object| {
+                let name = object.key.strip_prefix("manifests/")?;
+                name.ends_with(".vekt.json").then(|| name.to_string())
+            })
+            .collect();
+        stream::iter(names)
+            .map(|name| async move {
+                let manifest = self.download_manifest(&name).await?;
+                Ok((name, manifest))
+            })
+            .buffered(self.concurrency)
+            .try_collect()
+            .await
+    }
 
-        println!(
-            "Downloading {} blobs from remote...",
-            manifest.tensors.len()
-        );
+    /// Fetches a manifest pushed under `name`, or else the one tagged `name`, without
+    /// downloading its blobs
+    pub async fn fetch_manifest(&self, name: &str) -> Result<VektManifest> {
+        if !self.store.exists(&format!("manifests/{}", name)).await
+            && let Some((_, hash)) = self
+                .list_tags()
+                .await?
+                .into_iter()
+                .find(|(tag, _)| tag == name)
+        {
+            return self.peek_tag(&hash).await;
+        }
+        self.download_manifest(name).await
+    }
+
+    /// Restores `manifest` straight from the remote into `output`, so the model never
+    /// has to fit on disk twice, once in the store and once assembled. Blobs already in
+    /// the local store are read from there; the rest are downloaded a few at a time in
+    /// file order and written as they arrive, after verification. With `cache` the
+    /// downloaded blobs are kept in the store too. A failed restore removes `output`.
+    pub async fn restore_thin(
+        &self,
+        manifest: &VektManifest,
+        output: &Path,
+        filter: &TensorFilter,
+        cache: bool,
+    ) -> Result<ThinRestore> {
+        let mut writer = direct_io::create(output)?;
+        let restored = self
+            .restore_thin_to_writer(manifest, &mut writer, filter, cache)
+            .await
+            .and_then(|restore| Ok(writer.finish().map(|_| restore)?));
+        if restored.is_err() {
+            let _ = std::fs::remove_file(output);
+        }
+        restored
+    }
+
+    /// Like [`restore_thin`](Self::restore_thin), but streams to `writer`, e.g. stdout
+    pub async fn restore_thin_to_writer<W: Write>(
+        &self,
+        manifest: &VektManifest,
+        writer: &mut W,
+        filter: &TensorFilter,
+        cache: bool,
+    ) -> Result<ThinRestore> {
+        if manifest.layout.is_some() {
+            return Err(VektError::InvalidManifest(
+                "Restoring from a remote is only supported for safetensors models".to_string(),
+            ));
+        }
+        let plan = manifest.safetensor_writer(filter, VerifyPolicy::Always)?;
+        let (prefix, planned) = plan.plan()?;
+        writer.write_all(&prefix)?;
+
+        let mut restore = ThinRestore {
+            bytes: prefix.len() as u64,
+            ..Default::default()
+        };
+        let mut fetches = stream::iter(&planned)
+            .map(|item| async move { (item, self.fetch_blob(item.hash(), cache).await) })
+            .buffered(THIN_RESTORE_PREFETCH);
+        while let Some((item, fetched)) = fetches.next().await {
+            let (blob, downloaded) = fetched?;
+            restore.bytes += item.copy_to(writer, blob.reader()?, blob.size()?)? as u64;
+            if downloaded {
+                restore.downloaded += 1;
+            } else {
+                restore.local += 1;
+            }
+        }
+        Ok(restore)
+    }
+
+    /// Writes the manifest's attachments into `dir`, fetching the ones missing locally
+    /// from the remote the way [`restore_thin`](Self::restore_thin) does
+    pub async fn restore_thin_attachments(
+        &self,
+        manifest: &VektManifest,
+        dir: &Path,
+        cache: bool,
+    ) -> Result<usize> {
+        let mut fetched = HashMap::new();
+        for attachment in manifest.attachments.values() {
+            let (blob, _) = self.fetch_blob(&attachment.hash, cache).await?;
+            fetched.insert(attachment.hash.as_str(), blob.to_bytes()?);
+        }
+        manifest.restore_attachments_with(dir, |_, hash| Ok(fetched[hash].clone()))
+    }
+
+    /// Returns a blob, verified, from the local store if it is there and from the
+    /// remote otherwise, along with whether it was downloaded. Downloads stream to
+    /// disk like [`pull`](Self::pull) does, into the store with `cache` and into a
+    /// temporary file next to it otherwise.
+    pub async fn fetch_blob(&self, hash: &str, cache: bool) -> Result<(FetchedBlob, bool)> {
+        cancel::check(&self.cancel, "restore")?;
+        if let Some((path, encoding)) = blobs::find_blob(hash) {
+            if encoding != BlobEncoding::Raw {
+                let data = blobs::read_blob(hash)?;
+                verify_blob_hash(&data, hash)?;
+                return Ok((FetchedBlob::Bytes(data), false));
+            }
+            let check_path = path.clone();
+            let algorithm = HashAlgorithm::of(hash);
+            let actual =
+                tokio::task::spawn_blocking(move || blobs::hash_file_as(&check_path, algorithm))
+                    .await
+                    .map_err(|e| VektError::Io(std::io::Error::other(e)))??;
+            if actual != hash {
+                return Err(VektError::HashMismatch {
+                    expected: hash.to_string(),
+                    actual,
+                });
+            }
+            return Ok((FetchedBlob::Stored(path), false));
+        }
+
+        let fetched = match cache {
+            true => self
+                .download_blob(hash)
+                .await
+                .map(|()| FetchedBlob::Stored(blobs::get_blob_path(hash))),
+            false => self
+                .download_verified(hash)
+                .await
+                .map(FetchedBlob::Downloaded),
+        };
+        match fetched {
+            Err(VektError::ArchivedBlobs(_)) => Err(self.archived_blobs_error(&[hash]).await),
+            result => Ok((result?, true)),
+        }
+    }
+
+    /// Downloads every blob the manifest references that is missing locally, less
+    /// any tensors a [sparse](crate::sparse) spec leaves out
+    pub async fn download_blobs(&self, manifest: &VektManifest) -> Result<()> {
+        let hashes = sparse::wanted_blobs(manifest)?;
+        info!("Downloading {} blobs from remote...", hashes.len());
 
         let mut downloaded = 0;
         let mut skipped = 0;
 
-        let tasks = stream::iter(manifest.tensors.values())
-            .map(|tensor| {
-                let hash = tensor.hash.clone();
-                async move {
-                    let blob_path = blobs::get_blob_path(&hash);
+        let tasks = stream::iter(hashes)
+            .map(|hash| async move {
+                // Skip if blob already exists locally
+                if blobs::blob_exists(hash) {
+                    return (hash, Ok::<bool, VektError>(false));
+                }
+                (hash, self.download_blob(hash).await.map(|_| true))
+            })
+            .buffer_unordered(self.concurrency);
 
-                    // Skip if blob already exists locally
-                    if blob_path.exists() {
-                        return Ok::<bool, VektError>(false);
-                    }
+        let results: Vec<_> = tasks.collect().await;
+        let total = results.len();
+        let mut first_error = None;
+        let mut archived = Vec::new();
+        for (hash, res) in results {
+            match res {
+                Ok(true) => downloaded += 1,
+                Ok(false) => skipped += 1,
+                Err(VektError::ArchivedBlobs(_)) => archived.push(hash.as_str()),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        // Archived blobs need the user to act before any retry can succeed
+        if !archived.is_empty() {
+            return Err(self.archived_blobs_error(&archived).await);
+        }
+        if let Some(e) = first_error {
+            return Err(partial_transfer(downloaded + skipped, total, e));
+        }
 
-                    let remote_path = format!("blobs/{}", hash);
-
-                    let mut stream =
-                        self.bucket
-                            .get_object_stream(&remote_path)
-                            .await
-                            .map_err(|e| {
-                                VektError::RemoteError(format!(
-                                    "Failed to download blob {}: {}",
-                                    hash, e
-                                ))
-                            })?;
-
-                    // Write to temp file first, then rename for atomicity
-                    let tmp_path = blob_path.with_extension("tmp");
-                    let mut file = File::create(&tmp_path).await.map_err(|e| {
-                        VektError::Io(std::io::Error::other(format!(
-                            "Failed to create temp file for blob {}: {}",
-                            hash, e
-                        )))
-                    })?;
+        info!(
+            "Download complete: {} downloaded, {} skipped (already local)",
+            downloaded, skipped
+        );
 
-                    tokio::io::copy(&mut stream, &mut file).await.map_err(|e| {
-                        VektError::Io(std::io::Error::other(format!(
-                            "Failed to write blob {}: {}",
-                            hash, e
-                        )))
-                    })?;
+        Ok(())
+    }
+
+    /// Downloads a blob into the store as a raw file, verifying it before it becomes
+    /// visible. Whatever an interrupted download left in the blob's `.tmp` file is
+    /// kept and only the rest is requested.
+    async fn download_blob(&self, hash: &str) -> Result<()> {
+        let blob_path = blobs::get_blob_path(hash);
+        let tmp_path = self.download_verified(hash).await?;
+
+        // Atomic rename, replacing a corrupt copy when repairing
+        rename_replace(&tmp_path, &blob_path).map_err(|e| {
+            VektError::Io(std::io::Error::other(format!(
+                "Failed to finalize blob {}: {}",
+                hash, e
+            )))
+        })?;
+        blob_index::record_written(&blob_path);
+        debug!(hash = %hash, "Downloaded blob");
+
+        Ok(())
+    }
 
-                    // Ensure data is flushed
+    /// Downloads a blob into its `.tmp` file next to the store and verifies it.
+    /// Returns the file's path.
+    async fn download_verified(&self, hash: &str) -> Result<PathBuf> {
+        let tmp_path = blobs::get_blob_path(hash).with_extension("tmp");
+        loop {
+            let resumed = self.download_to_tmp(hash, &tmp_path).await?;
+
+            // Re-verify before the blob becomes visible in the store
+            let check_path = tmp_path.clone();
+            let algorithm = HashAlgorithm::of(hash);
+            let actual =
+                tokio::task::spawn_blocking(move || blobs::hash_file_as(&check_path, algorithm))
+                    .await
+                    .map_err(|e| VektError::Io(std::io::Error::other(e)))??;
+            if actual == hash {
+                break;
+            }
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            // The kept bytes may be what was wrong, so those get one fresh download
+            if !resumed {
+                return Err(VektError::HashMismatch {
+                    expected: hash.to_string(),
+                    actual,
+                });
+            }
+            warn!(hash = %hash, "Resumed download doesn't match its hash, downloading it again");
+        }
+        Ok(tmp_path)
+    }
+
+    /// Completes `tmp_path` with the blob's bytes from the remote, retrying transient
+    /// failures with backoff. Attempts that got further than the one before don't
+    /// count against the limit. Returns whether bytes from before this call were
+    /// kept.
+    async fn download_to_tmp(&self, hash: &str, tmp_path: &Path) -> Result<bool> {
+        let remote_path = format!("blobs/{}", hash);
+        let mut attempt = 1;
+        loop {
+            cancel::check(&self.cancel, "pull")?;
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(tmp_path)
+                .await
+                .map_err(|e| {
+                    VektError::Io(std::io::Error::other(format!(
+                        "Failed to create temp file for blob {}: {}",
+                        hash, e
+                    )))
+                })?;
+            let offset = file.metadata().await?.len();
+            if offset > 0 {
+                debug!(hash = %hash, offset, "Resuming blob download");
+            }
+
+            // A cancelled download keeps what it wrote for the next pull
+            let download = async {
+                let result = self
+                    .store
+                    .get_to_file(&remote_path, &mut file, offset)
+                    .await;
+                let _ = file.flush().await;
+                Ok(result)
+            };
+            match cancel::until_cancelled(&self.cancel, "pull", download).await? {
+                Ok(start) => {
                     file.sync_all().await.map_err(|e| {
                         VektError::Io(std::io::Error::other(format!(
                             "Failed to sync blob {}: {}",
                             hash, e
                         )))
                     })?;
-
+                    return Ok(start > 0);
+                }
+                Err(e) if e.is_transient() && attempt < DOWNLOAD_ATTEMPTS => {
                     drop(file);
+                    let written = tokio::fs::metadata(tmp_path).await?.len();
+                    if written > offset {
+                        attempt = 1;
+                    } else {
+                        attempt += 1;
+                    }
+                    warn!(hash = %hash, attempt, written, reason = %e, "Retrying blob download");
+                    tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+                }
+                Err(e) => return Err(download_error(hash, e)),
+            }
+        }
+    }
+
+    /// Checks every local blob the manifest references and downloads what is missing
+    /// or corrupt. Tensors with range hashes only download their corrupt ranges.
+    pub async fn repair(&self, manifest: &VektManifest) -> Result<Vec<(String, BlobRepair)>> {
+        let mut blobs: BTreeMap<&String, Option<(&RangeHashes, u64)>> = manifest
+            .referenced_hashes()
+            .map(|hash| (hash, None))
+            .collect();
+        for tensor in manifest.tensors.values() {
+            if let Some(ranges) = &tensor.ranges {
+                blobs.insert(&tensor.hash, Some((ranges, tensor.byte_size()? as u64)));
+            }
+        }
+
+        let mut repairs = Vec::new();
+        for (hash, ranges) in blobs {
+            let repair = self.repair_blob(hash, ranges).await?;
+            repairs.push((hash.clone(), repair));
+        }
+        Ok(repairs)
+    }
 
-                    // Atomic rename
-                    tokio::fs::rename(&tmp_path, &blob_path)
+    /// Verifies one local blob and repairs it from the remote if needed. With the
+    /// blob's range hashes and length, a raw blob of the right length is repaired by
+    /// downloading only the ranges that don't match; anything else is downloaded whole.
+    pub async fn repair_blob(
+        &self,
+        hash: &str,
+        ranges: Option<(&RangeHashes, u64)>,
+    ) -> Result<BlobRepair> {
+        let raw_path = blobs::get_blob_path(hash);
+        if let Some((ranges, len)) = ranges
+            && raw_path.is_file()
+        {
+            let check = ranges.clone();
+            let path = raw_path.clone();
+            let (mut data, corrupt) = tokio::task::spawn_blocking(move || {
+                let data = std::fs::read(&path)?;
+                let corrupt = check.corrupt_ranges(&data, len);
+                Ok::<_, std::io::Error>((data, corrupt))
+            })
+            .await
+            .map_err(|e| VektError::Io(std::io::Error::other(e)))??;
+
+            // Matching range hashes combine into the blob hash, so the blob is intact
+            if data.len() as u64 == len && corrupt.is_empty() {
+                return Ok(BlobRepair::Intact);
+            }
+            if data.len() as u64 == len {
+                let mut fetched = 0;
+                for &index in &corrupt {
+                    let range = ranges.range(index, len);
+                    let part = self
+                        .store
+                        .get_range(&format!("blobs/{}", hash), range.clone())
                         .await
                         .map_err(|e| {
-                            VektError::Io(std::io::Error::other(format!(
-                                "Failed to finalize blob {}: {}",
-                                hash, e
-                            )))
+                            VektError::RemoteError(format!(
+                                "Failed to download range {} of blob {}: {}",
+                                index, hash, e
+                            ))
                         })?;
-
-                    Ok(true)
+                    if !ranges.verify_range(index, &part) {
+                        return Err(VektError::TensorCorruption(format!(
+                            "Range {} of blob {} is corrupt on the remote too",
+                            index, hash
+                        )));
+                    }
+                    data[range.start as usize..range.end as usize].copy_from_slice(&part);
+                    fetched += part.len() as u64;
+                }
+                if verify_blob_hash(&data, hash).is_ok() {
+                    write_file_atomic(&raw_path, &data)?;
+                    blob_index::record_written(&raw_path);
+                    return Ok(BlobRepair::Ranges {
+                        ranges: corrupt,
+                        bytes: fetched,
+                    });
                 }
+            }
+        } else if blobs::blob_exists(hash) {
+            let check = hash.to_string();
+            let intact = tokio::task::spawn_blocking(move || {
+                blobs::read_blob(&check).is_ok_and(|data| verify_blob_hash(&data, &check).is_ok())
             })
-            .buffer_unordered(10);
+            .await
+            .map_err(|e| VektError::Io(std::io::Error::other(e)))?;
+            if intact {
+                return Ok(BlobRepair::Intact);
+            }
+        }
 
-        let results: Vec<_> = tasks.collect().await;
-        for res in results {
-            if res? {
-                downloaded += 1;
-            } else {
-                skipped += 1;
+        self.download_blob(hash).await?;
+        // The raw file now shadows any corrupt encoded copy; drop it
+        for encoding in BlobEncoding::ENCODED {
+            if let Some(extension) = encoding.extension() {
+                let path = raw_path.with_extension(extension);
+                if std::fs::remove_file(&path).is_ok() {
+                    blob_index::record_removed(&path);
+                }
             }
         }
+        let bytes = std::fs::metadata(&raw_path)?.len();
+        Ok(BlobRepair::Whole { bytes })
+    }
+}
 
-        println!(
-            "Download complete: {} downloaded, {} skipped (already local)",
-            downloaded, skipped
+impl RemoteClient {
+    /// Explains how to restore archived `hashes` so a pull can read them. Their keys
+    /// are added to `.vekt/archived-blobs`, one per line, for scripting the restore.
+    async fn archived_blobs_error(&self, hashes: &[&str]) -> VektError {
+        let keys: Vec<String> = hashes
+            .iter()
+            .map(|hash| format!("blobs/{}", hash))
+            .collect();
+        let statuses: Vec<_> = stream::iter(&keys)
+            .map(|key| self.store.archive_status(key))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        let restoring = statuses.iter().flatten().filter(|s| s.restoring).count();
+        let classes: BTreeSet<&str> = statuses
+            .iter()
+            .flatten()
+            .filter_map(|s| s.storage_class.as_deref())
+            .collect();
+
+        let list_path = get_vekt_dir().join("archived-blobs");
+        let mut listed: BTreeSet<String> = std::fs::read_to_string(&list_path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        listed.extend(keys.iter().cloned());
+        let list = listed.into_iter().collect::<Vec<_>>().join("\n") + "\n";
+        if let Err(e) = write_file_atomic(&list_path, list.as_bytes()) {
+            warn!("Failed to write {}: {}", list_path.display(), e);
+        }
+
+        let mut message = format!(
+            "{} blobs are in archive storage{}",
+            hashes.len(),
+            match classes.is_empty() {
+                true => String::new(),
+                false => format!(" ({})", classes.into_iter().collect::<Vec<_>>().join(", ")),
+            }
         );
+        if restoring > 0 {
+            message += &format!(
+                " and a restore is already in progress for {}; pull again once it finishes",
+                restoring
+            );
+        }
+        if restoring < hashes.len() {
+            message += &format!(
+                ". Their keys are listed in {}. Restore them, e.g. for 7 days, then pull again:\n  \
+                 xargs -I{{}} aws s3api restore-object --bucket {} --key {{}} \
+                 --restore-request '{{\"Days\":7}}' < {}",
+                list_path.display(),
+                self.store.bucket_name().unwrap_or("<bucket>"),
+                list_path.display()
+            );
+        }
+        VektError::ArchivedBlobs(message)
+    }
+}
 
-        Ok(manifest)
+/// A failed blob download as a [`VektError`]: [`VektError::ArchivedBlobs`] when the
+/// blob needs a restore first
+fn download_error(hash: &str, e: crate::object_store::StoreError) -> VektError {
+    if e.is_archived() {
+        VektError::ArchivedBlobs(format!("blob {} is archived", hash))
+    } else {
+        VektError::RemoteError(format!("Failed to download blob {}: {}", hash, e))
+    }
+}
+
+/// What [`RemoteClient::repair_blob`] did to a local blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlobRepair {
+    /// The local copy was already correct
+    Intact,
+    /// These ranges were corrupt and downloaded again, `bytes` in total
+    Ranges { ranges: Vec<usize>, bytes: u64 },
+    /// The blob was missing or couldn't be repaired by range and was downloaded whole
+    Whole { bytes: u64 },
+}
+
+/// Wraps the first failure of a blob batch. Nothing-transferred failures keep their
+/// own error so that, e.g., a missing local blob still reads as not found.
+fn partial_transfer(completed: usize, total: usize, error: VektError) -> VektError {
+    if completed == 0 || matches!(error, VektError::Cancelled(_)) {
+        return error;
+    }
+    VektError::PartialTransfer {
+        completed,
+        total,
+        reason: error.to_string(),
     }
 }
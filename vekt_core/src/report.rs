@@ -0,0 +1,273 @@
+use crate::storage::{ManifestTensor, VektManifest};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+static LAYER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_layer_regex() -> &'static Regex {
+    LAYER_REGEX
+        .get_or_init(|| Regex::new(r"(?:^|\.)(?:layers|layer|h|blocks|block)\.(\d+)\.").unwrap())
+}
+
+/// Extracts the transformer layer number from names like `model.layers.12.mlp.weight`
+pub fn layer_of(tensor_name: &str) -> Option<usize> {
+    get_layer_regex()
+        .captures(tensor_name)
+        .and_then(|caps| caps[1].parse().ok())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Default)]
+struct LayerStats {
+    total: usize,
+    changed: usize,
+}
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, Segoe UI, Helvetica, Arial, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; } h2 { font-size: 1.15em; margin-top: 2em; }
+.summary td { padding: 2px 12px 2px 0; }
+table.tensors { border-collapse: collapse; width: 100%; font-size: 0.85em; }
+table.tensors th { cursor: pointer; background: #f0f0f0; text-align: left; }
+table.tensors th, table.tensors td { border: 1px solid #ddd; padding: 4px 6px; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+tr.added { background: #e6ffec; } tr.removed { background: #ffebe9; } tr.modified { background: #fff8c5; } tr.renamed { background: #ddf4ff; }
+.heatmap { display: flex; flex-wrap: wrap; gap: 3px; }
+.cell { width: 38px; height: 38px; font-size: 0.7em; display: flex; align-items: center; justify-content: center; border: 1px solid #ccc; }
+code { font-size: 0.95em; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll('table.tensors th').forEach(function (th, col) {
+  th.addEventListener('click', function () {
+    var tbody = th.closest('table').tBodies[0];
+    var asc = th.dataset.asc !== 'true';
+    th.dataset.asc = asc;
+    Array.from(tbody.rows).sort(function (a, b) {
+      var x = a.cells[col].dataset.sort || a.cells[col].textContent;
+      var y = b.cells[col].dataset.sort || b.cells[col].textContent;
+      var nx = parseFloat(x), ny = parseFloat(y);
+      var cmp = (!isNaN(nx) && !isNaN(ny)) ? nx - ny : x.localeCompare(y);
+      return asc ? cmp : -cmp;
+    }).forEach(function (row) { tbody.appendChild(row); });
+  });
+});
+"#;
+
+fn tensor_cell(tensor: Option<&ManifestTensor>) -> (String, String, usize) {
+    match tensor {
+        Some(t) => (
+            format!("{:?}", t.shape),
+            t.hash[..8.min(t.hash.len())].to_string(),
+            // The report is informational; a tensor of unknown dtype counts as empty
+            t.byte_size().unwrap_or(0),
+        ),
+        None => ("-".to_string(), "-".to_string(), 0),
+    }
+}
+
+/// Renders a standalone HTML report comparing two manifests, with a sortable tensor
+/// table, a per-layer change heatmap, and size deltas. No external assets are used.
+pub fn render_html_diff(
+    old: &VektManifest,
+    new: &VektManifest,
+    old_label: &str,
+    new_label: &str,
+) -> String {
+    let comparison = old.diff(new);
+    let diff = &comparison.tensor_diff;
+
+    let added: HashSet<&String> = diff.added.iter().collect();
+    let removed: HashSet<&String> = diff.removed.iter().collect();
+    let modified: HashSet<&String> = diff.modified.iter().collect();
+    let renamed: HashSet<&String> = diff
+        .renamed
+        .iter()
+        .flat_map(|rename| [&rename.old, &rename.new])
+        .collect();
+    let status_of = |name: &String| -> &'static str {
+        if renamed.contains(name) {
+            "renamed"
+        } else if added.contains(name) {
+            "added"
+        } else if removed.contains(name) {
+            "removed"
+        } else if modified.contains(name) {
+            "modified"
+        } else {
+            "unchanged"
+        }
+    };
+
+    let names: BTreeSet<&String> = old.tensors.keys().chain(new.tensors.keys()).collect();
+
+    let mut layers: BTreeMap<usize, LayerStats> = BTreeMap::new();
+    let mut rows = String::new();
+    let (mut old_bytes, mut new_bytes) = (0usize, 0usize);
+
+    for name in &names {
+        let status = status_of(name);
+        let (old_shape, old_hash, old_size) = tensor_cell(old.tensors.get(*name));
+        let (new_shape, new_hash, new_size) = tensor_cell(new.tensors.get(*name));
+        old_bytes += old_size;
+        new_bytes += new_size;
+        let delta = new_size as i64 - old_size as i64;
+        let dtype = new
+            .tensors
+            .get(*name)
+            .or_else(|| old.tensors.get(*name))
+            .map(|t| t.dtype.as_str())
+            .unwrap_or("-");
+
+        if let Some(layer) = layer_of(name) {
+            let stats = layers.entry(layer).or_default();
+            stats.total += 1;
+            if status != "unchanged" {
+                stats.changed += 1;
+            }
+        }
+
+        let _ = writeln!(
+            rows,
+            "<tr class=\"{status}\"><td><code>{}</code></td><td>{status}</td><td>{}</td><td>{} &rarr; {}</td><td>{} &rarr; {}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\" data-sort=\"{delta}\">{:+}</td></tr>",
+            escape_html(name),
+            escape_html(dtype),
+            old_shape,
+            new_shape,
+            old_hash,
+            new_hash,
+            old_size,
+            new_size,
+            delta,
+        );
+    }
+
+    let mut heatmap = String::new();
+    for (layer, stats) in &layers {
+        let ratio = stats.changed as f64 / stats.total as f64;
+        // White (unchanged) to red (fully changed)
+        let channel = (255.0 * (1.0 - ratio)).round() as u8;
+        let _ = write!(
+            heatmap,
+            "<div class=\"cell\" style=\"background: rgb(255,{channel},{channel})\" title=\"layer {layer}: {}/{} tensors changed\">{layer}</div>",
+            stats.changed, stats.total
+        );
+    }
+    if heatmap.is_empty() {
+        heatmap.push_str("<p>No layer-indexed tensors found.</p>");
+    }
+
+    let size_delta = new_bytes as i64 - old_bytes as i64;
+    let savings = &comparison.storage_savings;
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>vekt diff: {old_label} &rarr; {new_label}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<h1>vekt diff: <code>{old_label}</code> &rarr; <code>{new_label}</code></h1>
+<table class="summary">
+<tr><td>Added</td><td>{added}</td></tr>
+<tr><td>Removed</td><td>{removed}</td></tr>
+<tr><td>Modified</td><td>{modified}</td></tr>
+<tr><td>Renamed</td><td>{renamed}</td></tr>
+<tr><td>Unchanged</td><td>{unchanged}</td></tr>
+<tr><td>Tensor bytes</td><td>{old_bytes} &rarr; {new_bytes} ({size_delta:+})</td></tr>
+<tr><td>Shared blobs</td><td>{shared}</td></tr>
+<tr><td>Deduplication ratio</td><td>{ratio:.2}x</td></tr>
+</table>
+<h2>Per-layer changes</h2>
+<div class="heatmap">{heatmap}</div>
+<h2>Tensors</h2>
+<table class="tensors">
+<thead><tr><th>Name</th><th>Status</th><th>Dtype</th><th>Shape</th><th>Hash</th><th>Old bytes</th><th>New bytes</th><th>Delta</th></tr></thead>
+<tbody>
+{rows}</tbody>
+</table>
+<script>{SCRIPT}</script>
+</body>
+</html>
+"#,
+        old_label = escape_html(old_label),
+        new_label = escape_html(new_label),
+        added = diff.added.len(),
+        removed = diff.removed.len(),
+        modified = diff.modified.len(),
+        renamed = diff.renamed.len(),
+        unchanged = diff.unchanged.len(),
+        shared = savings.shared_blobs,
+        ratio = savings.deduplication_ratio,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+
+    fn manifest(entries: &[(&str, &str)]) -> VektManifest {
+        VektManifest {
+            tensors: entries
+                .iter()
+                .enumerate()
+                .map(|(i, (name, hash))| {
+                    let tensor = ManifestTensor {
+                        shape: vec![2, 2],
+                        dtype: "F32".to_string(),
+                        hash: hash.to_string(),
+                        index: i,
+                        extra: IndexMap::new(),
+                        ranges: None,
+                        stats: None,
+                    };
+                    (name.to_string(), tensor)
+                })
+                .collect(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: 0,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_layer_of() {
+        assert_eq!(layer_of("model.layers.12.mlp.weight"), Some(12));
+        assert_eq!(layer_of("transformer.h.0.attn.bias"), Some(0));
+        assert_eq!(layer_of("lm_head.weight"), None);
+    }
+
+    #[test]
+    fn test_render_html_diff() {
+        let old = manifest(&[
+            ("model.layers.0.w", "aaaaaaaaaa"),
+            ("model.layers.1.w", "bbbbbbbbbb"),
+        ]);
+        let new = manifest(&[
+            ("model.layers.0.w", "aaaaaaaaaa"),
+            ("model.layers.1.w", "cccccccccc"),
+            ("<script>", "dddddddddd"),
+        ]);
+
+        let html = render_html_diff(&old, &new, "old.vekt.json", "new.vekt.json");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<tr class=\"modified\"><td><code>model.layers.1.w</code>"));
+        assert!(html.contains("layer 1: 1/1 tensors changed"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::numeric;
+use crate::storage::VektManifest;
+use crate::utils::get_dtype_bits;
+use crate::validation::verify_blob_hash;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How elements that don't exist in the old tensor are initialized
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResizeInit {
+    Zeros,
+    /// New rows are the mean of the existing rows (dim 0 growth only, float dtypes)
+    Mean,
+    /// Raw little-endian bytes for the new elements, in row-major order
+    File(PathBuf),
+}
+
+impl FromStr for ResizeInit {
+    type Err = VektError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zeros" => Ok(ResizeInit::Zeros),
+            "mean" => Ok(ResizeInit::Mean),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(ResizeInit::File(PathBuf::from(path))),
+                _ => Err(VektError::InvalidManifest(format!(
+                    "Unknown init '{}'. Use zeros, mean or file:<path>",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+/// Parses a shape such as `32064,4096` or `32064x4096`
+pub fn parse_shape(s: &str) -> Result<Vec<usize>> {
+    s.split([',', 'x'])
+        .map(|d| {
+            d.trim()
+                .parse::<usize>()
+                .map_err(|_| VektError::InvalidManifest(format!("Invalid shape '{}'", s)))
+        })
+        .collect()
+}
+
+fn element_count(shape: &[usize]) -> usize {
+    shape.iter().product()
+}
+
+/// Mean of the rows of a `[rows, cols]` float tensor, in the same dtype
+fn mean_row(data: &[u8], dtype: &str, rows: usize, cols: usize) -> Result<Vec<u8>> {
+    if !numeric::is_float_dtype(dtype) {
+        return Err(VektError::InvalidManifest(format!(
+            "Mean initialization is not supported for {}; use zeros or file:<path>",
+            dtype
+        )));
+    }
+    if rows == 0 {
+        return Err(VektError::InvalidManifest(
+            "Mean initialization needs at least one existing row".to_string(),
+        ));
+    }
+
+    let values = numeric::decode_floats(data, dtype)?;
+    let mut sums = vec![0f64; cols];
+    for row in values.chunks_exact(cols) {
+        for (sum, value) in sums.iter_mut().zip(row) {
+            *sum += value;
+        }
+    }
+    let means: Vec<f64> = sums.into_iter().map(|sum| sum / rows as f64).collect();
+    numeric::encode_floats(&means, dtype)
+}
+
+/// Copies the overlap of `old` (shape `old_shape`) into a tensor of `new_shape`,
+/// calling `fill` with the index of every element outside the old bounds
+fn resize_bytes<F>(
+    old: &[u8],
+    old_shape: &[usize],
+    new_shape: &[usize],
+    size: usize,
+    mut fill: F,
+) -> Result<Vec<u8>>
+where
+    F: FnMut(&[usize], &mut Vec<u8>) -> Result<()>,
+{
+    let rank = new_shape.len();
+    let mut out = Vec::with_capacity(element_count(new_shape) * size);
+    if rank == 0 {
+        out.extend_from_slice(&old[..size.min(old.len())]);
+        return Ok(out);
+    }
+
+    // Row-major strides of the old tensor
+    let mut old_strides = vec![1usize; rank];
+    for d in (0..rank - 1).rev() {
+        old_strides[d] = old_strides[d + 1] * old_shape[d + 1];
+    }
+
+    let mut index = vec![0usize; rank];
+    for _ in 0..element_count(new_shape) {
+        if index.iter().zip(old_shape).all(|(i, n)| i < n) {
+            let offset: usize = index.iter().zip(&old_strides).map(|(i, s)| i * s).sum();
+            out.extend_from_slice(&old[offset * size..(offset + 1) * size]);
+        } else {
+            fill(&index, &mut out)?;
+        }
+        // Advance the multi-dimensional index
+        for d in (0..rank).rev() {
+            index[d] += 1;
+            if index[d] < new_shape[d] {
+                break;
+            }
+            index[d] = 0;
+        }
+    }
+    Ok(out)
+}
+
+impl VektManifest {
+    /// Builds a new manifest where `tensor` has `new_shape`. Data overlapping the old
+    /// shape is reused from the old blob; new elements come from `init`. The result
+    /// is stored as a new blob, so the original manifest stays restorable.
+    ///
+    /// Only the resized tensor gets a new blob; every other tensor keeps its hash.
+    pub fn patch_resize(
+        &self,
+        tensor: &str,
+        new_shape: &[usize],
+        init: &ResizeInit,
+    ) -> Result<VektManifest> {
+        let old = self.tensors.get(tensor).ok_or_else(|| {
+            VektError::InvalidManifest(format!("Tensor '{}' not found in manifest", tensor))
+        })?;
+        if old.shape.is_empty() {
+            return Err(VektError::InvalidManifest(format!(
+                "Cannot resize scalar tensor '{}'",
+                tensor
+            )));
+        }
+        if old.shape.len() != new_shape.len() {
+            return Err(VektError::InvalidManifest(format!(
+                "Cannot resize '{}' from rank {} to rank {}",
+                tensor,
+                old.shape.len(),
+                new_shape.len()
+            )));
+        }
+
+        let bits = get_dtype_bits(&old.dtype)?;
+        if bits % 8 != 0 {
+            return Err(VektError::InvalidManifest(format!(
+                "Cannot resize '{}': {} packs several elements per byte",
+                tensor, old.dtype
+            )));
+        }
+        let size = bits / 8;
+        let byte_size = old.byte_size()?;
+        let data = blobs::read_blob(&old.hash)?;
+        verify_blob_hash(&data, &old.hash)?;
+        if data.len() != byte_size {
+            return Err(VektError::ManifestCorrupted(format!(
+                "Blob for '{}' is {} bytes, expected {}",
+                tensor,
+                data.len(),
+                byte_size
+            )));
+        }
+
+        let resized = match init {
+            ResizeInit::Zeros => resize_bytes(&data, &old.shape, new_shape, size, |_, out| {
+                out.extend(std::iter::repeat_n(0u8, size));
+                Ok(())
+            })?,
+            ResizeInit::Mean => {
+                if old.shape[1..] != new_shape[1..] {
+                    return Err(VektError::InvalidManifest(
+                        "Mean initialization only supports resizing dim 0".to_string(),
+                    ));
+                }
+                let cols = element_count(&old.shape[1..]);
+                let mean = mean_row(&data, &old.dtype, old.shape[0], cols)?;
+                resize_bytes(&data, &old.shape, new_shape, size, |index, out| {
+                    let col: usize = index[1..]
+                        .iter()
+                        .zip(&new_shape[1..])
+                        .fold(0, |acc, (i, n)| acc * n + i);
+                    out.extend_from_slice(&mean[col * size..(col + 1) * size]);
+                    Ok(())
+                })?
+            }
+            ResizeInit::File(path) => {
+                let init_data = std::fs::read(path)?;
+                let overlap: usize = old
+                    .shape
+                    .iter()
+                    .zip(new_shape)
+                    .map(|(a, b)| *a.min(b))
+                    .product();
+                let expected = (element_count(new_shape) - overlap) * size;
+                if init_data.len() != expected {
+                    return Err(VektError::InvalidManifest(format!(
+                        "{} has {} bytes; {} new elements of {} need exactly {} bytes",
+                        path.display(),
+                        init_data.len(),
+                        element_count(new_shape) - overlap,
+                        old.dtype,
+                        expected
+                    )));
+                }
+                let mut cursor = 0;
+                resize_bytes(&data, &old.shape, new_shape, size, |_, out| {
+                    out.extend_from_slice(&init_data[cursor..cursor + size]);
+                    cursor += size;
+                    Ok(())
+                })?
+            }
+        };
+
+        let (hash, _) = blobs::save_blob_deduplicated(&resized)?;
+        let mut patched = VektManifest {
+            checksum: None,
+            ..self.clone()
+        };
+        let entry = patched.tensors.get_mut(tensor).unwrap();
+        entry.shape = new_shape.to_vec();
+        entry.hash = hash;
+        entry.stats = None;
+        patched.total_size =
+            (self.total_size as i64 + resized.len() as i64 - data.len() as i64).max(0) as usize;
+        Ok(patched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_init_and_shape() {
+        assert_eq!("zeros".parse::<ResizeInit>().unwrap(), ResizeInit::Zeros);
+        assert_eq!(
+            "file:rows.bin".parse::<ResizeInit>().unwrap(),
+            ResizeInit::File(PathBuf::from("rows.bin"))
+        );
+        assert!("ones".parse::<ResizeInit>().is_err());
+        assert_eq!(parse_shape("32064,4096").unwrap(), vec![32064, 4096]);
+        assert_eq!(parse_shape("3x2").unwrap(), vec![3, 2]);
+        assert!(parse_shape("3,,2").is_err());
+    }
+
+    #[test]
+    fn test_resize_bytes_grow_and_shrink() {
+        // [[1, 2], [3, 4]] as U8
+        let old = [1u8, 2, 3, 4];
+        let mut zeros = |_: &[usize], out: &mut Vec<u8>| {
+            out.push(0);
+            Ok(())
+        };
+        let grown = resize_bytes(&old, &[2, 2], &[3, 3], 1, &mut zeros).unwrap();
+        assert_eq!(grown, vec![1, 2, 0, 3, 4, 0, 0, 0, 0]);
+        let shrunk = resize_bytes(&old, &[2, 2], &[1, 2], 1, &mut zeros).unwrap();
+        assert_eq!(shrunk, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_mean_row() {
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 6.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mean = mean_row(&data, "F32", 2, 2).unwrap();
+        assert_eq!(
+            mean,
+            [2.0f32, 4.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>()
+        );
+        assert!(mean_row(&data, "I32", 2, 2).is_err());
+    }
+}
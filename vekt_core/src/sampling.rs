@@ -0,0 +1,186 @@
+use crate::errors::{Result, VektError};
+#[cfg(feature = "native")]
+use rand::rngs::StdRng;
+#[cfg(feature = "native")]
+use rand::{Rng, SeedableRng};
+
+/// z-score for a two-sided 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// A blob as seen by a sampling verifier: its hash and stored size in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampledBlob {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Parses a sample size such as "5%", "0.5%" or "0.05" into a fraction in (0, 1]
+pub fn parse_sample_fraction(spec: &str) -> Result<f64> {
+    let spec = spec.trim();
+    let fraction = if let Some(pct) = spec.strip_suffix('%') {
+        pct.trim().parse::<f64>().map(|p| p / 100.0)
+    } else {
+        spec.parse::<f64>()
+    }
+    .map_err(|_| VektError::InvalidSample(format!("Cannot parse sample size '{}'", spec)))?;
+
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(VektError::InvalidSample(format!(
+            "Sample size '{}' must be greater than 0% and at most 100%",
+            spec
+        )));
+    }
+    Ok(fraction)
+}
+
+/// Selects `ceil(fraction * len)` blobs without replacement, weighted by blob size.
+///
+/// Uses Efraimidis-Spirakis weighted reservoir keys (`u^(1/w)`), so the selection is
+/// fully determined by `seed` and large blobs are proportionally more likely to be checked.
+#[cfg(feature = "native")]
+pub fn weighted_sample(blobs: &[SampledBlob], fraction: f64, seed: u64) -> Vec<SampledBlob> {
+    if blobs.is_empty() {
+        return Vec::new();
+    }
+
+    let count = ((blobs.len() as f64 * fraction).ceil() as usize).clamp(1, blobs.len());
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut keyed: Vec<(f64, &SampledBlob)> = blobs
+        .iter()
+        .map(|blob| {
+            // Zero-byte blobs still get a tiny weight so they can be selected
+            let weight = blob.size.max(1) as f64;
+            let u: f64 = rng.r#gen::<f64>().max(f64::MIN_POSITIVE);
+            (u.powf(1.0 / weight), blob)
+        })
+        .collect();
+
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed
+        .into_iter()
+        .take(count)
+        .map(|(_, blob)| blob.clone())
+        .collect()
+}
+
+/// Outcome of verifying a sample of the store
+#[derive(Debug, Default)]
+pub struct SampleReport {
+    pub seed: u64,
+    pub total_blobs: usize,
+    pub total_bytes: u64,
+    pub sampled_blobs: usize,
+    pub sampled_bytes: u64,
+    pub failed: Vec<String>,
+}
+
+impl SampleReport {
+    /// Observed fraction of sampled blobs that failed verification. Blobs are drawn
+    /// in proportion to their size, so this estimates the share of the store's bytes
+    /// held in corrupt blobs rather than the share of blobs that are corrupt.
+    pub fn failure_rate(&self) -> f64 {
+        if self.sampled_blobs == 0 {
+            return 0.0;
+        }
+        self.failed.len() as f64 / self.sampled_blobs as f64
+    }
+
+    /// Upper bound of the 95% Wilson score interval for the byte-weighted corruption
+    /// rate [`failure_rate`](Self::failure_rate) estimates
+    pub fn corruption_upper_bound(&self) -> f64 {
+        let n = self.sampled_blobs as f64;
+        if n == 0.0 {
+            return 1.0;
+        }
+        let p = self.failure_rate();
+        let z2 = Z_95 * Z_95;
+        let center = p + z2 / (2.0 * n);
+        let margin = Z_95 * ((p * (1.0 - p) + z2 / (4.0 * n)) / n).sqrt();
+        ((center + margin) / (1.0 + z2 / n)).min(1.0)
+    }
+
+    /// Fraction of the store's bytes that were actually verified
+    pub fn byte_coverage(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.sampled_bytes as f64 / self.total_bytes as f64
+    }
+
+    pub fn print_summary(&self) {
+        println!("\nSampled Verification Report:");
+        println!("==================");
+        println!("Seed: {}", self.seed);
+        println!(
+            "Verified {} of {} blobs ({} of {} bytes, {:.2}% coverage)",
+            self.sampled_blobs,
+            self.total_blobs,
+            self.sampled_bytes,
+            self.total_bytes,
+            self.byte_coverage() * 100.0
+        );
+        println!("Failures: {}", self.failed.len());
+        for hash in &self.failed {
+            println!("  ! {}", hash);
+        }
+        println!(
+            "Estimated share of bytes in corrupt blobs: {:.4}% (95% upper bound: {:.4}%)",
+            self.failure_rate() * 100.0,
+            self.corruption_upper_bound() * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "native")]
+    fn blobs() -> Vec<SampledBlob> {
+        (0..100)
+            .map(|i| SampledBlob {
+                hash: format!("hash{}", i),
+                size: if i == 0 { 1_000_000_000 } else { 10 },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_sample_fraction() {
+        assert_eq!(parse_sample_fraction("5%").unwrap(), 0.05);
+        assert_eq!(parse_sample_fraction("0.25").unwrap(), 0.25);
+        assert!(parse_sample_fraction("0%").is_err());
+        assert!(parse_sample_fraction("150%").is_err());
+        assert!(parse_sample_fraction("abc").is_err());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_weighted_sample_is_seedable_and_weighted() {
+        let blobs = blobs();
+        let a = weighted_sample(&blobs, 0.05, 42);
+        let b = weighted_sample(&blobs, 0.05, 42);
+
+        assert_eq!(a.len(), 5);
+        assert_eq!(a, b, "Same seed must select the same blobs");
+        assert!(
+            a.iter().any(|blob| blob.hash == "hash0"),
+            "Dominant blob should almost surely be sampled"
+        );
+    }
+
+    #[test]
+    fn test_corruption_upper_bound_shrinks_with_samples() {
+        let small = SampleReport {
+            sampled_blobs: 10,
+            ..Default::default()
+        };
+        let large = SampleReport {
+            sampled_blobs: 1000,
+            ..Default::default()
+        };
+        assert!(large.corruption_upper_bound() < small.corruption_upper_bound());
+        assert_eq!(large.failure_rate(), 0.0);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::hash::{self, HashAlgorithm};
+use crate::object_store::ObjectInfo;
+use crate::refs;
+use crate::releases::{self, Release};
+use crate::remote::ManifestLock;
+use crate::storage::VektManifest;
+use crate::utils::{LockFile, find_vekt_root, get_vekt_dir, write_file_atomic};
+use bytes::Bytes;
+use futures::TryStreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, Limited, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE, IF_NONE_MATCH, RANGE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+/// Largest manifest, tag or release object accepted by a `PUT`. Blobs are streamed
+/// to disk and have no limit.
+const MAX_OBJECT_SIZE: usize = 64 << 20;
+
+/// How long an upload waits for a gc or other command holding the repository lock
+const LOCK_WAIT: Duration = Duration::from_secs(60);
+
+type Body = BoxBody<Bytes, std::io::Error>;
+
+/// Key of an object in the remote layout, mapped onto the local repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Key {
+    Blob(String),
+    /// A `*.vekt.json` file in the repository root
+    Manifest(String),
+    ManifestObject(String),
+    Tag(String),
+    Release(String),
+    /// Lock on the manifest of that name
+    Lock(String),
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_manifest_name(name: &str) -> bool {
+    name.ends_with(".vekt.json") && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// Where the locks of served manifests are kept
+fn locks_dir() -> PathBuf {
+    get_vekt_dir().join("remote-locks")
+}
+
+impl Key {
+    fn parse(key: &str) -> Option<Self> {
+        let (prefix, name) = key.split_once('/')?;
+        match prefix {
+            "blobs" if hash::is_hash(name) => Some(Key::Blob(name.to_string())),
+            "manifest-objects" if is_hash(name) => Some(Key::ManifestObject(name.to_string())),
+            "manifests" if is_manifest_name(name) => Some(Key::Manifest(name.to_string())),
+            "locks" if is_manifest_name(name) => Some(Key::Lock(name.to_string())),
+            "refs" => {
+                let tag = name.strip_prefix("tags/")?;
+                refs::validate_ref_name(tag).ok()?;
+                Some(Key::Tag(tag.to_string()))
+            }
+            "releases" => {
+                releases::validate_release_name(name).ok()?;
+                Some(Key::Release(name.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    fn path(&self, root: &std::path::Path) -> PathBuf {
+        match self {
+            Key::Blob(hash) => blobs::get_blob_path(hash),
+            Key::Manifest(name) => root.join(name),
+            Key::ManifestObject(hash) => refs::manifest_objects_dir().join(hash),
+            Key::Tag(name) => refs::tags_dir().join(name),
+            Key::Release(name) => releases::releases_dir().join(name),
+            Key::Lock(name) => locks_dir().join(name),
+        }
+    }
+}
+
+/// Serves the local repository over HTTP in the layout remotes use, so another
+/// machine can `push` to and `pull` from it as `http://host:port`.
+///
+/// Objects are `GET`, `HEAD` and `PUT` at `/<key>` (`/blobs/<hash>`,
+/// `/manifests/<name>`, `/manifest-objects/<hash>`, `/refs/tags/<name>`,
+/// `/releases/<name>`, `/locks/<manifest>`), and `GET /<prefix>/` lists a prefix as
+/// JSON. A `GET` with a single `Range: bytes=<first>-<last>` returns just that part.
+/// Uploads are verified before they become visible: blobs and manifest objects must
+/// match their hash, and manifests, tags, releases and locks must parse. A `PUT` with
+/// `If-None-Match: *` fails with 412 if the object exists, and locks can be `DELETE`d.
+/// Uploads are stored under the repository lock, so they never land in the middle
+/// of a gc.
+pub struct BlobServer {
+    root: PathBuf,
+    token: Option<String>,
+    /// Uploads to this server take turns for the repository lock here rather than
+    /// polling for it
+    writes: Arc<Mutex<()>>,
+}
+
+impl BlobServer {
+    /// Serves the repository found from the current directory. With a `token`, every
+    /// request needs `Authorization: Bearer <token>`.
+    pub fn new(token: Option<String>) -> Result<Self> {
+        let root = find_vekt_root().ok_or(VektError::RepoNotFound)?;
+        Ok(BlobServer {
+            root,
+            token,
+            writes: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Accepts connections until the task is dropped
+    pub async fn run(self, listener: TcpListener) -> Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    warn!("Connection error: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Body> {
+        if !self.authorized(&request) {
+            return status(StatusCode::UNAUTHORIZED, "Missing or wrong token");
+        }
+        let key = request.uri().path().trim_start_matches('/').to_string();
+        let method = request.method().clone();
+        let result = match (&method, key.as_str()) {
+            (&Method::GET | &Method::HEAD, "") => Ok(status(StatusCode::OK, "vekt")),
+            (&Method::GET, prefix) if prefix.ends_with('/') => self.list(prefix).await,
+            (&Method::DELETE, key) => match Key::parse(key) {
+                Some(object @ Key::Lock(_)) => self.delete(&object).await,
+                Some(_) => Ok(status(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "Only locks can be deleted",
+                )),
+                None => Ok(status(StatusCode::NOT_FOUND, "No such object")),
+            },
+            (&Method::GET | &Method::HEAD | &Method::PUT, key) => match Key::parse(key) {
+                Some(object) if method == Method::PUT => self.put(&object, request).await,
+                Some(object) => {
+                    let range = request
+                        .headers()
+                        .get(RANGE)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_range);
+                    self.get(&object, method == Method::HEAD, range).await
+                }
+                None => Ok(status(StatusCode::NOT_FOUND, "No such object")),
+            },
+            _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED, "Unsupported method")),
+        };
+        result.unwrap_or_else(|e| {
+            let code = match e {
+                VektError::HashMismatch { .. }
+                | VektError::InvalidManifest(_)
+                | VektError::ManifestCorrupted(_)
+                | VektError::InvalidRefName(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            status(code, &e.to_string())
+        })
+    }
+
+    /// Runs `write` on a blocking thread while holding the repository lock
+    async fn locked<T: Send + 'static>(
+        &self,
+        write: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let writes = self.writes.clone();
+        tokio::task::spawn_blocking(move || {
+            let _turn = writes.lock().unwrap_or_else(|e| e.into_inner());
+            let _lock = LockFile::lock_with_wait(LOCK_WAIT)?;
+            write()
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    fn authorized(&self, request: &Request<Incoming>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| tokens_match(given, token))
+    }
+
+    /// Serves an object, or only `range` of it (clamped to its size) with a 206
+    async fn get(
+        &self,
+        object: &Key,
+        head: bool,
+        range: Option<Range<u64>>,
+    ) -> Result<Response<Body>> {
+        let path = object.path(&self.root);
+        // Blobs kept in another encoding are decoded; remotes always serve raw bytes
+        if let Key::Blob(hash) = object
+            && !path.exists()
+        {
+            if !blobs::blob_exists(hash) {
+                return Ok(status(StatusCode::NOT_FOUND, "No such blob"));
+            }
+            let hash = hash.clone();
+            let data = tokio::task::spawn_blocking(move || blobs::read_blob(&hash))
+                .await
+                .map_err(std::io::Error::other)??;
+            let size = data.len() as u64;
+            return Ok(match clamp_range(range, size) {
+                Some(range) => {
+                    let part = data[range.start as usize..range.end as usize].to_vec();
+                    partial(bytes(part.len() as u64, head, part), &range, size)
+                }
+                None => bytes(size, head, data),
+            });
+        }
+        if !path.is_file() {
+            return Ok(status(StatusCode::NOT_FOUND, "No such object"));
+        }
+
+        let mut file = tokio::fs::File::open(&path).await?;
+        let size = file.metadata().await?.len();
+        let range = clamp_range(range, size);
+        let length = match &range {
+            Some(range) => {
+                file.seek(std::io::SeekFrom::Start(range.start)).await?;
+                range.end - range.start
+            }
+            None => size,
+        };
+        let mut response = Response::new(if head {
+            empty()
+        } else {
+            let reader = ReaderStream::new(file.take(length));
+            BodyExt::boxed(StreamBody::new(reader.map_ok(Frame::data)))
+        });
+        response.headers_mut().insert(CONTENT_LENGTH, length.into());
+        Ok(match range {
+            Some(range) => partial(response, &range, size),
+            None => response,
+        })
+    }
+
+    async fn put(&self, object: &Key, request: Request<Incoming>) -> Result<Response<Body>> {
+        if let Key::Blob(hash) = object {
+            return self.put_blob(hash, request.into_body()).await;
+        }
+
+        let create_only = request.headers().contains_key(IF_NONE_MATCH);
+        let body = Limited::new(request.into_body(), MAX_OBJECT_SIZE)
+            .collect()
+            .await
+            .map_err(|e| VektError::InvalidManifest(format!("Failed to read upload: {}", e)))?
+            .to_bytes();
+        match object {
+            Key::Manifest(_) => {
+                serde_json::from_slice::<VektManifest>(&body)
+                    .map_err(|e| VektError::InvalidManifest(format!("Uploaded manifest: {}", e)))?;
+                let path = object.path(&self.root);
+                self.locked(move || write_file_atomic(&path, &body).map_err(Into::into))
+                    .await?;
+            }
+            Key::ManifestObject(hash) => {
+                let hash = hash.clone();
+                self.locked(move || refs::import_manifest_object(&body, &hash).map(|_| ()))
+                    .await?;
+            }
+            Key::Tag(name) => {
+                let hash = String::from_utf8_lossy(&body).trim().to_string();
+                if !is_hash(&hash) {
+                    return Err(VektError::InvalidRefName(format!(
+                        "Tag {} must hold a manifest hash",
+                        name
+                    )));
+                }
+                let name = name.clone();
+                self.locked(move || refs::write_tag(&name, &hash, true))
+                    .await?;
+            }
+            Key::Release(name) => {
+                let release = Release::from_bytes(&body)?;
+                if &release.name != name {
+                    return Err(VektError::InvalidRefName(format!(
+                        "Release '{}' uploaded as '{}'",
+                        release.name, name
+                    )));
+                }
+                self.locked(move || release.save()).await?;
+            }
+            Key::Lock(name) => {
+                let lock: ManifestLock = serde_json::from_slice(&body)
+                    .map_err(|e| VektError::InvalidManifest(format!("Uploaded lock: {}", e)))?;
+                if &lock.manifest != name {
+                    return Err(VektError::InvalidManifest(format!(
+                        "Lock on '{}' uploaded as '{}'",
+                        lock.manifest, name
+                    )));
+                }
+                let path = object.path(&self.root);
+                std::fs::create_dir_all(locks_dir())?;
+                if create_only {
+                    if !create_file(&path, &body)? {
+                        return Ok(status(StatusCode::PRECONDITION_FAILED, "Already locked"));
+                    }
+                } else {
+                    write_file_atomic(&path, &body)?;
+                }
+            }
+            Key::Blob(_) => unreachable!(),
+        }
+        Ok(status(StatusCode::OK, "Stored"))
+    }
+
+    async fn delete(&self, object: &Key) -> Result<Response<Body>> {
+        let path = object.path(&self.root);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(status(StatusCode::OK, "Deleted")),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(status(StatusCode::NOT_FOUND, "No such object"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Streams a blob to a temporary file and only moves it into the store once its
+    /// content matches the hash
+    async fn put_blob(&self, hash: &str, body: Incoming) -> Result<Response<Body>> {
+        let path = blobs::get_blob_path(hash);
+        // Concurrent uploads of the same blob each write their own file
+        let tmp_path = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        let mut frames = body.into_data_stream();
+        let written: std::io::Result<()> = async {
+            while let Some(chunk) = frames.try_next().await.map_err(std::io::Error::other)? {
+                file.write_all(&chunk).await?;
+            }
+            file.sync_all().await
+        }
+        .await;
+        drop(file);
+
+        let check_path = tmp_path.clone();
+        let verified = match written {
+            Ok(()) => {
+                let algorithm = HashAlgorithm::of(hash);
+                tokio::task::spawn_blocking(move || blobs::hash_file_as(&check_path, algorithm))
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|r| r)
+            }
+            Err(e) => Err(e),
+        };
+        let result = match verified {
+            Ok(actual) if actual == hash => {
+                let (tmp_path, hash) = (tmp_path.clone(), hash.to_string());
+                self.locked(move || blobs::finalize_blob(&tmp_path, &hash).map_err(Into::into))
+                    .await
+            }
+            Ok(actual) => Err(VektError::HashMismatch {
+                expected: hash.to_string(),
+                actual,
+            }),
+            Err(e) => Err(e.into()),
+        };
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        result.map(|()| status(StatusCode::OK, "Stored"))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Response<Body>> {
+        let root = self.root.clone();
+        let prefix = prefix.to_string();
+        let objects = tokio::task::spawn_blocking(move || list_objects(&root, &prefix))
+            .await
+            .map_err(std::io::Error::other)??;
+        let json = serde_json::to_vec(&objects)?;
+        Ok(bytes(json.len() as u64, false, json))
+    }
+}
+
+/// Objects under `prefix`, named by their full key
+fn list_objects(root: &std::path::Path, prefix: &str) -> Result<Vec<ObjectInfo>> {
+    let mut objects = Vec::new();
+    let mut add_dir = |dir: PathBuf, keep: &dyn Fn(&str) -> bool| -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_file() && keep(&name) {
+                objects.push(ObjectInfo {
+                    key: format!("{}{}", prefix, name),
+                    size: entry.metadata()?.len(),
+                });
+            }
+        }
+        Ok(())
+    };
+
+    match prefix {
+        "blobs/" => {
+            let mut hashes = std::collections::BTreeSet::new();
+            if let Ok(entries) = std::fs::read_dir(crate::utils::get_store_path()) {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let hash = hash::from_file_name(&name);
+                    if hash::is_hash(&hash) && !name.ends_with(".tmp") {
+                        hashes.insert(hash.into_owned());
+                    }
+                }
+            }
+            for hash in hashes {
+                let raw = blobs::get_blob_path(&hash);
+                let size = match std::fs::metadata(&raw) {
+                    Ok(metadata) => metadata.len(),
+                    Err(_) => blobs::read_blob(&hash)?.len() as u64,
+                };
+                objects.push(ObjectInfo {
+                    key: format!("blobs/{}", hash),
+                    size,
+                });
+            }
+        }
+        "manifests/" => add_dir(root.to_path_buf(), &|name| name.ends_with(".vekt.json"))?,
+        "manifest-objects/" => add_dir(refs::manifest_objects_dir(), &is_hash)?,
+        "releases/" => add_dir(releases::releases_dir(), &|name| !name.ends_with(".tmp"))?,
+        "locks/" => add_dir(locks_dir(), &is_manifest_name)?,
+        "refs/tags/" => {
+            for (name, hash) in refs::list_tags()? {
+                objects.push(ObjectInfo {
+                    key: format!("refs/tags/{}", name),
+                    size: hash.len() as u64 + 1,
+                });
+            }
+        }
+        _ => {}
+    }
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(objects)
+}
+
+/// Compares digests of the two tokens byte for byte without stopping at the first
+/// difference, so response times don't reveal how much of a guess was right
+fn tokens_match(given: &str, token: &str) -> bool {
+    let (given, token) = (Sha256::digest(given), Sha256::digest(token));
+    let difference = given
+        .iter()
+        .zip(token.iter())
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b));
+    std::hint::black_box(difference) == 0
+}
+
+/// Writes `path` unless it exists, returning whether it was written. The content is
+/// complete before the file appears, and of two racing writers only one succeeds.
+fn create_file(path: &std::path::Path, data: &[u8]) -> std::io::Result<bool> {
+    let tmp_path = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+    std::fs::write(&tmp_path, data)?;
+    let linked = std::fs::hard_link(&tmp_path, path);
+    let _ = std::fs::remove_file(&tmp_path);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn empty() -> Body {
+    BodyExt::boxed(Full::new(Bytes::new()).map_err(|never| match never {}))
+}
+
+/// Parses a single `bytes=<first>-<last>` range; anything else is served whole
+fn parse_range(header: &str) -> Option<Range<u64>> {
+    let (first, last) = header.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = first.parse().ok()?;
+    let end = match last {
+        "" => u64::MAX,
+        last => last.parse::<u64>().ok()?.checked_add(1)?,
+    };
+    (start < end).then_some(start..end)
+}
+
+fn clamp_range(range: Option<Range<u64>>, size: u64) -> Option<Range<u64>> {
+    range
+        .filter(|range| range.start < size)
+        .map(|range| range.start..range.end.min(size))
+}
+
+fn partial(mut response: Response<Body>, range: &Range<u64>, size: u64) -> Response<Body> {
+    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    let content_range = format!("bytes {}-{}/{}", range.start, range.end - 1, size);
+    if let Ok(value) = content_range.parse() {
+        response.headers_mut().insert(CONTENT_RANGE, value);
+    }
+    response
+}
+
+fn bytes(size: u64, head: bool, data: Vec<u8>) -> Response<Body> {
+    let body = if head {
+        empty()
+    } else {
+        BodyExt::boxed(Full::new(Bytes::from(data)).map_err(|never| match never {}))
+    };
+    let mut response = Response::new(body);
+    response.headers_mut().insert(CONTENT_LENGTH, size.into());
+    response
+}
+
+fn status(code: StatusCode, message: &str) -> Response<Body> {
+    let mut response = bytes(message.len() as u64, false, message.as_bytes().to_vec());
+    *response.status_mut() = code;
+    response
+}
@@ -0,0 +1,147 @@
+//! Sparse models: `.vekt/sparse` lists the tensors this repository wants, and `pull`
+//! and `restore` never fetch or assemble any other. Consumers that only need the
+//! embeddings or the head of a 400GB model then only ever store those.
+//!
+//! One pattern per line, with `*` and `?` wildcards. A line starting with `!` drops
+//! tensors an earlier line kept, and the last line a tensor matches decides. `#`
+//! starts a comment. With only `!` lines, every tensor they don't drop is wanted.
+//! Attachments and the other small files a manifest references are always fetched.
+//! Library restores stay whole; they leave tensors out only through
+//! [`TensorFilter::sparse`](crate::tensor_filter::TensorFilter::sparse).
+use crate::errors::{Result, VektError};
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use crate::watch::matches_pattern;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Where the spec lives (.vekt/sparse)
+pub fn sparse_path() -> PathBuf {
+    get_vekt_dir().join("sparse")
+}
+
+/// The tensors a sparse repository wants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseSpec {
+    /// Patterns in order, `false` for `!` lines
+    rules: Vec<(bool, String)>,
+}
+
+impl SparseSpec {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keep, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (false, pattern.trim()),
+                None => (true, line),
+            };
+            if pattern.is_empty() {
+                return Err(VektError::InvalidPattern(format!(
+                    "'{}' in {}",
+                    line,
+                    sparse_path().display()
+                )));
+            }
+            rules.push((keep, pattern.to_string()));
+        }
+        Ok(SparseSpec { rules })
+    }
+
+    /// The repository's spec, `None` when it isn't sparse
+    pub fn load() -> Result<Option<Self>> {
+        match fs::read_to_string(sparse_path()) {
+            Ok(text) => Ok(Some(Self::parse(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Makes the repository sparse with `patterns`, one rule each
+    pub fn save(patterns: &[String]) -> Result<Self> {
+        let text: String = patterns.iter().map(|p| format!("{}\n", p)).collect();
+        let spec = Self::parse(&text)?;
+        write_file_atomic(&sparse_path(), text.as_bytes())?;
+        Ok(spec)
+    }
+
+    /// Makes the repository whole again. Returns whether it was sparse.
+    pub fn remove() -> Result<bool> {
+        match fs::remove_file(sparse_path()) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The patterns as written, `!` included
+    pub fn patterns(&self) -> impl Iterator<Item = String> + '_ {
+        self.rules.iter().map(|(keep, pattern)| {
+            if *keep {
+                pattern.clone()
+            } else {
+                format!("!{}", pattern)
+            }
+        })
+    }
+
+    /// Whether tensor `name` is wanted
+    pub fn matches(&self, name: &str) -> bool {
+        let default = self.rules.iter().all(|(keep, _)| !keep);
+        self.rules
+            .iter()
+            .rev()
+            .find(|(_, pattern)| matches_pattern(pattern, name))
+            .map_or(default, |(keep, _)| *keep)
+    }
+
+    /// Blobs of `manifest` a repository with this spec stores: those of wanted
+    /// tensors, and everything that isn't a tensor
+    pub fn blobs<'a>(&self, manifest: &'a VektManifest) -> BTreeSet<&'a String> {
+        let tensors: BTreeSet<&String> = manifest.tensors.values().map(|t| &t.hash).collect();
+        let wanted = manifest
+            .tensors
+            .iter()
+            .filter(|(name, _)| self.matches(name))
+            .map(|(_, t)| &t.hash);
+        manifest
+            .referenced_hashes()
+            .filter(|hash| !tensors.contains(hash))
+            .chain(wanted)
+            .collect()
+    }
+}
+
+/// Blobs of `manifest` this repository stores: all of them, unless it is sparse
+pub fn wanted_blobs(manifest: &VektManifest) -> Result<BTreeSet<&String>> {
+    Ok(match SparseSpec::load()? {
+        Some(spec) => spec.blobs(manifest),
+        None => manifest.referenced_hashes().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_spec_matching() {
+        let spec = SparseSpec::parse(
+            "# embeddings and the head only\n*embed_tokens*\nlm_head.*\n!*.bias\n",
+        )
+        .unwrap();
+        assert!(spec.matches("model.embed_tokens.weight"));
+        assert!(spec.matches("lm_head.weight"));
+        assert!(!spec.matches("lm_head.bias"));
+        assert!(!spec.matches("model.layers.0.mlp.up_proj.weight"));
+
+        let excluding = SparseSpec::parse("!model.layers.*").unwrap();
+        assert!(excluding.matches("lm_head.weight"));
+        assert!(!excluding.matches("model.layers.3.attn.weight"));
+
+        assert!(SparseSpec::parse("!\n").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(feature = "native")]
+use crate::blobs;
+#[cfg(feature = "native")]
+use crate::errors::Result;
+use crate::numeric::{bf16_to_f32, f16_to_f32, is_float_dtype};
+#[cfg(feature = "native")]
+use crate::storage::VektManifest;
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "native")]
+use std::io::Read;
+#[cfg(feature = "native")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Summary of a float tensor's values. `min`, `max`, `mean` and `norm` (L2) only
+/// cover finite values and are 0 when there are none; NaN and infinite values are
+/// counted instead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TensorStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub norm: f64,
+    pub nan_count: u64,
+    pub inf_count: u64,
+    pub zero_count: u64,
+}
+
+impl TensorStats {
+    /// Whether any value is NaN or infinite
+    pub fn has_non_finite(&self) -> bool {
+        self.nan_count > 0 || self.inf_count > 0
+    }
+}
+
+/// Running sums over part of a tensor, merged across chunks
+#[derive(Clone, Copy)]
+struct Accumulator {
+    finite: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    sum_squares: f64,
+    nan: u64,
+    inf: u64,
+    zero: u64,
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Accumulator {
+            finite: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            sum_squares: 0.0,
+            nan: 0,
+            inf: 0,
+            zero: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        if value.is_nan() {
+            self.nan += 1;
+        } else if value.is_infinite() {
+            self.inf += 1;
+        } else {
+            self.finite += 1;
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+            self.sum += value;
+            self.sum_squares += value * value;
+            if value == 0.0 {
+                self.zero += 1;
+            }
+        }
+    }
+
+    #[cfg(feature = "native")]
+    fn merge(mut self, other: Self) -> Self {
+        self.finite += other.finite;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.sum_squares += other.sum_squares;
+        self.nan += other.nan;
+        self.inf += other.inf;
+        self.zero += other.zero;
+        self
+    }
+
+    fn finish(self) -> TensorStats {
+        let finite = self.finite > 0;
+        TensorStats {
+            min: if finite { self.min } else { 0.0 },
+            max: if finite { self.max } else { 0.0 },
+            mean: if finite {
+                self.sum / self.finite as f64
+            } else {
+                0.0
+            },
+            norm: self.sum_squares.sqrt(),
+            nan_count: self.nan,
+            inf_count: self.inf,
+            zero_count: self.zero,
+        }
+    }
+}
+
+/// Chunks are a multiple of every float width, so no value straddles two
+#[cfg(feature = "native")]
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Folds the values of little-endian `dtype` bytes into an accumulator
+fn summarize(chunk: &[u8], dtype: &str) -> Accumulator {
+    let width = match dtype {
+        "F64" => 8,
+        "F32" => 4,
+        _ => 2,
+    };
+    let decode = |b: &[u8]| match dtype {
+        "F64" => f64::from_le_bytes(b.try_into().unwrap()),
+        "F32" => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+        "F16" => f16_to_f32(u16::from_le_bytes([b[0], b[1]])) as f64,
+        _ => bf16_to_f32(u16::from_le_bytes([b[0], b[1]])) as f64,
+    };
+    let mut accumulator = Accumulator::new();
+    for value in chunk.chunks_exact(width) {
+        accumulator.push(decode(value));
+    }
+    accumulator
+}
+
+/// Statistics of little-endian tensor bytes of `dtype`, or `None` for dtypes that
+/// aren't floats
+pub fn compute_stats(data: &[u8], dtype: &str) -> Option<TensorStats> {
+    if !is_float_dtype(dtype) {
+        return None;
+    }
+    #[cfg(feature = "native")]
+    let accumulator = data
+        .par_chunks(CHUNK_SIZE)
+        .map(|chunk| summarize(chunk, dtype))
+        .reduce(Accumulator::new, Accumulator::merge);
+    #[cfg(not(feature = "native"))]
+    let accumulator = summarize(data, dtype);
+    Some(accumulator.finish())
+}
+
+/// [`compute_stats`] over data read from `reader` a chunk at a time, so the tensor
+/// never has to fit in memory
+#[cfg(feature = "native")]
+pub fn compute_stats_from_reader<R: Read>(
+    mut reader: R,
+    dtype: &str,
+) -> std::io::Result<Option<TensorStats>> {
+    if !is_float_dtype(dtype) {
+        return Ok(None);
+    }
+    let mut accumulator = Accumulator::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        // Fill the whole buffer so a value never spans two reads
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        accumulator = accumulator.merge(summarize(&buffer[..filled], dtype));
+        if filled < buffer.len() {
+            break;
+        }
+    }
+    Ok(Some(accumulator.finish()))
+}
+
+#[cfg(feature = "native")]
+impl VektManifest {
+    /// Records statistics for every float tensor, streaming their blobs from the
+    /// store, several tensors at a time. Tensors whose blob is unchanged from
+    /// `previous` keep the statistics recorded there. Returns how many tensors were
+    /// decoded.
+    pub fn compute_stats(&mut self, previous: Option<&VektManifest>) -> Result<usize> {
+        let decoded = AtomicUsize::new(0);
+        self.tensors
+            .par_iter_mut()
+            .filter(|(_, tensor)| is_float_dtype(&tensor.dtype))
+            .try_for_each(|(name, tensor)| -> Result<()> {
+                let recorded = previous
+                    .and_then(|previous| previous.tensors.get(name))
+                    .filter(|old| old.hash == tensor.hash)
+                    .and_then(|old| old.stats);
+                tensor.stats = match recorded {
+                    Some(stats) => Some(stats),
+                    None => {
+                        decoded.fetch_add(1, Ordering::Relaxed);
+                        compute_stats_from_reader(blobs::open_blob(&tensor.hash)?, &tensor.dtype)?
+                    }
+                };
+                Ok(())
+            })?;
+        Ok(decoded.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeric::encode_floats;
+
+    #[test]
+    fn test_stats_count_non_finite_separately() {
+        let data = encode_floats(&[3.0, -4.0, 0.0, f64::NAN, f64::INFINITY], "F32").unwrap();
+        let stats = compute_stats(&data, "F32").unwrap();
+        assert_eq!(stats.min, -4.0);
+        assert_eq!(stats.max, 3.0);
+        assert!((stats.mean + 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(stats.norm, 5.0);
+        assert_eq!(
+            (stats.nan_count, stats.inf_count, stats.zero_count),
+            (1, 1, 1)
+        );
+        assert!(stats.has_non_finite());
+        assert!(compute_stats(&[0u8; 4], "I32").is_none());
+
+        // Streamed across reads of odd sizes, the result is the same
+        let large = encode_floats(
+            &(0..300_000).map(|i| i as f64 - 1000.5).collect::<Vec<_>>(),
+            "F32",
+        )
+        .unwrap();
+        let reader = std::io::Read::chain(&large[..1001], &large[1001..]);
+        assert_eq!(
+            compute_stats_from_reader(reader, "F32").unwrap(),
+            compute_stats(&large, "F32")
+        );
+    }
+}
@@ -1,13 +1,39 @@
-use crate::blobs;
+#[cfg(feature = "native")]
+use crate::cancel::{self, CancellationToken};
+#[cfg(feature = "native")]
+use crate::checks::CheckPolicy;
+#[cfg(feature = "native")]
+use crate::config::{self, ConfigScope};
+#[cfg(feature = "native")]
+use crate::encoding::{BlobEncoding, StorageRule};
 use crate::errors::{Result, VektError};
-use crate::utils::{ensure_vekt_dir, find_vekt_root, write_file_atomic};
-use crate::validation::{validate_tensor_name, verify_blob_hash};
+#[cfg(feature = "native")]
+use crate::hash::HashAlgorithm;
+use crate::layout::SourceLayout;
+use crate::ranges::RangeHashes;
+#[cfg(feature = "native")]
+use crate::remote::{DEFAULT_CONCURRENCY, RemoteCredentials};
+#[cfg(feature = "native")]
+use crate::sparse::SparseSpec;
+use crate::stats::TensorStats;
+#[cfg(feature = "native")]
+use crate::tensor_filter::TensorFilter;
+use crate::utils::write_file_atomic;
+#[cfg(feature = "native")]
+use crate::utils::{ensure_vekt_dir, find_vekt_root, parse_size};
+#[cfg(feature = "native")]
+use crate::validation::{VerifyPolicy, validate_tensor_name};
+#[cfg(feature = "native")]
+use crate::writer::SafetensorWriter;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
+#[cfg(feature = "native")]
 use std::io::Write;
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::path::PathBuf;
 
 // Metadata for a single tensor in raw format in safetensor file
 #[derive(Serialize, Deserialize, Debug)]
@@ -22,7 +48,7 @@ pub struct RawTensorMetaData {
 // Header for safetensor file in raw format
 pub type RawHeader = IndexMap<String, RawTensorMetaData>;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ManifestTensor {
     pub shape: Vec<usize>,
     pub dtype: String,
@@ -30,11 +56,28 @@ pub struct ManifestTensor {
     // Fix Issue #4: Preserve physical layout order
     pub index: usize,
 
-    #[serde(default)]
+    // Per-range hashes of large tensors, for partial verification and repair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ranges: Option<RangeHashes>,
+
+    // Summary statistics of the values, recorded by `add --stats`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<TensorStats>,
+
+    // Other fields of the tensor's header entry, inline as in the safetensors header
+    #[serde(flatten)]
     pub extra: IndexMap<String, serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ManifestTensor {
+    /// Size of the tensor data in bytes, derived from shape and dtype
+    pub fn byte_size(&self) -> Result<usize> {
+        crate::utils::tensor_byte_size(&self.shape, &self.dtype)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "StoredManifest")]
 pub struct VektManifest {
     // Fix Issue #1: Deterministic serialization for Git diffs
     pub tensors: BTreeMap<String, ManifestTensor>,
@@ -42,23 +85,165 @@ pub struct VektManifest {
 
     // Total size of all tensors in bytes
     pub total_size: usize,
+
+    // Hash and size of the file the manifest was made from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<FileChecksum>,
+
+    // Auxiliary files (tokenizer, configs, license) keyed by relative file name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attachments: BTreeMap<String, ManifestAttachment>,
+
+    // Free-form annotations such as the training step a snapshot was taken at
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+
+    // How to rebuild the original files of non-safetensors models (e.g. ONNX)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<SourceLayout>,
+
+    // Running offset of the builder methods, never saved
+    #[serde(skip)]
+    pub placement: Placement,
+}
+
+/// How the file a restore writes was last laid out by
+/// `VektManifest::recompute_total_size`, so appending a tensor continues from the
+/// running offset instead of laying out every tensor again. Only trusted while the
+/// manifest still has the tensor count and size it was taken at; clones start
+/// without one.
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub struct Placement {
+    pub(crate) tensors: usize,
+    pub(crate) total_size: usize,
+    /// Length of the header's entries and the commas between them
+    pub(crate) header_len: usize,
+    pub(crate) data_end: usize,
+    /// Hash -> offsets of the copy already laid out
+    pub(crate) placed: HashMap<String, (usize, usize)>,
+}
+
+impl Clone for Placement {
+    fn clone(&self) -> Self {
+        Placement::default()
+    }
+}
+
+/// A manifest as written by any supported version; every deserialized manifest is
+/// passed through [`VektManifest::validate_and_migrate`]
+#[derive(Deserialize)]
+struct StoredManifest {
+    tensors: BTreeMap<String, ManifestTensor>,
+    version: String,
+    total_size: usize,
+    #[serde(default)]
+    checksum: Option<FileChecksum>,
+    #[serde(default)]
+    attachments: BTreeMap<String, ManifestAttachment>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    layout: Option<SourceLayout>,
+}
+
+impl TryFrom<StoredManifest> for VektManifest {
+    type Error = VektError;
+
+    fn try_from(stored: StoredManifest) -> Result<Self> {
+        VektManifest {
+            tensors: stored.tensors,
+            version: stored.version,
+            total_size: stored.total_size,
+            checksum: stored.checksum,
+            attachments: stored.attachments,
+            metadata: stored.metadata,
+            layout: stored.layout,
+            placement: Placement::default(),
+        }
+        .validate_and_migrate()
+    }
+}
+
+/// Blake3 hash and length of a whole file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileChecksum {
+    pub hash: String,
+    pub size: u64,
+    // Blob of the safetensors bytes outside tensor data, to rebuild the file exactly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skeleton: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestAttachment {
+    pub hash: String,
+    pub size: u64,
+}
+
+#[cfg(feature = "native")]
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct VektConfig {
+    #[serde(default)]
     pub remotes: HashMap<String, String>,
+
+    // Restore verification policy; `restore --verify` overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<VerifyPolicy>,
+
+    // How blobs of matching tensors are stored; the first matching rule wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub storage_rules: Vec<StorageRule>,
+
+    // Named sets of remotes that `push <group>` mirrors to, e.g.
+    // {"mirror": ["origin", "onprem"]}
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remote_groups: BTreeMap<String, Vec<String>>,
+
+    // Credentials of remotes that don't use the default AWS chain, by remote name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub credentials: HashMap<String, RemoteCredentials>,
+
+    // Largest the blob store may grow, e.g. "50GiB"; add and pull evict
+    // least-recently-used blobs beyond it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_store_size: Option<String>,
+
+    // Write blobs and restored models with O_DIRECT on Linux, bypassing the page cache
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub direct_io: bool,
+
+    // Algorithm new blobs are hashed with; existing blobs keep theirs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<HashAlgorithm>,
+
+    // Encoding of tensors no storage rule matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<BlobEncoding>,
+
+    // Blobs transferred at once by push, pull and remote verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+
+    // Where blobs are kept instead of .vekt/blobs, relative to the repository root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
+
+    // What `add --check` does about NaN/Inf, all-zero and drifting tensors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checks: Option<CheckPolicy>,
 }
 
 impl VektManifest {
     /// Current manifest version
-    pub const CURRENT_VERSION: &'static str = "1.0";
+    pub const CURRENT_VERSION: &'static str = "2.0";
 
-    /// Validates and migrates manifest to current version if needed
+    /// Validates and migrates manifest to current version if needed. Runs on every
+    /// manifest that is deserialized, so older versions never reach callers.
     pub fn validate_and_migrate(self) -> Result<Self> {
         match self.version.as_str() {
-            "1.0" => Ok(self),
-            // Future versions would be handled here
-            // "2.0" => self.migrate_from_v2_to_current(),
+            "2.0" => Ok(self),
+            "1.0" => Ok(self.migrate_from_v1()),
             unknown => Err(VektError::InvalidManifest(format!(
                 "Unsupported manifest version '{}'. Current version is '{}'. Please update vekt.",
                 unknown,
@@ -67,6 +252,60 @@ impl VektManifest {
         }
     }
 
+    /// v1 nested a tensor's extra header fields under `extra`; v2 keeps them inline
+    fn migrate_from_v1(mut self) -> Self {
+        for tensor in self.tensors.values_mut() {
+            if let Some(serde_json::Value::Object(nested)) = tensor.extra.shift_remove("extra") {
+                tensor.extra.extend(nested);
+            }
+        }
+        self.version = Self::CURRENT_VERSION.to_string();
+        self
+    }
+
+    /// Loads a manifest from a `.vekt.json` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            VektError::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to open manifest {}: {}", path.display(), e),
+            ))
+        })?;
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| {
+            VektError::InvalidManifest(format!(
+                "Failed to parse manifest {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// The manifest as written to `.vekt.json` files and stored as an object: pretty
+    /// JSON with fields in declaration order, tensors and annotations sorted by name
+    /// and a trailing newline. Equal manifests always give identical bytes, so Git
+    /// diffs only show what changed.
+    pub fn to_json(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Atomically writes the manifest as [`to_json`](Self::to_json)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_file_atomic(path, self.to_json()?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Every blob hash this manifest depends on (tensors, attachments and skeletons)
+    pub fn referenced_hashes(&self) -> impl Iterator<Item = &String> {
+        self.tensors
+            .values()
+            .map(|t| &t.hash)
+            .chain(self.attachments.values().map(|a| &a.hash))
+            .chain(self.layout.iter().flat_map(|l| l.skeleton_hashes()))
+            .chain(self.checksum.iter().filter_map(|c| c.skeleton.as_ref()))
+    }
+
     pub fn print_summary(&self) {
         println!("vekt Manifest Summary:");
         println!("Version: {}", self.version);
@@ -84,162 +323,159 @@ impl VektManifest {
             );
         }
     }
+}
 
+#[cfg(feature = "native")]
+impl VektManifest {
+    /// Restores the weights, hashing every blob before it is written
     pub fn restore(&self, output_path: &std::path::Path, filter: Option<&str>) -> Result<()> {
-        // Validate all tensor names before processing to prevent path traversal
-        for name in self.tensors.keys() {
-            validate_tensor_name(name)?;
-        }
-
-        let file = File::create(output_path)?;
-        let mut writer = std::io::BufWriter::new(file);
+        self.restore_with_policy(output_path, filter, VerifyPolicy::Always)
+    }
 
-        // Filter tensors
-        let mut sorted_tensor_names: Vec<&String> = self
-            .tensors
-            .keys()
-            .filter(|name| {
-                if let Some(f) = filter {
-                    // Simple logic: keep if name contains any of the comma-separated terms
-                    f.split(',').any(|term| name.contains(term.trim()))
-                } else {
-                    true
-                }
-            })
-            .collect();
+    /// Restores the weights, verifying blobs according to `policy`
+    pub fn restore_with_policy(
+        &self,
+        output_path: &std::path::Path,
+        filter: Option<&str>,
+        policy: VerifyPolicy,
+    ) -> Result<()> {
+        self.restore_filtered(output_path, &TensorFilter::layers(filter), policy)
+    }
 
-        // Fix Issue #4: Sort by original index to ensure deterministic restoration
-        sorted_tensor_names.sort_by_key(|name| self.tensors[*name].index);
+    /// Restores the tensors `filter` keeps, verifying blobs according to `policy`
+    pub fn restore_filtered(
+        &self,
+        output_path: &std::path::Path,
+        filter: &TensorFilter,
+        policy: VerifyPolicy,
+    ) -> Result<()> {
+        self.restore_cancellable(output_path, filter, policy, &CancellationToken::new())
+    }
 
-        let mut header_map: RawHeader = IndexMap::new();
-        let mut current_offset = 0;
-
-        // Hash -> (start_offset, end_offset)
-        let mut written_hashes: HashMap<String, (usize, usize)> = HashMap::new();
-
-        // Pass 1: Build the Header (calculate offsets with alignment)
-        for name in &sorted_tensor_names {
-            let tensor = &self.tensors[*name];
-
-            // Shared Weights Deduplication
-            if let Some(&(start, end)) = written_hashes.get(&tensor.hash) {
-                let meta = RawTensorMetaData {
-                    shape: tensor.shape.clone(),
-                    dtype: tensor.dtype.clone(),
-                    data_offsets: (start, end),
-                    extra: tensor.extra.clone(),
-                };
-                header_map.insert((*name).clone(), meta);
-                continue;
+    /// [`restore_filtered`](Self::restore_filtered) that stops once `cancel` is
+    /// cancelled, removing the partial output
+    pub fn restore_cancellable(
+        &self,
+        output_path: &std::path::Path,
+        filter: &TensorFilter,
+        policy: VerifyPolicy,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        cancel::check(cancel, "restore")?;
+        // Models ingested from other formats are rebuilt as their original files
+        if self.layout.is_some() {
+            if !filter.is_empty() {
+                return Err(VektError::InvalidManifest(
+                    "Partial restore is only supported for safetensors models".to_string(),
+                ));
             }
-
-            let padding = (8 - (current_offset % 8)) % 8;
-            current_offset += padding;
-
-            let size = tensor.shape.iter().product::<usize>()
-                * crate::utils::get_dtype_size(&tensor.dtype);
-            let start = current_offset;
-            let end = current_offset + size;
-
-            let meta = RawTensorMetaData {
-                shape: tensor.shape.clone(),
-                dtype: tensor.dtype.clone(),
-                data_offsets: (start, end),
-                extra: tensor.extra.clone(),
-            };
-            header_map.insert((*name).clone(), meta);
-
-            written_hashes.insert(tensor.hash.clone(), (start, end));
-            current_offset += size;
+            return self.restore_layout(output_path);
         }
 
-        let header_json = serde_json::to_string(&header_map)?;
-        let header_len = header_json.len() as u64;
-        let header_bytes = header_json.as_bytes();
-
-        writer.write_all(&header_len.to_le_bytes())?;
-        writer.write_all(header_bytes)?;
-
-        // Pass 2: Write Data (with alignment padding and deduplication)
-        written_hashes.clear(); // Reset to track what we have effectively written in this pass
-        let mut current_write_pos = 0;
-
-        for name in &sorted_tensor_names {
-            let tensor = &self.tensors[*name];
-
-            if written_hashes.contains_key(&tensor.hash) {
-                // Data already written for this hash
-                continue;
-            }
+        self.safetensor_writer(filter, policy)?
+            .with_cancel(cancel.clone())
+            .write(output_path)?;
+        Ok(())
+    }
 
-            // Add Padding
-            let padding = (8 - (current_write_pos % 8)) % 8;
-            if padding > 0 {
-                let zeros = vec![0u8; padding];
-                writer.write_all(&zeros)?;
-                current_write_pos += padding;
+    /// Streams the restored weights to `writer` instead of a file, e.g. stdout.
+    /// Models whose original format spans several files can't be streamed.
+    pub fn restore_to_writer<W: Write>(
+        &self,
+        writer: &mut W,
+        filter: &TensorFilter,
+        policy: VerifyPolicy,
+    ) -> Result<()> {
+        if let Some(layout) = &self.layout {
+            if !filter.is_empty() {
+                return Err(VektError::InvalidManifest(
+                    "Partial restore is only supported for safetensors models".to_string(),
+                ));
             }
-
-            // Use centralized blob path resolution
-            let blob_path = blobs::get_blob_path(&tensor.hash);
-            if !blob_path.exists() {
-                return Err(VektError::BlobNotFound(format!(
-                    "Blob {} not found for tensor '{}'",
-                    tensor.hash, name
+            if !layout.external.is_empty() {
+                return Err(VektError::InvalidManifest(format!(
+                    "This {} model is stored in {} files and can only be restored to disk",
+                    layout.format,
+                    layout.external.len() + 1
                 )));
             }
+            return layout.main.write_to(self, writer);
+        }
+        self.safetensor_writer(filter, policy)?.write_to(writer)?;
+        Ok(())
+    }
 
-            // CRITICAL: Verify blob hash to detect corruption
-            let blob_data = std::fs::read(&blob_path).map_err(|e| {
-                VektError::Io(std::io::Error::other(format!(
-                    "Failed to read blob {}: {}",
-                    tensor.hash, e
-                )))
-            })?;
-
-            verify_blob_hash(&blob_data, &tensor.hash)?;
+    /// How many of this model's tensors the repository's [sparse](crate::sparse) spec
+    /// doesn't want
+    pub fn sparse_excluded(&self) -> Result<usize> {
+        Ok(SparseSpec::load()?.map_or(0, |spec| {
+            self.tensors
+                .keys()
+                .filter(|name| !spec.matches(name))
+                .count()
+        }))
+    }
 
-            // Write verified blob data
-            writer.write_all(&blob_data)?;
-            current_write_pos += blob_data.len();
-            written_hashes.insert(tensor.hash.clone(), (0, 0)); // Value irrelevant, just marking as written
+    /// Writer for the tensors `filter` keeps, in their original order
+    pub(crate) fn safetensor_writer(
+        &self,
+        filter: &TensorFilter,
+        policy: VerifyPolicy,
+    ) -> Result<SafetensorWriter> {
+        // Validate all tensor names before processing to prevent path traversal
+        for name in self.tensors.keys() {
+            validate_tensor_name(name)?;
         }
 
-        writer.flush()?;
+        // Filter tensors
+        let mut sorted_tensor_names: Vec<&String> = self
+            .tensors
+            .keys()
+            .filter(|name| filter.matches(name, self.tensors[*name].index))
+            .collect();
+
+        // Fix Issue #4: Sort by original index to ensure deterministic restoration
+        sorted_tensor_names.sort_by_key(|name| self.tensors[*name].index);
 
-        Ok(())
+        // The writer shares one copy of data between tensors with the same hash
+        let mut writer = SafetensorWriter::new().with_verify_policy(policy);
+        for name in sorted_tensor_names {
+            writer.add_manifest_tensor(name, &self.tensors[name])?;
+        }
+        Ok(writer)
     }
 }
 
+#[cfg(feature = "native")]
 impl VektConfig {
+    /// The effective config: user, repository and environment layers merged as
+    /// [`config`](crate::config) describes
     pub fn load() -> Result<Self> {
-        let root = find_vekt_root().ok_or(VektError::RepoNotFound)?;
-        let path = root.join(".vekt").join("config.json");
-        if !path.exists() {
-            return Ok(VektConfig::default());
-        }
-        let file = File::open(&path).map_err(|e| {
-            VektError::Io(std::io::Error::other(format!(
-                "Failed to open config file at {}: {}",
-                path.display(),
-                e
-            )))
-        })?;
-        let reader = std::io::BufReader::new(file);
-        let config = serde_json::from_reader(reader).map_err(|e| {
-            VektError::InvalidManifest(format!(
+        find_vekt_root().ok_or(VektError::RepoNotFound)?;
+        serde_json::from_value(serde_json::Value::Object(config::merged()?))
+            .map_err(|e| VektError::InvalidManifest(format!("Failed to parse config: {}", e)))
+    }
+
+    /// Only what `.vekt/config.json` holds, for changing it with [`save`](Self::save)
+    /// without copying user settings or environment overrides into it
+    pub fn load_repo() -> Result<Self> {
+        let path = config::repo_config_path()?;
+        let layer = config::read_layer(ConfigScope::Repo)?;
+        serde_json::from_value(serde_json::Value::Object(layer)).map_err(|e| {
+            VektError::InvalidConfig(format!(
                 "Failed to parse config file at {}: {}",
                 path.display(),
                 e
             ))
-        })?;
-        Ok(config)
+        })
     }
 
+    /// Writes the config to `.vekt/config.json`
     pub fn save(&self) -> Result<()> {
-        let dir = std::env::current_dir()?.join(".vekt");
-        ensure_vekt_dir(&dir)?;
-        let config_path = dir.join("config.json");
+        let config_path = config::repo_config_path()?;
+        if let Some(dir) = config_path.parent() {
+            ensure_vekt_dir(dir)?;
+        }
         let json = serde_json::to_string_pretty(self)?;
         write_file_atomic(&config_path, json.as_bytes()).map_err(|e| {
             VektError::Io(std::io::Error::other(format!(
@@ -250,7 +486,80 @@ impl VektConfig {
         Ok(())
     }
 
+    /// `max_store_size` in bytes, if set
+    pub fn max_store_size(&self) -> Result<Option<u64>> {
+        self.max_store_size.as_deref().map(parse_size).transpose()
+    }
+
+    /// Sorted names of configured remotes
+    pub fn remote_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.remotes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// `storage_rules`, followed by a rule for every other tensor when `compression`
+    /// is set
+    pub fn storage_rules(&self) -> Vec<StorageRule> {
+        let mut rules = self.storage_rules.clone();
+        if let Some(encoding) = self.compression {
+            rules.push(StorageRule {
+                tensors: None,
+                dtype: None,
+                encoding,
+            });
+        }
+        rules
+    }
+
+    /// Blobs transferred at once; [`DEFAULT_CONCURRENCY`] unless configured
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1)
+    }
+
+    /// The configured blob store location, resolved against the repository root
+    pub fn store_path(&self) -> Option<PathBuf> {
+        let path = self.store_path.as_ref()?;
+        Some(match find_vekt_root() {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path.clone(),
+        })
+    }
+
+    /// The remotes `name` stands for: itself when it is a remote, the members of the
+    /// remote group of that name otherwise
+    pub fn resolve_remotes(&self, name: &str) -> Result<Vec<String>> {
+        if self.remotes.contains_key(name) {
+            return Ok(vec![name.to_string()]);
+        }
+        let group = self
+            .remote_groups
+            .get(name)
+            .ok_or_else(|| VektError::RemoteNotFound(name.to_string()))?;
+        if group.is_empty() {
+            return Err(VektError::InvalidConfig(format!(
+                "Remote group '{}' has no remotes",
+                name
+            )));
+        }
+        for member in group {
+            if !self.remotes.contains_key(member) {
+                return Err(VektError::RemoteNotFound(format!(
+                    "{} (in remote group '{}')",
+                    member, name
+                )));
+            }
+        }
+        Ok(group.clone())
+    }
+
     pub fn add_remote(&mut self, name: String, url: String) {
         self.remotes.insert(name, url);
     }
+
+    /// Credentials configured for the remote called `name`; the default AWS chain
+    /// when there are none
+    pub fn remote_credentials(&self, name: &str) -> RemoteCredentials {
+        self.credentials.get(name).cloned().unwrap_or_default()
+    }
 }
@@ -0,0 +1,150 @@
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::hub::StreamHeader;
+use crate::ranges::{RangeHasher, RangeHashes};
+use crate::storage::{FileChecksum, ManifestTensor, VektManifest};
+use crate::transaction;
+use crate::utils::get_store_path;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+/// Ingests a safetensors file read front to back from `reader`, e.g. stdin, one
+/// tensor at a time, so the file never has to exist on disk. Calls `on_blob` with
+/// each tensor's hash as soon as its blob is in the store.
+pub fn ingest_reader<R: Read>(reader: R, on_blob: &dyn Fn(&str)) -> Result<VektManifest> {
+    let mut reader = HashingReader {
+        inner: reader,
+        hasher: blake3::Hasher::new(),
+        read: 0,
+    };
+    let mut len = [0u8; 8];
+    read_exact(&mut reader, &mut len, "header length")?;
+    let len = u64::from_le_bytes(len);
+    let mut json = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut json)?;
+    if json.len() as u64 != len {
+        return Err(truncated("header"));
+    }
+    let json = String::from_utf8(json)
+        .map_err(|e| VektError::InvalidSafetensor(format!("Header is not valid UTF-8: {}", e)))?;
+    let header = StreamHeader::parse(&json)?;
+    // Everything but tensor data, as safetensors_checksum keeps it
+    let mut skeleton = [len.to_le_bytes().as_slice(), json.as_bytes()].concat();
+
+    let mut tensors = BTreeMap::new();
+    let mut pos = 0u64;
+    for (index, name, meta) in header.tensors.iter() {
+        let (begin, end) = meta.data_offsets;
+        // Bytes between tensors (alignment padding) aren't part of any tensor
+        read_gap(&mut reader, begin as u64 - pos, name, &mut skeleton)?;
+        let (hash, ranges) = store_tensor(&mut reader, (end - begin) as u64, name)?;
+        on_blob(&hash);
+        pos = end as u64;
+        tensors.insert(
+            name.clone(),
+            ManifestTensor {
+                shape: meta.shape.clone(),
+                dtype: meta.dtype.clone(),
+                hash,
+                index: *index,
+                extra: meta.extra.clone(),
+                ranges,
+                stats: None,
+            },
+        );
+    }
+
+    reader.read_to_end(&mut skeleton)?;
+
+    Ok(VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: header.total_size() as usize,
+        checksum: Some(FileChecksum {
+            hash: reader.hasher.finalize().to_hex().to_string(),
+            size: reader.read,
+            skeleton: Some(blobs::save_blob_deduplicated(&skeleton)?.0),
+        }),
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    })
+}
+
+/// Copies the next `len` bytes of `reader` into the blob store, hashing as they arrive
+fn store_tensor<R: Read>(
+    reader: &mut R,
+    len: u64,
+    name: &str,
+) -> Result<(String, Option<RangeHashes>)> {
+    let store = get_store_path();
+    fs::create_dir_all(&store)?;
+    let tmp_path = store.join(format!("stdin-{:016x}.tmp", rand::random::<u64>()));
+    transaction::record(&tmp_path);
+
+    let copied = (|| -> Result<(String, Option<RangeHashes>)> {
+        let mut file = File::create(&tmp_path)?;
+        let mut hasher = RangeHasher::new(len);
+        let mut buffer = vec![0u8; (1 << 20).min(len as usize)];
+        let mut remaining = len;
+        while remaining > 0 {
+            let want = remaining.min(buffer.len() as u64) as usize;
+            let n = reader.read(&mut buffer[..want])?;
+            if n == 0 {
+                return Err(truncated(&format!("tensor '{}'", name)));
+            }
+            hasher.update(&buffer[..n]);
+            file.write_all(&buffer[..n])?;
+            remaining -= n as u64;
+        }
+        file.sync_all()?;
+        Ok(hasher.finalize())
+    })();
+    let (hash, ranges) = match copied {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+
+    blobs::finalize_blob(&tmp_path, &hash)?;
+    Ok((hash, ranges))
+}
+
+fn read_gap<R: Read>(reader: &mut R, len: u64, name: &str, skeleton: &mut Vec<u8>) -> Result<()> {
+    let read = reader.by_ref().take(len).read_to_end(skeleton)?;
+    if read as u64 != len {
+        return Err(truncated(&format!("tensor '{}'", name)));
+    }
+    Ok(())
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8], what: &str) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(what),
+        _ => VektError::Io(e),
+    })
+}
+
+fn truncated(what: &str) -> VektError {
+    VektError::InvalidSafetensor(format!("Input ended before the {} did", what))
+}
+
+/// Hashes and counts everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+    read: u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.read += n as u64;
+        Ok(n)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::SafetensorFile;
+use crate::blobs;
+use crate::errors::{Result, VektError};
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::validation::verify_blob_hash;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+/// Name of the symlink inference servers read weights through
+pub const CURRENT_LINK: &str = "current";
+
+/// File names used inside each version directory
+pub const WEIGHTS_FILE: &str = "model.safetensors";
+pub const MANIFEST_FILE: &str = "model.vekt.json";
+
+/// What a swap did, passed to the event hook and printed by `vekt swap --json`
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapEvent {
+    /// `current` now points at the new version
+    Swapped,
+    /// The manifest was already live; nothing changed
+    Unchanged,
+    /// Verification failed and `current` was left on the previous version
+    RolledBack,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SwapResult {
+    pub event: SwapEvent,
+    pub target: PathBuf,
+    /// Version directory name, the short manifest hash
+    pub version: String,
+    /// Version `current` pointed at before the swap
+    pub previous: Option<String>,
+    /// Verification error that caused a rollback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Version directory name for a manifest: its short content hash, so swapping the
+/// same manifest twice lands in the same directory
+pub fn version_name(manifest: &VektManifest) -> Result<String> {
+    let hash = blobs::compute_object_hash(&refs::manifest_object_bytes(manifest)?);
+    Ok(hash[..12].to_string())
+}
+
+/// Version directory `current` points at, if the link exists
+pub fn current_version(target: &Path) -> Option<String> {
+    let link = fs::read_link(target.join(CURRENT_LINK)).ok()?;
+    link.file_name().map(|n| n.to_string_lossy().into_owned())
+}
+
+/// Re-hashes every tensor of a restored file, each with the algorithm its manifest
+/// hash names, and compares against the manifest
+fn verify_restored(path: &Path, manifest: &VektManifest) -> Result<()> {
+    let restored = SafetensorFile::open(&path.to_string_lossy())?;
+    let data_start = restored.header_len + 8;
+    for (name, tensor) in &manifest.tensors {
+        let Some(meta) = restored.header.get(name) else {
+            return Err(VektError::TensorCorruption(format!(
+                "Tensor '{}' missing from restored weights",
+                name
+            )));
+        };
+        let (start, end) = meta.data_offsets;
+        let bytes = restored
+            .mmap
+            .get(data_start + start..data_start + end)
+            .ok_or_else(|| {
+                VektError::TensorCorruption(format!(
+                    "Tensor '{}' runs past the end of the restored weights",
+                    name
+                ))
+            })?;
+        verify_blob_hash(bytes, &tensor.hash)?;
+    }
+    Ok(())
+}
+
+/// Restores the manifest, its attachments and a copy of the manifest into `dir`
+fn restore_version(manifest: &VektManifest, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let weights = dir.join(WEIGHTS_FILE);
+    manifest.restore(&weights, None)?;
+    manifest.restore_attachments(dir)?;
+    manifest.save(&dir.join(MANIFEST_FILE))?;
+    verify_restored(&weights, manifest)
+}
+
+#[cfg(unix)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(original, link)
+}
+
+/// Points `target/current` at `version` by renaming a fresh link over the old one,
+/// so readers always see either the old or the new directory
+fn point_current(target: &Path, version: &str) -> Result<()> {
+    let staging = target.join(format!(".{}.tmp", CURRENT_LINK));
+    let _ = fs::remove_file(&staging);
+    // Relative link so the target directory can be moved or mounted elsewhere
+    symlink_dir(Path::new(version), &staging)?;
+    fs::rename(&staging, target.join(CURRENT_LINK))?;
+    Ok(())
+}
+
+/// Runs the event hook through the shell with the swap described in `VEKT_SWAP_*`
+/// environment variables. A failing hook is reported but does not undo the swap.
+pub fn run_hook(hook: &str, result: &SwapResult) -> Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(hook);
+        c
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(hook);
+        c
+    };
+    let event = serde_json::to_value(result.event)?;
+    let status = command
+        .env("VEKT_SWAP_EVENT", event.as_str().unwrap_or_default())
+        .env("VEKT_SWAP_TARGET", &result.target)
+        .env("VEKT_SWAP_VERSION", &result.version)
+        .env(
+            "VEKT_SWAP_PREVIOUS",
+            result.previous.as_deref().unwrap_or_default(),
+        )
+        .status()?;
+    if !status.success() {
+        warn!("swap hook exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Restores `manifest` into `target/<version>`, verifies it, then atomically switches
+/// the `target/current` symlink to it.
+///
+/// On verification failure the new directory is removed and `current` keeps pointing
+/// at the previous version; the result then carries [`SwapEvent::RolledBack`].
+pub fn swap(manifest: &VektManifest, target: &Path, hook: Option<&str>) -> Result<SwapResult> {
+    fs::create_dir_all(target)?;
+    let version = version_name(manifest)?;
+    let previous = current_version(target);
+    let mut result = SwapResult {
+        event: SwapEvent::Unchanged,
+        target: target.to_path_buf(),
+        version: version.clone(),
+        previous: previous.clone(),
+        error: None,
+    };
+    if previous.as_ref() == Some(&version) {
+        return Ok(result);
+    }
+
+    let version_dir = target.join(&version);
+    let staging = target.join(format!(".{}.tmp", version));
+    let _ = fs::remove_dir_all(&staging);
+
+    let outcome = restore_version(manifest, &staging).and_then(|_| {
+        // A leftover directory for this version is never live here, so replace it
+        if version_dir.exists() {
+            fs::remove_dir_all(&version_dir)?;
+        }
+        fs::rename(&staging, &version_dir)?;
+        point_current(target, &version)
+    });
+
+    match outcome {
+        Ok(()) => result.event = SwapEvent::Swapped,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            // Only drop the version directory if `current` never switched to it
+            if current_version(target).as_ref() != Some(&version) {
+                let _ = fs::remove_dir_all(&version_dir);
+            }
+            result.event = SwapEvent::RolledBack;
+            result.error = Some(e.to_string());
+        }
+    }
+
+    if let Some(hook) = hook {
+        run_hook(hook, &result)?;
+    }
+    Ok(result)
+}
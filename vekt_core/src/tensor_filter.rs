@@ -0,0 +1,258 @@
+use crate::errors::{Result, VektError};
+use crate::sparse::SparseSpec;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+static LAYER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_layer_regex() -> &'static Regex {
+    // model.layers.N. (Llama, Mistral), transformer.h.N. (GPT-2), encoder.layer.N. (BERT),
+    // blocks.N. (ViT, Mamba)
+    LAYER_REGEX.get_or_init(|| {
+        Regex::new(r"(?:^|\.)(?:layers|layer|h|blocks|block)\.(\d+)(?:\.|$)").unwrap()
+    })
+}
+
+/// The transformer block a tensor belongs to, from names like `model.layers.12.mlp.weight`
+pub fn layer_number(name: &str) -> Option<usize> {
+    get_layer_regex().captures(name)?[1].parse().ok()
+}
+
+/// Parses comma-separated ranges such as `0..16`, `0..=15`, `24..` or `..8,30..32`
+pub fn parse_ranges(spec: &str) -> Result<Vec<Range<usize>>> {
+    let invalid = || {
+        VektError::InvalidPattern(format!(
+            "'{}' is not a range list like 0..16, 0..=15 or 24..",
+            spec
+        ))
+    };
+    let bound = |value: &str, default: usize| match value.trim() {
+        "" => Ok(default),
+        value => value.parse::<usize>().map_err(|_| invalid()),
+    };
+    spec.split(',')
+        .map(|part| {
+            let (start, end) = part.split_once("..").ok_or_else(invalid)?;
+            let start = bound(start, 0)?;
+            let end = match end.strip_prefix('=') {
+                Some(end) => bound(end, usize::MAX - 1)?
+                    .checked_add(1)
+                    .ok_or_else(invalid)?,
+                None => bound(end, usize::MAX)?,
+            };
+            if start >= end {
+                return Err(invalid());
+            }
+            Ok(start..end)
+        })
+        .collect()
+}
+
+/// Which tensors a partial restore keeps.
+///
+/// A tensor is kept when it matches any include (or there are none), and no exclude.
+/// Terms match anywhere in the name; patterns are regular expressions, anchored only
+/// if they say so. Layer ranges select by [`layer_number`], index ranges by the
+/// tensor's position in the original file.
+///
+/// ```
+/// use vekt_core::tensor_filter::TensorFilter;
+///
+/// // Attention layers, except layers 30-39
+/// let filter = TensorFilter::new()
+///     .include("attn")
+///     .exclude_regex(r"^layers\.3\d\.")?;
+/// assert!(filter.matches("layers.4.attn.q_proj.weight", 0));
+/// assert!(!filter.matches("layers.31.attn.q_proj.weight", 0));
+///
+/// // The first half of a 32-layer model, plus the embeddings
+/// let first_half = TensorFilter::new().layer_range(0..16).include("embed_tokens");
+/// assert!(first_half.matches("model.layers.15.mlp.up_proj.weight", 0));
+/// assert!(!first_half.matches("model.layers.16.mlp.up_proj.weight", 0));
+/// assert!(first_half.matches("model.embed_tokens.weight", 0));
+/// # Ok::<(), vekt_core::errors::VektError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TensorFilter {
+    include: Vec<String>,
+    include_regex: Vec<Regex>,
+    layer_ranges: Vec<Range<usize>>,
+    index_ranges: Vec<Range<usize>>,
+    exclude: Vec<String>,
+    exclude_regex: Vec<Regex>,
+    sparse: Option<SparseSpec>,
+}
+
+impl TensorFilter {
+    /// A filter that keeps every tensor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The filter `--layers` has always applied: keep tensors containing any of the
+    /// comma-separated `terms`, or every tensor for `None`
+    pub fn layers(terms: Option<&str>) -> Self {
+        match terms {
+            Some(terms) => Self::new().include(terms),
+            None => Self::new(),
+        }
+    }
+
+    /// Also keeps tensors containing any of the comma-separated `terms`
+    pub fn include(mut self, terms: &str) -> Self {
+        self.include.extend(split_terms(terms));
+        self
+    }
+
+    /// Also keeps tensors matching `pattern`
+    pub fn include_regex(mut self, pattern: &str) -> Result<Self> {
+        self.include_regex.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Also keeps tensors of the transformer blocks in `range`
+    pub fn layer_range(mut self, range: Range<usize>) -> Self {
+        self.layer_ranges.push(range);
+        self
+    }
+
+    /// Also keeps the tensors at positions `range` of the original file
+    pub fn index_range(mut self, range: Range<usize>) -> Self {
+        self.index_ranges.push(range);
+        self
+    }
+
+    /// Drops tensors containing any of the comma-separated `terms`
+    pub fn exclude(mut self, terms: &str) -> Self {
+        self.exclude.extend(split_terms(terms));
+        self
+    }
+
+    /// Drops tensors matching `pattern`
+    pub fn exclude_regex(mut self, pattern: &str) -> Result<Self> {
+        self.exclude_regex.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Drops tensors a [sparse](crate::sparse) repository with `spec` doesn't want
+    pub fn sparse(mut self, spec: SparseSpec) -> Self {
+        self.sparse = Some(spec);
+        self
+    }
+
+    /// True if the filter keeps every tensor
+    pub fn is_empty(&self) -> bool {
+        self.includes_all()
+            && self.exclude.is_empty()
+            && self.exclude_regex.is_empty()
+            && self.sparse.is_none()
+    }
+
+    fn includes_all(&self) -> bool {
+        self.include.is_empty()
+            && self.include_regex.is_empty()
+            && self.layer_ranges.is_empty()
+            && self.index_ranges.is_empty()
+    }
+
+    /// Whether the tensor `name`, at position `index` of the original file, is kept
+    pub fn matches(&self, name: &str, index: usize) -> bool {
+        let included = self.includes_all()
+            || self.include.iter().any(|term| name.contains(term.as_str()))
+            || self.include_regex.iter().any(|re| re.is_match(name))
+            || self.index_ranges.iter().any(|range| range.contains(&index))
+            || (!self.layer_ranges.is_empty()
+                && layer_number(name)
+                    .is_some_and(|layer| self.layer_ranges.iter().any(|r| r.contains(&layer))));
+        let excluded = self.exclude.iter().any(|term| name.contains(term.as_str()))
+            || self.exclude_regex.iter().any(|re| re.is_match(name))
+            || self.sparse.as_ref().is_some_and(|spec| !spec.matches(name));
+        included && !excluded
+    }
+}
+
+fn split_terms(terms: &str) -> impl Iterator<Item = String> + '_ {
+    terms
+        .split(',')
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+        .map(str::to_string)
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| VektError::InvalidPattern(format!("'{}': {}", pattern, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_filter() {
+        assert!(TensorFilter::layers(None).matches("anything", 0));
+        assert!(TensorFilter::layers(None).is_empty());
+
+        let layers = TensorFilter::layers(Some("encoder, lm_head"));
+        assert!(layers.matches("model.encoder.0.weight", 0));
+        assert!(layers.matches("lm_head.weight", 0));
+        assert!(!layers.matches("model.decoder.0.weight", 0));
+
+        // Excludes win over includes, and alone keep everything else
+        let filter = TensorFilter::new()
+            .include_regex(r"\.attn\.")
+            .unwrap()
+            .exclude("bias")
+            .exclude_regex(r"^layers\.(3\d)\.")
+            .unwrap();
+        assert!(filter.matches("layers.2.attn.q.weight", 0));
+        assert!(!filter.matches("layers.2.attn.q.bias", 0));
+        assert!(!filter.matches("layers.35.attn.q.weight", 0));
+        assert!(!filter.matches("layers.2.mlp.weight", 0));
+        assert!(TensorFilter::new().exclude("bias").matches("fc.weight", 0));
+
+        assert!(matches!(
+            TensorFilter::new().include_regex("layers.(3"),
+            Err(VektError::InvalidPattern(_))
+        ));
+
+        let indexed = TensorFilter::new().index_range(2..4);
+        assert!(indexed.matches("a", 3));
+        assert!(!indexed.matches("a", 4));
+    }
+
+    #[test]
+    fn test_layer_number() {
+        assert_eq!(
+            layer_number("model.layers.12.self_attn.q_proj.weight"),
+            Some(12)
+        );
+        assert_eq!(layer_number("transformer.h.3.attn.c_attn.weight"), Some(3));
+        assert_eq!(
+            layer_number("bert.encoder.layer.0.output.dense.bias"),
+            Some(0)
+        );
+        assert_eq!(layer_number("blocks.7.norm1.weight"), Some(7));
+        assert_eq!(layer_number("model.embed_tokens.weight"), None);
+        assert_eq!(layer_number("mlp.0.weight"), None);
+        assert_eq!(layer_number("model.layers_norm.1.weight"), None);
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse_ranges("0..16").unwrap(), vec![0..16]);
+        assert_eq!(
+            parse_ranges("0..=15, 30..").unwrap(),
+            [0..16, 30..usize::MAX]
+        );
+        assert_eq!(parse_ranges("..8").unwrap(), vec![0..8]);
+        for bad in ["16", "8..4", "a..b", "3..3"] {
+            assert!(parse_ranges(bad).is_err(), "{}", bad);
+        }
+        let max = format!("0..={}", usize::MAX);
+        assert!(matches!(
+            parse_ranges(&max),
+            Err(VektError::InvalidPattern(_))
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::ModelArchiver;
+use crate::checksum::bytes_checksum;
+use crate::errors::{Result, VektError};
+use crate::layout::{FileLayout, SourceLayout, TF_CHECKPOINT_FORMAT, map_file};
+use crate::protobuf::{Fields, Value, read_varint};
+use crate::storage::{ManifestTensor, VektManifest};
+use indexmap::IndexMap;
+use memmap2::Mmap;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// SSTable (LevelDB table) layout used for checkpoint index files
+const TABLE_MAGIC: u64 = 0xdb47_7524_8b80_fb57;
+const FOOTER_LEN: usize = 48;
+const BLOCK_TRAILER_LEN: usize = 5;
+const NO_COMPRESSION: u8 = 0;
+
+// Field numbers from tensor_bundle.proto and tensor_shape.proto
+const HEADER_NUM_SHARDS: u32 = 1;
+const ENTRY_DTYPE: u32 = 1;
+const ENTRY_SHAPE: u32 = 2;
+const ENTRY_SHARD_ID: u32 = 3;
+const ENTRY_OFFSET: u32 = 4;
+const ENTRY_SIZE: u32 = 5;
+const ENTRY_SLICES: u32 = 7;
+const SHAPE_DIM: u32 = 2;
+const DIM_SIZE: u32 = 1;
+
+/// Maps `tensorflow.DataType` to the dtype names used in manifests
+pub fn tf_dtype(dtype: u64) -> String {
+    let name = match dtype {
+        1 => "F32",
+        2 => "F64",
+        3 => "I32",
+        4 => "U8",
+        5 => "I16",
+        6 => "I8",
+        7 => "TF_STRING",
+        8 => "C64",
+        9 => "I64",
+        10 => "BOOL",
+        14 => "BF16",
+        17 => "U16",
+        19 => "F16",
+        22 => "U32",
+        23 => "U64",
+        other => return format!("TF_{}", other),
+    };
+    name.to_string()
+}
+
+fn malformed(reason: &str) -> VektError {
+    VektError::InvalidManifest(format!("Malformed TensorFlow checkpoint index: {}", reason))
+}
+
+/// Contents of the block in `data` that the handle (varint offset, varint size) at
+/// `handle[pos..]` points to
+fn read_block<'a>(data: &'a [u8], handle: &[u8], pos: &mut usize) -> Result<&'a [u8]> {
+    let offset = read_varint(handle, pos)? as usize;
+    let size = read_varint(handle, pos)? as usize;
+    let end = offset
+        .checked_add(size)
+        .filter(|end| end + BLOCK_TRAILER_LEN <= data.len())
+        .ok_or_else(|| malformed("block runs past the end of the file"))?;
+    if data[end] != NO_COMPRESSION {
+        return Err(malformed("compressed index blocks are not supported"));
+    }
+    Ok(&data[offset..end])
+}
+
+/// Decodes the prefix-compressed key/value entries of one block
+fn block_entries(block: &[u8]) -> Result<Vec<(Vec<u8>, &[u8])>> {
+    let count_pos = block
+        .len()
+        .checked_sub(4)
+        .ok_or_else(|| malformed("block too small"))?;
+    let restarts = u32::from_le_bytes(block[count_pos..].try_into().unwrap()) as usize;
+    let entries_end = restarts
+        .checked_mul(4)
+        .and_then(|len| count_pos.checked_sub(len))
+        .ok_or_else(|| malformed("bad restart count"))?;
+    let block = &block[..entries_end];
+
+    let mut entries = Vec::new();
+    let mut key: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    while pos < block.len() {
+        let shared = read_varint(block, &mut pos)? as usize;
+        let unshared = read_varint(block, &mut pos)? as usize;
+        let value_len = read_varint(block, &mut pos)? as usize;
+        let key_end = pos.saturating_add(unshared);
+        let value_end = key_end.saturating_add(value_len);
+        if shared > key.len() || value_end > block.len() {
+            return Err(malformed("block entry runs past the end of its block"));
+        }
+        key.truncate(shared);
+        key.extend_from_slice(&block[pos..key_end]);
+        entries.push((key.clone(), &block[key_end..value_end]));
+        pos = value_end;
+    }
+    Ok(entries)
+}
+
+/// Every key/value pair of an uncompressed SSTable, in key order
+fn table_entries(data: &[u8]) -> Result<Vec<(Vec<u8>, &[u8])>> {
+    let footer = data
+        .len()
+        .checked_sub(FOOTER_LEN)
+        .ok_or_else(|| malformed("file too small"))?;
+    let magic = u64::from_le_bytes(data[data.len() - 8..].try_into().unwrap());
+    if magic != TABLE_MAGIC {
+        return Err(malformed("bad table magic"));
+    }
+    let mut pos = footer;
+    read_block(data, data, &mut pos)?; // metaindex, unused
+    let index = read_block(data, data, &mut pos)?;
+
+    // The index block maps the last key of each data block to its handle
+    let mut entries = Vec::new();
+    for (_, handle) in block_entries(index)? {
+        entries.extend(block_entries(read_block(data, handle, &mut 0)?)?);
+    }
+    Ok(entries)
+}
+
+/// A tensor recorded in the index: where its bytes are and what they are
+#[derive(Debug, Clone, PartialEq)]
+struct BundleEntry {
+    dtype: u64,
+    shape: Vec<usize>,
+    shard: u64,
+    range: Range<usize>,
+    sliced: bool,
+}
+
+fn parse_shape(data: &[u8], range: Range<usize>) -> Result<Vec<usize>> {
+    let mut shape = Vec::new();
+    for field in Fields::new(data, range) {
+        if let (SHAPE_DIM, Value::Bytes(dim)) = field? {
+            let mut size = 0;
+            for field in Fields::new(data, dim) {
+                if let (DIM_SIZE, Value::Varint(value)) = field? {
+                    size = usize::try_from(value as i64)
+                        .map_err(|_| malformed("negative dimension"))?;
+                }
+            }
+            shape.push(size);
+        }
+    }
+    Ok(shape)
+}
+
+fn parse_entry(value: &[u8]) -> Result<BundleEntry> {
+    let mut entry = BundleEntry {
+        dtype: 0,
+        shape: Vec::new(),
+        shard: 0,
+        range: 0..0,
+        sliced: false,
+    };
+    let (mut offset, mut size) = (0, 0);
+    for field in Fields::new(value, 0..value.len()) {
+        match field? {
+            (ENTRY_DTYPE, Value::Varint(dtype)) => entry.dtype = dtype,
+            (ENTRY_SHAPE, Value::Bytes(shape)) => entry.shape = parse_shape(value, shape)?,
+            (ENTRY_SHARD_ID, Value::Varint(shard)) => entry.shard = shard,
+            (ENTRY_OFFSET, Value::Varint(v)) => offset = v as usize,
+            (ENTRY_SIZE, Value::Varint(v)) => size = v as usize,
+            (ENTRY_SLICES, Value::Bytes(_)) => entry.sliced = true,
+            _ => {}
+        }
+    }
+    let end = offset
+        .checked_add(size)
+        .ok_or_else(|| malformed("tensor size overflows"))?;
+    entry.range = offset..end;
+    Ok(entry)
+}
+
+/// Name suffix of a data shard, e.g. `data-00000-of-00001`
+fn shard_suffix(shard: u64, num_shards: u64) -> String {
+    format!("data-{:05}-of-{:05}", shard, num_shards)
+}
+
+/// A TensorFlow checkpoint, opened through its `.index` file (e.g. a SavedModel's
+/// `variables/variables.index` or `model.ckpt-1000.index`).
+///
+/// The index is kept whole; every variable in the `.data-*-of-*` shards becomes a
+/// blob, and restore rebuilds the index and all shards byte for byte.
+pub struct TfCheckpoint {
+    pub path: PathBuf,
+    pub mmap: Mmap,
+}
+
+impl TfCheckpoint {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(TfCheckpoint {
+            path: path.to_path_buf(),
+            mmap: map_file(path)?,
+        })
+    }
+}
+
+impl ModelArchiver for TfCheckpoint {
+    fn process(&self, save_blobs: bool) -> Result<VektManifest> {
+        let mut num_shards = 1;
+        let mut tensors = BTreeMap::new();
+        let mut regions: BTreeMap<u64, Vec<(Range<usize>, String)>> = BTreeMap::new();
+        for (key, value) in table_entries(&self.mmap)? {
+            // The empty key holds the BundleHeaderProto
+            if key.is_empty() {
+                for field in Fields::new(value, 0..value.len()) {
+                    if let (HEADER_NUM_SHARDS, Value::Varint(n)) = field? {
+                        num_shards = n;
+                    }
+                }
+                continue;
+            }
+            let name = String::from_utf8(key).map_err(|_| malformed("tensor name is not UTF-8"))?;
+            let entry = parse_entry(value)?;
+            // Partitioned variables list their slices, which have entries of their own
+            if entry.sliced {
+                continue;
+            }
+            if entry.shard >= num_shards {
+                return Err(malformed(&format!(
+                    "'{}' is in missing shard {}",
+                    name, entry.shard
+                )));
+            }
+            tensors.insert(
+                name.clone(),
+                ManifestTensor {
+                    shape: entry.shape,
+                    dtype: tf_dtype(entry.dtype),
+                    hash: String::new(),
+                    index: tensors.len(),
+                    extra: IndexMap::new(),
+                    ranges: None,
+                    stats: None,
+                },
+            );
+            regions
+                .entry(entry.shard)
+                .or_default()
+                .push((entry.range, name));
+        }
+
+        let main = FileLayout::split(&self.mmap, Vec::new(), save_blobs)?;
+        let mut total_size = self.mmap.len();
+        let mut external = BTreeMap::new();
+        for shard in 0..num_shards {
+            let suffix = shard_suffix(shard, num_shards);
+            let mmap = map_file(&self.path.with_extension(&suffix))?;
+            let shard_regions = regions.remove(&shard).unwrap_or_default();
+            let (layout, hashes) = FileLayout::split_hashed(&mmap, shard_regions, save_blobs)?;
+            for (name, hash) in hashes {
+                tensors.get_mut(&name).unwrap().hash = hash;
+            }
+            total_size += mmap.len();
+            external.insert(suffix, layout);
+        }
+
+        Ok(VektManifest {
+            tensors,
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size,
+            checksum: save_blobs.then(|| bytes_checksum(&self.mmap)),
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: Some(SourceLayout {
+                format: TF_CHECKPOINT_FORMAT.to_string(),
+                main,
+                external,
+            }),
+            placement: Default::default(),
+        })
+    }
+
+    fn restore(manifest: &VektManifest, output_path: &Path, filter: Option<&str>) -> Result<()> {
+        manifest.restore(output_path, filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_entries() {
+        // "ab" -> [1], "ac" -> [2] (shares one byte), then one restart at 0
+        let block = [
+            0, 2, 1, b'a', b'b', 1, //
+            1, 1, 1, b'c', 2, //
+            0, 0, 0, 0, 1, 0, 0, 0,
+        ];
+        let entries = block_entries(&block).unwrap();
+        assert_eq!(entries[0], (b"ab".to_vec(), &[1u8][..]));
+        assert_eq!(entries[1], (b"ac".to_vec(), &[2u8][..]));
+        assert!(block_entries(&[9, 0, 0, 0]).is_err());
+
+        assert_eq!(tf_dtype(1), "F32");
+        assert_eq!(tf_dtype(14), "BF16");
+        assert_eq!(tf_dtype(8), "C64");
+        assert_eq!(tf_dtype(20), "TF_20");
+        assert_eq!(shard_suffix(0, 2), "data-00000-of-00002");
+    }
+}
@@ -0,0 +1,241 @@
+use crate::errors::{Result, VektError};
+use crate::storage::{ManifestTensor, VektManifest};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// How one side changed an entry relative to the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl Change {
+    fn between<T>(base: Option<&T>, side: Option<&T>) -> Self {
+        match (base, side) {
+            (None, _) => Change::Added,
+            (Some(_), None) => Change::Removed,
+            (Some(_), Some(_)) => Change::Modified,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Change::Added => "added",
+            Change::Modified => "modified",
+            Change::Removed => "removed",
+        })
+    }
+}
+
+/// Which part of the manifest a conflict is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Tensor,
+    Attachment,
+    Metadata,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntryKind::Tensor => "tensor",
+            EntryKind::Attachment => "attachment",
+            EntryKind::Metadata => "metadata",
+        })
+    }
+}
+
+/// An entry both sides changed, in different ways
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub kind: EntryKind,
+    pub name: String,
+    pub ours: Change,
+    pub theirs: Change,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}': {} in ours, {} in theirs",
+            self.kind, self.name, self.ours, self.theirs
+        )
+    }
+}
+
+/// Which side wins conflicting entries instead of failing the merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Favor {
+    Ours,
+    Theirs,
+}
+
+impl std::str::FromStr for Favor {
+    type Err = VektError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ours" => Ok(Favor::Ours),
+            "theirs" => Ok(Favor::Theirs),
+            _ => Err(VektError::InvalidManifest(format!(
+                "Unknown side '{}'. Use ours or theirs",
+                s
+            ))),
+        }
+    }
+}
+
+/// Result of a three-way merge
+#[derive(Debug)]
+pub struct ThreeWayMerge {
+    /// The merged manifest. Conflicting entries hold the favored side's version, or
+    /// ours when nothing was favored.
+    pub manifest: VektManifest,
+    /// Tensors changed in ours (and possibly the same way in theirs)
+    pub from_ours: Vec<String>,
+    /// Tensors only theirs changed, taken from theirs
+    pub from_theirs: Vec<String>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Same data, shape and dtype; statistics and other annotations don't matter
+fn same_tensor(a: &ManifestTensor, b: &ManifestTensor) -> bool {
+    a.hash == b.hash && a.shape == b.shape && a.dtype == b.dtype
+}
+
+/// State of a merge in progress, shared by tensors, attachments and metadata
+struct Merger {
+    favor: Option<Favor>,
+    from_ours: Vec<String>,
+    from_theirs: Vec<String>,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merger {
+    /// Merges every key of the three maps
+    fn merge<T: Clone>(
+        &mut self,
+        kind: EntryKind,
+        base: &BTreeMap<String, T>,
+        ours: &BTreeMap<String, T>,
+        theirs: &BTreeMap<String, T>,
+        same: impl Fn(&T, &T) -> bool,
+    ) -> BTreeMap<String, T> {
+        let same = |a: Option<&T>, b: Option<&T>| match (a, b) {
+            (Some(a), Some(b)) => same(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let names: BTreeSet<&String> = base
+            .keys()
+            .chain(ours.keys())
+            .chain(theirs.keys())
+            .collect();
+        let mut merged = BTreeMap::new();
+        for name in names {
+            let (base, ours, theirs) = (base.get(name), ours.get(name), theirs.get(name));
+            let take_theirs = if same(ours, theirs) || same(base, theirs) {
+                if !same(base, ours) && kind == EntryKind::Tensor {
+                    self.from_ours.push(name.clone());
+                }
+                false
+            } else if same(base, ours) {
+                if kind == EntryKind::Tensor {
+                    self.from_theirs.push(name.clone());
+                }
+                true
+            } else {
+                self.conflicts.push(MergeConflict {
+                    kind,
+                    name: name.clone(),
+                    ours: Change::between(base, ours),
+                    theirs: Change::between(base, theirs),
+                });
+                self.favor == Some(Favor::Theirs)
+            };
+            if let Some(value) = if take_theirs { theirs } else { ours } {
+                merged.insert(name.clone(), value.clone());
+            }
+        }
+        merged
+    }
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`, tensor by tensor.
+///
+/// A tensor only one side changed (added, replaced, reshaped or removed) takes that
+/// side's version; one both sides changed the same way is kept. Tensors changed
+/// differently on each side are conflicts, as are attachments and metadata keys.
+/// With `favor`, conflicts are resolved in favor of that side; either way they are
+/// reported, and the caller decides whether the merge stands.
+///
+/// Tensors keep the order of `ours`, with tensors only theirs has appended in their
+/// order. Models added from other formats can't be merged, since their tensors must
+/// match the source layout.
+pub fn merge_three_way(
+    base: &VektManifest,
+    ours: &VektManifest,
+    theirs: &VektManifest,
+    favor: Option<Favor>,
+) -> Result<ThreeWayMerge> {
+    ours.check_mutable()?;
+    theirs.check_mutable()?;
+
+    let mut merger = Merger {
+        favor,
+        from_ours: Vec::new(),
+        from_theirs: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    let mut tensors = merger.merge(
+        EntryKind::Tensor,
+        &base.tensors,
+        &ours.tensors,
+        &theirs.tensors,
+        same_tensor,
+    );
+    let attachments = merger.merge(
+        EntryKind::Attachment,
+        &base.attachments,
+        &ours.attachments,
+        &theirs.attachments,
+        |a, b| a == b,
+    );
+    let metadata = merger.merge(
+        EntryKind::Metadata,
+        &base.metadata,
+        &ours.metadata,
+        &theirs.metadata,
+        |a, b| a == b,
+    );
+
+    // Ours first in its order, then tensors only theirs has
+    let position = |name: &String| match ours.tensors.get(name) {
+        Some(tensor) => (0, tensor.index),
+        None => (1, theirs.tensors.get(name).map_or(0, |t| t.index)),
+    };
+    let mut order: Vec<String> = tensors.keys().cloned().collect();
+    order.sort_by_key(position);
+    for (index, name) in order.iter().enumerate() {
+        tensors.get_mut(name).unwrap().index = index;
+    }
+
+    let mut manifest = VektManifest {
+        tensors,
+        attachments,
+        metadata,
+        checksum: None,
+        ..ours.clone()
+    };
+    manifest.recompute_total_size()?;
+    Ok(ThreeWayMerge {
+        manifest,
+        from_ours: merger.from_ours,
+        from_theirs: merger.from_theirs,
+        conflicts: merger.conflicts,
+    })
+}
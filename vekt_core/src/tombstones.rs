@@ -0,0 +1,165 @@
+use crate::blobs;
+use crate::checksum::file_checksum;
+use crate::ephemeral::now_secs;
+use crate::errors::{Result, VektError};
+use crate::refcount;
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A manifest removed with `vekt rm`, stored at `.vekt/tombstones/<manifest hash>`.
+/// `gc --removed` only considers the blobs of tombstoned manifests; any gc clears
+/// the tombstones it settled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    /// Hash of the removed manifest object
+    #[serde(skip)]
+    pub hash: String,
+    /// Manifest file or tag that was removed
+    pub spec: String,
+    /// Seconds since the epoch when it was removed
+    pub removed: u64,
+    /// Blobs the manifest referenced
+    pub blobs: Vec<String>,
+}
+
+/// What [`remove`] deleted
+#[derive(Debug)]
+pub struct Removal {
+    pub tombstone: Tombstone,
+    /// The model file the manifest was made from, unless it was kept
+    pub model: Option<PathBuf>,
+}
+
+/// Directory holding tombstones (.vekt/tombstones)
+pub fn tombstones_dir() -> PathBuf {
+    get_vekt_dir().join("tombstones")
+}
+
+/// Stops tracking the manifest file or tag `spec`: deletes the manifest file and,
+/// unless `cached`, the model file next to it, or deletes the tag. Records a
+/// tombstone for its blobs; they stay in the store until gc finds them unreferenced.
+pub fn remove(spec: &str, cached: bool) -> Result<Removal> {
+    let path = Path::new(spec);
+    let (manifest, model) = if path.is_file() {
+        let manifest = VektManifest::load(path)?;
+        let model = if cached {
+            None
+        } else {
+            model_file(path, &manifest)
+        };
+        refcount::note_removed(path);
+        fs::remove_file(path)?;
+        if let Some(model) = &model {
+            fs::remove_file(model)?;
+        }
+        (manifest, model)
+    } else if refs::validate_ref_name(spec).is_ok() && refs::tags_dir().join(spec).is_file() {
+        let manifest = refs::load_manifest_object(&refs::read_tag(spec)?)?;
+        refs::delete_tag(spec)?;
+        (manifest, None)
+    } else {
+        return Err(VektError::RefNotFound(format!(
+            "'{}' is neither a manifest file nor a tag",
+            spec
+        )));
+    };
+
+    let mut blobs: Vec<String> = manifest.referenced_hashes().cloned().collect();
+    blobs.sort();
+    blobs.dedup();
+    let tombstone = Tombstone {
+        hash: blobs::compute_object_hash(&refs::manifest_object_bytes(&manifest)?),
+        spec: spec.to_string(),
+        removed: now_secs(),
+        blobs,
+    };
+    fs::create_dir_all(tombstones_dir())?;
+    write_file_atomic(
+        &tombstones_dir().join(&tombstone.hash),
+        serde_json::to_string(&tombstone)?.as_bytes(),
+    )?;
+    Ok(Removal { tombstone, model })
+}
+
+/// The model file `manifest` at `path` was made from: a sibling with the same stem
+/// whose contents match the checksum the manifest recorded. Without a checksum, or
+/// when no sibling matches it, the model file is kept and a warning logged.
+fn model_file(path: &Path, manifest: &VektManifest) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(".vekt.json")?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let dir = dir.unwrap_or(Path::new("."));
+    let Some(checksum) = &manifest.checksum else {
+        let model = dir.join(format!("{}.safetensors", stem));
+        if model.is_file() {
+            warn!(
+                "Keeping {}: {} has no checksum to check it against",
+                model.display(),
+                path.display()
+            );
+        }
+        return None;
+    };
+    let candidates: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| {
+                    n != name && n.strip_prefix(stem).is_some_and(|ext| ext.starts_with('.'))
+                })
+                && fs::metadata(candidate).is_ok_and(|m| m.is_file() && m.len() == checksum.size)
+        })
+        .collect();
+    let model = candidates.iter().find(|candidate| {
+        file_checksum(candidate).is_ok_and(|actual| checksum.verify(&actual).is_ok())
+    });
+    if model.is_none()
+        && let Some(candidate) = candidates.first()
+    {
+        warn!(
+            "Keeping {}: it doesn't match the checksum {} recorded",
+            candidate.display(),
+            path.display()
+        );
+    }
+    model.cloned()
+}
+
+/// Lists tombstones, oldest first
+pub fn list_tombstones() -> Result<Vec<Tombstone>> {
+    let dir = tombstones_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut tombstones = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let hash = entry.file_name().to_string_lossy().into_owned();
+        if hash.ends_with(".tmp") {
+            continue;
+        }
+        let mut tombstone: Tombstone = serde_json::from_slice(&fs::read(entry.path())?)?;
+        tombstone.hash = hash;
+        tombstones.push(tombstone);
+    }
+    tombstones.sort_by(|a, b| a.removed.cmp(&b.removed).then_with(|| a.spec.cmp(&b.spec)));
+    Ok(tombstones)
+}
+
+/// Every blob referenced by a removed manifest
+pub fn tombstoned_hashes() -> Result<HashSet<String>> {
+    Ok(list_tombstones()?
+        .into_iter()
+        .flat_map(|tombstone| tombstone.blobs)
+        .collect())
+}
@@ -0,0 +1,223 @@
+use crate::blob_index;
+use crate::encoding::BlobEncoding;
+use crate::ephemeral::now_secs;
+use crate::errors::Result;
+use crate::index::VektIndex;
+use crate::utils::{get_store_path, get_vekt_dir, write_file_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use tracing::warn;
+
+/// Journal of an operation that adds blobs, at `.vekt/journal/<id>.json` while it runs
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    operation: String,
+    /// Seconds since the epoch when the operation began
+    started: u64,
+    pid: u32,
+}
+
+/// What a rollback removed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Rollback {
+    /// Blobs and temp files the transaction wrote
+    pub removed: usize,
+}
+
+/// Makes an operation that writes blobs all-or-nothing. The blobs and temp files it
+/// writes to the store are removed again, and `.vekt/index` put back, unless the
+/// transaction is [committed](Self::commit). Dropping an uncommitted transaction,
+/// e.g. on an early `?` return, rolls it back.
+///
+/// Every store file this process writes while the transaction runs counts as its
+/// own, so run one operation at a time and hold the repository lock. Files other
+/// processes write meanwhile, e.g. `vekt serve` uploads, are left alone. If the
+/// process dies mid-operation, the journal stays behind and the next `gc` cleans
+/// up after it.
+pub struct Transaction {
+    journal: PathBuf,
+    index: Option<Vec<u8>>,
+    finished: bool,
+}
+
+/// Store files each running transaction has written, by journal
+static WRITTEN: LazyLock<Mutex<HashMap<PathBuf, Vec<OsString>>>> = LazyLock::new(Default::default);
+
+impl Transaction {
+    pub fn begin(operation: &str) -> Result<Self> {
+        let dir = journal_dir();
+        fs::create_dir_all(&dir)?;
+        let journal = dir.join(format!("{:016x}.json", rand::random::<u64>()));
+        let entry = JournalEntry {
+            operation: operation.to_string(),
+            started: now_secs(),
+            pid: std::process::id(),
+        };
+        write_file_atomic(&journal, serde_json::to_string(&entry)?.as_bytes())?;
+        written().insert(journal.clone(), Vec::new());
+
+        Ok(Transaction {
+            journal,
+            index: fs::read(VektIndex::path()).ok(),
+            finished: false,
+        })
+    }
+
+    /// Keeps everything written since the transaction began
+    pub fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        written().remove(&self.journal);
+        remove_if_exists(&temp_list(&self.journal))?;
+        fs::remove_file(&self.journal)?;
+        Ok(())
+    }
+
+    /// Removes the blobs and temp files written since the transaction began and
+    /// restores the index
+    pub fn rollback(mut self) -> Result<Rollback> {
+        self.finished = true;
+        self.undo()
+    }
+
+    fn undo(&self) -> Result<Rollback> {
+        let mut rollback = Rollback::default();
+        let store = get_store_path();
+        let names = written().remove(&self.journal).unwrap_or_default();
+        for name in names {
+            let path = store.join(&name);
+            if remove_if_exists(&path)? {
+                blob_index::record_removed(&path);
+                rollback.removed += 1;
+            }
+            // Storage rules may have re-encoded a blob it wrote
+            if !is_temp(&name) {
+                for encoding in BlobEncoding::ENCODED {
+                    let encoded = path.with_extension(encoding.extension().unwrap());
+                    if remove_if_exists(&encoded)? {
+                        blob_index::record_removed(&encoded);
+                    }
+                }
+            }
+        }
+        match &self.index {
+            Some(index) => write_file_atomic(&VektIndex::path(), index)?,
+            None => {
+                remove_if_exists(&VektIndex::path())?;
+            }
+        }
+        remove_if_exists(&temp_list(&self.journal))?;
+        fs::remove_file(&self.journal)?;
+        Ok(rollback)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished
+            && let Err(e) = self.undo()
+        {
+            warn!("Failed to roll back: {}", e);
+        }
+    }
+}
+
+/// Notes that `path` was just created in the blob store, so the running
+/// transactions remove it if they roll back. Temp files also go into the journal:
+/// they're all a process that dies leaves behind that gc can't tell apart.
+pub(crate) fn record(path: &Path) {
+    let Some(name) = path.file_name() else {
+        return;
+    };
+    let mut written = written();
+    for (journal, names) in written.iter_mut() {
+        names.push(name.to_os_string());
+        if is_temp(name)
+            && let Err(e) = append_line(&temp_list(journal), name)
+        {
+            warn!("Failed to journal {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Cleans up after operations that died without committing or rolling back: removes
+/// their journals and the temp files they recorded. Their blobs are unreferenced, so
+/// `gc` removes them as usual. Returns the number of operations cleaned up after.
+/// Only call this while holding the repository lock.
+pub fn recover_journals() -> Result<usize> {
+    let dir = journal_dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let store = get_store_path();
+    let mut recovered = 0;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let temps = temp_list(&path);
+        if let Ok(names) = fs::read_to_string(&temps) {
+            for name in names.lines().filter(|name| is_temp(OsStr::new(name))) {
+                remove_if_exists(&store.join(name))?;
+            }
+        }
+        remove_if_exists(&temps)?;
+        fs::remove_file(&path)?;
+        recovered += 1;
+    }
+    Ok(recovered)
+}
+
+/// Number of operations [`recover_journals`] would clean up after
+pub fn interrupted_journals() -> Result<usize> {
+    let dir = journal_dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut count = 0;
+    for entry in fs::read_dir(&dir)? {
+        if entry?.path().extension().is_some_and(|ext| ext == "json") {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn journal_dir() -> PathBuf {
+    get_vekt_dir().join("journal")
+}
+
+fn written() -> MutexGuard<'static, HashMap<PathBuf, Vec<OsString>>> {
+    WRITTEN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Temp files a transaction has written, one name per line, next to its journal
+fn temp_list(journal: &Path) -> PathBuf {
+    journal.with_extension("temps")
+}
+
+fn is_temp(name: &OsStr) -> bool {
+    name.to_string_lossy().ends_with(".tmp")
+}
+
+fn append_line(path: &Path, name: &OsStr) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", name.to_string_lossy())
+}
+
+/// Returns whether there was a file to remove
+fn remove_if_exists(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,47 @@
+//! Remembers, per remote and manifest name, which version of the remote manifest the
+//! local one is based on: the one last pushed to or pulled from it. A push that would
+//! replace any other version is not a fast-forward.
+//!
+//! Records live in `.vekt/upstream/<hash of the remote URL>/<manifest name>`, one file
+//! each, so pushes of different manifests never race on a shared file.
+use crate::blobs;
+use crate::errors::Result;
+use crate::refs;
+use crate::storage::VektManifest;
+use crate::utils::{get_vekt_dir, write_file_atomic};
+use std::fs;
+use std::path::PathBuf;
+
+/// Where the record for `manifest_name` on the remote at `url` is kept
+fn record_path(url: &str, manifest_name: &str) -> PathBuf {
+    get_vekt_dir()
+        .join("upstream")
+        .join(blobs::compute_object_hash(url.as_bytes()))
+        .join(manifest_name)
+}
+
+/// Hash identifying a version of a manifest, as its manifest object would be named
+pub fn manifest_hash(manifest: &VektManifest) -> Result<String> {
+    Ok(blobs::compute_object_hash(&refs::manifest_object_bytes(
+        manifest,
+    )?))
+}
+
+/// Hash of the remote manifest `manifest_name` was last pushed or pulled as, if ever
+pub fn read(url: &str, manifest_name: &str) -> Result<Option<String>> {
+    match fs::read_to_string(record_path(url, manifest_name)) {
+        Ok(hash) => Ok(Some(hash.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Records that the remote at `url` now holds version `hash` of `manifest_name`
+pub fn write(url: &str, manifest_name: &str, hash: &str) -> Result<()> {
+    let path = record_path(url, manifest_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_file_atomic(&path, hash.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,379 @@
+use crate::blob_index::BlobIndex;
+use crate::encoding;
+use crate::errors::Result;
+use crate::refcount::Reclaimable;
+use crate::storage::VektManifest;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Formats a byte count with binary units, e.g. `1.50 GiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BlobUsage {
+    pub hash: String,
+    pub size: u64,
+    /// Number of manifests referencing the blob
+    pub references: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ManifestUsage {
+    pub label: String,
+    /// Bytes the manifest describes, counting shared tensors each time
+    pub logical_bytes: u64,
+    /// Bytes of blobs no other manifest references: what deleting it would free
+    pub unique_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct UsageReport {
+    pub blob_count: usize,
+    /// Physical size of the blob store
+    pub store_bytes: u64,
+    /// Sum of every manifest's logical size
+    pub logical_bytes: u64,
+    /// Physical size of blobs referenced by at least one manifest
+    pub referenced_bytes: u64,
+    /// Physical size of blobs none of these manifests reference
+    pub unreferenced_bytes: u64,
+    /// What gc would reclaim, counting everything that keeps blobs alive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reclaimable: Option<Reclaimable>,
+    /// Referenced blobs missing from the store
+    pub missing_blobs: usize,
+    pub largest_blobs: Vec<BlobUsage>,
+    pub manifests: Vec<ManifestUsage>,
+}
+
+impl UsageReport {
+    /// Logical bytes per physical byte actually stored
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.referenced_bytes == 0 {
+            1.0
+        } else {
+            self.logical_bytes as f64 / self.referenced_bytes as f64
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Blob store: {} in {} blobs",
+            format_bytes(self.store_bytes),
+            self.blob_count
+        );
+        println!("Logical size: {}", format_bytes(self.logical_bytes));
+        println!("Referenced: {}", format_bytes(self.referenced_bytes));
+        println!(
+            "Unreferenced by these manifests: {}",
+            format_bytes(self.unreferenced_bytes)
+        );
+        if let Some(reclaimable) = &self.reclaimable {
+            println!(
+                "Reclaimable by gc: {} in {} blobs",
+                format_bytes(reclaimable.bytes),
+                reclaimable.blobs
+            );
+        }
+        println!("Deduplication ratio: {:.2}x", self.dedup_ratio());
+        if self.missing_blobs > 0 {
+            println!("Missing blobs: {}", self.missing_blobs);
+        }
+
+        if !self.largest_blobs.is_empty() {
+            println!("\nLargest blobs:");
+            for blob in &self.largest_blobs {
+                println!(
+                    "  {}  {:>12}  {} refs",
+                    &blob.hash[..12.min(blob.hash.len())],
+                    format_bytes(blob.size),
+                    blob.references
+                );
+            }
+        }
+
+        if !self.manifests.is_empty() {
+            println!("\nPer manifest (unique = freed if deleted):");
+            for manifest in &self.manifests {
+                println!(
+                    "  {}  logical {}, unique {}",
+                    manifest.label,
+                    format_bytes(manifest.logical_bytes),
+                    format_bytes(manifest.unique_bytes)
+                );
+            }
+        }
+    }
+}
+
+/// Bytes `manifest` describes, counting shared tensors each time
+fn logical_bytes(manifest: &VektManifest, sizes: &HashMap<String, u64>) -> Result<u64> {
+    manifest
+        .tensors
+        .values()
+        // Tensors of formats vekt can't size (TF strings, ...) count as their blob
+        .map(|t| match t.byte_size() {
+            Ok(size) => Ok(size as u64),
+            Err(e) => sizes.get(&t.hash).copied().ok_or(e),
+        })
+        .chain(manifest.attachments.values().map(|a| Ok(a.size)))
+        .sum()
+}
+
+/// Computes store usage for the given labelled manifests, listing the `top` largest blobs
+pub fn compute_usage(manifests: &[(String, VektManifest)], top: usize) -> Result<UsageReport> {
+    let sizes = BlobIndex::open()?.sizes();
+    let mut report = UsageReport {
+        blob_count: sizes.len(),
+        store_bytes: sizes.values().sum(),
+        ..Default::default()
+    };
+
+    // Blob -> number of manifests referencing it
+    let mut references: HashMap<&String, usize> = HashMap::new();
+    let per_manifest: Vec<BTreeSet<&String>> = manifests
+        .iter()
+        .map(|(_, m)| m.referenced_hashes().collect())
+        .collect();
+    for hashes in &per_manifest {
+        for hash in hashes {
+            *references.entry(*hash).or_default() += 1;
+        }
+    }
+
+    for ((label, manifest), hashes) in manifests.iter().zip(&per_manifest) {
+        let logical_bytes = logical_bytes(manifest, &sizes)?;
+        let unique_bytes = hashes
+            .iter()
+            .filter(|h| references[*h] == 1)
+            .filter_map(|h| sizes.get(*h))
+            .sum();
+        report.logical_bytes += logical_bytes;
+        report.manifests.push(ManifestUsage {
+            label: label.clone(),
+            logical_bytes,
+            unique_bytes,
+        });
+    }
+
+    for hash in references.keys() {
+        match sizes.get(*hash) {
+            Some(size) => report.referenced_bytes += size,
+            None => report.missing_blobs += 1,
+        }
+    }
+    // Chunks and delta bases are referenced through the blobs built from them
+    for hash in encoding::with_dependencies(references.keys().copied())? {
+        if !references.contains_key(&hash) {
+            report.referenced_bytes += sizes.get(&hash).copied().unwrap_or(0);
+        }
+    }
+    report.unreferenced_bytes = report.store_bytes - report.referenced_bytes;
+
+    let mut blobs: Vec<BlobUsage> = sizes
+        .iter()
+        .map(|(hash, size)| BlobUsage {
+            hash: hash.clone(),
+            size: *size,
+            references: references.get(hash).copied().unwrap_or(0),
+        })
+        .collect();
+    blobs.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.hash.cmp(&b.hash)));
+    blobs.truncate(top);
+    report.largest_blobs = blobs;
+
+    report.manifests.sort_by(|a, b| {
+        b.unique_bytes
+            .cmp(&a.unique_bytes)
+            .then_with(|| a.label.cmp(&b.label))
+    });
+    Ok(report)
+}
+
+/// Blobs referenced by exactly the same set of manifests
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SharedGroup {
+    /// Labels of the manifests sharing them, in the order given
+    pub manifests: Vec<String>,
+    pub blobs: usize,
+    /// Size on disk of the shared blobs, each stored once
+    pub bytes: u64,
+    /// Tensors and attachments holding them, as the first manifest names them
+    pub entries: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestSharing {
+    pub label: String,
+    pub logical_bytes: u64,
+    /// Bytes of blobs at least one other manifest references too
+    pub shared_bytes: u64,
+    /// Bytes of blobs no other manifest references
+    pub unique_bytes: u64,
+}
+
+/// What a set of manifests share, from [`compute_dedup`]
+#[derive(Serialize, Debug, Default)]
+pub struct DedupReport {
+    /// Largest first
+    pub groups: Vec<SharedGroup>,
+    pub manifests: Vec<ManifestSharing>,
+    /// Sum of every manifest's logical size
+    pub logical_bytes: u64,
+    /// Size on disk of every blob they reference, each counted once
+    pub stored_bytes: u64,
+    /// Referenced blobs missing from the store
+    pub missing_blobs: usize,
+}
+
+impl DedupReport {
+    /// Bytes deduplication saves compared to storing each manifest on its own
+    pub fn saved_bytes(&self) -> u64 {
+        self.logical_bytes.saturating_sub(self.stored_bytes)
+    }
+
+    pub fn print_summary(&self) {
+        if self.groups.is_empty() {
+            println!("No blobs are shared between these manifests");
+        }
+        for group in &self.groups {
+            println!(
+                "Shared by {}: {} blobs, {}",
+                group.manifests.join(", "),
+                group.blobs,
+                format_bytes(group.bytes)
+            );
+            const SHOWN: usize = 5;
+            for entry in group.entries.iter().take(SHOWN) {
+                println!("  {}", entry);
+            }
+            if group.entries.len() > SHOWN {
+                println!("  ... and {} more", group.entries.len() - SHOWN);
+            }
+        }
+
+        println!("\nPer manifest:");
+        for manifest in &self.manifests {
+            println!(
+                "  {}  logical {}, shared {}, unique {}",
+                manifest.label,
+                format_bytes(manifest.logical_bytes),
+                format_bytes(manifest.shared_bytes),
+                format_bytes(manifest.unique_bytes)
+            );
+        }
+
+        println!("\nLogical size: {}", format_bytes(self.logical_bytes));
+        println!("Stored: {}", format_bytes(self.stored_bytes));
+        println!(
+            "Saved by deduplication: {}",
+            format_bytes(self.saved_bytes())
+        );
+        if self.missing_blobs > 0 {
+            println!("Missing blobs: {}", self.missing_blobs);
+        }
+    }
+}
+
+/// Works out which blobs the given labelled manifests share (e.g. a base model and
+/// its fine-tunes) and how much storing them once saves
+pub fn compute_dedup(manifests: &[(String, VektManifest)]) -> Result<DedupReport> {
+    let sizes = BlobIndex::open()?.sizes();
+    let mut report = DedupReport::default();
+
+    // Blob -> positions of the manifests referencing it
+    let mut holders: HashMap<&String, BTreeSet<usize>> = HashMap::new();
+    // Blob -> the first entry naming it
+    let mut names: HashMap<&String, &String> = HashMap::new();
+    for (position, (_, manifest)) in manifests.iter().enumerate() {
+        for hash in manifest.referenced_hashes() {
+            holders.entry(hash).or_default().insert(position);
+        }
+        let entries = manifest
+            .tensors
+            .iter()
+            .map(|(name, t)| (name, &t.hash))
+            .chain(manifest.attachments.iter().map(|(name, a)| (name, &a.hash)));
+        for (name, hash) in entries {
+            names.entry(hash).or_insert(name);
+        }
+    }
+
+    let mut groups: BTreeMap<&BTreeSet<usize>, SharedGroup> = BTreeMap::new();
+    let mut shared_bytes = vec![0; manifests.len()];
+    let mut unique_bytes = vec![0; manifests.len()];
+    for (hash, positions) in &holders {
+        let Some(&size) = sizes.get(*hash) else {
+            report.missing_blobs += 1;
+            continue;
+        };
+        report.stored_bytes += size;
+        let per_manifest = if positions.len() > 1 {
+            &mut shared_bytes
+        } else {
+            &mut unique_bytes
+        };
+        for &position in positions {
+            per_manifest[position] += size;
+        }
+        if positions.len() > 1 {
+            let group = groups.entry(positions).or_insert_with(|| SharedGroup {
+                manifests: positions.iter().map(|&p| manifests[p].0.clone()).collect(),
+                blobs: 0,
+                bytes: 0,
+                entries: Vec::new(),
+            });
+            group.blobs += 1;
+            group.bytes += size;
+            if let Some(name) = names.get(hash) {
+                group.entries.push((*name).clone());
+            }
+        }
+    }
+
+    for (position, (label, manifest)) in manifests.iter().enumerate() {
+        let logical_bytes = logical_bytes(manifest, &sizes)?;
+        report.logical_bytes += logical_bytes;
+        report.manifests.push(ManifestSharing {
+            label: label.clone(),
+            logical_bytes,
+            shared_bytes: shared_bytes[position],
+            unique_bytes: unique_bytes[position],
+        });
+    }
+    report.groups = groups.into_values().collect();
+    for group in &mut report.groups {
+        group.entries.sort();
+    }
+    report.groups.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.manifests.cmp(&b.manifests))
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.00 GiB");
+    }
+}
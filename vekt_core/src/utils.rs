@@ -2,16 +2,89 @@ use crate::errors::{Result, VektError};
 use std::fs::{self};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
 
-/// Atomically writes data to a file using temp file + rename pattern
+/// Atomically writes data to a file using temp file + rename pattern. On failure
+/// the file is untouched and the temp file removed.
 pub fn write_file_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
     let tmp_path = path.with_extension("tmp");
-    let mut f = fs::File::create(&tmp_path)?;
-    f.write_all(data)?;
-    f.sync_all()?;
-    fs::rename(tmp_path, path)?;
-    Ok(())
+    let written = (|| {
+        let mut f = fs::File::create(&tmp_path)?;
+        f.write_all(data)?;
+        f.sync_all()?;
+        drop(f);
+        rename_replace(&tmp_path, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    written
+}
+
+/// Attempts [`rename_replace`] makes on Windows before giving up
+#[cfg(windows)]
+const RENAME_ATTEMPTS: u32 = 8;
+
+/// Renames `from` to `to`, replacing any file already at `to`.
+///
+/// Windows refuses while another process, such as a virus scanner, the search
+/// indexer or a reader of the old file, has `to` open, so there the rename is
+/// retried with backoff for about a second before the error is returned.
+pub fn rename_replace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        let mut delay = Duration::from_millis(5);
+        for _ in 1..RENAME_ATTEMPTS {
+            match fs::rename(from, to) {
+                Err(e) if is_sharing_violation(&e) => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+    fs::rename(from, to)
+}
+
+/// ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION or ERROR_LOCK_VIOLATION: another
+/// handle is open on the file, which usually goes away shortly
+#[cfg(windows)]
+fn is_sharing_violation(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(5 | 32 | 33))
+}
+
+/// Paths at least this long get the `\\?\` prefix on Windows, leaving room for a
+/// 64-character blob name and an extension below the 260-character `MAX_PATH`
+#[cfg(windows)]
+const LONG_PATH_THRESHOLD: usize = 180;
+
+/// Makes `path` usable on Windows when it's close to the 260-character `MAX_PATH`
+/// limit, by turning it into an absolute `\\?\` path, which has no such limit.
+/// Other paths are returned unchanged.
+#[cfg(windows)]
+pub fn long_path(path: PathBuf) -> PathBuf {
+    if path.as_os_str().len() < LONG_PATH_THRESHOLD || path.to_string_lossy().starts_with(r"\\?\") {
+        return path;
+    }
+    // The prefix turns off separator and `..` handling, so resolve those first
+    let Ok(absolute) = std::path::absolute(&path) else {
+        return path;
+    };
+    let absolute = absolute.to_string_lossy().replace('/', "\\");
+    match absolute.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", absolute)),
+    }
+}
+
+/// Paths have no `MAX_PATH` limit outside Windows
+#[cfg(not(windows))]
+pub fn long_path(path: PathBuf) -> PathBuf {
+    path
 }
 
 /// Ensures .vekt directory exists with proper .gitignore file
@@ -46,38 +119,157 @@ pub fn find_vekt_root() -> Option<PathBuf> {
     None
 }
 
-/// Returns the path to the blobs directory.
-/// Uses the local repository's .vekt/blobs if found, otherwise defaults to ./.vekt/blobs
+/// Recursively finds `*.vekt.json` manifest files under `dir`, skipping VCS,
+/// build and dependency directories. Results are sorted for stable output.
+pub fn find_manifest_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    collect_manifest_files(dir, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+fn collect_manifest_files(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            // Ignore common build/hidden dirs to avoid scanning too much or loops
+            if name == ".git" || name == ".vekt" || name == "target" || name == "node_modules" {
+                continue;
+            }
+            collect_manifest_files(&path, found)?;
+        } else if path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with(".vekt.json"))
+        {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path to the .vekt directory.
+/// Uses the local repository's .vekt if found, otherwise defaults to ./.vekt
 /// Also ensures .vekt has a .gitignore file
-pub fn get_store_path() -> PathBuf {
-    let vekt_dir = match find_vekt_root() {
+pub fn get_vekt_dir() -> PathBuf {
+    let vekt_dir = long_path(match find_vekt_root() {
         Some(root) => root.join(".vekt"),
         None => std::env::current_dir()
             .unwrap_or_else(|_| PathBuf::from("."))
             .join(".vekt"),
-    };
+    });
 
     // Ensure .vekt has .gitignore (ignore errors as this is best-effort)
     let _ = ensure_vekt_dir(&vekt_dir);
 
-    vekt_dir.join("blobs")
+    vekt_dir
+}
+
+/// Returns the path to the blobs directory (.vekt/blobs)
+static STORE_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Keeps blobs at `path` instead of `.vekt/blobs` for this process, as `store_path`
+/// in the config asks
+pub fn set_store_path(path: Option<PathBuf>) {
+    *STORE_PATH.write().unwrap_or_else(|e| e.into_inner()) = path;
 }
 
-pub fn get_dtype_size(dtype: &str) -> usize {
-    match dtype {
-        "F32" => 4,
-        "F16" => 2,
-        "BF16" => 2,
-        "I64" => 8,
-        "I32" => 4,
-        "I16" => 2,
-        "I8" => 1,
-        "U8" => 1,
-        "BOOL" => 1,
-        _ => 1, // Fallback
+pub fn get_store_path() -> PathBuf {
+    if let Some(path) = STORE_PATH
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        return long_path(path.clone());
     }
+    get_vekt_dir().join("blobs")
 }
 
+/// Parses a duration such as `90s`, `30m`, `12h` or `7d`. A bare number is seconds.
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| VektError::InvalidDuration(input.to_string()))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(VektError::InvalidDuration(input.to_string())),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| VektError::InvalidDuration(input.to_string()))
+}
+
+/// Parses a size such as `500MB`, `20GiB` or `1T`. Units are powers of 1024 with or
+/// without the `i`, and a bare number is bytes.
+pub fn parse_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| VektError::InvalidSize(input.to_string()))?;
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return Err(VektError::InvalidSize(input.to_string())),
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| VektError::InvalidSize(input.to_string()))
+}
+
+/// Bits per element of a safetensors dtype. Sub-byte dtypes (F4, F6, I4, U4) are
+/// packed, see [`tensor_byte_size`].
+pub fn get_dtype_bits(dtype: &str) -> Result<usize> {
+    Ok(match dtype {
+        "F64" | "I64" | "U64" | "C64" => 64,
+        "F32" | "I32" | "U32" => 32,
+        "F16" | "BF16" | "I16" | "U16" => 16,
+        "F8_E4M3" | "F8_E5M2" | "F8_E8M0" | "I8" | "U8" | "BOOL" => 8,
+        "F6_E2M3" | "F6_E3M2" => 6,
+        "F4" | "I4" | "U4" => 4,
+        _ => return Err(VektError::UnsupportedDtype(dtype.to_string())),
+    })
+}
+
+/// Size in bytes of a tensor's data, rounded up to whole bytes for packed dtypes
+pub fn tensor_byte_size(shape: &[usize], dtype: &str) -> Result<usize> {
+    let bits = get_dtype_bits(dtype)?;
+    shape
+        .iter()
+        .try_fold(bits, |acc, &dim| acc.checked_mul(dim))
+        .map(|bits| bits.div_ceil(8))
+        .ok_or_else(|| {
+            VektError::TensorCorruption(format!(
+                "{} tensor of shape {:?} is too large",
+                dtype, shape
+            ))
+        })
+}
+
+/// How long [`LockFile::lock`] waits for a lock held by another process, in
+/// milliseconds. Zero fails immediately.
+static LOCK_WAIT_MS: AtomicU64 = AtomicU64::new(0);
+
 pub struct LockFile {
     path: PathBuf,
 }
@@ -86,11 +278,56 @@ impl LockFile {
     /// Maximum age of lock file before considering it stale (5 minutes)
     const STALE_LOCK_THRESHOLD_SECS: u64 = 300;
 
+    /// Delay between attempts while waiting for the lock
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Interval between "still waiting" messages
+    const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Sets how long every later [`lock`](Self::lock) in this process waits for
+    /// another process to release the lock (`--lock-wait`)
+    pub fn set_wait(wait: Duration) {
+        LOCK_WAIT_MS.store(wait.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Acquires the repository lock, waiting as configured by [`set_wait`](Self::set_wait)
     pub fn lock() -> Result<Self> {
+        Self::lock_with_wait(Duration::from_millis(LOCK_WAIT_MS.load(Ordering::Relaxed)))
+    }
+
+    /// Acquires the repository lock, retrying for up to `wait` while another
+    /// process holds it
+    pub fn lock_with_wait(wait: Duration) -> Result<Self> {
+        let start = Instant::now();
+        let mut next_progress = Duration::ZERO;
+        loop {
+            let elapsed = start.elapsed();
+            match Self::try_lock() {
+                Err(VektError::LockExists) if wait.is_zero() => return Err(VektError::LockExists),
+                Err(VektError::LockExists) if elapsed < wait => {
+                    if elapsed >= next_progress {
+                        info!(
+                            "Waiting for another vekt operation to finish ({}s of {}s)...",
+                            elapsed.as_secs(),
+                            wait.as_secs()
+                        );
+                        next_progress = elapsed + Self::PROGRESS_INTERVAL;
+                    }
+                    std::thread::sleep(Self::POLL_INTERVAL.min(wait - elapsed));
+                }
+                Err(VektError::LockExists) => {
+                    return Err(VektError::LockTimeout(wait.as_secs()));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn try_lock() -> Result<Self> {
         // Use the found root or current dir for locking
         let root = find_vekt_root()
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-        let vekt_dir = root.join(".vekt");
+        let vekt_dir = long_path(root.join(".vekt"));
         let path = vekt_dir.join("lock");
 
         // Ensure .vekt exists with .gitignore
@@ -110,8 +347,8 @@ impl LockFile {
             let age_secs = duration.as_secs();
             if age_secs > Self::STALE_LOCK_THRESHOLD_SECS {
                 // Remove stale lock
-                eprintln!(
-                    "Warning: Removing stale lock file (age: {} seconds). Previous process may have crashed.",
+                warn!(
+                    "Removing stale lock file (age: {} seconds). Previous process may have crashed.",
                     age_secs
                 );
                 fs::remove_file(&path).map_err(|e| {
@@ -136,14 +373,15 @@ impl LockFile {
                 .as_secs()
         );
 
-        fs::OpenOptions::new()
+        let mut file = fs::OpenOptions::new()
             .write(true)
             .create_new(true)
             .open(&path)
             .map_err(|_| VektError::LockExists)?;
 
-        // Write PID to lock file
-        fs::write(&path, lock_content).map_err(|e| {
+        // Write PID through the handle that created the file; reopening it can fail
+        // on Windows while a scanner looks at the new file
+        file.write_all(lock_content.as_bytes()).map_err(|e| {
             VektError::Io(io::Error::other(format!(
                 "Failed to write lock file: {}",
                 e
@@ -159,3 +397,76 @@ impl Drop for LockFile {
         let _ = fs::remove_file(&self.path);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_replace() {
+        let dir = std::env::temp_dir().join(format!("vekt_rename_{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let (from, to) = (dir.join("new.tmp"), dir.join("current"));
+        fs::write(&to, "old").unwrap();
+        fs::write(&from, "new").unwrap();
+        rename_replace(&from, &to).unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+        assert!(!from.exists());
+
+        // Short paths never change; long ones only on Windows
+        assert_eq!(long_path(dir.clone()), dir);
+        let deep = (0..12).fold(dir.clone(), |path, i| {
+            path.join(format!("level-{:02}-{}", i, "x".repeat(16)))
+        });
+        let long = long_path(deep.clone());
+        if cfg!(windows) {
+            assert!(long.to_string_lossy().starts_with(r"\\?\"));
+        } else {
+            assert_eq!(long, deep);
+        }
+        fs::create_dir_all(&long).unwrap();
+        write_file_atomic(&long.join("file"), b"deep").unwrap();
+        assert_eq!(fs::read(long.join("file")).unwrap(), b"deep");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("500MB").unwrap(), 500 << 20);
+        assert_eq!(parse_size("20GiB").unwrap(), 20 << 30);
+        assert_eq!(parse_size("1t").unwrap(), 1 << 40);
+        assert!(parse_size("GB").is_err());
+        assert!(parse_size("5PB").is_err());
+        assert!(parse_size("99999999T").is_err());
+    }
+
+    #[test]
+    fn test_tensor_byte_size() {
+        assert_eq!(tensor_byte_size(&[2, 3], "F64").unwrap(), 48);
+        assert_eq!(tensor_byte_size(&[4096], "F8_E4M3").unwrap(), 4096);
+        assert_eq!(tensor_byte_size(&[3], "U16").unwrap(), 6);
+        assert_eq!(tensor_byte_size(&[], "U64").unwrap(), 8);
+        // Packed dtypes round up to whole bytes
+        assert_eq!(tensor_byte_size(&[5], "I4").unwrap(), 3);
+        assert_eq!(tensor_byte_size(&[4], "F6_E3M2").unwrap(), 3);
+        assert_eq!(tensor_byte_size(&[0, 7], "F4").unwrap(), 0);
+
+        assert!(matches!(
+            tensor_byte_size(&[1], "Q4_K"),
+            Err(VektError::UnsupportedDtype(dtype)) if dtype == "Q4_K"
+        ));
+        assert!(tensor_byte_size(&[usize::MAX, 2], "U8").is_err());
+    }
+}
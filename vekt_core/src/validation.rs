@@ -1,6 +1,11 @@
-use crate::blobs;
 use crate::errors::{Result, VektError};
+use crate::hash::HashAlgorithm;
+use crate::sampling::parse_sample_fraction;
+use crate::storage::RawTensorMetaData;
+use crate::utils::tensor_byte_size;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::OnceLock;
 
@@ -20,9 +25,12 @@ fn get_s3_bucket_regex() -> &'static Regex {
     })
 }
 
-/// Validates that a path doesn't contain path traversal attempts
+/// Validates that a path doesn't contain path traversal attempts. Drive-relative
+/// paths such as `C:model.bin` are refused too, since Windows resolves them outside
+/// the directory they are joined to.
 pub fn validate_path_safe(path: &str) -> Result<()> {
-    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+    let drive = path.as_bytes().get(1) == Some(&b':');
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') || drive {
         return Err(VektError::PathTraversal(format!(
             "Path contains unsafe characters or traversal attempt: {}",
             path
@@ -58,15 +66,121 @@ pub fn validate_tensor_name(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validates that a safetensors header entry has a known dtype and that its data
+/// offsets span exactly the bytes its shape needs
+pub fn validate_tensor_meta(name: &str, meta: &RawTensorMetaData) -> Result<()> {
+    let needed = tensor_byte_size(&meta.shape, &meta.dtype)?;
+    let (start, end) = meta.data_offsets;
+    if end.checked_sub(start) != Some(needed) {
+        return Err(VektError::InvalidSafetensor(format!(
+            "Tensor '{}': {} {:?} needs {} bytes, but its data offsets span {}..{}",
+            name, meta.dtype, meta.shape, needed, start, end
+        )));
+    }
+    Ok(())
+}
+
 /// Validates S3 URL format
 pub fn validate_s3_url(url: &str) -> Result<String> {
+    Ok(parse_s3_url(url)?.bucket)
+}
+
+/// S3 storage classes a remote can upload blobs with
+pub const STORAGE_CLASSES: &[&str] = &[
+    "STANDARD",
+    "STANDARD_IA",
+    "ONEZONE_IA",
+    "INTELLIGENT_TIERING",
+    "GLACIER_IR",
+    "GLACIER",
+    "DEEP_ARCHIVE",
+    "REDUCED_REDUNDANCY",
+];
+
+/// Server-side encryption S3 applies to every object a remote uploads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerSideEncryption {
+    /// Keys managed by S3 (`sse=AES256`)
+    Aes256,
+    /// AWS KMS, with the bucket's default key unless a key ID or ARN is given
+    /// (`sse=aws:kms` or `sse-kms-key-id=<key>`)
+    Kms(Option<String>),
+}
+
+/// Longest S3 object tag key and value
+const MAX_TAG_KEY_LEN: usize = 128;
+const MAX_TAG_VALUE_LEN: usize = 256;
+/// Most tags S3 allows on one object
+const MAX_TAGS: usize = 10;
+/// Tags pushes add to what they upload for a model, unless configured
+const MODEL_TAGS: [&str; 2] = ["model", "manifest-version"];
+
+/// A remote URL: `s3://bucket`, optionally followed by `?endpoint=...&region=...&path-style=true`
+/// for S3-compatible stores such as MinIO, Ceph or R2, `storage-class=...`, and
+/// `sse=...` and `tag.<key>=<value>` options for uploads
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct S3Url {
+    pub bucket: String,
+    pub region: Option<String>,
+    /// Base URL of an S3-compatible store, e.g. `https://minio.internal:9000`
+    pub endpoint: Option<String>,
+    /// Address the bucket as `<endpoint>/<bucket>` rather than `<bucket>.<endpoint>`
+    pub path_style: bool,
+    /// Storage class blobs are uploaded with, one of [`STORAGE_CLASSES`]. Manifests,
+    /// tags and releases always use the bucket's default so they stay readable.
+    pub storage_class: Option<String>,
+    /// Encryption requested on every upload, blobs and manifests alike
+    pub encryption: Option<ServerSideEncryption>,
+    /// Tags set on every uploaded object
+    pub tags: BTreeMap<String, String>,
+}
+
+impl std::fmt::Display for S3Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "s3://{}", self.bucket)?;
+        let mut options = Vec::new();
+        if let Some(endpoint) = &self.endpoint {
+            options.push(format!("endpoint={}", endpoint));
+        }
+        if let Some(region) = &self.region {
+            options.push(format!("region={}", region));
+        }
+        if self.path_style {
+            options.push("path-style=true".to_string());
+        }
+        if let Some(storage_class) = &self.storage_class {
+            options.push(format!("storage-class={}", storage_class));
+        }
+        match &self.encryption {
+            Some(ServerSideEncryption::Aes256) => options.push("sse=AES256".to_string()),
+            Some(ServerSideEncryption::Kms(None)) => options.push("sse=aws:kms".to_string()),
+            Some(ServerSideEncryption::Kms(Some(key))) => {
+                options.push(format!("sse-kms-key-id={}", key))
+            }
+            None => {}
+        }
+        for (key, value) in &self.tags {
+            options.push(format!("tag.{}={}", key, value));
+        }
+        if !options.is_empty() {
+            write!(f, "?{}", options.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses and validates an `s3://` remote URL
+pub fn parse_s3_url(url: &str) -> Result<S3Url> {
     if !url.starts_with("s3://") {
         return Err(VektError::InvalidRemoteUrl(
             "URL must start with s3://".to_string(),
         ));
     }
 
-    let bucket_name = url.trim_start_matches("s3://").trim_end_matches('/');
+    let (location, query) = url["s3://".len()..]
+        .split_once('?')
+        .unwrap_or((&url["s3://".len()..], ""));
+    let bucket_name = location.trim_end_matches('/');
 
     // Use regex for cleaner S3 bucket name validation
     if !get_s3_bucket_regex().is_match(bucket_name) {
@@ -76,12 +190,103 @@ pub fn validate_s3_url(url: &str) -> Result<String> {
         )));
     }
 
-    Ok(bucket_name.to_string())
+    let mut parsed = S3Url {
+        bucket: bucket_name.to_string(),
+        ..Default::default()
+    };
+    for option in query.split('&').filter(|o| !o.is_empty()) {
+        let (key, value) = option.split_once('=').unwrap_or((option, "true"));
+        match key {
+            "endpoint" => {
+                if !value.starts_with("http://") && !value.starts_with("https://") {
+                    return Err(VektError::InvalidRemoteUrl(format!(
+                        "Endpoint '{}' must start with http:// or https://",
+                        value
+                    )));
+                }
+                parsed.endpoint = Some(value.trim_end_matches('/').to_string());
+            }
+            "region" if !value.is_empty() => parsed.region = Some(value.to_string()),
+            "path-style" => {
+                parsed.path_style = value.parse().map_err(|_| {
+                    VektError::InvalidRemoteUrl(format!(
+                        "path-style must be true or false, got '{}'",
+                        value
+                    ))
+                })?
+            }
+            "storage-class" => {
+                let storage_class = value.to_ascii_uppercase();
+                if !STORAGE_CLASSES.contains(&storage_class.as_str()) {
+                    return Err(VektError::InvalidRemoteUrl(format!(
+                        "Unknown storage class '{}'. Supported: {}",
+                        value,
+                        STORAGE_CLASSES.join(", ")
+                    )));
+                }
+                parsed.storage_class = Some(storage_class);
+            }
+            "sse" => {
+                parsed.encryption = match value {
+                    "AES256" => Some(ServerSideEncryption::Aes256),
+                    // A key given on its own is kept whichever order the options are in
+                    "aws:kms" => match parsed.encryption.take() {
+                        Some(ServerSideEncryption::Kms(key)) => {
+                            Some(ServerSideEncryption::Kms(key))
+                        }
+                        _ => Some(ServerSideEncryption::Kms(None)),
+                    },
+                    _ => {
+                        return Err(VektError::InvalidRemoteUrl(format!(
+                            "Unknown encryption '{}'. Supported: AES256, aws:kms",
+                            value
+                        )));
+                    }
+                }
+            }
+            "sse-kms-key-id" if !value.is_empty() => {
+                parsed.encryption = Some(ServerSideEncryption::Kms(Some(value.to_string())))
+            }
+            _ if key.starts_with("tag.") => {
+                let tag = &key["tag.".len()..];
+                if tag.is_empty() || tag.len() > MAX_TAG_KEY_LEN || value.len() > MAX_TAG_VALUE_LEN
+                {
+                    return Err(VektError::InvalidRemoteUrl(format!(
+                        "Invalid tag '{}': keys must be 1-{} characters and values at most {}",
+                        option, MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN
+                    )));
+                }
+                parsed.tags.insert(tag.to_string(), value.to_string());
+            }
+            _ => {
+                return Err(VektError::InvalidRemoteUrl(format!(
+                    "Unknown remote option '{}'. Supported: endpoint, region, path-style, storage-class, sse, sse-kms-key-id, tag.<key>",
+                    option
+                )));
+            }
+        }
+    }
+    if !parsed.tags.is_empty() {
+        let added = MODEL_TAGS
+            .iter()
+            .filter(|tag| !parsed.tags.contains_key(**tag))
+            .count();
+        if parsed.tags.len() + added > MAX_TAGS {
+            return Err(VektError::InvalidRemoteUrl(format!(
+                "Too many tags: S3 allows {} per object and pushes add {} of their own ({})",
+                MAX_TAGS,
+                added,
+                MODEL_TAGS.join(", ")
+            )));
+        }
+    }
+    Ok(parsed)
 }
 
-/// Verifies blob integrity by comparing hash
+/// Verifies blob integrity by comparing hash, computed with the algorithm the
+/// expected hash is tagged with
 pub fn verify_blob_hash(data: &[u8], expected_hash: &str) -> Result<()> {
-    let actual_hash = blobs::compute_blob_hash(data);
+    let actual_hash = HashAlgorithm::of(expected_hash).hash(data);
 
     if actual_hash != expected_hash {
         return Err(VektError::HashMismatch {
@@ -93,6 +298,78 @@ pub fn verify_blob_hash(data: &[u8], expected_hash: &str) -> Result<()> {
     Ok(())
 }
 
+/// How many blobs a restore re-hashes before writing them out
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum VerifyPolicy {
+    /// Hash every blob (the default)
+    #[default]
+    Always,
+    /// Trust the store and skip hashing
+    Never,
+    /// Hash a random fraction of blobs, so repeated restores cover the whole store
+    Sampled(f64),
+}
+
+impl VerifyPolicy {
+    /// Fraction checked by plain `sampled`
+    pub const DEFAULT_SAMPLE: f64 = 0.1;
+
+    /// Decides whether the next blob gets verified
+    #[cfg(feature = "native")]
+    pub fn should_verify(&self) -> bool {
+        match self {
+            VerifyPolicy::Always => true,
+            VerifyPolicy::Never => false,
+            VerifyPolicy::Sampled(fraction) => rand::random::<f64>() < *fraction,
+        }
+    }
+}
+
+impl std::str::FromStr for VerifyPolicy {
+    type Err = VektError;
+
+    /// Parses `always`, `never`, `sampled` or `sampled:<size>` such as `sampled:5%`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "always" => Ok(VerifyPolicy::Always),
+            "never" => Ok(VerifyPolicy::Never),
+            "sampled" => Ok(VerifyPolicy::Sampled(Self::DEFAULT_SAMPLE)),
+            other => match other.strip_prefix("sampled:") {
+                Some(size) => Ok(VerifyPolicy::Sampled(parse_sample_fraction(size)?)),
+                None => Err(VektError::InvalidManifest(format!(
+                    "Unknown verify policy '{}'. Use always, never, sampled or sampled:<size>",
+                    other
+                ))),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyPolicy::Always => write!(f, "always"),
+            VerifyPolicy::Never => write!(f, "never"),
+            VerifyPolicy::Sampled(fraction) => write!(f, "sampled:{}%", fraction * 100.0),
+        }
+    }
+}
+
+impl TryFrom<String> for VerifyPolicy {
+    type Error = VektError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<VerifyPolicy> for String {
+    fn from(policy: VerifyPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
 /// Validates that a file exists and is readable
 pub fn validate_file_exists(path: &Path) -> Result<()> {
     if !path.exists() {
@@ -111,3 +388,97 @@ pub fn validate_file_exists(path: &Path) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_url() {
+        let url = parse_s3_url("s3://models/").unwrap();
+        assert_eq!(url.bucket, "models");
+        assert_eq!(url.endpoint, None);
+        assert!(!url.path_style);
+
+        let url = parse_s3_url(
+            "s3://models?endpoint=https://minio.internal:9000/&path-style=true&region=eu-1",
+        )
+        .unwrap();
+        assert_eq!(url.endpoint.as_deref(), Some("https://minio.internal:9000"));
+        assert_eq!(url.region.as_deref(), Some("eu-1"));
+        assert!(url.path_style);
+        assert_eq!(parse_s3_url(&url.to_string()).unwrap(), url);
+
+        let url = parse_s3_url("s3://models?storage-class=glacier_ir").unwrap();
+        assert_eq!(url.storage_class.as_deref(), Some("GLACIER_IR"));
+        assert_eq!(parse_s3_url(&url.to_string()).unwrap(), url);
+        assert!(parse_s3_url("s3://models?storage-class=COLD").is_err());
+
+        let url = parse_s3_url(
+            "s3://models?sse-kms-key-id=arn:aws:kms:eu-1:1:key/abc&sse=aws:kms&tag.project=llm",
+        )
+        .unwrap();
+        assert_eq!(
+            url.encryption,
+            Some(ServerSideEncryption::Kms(Some(
+                "arn:aws:kms:eu-1:1:key/abc".to_string()
+            )))
+        );
+        assert_eq!(url.tags["project"], "llm");
+        assert_eq!(parse_s3_url(&url.to_string()).unwrap(), url);
+        let url = parse_s3_url("s3://models?sse=AES256").unwrap();
+        assert_eq!(url.encryption, Some(ServerSideEncryption::Aes256));
+        assert!(parse_s3_url("s3://models?sse=DES").is_err());
+        assert!(parse_s3_url("s3://models?tag.=x").is_err());
+        let tags = |n: usize| {
+            (0..n)
+                .map(|i| format!("tag.k{}=v", i))
+                .collect::<Vec<_>>()
+                .join("&")
+        };
+        assert!(parse_s3_url(&format!("s3://models?{}", tags(8))).is_ok());
+        assert!(parse_s3_url(&format!("s3://models?{}", tags(9))).is_err());
+        assert!(parse_s3_url(&format!("s3://models?{}&tag.model=m", tags(8))).is_ok());
+        assert!(parse_s3_url(&format!("s3://models?{}&tag.model=m", tags(9))).is_err());
+        assert!(
+            parse_s3_url(&format!(
+                "s3://models?{}&tag.model=m&tag.manifest-version=1",
+                tags(8)
+            ))
+            .is_ok()
+        );
+
+        assert!(parse_s3_url("s3://models?endpoint=minio:9000").is_err());
+        assert!(parse_s3_url("s3://models?path-style=yes").is_err());
+        assert!(parse_s3_url("s3://models?acl=public").is_err());
+        assert!(parse_s3_url("s3://Models").is_err());
+    }
+
+    #[test]
+    fn test_verify_policy_parse() {
+        assert_eq!(
+            "always".parse::<VerifyPolicy>().unwrap(),
+            VerifyPolicy::Always
+        );
+        assert_eq!(
+            "sampled".parse::<VerifyPolicy>().unwrap(),
+            VerifyPolicy::Sampled(VerifyPolicy::DEFAULT_SAMPLE)
+        );
+        let policy: VerifyPolicy = "sampled:5%".parse().unwrap();
+        assert_eq!(policy, VerifyPolicy::Sampled(0.05));
+        assert_eq!(policy.to_string().parse::<VerifyPolicy>().unwrap(), policy);
+        assert!("sometimes".parse::<VerifyPolicy>().is_err());
+        assert!("sampled:0%".parse::<VerifyPolicy>().is_err());
+        #[cfg(feature = "native")]
+        assert!(!VerifyPolicy::Never.should_verify());
+    }
+
+    #[test]
+    fn test_validate_path_safe() {
+        assert!(validate_path_safe("weights/model.bin").is_ok());
+        assert!(validate_path_safe("notes:v1.txt").is_ok());
+        for path in ["../x", "/etc/x", "\\\\server\\x", "C:\\x", "c:x"] {
+            assert!(validate_path_safe(path).is_err(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::errors::{Result, VektError};
+use notify::{EventKind, RecursiveMode, Watcher};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+static STEP_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_step_regex() -> &'static Regex {
+    STEP_REGEX.get_or_init(|| {
+        Regex::new(r"(?i)(?:^|[^a-z])(?:step|checkpoint|ckpt|iter|iteration)[-_]?(\d+)").unwrap()
+    })
+}
+
+/// Options for [`watch_checkpoints`]
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// File name pattern; supports `*` and `?` wildcards
+    pub pattern: String,
+    /// How long a file must stay unchanged before it is considered complete
+    pub settle: Duration,
+    pub recursive: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            pattern: "*.safetensors".to_string(),
+            settle: Duration::from_secs(2),
+            recursive: false,
+        }
+    }
+}
+
+/// A checkpoint file that finished being written
+#[derive(Debug, Clone)]
+pub struct CheckpointEvent {
+    pub path: PathBuf,
+    /// Training step parsed from the file name, if any
+    pub step: Option<u64>,
+}
+
+/// Matches a file name against a glob pattern with `*` and `?` wildcards
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative wildcard matching with single-star backtracking
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((sp, sn)) = star {
+            p = sp + 1;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Parses a training step from a checkpoint file name, e.g. `model-step-1200.safetensors`
+/// or `checkpoint_500.safetensors`. Other numbers in the name, like a model size or
+/// run number, are not steps.
+pub fn parse_step(file_name: &str) -> Option<u64> {
+    let stem = file_name.split('.').next().unwrap_or(file_name);
+    get_step_regex().captures(stem)?[1].parse().ok()
+}
+
+fn file_signature(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Watches `dir` for new or updated files matching `options.pattern` and calls
+/// `on_ready` once each file has stopped changing for `options.settle`.
+///
+/// Runs until the watcher fails or `on_ready` returns an error. Errors from
+/// `on_ready` stop the watch so callers decide whether a failed add is fatal.
+pub fn watch_checkpoints<F>(dir: &Path, options: &WatchOptions, mut on_ready: F) -> Result<()>
+where
+    F: FnMut(CheckpointEvent) -> Result<()>,
+{
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| VektError::WatchError(format!("Failed to create watcher: {}", e)))?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(dir, mode)
+        .map_err(|e| VektError::WatchError(format!("Failed to watch {}: {}", dir.display(), e)))?;
+
+    // Path -> (last observed signature, when it was observed)
+    let mut pending: HashMap<PathBuf, (Option<(u64, SystemTime)>, Instant)> = HashMap::new();
+    let poll = (options.settle / 4).max(Duration::from_millis(50));
+
+    loop {
+        match rx.recv_timeout(poll) {
+            Ok(Ok(event)) => {
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    continue;
+                }
+                for path in event.paths {
+                    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                        continue;
+                    };
+                    if matches_pattern(&options.pattern, name) {
+                        pending.insert(path.clone(), (file_signature(&path), Instant::now()));
+                    }
+                }
+            }
+            Ok(Err(e)) => return Err(VektError::WatchError(e.to_string())),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let mut ready = Vec::new();
+        for (path, (signature, since)) in pending.iter_mut() {
+            let current = file_signature(path);
+            if current != *signature {
+                *signature = current;
+                *since = Instant::now();
+            } else if current.is_some() && since.elapsed() >= options.settle {
+                ready.push(path.clone());
+            }
+        }
+        pending.retain(|_, (signature, _)| signature.is_some());
+
+        ready.sort();
+        for path in ready {
+            pending.remove(&path);
+            let step = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(parse_step);
+            on_ready(CheckpointEvent { path, step })?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*.safetensors", "model.safetensors"));
+        assert!(matches_pattern(
+            "ckpt-??.safetensors",
+            "ckpt-01.safetensors"
+        ));
+        assert!(matches_pattern("*step*", "model-step-10.bin"));
+        assert!(!matches_pattern("*.safetensors", "model.safetensors.tmp"));
+        assert!(!matches_pattern(
+            "ckpt-?.safetensors",
+            "ckpt-10.safetensors"
+        ));
+    }
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(parse_step("model-step-1200.safetensors"), Some(1200));
+        assert_eq!(parse_step("checkpoint_500.safetensors"), Some(500));
+        assert_eq!(parse_step("llama2-7b-ckpt3.safetensors"), Some(3));
+        assert_eq!(parse_step("iter_9.safetensors"), Some(9));
+        assert_eq!(parse_step("run7-000042.safetensors"), None);
+        assert_eq!(parse_step("epoch-3.safetensors"), None);
+        assert_eq!(parse_step("timestep64.safetensors"), None);
+        assert_eq!(parse_step("model.safetensors"), None);
+    }
+}
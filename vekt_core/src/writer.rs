@@ -0,0 +1,343 @@
+use crate::blobs;
+use crate::cancel::{self, CancellationToken};
+use crate::direct_io;
+use crate::errors::{Result, VektError};
+use crate::storage::{ManifestTensor, RawHeader, RawTensorMetaData};
+use crate::utils::tensor_byte_size;
+use crate::validation::{VerifyPolicy, validate_tensor_name, verify_blob_hash};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Tensor data offsets are padded to this many bytes
+pub const ALIGNMENT: usize = 8;
+
+enum TensorSource {
+    Bytes(Vec<u8>),
+    /// Read from the blob store, and verified per the policy, when the file is written
+    Blob,
+}
+
+struct Entry {
+    name: String,
+    shape: Vec<usize>,
+    dtype: String,
+    extra: IndexMap<String, serde_json::Value>,
+    hash: String,
+    source: TensorSource,
+    // Data size in bytes, from shape and dtype
+    size: usize,
+}
+
+/// Builds a safetensors file from in-memory tensors and blob-store tensors.
+///
+/// Tensors are written in insertion order with 8-byte aligned offsets. Entries
+/// with identical contents share one copy of the data, as tied weights do.
+///
+/// ```no_run
+/// use vekt_core::SafetensorWriter;
+///
+/// let mut writer = SafetensorWriter::new();
+/// writer.add_tensor("bias", vec![2], "F32", [0f32, 1.0].iter().flat_map(|v| v.to_le_bytes()).collect())?;
+/// writer.write(std::path::Path::new("model.safetensors"))?;
+/// # Ok::<(), vekt_core::errors::VektError>(())
+/// ```
+#[derive(Default)]
+pub struct SafetensorWriter {
+    entries: Vec<Entry>,
+    names: HashSet<String>,
+    verify: VerifyPolicy,
+    cancel: CancellationToken,
+}
+
+impl SafetensorWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets which blob-store tensors are re-hashed on write. Defaults to all of them.
+    pub fn with_verify_policy(mut self, policy: VerifyPolicy) -> Self {
+        self.verify = policy;
+        self
+    }
+
+    /// Makes writing stop with [`VektError::Cancelled`] once `token` is cancelled;
+    /// [`write`](Self::write) then removes the partial file
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn push(&mut self, entry: Entry) -> Result<&mut Self> {
+        validate_tensor_name(&entry.name)?;
+        if !self.names.insert(entry.name.clone()) {
+            return Err(VektError::InvalidTensorName(format!(
+                "Duplicate tensor '{}'",
+                entry.name
+            )));
+        }
+        self.entries.push(entry);
+        Ok(self)
+    }
+
+    /// Adds a tensor from raw little-endian bytes
+    pub fn add_tensor(
+        &mut self,
+        name: &str,
+        shape: Vec<usize>,
+        dtype: &str,
+        data: Vec<u8>,
+    ) -> Result<&mut Self> {
+        let len = data.len();
+        let entry = Entry {
+            name: name.to_string(),
+            size: tensor_byte_size(&shape, dtype)?,
+            shape,
+            dtype: dtype.to_string(),
+            extra: IndexMap::new(),
+            hash: blobs::compute_blob_hash(&data),
+            source: TensorSource::Bytes(data),
+        };
+        if len != entry.size {
+            return Err(size_mismatch(&entry, len));
+        }
+        self.push(entry)
+    }
+
+    /// Adds a tensor whose data lives in the blob store. The blob is read, and
+    /// verified against its hash, only when the file is written.
+    pub fn add_manifest_tensor(
+        &mut self,
+        name: &str,
+        tensor: &ManifestTensor,
+    ) -> Result<&mut Self> {
+        self.push(Entry {
+            name: name.to_string(),
+            shape: tensor.shape.clone(),
+            dtype: tensor.dtype.clone(),
+            extra: tensor.extra.clone(),
+            hash: tensor.hash.clone(),
+            source: TensorSource::Blob,
+            size: tensor.byte_size()?,
+        })
+    }
+
+    /// Size of the file [`write_to`](Self::write_to) will produce
+    pub fn file_size(&self) -> Result<u64> {
+        let header = self.header();
+        let data_end = header
+            .values()
+            .map(|meta| meta.data_offsets.1)
+            .max()
+            .unwrap_or(0);
+        Ok((8 + serde_json::to_string(&header)?.len() + data_end) as u64)
+    }
+
+    /// The header that [`write_to`](Self::write_to) will emit
+    pub fn header(&self) -> RawHeader {
+        let mut header: RawHeader = IndexMap::new();
+        // Hash -> offsets of the copy already laid out
+        let mut placed: HashMap<&str, (usize, usize)> = HashMap::new();
+        let mut offset = 0;
+
+        for entry in &self.entries {
+            let data_offsets = *placed.entry(&entry.hash).or_insert_with(|| {
+                offset += (ALIGNMENT - offset % ALIGNMENT) % ALIGNMENT;
+                let start = offset;
+                offset += entry.size;
+                (start, offset)
+            });
+            header.insert(
+                entry.name.clone(),
+                RawTensorMetaData {
+                    shape: entry.shape.clone(),
+                    dtype: entry.dtype.clone(),
+                    data_offsets,
+                    extra: entry.extra.clone(),
+                },
+            );
+        }
+        header
+    }
+
+    /// Returns the length prefix and header, followed by the tensor data in the order
+    /// it appears in the file, shared data only once
+    pub(crate) fn plan(&self) -> Result<(Vec<u8>, Vec<PlannedData<'_>>)> {
+        let header_json = serde_json::to_string(&self.header())?;
+        let mut prefix = (header_json.len() as u64).to_le_bytes().to_vec();
+        prefix.extend_from_slice(header_json.as_bytes());
+
+        let mut position = 0;
+        let mut written: HashSet<&str> = HashSet::new();
+        let mut planned = Vec::new();
+        for entry in &self.entries {
+            if !written.insert(&entry.hash) {
+                continue;
+            }
+            let padding = (ALIGNMENT - position % ALIGNMENT) % ALIGNMENT;
+            position += padding + entry.size;
+            planned.push(PlannedData { padding, entry });
+        }
+        Ok((prefix, planned))
+    }
+
+    /// Writes the complete file to `writer` and returns the number of bytes written
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<u64> {
+        let (prefix, planned) = self.plan()?;
+        writer.write_all(&prefix)?;
+
+        let mut written = prefix.len();
+        for item in planned {
+            cancel::check(&self.cancel, "restore")?;
+            let blob;
+            let data = match item.bytes() {
+                Some(data) => data,
+                None => {
+                    blob = read_blob(item.hash(), item.name(), self.verify.should_verify())?;
+                    &blob
+                }
+            };
+            written += item.write_to(writer, data)?;
+        }
+
+        Ok(written as u64)
+    }
+
+    /// Writes the file to `path`, removing what was written if it fails
+    pub fn write(&self, path: &Path) -> Result<u64> {
+        let mut writer = direct_io::create(path)?;
+        let written = self
+            .write_to(&mut writer)
+            .and_then(|size| Ok(writer.finish().map(|_| size)?));
+        if written.is_err() {
+            let _ = fs::remove_file(path);
+        }
+        written
+    }
+}
+
+/// One tensor's data as placed by [`SafetensorWriter::plan`]
+pub(crate) struct PlannedData<'a> {
+    padding: usize,
+    entry: &'a Entry,
+}
+
+impl PlannedData<'_> {
+    pub(crate) fn name(&self) -> &str {
+        &self.entry.name
+    }
+
+    pub(crate) fn hash(&self) -> &str {
+        &self.entry.hash
+    }
+
+    /// The data, unless it has to be read from a blob
+    pub(crate) fn bytes(&self) -> Option<&[u8]> {
+        match &self.entry.source {
+            TensorSource::Bytes(data) => Some(data),
+            TensorSource::Blob => None,
+        }
+    }
+
+    /// Like [`write_to`](Self::write_to), copying the `len` bytes of data from
+    /// `reader` instead of holding them in memory
+    pub(crate) fn copy_to<W: Write, R: Read>(
+        &self,
+        writer: &mut W,
+        reader: R,
+        len: u64,
+    ) -> Result<usize> {
+        if len != self.entry.size as u64 {
+            return Err(size_mismatch(self.entry, len as usize));
+        }
+        writer.write_all(&[0u8; ALIGNMENT][..self.padding])?;
+        let copied = std::io::copy(&mut reader.take(len), writer)?;
+        if copied != len {
+            return Err(size_mismatch(self.entry, copied as usize));
+        }
+        Ok(self.padding + self.entry.size)
+    }
+
+    /// Writes the padding before the data and then `data`, after checking it has the
+    /// size the header promises. Returns the number of bytes written.
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W, data: &[u8]) -> Result<usize> {
+        if data.len() != self.entry.size {
+            return Err(size_mismatch(self.entry, data.len()));
+        }
+        writer.write_all(&[0u8; ALIGNMENT][..self.padding])?;
+        writer.write_all(data)?;
+        Ok(self.padding + data.len())
+    }
+}
+
+fn size_mismatch(entry: &Entry, actual: usize) -> VektError {
+    VektError::TensorCorruption(format!(
+        "Tensor '{}': {} {:?} needs {} bytes, got {}",
+        entry.name, entry.dtype, entry.shape, entry.size, actual
+    ))
+}
+
+fn read_blob(hash: &str, name: &str, verify: bool) -> Result<Vec<u8>> {
+    if !blobs::blob_exists(hash) {
+        return Err(VektError::BlobNotFound(format!(
+            "Blob {} not found for tensor '{}'",
+            hash, name
+        )));
+    }
+    let data = blobs::read_blob(hash)?;
+    if verify {
+        // Verify blob hash to detect corruption before it reaches the output
+        verify_blob_hash(&data, hash)?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ModelArchiver, SafetensorFile};
+
+    #[test]
+    fn test_writer_alignment_and_sharing() {
+        let mut writer = SafetensorWriter::new();
+        writer
+            .add_tensor("a", vec![3], "U8", vec![1, 2, 3])
+            .unwrap()
+            .add_tensor("b", vec![2], "F32", vec![0; 8])
+            .unwrap()
+            .add_tensor("tied", vec![3], "U8", vec![1, 2, 3])
+            .unwrap();
+        assert!(writer.add_tensor("a", vec![1], "U8", vec![0]).is_err());
+        assert!(writer.add_tensor("c", vec![2], "F32", vec![0; 4]).is_err());
+
+        let header = writer.header();
+        assert_eq!(header["a"].data_offsets, (0, 3));
+        assert_eq!(header["b"].data_offsets, (8, 16));
+        assert_eq!(header["tied"].data_offsets, (0, 3));
+
+        let path = std::env::temp_dir().join(format!(
+            "vekt_writer_test_{}.safetensors",
+            rand::random::<u64>()
+        ));
+        let size = writer.write(&path).unwrap();
+        assert_eq!(size, std::fs::metadata(&path).unwrap().len());
+
+        let reopened = SafetensorFile::open(&path.to_string_lossy())
+            .unwrap()
+            .process(false)
+            .unwrap();
+        assert_eq!(reopened.tensors["a"].hash, reopened.tensors["tied"].hash);
+        assert_eq!(reopened.tensors["b"].shape, vec![2]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
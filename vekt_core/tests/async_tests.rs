@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+
+use vekt_core::async_api::process_async;
+use vekt_core::storage::VektManifest;
+use vekt_core::tensor_filter::TensorFilter;
+use vekt_core::validation::VerifyPolicy;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_process_and_restore() {
+    let root = env::temp_dir().join(format!("vekt_async_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut models = Vec::new();
+    for i in 0..4u8 {
+        let mut manifest = VektManifest::new();
+        manifest
+            .add_tensor("w", vec![16], "U8", &[i; 16])
+            .unwrap()
+            .add_tensor("b", vec![2], "U8", &[i, i])
+            .unwrap();
+        let path = root.join(format!("model{}.safetensors", i));
+        manifest.restore(&path, None).unwrap();
+        models.push(path);
+    }
+
+    // More operations than worker threads, all in flight at once
+    let tasks: Vec<_> = models
+        .iter()
+        .map(|path| {
+            let path = path.clone();
+            tokio::spawn(async move {
+                let manifest = process_async(&path, true).await.unwrap();
+                let manifest_path = path.with_extension("vekt.json");
+                manifest.save_async(&manifest_path).await.unwrap();
+
+                let loaded = VektManifest::load_async(&manifest_path).await.unwrap();
+                let output = path.with_extension("restored.safetensors");
+                loaded
+                    .restore_async(&output, TensorFilter::default(), VerifyPolicy::Always)
+                    .await
+                    .unwrap();
+                (path, output)
+            })
+        })
+        .collect();
+    for task in tasks {
+        let (original, restored) = task.await.unwrap();
+        assert_eq!(fs::read(restored).unwrap(), fs::read(original).unwrap());
+    }
+
+    assert!(
+        VektManifest::load_async(root.join("missing.vekt.json"))
+            .await
+            .is_err()
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
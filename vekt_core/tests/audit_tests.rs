@@ -0,0 +1,61 @@
+use std::env;
+use std::fs;
+
+use vekt_core::audit::{self, AuditEntry, TransferDigest, UploadVerification};
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_transfer_digest_and_audit_log() {
+    let root = env::temp_dir().join(format!("vekt_audit_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut base = VektManifest::new();
+    base.add_tensor("w", vec![2], "F32", &[1; 8]).unwrap();
+    let mut tuned = VektManifest::new();
+    tuned
+        .add_tensor("w", vec![2], "F32", &[1; 8])
+        .unwrap()
+        .add_tensor("b", vec![1], "F32", &[2; 4])
+        .unwrap();
+
+    // The pushing and pulling side see manifests in different orders
+    let mut pushed = TransferDigest::new();
+    pushed.add(&base).unwrap();
+    pushed.add(&tuned).unwrap();
+    let mut pulled = TransferDigest::new();
+    pulled.add(&tuned).unwrap();
+    pulled.add(&base).unwrap();
+    pulled.add(&tuned).unwrap();
+    assert_eq!(pushed.digest(), pulled.digest());
+    assert_eq!((pulled.manifest_count(), pulled.blob_count()), (2, 2));
+
+    let mut other = TransferDigest::new();
+    other.add(&base).unwrap();
+    tuned.set_metadata("step", "200");
+    other.add(&tuned).unwrap();
+    assert_ne!(pushed.digest(), other.digest());
+
+    assert!(audit::read_log().unwrap().is_empty());
+    let mut push = AuditEntry::new("push", "origin", &pushed);
+    push.uploads = Some(UploadVerification {
+        verified: 3,
+        unconfirmed: 1,
+    });
+    audit::record(&push).unwrap();
+    audit::record(&AuditEntry::new("pull", "origin", &pulled)).unwrap();
+    let log = audit::read_log().unwrap();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].operation, "push");
+    assert_eq!(log[0].uploads, push.uploads);
+    assert_eq!(log[1].uploads, None);
+    assert_eq!(log[1].digest, pushed.digest());
+    assert_eq!(log[1].manifests.len(), 2);
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
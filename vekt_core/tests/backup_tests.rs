@@ -0,0 +1,106 @@
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use vekt_core::backup::{create_backup, restore_backup};
+use vekt_core::blobs;
+use vekt_core::errors::VektError;
+use vekt_core::hash::HashAlgorithm;
+use vekt_core::refs;
+use vekt_core::storage::{ManifestTensor, VektManifest};
+
+fn use_repo(root: &Path) {
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", root);
+    }
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let mut base = env::temp_dir();
+    base.push("vekt_backup_test");
+    base.push(format!("{}", rand::random::<u64>()));
+    let source = base.join("source");
+    use_repo(&source);
+
+    let data = [9u8, 8, 7, 6];
+    // A blob hashed before the repository switched algorithms keeps its sha256 name
+    let (legacy, _) =
+        blobs::write_blob_from_reader_as(&[5u8, 4, 3][..], HashAlgorithm::Sha256).unwrap();
+    assert_eq!(HashAlgorithm::of(&legacy), HashAlgorithm::Sha256);
+    let mut tensors = BTreeMap::new();
+    tensors.insert(
+        "legacy".to_string(),
+        ManifestTensor {
+            shape: vec![3],
+            dtype: "U8".to_string(),
+            hash: legacy.clone(),
+            index: 1,
+            extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
+        },
+    );
+    tensors.insert(
+        "weight".to_string(),
+        ManifestTensor {
+            shape: vec![4],
+            dtype: "U8".to_string(),
+            hash: blobs::write_blob_atomic(&data).unwrap(),
+            index: 0,
+            extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
+        },
+    );
+    let manifest = VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 7,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+    let hash = refs::create_tag("v1", &manifest, false).unwrap();
+    // Blobs only the working tree's manifests reference are backed up too
+    let mut working = VektManifest::new();
+    working.add_tensor("draft", vec![2], "U8", &[3, 1]).unwrap();
+    working.save(&source.join("draft.vekt.json")).unwrap();
+
+    let metadata_only = base.join("meta.tar.zst");
+    let info = create_backup(&metadata_only, false).unwrap();
+    assert_eq!(info.blobs, 0);
+    assert!(info.metadata_files >= 2);
+    let full = base.join("full.tar.zst");
+    assert_eq!(create_backup(&full, true).unwrap().blobs, 3);
+
+    // Metadata comes back without blobs, and the gap is reported
+    let target = base.join("target");
+    use_repo(&target);
+    let summary = restore_backup(&metadata_only, false).unwrap();
+    assert_eq!(summary.blobs, 0);
+    assert_eq!(summary.missing_blobs, 2);
+    assert_eq!(summary.incomplete[0].0, hash);
+    assert_eq!(refs::read_tag("v1").unwrap(), hash);
+
+    assert!(matches!(
+        restore_backup(&full, false),
+        Err(VektError::ConflictDetected(_))
+    ));
+    let summary = restore_backup(&full, true).unwrap();
+    assert_eq!(summary.blobs, 3);
+    assert_eq!(summary.missing_blobs, 0);
+    assert_eq!(blobs::read_blob(&legacy).unwrap(), [5, 4, 3]);
+    assert_eq!(
+        refs::resolve_manifest("v1").unwrap().tensors["weight"].hash,
+        manifest.tensors["weight"].hash
+    );
+    assert!(!target.join(".vekt").join("lock").exists());
+
+    let _ = fs::remove_dir_all(&base);
+}
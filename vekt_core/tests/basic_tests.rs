@@ -4,6 +4,7 @@ use std::fs::File;
 use std::io::Write;
 
 // Import from the public API of the crate
+use vekt_core::errors::VektError;
 use vekt_core::storage::{RawHeader, RawTensorMetaData};
 use vekt_core::{ModelArchiver, SafetensorFile};
 
@@ -87,3 +88,33 @@ fn test_safetensor_process() {
     assert!(manifest.tensors.contains_key("tensor1"));
     assert_eq!(manifest.tensors["tensor1"].index, 0);
 }
+
+#[test]
+fn test_safetensor_open_checks_dtypes() -> Result<(), Box<dyn std::error::Error>> {
+    let open = |dtype: &str, end: usize| {
+        let path =
+            std::env::temp_dir().join(format!("vekt_dtype_{}.safetensors", rand::random::<u64>()));
+        let header = format!(
+            r#"{{"t": {{"dtype": "{}", "shape": [2, 2], "data_offsets": [0, {}]}}}}"#,
+            dtype, end
+        );
+        let mut file = (header.len() as u64).to_le_bytes().to_vec();
+        file.extend_from_slice(header.as_bytes());
+        file.resize(file.len() + end, 0);
+        std::fs::write(&path, file).unwrap();
+        let opened = SafetensorFile::open(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        opened
+    };
+
+    assert!(open("F8_E5M2", 4).is_ok());
+    assert!(open("U32", 16).is_ok());
+    assert!(open("I4", 2).is_ok());
+    // An unknown dtype used to count as one byte per element
+    assert!(matches!(open("Q8", 4), Err(VektError::UnsupportedDtype(_))));
+    assert!(matches!(
+        open("F64", 16),
+        Err(VektError::InvalidSafetensor(_))
+    ));
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blob_index::BlobIndex;
+use vekt_core::blobs;
+use vekt_core::gc;
+
+#[test]
+fn test_blob_index_follows_the_store() {
+    let root = env::temp_dir().join(format!("vekt_blob_index_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    // The first open lists the store
+    let (a, _) = blobs::save_blob_deduplicated(&[1; 100]).unwrap();
+    let index = BlobIndex::open().unwrap();
+    assert!(index.contains(&a));
+    assert!(!index.contains(&a[..10]));
+    assert!(root.join(".vekt/blob-index.json").exists());
+    assert!(!root.join(".vekt/blob-index.log").exists());
+
+    // Later writes are replayed from the log
+    let (b, _) = blobs::save_blob_deduplicated(&[2; 50]).unwrap();
+    let index = BlobIndex::open().unwrap();
+    assert!(index.contains(&a) && index.contains(&b));
+    assert_eq!(index.blob_count(), 2);
+    assert_eq!(index.total_size(), 150);
+
+    // Rewritten in place: only a rebuild sees the new size
+    fs::write(blobs::get_blob_path(&a), [0; 16]).unwrap();
+    assert_eq!(BlobIndex::open().unwrap().total_size(), 150);
+    assert_eq!(BlobIndex::rebuild().unwrap().total_size(), 66);
+    assert!(!root.join(".vekt/blob-index.log").exists());
+
+    // Files added or removed behind its back are picked up on the next open. They're
+    // noticed by the store's modification time, so let the clock move on first.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::remove_file(blobs::get_blob_path(&b)).unwrap();
+    fs::write(root.join(".vekt/blobs").join(format!("{}.zst", b)), [0; 10]).unwrap();
+    let index = BlobIndex::open().unwrap();
+    assert!(index.contains(&b));
+    assert_eq!(index.total_size(), 26);
+
+    // Nothing references either blob; gc deletes them and the index follows
+    assert_eq!(gc::run_gc(&root).unwrap().deleted, 2);
+    assert_eq!(BlobIndex::open().unwrap().blob_count(), 0);
+
+    // A long log is folded into the saved index
+    for i in 0..4100u32 {
+        blobs::save_blob_deduplicated(&i.to_le_bytes()).unwrap();
+    }
+    assert_eq!(BlobIndex::open().unwrap().blob_count(), 4100);
+    assert!(!root.join(".vekt/blob-index.log").exists());
+    assert_eq!(BlobIndex::open().unwrap().blob_count(), 4100);
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,99 @@
+use std::env;
+use std::fs;
+
+use vekt_core::storage::VektManifest;
+use vekt_core::{ModelArchiver, SafetensorFile};
+
+#[test]
+fn test_build_and_edit_manifest() {
+    let root = env::temp_dir().join(format!("vekt_builder_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let weight: Vec<u8> = (0..24).collect();
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("embed", vec![2], "F32", &[1; 8])
+        .unwrap()
+        .add_tensor_from_reader("weight", vec![2, 3], "F32", weight.as_slice())
+        .unwrap()
+        .add_tensor("bias", vec![3], "F16", &[2; 6])
+        .unwrap()
+        .set_metadata("step", "100");
+
+    // Invariants are enforced
+    assert!(manifest.add_tensor("bias", vec![1], "U8", &[0]).is_err());
+    assert!(
+        manifest
+            .add_tensor("short", vec![4], "F32", &[0; 8])
+            .is_err()
+    );
+    assert!(manifest.add_tensor("../evil", vec![1], "U8", &[0]).is_err());
+    // Data of the wrong size is refused before it reaches the store
+    let stored = || fs::read_dir(root.join(".vekt/blobs")).unwrap().count();
+    let before = stored();
+    assert!(
+        manifest
+            .add_tensor("long", vec![1], "U8", &[7, 7, 7])
+            .is_err()
+    );
+    for data in [&[7u8; 3][..], &[7; 5]] {
+        assert!(
+            manifest
+                .add_tensor_from_reader("long", vec![4], "U8", data)
+                .is_err()
+        );
+    }
+    assert_eq!(stored(), before);
+    assert!(manifest.rename_tensor("missing", "x").is_err());
+    assert!(manifest.rename_tensor("bias", "weight").is_err());
+
+    manifest.rename_tensor("embed", "embed_tokens").unwrap();
+    let removed = manifest.remove_tensor("weight").unwrap().unwrap();
+    assert_eq!(removed.index, 1);
+    assert!(manifest.remove_tensor("weight").unwrap().is_none());
+    manifest
+        .add_tensor_blob("lm_head", vec![2, 3], "F32", &removed.hash)
+        .unwrap();
+    manifest.validate().unwrap();
+
+    let indices: Vec<(&str, usize)> = manifest
+        .tensors
+        .iter()
+        .map(|(n, t)| (n.as_str(), t.index))
+        .collect();
+    assert_eq!(indices, [("bias", 1), ("embed_tokens", 0), ("lm_head", 2)]);
+
+    // Appends continue from the running offset; a tensor sharing data with another
+    // takes no space of its own
+    manifest
+        .add_tensor("tied", vec![3], "F16", &[2; 6])
+        .unwrap();
+    let appended = manifest.total_size;
+    assert_eq!(manifest.recompute_total_size().unwrap(), appended);
+
+    // total_size is exactly what a restore writes
+    let path = root.join("built.safetensors");
+    manifest.restore(&path, None).unwrap();
+    assert_eq!(
+        fs::metadata(&path).unwrap().len() as usize,
+        manifest.total_size
+    );
+    let restored = SafetensorFile::open(path.to_str().unwrap())
+        .unwrap()
+        .process(false)
+        .unwrap();
+    assert_eq!(restored.tensors["lm_head"].hash, removed.hash);
+    assert_eq!(restored.tensors["bias"].index, 1);
+
+    let mut broken = manifest.clone();
+    broken.tensors.get_mut("bias").unwrap().index = 0;
+    assert!(broken.validate().is_err());
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
@@ -0,0 +1,118 @@
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use vekt_core::blobs;
+use vekt_core::bundle::{create_bundle, read_bundle_info, unpack_bundle};
+use vekt_core::errors::VektError;
+use vekt_core::refs;
+use vekt_core::storage::{ManifestTensor, VektManifest};
+
+fn use_repo(root: &Path) {
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", root);
+    }
+}
+
+fn manifest(hashes: &[&str]) -> VektManifest {
+    let tensors = hashes
+        .iter()
+        .enumerate()
+        .map(|(index, hash)| {
+            let tensor = ManifestTensor {
+                shape: vec![4],
+                dtype: "U8".to_string(),
+                hash: hash.to_string(),
+                index,
+                extra: IndexMap::new(),
+                ranges: None,
+                stats: None,
+            };
+            (format!("layer.{}", index), tensor)
+        })
+        .collect();
+    VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: hashes.len() * 4,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    }
+}
+
+#[test]
+fn test_bundle_create_and_unpack() {
+    let base = env::temp_dir().join(format!("vekt_bundle_{}", rand::random::<u64>()));
+    let source = base.join("source");
+    use_repo(&source);
+
+    let shared = blobs::write_blob_atomic(&[1, 2, 3, 4]).unwrap();
+    let own = blobs::write_blob_atomic(&[5, 6, 7, 8]).unwrap();
+    let base_model = manifest(&[&shared]);
+    let finetune = manifest(&[&shared, &own]);
+    let bundle = base.join("models.vektbundle");
+    let info = create_bundle(
+        &bundle,
+        &[
+            ("base.vekt.json".to_string(), base_model),
+            ("finetune.vekt.json".to_string(), finetune.clone()),
+        ],
+    )
+    .unwrap();
+    // Blobs shared between manifests are stored once
+    assert_eq!(info.blobs, 2);
+    assert_eq!(info.blob_bytes, 8);
+    assert_eq!(read_bundle_info(&bundle).unwrap(), info);
+
+    // Bundles only carry what the store has
+    let missing = manifest(&[&"0".repeat(64)]);
+    assert!(matches!(
+        create_bundle(
+            &base.join("missing.vektbundle"),
+            &[("missing.vekt.json".to_string(), missing)]
+        ),
+        Err(VektError::BlobNotFound(_))
+    ));
+
+    let target = base.join("target");
+    use_repo(&target);
+    let summary = unpack_bundle(&bundle, &target, false).unwrap();
+    assert_eq!(summary.blobs, 2);
+    assert_eq!(summary.manifests.len(), 2);
+    assert_eq!(blobs::read_blob(&own).unwrap(), [5, 6, 7, 8]);
+    let unpacked = VektManifest::load(&target.join("finetune.vekt.json")).unwrap();
+    assert_eq!(unpacked.tensors["layer.1"].hash, own);
+    let object = blobs::compute_blob_hash(&refs::manifest_object_bytes(&finetune).unwrap());
+    assert_eq!(
+        refs::load_manifest_object(&object).unwrap().tensors.len(),
+        2
+    );
+
+    // Unpacking again is a no-op, but a different manifest under the same name is kept
+    assert_eq!(
+        unpack_bundle(&bundle, &target, false)
+            .unwrap()
+            .existing_blobs,
+        2
+    );
+    fs::write(
+        target.join("base.vekt.json"),
+        serde_json::to_vec(&finetune).unwrap(),
+    )
+    .unwrap();
+    assert!(matches!(
+        unpack_bundle(&bundle, &target, false),
+        Err(VektError::ConflictDetected(_))
+    ));
+    unpack_bundle(&bundle, &target, true).unwrap();
+    let restored = VektManifest::load(&target.join("base.vekt.json")).unwrap();
+    assert_eq!(restored.tensors.len(), 1);
+
+    let _ = fs::remove_dir_all(&base);
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::cache::{self, Eviction};
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_evict_least_recently_used() {
+    let root = env::temp_dir().join(format!("vekt_cache_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let (old, _) = blobs::save_blob_deduplicated(&[1; 100]).unwrap();
+    let (newest, _) = blobs::save_blob_deduplicated(&[2; 100]).unwrap();
+    let (middle, _) = blobs::save_blob_deduplicated(&[3; 100]).unwrap();
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("w", vec![100], "U8", &[4; 100])
+        .unwrap();
+    let tagged = manifest.tensors["w"].hash.clone();
+    refs::write_tag(
+        "prod",
+        &refs::store_manifest_object(&manifest).unwrap(),
+        false,
+    )
+    .unwrap();
+
+    // The tagged blob is the least recently used, but tags protect it
+    let access: BTreeMap<&String, u64> =
+        BTreeMap::from([(&tagged, 0), (&old, 1), (&middle, 2), (&newest, 3)]);
+    fs::write(
+        root.join(".vekt/access.json"),
+        serde_json::to_vec(&access).unwrap(),
+    )
+    .unwrap();
+
+    assert!(cache::protected_hashes().unwrap().contains(&tagged));
+    assert_eq!(
+        cache::evict(400, &HashSet::new(), &HashSet::new())
+            .unwrap()
+            .evicted,
+        0
+    );
+    assert_eq!(
+        cache::evict(250, &HashSet::new(), &HashSet::new()).unwrap(),
+        Eviction {
+            evicted: 2,
+            freed: 200,
+            remaining: 200,
+        }
+    );
+    assert!(!blobs::blob_exists(&old));
+    assert!(!blobs::blob_exists(&middle));
+    assert!(blobs::blob_exists(&newest));
+    assert!(blobs::blob_exists(&tagged));
+
+    // Kept blobs stay even when the store can't get under the cap
+    let (again, _) = blobs::save_blob_deduplicated(&[1; 100]).unwrap();
+    cache::touch([&again]).unwrap();
+    let eviction = cache::evict(0, &HashSet::from([again.clone()]), &HashSet::new()).unwrap();
+    assert_eq!(eviction.evicted, 1);
+    assert_eq!(eviction.remaining, 200);
+    assert!(!blobs::blob_exists(&newest));
+
+    // A working-tree manifest's blob stays until a remote is known to have it
+    let mut working = VektManifest::new();
+    working.add_tensor("w", vec![100], "U8", &[5; 100]).unwrap();
+    working.save(&root.join("model.vekt.json")).unwrap();
+    let current = working.tensors["w"].hash.clone();
+    assert_eq!(
+        cache::evict(0, &HashSet::new(), &HashSet::new())
+            .unwrap()
+            .evicted,
+        1
+    );
+    assert!(blobs::blob_exists(&current));
+    let eviction = cache::evict(0, &HashSet::new(), &HashSet::from([current.clone()])).unwrap();
+    assert_eq!(eviction.evicted, 1);
+    assert!(!blobs::blob_exists(&current));
+    assert!(blobs::blob_exists(&tagged));
+
+    let _ = fs::remove_dir_all(&root);
+}
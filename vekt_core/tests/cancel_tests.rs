@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+use vekt_core::cancel::{self, CancellationToken};
+use vekt_core::errors::{VektError, exit_code};
+use vekt_core::storage::VektManifest;
+use vekt_core::tensor_filter::TensorFilter;
+use vekt_core::validation::VerifyPolicy;
+use vekt_core::{ModelArchiver, SafetensorFile};
+
+#[tokio::test]
+async fn test_cancelled_operations_clean_up() {
+    let root = env::temp_dir().join(format!("vekt_cancel_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("a", vec![4], "U8", &[1, 2, 3, 4])
+        .unwrap()
+        .add_tensor("b", vec![4], "U8", &[5, 6, 7, 8])
+        .unwrap();
+    let model = root.join("model.safetensors");
+    manifest.restore(&model, None).unwrap();
+
+    let token = CancellationToken::new();
+    token.cancel();
+
+    let processed = SafetensorFile::open(model.to_str().unwrap())
+        .unwrap()
+        .with_cancel(token.clone())
+        .process(true);
+    let err = processed.unwrap_err();
+    assert!(matches!(err, VektError::Cancelled(_)));
+    assert_eq!(err.exit_code(), exit_code::CANCELLED);
+
+    // The partial output is removed
+    let output = root.join("restored.safetensors");
+    let restored = manifest.restore_cancellable(
+        &output,
+        &TensorFilter::default(),
+        VerifyPolicy::Always,
+        &token,
+    );
+    assert!(matches!(restored, Err(VektError::Cancelled(_))));
+    assert!(!output.exists());
+
+    let writer = manifest
+        .tensors
+        .iter()
+        .try_fold(
+            vekt_core::SafetensorWriter::new(),
+            |mut writer, (name, tensor)| {
+                writer.add_manifest_tensor(name, tensor)?;
+                Ok::<_, VektError>(writer)
+            },
+        )
+        .unwrap()
+        .with_cancel(token);
+    assert!(matches!(
+        writer.write(&output),
+        Err(VektError::Cancelled(_))
+    ));
+    assert!(!output.exists());
+
+    // A deadline cancels whatever is still running when it passes
+    let deadline = CancellationToken::new();
+    cancel::cancel_after(&deadline, Duration::from_millis(20));
+    let slow = cancel::until_cancelled(&deadline, "sleep", async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok(())
+    });
+    assert!(matches!(slow.await, Err(VektError::Cancelled(_))));
+    assert_eq!(
+        cancel::until_cancelled(&CancellationToken::new(), "sum", async { Ok(1 + 1) })
+            .await
+            .unwrap(),
+        2
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
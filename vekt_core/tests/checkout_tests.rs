@@ -0,0 +1,97 @@
+use std::env;
+use std::fs;
+
+use vekt_core::checkout::{self, Materialized};
+use vekt_core::commits;
+use vekt_core::errors::VektError;
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_checkout_materializes_a_ref() {
+    let root = env::temp_dir().join(format!("vekt_checkout_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    fs::create_dir_all(root.join("vision")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut text = VektManifest::new();
+    text.add_tensor("w", vec![2], "F32", &[1; 8]).unwrap();
+    text.attach_bytes("config.json", b"{\"layers\": 2}")
+        .unwrap();
+    text.save(&root.join("text.vekt.json")).unwrap();
+    let mut vision = VektManifest::new();
+    vision.add_tensor("w", vec![2], "F32", &[2; 8]).unwrap();
+    vision.save(&root.join("vision/model.vekt.json")).unwrap();
+    let commit = commits::commit(&[], "two models").unwrap();
+    refs::create_tag("text-v1", &text, false).unwrap();
+
+    // A branch checks out every model it tracks
+    let dir = root.join("out");
+    let summary = checkout::checkout("main", &dir, false).unwrap();
+    let names: Vec<&str> = summary.files.iter().map(|(f, _)| f.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "text.safetensors",
+            "config.json",
+            "vision/model.safetensors"
+        ]
+    );
+    assert_eq!(
+        fs::read(dir.join("config.json")).unwrap(),
+        b"{\"layers\": 2}"
+    );
+    assert_ne!(summary.files[1].1, Materialized::Unchanged);
+    let restored = dir.join("text.safetensors");
+    let restored = vekt_core::SafetensorFile::open(restored.to_str().unwrap()).unwrap();
+    assert!(restored.header.contains_key("w"));
+
+    let recorded = checkout::read_checkout(&dir).unwrap().unwrap();
+    assert_eq!(recorded.spec, "main");
+    assert_eq!(recorded.commit.as_deref(), Some(commit.as_str()));
+    assert_eq!(checkout::list_checkouts().unwrap(), vec![recorded.clone()]);
+    assert!(recorded.modified().is_empty());
+
+    // Checking out again leaves untouched files alone
+    let again = checkout::checkout("main", &dir, false).unwrap();
+    assert!(
+        again
+            .files
+            .iter()
+            .all(|(_, how)| *how == Materialized::Unchanged)
+    );
+    fs::remove_file(dir.join("vision/model.safetensors")).unwrap();
+    assert_eq!(
+        checkout::read_checkout(&dir).unwrap().unwrap().modified(),
+        vec!["vision/model.safetensors"]
+    );
+
+    // A tag is a single model; files only the branch had are removed
+    let summary = checkout::checkout("text-v1", &dir, false).unwrap();
+    assert_eq!(summary.removed, vec!["text.safetensors"]);
+    assert!(dir.join("model.safetensors").is_file());
+    assert!(!dir.join("text.safetensors").exists());
+    assert_eq!(summary.checkout.commit, None);
+
+    // Files no checkout wrote are only overwritten with force
+    let other = root.join("other");
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("config.json"), b"mine").unwrap();
+    assert!(matches!(
+        checkout::checkout("text-v1", &other, false),
+        Err(VektError::ConflictDetected(_))
+    ));
+    assert_eq!(fs::read(other.join("config.json")).unwrap(), b"mine");
+    checkout::checkout("text-v1", &other, true).unwrap();
+    assert_eq!(
+        fs::read(other.join("config.json")).unwrap(),
+        b"{\"layers\": 2}"
+    );
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
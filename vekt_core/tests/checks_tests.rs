@@ -0,0 +1,75 @@
+use std::env;
+use std::fs;
+
+use vekt_core::checks::{self, CheckAction, CheckIssue, CheckPolicy};
+use vekt_core::errors::VektError;
+use vekt_core::numeric::encode_floats;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_checks_flag_poisoned_zero_and_drifting_tensors() {
+    let root = env::temp_dir().join(format!("vekt_checks_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let floats = |values: &[f64]| encode_floats(values, "F32").unwrap();
+    let mut reference = VektManifest::new();
+    reference
+        .add_tensor("a", vec![2], "F32", &floats(&[1.0, 1.0]))
+        .unwrap()
+        .add_tensor("b", vec![2], "F32", &floats(&[1.0, 1.0]))
+        .unwrap();
+    reference.compute_stats(None).unwrap();
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("a", vec![2], "F32", &floats(&[100.0, 100.0]))
+        .unwrap()
+        .add_tensor("b", vec![2], "F32", &floats(&[0.0, 0.0]))
+        .unwrap()
+        .add_tensor("c", vec![2], "F32", &floats(&[f64::NAN, 1.0]))
+        .unwrap();
+
+    // Nothing to check before stats are recorded
+    let report = checks::check_manifest(&manifest, None, &CheckPolicy::default());
+    assert_eq!((report.checked, report.missing_stats), (0, 3));
+
+    manifest.compute_stats(None).unwrap();
+    let report = checks::check_manifest(&manifest, Some(&reference), &CheckPolicy::default());
+    assert_eq!(report.checked, 3);
+    let issues: Vec<_> = report
+        .findings
+        .iter()
+        .map(|f| (f.tensor.as_str(), &f.issue, f.action))
+        .collect();
+    assert!(matches!(
+        issues[..],
+        [
+            ("a", CheckIssue::Drift { .. }, CheckAction::Warn),
+            ("b", CheckIssue::AllZero, CheckAction::Warn),
+            ("b", CheckIssue::Drift { .. }, CheckAction::Warn),
+            (
+                "c",
+                CheckIssue::NonFinite { nan_count: 1, .. },
+                CheckAction::Fail
+            ),
+        ]
+    ));
+    assert!(matches!(
+        report.into_result(),
+        Err(VektError::CheckFailed(msg)) if msg.starts_with("c: 1 NaN")
+    ));
+
+    let lenient = CheckPolicy {
+        non_finite: CheckAction::Warn,
+        drift: CheckAction::Off,
+        ..Default::default()
+    };
+    let report = checks::check_manifest(&manifest, Some(&reference), &lenient);
+    assert_eq!(report.warnings().count(), 2);
+    assert!(report.into_result().is_ok());
+
+    let _ = fs::remove_dir_all(&root);
+}
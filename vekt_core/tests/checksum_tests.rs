@@ -0,0 +1,83 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::stream::ingest_reader;
+use vekt_core::{ModelArchiver, SafetensorFile};
+
+fn safetensors(header: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut file = (header.len() as u64).to_le_bytes().to_vec();
+    file.extend_from_slice(header);
+    file.extend_from_slice(data);
+    file
+}
+
+#[test]
+fn test_checksum_restores_exact_file() {
+    let root = env::temp_dir().join(format!("vekt_checksum_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    // Unusual header spacing, padding between tensors and trailing bytes: a plain
+    // restore would normalize all of it
+    let header = br#"{ "b": {"dtype": "U8", "shape": [4], "data_offsets": [8, 12]},
+        "a": {"dtype": "U8", "shape": [3], "data_offsets": [0, 3]} }  "#;
+    let original = safetensors(header, &[1, 2, 3, 9, 9, 9, 9, 9, 4, 5, 6, 7, 0xff]);
+    let path = root.join("model.safetensors");
+    fs::write(&path, &original).unwrap();
+
+    let manifest = SafetensorFile::open(path.to_str().unwrap())
+        .unwrap()
+        .process(true)
+        .unwrap();
+    let checksum = manifest.checksum.clone().unwrap();
+    assert_eq!(checksum.size, original.len() as u64);
+    assert_eq!(checksum.hash, blake3::hash(&original).to_hex().to_string());
+    assert!(
+        manifest
+            .referenced_hashes()
+            .any(|hash| Some(hash) == checksum.skeleton.as_ref())
+    );
+
+    manifest.verify_checksum().unwrap();
+    manifest.verify_file(&path).unwrap();
+    let restored = root.join("restored.safetensors");
+    manifest.restore_exact(&restored).unwrap();
+    assert_eq!(fs::read(&restored).unwrap(), original);
+
+    let mut changed = original.clone();
+    *changed.last_mut().unwrap() = 0;
+    fs::write(&path, &changed).unwrap();
+    assert!(matches!(
+        manifest.verify_file(&path),
+        Err(VektError::HashMismatch { .. })
+    ));
+
+    // Streamed ingests record one too, with the header metadata kept
+    let header = br#"{"__metadata__": {"format": "pt"}, "a": {"dtype": "U8", "shape": [3], "data_offsets": [0, 3]}}"#;
+    let input = safetensors(header, &[1, 2, 3, 0, 0]);
+    let streamed = ingest_reader(&input[..], &|_| {}).unwrap();
+    let mut written = Vec::new();
+    let checksum = streamed.restore_exact_to_writer(&mut written).unwrap();
+    assert_eq!(written, input);
+    assert_eq!(checksum, {
+        let mut expected = streamed.checksum.clone().unwrap();
+        expected.skeleton = None;
+        expected
+    });
+
+    // Edited manifests no longer describe the file that was added
+    let mut edited = manifest.clone();
+    edited.remove_tensor("a").unwrap();
+    assert!(edited.checksum.is_none());
+    assert!(matches!(
+        edited.restore_exact(&restored),
+        Err(VektError::InvalidManifest(_))
+    ));
+    // Nothing was written over the earlier restore
+    assert_eq!(fs::read(&restored).unwrap(), original);
+
+    let _ = fs::remove_dir_all(&root);
+}
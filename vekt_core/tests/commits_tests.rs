@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+
+use vekt_core::commits;
+use vekt_core::errors::VektError;
+use vekt_core::refs::{self, Head};
+use vekt_core::storage::VektManifest;
+
+fn manifest(total_size: usize) -> VektManifest {
+    VektManifest {
+        tensors: BTreeMap::new(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    }
+}
+
+#[test]
+fn test_branch_lifecycle() {
+    let mut root = env::temp_dir();
+    root.push("vekt_commits_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+    let model = root.join("model.vekt.json");
+
+    // Unborn main: nothing to commit without manifests
+    assert!(matches!(
+        commits::commit(&[], "empty"),
+        Err(VektError::NothingToCommit(_))
+    ));
+
+    manifest(1).save(&model).unwrap();
+    let base = commits::commit(&[], "base").unwrap();
+    assert_eq!(refs::read_branch("main").unwrap(), Some(base.clone()));
+    assert!(matches!(
+        commits::commit(&[], "again"),
+        Err(VektError::NothingToCommit(_))
+    ));
+
+    // Fine-tune on a separate line
+    commits::create_branch("lora", None).unwrap();
+    commits::switch_branch("lora", false).unwrap();
+    manifest(2).save(&model).unwrap();
+    let lora = commits::commit(&[], "lora run").unwrap();
+
+    // Dirty manifests block switching
+    manifest(3).save(&model).unwrap();
+    assert!(matches!(
+        commits::switch_branch("main", false),
+        Err(VektError::UncommittedChanges(_))
+    ));
+    let summary = commits::switch_branch("main", true).unwrap();
+    assert_eq!(summary.written, vec!["model.vekt.json".to_string()]);
+    assert_eq!(VektManifest::load(&model).unwrap().total_size, 1);
+    assert_eq!(refs::read_head().unwrap(), Head::Branch("main".to_string()));
+
+    manifest(4).save(&model).unwrap();
+    let main = commits::commit(&[], "main update").unwrap();
+
+    // History and resolution
+    let history = commits::walk_history(&[main.clone(), lora.clone()]).unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.last().unwrap().0, base);
+    assert_eq!(refs::resolve_manifest("lora").unwrap().total_size, 2);
+    assert_eq!(
+        refs::resolve_manifest("main:model.vekt.json")
+            .unwrap()
+            .total_size,
+        4
+    );
+    assert_eq!(commits::resolve_commit(&base[..10]).unwrap(), base);
+
+    let graph = commits::render_log(&[main, lora], true, &HashMap::new()).unwrap();
+    let lines: Vec<&str> = graph.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("* "));
+    assert!(lines[1].starts_with("| * "));
+    assert_eq!(lines[2], "|/");
+    assert!(lines[3].starts_with("* ") && lines[3].ends_with("base"));
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
@@ -0,0 +1,127 @@
+//! A scripted HTTP server for tests that stand in for remotes `vekt serve` can't
+//! play: LFS servers, the Hub, and connections that drop partway through.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// The `Range` header's first and, if given, last byte
+    pub fn range(&self) -> Option<(usize, Option<usize>)> {
+        let (first, last) = self
+            .header("range")?
+            .strip_prefix("bytes=")?
+            .split_once('-')?;
+        Some((first.parse().ok()?, last.parse().ok()))
+    }
+}
+
+pub struct Response {
+    pub status: &'static str,
+    pub body: Vec<u8>,
+    /// Drops the connection after this many bytes of the body, still announcing
+    /// its full length
+    pub cut: Option<usize>,
+}
+
+impl Response {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status: "200 OK",
+            body: body.into(),
+            cut: None,
+        }
+    }
+
+    pub fn status(status: &'static str) -> Self {
+        Response {
+            status,
+            body: Vec::new(),
+            cut: None,
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Response::status("404 Not Found")
+    }
+
+    /// `data`, or the part the request's `Range` asks for
+    pub fn ranged(data: &[u8], request: &Request) -> Self {
+        match request.range() {
+            Some((first, last)) => Response {
+                status: "206 Partial Content",
+                body: data[first..last.map_or(data.len(), |last| last + 1)].to_vec(),
+                cut: None,
+            },
+            None => Response::ok(data),
+        }
+    }
+
+    pub fn cut(mut self, after: usize) -> Self {
+        self.cut = Some(after);
+        self
+    }
+}
+
+/// Answers each request with `handler`, one connection at a time, and returns the
+/// server's `http://` URL
+pub fn serve(mut handler: impl FnMut(Request) -> Response + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap_or((line, ""));
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            let length = headers
+                .get("content-length")
+                .map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let response = handler(Request {
+                method,
+                path,
+                headers,
+                body,
+            });
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.status,
+                response.body.len()
+            );
+            let sent = response.cut.unwrap_or(response.body.len());
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&response.body[..sent]);
+        }
+    });
+    format!("http://{}", addr)
+}
@@ -0,0 +1,79 @@
+use std::env;
+use std::fs;
+
+use serde_json::json;
+use vekt_core::config::{self, ConfigScope};
+use vekt_core::encoding::BlobEncoding;
+use vekt_core::storage::VektConfig;
+
+#[test]
+fn test_layers_merge_by_precedence() {
+    let base = env::temp_dir().join(format!("vekt_config_{}", rand::random::<u64>()));
+    let root = base.join("repo");
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+        env::set_var("XDG_CONFIG_HOME", base.join("user"));
+    }
+
+    config::set(ConfigScope::User, "remotes.shared", json!("s3://shared")).unwrap();
+    config::set(ConfigScope::User, "concurrency", json!(4)).unwrap();
+    config::set(ConfigScope::Repo, "remotes.origin", json!("s3://origin")).unwrap();
+    config::set(ConfigScope::Repo, "concurrency", json!(16)).unwrap();
+    config::set(ConfigScope::Repo, "compression", json!("zstd")).unwrap();
+    assert!(base.join("user/vekt/config.json").exists());
+
+    // Remotes merge across layers; the repository wins on plain values
+    let loaded = VektConfig::load().unwrap();
+    assert_eq!(loaded.remotes.len(), 2);
+    assert_eq!(loaded.concurrency(), 16);
+    let rules = loaded.storage_rules();
+    assert_eq!(rules.last().unwrap().encoding, BlobEncoding::Zstd);
+    assert!(rules.last().unwrap().matches("any.weight", "F32"));
+
+    // The environment wins over both
+    unsafe {
+        env::set_var("VEKT_CONCURRENCY", "32");
+        env::set_var("VEKT_REMOTE_BACKUP", "s3://backup");
+    }
+    assert_eq!(config::get("concurrency").unwrap(), Some(json!(32)));
+    assert_eq!(
+        config::get("remotes.backup").unwrap(),
+        Some(json!("s3://backup"))
+    );
+    let entries = config::list().unwrap();
+    let origin = |key: &str| entries.iter().find(|entry| entry.key == key).unwrap().scope;
+    assert_eq!(origin("concurrency"), ConfigScope::Env);
+    assert_eq!(origin("remotes.origin"), ConfigScope::Repo);
+    assert_eq!(origin("remotes.shared"), ConfigScope::User);
+    unsafe {
+        env::remove_var("VEKT_CONCURRENCY");
+        env::remove_var("VEKT_REMOTE_BACKUP");
+    }
+
+    // Values that don't fit the key and unknown keys are rejected without writing
+    assert!(config::set(ConfigScope::Repo, "concurrency", json!("many")).is_err());
+    assert!(config::set(ConfigScope::Repo, "max_store_size", json!("lots")).is_err());
+    assert!(config::set(ConfigScope::Repo, "concurency", json!(8)).is_err());
+    assert_eq!(config::get("concurrency").unwrap(), Some(json!(16)));
+
+    // Saving the repository config doesn't copy user settings into it
+    let mut repo = VektConfig::load_repo().unwrap();
+    repo.add_remote("mirror".to_string(), "s3://mirror".to_string());
+    repo.save().unwrap();
+    let layer = config::read_layer(ConfigScope::Repo).unwrap();
+    assert!(layer["remotes"].get("shared").is_none());
+    assert_eq!(layer["remotes"]["mirror"], json!("s3://mirror"));
+
+    assert!(config::unset(ConfigScope::Repo, "concurrency").unwrap());
+    assert!(!config::unset(ConfigScope::Repo, "concurrency").unwrap());
+    assert_eq!(VektConfig::load().unwrap().concurrency(), 4);
+
+    config::set(ConfigScope::Repo, "store_path", json!("blobs")).unwrap();
+    assert_eq!(
+        VektConfig::load().unwrap().store_path(),
+        Some(root.join("blobs"))
+    );
+
+    let _ = fs::remove_dir_all(&base);
+}
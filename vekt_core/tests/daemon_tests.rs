@@ -0,0 +1,99 @@
+#![cfg(unix)]
+
+use std::cell::Cell;
+use std::env;
+use std::rc::Rc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use vekt_core::daemon::{self, DaemonClient, DaemonRequest, DaemonResponse};
+use vekt_core::errors::exit_code;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_daemon_runs_requests_one_at_a_time() {
+    let path = env::temp_dir().join(format!("vekt_daemon_{}.sock", rand::random::<u64>()));
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+    let clients = {
+        let path = path.clone();
+        tokio::spawn(async move {
+            let mut tasks = Vec::new();
+            for i in 0..4 {
+                let path = path.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mut client = DaemonClient::connect(&path).await.unwrap();
+                    let mut responses = Vec::new();
+                    for _ in 0..5 {
+                        let request = match i % 2 {
+                            0 => DaemonRequest::Status,
+                            _ => DaemonRequest::Pull {
+                                remote: format!("r{}", i),
+                            },
+                        };
+                        responses.push(client.request(&request).await.unwrap());
+                    }
+                    responses
+                }));
+            }
+            let mut responses = Vec::new();
+            for task in tasks {
+                responses.extend(task.await.unwrap());
+            }
+
+            // Malformed lines get an error answer and the connection stays usable
+            let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"{\"op\":\"explode\"}\n").await.unwrap();
+            let invalid: DaemonResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            writer.write_all(b"{\"op\":\"ping\"}\n").await.unwrap();
+            let ping: DaemonResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+            let mut client = DaemonClient::connect(&path).await.unwrap();
+            let shutdown = client.request(&DaemonRequest::Shutdown).await.unwrap();
+            (responses, invalid, ping, shutdown)
+        })
+    };
+
+    // Requests never overlap, even with several clients connected
+    let active = Rc::new(Cell::new(0));
+    let handled = Rc::new(Cell::new(0));
+    daemon::serve(listener, |request| {
+        let active = active.clone();
+        let handled = handled.clone();
+        async move {
+            active.set(active.get() + 1);
+            assert_eq!(active.get(), 1);
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            active.set(active.get() - 1);
+            handled.set(handled.get() + 1);
+            match request {
+                DaemonRequest::Pull { remote } => {
+                    DaemonResponse::failure(format!("Remote '{}' not found", remote), 4)
+                }
+                _ => DaemonResponse::success(serde_json::json!("done")),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let (responses, invalid, ping, shutdown) = clients.await.unwrap();
+    assert_eq!(handled.get(), 21);
+    assert_eq!(responses.iter().filter(|r| r.ok).count(), 10);
+    assert!(
+        responses
+            .iter()
+            .filter(|r| !r.ok)
+            .all(|r| r.exit_code == Some(4) && r.error.as_deref().unwrap().contains("not found"))
+    );
+    assert!(!invalid.ok);
+    assert_eq!(invalid.exit_code, Some(exit_code::USAGE));
+    assert!(ping.ok);
+    assert!(shutdown.ok);
+
+    let _ = std::fs::remove_file(&path);
+}
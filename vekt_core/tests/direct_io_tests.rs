@@ -0,0 +1,37 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+
+use vekt_core::blobs;
+use vekt_core::direct_io;
+
+#[test]
+fn test_direct_writes_round_trip() {
+    // The target dir is on a real disk more often than the system temp dir, which
+    // may be tmpfs and fall back to buffered writes
+    let root = env::current_dir()
+        .unwrap()
+        .join(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("vekt_direct_io_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+    direct_io::set_enabled(true);
+
+    for len in [0, 1, 4096, 3 * 4096 + 5, (8 << 20) + 123] {
+        let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+        let path = root.join(format!("file-{}", len));
+        let mut writer = direct_io::create(&path).unwrap();
+        for piece in data.chunks(1000 + len % 7) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data, "len {}", len);
+
+        let (hash, _) = blobs::save_blob_deduplicated(&data).unwrap();
+        assert_eq!(blobs::read_blob(&hash).unwrap(), data, "len {}", len);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
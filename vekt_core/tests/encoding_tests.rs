@@ -0,0 +1,119 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use vekt_core::blobs;
+use vekt_core::encoding::{BlobEncoding, StorageRule, apply_storage_rules};
+use vekt_core::{ModelArchiver, SafetensorFile, SafetensorWriter, gc};
+
+fn write_model(path: &Path, embed: &[u8], lora: &[u8]) {
+    let mut writer = SafetensorWriter::new();
+    writer
+        .add_tensor(
+            "model.embed_tokens.weight",
+            vec![embed.len()],
+            "U8",
+            embed.to_vec(),
+        )
+        .unwrap();
+    writer
+        .add_tensor("lora_A.weight", vec![lora.len()], "U8", lora.to_vec())
+        .unwrap();
+    writer
+        .add_tensor("layers.0.qweight", vec![4096], "U8", vec![7; 4096])
+        .unwrap();
+    writer
+        .add_tensor("layers.0.bias", vec![4096], "U8", vec![1; 4096])
+        .unwrap();
+    writer.write(path).unwrap();
+}
+
+fn encoding(hash: &str) -> BlobEncoding {
+    blobs::find_blob(hash).unwrap().1
+}
+
+#[test]
+fn test_storage_rules_per_tensor() {
+    let root = env::temp_dir().join(format!("vekt_encoding_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let rules: Vec<StorageRule> = serde_json::from_str(
+        r#"[
+            {"tensors": "*.qweight", "encoding": "raw"},
+            {"tensors": "*embed_tokens*", "encoding": "chunked"},
+            {"tensors": "lora_*", "encoding": "delta"},
+            {"encoding": "zstd"}
+        ]"#,
+    )
+    .unwrap();
+
+    let embed: Vec<u8> = (0..3 << 20).map(|i| (i % 251) as u8).collect();
+    let lora: Vec<u8> = (0..8192).map(|i| (i % 13) as u8).collect();
+    let v1_path = root.join("v1.safetensors");
+    write_model(&v1_path, &embed, &lora);
+    let v1 = SafetensorFile::open(v1_path.to_str().unwrap())
+        .unwrap()
+        .process(true)
+        .unwrap();
+    assert_eq!(apply_storage_rules(&v1, &rules, None).unwrap(), 3);
+    assert_eq!(
+        encoding(&v1.tensors["layers.0.qweight"].hash),
+        BlobEncoding::Raw
+    );
+    assert_eq!(
+        encoding(&v1.tensors["layers.0.bias"].hash),
+        BlobEncoding::Zstd
+    );
+    assert_eq!(
+        encoding(&v1.tensors["model.embed_tokens.weight"].hash),
+        BlobEncoding::Chunked
+    );
+    // Nothing to take a delta against yet
+    assert_eq!(
+        encoding(&v1.tensors["lora_A.weight"].hash),
+        BlobEncoding::Zstd
+    );
+
+    // A fine-tuning step touches one embedding row and the adapter
+    let (mut embed2, mut lora2) = (embed.clone(), lora.clone());
+    embed2[10] ^= 0xff;
+    lora2[100] ^= 0xff;
+    let v2_path = root.join("v2.safetensors");
+    write_model(&v2_path, &embed2, &lora2);
+    let chunks_before = fs::read_dir(root.join(".vekt/blobs")).unwrap().count();
+    let v2 = SafetensorFile::open(v2_path.to_str().unwrap())
+        .unwrap()
+        .process(true)
+        .unwrap();
+    apply_storage_rules(&v2, &rules, Some(&v1)).unwrap();
+    assert_eq!(
+        encoding(&v2.tensors["lora_A.weight"].hash),
+        BlobEncoding::Delta
+    );
+    // New chunk list, lora delta and the single changed chunk
+    let added = fs::read_dir(root.join(".vekt/blobs")).unwrap().count() - chunks_before;
+    assert_eq!(added, 3);
+
+    // Only v2 is kept; gc must leave the chunks and delta base it is built from
+    v2.save(&root.join("v2.vekt.json")).unwrap();
+    fs::remove_file(&v1_path).unwrap();
+    gc::run_gc(&root).unwrap();
+
+    let restored = root.join("restored.safetensors");
+    v2.restore(&restored, None).unwrap();
+    let check = SafetensorFile::open(restored.to_str().unwrap())
+        .unwrap()
+        .process(false)
+        .unwrap();
+    for (name, tensor) in &v2.tensors {
+        assert_eq!(check.tensors[name].hash, tensor.hash);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use vekt_core::ephemeral::EXPIRES_AT_KEY;
+use vekt_core::{ModelArchiver, SafetensorFile, blobs, gc};
+
+fn write_model(path: &Path, tensors: &[(&str, [u8; 4])]) {
+    let mut header = Vec::new();
+    let mut data = Vec::new();
+    for (name, bytes) in tensors {
+        header.push(format!(
+            r#""{}": {{"dtype":"F32","shape":[1],"data_offsets":[{},{}]}}"#,
+            name,
+            data.len(),
+            data.len() + 4
+        ));
+        data.extend_from_slice(bytes);
+    }
+    let header = format!("{{{}}}", header.join(","));
+    let mut f = fs::File::create(path).unwrap();
+    f.write_all(&(header.len() as u64).to_le_bytes()).unwrap();
+    f.write_all(header.as_bytes()).unwrap();
+    f.write_all(&data).unwrap();
+}
+
+#[test]
+fn test_gc_reclaims_expired_ephemeral_manifests() {
+    let root = env::temp_dir().join(format!("vekt_ephemeral_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let shared = [1, 1, 1, 1];
+    write_model(
+        &root.join("base.safetensors"),
+        &[("shared", shared), ("a", [2; 4])],
+    );
+    write_model(
+        &root.join("run.safetensors"),
+        &[("shared", shared), ("b", [3; 4])],
+    );
+
+    let base = SafetensorFile::open(root.join("base.safetensors").to_str().unwrap())
+        .unwrap()
+        .process(true)
+        .unwrap();
+    base.save(&root.join("base.vekt.json")).unwrap();
+
+    let mut run = SafetensorFile::open(root.join("run.safetensors").to_str().unwrap())
+        .unwrap()
+        .process(true)
+        .unwrap();
+    run.mark_ephemeral(Duration::from_secs(3600));
+    assert!(run.expires_at().is_some());
+    run.save(&root.join("run.vekt.json")).unwrap();
+
+    // Not yet expired: everything is kept
+    let stats = gc::run_gc(&root).unwrap();
+    assert_eq!((stats.deleted, stats.expired), (0, 0));
+    assert!(root.join("run.vekt.json").exists());
+
+    // Once the TTL has passed, only the blobs unique to the ephemeral manifest go:
+    // its tensor and the skeleton of its file
+    run.metadata
+        .insert(EXPIRES_AT_KEY.to_string(), "1".to_string());
+    run.save(&root.join("run.vekt.json")).unwrap();
+    let stats = gc::run_gc(&root).unwrap();
+    assert_eq!((stats.deleted, stats.expired), (2, 1));
+    assert!(!root.join("run.vekt.json").exists());
+    assert!(!blobs::blob_exists(&run.tensors["b"].hash));
+    assert!(blobs::blob_exists(&run.tensors["shared"].hash));
+    assert!(blobs::blob_exists(&base.tensors["a"].hash));
+
+    // Adding without --ephemeral makes a manifest permanent again
+    run.clear_ephemeral();
+    assert!(!run.is_expired(u64::MAX));
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
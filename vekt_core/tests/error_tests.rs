@@ -50,3 +50,56 @@ fn test_open_invalid_header_len() -> Result<(), Box<dyn std::error::Error>> {
     std::fs::remove_file(path)?;
     Ok(())
 }
+
+#[test]
+fn test_exit_codes() {
+    use vekt_core::errors::exit_code;
+
+    assert_eq!(VektError::RepoNotFound.exit_code(), exit_code::NOT_A_REPO);
+    assert_eq!(VektError::LockExists.exit_code(), exit_code::LOCK_HELD);
+    assert_eq!(
+        VektError::HashMismatch {
+            expected: "a".into(),
+            actual: "b".into()
+        }
+        .exit_code(),
+        exit_code::CORRUPTION
+    );
+    assert_eq!(
+        VektError::CredentialError("no key".into()).exit_code(),
+        exit_code::REMOTE_AUTH
+    );
+    assert_eq!(
+        VektError::PartialTransfer {
+            completed: 3,
+            total: 5,
+            reason: "timeout".into()
+        }
+        .exit_code(),
+        exit_code::PARTIAL_TRANSFER
+    );
+    assert_eq!(
+        VektError::ArchivedBlobs("2 blobs".into()).exit_code(),
+        exit_code::ARCHIVED
+    );
+    assert_eq!(
+        VektError::CheckFailed("w: 1 NaN and 0 infinite values".into()).exit_code(),
+        exit_code::CORRUPTION
+    );
+    assert_eq!(
+        VektError::ManifestLocked {
+            manifest: "model.vekt.json".into(),
+            owner: "alice".into()
+        }
+        .exit_code(),
+        exit_code::CONFLICT
+    );
+    assert_eq!(
+        VektError::NotFastForward("model.vekt.json".into()).exit_code(),
+        exit_code::CONFLICT
+    );
+    assert_eq!(
+        VektError::RemoteError("500".into()).exit_code(),
+        exit_code::FAILURE
+    );
+}
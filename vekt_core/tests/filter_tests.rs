@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::filter::{self, Pointer};
+use vekt_core::refs;
+
+fn safetensors_file() -> Vec<u8> {
+    // Padded header with file metadata and tensors out of offset order, as written by
+    // other tools; the round trip must keep it byte for byte
+    let mut header = br#"{"__metadata__": {"format": "pt"}, "b": {"dtype": "F32", "shape": [2], "data_offsets": [8, 16]}, "a": {"dtype": "F32", "shape": [2], "data_offsets": [0, 8]}}"#.to_vec();
+    while !header.len().is_multiple_of(8) {
+        header.push(b' ');
+    }
+    let mut file = (header.len() as u64).to_le_bytes().to_vec();
+    file.extend_from_slice(&header);
+    file.extend_from_slice(&[1; 8]);
+    file.extend_from_slice(&[2; 8]);
+    file
+}
+
+#[test]
+fn test_clean_and_smudge_round_trip() {
+    let root = env::temp_dir().join(format!("vekt_filter_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let original = safetensors_file();
+    let mut pointer_file = Vec::new();
+    let pointer = filter::clean(&original[..], &mut pointer_file).unwrap();
+    assert_eq!(Pointer::parse(&pointer_file), Some(pointer.clone()));
+    assert_eq!(pointer.size, original.len() as u64);
+    let manifest = refs::load_manifest_object(&pointer.manifest).unwrap();
+    assert_eq!(manifest.tensors.len(), 2);
+    assert_eq!(manifest.tensors["b"].index, 0);
+
+    // A truncated file fails instead of reading past its end
+    let truncated = &original[..original.len() - 4];
+    assert!(matches!(
+        filter::clean(truncated, &mut Vec::new()),
+        Err(VektError::InvalidSafetensor(_))
+    ));
+
+    // Cleaning a pointer again leaves it alone
+    let mut again = Vec::new();
+    filter::clean(&pointer_file[..], &mut again).unwrap();
+    assert_eq!(again, pointer_file);
+
+    let mut smudged = Vec::new();
+    filter::smudge(&pointer_file[..], &mut smudged).unwrap();
+    assert_eq!(smudged, original);
+
+    // Files that were committed before the filter was set up check out as they are
+    let mut passed = Vec::new();
+    filter::smudge(&original[..], &mut passed).unwrap();
+    assert_eq!(passed, original);
+
+    unsafe {
+        env::set_var(filter::SKIP_SMUDGE_ENV, "1");
+    }
+    let mut skipped = Vec::new();
+    filter::smudge(&pointer_file[..], &mut skipped).unwrap();
+    assert_eq!(skipped, pointer_file);
+
+    let _ = fs::remove_dir_all(&root);
+}
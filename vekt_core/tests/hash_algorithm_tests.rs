@@ -0,0 +1,74 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use vekt_core::async_api;
+use vekt_core::blob_index::BlobIndex;
+use vekt_core::blobs;
+use vekt_core::gc;
+use vekt_core::hash::HashAlgorithm;
+use vekt_core::index::{IndexStatus, process_with_index};
+
+fn write_model(path: &Path, data: &[u8; 8]) {
+    let header_json = r#"{"a": {"dtype":"U8","shape":[4],"data_offsets":[0,4]}, "b": {"dtype":"U8","shape":[4],"data_offsets":[4,8]}}"#;
+    let mut f = fs::File::create(path).unwrap();
+    f.write_all(&(header_json.len() as u64).to_le_bytes())
+        .unwrap();
+    f.write_all(header_json.as_bytes()).unwrap();
+    f.write_all(data).unwrap();
+}
+
+#[test]
+fn test_sha256_and_blake3_blobs_share_a_store() {
+    let root = env::temp_dir().join(format!("vekt_hash_alg_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let model_path = root.join("model.safetensors");
+    let original = [1, 2, 3, 4, 5, 6, 7, 8];
+    write_model(&model_path, &original);
+
+    HashAlgorithm::Sha256.set_current();
+    let (sha_manifest, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(status, IndexStatus::Full);
+    let sha_hash = &sha_manifest.tensors["a"].hash;
+    assert!(sha_hash.starts_with("sha256:"));
+    let blob_name = blobs::get_blob_path(sha_hash)
+        .file_name()
+        .unwrap()
+        .to_owned();
+    assert!(blob_name.to_string_lossy().starts_with("sha256-"));
+    assert!(blobs::blob_exists(sha_hash));
+    assert!(BlobIndex::open().unwrap().contains(sha_hash));
+    sha_manifest.save(&root.join("sha.vekt.json")).unwrap();
+
+    // Index entries of the other algorithm are ignored, so every tensor is re-hashed
+    HashAlgorithm::Blake3.set_current();
+    let (blake_manifest, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(status, IndexStatus::Full);
+    let blake_hash = &blake_manifest.tensors["a"].hash;
+    assert!(!blake_hash.contains(':'));
+    assert_eq!(
+        *blake_hash,
+        blake3::hash(&original[..4]).to_hex().to_string()
+    );
+    blake_manifest.save(&root.join("blake.vekt.json")).unwrap();
+
+    // Each blob is verified with the algorithm that named it
+    let restored = root.join("restored.safetensors");
+    sha_manifest.restore(&restored, None).unwrap();
+    let rehashed = async_api::process_file(&restored, false).unwrap();
+    for (name, tensor) in &rehashed.tensors {
+        assert_eq!(tensor.hash, blake_manifest.tensors[name].hash);
+    }
+
+    // Both manifests keep their blobs through gc
+    gc::plan_gc(&root).unwrap().execute().unwrap();
+    assert!(blobs::blob_exists(sha_hash));
+    assert!(blobs::blob_exists(blake_hash));
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use vekt_core::errors::VektError;
+use vekt_core::hooks::{self, HOOK_MARKER};
+
+#[test]
+fn test_install_hooks() {
+    let dir = env::temp_dir().join(format!("vekt_hooks_{}", rand::random::<u64>()));
+    let hooks_dir = dir.join("hooks");
+    let root = Path::new("/srv/models repo");
+
+    let written = hooks::install_hooks(&hooks_dir, root, "backup", false).unwrap();
+    assert_eq!(
+        written,
+        [hooks_dir.join("pre-commit"), hooks_dir.join("pre-push")]
+    );
+    let pre_push = fs::read_to_string(hooks_dir.join("pre-push")).unwrap();
+    assert!(pre_push.contains(HOOK_MARKER));
+    assert!(pre_push.contains(r#"push "backup""#));
+    assert!(pre_push.contains(r#"VEKT_ROOT="${VEKT_ROOT:-/srv/models repo}""#));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(hooks_dir.join("pre-commit"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o111, 0o111);
+    }
+
+    // Our own hooks are replaced freely
+    hooks::install_hooks(&hooks_dir, root, "origin", false).unwrap();
+    assert!(
+        fs::read_to_string(hooks_dir.join("pre-push"))
+            .unwrap()
+            .contains(r#"push "origin""#)
+    );
+
+    // Anyone else's only with force, and they are kept
+    fs::write(hooks_dir.join("pre-commit"), "#!/bin/sh\nmake lint\n").unwrap();
+    assert!(matches!(
+        hooks::install_hooks(&hooks_dir, root, "origin", false),
+        Err(VektError::ConflictDetected(_))
+    ));
+    hooks::install_hooks(&hooks_dir, root, "origin", true).unwrap();
+    assert_eq!(
+        fs::read_to_string(hooks_dir.join("pre-commit.orig")).unwrap(),
+        "#!/bin/sh\nmake lint\n"
+    );
+    assert!(
+        fs::read_to_string(hooks_dir.join("pre-commit"))
+            .unwrap()
+            .contains(HOOK_MARKER)
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,145 @@
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use vekt_core::hub::{HubClient, HubRepo, SOURCE_KEY};
+use vekt_core::{ModelArchiver, SafetensorFile, blobs};
+
+mod common;
+use common::Response;
+
+fn safetensors() -> Vec<u8> {
+    let header = r#"{"__metadata__":{"format":"pt"},"w":{"dtype":"F32","shape":[2],"data_offsets":[0,8]},"b":{"dtype":"F32","shape":[1],"data_offsets":[8,12]}}"#;
+    let mut out = (header.len() as u64).to_le_bytes().to_vec();
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    out
+}
+
+/// Serves a fake Hub. The first download of the weights drops the connection
+/// partway through the second tensor; later ones honour `Range`.
+fn serve(model: Vec<u8>, cut: usize, ranges: Arc<Mutex<Vec<String>>>) -> String {
+    let mut weights_requests = 0;
+    common::serve(move |request| {
+        if request.header("authorization") != Some("Bearer secret") {
+            return Response::status("401 Unauthorized");
+        }
+        match request.path.as_str() {
+            "/api/models/org/model/revision/main" => Response::ok(
+                br#"{"sha":"abc123","siblings":[{"rfilename":"model.safetensors"},{"rfilename":"config.json"},{"rfilename":"notes.txt"}]}"#,
+            ),
+            "/org/model/resolve/abc123/config.json" => Response::ok(b"{}"),
+            "/org/model/resolve/abc123/model.safetensors" => {
+                weights_requests += 1;
+                if weights_requests == 1 {
+                    return Response::ok(model.clone()).cut(cut);
+                }
+                let range = request.header("range").unwrap_or_default();
+                ranges.lock().unwrap().push(range.to_string());
+                Response::ranged(&model, &request)
+            }
+            _ => Response::not_found(),
+        }
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pull_from_hub_resumes_and_matches_local_add() {
+    let root = env::temp_dir().join(format!("vekt_hub_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let model = safetensors();
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+    // Header, all of "w" and half of "b"
+    let cut = model.len() - 2;
+    let endpoint = serve(model.clone(), cut, ranges.clone());
+
+    let anonymous = HubClient::new(&endpoint, None).unwrap();
+    let repo = HubRepo::parse("hf://org/model").unwrap();
+    assert!(anonymous.resolve(&repo).await.is_err());
+
+    let client = HubClient::new(&endpoint, Some("secret".to_string())).unwrap();
+    let snapshot = client.resolve(&repo).await.unwrap();
+    assert_eq!(snapshot.sha, "abc123");
+    assert_eq!(
+        snapshot.safetensors().collect::<Vec<_>>(),
+        ["model.safetensors"]
+    );
+    assert_eq!(snapshot.sidecars().collect::<Vec<_>>(), ["config.json"]);
+    assert_eq!(
+        client.download(&snapshot, "config.json").await.unwrap(),
+        b"{}"
+    );
+
+    let manifest = client
+        .pull_safetensors(&snapshot, "model.safetensors")
+        .await
+        .unwrap();
+    // The retry started right after the last complete tensor
+    let resume_at = model.len() - 4;
+    assert_eq!(*ranges.lock().unwrap(), [format!("bytes={}-", resume_at)]);
+    assert_eq!(manifest.metadata[SOURCE_KEY], "hf://org/model@abc123");
+    assert_eq!(manifest.total_size, model.len());
+    for tensor in manifest.tensors.values() {
+        assert!(blobs::blob_exists(&tensor.hash));
+    }
+
+    // A pull killed after "w" whose blob was collected since starts that tensor over
+    let w = manifest.tensors["w"].hash.clone();
+    let key = "org/model@abc123:model.safetensors";
+    let progress = root.join(".vekt/hub").join(format!(
+        "{}.json",
+        blobs::compute_object_hash(key.as_bytes())
+    ));
+    let header = &model[8..model.len() - 12];
+    fs::create_dir_all(progress.parent().unwrap()).unwrap();
+    fs::write(
+        &progress,
+        serde_json::json!({
+            "header": String::from_utf8(header.to_vec()).unwrap(),
+            "done": [["w", w]],
+        })
+        .to_string(),
+    )
+    .unwrap();
+    fs::remove_file(blobs::get_blob_path(&w)).unwrap();
+    let again = client
+        .pull_safetensors(&snapshot, "model.safetensors")
+        .await
+        .unwrap();
+    for (name, tensor) in &manifest.tensors {
+        assert_eq!(again.tensors[name].hash, tensor.hash);
+    }
+    assert!(blobs::blob_exists(&w));
+    assert_eq!(
+        ranges.lock().unwrap().last().unwrap(),
+        &format!("bytes={}-", model.len() - 12)
+    );
+
+    // Same tensors, order and hashes as adding the file locally
+    let local_path = root.join("local.safetensors");
+    let stripped = {
+        let header = r#"{"w":{"dtype":"F32","shape":[2],"data_offsets":[0,8]},"b":{"dtype":"F32","shape":[1],"data_offsets":[8,12]}}"#;
+        let mut out = (header.len() as u64).to_le_bytes().to_vec();
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(&model[model.len() - 12..]);
+        out
+    };
+    fs::write(&local_path, &stripped).unwrap();
+    let local = SafetensorFile::open(local_path.to_str().unwrap())
+        .unwrap()
+        .process(false)
+        .unwrap();
+    for (name, tensor) in &local.tensors {
+        assert_eq!(manifest.tensors[name].hash, tensor.hash);
+        assert_eq!(manifest.tensors[name].index, tensor.index);
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
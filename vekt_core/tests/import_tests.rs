@@ -0,0 +1,231 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use vekt_core::blobs;
+use vekt_core::errors::VektError;
+use vekt_core::hub::SOURCE_KEY;
+use vekt_core::import::{Importer, PointerKind};
+
+mod common;
+use common::Response;
+
+fn safetensors(values: &[u8]) -> Vec<u8> {
+    let header = format!(
+        r#"{{"w": {{"dtype": "U8", "shape": [{}], "data_offsets": [0, {}]}}}}"#,
+        values.len(),
+        values.len()
+    );
+    let mut file = (header.len() as u64).to_le_bytes().to_vec();
+    file.extend_from_slice(header.as_bytes());
+    file.extend_from_slice(values);
+    file
+}
+
+fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn md5(data: &[u8]) -> String {
+    format!("{:x}", md5::compute(data))
+}
+
+fn write(path: &Path, data: impl AsRef<[u8]>) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, data).unwrap();
+}
+
+fn lfs_pointer(data: &[u8]) -> String {
+    format!(
+        "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n",
+        sha256(data),
+        data.len()
+    )
+}
+
+/// A git-lfs server: the batch API points every object at `/objects/<oid>`, which
+/// serves it from `objects`. Records the Authorization header of batch requests.
+fn lfs_server(objects: Vec<Vec<u8>>, auth: Arc<Mutex<Vec<String>>>) -> String {
+    let url = common::serve(move |request| {
+        if let Some(value) = request.header("authorization") {
+            auth.lock().unwrap().push(value.to_string());
+        }
+        if !request.path.ends_with("/objects/batch") {
+            let oid = request.path.rsplit('/').next().unwrap();
+            return Response::ok(objects.iter().find(|o| sha256(o) == oid).unwrap().clone());
+        }
+        let batch: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let oid = batch["objects"][0]["oid"].as_str().unwrap().to_string();
+        let object = match objects.iter().any(|o| sha256(o) == oid) {
+            true => serde_json::json!({
+                "oid": oid,
+                "actions": {"download": {
+                    "href": format!("http://{}/objects/{}", request.header("host").unwrap(), oid)
+                }}
+            }),
+            false => serde_json::json!({
+                "oid": oid,
+                "error": {"code": 404, "message": "Object does not exist"}
+            }),
+        };
+        Response::ok(serde_json::to_vec(&serde_json::json!({ "objects": [object] })).unwrap())
+    });
+    format!("{}/lfs", url.replace("http://", "http://user:secret@"))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_import_from_lfs_and_dvc() {
+    let root = env::temp_dir().join(format!("vekt_import_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    // git-lfs: one object in .git/lfs, one only on the server
+    let lfs = root.join("lfs-repo");
+    let cached = safetensors(&[1, 2, 3, 4]);
+    let remote = safetensors(&[5, 6, 7, 8, 9]);
+    let oid = sha256(&cached);
+    write(
+        &lfs.join(".git/lfs/objects")
+            .join(&oid[..2])
+            .join(&oid[2..4])
+            .join(&oid),
+        &cached,
+    );
+    write(&lfs.join("base.safetensors"), lfs_pointer(&cached));
+    write(&lfs.join("runs/tuned.safetensors"), lfs_pointer(&remote));
+    // Checked out in full: no pointer to read
+    write(&lfs.join("local.safetensors"), safetensors(&[0; 2048]));
+
+    let auth = Arc::new(Mutex::new(Vec::new()));
+    let url = lfs_server(vec![remote.clone()], auth.clone());
+    let importer = Importer::new(PointerKind::Lfs, &lfs).with_url(Some(url.clone()));
+    let pointers = importer.pointers().await.unwrap();
+    let paths: Vec<&str> = pointers.iter().map(|p| p.path.as_str()).collect();
+    assert_eq!(paths, vec!["base.safetensors", "runs/tuned.safetensors"]);
+
+    let base = importer.import(&pointers[0], &|_| {}).await.unwrap();
+    assert_eq!(base.metadata[SOURCE_KEY], format!("git-lfs:sha256:{}", oid));
+    let tuned = importer.import(&pointers[1], &|_| {}).await.unwrap();
+    assert_eq!(
+        blobs::read_blob(&tuned.tensors["w"].hash).unwrap(),
+        vec![5, 6, 7, 8, 9]
+    );
+    // Only the missing object was fetched, with the URL's credentials
+    assert_eq!(*auth.lock().unwrap(), vec!["Basic dXNlcjpzZWNyZXQ="]);
+    // Downloads are ingested as they arrive, never landing on disk first
+    assert!(!root.join(".vekt/import").exists());
+
+    // A cached object that isn't what the pointer says is refused
+    let mut wrong = pointers[0].clone();
+    wrong.oid = sha256(b"corrupted");
+    write(
+        &lfs.join(".git/lfs/objects")
+            .join(&wrong.oid[..2])
+            .join(&wrong.oid[2..4])
+            .join(&wrong.oid),
+        &cached,
+    );
+    assert!(matches!(
+        importer.import(&wrong, &|_| {}).await,
+        Err(VektError::HashMismatch { .. })
+    ));
+    let mut gone = pointers[0].clone();
+    gone.oid = sha256(b"never pushed");
+    assert!(matches!(
+        importer.import(&gone, &|_| {}).await,
+        Err(VektError::RemoteNotFound(_))
+    ));
+
+    // DVC: a file in the cache, a directory whose listing and files are on a local
+    // remote in the 2.x layout, and a pipeline output in dvc.lock
+    let dvc = root.join("dvc-repo");
+    let model = safetensors(&[10, 11]);
+    let shard = safetensors(&[12, 13, 14]);
+    let output = safetensors(&[15]);
+    let model_md5 = md5(&model);
+    write(
+        &dvc.join(".dvc/cache/files/md5")
+            .join(&model_md5[..2])
+            .join(&model_md5[2..]),
+        &model,
+    );
+    write(
+        &dvc.join("models/model.safetensors.dvc"),
+        format!(
+            "outs:\n- md5: {}\n  size: {}\n  hash: md5\n  path: model.safetensors\n",
+            model_md5,
+            model.len()
+        ),
+    );
+    write(
+        &dvc.join(".dvc/config"),
+        "[core]\n    remote = local\n['remote \"local\"']\n    url = ../storage\n",
+    );
+    let to_remote = |data: &[u8], name: String| {
+        write(&dvc.join("storage").join(&name[..2]).join(&name[2..]), data);
+    };
+    let listing = serde_json::to_vec(&serde_json::json!([
+        {"md5": md5(&shard), "relpath": "shard-1.safetensors"},
+        {"md5": md5(b"{}"), "relpath": "config.json"},
+    ]))
+    .unwrap();
+    to_remote(&shard, md5(&shard));
+    let listing_md5 = format!("{}.dir", md5(&listing));
+    to_remote(&listing, listing_md5.clone());
+    write(
+        &dvc.join("weights.dvc"),
+        format!("outs:\n- md5: {}\n  path: weights\n", listing_md5),
+    );
+    to_remote(&output, md5(&output));
+    write(
+        &dvc.join("dvc.lock"),
+        format!(
+            "schema: '2.0'\nstages:\n  train:\n    cmd: python train.py\n    outs:\n    - path: out/final.safetensors\n      hash: md5\n      md5: {}\n",
+            md5(&output)
+        ),
+    );
+
+    let importer = Importer::new(PointerKind::Dvc, &dvc);
+    let pointers = importer.pointers().await.unwrap();
+    let paths: Vec<&str> = pointers.iter().map(|p| p.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "models/model.safetensors",
+            "out/final.safetensors",
+            "weights/shard-1.safetensors"
+        ]
+    );
+    for (pointer, values) in pointers.iter().zip([&[10u8, 11][..], &[15], &[12, 13, 14]]) {
+        let manifest = importer.import(pointer, &|_| {}).await.unwrap();
+        assert_eq!(
+            blobs::read_blob(&manifest.tensors["w"].hash).unwrap(),
+            values
+        );
+        assert_eq!(
+            manifest.metadata[SOURCE_KEY],
+            format!("dvc:md5:{}", pointer.oid)
+        );
+    }
+    assert!(!root.join(".vekt/import").exists());
+
+    // A directory listing that isn't what its hash says is refused
+    let forged = serde_json::to_vec(&serde_json::json!([
+        {"md5": md5(&output), "relpath": "shard-1.safetensors"},
+    ]))
+    .unwrap();
+    to_remote(&forged, listing_md5.clone());
+    assert!(matches!(
+        importer.pointers().await,
+        Err(VektError::HashMismatch { .. })
+    ));
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use std::sync::Mutex;
+
+use vekt_core::blobs;
+use vekt_core::index::{IndexStatus, VektIndex, process_with_index, process_with_index_streaming};
+
+fn write_model(path: &Path, data: &[u8; 8]) {
+    let header_json = r#"{"a": {"dtype":"U8","shape":[4],"data_offsets":[0,4]}, "b": {"dtype":"U8","shape":[4],"data_offsets":[4,8]}}"#;
+    let mut f = fs::File::create(path).unwrap();
+    f.write_all(&(header_json.len() as u64).to_le_bytes())
+        .unwrap();
+    f.write_all(header_json.as_bytes()).unwrap();
+    f.write_all(data).unwrap();
+}
+
+#[test]
+fn test_incremental_add_uses_index() {
+    let mut root = env::temp_dir();
+    root.push("vekt_index_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt").join("blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let model_path = root.join("model.safetensors");
+    write_model(&model_path, &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let (first, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(status, IndexStatus::Full);
+    assert!(root.join(".vekt").join("index").exists());
+
+    let (second, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(status, IndexStatus::Unchanged);
+    assert_eq!(first.tensors["a"].hash, second.tensors["a"].hash);
+
+    // Same bytes, new mtime: content is re-read but no tensor is re-hashed
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write_model(&model_path, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    let (_, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(
+        status,
+        IndexStatus::Partial {
+            rehashed: 0,
+            reused: 2
+        }
+    );
+
+    // Changed bytes: the chunk differs, so its tensors get fresh hashes
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write_model(&model_path, &[1, 2, 3, 4, 9, 9, 9, 9]);
+    let (changed, status) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(
+        status,
+        IndexStatus::Partial {
+            rehashed: 2,
+            reused: 0
+        }
+    );
+    assert_eq!(changed.tensors["a"].hash, first.tensors["a"].hash);
+    assert_ne!(changed.tensors["b"].hash, first.tensors["b"].hash);
+
+    // Streaming reports every tensor's blob, each already in the store when reported
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::remove_file(blobs::get_blob_path(&changed.tensors["b"].hash)).unwrap();
+    let reported = Mutex::new(Vec::new());
+    let (streamed, status) = process_with_index_streaming(&model_path, true, &|hash| {
+        assert!(blobs::blob_exists(hash));
+        reported.lock().unwrap().push(hash.to_string());
+    })
+    .unwrap();
+    assert_eq!(status, IndexStatus::Unchanged);
+    let mut reported = reported.into_inner().unwrap();
+    reported.sort();
+    let mut expected: Vec<String> = streamed.tensors.values().map(|t| t.hash.clone()).collect();
+    expected.sort();
+    assert_eq!(reported, expected);
+
+    // An indexed hash whose blob is gone isn't trusted: the tensor is stored and
+    // recorded under the hash of its bytes
+    let mut index = VektIndex::load().unwrap();
+    let wrong = blobs::compute_blob_hash(b"not this tensor");
+    for entry in index.entries.values_mut() {
+        entry.tensors.get_mut("a").unwrap().hash = wrong.clone();
+    }
+    index.save().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    write_model(&model_path, &[1, 2, 3, 4, 9, 9, 9, 9]);
+    let (repaired, _) = process_with_index(&model_path, true).unwrap();
+    assert_eq!(repaired.tensors["a"].hash, first.tensors["a"].hash);
+    assert!(blobs::blob_exists(&repaired.tensors["a"].hash));
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
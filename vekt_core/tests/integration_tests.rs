@@ -90,11 +90,11 @@ fn test_full_workflow() {
     );
 
     // 3. GC
-    // If we delete manifest, GC should remove blob
+    // If we delete manifest, GC should remove the tensor blob and the file's skeleton
     fs::remove_file(manifest_path).unwrap();
 
     let stats = gc::run_gc(&root).expect("GC failed");
-    assert_eq!(stats.deleted, 1, "GC should delete 1 blob");
+    assert_eq!(stats.deleted, 2, "GC should delete 2 blobs");
     assert!(!blob_path.exists(), "Blob should be gone");
 
     cleanup(root);
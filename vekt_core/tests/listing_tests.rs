@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+
+use vekt_core::commits;
+use vekt_core::listing::{self, ListFilter, ManifestState, SortKey};
+use vekt_core::meta;
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+
+fn model(tensors: usize, value: u8) -> VektManifest {
+    let mut manifest = VektManifest::new();
+    for i in 0..tensors {
+        manifest
+            .add_tensor(&format!("t{}", i), vec![4], "U8", &[value; 4])
+            .unwrap();
+    }
+    manifest
+}
+
+#[test]
+fn test_listing_states_tags_filters_and_sorting() {
+    let root = env::temp_dir().join(format!("vekt_listing_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    fs::create_dir_all(root.join("runs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut base = model(1, 1);
+    meta::set(&mut base, "license", "apache-2.0").unwrap();
+    base.save(&root.join("base.vekt.json")).unwrap();
+    model(2, 2)
+        .save(&root.join("runs/tuned.vekt.json"))
+        .unwrap();
+    commits::commit(&[], "first").unwrap();
+    refs::create_tag("v1", &base, false).unwrap();
+
+    // One committed file changes, another appears
+    model(3, 3)
+        .save(&root.join("runs/tuned.vekt.json"))
+        .unwrap();
+    model(4, 4).save(&root.join("scratch.vekt.json")).unwrap();
+    // Not a manifest; skipped rather than failing the listing
+    fs::write(root.join("runs/broken.vekt.json"), "{").unwrap();
+
+    let local = listing::list_local(&root).unwrap();
+    let states: Vec<(&str, ManifestState)> = local
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.state))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            ("base.vekt.json", ManifestState::Tracked),
+            ("runs/tuned.vekt.json", ManifestState::Modified),
+            ("scratch.vekt.json", ManifestState::Untracked),
+        ]
+    );
+    assert_eq!(local[0].tags, vec!["v1"]);
+    assert_eq!(local[1].tensors, 3);
+    assert!(local[1].size > local[0].size);
+    assert!(local.iter().all(|entry| entry.modified.is_some()));
+
+    // Pushed under file names: the one a local file holds as it is isn't listed, an
+    // older version and a manifest no local file has are
+    let pushed = vec![
+        ("base.vekt.json".to_string(), base.clone()),
+        ("tuned.vekt.json".to_string(), model(2, 2)),
+        ("old.vekt.json".to_string(), model(5, 5)),
+    ];
+    let remote_tags = vec![(
+        "archived".to_string(),
+        vekt_core::blobs::compute_object_hash(&refs::manifest_object_bytes(&pushed[2].1).unwrap()),
+    )];
+    let remote = listing::remote_only(&local, pushed, remote_tags).unwrap();
+    let remote_names: Vec<&str> = remote.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(remote_names, vec!["tuned.vekt.json", "old.vekt.json"]);
+    assert_eq!(remote[1].state, ManifestState::Remote);
+    assert_eq!(remote[1].tags, vec!["archived"]);
+    assert_eq!(remote[1].modified, None);
+
+    let mut listed = local;
+    listed.extend(remote);
+    let names = |listed: &[listing::ListedManifest]| -> Vec<String> {
+        listed.iter().map(|entry| entry.name.clone()).collect()
+    };
+
+    listing::sort(&mut listed, SortKey::Tensors, false);
+    assert_eq!(
+        names(&listed),
+        vec![
+            "old.vekt.json",
+            "scratch.vekt.json",
+            "runs/tuned.vekt.json",
+            "tuned.vekt.json",
+            "base.vekt.json"
+        ]
+    );
+    listing::sort(&mut listed, SortKey::Size, true);
+    assert_eq!(names(&listed)[0], "base.vekt.json");
+    listing::sort(&mut listed, SortKey::Name, false);
+    assert_eq!(names(&listed)[0], "base.vekt.json");
+
+    let filter = ListFilter {
+        metadata: Vec::new(),
+        states: vec![ManifestState::Modified, ManifestState::Remote],
+    };
+    let kept: Vec<_> = listed
+        .iter()
+        .filter(|e| filter.matches(e))
+        .cloned()
+        .collect();
+    assert_eq!(
+        names(&kept),
+        vec!["old.vekt.json", "runs/tuned.vekt.json", "tuned.vekt.json"]
+    );
+
+    let filter = ListFilter {
+        metadata: vec!["license=apache-*".parse().unwrap()],
+        states: Vec::new(),
+    };
+    let kept: Vec<_> = listed
+        .iter()
+        .filter(|e| filter.matches(e))
+        .cloned()
+        .collect();
+    assert_eq!(names(&kept), vec!["base.vekt.json"]);
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
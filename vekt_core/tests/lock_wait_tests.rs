@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use vekt_core::errors::VektError;
+use vekt_core::utils::LockFile;
+
+#[test]
+fn test_lock_wait_acquires_after_release_and_times_out() {
+    let root = env::temp_dir().join(format!("vekt_lock_wait_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let held = LockFile::lock().unwrap();
+    assert!(matches!(LockFile::lock(), Err(VektError::LockExists)));
+
+    let start = Instant::now();
+    let result = LockFile::lock_with_wait(Duration::from_millis(300));
+    assert!(matches!(result, Err(VektError::LockTimeout(_))));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+
+    // The other holder finishes while we wait
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        drop(held);
+    });
+    LockFile::set_wait(Duration::from_secs(10));
+    let acquired = LockFile::lock().unwrap();
+    releaser.join().unwrap();
+    drop(acquired);
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
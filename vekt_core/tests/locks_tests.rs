@@ -0,0 +1,87 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::remote::RemoteClient;
+use vekt_core::serve::BlobServer;
+use vekt_core::storage::VektManifest;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_manifest_locks_guard_pushes() {
+    let root = env::temp_dir().join(format!("vekt_locks_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+        env::set_var("VEKT_LOCK_OWNER", "bob");
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(BlobServer::new(None).unwrap().run(listener));
+    let client = RemoteClient::new(&url).unwrap();
+
+    let mut manifest = VektManifest::new();
+    manifest.add_tensor("w", vec![2], "F32", &[5; 8]).unwrap();
+    let name = "model.vekt.json";
+
+    // Alice locks the manifest; locking it again is a no-op, taking it from her isn't
+    let lock = client.lock_manifest(name, "alice").await.unwrap();
+    assert_eq!(lock.owner, "alice");
+    assert_eq!(client.lock_manifest(name, "alice").await.unwrap(), lock);
+    assert!(matches!(
+        client.lock_manifest(name, "bob").await,
+        Err(VektError::ManifestLocked { ref owner, .. }) if owner == "alice"
+    ));
+    assert_eq!(client.list_locks().await.unwrap(), vec![lock]);
+
+    // Bob can't push over her lock without forcing it
+    let refused = client.push(&manifest, name).await.unwrap_err();
+    assert!(matches!(refused, VektError::ManifestLocked { .. }));
+    assert!(!root.join(name).exists());
+    assert!(client.push_manifest(&manifest, name).await.is_err());
+
+    let forced = RemoteClient::new(&url).unwrap().with_force(true);
+    forced.push(&manifest, name).await.unwrap();
+    assert!(root.join(name).exists());
+
+    // Only the owner unlocks, unless forced
+    assert!(matches!(
+        client.unlock_manifest(name, "bob", false).await,
+        Err(VektError::ManifestLocked { .. })
+    ));
+    client.unlock_manifest(name, "alice", false).await.unwrap();
+    assert!(client.manifest_lock(name).await.unwrap().is_none());
+    assert!(matches!(
+        client.unlock_manifest(name, "alice", false).await,
+        Err(VektError::RefNotFound(_))
+    ));
+
+    // Bob's own lock doesn't stop his pushes
+    client.lock_manifest(name, "bob").await.unwrap();
+    client.push(&manifest, name).await.unwrap();
+    client.unlock_manifest(name, "carol", true).await.unwrap();
+    assert!(client.list_locks().await.unwrap().is_empty());
+
+    // Racing lock uploads: the server refuses to replace an existing lock
+    client.lock_manifest(name, "alice").await.unwrap();
+    let http = reqwest::Client::new();
+    let body = serde_json::json!({"manifest": name, "owner": "mallory", "locked_at": 0});
+    let raced = http
+        .put(format!("{}/locks/{}", url, name))
+        .header("If-None-Match", "*")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(raced.status(), 412);
+    assert_eq!(
+        client.manifest_lock(name).await.unwrap().unwrap().owner,
+        "alice"
+    );
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+        env::remove_var("VEKT_LOCK_OWNER");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
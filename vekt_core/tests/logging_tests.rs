@@ -0,0 +1,49 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use vekt_core::blobs;
+
+/// Collects everything the subscriber writes
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_blob_events_reach_the_callers_subscriber() {
+    let root = env::temp_dir().join(format!("vekt_logging_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let hash = tracing::subscriber::with_default(subscriber, || {
+        let hash = blobs::write_blob_atomic(b"logged").unwrap();
+        blobs::write_blob_atomic(b"logged").unwrap();
+        hash
+    });
+
+    let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(log.contains(&format!("Wrote blob hash={} size=6", hash)));
+    assert!(log.contains(&format!("Blob already stored hash={}", hash)));
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,85 @@
+use vekt_core::errors::VektError;
+use vekt_core::storage::VektManifest;
+
+const V1_MANIFEST: &str = r#"{
+  "tensors": {
+    "lm_head.weight": {
+      "shape": [2, 2],
+      "dtype": "F16",
+      "hash": "aa",
+      "index": 1,
+      "extra": {"quant": "none", "scale": 0.5}
+    },
+    "embed.weight": {
+      "shape": [2],
+      "dtype": "F32",
+      "hash": "bb",
+      "index": 0,
+      "extra": {}
+    }
+  },
+  "version": "1.0",
+  "total_size": 100
+}"#;
+
+#[test]
+fn test_v1_manifest_migrates_on_load() {
+    let manifest: VektManifest = serde_json::from_str(V1_MANIFEST).unwrap();
+    assert_eq!(manifest.version, VektManifest::CURRENT_VERSION);
+    let head = &manifest.tensors["lm_head.weight"];
+    assert_eq!(head.index, 1);
+    assert_eq!(head.extra["quant"], "none");
+    assert_eq!(head.extra["scale"], 0.5);
+    assert!(!head.extra.contains_key("extra"));
+    assert!(manifest.tensors["embed.weight"].extra.is_empty());
+
+    // Extras are written inline, as in the safetensors header, and read back as such
+    let json: serde_json::Value = serde_json::to_value(&manifest).unwrap();
+    assert_eq!(json["version"], "2.0");
+    assert_eq!(json["tensors"]["lm_head.weight"]["quant"], "none");
+    assert!(json["tensors"]["lm_head.weight"].get("extra").is_none());
+    let reloaded: VektManifest = serde_json::from_value(json).unwrap();
+    assert_eq!(reloaded.tensors["lm_head.weight"].extra, head.extra);
+}
+
+#[test]
+fn test_unknown_manifest_version() {
+    let future = V1_MANIFEST.replace(r#""version": "1.0""#, r#""version": "9.0""#);
+    let error = serde_json::from_str::<VektManifest>(&future).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("Unsupported manifest version '9.0'")
+    );
+
+    let path =
+        std::env::temp_dir().join(format!("vekt_future_{}.vekt.json", rand::random::<u64>()));
+    std::fs::write(&path, future).unwrap();
+    assert!(matches!(
+        VektManifest::load(&path),
+        Err(VektError::InvalidManifest(_))
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_manifest_json_is_deterministic() {
+    let manifest: VektManifest = serde_json::from_str(V1_MANIFEST).unwrap();
+    let json = manifest.to_json().unwrap();
+    assert!(json.ends_with("}\n"));
+    // Tensors are sorted by name, whatever order they were read in
+    assert!(json.find("embed.weight").unwrap() < json.find("lm_head.weight").unwrap());
+
+    let path =
+        std::env::temp_dir().join(format!("vekt_stable_{}.vekt.json", rand::random::<u64>()));
+    manifest.save(&path).unwrap();
+    let first = std::fs::read(&path).unwrap();
+    VektManifest::load(&path).unwrap().save(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), first);
+    assert_eq!(first, json.as_bytes());
+    assert_eq!(
+        vekt_core::refs::manifest_object_bytes(&manifest).unwrap(),
+        first
+    );
+    std::fs::remove_file(&path).unwrap();
+}
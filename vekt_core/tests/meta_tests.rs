@@ -0,0 +1,83 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::listing::{self, ListFilter};
+use vekt_core::meta::{self, MetaFilter};
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_metadata_travels_with_manifests_and_filters_listings() {
+    let root = env::temp_dir().join(format!("vekt_meta_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    fs::create_dir_all(root.join("runs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let card = "# Model card\n\nTrained on the cleaned corpus.\n";
+    let mut base = VektManifest::new();
+    base.add_tensor("w", vec![2], "F32", &[1; 8]).unwrap();
+    meta::set(&mut base, "license", "apache-2.0").unwrap();
+    meta::set(&mut base, "model_card", card).unwrap();
+    base.save(&root.join("base.vekt.json")).unwrap();
+
+    let mut tuned = VektManifest::new();
+    tuned.add_tensor("w", vec![2], "F32", &[2; 8]).unwrap();
+    meta::set(&mut tuned, "license", "cc-by-4.0").unwrap();
+    meta::set(&mut tuned, "run_id", "wandb/abc123").unwrap();
+    assert_eq!(
+        meta::set(&mut tuned, "run_id", "wandb/def456").unwrap(),
+        Some("wandb/abc123".to_string())
+    );
+    tuned.save(&root.join("runs/tuned.vekt.json")).unwrap();
+
+    let loaded = VektManifest::load(&root.join("base.vekt.json")).unwrap();
+    assert_eq!(loaded.metadata["model_card"], card);
+
+    // Keys can't hold the = of a filter
+    assert!(matches!(
+        meta::set(&mut base, "eval=score", "1"),
+        Err(VektError::InvalidMetadata(_))
+    ));
+    assert!("".parse::<MetaFilter>().is_err());
+
+    // Keys vekt writes itself can be filtered on, but not set or removed
+    for key in meta::RESERVED_KEYS {
+        assert!(matches!(
+            meta::set(&mut base, key, "1"),
+            Err(VektError::InvalidMetadata(_))
+        ));
+        assert!(meta::unset(&mut base, key).is_err());
+    }
+    assert!("source=hf:*".parse::<MetaFilter>().is_ok());
+
+    let local = listing::list_local(&root).unwrap();
+    let found = |filters: &[&str]| -> Vec<String> {
+        let filter = ListFilter {
+            metadata: filters.iter().map(|f| f.parse().unwrap()).collect(),
+            states: Vec::new(),
+        };
+        local
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .map(|entry| entry.name.clone())
+            .collect()
+    };
+    assert_eq!(found(&[]), vec!["base.vekt.json", "runs/tuned.vekt.json"]);
+    assert_eq!(found(&["license=apache-2.0"]), vec!["base.vekt.json"]);
+    assert_eq!(found(&["license=c*"]), vec!["runs/tuned.vekt.json"]);
+    assert_eq!(found(&["run_id"]), vec!["runs/tuned.vekt.json"]);
+    assert!(found(&["license=apache-2.0", "run_id"]).is_empty());
+
+    assert_eq!(
+        meta::unset(&mut tuned, "run_id").unwrap(),
+        Some("wandb/def456".to_string())
+    );
+    assert_eq!(meta::unset(&mut tuned, "run_id").unwrap(), None);
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
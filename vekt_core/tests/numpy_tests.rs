@@ -0,0 +1,128 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::{ModelArchiver, NumpyFile};
+
+fn npy(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+    let header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}\n",
+        descr, shape
+    );
+    let mut out = b"\x93NUMPY\x01\x00".to_vec();
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Minimal zip writer; CRCs are left at zero since ingestion never checks them.
+/// With `zip64_offset` the central directory stores the member offset in a zip64 extra.
+fn zip(members: &[(&str, u16, Vec<u8>, bool)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, method, data, zip64_offset) in members {
+        let offset = out.len() as u32;
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[20, 0, 0, 0]);
+        out.extend_from_slice(&method.to_le_bytes());
+        out.extend_from_slice(&[0; 8]); // time, date, crc
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        let extra: Vec<u8> = if *zip64_offset {
+            [
+                &1u16.to_le_bytes()[..],
+                &8u16.to_le_bytes(),
+                &(offset as u64).to_le_bytes(),
+            ]
+            .concat()
+        } else {
+            Vec::new()
+        };
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+        central.extend_from_slice(&method.to_le_bytes());
+        central.extend_from_slice(&[0; 8]);
+        central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0; 8]); // comment, disk, attributes
+        central.extend_from_slice(&[0; 2]);
+        let stored_offset = if *zip64_offset { u32::MAX } else { offset };
+        central.extend_from_slice(&stored_offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+        central.extend_from_slice(&extra);
+    }
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
+
+#[test]
+fn test_npz_roundtrip() {
+    let mut root = env::temp_dir();
+    root.push("vekt_numpy_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let embeddings: Vec<u8> = (0..24).collect();
+    let labels: Vec<u8> = (100..132).collect();
+    let archive = zip(&[
+        (
+            "embeddings.npy",
+            0,
+            npy("<f4", "(2, 3)", &embeddings),
+            false,
+        ),
+        ("labels.npy", 0, npy("<i8", "(4,)", &labels), true),
+        // Compressed members are kept whole and not split into blobs
+        ("cache.npy", 8, vec![1, 2, 3, 4, 5], false),
+    ]);
+    let path = root.join("arrays.npz");
+    fs::write(&path, &archive).unwrap();
+
+    let manifest = NumpyFile::open(&path).unwrap().process(true).unwrap();
+    assert_eq!(manifest.tensors.len(), 2);
+    assert_eq!(manifest.tensors["embeddings"].dtype, "F32");
+    assert_eq!(manifest.tensors["embeddings"].shape, vec![2, 3]);
+    assert_eq!(manifest.tensors["labels"].shape, vec![4]);
+    assert_eq!(
+        manifest.tensors["labels"].hash,
+        blobs::compute_blob_hash(&labels)
+    );
+    assert_eq!(manifest.layout.as_ref().unwrap().format, "npz");
+
+    // The same array saved on its own deduplicates against the archive
+    let single = root.join("labels.npy");
+    fs::write(&single, npy("<i8", "(4,)", &labels)).unwrap();
+    let single_manifest = NumpyFile::open(&single).unwrap().process(true).unwrap();
+    assert_eq!(
+        single_manifest.tensors["labels"].hash,
+        manifest.tensors["labels"].hash
+    );
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&single).unwrap();
+    manifest.restore(&path, None).unwrap();
+    single_manifest.restore(&single, None).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), archive);
+    assert_eq!(fs::read(&single).unwrap(), npy("<i8", "(4,)", &labels));
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,162 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use vekt_core::errors::VektError;
+use vekt_core::{ModelArchiver, OnnxFile};
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn field_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn field_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, (field << 3) | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn string_entry(key: &str, value: &str) -> Vec<u8> {
+    let mut entry = Vec::new();
+    field_bytes(&mut entry, 1, key.as_bytes());
+    field_bytes(&mut entry, 2, value.as_bytes());
+    entry
+}
+
+/// TensorProto with dims, data_type, name and one data field
+fn tensor(name: &str, dims: &[u64], data_type: u64, data: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut t = Vec::new();
+    for dim in dims {
+        field_varint(&mut t, 1, *dim);
+    }
+    field_varint(&mut t, 2, data_type);
+    field_bytes(&mut t, 8, name.as_bytes());
+    for (field, bytes) in data {
+        field_bytes(&mut t, *field, bytes);
+    }
+    t
+}
+
+#[test]
+fn test_onnx_roundtrip_with_external_data() {
+    let mut root = env::temp_dir();
+    root.push("vekt_onnx_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let raw: Vec<u8> = (0..16).collect();
+    let packed: Vec<u8> = [1.5f32, -2.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let external: Vec<u8> = (100..124).collect();
+
+    let mut graph = Vec::new();
+    field_bytes(&mut graph, 1, b"node-placeholder");
+    field_bytes(
+        &mut graph,
+        5,
+        &tensor("w.raw", &[4], 1, &[(9, raw.clone())]),
+    );
+    field_bytes(&mut graph, 5, &tensor("w.packed", &[2], 1, &[(4, packed)]));
+    let mut ext = tensor(
+        "w.ext",
+        &[2, 3],
+        1,
+        &[
+            (13, string_entry("location", "weights.bin")),
+            (13, string_entry("offset", "4")),
+            (13, string_entry("length", "24")),
+        ],
+    );
+    field_varint(&mut ext, 14, 1);
+    field_bytes(&mut graph, 5, &ext);
+
+    let mut model = Vec::new();
+    field_varint(&mut model, 1, 8);
+    field_bytes(&mut model, 7, &graph);
+
+    let mut side = vec![0xAA; 4];
+    side.extend_from_slice(&external);
+    side.extend_from_slice(&[0xBB; 3]);
+
+    let model_path = root.join("model.onnx");
+    let side_path = root.join("weights.bin");
+    fs::write(&model_path, &model).unwrap();
+    fs::write(&side_path, &side).unwrap();
+
+    let manifest = OnnxFile::open(&model_path).unwrap().process(true).unwrap();
+    assert_eq!(manifest.tensors.len(), 3);
+    assert_eq!(manifest.tensors["w.ext"].shape, vec![2, 3]);
+    assert_eq!(manifest.tensors["w.ext"].dtype, "F32");
+    assert_eq!(
+        manifest.tensors["w.raw"].hash,
+        vekt_core::blobs::compute_blob_hash(&raw)
+    );
+    assert_eq!(
+        manifest.tensors["w.ext"].hash,
+        vekt_core::blobs::compute_blob_hash(&external)
+    );
+    let layout = manifest.layout.as_ref().unwrap();
+    assert_eq!(layout.format, "onnx");
+    assert!(layout.external.contains_key("weights.bin"));
+
+    // Survives a save/load cycle and restores byte-exact into a fresh directory
+    let manifest_path = root.join("model.vekt.json");
+    manifest.save(&manifest_path).unwrap();
+    let loaded = vekt_core::storage::VektManifest::load(&manifest_path).unwrap();
+    assert_eq!(loaded.layout, manifest.layout);
+
+    let out = root.join("out");
+    fs::create_dir_all(&out).unwrap();
+    loaded.restore(&out.join("model.onnx"), None).unwrap();
+    assert_eq!(fs::read(out.join("model.onnx")).unwrap(), model);
+    assert_eq!(fs::read(out.join("weights.bin")).unwrap(), side);
+
+    // Tensor filters would drop bytes the graph still refers to
+    assert!(
+        loaded
+            .restore(&out.join("filtered.onnx"), Some("w.raw"))
+            .is_err()
+    );
+    assert!(!Path::new(&out.join("filtered.onnx")).exists());
+
+    // External data past the end of its file, or at an offset that overflows, is
+    // rejected instead of read
+    for (offset, length) in [("4", "64"), ("1", &u64::MAX.to_string()[..])] {
+        let mut bad = tensor(
+            "w.bad",
+            &[2],
+            1,
+            &[
+                (13, string_entry("location", "weights.bin")),
+                (13, string_entry("offset", offset)),
+                (13, string_entry("length", length)),
+            ],
+        );
+        field_varint(&mut bad, 14, 1);
+        let mut graph = Vec::new();
+        field_bytes(&mut graph, 5, &bad);
+        let mut model = Vec::new();
+        field_bytes(&mut model, 7, &graph);
+        let bad_path = root.join("bad.onnx");
+        fs::write(&bad_path, &model).unwrap();
+        assert!(matches!(
+            OnnxFile::open(&bad_path).unwrap().process(true),
+            Err(VektError::InvalidManifest(_))
+        ));
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
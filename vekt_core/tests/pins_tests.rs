@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::cache;
+use vekt_core::errors::VektError;
+use vekt_core::gc;
+use vekt_core::pins;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_pins_protect_blobs_from_gc_and_eviction() {
+    let root = env::temp_dir().join(format!("vekt_pins_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("w", vec![4], "U8", &[1, 2, 3, 4])
+        .unwrap();
+    let pinned = manifest.tensors["w"].hash.clone();
+    let path = root.join("prod.vekt.json");
+    manifest.save(&path).unwrap();
+    let spec = path.to_str().unwrap();
+    let pin = pins::pin(spec).unwrap();
+    assert_eq!(pins::list_pins().unwrap(), vec![pin]);
+
+    // The file moves on, but the pinned snapshot keeps its blobs
+    let mut next = VektManifest::new();
+    next.add_tensor("w", vec![4], "U8", &[5, 6, 7, 8]).unwrap();
+    next.save(&path).unwrap();
+    gc::run_gc(&root).unwrap();
+    assert!(blobs::blob_exists(&pinned));
+    assert!(cache::protected_hashes().unwrap().contains(&pinned));
+    // The working-tree file's blob goes only once a remote has it
+    let current = HashSet::from([next.tensors["w"].hash.clone()]);
+    assert_eq!(
+        cache::evict(0, &HashSet::new(), &HashSet::new())
+            .unwrap()
+            .evicted,
+        0
+    );
+    assert_eq!(
+        cache::evict(0, &HashSet::new(), &current).unwrap().evicted,
+        1
+    );
+    assert!(blobs::blob_exists(&pinned));
+
+    assert_eq!(pins::unpin(spec).unwrap(), 1);
+    assert!(matches!(pins::unpin(spec), Err(VektError::RefNotFound(_))));
+    gc::run_gc(&root).unwrap();
+    assert!(!blobs::blob_exists(&pinned));
+
+    let _ = fs::remove_dir_all(&root);
+}
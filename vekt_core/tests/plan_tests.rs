@@ -0,0 +1,91 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use vekt_core::blobs;
+use vekt_core::gc;
+use vekt_core::index::process_with_index;
+use vekt_core::plan;
+use vekt_core::refcount::RefCounts;
+use vekt_core::storage::VektManifest;
+
+fn write_model(path: &Path, data: &[u8; 8]) {
+    let header_json = r#"{"a": {"dtype":"U8","shape":[4],"data_offsets":[0,4]}, "b": {"dtype":"U8","shape":[4],"data_offsets":[4,8]}}"#;
+    let mut f = fs::File::create(path).unwrap();
+    f.write_all(&(header_json.len() as u64).to_le_bytes())
+        .unwrap();
+    f.write_all(header_json.as_bytes()).unwrap();
+    f.write_all(data).unwrap();
+}
+
+#[test]
+fn test_plans_change_nothing() {
+    let root = env::temp_dir().join(format!("vekt_plan_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let model_path = root.join("model.safetensors");
+    write_model(&model_path, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    let attachment = root.join("config.json");
+    fs::write(&attachment, b"{}").unwrap();
+
+    let planned = plan::plan_add(&model_path, std::slice::from_ref(&attachment)).unwrap();
+    assert_eq!(planned.tensors, 2);
+    assert_eq!(planned.hashed, 2);
+    assert_eq!(planned.hashed_bytes, 8);
+    assert_eq!(planned.existing, 0);
+    assert!(planned.new_bytes() >= 10);
+    for blob in &planned.new_blobs {
+        assert!(!blobs::blob_exists(&blob.hash));
+    }
+    assert!(!root.join(".vekt/index").exists());
+
+    // Once added, the index answers for every tensor and the blobs are stored
+    let (manifest, _) = process_with_index(&model_path, true).unwrap();
+    let planned = plan::plan_add(&model_path, &[]).unwrap();
+    assert_eq!(planned.hashed, 0);
+    assert_eq!(planned.hashed_bytes, 0);
+    assert!(planned.new_blobs.is_empty());
+
+    let download = plan::plan_download(&[&manifest]).unwrap();
+    assert!(download.blobs.is_empty());
+    assert_eq!(download.present, manifest.referenced_hashes().count());
+
+    // No manifest references the blobs, so gc would delete them all, but only on execute.
+    // Planning reads the new manifest without saving the refcount database.
+    RefCounts::open(&root).unwrap();
+    VektManifest::new()
+        .save(&root.join("empty.vekt.json"))
+        .unwrap();
+    let refcounts = root.join(".vekt/refcounts.json");
+    let before = fs::read(&refcounts).unwrap();
+    let gc_plan = gc::plan_gc(&root).unwrap();
+    assert_eq!(fs::read(&refcounts).unwrap(), before);
+    assert_eq!(gc_plan.delete.len(), download.present);
+    assert!(gc_plan.bytes() >= 8);
+    assert!(blobs::blob_exists(&manifest.tensors["a"].hash));
+    let stats = gc_plan.execute().unwrap();
+    assert_eq!(stats.deleted, download.present);
+    assert!(!blobs::blob_exists(&manifest.tensors["a"].hash));
+
+    // A delta push only plans the blobs the remote doesn't already have
+    let old_path = root.join("old.safetensors");
+    write_model(&old_path, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    let (old, _) = process_with_index(&old_path, true).unwrap();
+    let new_path = root.join("new.safetensors");
+    write_model(&new_path, &[1, 2, 3, 4, 9, 9, 9, 9]);
+    let (new, _) = process_with_index(&new_path, true).unwrap();
+
+    let known = old.referenced_hashes().cloned().collect();
+    let delta = plan::plan_delta(&[&new], &known);
+    assert_eq!(delta.blobs.len(), 1);
+    assert_eq!(delta.blobs[0].hash, new.tensors["b"].hash);
+    assert_eq!(delta.bytes(), 4);
+    assert_eq!(delta.reused, new.referenced_hashes().count() - 1);
+    assert!(delta.saved_bytes >= 4);
+
+    let _ = fs::remove_dir_all(&root);
+}
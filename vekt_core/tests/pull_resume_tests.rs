@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use vekt_core::blobs;
+use vekt_core::remote::RemoteClient;
+use vekt_core::storage::VektManifest;
+
+mod common;
+use common::Response;
+
+type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Serves `blobs` like `vekt serve`, honouring `Range: bytes=<start>-`. The first
+/// request for `flaky` drops the connection after `cut` bytes.
+fn serve(blobs: HashMap<String, Vec<u8>>, flaky: String, cut: usize, requests: Requests) -> String {
+    common::serve(move |request| {
+        let hash = request.path.trim_start_matches("/blobs/").to_string();
+        let first = !requests.lock().unwrap().iter().any(|(h, _)| *h == hash);
+        let range = request.header("range").map(str::to_string);
+        requests.lock().unwrap().push((hash.clone(), range));
+        match blobs.get(&hash) {
+            None => Response::not_found(),
+            Some(data) if first && hash == flaky => Response::ok(data.clone()).cut(cut),
+            Some(data) => Response::ranged(data, &request),
+        }
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_pull_resumes_partial_blobs() {
+    let root = env::temp_dir().join(format!("vekt_pull_resume_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let flaky_data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+    let stale_data: Vec<u8> = (0..600u32).map(|i| (i % 13) as u8).collect();
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("flaky", vec![1000], "U8", &flaky_data)
+        .unwrap();
+    manifest
+        .add_tensor("stale", vec![600], "U8", &stale_data)
+        .unwrap();
+    let flaky = manifest.tensors["flaky"].hash.clone();
+    let stale = manifest.tensors["stale"].hash.clone();
+    fs::remove_file(blobs::get_blob_path(&flaky)).unwrap();
+    fs::remove_file(blobs::get_blob_path(&stale)).unwrap();
+
+    // An earlier pull left a partial blob that isn't a prefix of the real one
+    fs::write(
+        blobs::get_blob_path(&stale).with_extension("tmp"),
+        [0xff; 300],
+    )
+    .unwrap();
+
+    let log = Requests::default();
+    let url = serve(
+        HashMap::from([
+            (flaky.clone(), flaky_data.clone()),
+            (stale.clone(), stale_data.clone()),
+        ]),
+        flaky.clone(),
+        400,
+        log.clone(),
+    );
+    let client = RemoteClient::new(&url).unwrap();
+    client.download_blobs(&manifest).await.unwrap();
+    assert_eq!(blobs::read_blob(&flaky).unwrap(), flaky_data);
+    assert_eq!(blobs::read_blob(&stale).unwrap(), stale_data);
+    assert!(!blobs::get_blob_path(&flaky).with_extension("tmp").exists());
+
+    let requests = log.lock().unwrap().clone();
+    let ranges = |hash: &str| -> Vec<Option<String>> {
+        requests
+            .iter()
+            .filter(|(h, _)| h == hash)
+            .map(|(_, range)| range.clone())
+            .collect()
+    };
+    // The dropped download continues where it stopped
+    assert_eq!(ranges(&flaky), vec![None, Some("bytes=400-".to_string())]);
+    // The leftover was continued too, then downloaded again once the hash didn't match
+    assert_eq!(ranges(&stale), vec![Some("bytes=300-".to_string()), None]);
+
+    // Missing blobs aren't retried
+    let mut missing = VektManifest::new();
+    missing
+        .add_tensor("gone", vec![3], "U8", &[7, 7, 7])
+        .unwrap();
+    let gone = missing.tensors["gone"].hash.clone();
+    fs::remove_file(blobs::get_blob_path(&gone)).unwrap();
+    let client = RemoteClient::new(&url).unwrap();
+    assert!(client.download_blobs(&missing).await.is_err());
+    assert_eq!(
+        log.lock()
+            .unwrap()
+            .iter()
+            .filter(|(h, _)| *h == gone)
+            .count(),
+        1
+    );
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
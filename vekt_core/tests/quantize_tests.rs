@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::gc;
+use vekt_core::quantize::{QUANT_SCHEME_KEY, QuantScheme, dequantize_values};
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_quantize_manifest() {
+    let root = env::temp_dir().join(format!("vekt_quantize_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let weight: Vec<f32> = (0..128).map(|i| (i as f32 - 64.0) / 16.0).collect();
+    let weight_bytes: Vec<u8> = weight.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let mut full = VektManifest::new();
+    full.add_tensor("fc.weight", vec![2, 64], "F32", &weight_bytes)
+        .unwrap()
+        .add_tensor("fc.bias", vec![2], "F32", &[0u8; 8])
+        .unwrap();
+
+    let (quantized, summary) = full.quantize(QuantScheme::Q8_0).unwrap();
+    assert_eq!(summary.quantized, ["fc.weight"]);
+    assert_eq!(summary.kept, ["fc.bias"]);
+    assert_eq!(quantized.metadata[QUANT_SCHEME_KEY], "q8_0");
+
+    let q = &quantized.tensors["fc.weight"];
+    let scale = &quantized.tensors["fc.weight_scale"];
+    assert_eq!((q.dtype.as_str(), q.index), ("I8", 0));
+    assert_eq!((scale.shape.as_slice(), scale.index), (&[2, 2][..], 1));
+    assert_eq!(quantized.tensors["fc.bias"].index, 2);
+    assert_eq!(
+        quantized.tensors["fc.bias"].hash,
+        full.tensors["fc.bias"].hash
+    );
+
+    let restored = dequantize_values(
+        &blobs::read_blob(&q.hash).unwrap(),
+        &blobs::read_blob(&scale.hash).unwrap(),
+        &q.shape,
+        QuantScheme::Q8_0,
+    );
+    for (a, b) in weight.iter().zip(&restored) {
+        assert!((*a as f64 - b).abs() < 0.02, "{} vs {}", a, b);
+    }
+
+    // The quantized manifest keeps its full-precision parent alive
+    quantized.save(&root.join("model.q8_0.vekt.json")).unwrap();
+    gc::run_gc(&root).unwrap();
+    let parent = quantized.quantized_from().unwrap().unwrap();
+    assert_eq!(
+        parent.tensors["fc.weight"].hash,
+        full.tensors["fc.weight"].hash
+    );
+    assert!(blobs::blob_exists(&full.tensors["fc.weight"].hash));
+
+    let output = root.join("model.q8_0.safetensors");
+    quantized.restore(&output, None).unwrap();
+    assert_eq!(
+        fs::metadata(&output).unwrap().len(),
+        quantized.total_size as u64
+    );
+    assert!(quantized.quantize(QuantScheme::Q4_0).is_err());
+
+    let _ = fs::remove_dir_all(&root);
+}
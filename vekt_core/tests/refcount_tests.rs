@@ -0,0 +1,114 @@
+use std::env;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use vekt_core::adapter::ADAPTER_BASE_KEY;
+use vekt_core::blob_index::BlobIndex;
+use vekt_core::blobs;
+use vekt_core::commits;
+use vekt_core::gc;
+use vekt_core::refcount::{Reclaimable, RefCounts};
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+use vekt_core::tombstones;
+
+#[test]
+fn test_refcounts_track_changes_incrementally() {
+    let root = env::temp_dir().join(format!("vekt_refcount_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut a = VektManifest::new();
+    a.add_tensor("w", vec![4], "U8", &[1, 2, 3, 4]).unwrap();
+    let a_blob = a.tensors["w"].hash.clone();
+    let a_path = root.join("a.vekt.json");
+    a.save(&a_path).unwrap();
+    let mut b = VektManifest::new();
+    b.add_tensor("w", vec![4], "U8", &[5, 6, 7, 8]).unwrap();
+    let b_blob = b.tensors["w"].hash.clone();
+    let b_path = root.join("b.vekt.json");
+    b.save(&b_path).unwrap();
+    let (stray, _) = blobs::save_blob_deduplicated(&[9, 9, 9, 9]).unwrap();
+
+    // The first open builds the database from scratch
+    let refcounts = RefCounts::open(&root).unwrap();
+    assert!(root.join(".vekt/refcounts.json").exists());
+    assert_eq!(refcounts.count(&a_blob), 1);
+    assert_eq!(refcounts.count(&stray), 0);
+    let index = BlobIndex::open().unwrap();
+    assert_eq!(
+        refcounts.reclaimable(&index).unwrap(),
+        Reclaimable { blobs: 1, bytes: 4 }
+    );
+
+    // Once a manifest is old enough for its stamp to be trusted, it isn't read again
+    // while its size and modification time stay the same
+    let old = SystemTime::now() - Duration::from_secs(60);
+    let set_modified = |time| {
+        fs::File::options()
+            .write(true)
+            .open(&a_path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap()
+    };
+    set_modified(old);
+    RefCounts::open(&root).unwrap();
+    let size = fs::metadata(&a_path).unwrap().len() as usize;
+    fs::write(&a_path, vec![b' '; size]).unwrap();
+    set_modified(old);
+    assert_eq!(RefCounts::open(&root).unwrap().count(&a_blob), 1);
+    assert_eq!(RefCounts::rebuild(&root).unwrap().count(&a_blob), 0);
+    a.save(&a_path).unwrap();
+
+    // rm and commit update the database as they go
+    tombstones::remove(b_path.to_str().unwrap(), true).unwrap();
+    commits::commit(std::slice::from_ref(&a_path), "keep a").unwrap();
+    let refcounts = RefCounts::open(&root).unwrap();
+    assert_eq!(refcounts.count(&b_blob), 0);
+    assert_eq!(refcounts.count(&a_blob), 2);
+
+    let plan = gc::plan_gc(&root).unwrap();
+    let mut deleted: Vec<_> = plan.delete.iter().map(|(path, _)| path.clone()).collect();
+    deleted.sort();
+    let mut expected = vec![blobs::get_blob_path(&b_blob), blobs::get_blob_path(&stray)];
+    expected.sort();
+    assert_eq!(deleted, expected);
+    plan.execute().unwrap();
+    assert!(blobs::blob_exists(&a_blob));
+
+    // Deleting the manifest file behind vekt's back is noticed too; the commit still
+    // holds its blob
+    fs::remove_file(&a_path).unwrap();
+    assert_eq!(RefCounts::open(&root).unwrap().count(&a_blob), 1);
+    assert!(gc::plan_gc(&root).unwrap().delete.is_empty());
+    assert!(gc::plan_gc_full(&root).unwrap().delete.is_empty());
+
+    // An adapter whose base can't be loaded yet is read again once it can
+    let mut base = VektManifest::new();
+    base.add_tensor("w", vec![4], "U8", &[3, 3, 3, 3]).unwrap();
+    let base_blob = base.tensors["w"].hash.clone();
+    let base_hash = blobs::compute_object_hash(&refs::manifest_object_bytes(&base).unwrap());
+    let mut lora = VektManifest::new();
+    lora.add_tensor("w", vec![1], "U8", &[1]).unwrap();
+    lora.metadata
+        .insert(ADAPTER_BASE_KEY.to_string(), base_hash.clone());
+    let lora_path = root.join("lora.vekt.json");
+    lora.save(&lora_path).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&lora_path)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    assert_eq!(RefCounts::open(&root).unwrap().count(&base_blob), 0);
+    assert_eq!(refs::store_manifest_object(&base).unwrap(), base_hash);
+    assert_eq!(RefCounts::open(&root).unwrap().count(&base_blob), 1);
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_tag_lifecycle() {
+    let mut root = env::temp_dir();
+    root.push("vekt_refs_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest {
+        tensors: BTreeMap::new(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 42,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+
+    let hash = refs::create_tag("v1.0", &manifest, false).unwrap();
+    assert_eq!(refs::read_tag("v1.0").unwrap(), hash);
+    assert_eq!(refs::resolve_manifest("v1.0").unwrap().total_size, 42);
+    assert_eq!(refs::resolve_manifest(&hash).unwrap().total_size, 42);
+
+    // Moving a tag requires force
+    manifest.total_size = 43;
+    assert!(matches!(
+        refs::create_tag("v1.0", &manifest, false),
+        Err(VektError::RefExists(_))
+    ));
+    refs::create_tag("v1.0", &manifest, true).unwrap();
+    refs::create_tag("team/prod", &manifest, false).unwrap();
+
+    let names: Vec<String> = refs::list_tags()
+        .unwrap()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, vec!["team/prod".to_string(), "v1.0".to_string()]);
+    assert_eq!(refs::tag_names(), names);
+
+    refs::delete_tag("v1.0").unwrap();
+    assert!(matches!(
+        refs::resolve_manifest("v1.0"),
+        Err(VektError::RefNotFound(_))
+    ));
+
+    // Objects written by another serializer are kept byte for byte
+    let compact = serde_json::to_vec(&manifest).unwrap();
+    let compact_hash = vekt_core::blobs::compute_object_hash(&compact);
+    refs::import_manifest_object(&compact, &compact_hash).unwrap();
+    assert_eq!(
+        refs::read_manifest_object_bytes(&compact_hash).unwrap(),
+        compact
+    );
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
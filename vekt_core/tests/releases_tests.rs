@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use vekt_core::releases::{self, Release};
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_release_roundtrip() {
+    let mut root = env::temp_dir();
+    root.push("vekt_releases_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut paths = Vec::new();
+    for (name, size) in [("encoder.vekt.json", 1), ("decoder.vekt.json", 2)] {
+        let manifest = VektManifest {
+            tensors: BTreeMap::new(),
+            version: VektManifest::CURRENT_VERSION.to_string(),
+            total_size: size,
+            checksum: None,
+            attachments: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            layout: None,
+            placement: Default::default(),
+        };
+        let path = root.join(name);
+        manifest.save(&path).unwrap();
+        paths.push(path);
+    }
+
+    assert!(Release::create("team/v1", &paths).is_err());
+    assert!(Release::create("v2024.1", &[paths[0].clone(), paths[0].clone()]).is_err());
+
+    let release = Release::create("v2024.1", &paths).unwrap();
+    release.save().unwrap();
+    assert_eq!(Release::load("v2024.1").unwrap(), release);
+    assert_eq!(releases::list_releases().unwrap().len(), 1);
+
+    // Later edits to the working manifests don't change the release
+    fs::remove_file(&paths[0]).unwrap();
+    let out = root.join("checkout");
+    fs::create_dir_all(&out).unwrap();
+    let written = release.checkout(&out).unwrap();
+    assert_eq!(
+        written,
+        vec![out.join("decoder.vekt.json"), out.join("encoder.vekt.json")]
+    );
+    let encoder = VektManifest::load(&PathBuf::from(&written[1])).unwrap();
+    assert_eq!(encoder.total_size, 1);
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
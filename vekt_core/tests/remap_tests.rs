@@ -0,0 +1,53 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::remap::RenameRules;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_remap_keeps_blobs_and_diffs_as_renames() {
+    let root = env::temp_dir().join(format!("vekt_remap_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("transformer.h.0.attn.weight", vec![4], "U8", &[1, 2, 3, 4])
+        .unwrap()
+        .add_tensor("transformer.h.1.attn.weight", vec![4], "U8", &[5, 6, 7, 8])
+        .unwrap()
+        .add_tensor("lm_head.weight", vec![2], "U8", &[9, 9])
+        .unwrap();
+    let rules_path = root.join("rules.json");
+    fs::write(
+        &rules_path,
+        r#"{"^transformer\\.h\\.(\\d+)\\.": "model.layers.$1."}"#,
+    )
+    .unwrap();
+    let rules = RenameRules::load(&rules_path).unwrap();
+
+    let (remapped, renamed) = manifest.remap(&rules).unwrap();
+    assert_eq!(renamed.len(), 2);
+    let moved = &remapped.tensors["model.layers.1.attn.weight"];
+    let original = &manifest.tensors["transformer.h.1.attn.weight"];
+    assert_eq!(moved.hash, original.hash);
+    assert_eq!(moved.index, original.index);
+    remapped.validate().unwrap();
+
+    let diff = manifest.diff(&remapped).tensor_diff;
+    assert_eq!(diff.renamed, renamed);
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+    assert_eq!(diff.unchanged, vec!["lm_head.weight"]);
+
+    // Two tensors can't end up with one name
+    let collapse = RenameRules::from_json(r#"{"^transformer\\.h\\.\\d+\\.": "block."}"#).unwrap();
+    assert!(matches!(
+        manifest.remap(&collapse),
+        Err(VektError::InvalidTensorName(_))
+    ));
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::remote::{RemoteClient, RemoteCredentials};
+use vekt_core::storage::VektConfig;
+
+#[test]
+fn test_per_remote_credentials() {
+    let dir = env::temp_dir().join(format!("vekt_creds_{}", rand::random::<u64>()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("credentials");
+    fs::write(
+        &file,
+        "[staging]\naws_access_key_id = STAGINGKEY\naws_secret_access_key = staging-secret\n\n\
+         [prod]\naws_access_key_id = PRODKEY\naws_secret_access_key = prod-secret\n",
+    )
+    .unwrap();
+
+    let config: VektConfig = serde_json::from_str(&format!(
+        r#"{{
+            "remotes": {{"staging": "s3://staging-bucket", "prod": "s3://prod-bucket", "origin": "s3://b"}},
+            "credentials": {{
+                "staging": {{"profile": "staging", "credentials_file": {:?}}},
+                "prod": {{"profile": "prod", "credentials_file": {:?}}},
+                "ci": {{"access_key_env": "VEKT_TEST_CI_KEY", "secret_key_env": "VEKT_TEST_CI_SECRET"}}
+            }}
+        }}"#,
+        file, file
+    ))
+    .unwrap();
+
+    let staging = config.remote_credentials("staging").load().unwrap();
+    assert_eq!(staging.access_key.as_deref(), Some("STAGINGKEY"));
+    let prod = config.remote_credentials("prod").load().unwrap();
+    assert_eq!(prod.secret_key.as_deref(), Some("prod-secret"));
+    assert!(config.remote_credentials("origin").is_default());
+    assert!(
+        RemoteClient::with_credentials("s3://prod-bucket", &config.remote_credentials("prod"))
+            .is_ok()
+    );
+
+    // Environment variable names override the default ones
+    unsafe {
+        env::set_var("VEKT_TEST_CI_KEY", "CIKEY");
+        env::set_var("VEKT_TEST_CI_SECRET", "ci-secret");
+    }
+    let ci = config.remote_credentials("ci").load().unwrap();
+    assert_eq!(ci.access_key.as_deref(), Some("CIKEY"));
+
+    let missing = RemoteCredentials {
+        profile: Some("nope".to_string()),
+        credentials_file: Some(file.clone()),
+        ..Default::default()
+    };
+    let err = missing.load().unwrap_err();
+    assert!(matches!(err, VektError::CredentialError(_)));
+    assert!(err.to_string().contains("profile 'nope'"));
+
+    // Remotes without credentials keep the config file unchanged
+    let plain: VektConfig = serde_json::from_str(r#"{"remotes": {}}"#).unwrap();
+    assert!(
+        !serde_json::to_string(&plain)
+            .unwrap()
+            .contains("credentials")
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
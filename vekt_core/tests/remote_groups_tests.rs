@@ -0,0 +1,40 @@
+use vekt_core::errors::VektError;
+use vekt_core::storage::VektConfig;
+
+#[test]
+fn test_remote_groups_resolve_to_their_members() {
+    let config: VektConfig = serde_json::from_str(
+        r#"{
+            "remotes": {"origin": "s3://models", "onprem": "http://models.internal:8080"},
+            "remote_groups": {
+                "mirror": ["origin", "onprem"],
+                "broken": ["origin", "gone"],
+                "empty": []
+            }
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.resolve_remotes("onprem").unwrap(), ["onprem"]);
+    assert_eq!(
+        config.resolve_remotes("mirror").unwrap(),
+        ["origin", "onprem"]
+    );
+    assert!(matches!(
+        config.resolve_remotes("broken"),
+        Err(VektError::RemoteNotFound(name)) if name.starts_with("gone")
+    ));
+    assert!(matches!(
+        config.resolve_remotes("empty"),
+        Err(VektError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        config.resolve_remotes("nowhere"),
+        Err(VektError::RemoteNotFound(_))
+    ));
+
+    // Groups survive a save and load of the config
+    let json = serde_json::to_string(&config).unwrap();
+    let reloaded: VektConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(reloaded.remote_groups, config.remote_groups);
+}
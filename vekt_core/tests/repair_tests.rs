@@ -0,0 +1,94 @@
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use vekt_core::blobs;
+use vekt_core::ranges::{RangeHashes, range_hash};
+use vekt_core::remote::{BlobRepair, RemoteClient};
+use vekt_core::storage::VektManifest;
+
+mod common;
+use common::Response;
+
+/// Serves `data` as every blob, honouring `Range`, and records the requested ranges
+fn serve_blob(data: Vec<u8>, requests: Arc<Mutex<Vec<Option<String>>>>) -> String {
+    common::serve(move |request| {
+        let range = request
+            .header("range")
+            .map(|r| r.trim_start_matches("bytes=").to_string());
+        requests.lock().unwrap().push(range);
+        Response::ranged(&data, &request)
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_repair_downloads_only_corrupt_ranges() {
+    let root = env::temp_dir().join(format!("vekt_repair_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let range_size = 4096;
+    let data: Vec<u8> = (0..4 * range_size + 100)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect();
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("w", vec![data.len()], "U8", &data)
+        .unwrap();
+    let tensor = manifest.tensors.get_mut("w").unwrap();
+    tensor.ranges = Some(RangeHashes {
+        range_size: range_size as u64,
+        hashes: data
+            .chunks(range_size)
+            .enumerate()
+            .map(|(i, range)| range_hash((i * range_size) as u64, range))
+            .collect(),
+    });
+    let hash = tensor.hash.clone();
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let client = RemoteClient::new(&serve_blob(data.clone(), requests.clone())).unwrap();
+
+    // An intact blob downloads nothing
+    let repairs = client.repair(&manifest).await.unwrap();
+    assert_eq!(repairs, [(hash.clone(), BlobRepair::Intact)]);
+    assert!(requests.lock().unwrap().is_empty());
+
+    // One flipped byte only fetches its range
+    let path = blobs::get_blob_path(&hash);
+    let mut corrupt = data.clone();
+    corrupt[range_size + 5] ^= 0xff;
+    fs::write(&path, &corrupt).unwrap();
+    let repairs = client.repair(&manifest).await.unwrap();
+    assert_eq!(
+        repairs,
+        [(
+            hash.clone(),
+            BlobRepair::Ranges {
+                ranges: vec![1],
+                bytes: range_size as u64
+            }
+        )]
+    );
+    assert_eq!(*requests.lock().unwrap(), [Some("4096-8191".to_string())]);
+    assert_eq!(fs::read(&path).unwrap(), data);
+
+    // A truncated blob can't be patched by range and is downloaded whole
+    fs::write(&path, &data[..100]).unwrap();
+    let repairs = client.repair(&manifest).await.unwrap();
+    assert_eq!(
+        repairs,
+        [(
+            hash.clone(),
+            BlobRepair::Whole {
+                bytes: data.len() as u64
+            }
+        )]
+    );
+    assert_eq!(requests.lock().unwrap().last(), Some(&None));
+    assert_eq!(fs::read(&path).unwrap(), data);
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -61,6 +61,8 @@ fn test_shared_weights_deduplication() {
             hash: hash.clone(),
             index: 0,
             extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
         },
     );
 
@@ -72,13 +74,20 @@ fn test_shared_weights_deduplication() {
             hash: hash.clone(),
             index: 1,
             extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
         },
     );
 
     let manifest = VektManifest {
         tensors,
-        version: "1.0".to_string(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
         total_size: 4,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
     };
 
     let output_path = std::path::Path::new("test_shared.safetensors");
@@ -122,6 +131,8 @@ fn test_alignment_padding() {
             hash: hash_a.clone(),
             index: 0,
             extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
         },
     );
 
@@ -133,13 +144,20 @@ fn test_alignment_padding() {
             hash: hash_b.clone(),
             index: 1,
             extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
         },
     );
 
     let manifest = VektManifest {
         tensors,
-        version: "1.0".to_string(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
         total_size: 2,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
     };
 
     let output_path = std::path::Path::new("test_aligned.safetensors");
@@ -154,6 +172,7 @@ fn test_alignment_padding() {
     let data_section = &buffer[data_start..];
 
     assert_eq!(data_section[0], 0xCC);
+    #[allow(clippy::needless_range_loop)]
     for i in 1..8 {
         assert_eq!(data_section[i], 0x00, "Padding mismatch");
     }
@@ -182,13 +201,20 @@ fn test_extra_metadata_preservation() {
             hash: hash.clone(),
             index: 0,
             extra,
+            ranges: None,
+            stats: None,
         },
     );
 
     let manifest = VektManifest {
         tensors,
-        version: "1.0".to_string(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
         total_size: 1,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
     };
 
     let output_path = std::path::Path::new("test_meta.safetensors");
@@ -206,3 +232,231 @@ fn test_extra_metadata_preservation() {
     std::fs::remove_file(output_path).unwrap();
     std::fs::remove_file(blobs::get_blob_path(&hash)).ok();
 }
+
+#[test]
+fn test_attachment_roundtrip() {
+    let source_path = std::path::Path::new("test_attach_tokenizer.json");
+    std::fs::write(source_path, br#"{"vocab": ["a", "b"]}"#).unwrap();
+
+    let mut manifest = VektManifest {
+        tensors: BTreeMap::new(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 0,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+    let name = manifest
+        .attach_file(source_path, Some("tokenizer.json"))
+        .unwrap();
+    assert_eq!(name, "tokenizer.json");
+    assert!(
+        manifest
+            .attach_file(source_path, Some("../escape.json"))
+            .is_err()
+    );
+
+    let out_dir = std::path::Path::new("test_attach_out");
+    let restored = manifest.restore_attachments(out_dir).unwrap();
+    assert_eq!(restored, 1);
+    assert_eq!(
+        std::fs::read(out_dir.join("tokenizer.json")).unwrap(),
+        std::fs::read(source_path).unwrap()
+    );
+
+    let hash = manifest.attachments["tokenizer.json"].hash.clone();
+    std::fs::remove_file(source_path).unwrap();
+    std::fs::remove_dir_all(out_dir).unwrap();
+    std::fs::remove_file(blobs::get_blob_path(&hash)).ok();
+}
+
+#[test]
+fn test_discover_sidecars() {
+    let dir = std::env::temp_dir().join(format!("vekt_sidecars_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(dir.join("tokenizer.json")).unwrap();
+    assert!(vekt_core::attachments::discover_sidecars(&dir).is_empty());
+
+    // Known names that are files are found in list order; directories and other
+    // files aren't
+    for name in ["vocab.txt", "config.json", "notes.txt"] {
+        std::fs::write(dir.join(name), b"{}").unwrap();
+    }
+    assert_eq!(
+        vekt_core::attachments::discover_sidecars(&dir),
+        vec![dir.join("config.json"), dir.join("vocab.txt")]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_apply_lora_adapter() {
+    let f32_blob = |values: &[f32]| -> String {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        create_blob(&bytes)
+    };
+    let tensor = |hash: &str, shape: Vec<usize>, index: usize| ManifestTensor {
+        shape,
+        dtype: "F32".to_string(),
+        hash: hash.to_string(),
+        index,
+        extra: IndexMap::new(),
+        ranges: None,
+        stats: None,
+    };
+
+    let base_hash = f32_blob(&[1.0, 0.0, 0.0, 1.0]);
+    let mut base_tensors = BTreeMap::new();
+    base_tensors.insert("proj.weight".to_string(), tensor(&base_hash, vec![2, 2], 0));
+    let base = VektManifest {
+        tensors: base_tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 16,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+
+    let a_hash = f32_blob(&[1.0, 2.0]);
+    let b_hash = f32_blob(&[3.0, 4.0]);
+    let mut adapter_tensors = BTreeMap::new();
+    adapter_tensors.insert(
+        "base_model.model.proj.lora_A.weight".to_string(),
+        tensor(&a_hash, vec![1, 2], 0),
+    );
+    adapter_tensors.insert(
+        "base_model.model.proj.lora_B.weight".to_string(),
+        tensor(&b_hash, vec![2, 1], 1),
+    );
+    let adapter = VektManifest {
+        tensors: adapter_tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 16,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+
+    // W + 0.5 * B @ A = I + 0.5 * [[3, 6], [4, 8]]
+    let merged = base.apply_lora(&adapter, 0.5).unwrap();
+    let merged_hash = merged.tensors["proj.weight"].hash.clone();
+    let values: Vec<f32> = blobs::read_blob(&merged_hash)
+        .unwrap()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(values, vec![2.5, 3.0, 2.0, 5.0]);
+
+    let side_by_side = base.with_adapter_side_by_side(&adapter).unwrap();
+    assert_eq!(side_by_side.tensors.len(), 3);
+    assert_eq!(side_by_side.tensors["proj.weight"].hash, base_hash);
+
+    for hash in [base_hash, a_hash, b_hash, merged_hash] {
+        std::fs::remove_file(blobs::get_blob_path(&hash)).ok();
+    }
+}
+
+#[test]
+fn test_restore_verify_policy() {
+    use vekt_core::errors::VektError;
+    use vekt_core::validation::VerifyPolicy;
+
+    // Random contents so corrupting the blob can't affect other tests
+    let data: Vec<u8> = (0..16).map(|_| rand::random::<u8>()).collect();
+    let hash = create_blob(&data);
+    std::fs::write(blobs::get_blob_path(&hash), [0u8; 16]).unwrap();
+
+    let mut tensors = BTreeMap::new();
+    tensors.insert(
+        "weight".to_string(),
+        ManifestTensor {
+            shape: vec![16],
+            dtype: "U8".to_string(),
+            hash: hash.clone(),
+            index: 0,
+            extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
+        },
+    );
+    let manifest = VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 16,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+
+    let output_path = std::path::Path::new("test_verify_policy.safetensors");
+    let result = manifest.restore_with_policy(output_path, None, VerifyPolicy::Always);
+    assert!(matches!(result, Err(VektError::HashMismatch { .. })));
+    // Skipping verification trusts the store, corrupted or not
+    manifest
+        .restore_with_policy(output_path, None, VerifyPolicy::Never)
+        .unwrap();
+
+    std::fs::remove_file(output_path).unwrap();
+    std::fs::remove_file(blobs::get_blob_path(&hash)).ok();
+}
+
+#[test]
+fn test_restore_to_writer() {
+    use vekt_core::tensor_filter::TensorFilter;
+    use vekt_core::validation::VerifyPolicy;
+
+    let data: Vec<u8> = (0..8).map(|_| rand::random::<u8>()).collect();
+    let mut tensors = BTreeMap::new();
+    for (index, name) in ["a.weight", "b.weight"].iter().enumerate() {
+        tensors.insert(
+            name.to_string(),
+            ManifestTensor {
+                shape: vec![4],
+                dtype: "U8".to_string(),
+                hash: create_blob(&data[index * 4..(index + 1) * 4]),
+                index,
+                extra: IndexMap::new(),
+                ranges: None,
+                stats: None,
+            },
+        );
+    }
+    let manifest = VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 8,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    };
+
+    // Streaming produces exactly the file a restore to disk would
+    let output_path = std::path::Path::new("test_restore_to_writer.safetensors");
+    manifest.restore(output_path, None).unwrap();
+    let mut streamed = Vec::new();
+    manifest
+        .restore_to_writer(&mut streamed, &TensorFilter::new(), VerifyPolicy::Always)
+        .unwrap();
+    assert_eq!(streamed, std::fs::read(output_path).unwrap());
+    std::fs::remove_file(output_path).unwrap();
+
+    let mut partial = Vec::new();
+    manifest
+        .restore_to_writer(
+            &mut partial,
+            &TensorFilter::new().exclude("a."),
+            VerifyPolicy::Always,
+        )
+        .unwrap();
+    assert!(partial.ends_with(&data[4..]));
+    assert!(partial.len() < streamed.len());
+}
@@ -0,0 +1,122 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::errors::VektError;
+use vekt_core::refs;
+use vekt_core::remote::{RemoteClient, RemoteCredentials};
+use vekt_core::serve::BlobServer;
+use vekt_core::storage::VektManifest;
+use vekt_core::utils::LockFile;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_serve_acts_as_remote() {
+    let root = env::temp_dir().join(format!("vekt_serve_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+        env::set_var("VEKT_TEST_SERVE_TOKEN", "secret");
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = BlobServer::new(Some("secret".to_string())).unwrap();
+    tokio::spawn(server.run(listener));
+
+    // Without the token nothing is served
+    let anonymous = RemoteClient::new(&url).unwrap();
+    assert!(matches!(
+        anonymous.validate_access().await,
+        Err(VektError::CredentialError(_))
+    ));
+    let credentials = RemoteCredentials {
+        token_env: Some("VEKT_TEST_SERVE_TOKEN".to_string()),
+        ..Default::default()
+    };
+    let client = RemoteClient::with_credentials(&url, &credentials).unwrap();
+    client.validate_access().await.unwrap();
+
+    // Uploads must match their hash
+    let http = reqwest::Client::new();
+    let data = b"remote blob".to_vec();
+    let hash = blobs::compute_blob_hash(&data);
+    let wrong = http
+        .put(format!("{}/blobs/{}", url, "0".repeat(64)))
+        .bearer_auth("secret")
+        .body(data.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong.status(), 400);
+    let stored = http
+        .put(format!("{}/blobs/{}", url, hash))
+        .bearer_auth("secret")
+        .body(data.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stored.status(), 200);
+    assert_eq!(blobs::read_blob(&hash).unwrap(), data);
+    // An upload waits for a gc or other command holding the repository lock
+    let held = LockFile::lock().unwrap();
+    let late = b"uploaded during gc".to_vec();
+    let late_hash = blobs::compute_blob_hash(&late);
+    let upload = tokio::spawn(
+        http.put(format!("{}/blobs/{}", url, late_hash))
+            .bearer_auth("secret")
+            .body(late)
+            .send(),
+    );
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!blobs::blob_exists(&late_hash));
+    drop(held);
+    assert_eq!(upload.await.unwrap().unwrap().status(), 200);
+    assert!(blobs::blob_exists(&late_hash));
+    let guessed = http
+        .get(format!("{}/blobs/{}", url, hash))
+        .bearer_auth("secreT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(guessed.status(), 401);
+    let partial = http
+        .get(format!("{}/blobs/{}", url, hash))
+        .bearer_auth("secret")
+        .header("Range", "bytes=2-5")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(partial.status(), 206);
+    assert_eq!(partial.bytes().await.unwrap().as_ref(), b"mote");
+    let traversal = http
+        .get(format!("{}/manifests/..%2Fsecret.vekt.json", url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(traversal.status(), 404);
+
+    let mut manifest = VektManifest::new();
+    manifest.add_tensor("w", vec![2], "F32", &[3; 8]).unwrap();
+    client.push(&manifest, "model.vekt.json").await.unwrap();
+    let served = VektManifest::load(&root.join("model.vekt.json")).unwrap();
+    assert_eq!(served.tensors["w"].hash, manifest.tensors["w"].hash);
+    let pulled = client.pull("model.vekt.json").await.unwrap();
+    assert_eq!(pulled.total_size, manifest.total_size);
+
+    let tag_hash = refs::store_manifest_object(&manifest).unwrap();
+    client.push_tag("v1", &tag_hash, &manifest).await.unwrap();
+    assert_eq!(
+        client.list_tags().await.unwrap(),
+        [("v1".to_string(), tag_hash)]
+    );
+
+    let report = client.verify_sample(1.0, 7).await.unwrap();
+    assert_eq!(report.total_blobs, 3);
+    assert!(report.failed.is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+
+use vekt_core::audit::TransferDigest;
+use vekt_core::blobs;
+use vekt_core::plan;
+use vekt_core::sparse::{self, SparseSpec};
+use vekt_core::storage::VektManifest;
+use vekt_core::tensor_filter::TensorFilter;
+use vekt_core::validation::VerifyPolicy;
+
+#[test]
+fn test_sparse_spec_limits_pull_and_restore() {
+    let root = env::temp_dir().join(format!("vekt_sparse_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("model.embed_tokens.weight", vec![2], "F32", &[1; 8])
+        .unwrap();
+    manifest
+        .add_tensor("model.layers.0.mlp.weight", vec![2], "F32", &[2; 8])
+        .unwrap();
+    manifest
+        .add_tensor("lm_head.weight", vec![2], "F32", &[3; 8])
+        .unwrap();
+    manifest.attach_bytes("config.json", b"{}").unwrap();
+    let layer = manifest.tensors["model.layers.0.mlp.weight"].hash.clone();
+    let head = manifest.tensors["lm_head.weight"].hash.clone();
+
+    assert_eq!(SparseSpec::load().unwrap(), None);
+    assert_eq!(
+        sparse::wanted_blobs(&manifest).unwrap().len(),
+        manifest.referenced_hashes().count()
+    );
+
+    // A sparse repository neither has nor wants the layers
+    SparseSpec::save(&["*embed_tokens*".to_string(), "lm_head.*".to_string()]).unwrap();
+    fs::remove_file(blobs::get_blob_path(&layer)).unwrap();
+    fs::remove_file(blobs::get_blob_path(&head)).unwrap();
+    let wanted = sparse::wanted_blobs(&manifest).unwrap();
+    assert!(!wanted.contains(&layer));
+    assert!(wanted.contains(&manifest.attachments["config.json"].hash));
+    let download = plan::plan_download(&[&manifest]).unwrap();
+    let planned: Vec<&String> = download.blobs.iter().map(|b| &b.hash).collect();
+    assert_eq!(planned, vec![&head]);
+    assert_eq!(manifest.sparse_excluded().unwrap(), 1);
+    // A pull's record covers what it fetched, not the layers it left on the remote
+    let mut pulled = TransferDigest::new();
+    pulled.add_wanted(&manifest).unwrap();
+    assert_eq!(pulled.blob_count(), wanted.len());
+    blobs::save_blob_deduplicated(&[3; 8]).unwrap();
+
+    // Library restores stay whole unless asked for the sparse set, so they miss the layer
+    let output = root.join("model.safetensors");
+    assert!(
+        manifest
+            .restore_filtered(&output, &TensorFilter::new(), VerifyPolicy::Always)
+            .is_err()
+    );
+    // Restores that ask for it assemble only the wanted tensors, without touching the missing blob
+    let spec = SparseSpec::load().unwrap().unwrap();
+    manifest
+        .restore_filtered(
+            &output,
+            &TensorFilter::new().sparse(spec),
+            VerifyPolicy::Always,
+        )
+        .unwrap();
+    let restored = vekt_core::SafetensorFile::open(output.to_str().unwrap()).unwrap();
+    assert!(restored.header.contains_key("model.embed_tokens.weight"));
+    assert!(restored.header.contains_key("lm_head.weight"));
+    assert!(!restored.header.contains_key("model.layers.0.mlp.weight"));
+
+    assert!(SparseSpec::remove().unwrap());
+    assert_eq!(manifest.sparse_excluded().unwrap(), 0);
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,43 @@
+use std::env;
+use std::fs;
+
+use vekt_core::numeric::encode_floats;
+use vekt_core::storage::VektManifest;
+
+#[test]
+fn test_stats_are_recorded_and_reused() {
+    let root = env::temp_dir().join(format!("vekt_stats_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor(
+            "w",
+            vec![3],
+            "BF16",
+            &encode_floats(&[1.0, f64::NAN, -2.0], "BF16").unwrap(),
+        )
+        .unwrap()
+        .add_tensor("ids", vec![2], "U8", &[1, 2])
+        .unwrap();
+    assert_eq!(manifest.compute_stats(None).unwrap(), 1);
+    let stats = manifest.tensors["w"].stats.unwrap();
+    assert_eq!((stats.min, stats.max, stats.nan_count), (-2.0, 1.0, 1));
+    assert!(manifest.tensors["ids"].stats.is_none());
+
+    let path = root.join("model.vekt.json");
+    manifest.save(&path).unwrap();
+    let loaded = VektManifest::load(&path).unwrap();
+    assert_eq!(loaded.tensors["w"].stats, Some(stats));
+
+    // Unchanged tensors aren't decoded again
+    let mut readded = loaded.clone();
+    readded.tensors.get_mut("w").unwrap().stats = None;
+    assert_eq!(readded.compute_stats(Some(&loaded)).unwrap(), 0);
+    assert_eq!(readded.tensors["w"].stats, Some(stats));
+
+    let _ = fs::remove_dir_all(&root);
+}
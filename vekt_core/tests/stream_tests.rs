@@ -0,0 +1,49 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::errors::VektError;
+use vekt_core::stream::ingest_reader;
+
+#[test]
+fn test_ingest_reader() {
+    let root = env::temp_dir().join(format!("vekt_stream_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    // Tensors out of offset order, with a gap between them
+    let header = br#"{"__metadata__": {"format": "pt"}, "b": {"dtype": "U8", "shape": [4], "data_offsets": [8, 12]}, "a": {"dtype": "U8", "shape": [3], "data_offsets": [0, 3]}}"#;
+    let mut file = (header.len() as u64).to_le_bytes().to_vec();
+    file.extend_from_slice(header);
+    file.extend_from_slice(&[1, 2, 3, 0, 0, 0, 0, 0, 4, 5, 6, 7]);
+
+    let stored = std::cell::RefCell::new(Vec::new());
+    let manifest = ingest_reader(&file[..], &|hash| {
+        stored.borrow_mut().push(hash.to_string())
+    })
+    .unwrap();
+    assert_eq!(manifest.total_size, file.len());
+    assert_eq!(manifest.tensors["a"].index, 1);
+    assert_eq!(manifest.tensors["b"].index, 0);
+    assert_eq!(
+        blobs::read_blob(&manifest.tensors["b"].hash).unwrap(),
+        [4, 5, 6, 7]
+    );
+    assert_eq!(
+        *stored.borrow(),
+        [
+            manifest.tensors["a"].hash.clone(),
+            manifest.tensors["b"].hash.clone()
+        ]
+    );
+
+    // A truncated stream is an error, not a manifest with missing data
+    assert!(matches!(
+        ingest_reader(&file[..file.len() - 2], &|_| {}),
+        Err(VektError::InvalidSafetensor(_))
+    ));
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,90 @@
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::storage::{ManifestTensor, VektManifest};
+use vekt_core::swap::{CURRENT_LINK, SwapEvent, WEIGHTS_FILE, current_version, swap};
+
+fn manifest(data: &[u8]) -> VektManifest {
+    let hash = blobs::write_blob_atomic(data).unwrap();
+    let mut tensors = BTreeMap::new();
+    tensors.insert(
+        "weight".to_string(),
+        ManifestTensor {
+            shape: vec![data.len()],
+            dtype: "U8".to_string(),
+            hash,
+            index: 0,
+            extra: IndexMap::new(),
+            ranges: None,
+            stats: None,
+        },
+    );
+    VektManifest {
+        tensors,
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 0,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    }
+}
+
+#[test]
+fn test_swap_and_rollback() {
+    let mut root = env::temp_dir();
+    root.push("vekt_swap_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+    let target = root.join("serving");
+
+    let first = manifest(&[1, 2, 3, 4]);
+    let result = swap(&first, &target, None).unwrap();
+    assert_eq!(result.event, SwapEvent::Swapped);
+    assert_eq!(result.previous, None);
+    let v1 = result.version.clone();
+    assert!(target.join(CURRENT_LINK).join(WEIGHTS_FILE).is_file());
+
+    // Same manifest again is a no-op
+    assert_eq!(
+        swap(&first, &target, None).unwrap().event,
+        SwapEvent::Unchanged
+    );
+
+    let second = manifest(&[5, 6, 7, 8]);
+    let result = swap(&second, &target, None).unwrap();
+    assert_eq!(result.event, SwapEvent::Swapped);
+    assert_eq!(result.previous.as_deref(), Some(v1.as_str()));
+    let v2 = result.version.clone();
+    assert_eq!(current_version(&target).as_deref(), Some(v2.as_str()));
+    // Old version stays on disk for readers still holding it open
+    assert!(target.join(&v1).is_dir());
+
+    // A corrupted blob fails verification and leaves `current` alone
+    let third = manifest(&[9, 9, 9, 9]);
+    fs::write(
+        blobs::get_blob_path(&third.tensors["weight"].hash),
+        [0u8; 4],
+    )
+    .unwrap();
+    let marker = root.join("hook.log");
+    let hook = format!("echo $VEKT_SWAP_EVENT > {}", marker.display());
+    let result = swap(&third, &target, Some(&hook)).unwrap();
+    assert_eq!(result.event, SwapEvent::RolledBack);
+    assert!(result.error.is_some());
+    assert_eq!(current_version(&target).as_deref(), Some(v2.as_str()));
+    assert!(!target.join(&result.version).exists());
+    assert_eq!(fs::read_to_string(&marker).unwrap().trim(), "rolled_back");
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}
@@ -0,0 +1,153 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::{ModelArchiver, TfCheckpoint};
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn field_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn field_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, (field << 3) | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// BundleEntryProto for an F32 tensor
+fn bundle_entry(dims: &[u64], shard: u64, offset: u64, size: u64) -> Vec<u8> {
+    let mut shape = Vec::new();
+    for dim in dims {
+        let mut d = Vec::new();
+        field_varint(&mut d, 1, *dim);
+        field_bytes(&mut shape, 2, &d);
+    }
+    let mut entry = Vec::new();
+    field_varint(&mut entry, 1, 1);
+    field_bytes(&mut entry, 2, &shape);
+    field_varint(&mut entry, 3, shard);
+    field_varint(&mut entry, 4, offset);
+    field_varint(&mut entry, 5, size);
+    entry
+}
+
+/// Block without prefix compression, one restart point, plus an uncompressed trailer
+fn block(entries: &[(&[u8], Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (key, value) in entries {
+        varint(&mut out, 0);
+        varint(&mut out, key.len() as u64);
+        varint(&mut out, value.len() as u64);
+        out.extend_from_slice(key);
+        out.extend_from_slice(value);
+    }
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out
+}
+
+fn handle(offset: usize, size: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    varint(&mut out, offset as u64);
+    varint(&mut out, size as u64);
+    out
+}
+
+/// SSTable with a single data block
+fn table(entries: &[(&[u8], Vec<u8>)]) -> Vec<u8> {
+    let mut file = Vec::new();
+    let data = block(entries);
+    let data_handle = handle(0, data.len());
+    file.extend_from_slice(&data);
+    file.extend_from_slice(&[0; 5]);
+
+    let meta = block(&[]);
+    let meta_handle = handle(file.len(), meta.len());
+    file.extend_from_slice(&meta);
+    file.extend_from_slice(&[0; 5]);
+
+    let last_key = entries.last().unwrap().0;
+    let index = block(&[(last_key, data_handle)]);
+    let index_handle = handle(file.len(), index.len());
+    file.extend_from_slice(&index);
+    file.extend_from_slice(&[0; 5]);
+
+    let mut footer = [meta_handle, index_handle].concat();
+    footer.resize(40, 0);
+    file.extend_from_slice(&footer);
+    file.extend_from_slice(&0xdb47_7524_8b80_fb57u64.to_le_bytes());
+    file
+}
+
+#[test]
+fn test_tf_checkpoint_roundtrip() {
+    let mut root = env::temp_dir();
+    root.push("vekt_tensorflow_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let bias: Vec<u8> = (0..16).collect();
+    let kernel: Vec<u8> = (50..66).collect();
+    let mut header = Vec::new();
+    field_varint(&mut header, 1, 2);
+    let mut sliced = bundle_entry(&[8], 0, 0, 0);
+    field_bytes(&mut sliced, 7, &[]);
+    let index = table(&[
+        (b"", header),
+        (b"dense/bias", bundle_entry(&[4], 0, 0, 16)),
+        (b"dense/kernel", bundle_entry(&[2, 2], 1, 8, 16)),
+        (b"partitioned", sliced),
+    ]);
+    let shard0 = [bias.clone(), vec![0xEE; 4]].concat();
+    let shard1 = [vec![0xAB; 8], kernel.clone()].concat();
+
+    let ckpt = root.join("ckpt");
+    fs::create_dir_all(&ckpt).unwrap();
+    fs::write(ckpt.join("model.ckpt-100.index"), &index).unwrap();
+    fs::write(ckpt.join("model.ckpt-100.data-00000-of-00002"), &shard0).unwrap();
+    fs::write(ckpt.join("model.ckpt-100.data-00001-of-00002"), &shard1).unwrap();
+
+    let manifest = TfCheckpoint::open(&ckpt.join("model.ckpt-100.index"))
+        .unwrap()
+        .process(true)
+        .unwrap();
+    assert_eq!(manifest.tensors.len(), 2);
+    assert_eq!(manifest.tensors["dense/kernel"].shape, vec![2, 2]);
+    assert_eq!(manifest.tensors["dense/kernel"].dtype, "F32");
+    assert_eq!(
+        manifest.tensors["dense/kernel"].hash,
+        blobs::compute_blob_hash(&kernel)
+    );
+    assert_eq!(
+        manifest.total_size,
+        index.len() + shard0.len() + shard1.len()
+    );
+
+    // Shards follow the prefix of the restored index file
+    let out = root.join("out");
+    fs::create_dir_all(&out).unwrap();
+    manifest.restore(&out.join("restored.index"), None).unwrap();
+    assert_eq!(fs::read(out.join("restored.index")).unwrap(), index);
+    assert_eq!(
+        fs::read(out.join("restored.data-00000-of-00002")).unwrap(),
+        shard0
+    );
+    assert_eq!(
+        fs::read(out.join("restored.data-00001-of-00002")).unwrap(),
+        shard1
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
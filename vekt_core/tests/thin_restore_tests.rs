@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::remote::{RemoteClient, ThinRestore};
+use vekt_core::storage::VektManifest;
+use vekt_core::tensor_filter::TensorFilter;
+
+mod common;
+use common::Response;
+
+/// Serves `objects` by key over plain HTTP GET, 404 for anything else
+fn serve_objects(objects: HashMap<String, Vec<u8>>) -> String {
+    common::serve(
+        move |request| match objects.get(request.path.trim_start_matches('/')) {
+            Some(body) => Response::ok(body.clone()),
+            None => Response::not_found(),
+        },
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_thin_restore_streams_from_remote() {
+    let root = env::temp_dir().join(format!("vekt_thin_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("a", vec![3], "U8", &[1, 2, 3])
+        .unwrap()
+        .add_tensor("b", vec![5], "U8", &[4, 5, 6, 7, 8])
+        .unwrap()
+        .add_tensor("tied", vec![3], "U8", &[1, 2, 3])
+        .unwrap();
+    manifest.attach_bytes("tokenizer.json", b"{}").unwrap();
+    let expected = root.join("expected.safetensors");
+    manifest.restore(&expected, None).unwrap();
+
+    // The remote has everything; the local store only has what `local` names
+    let mut objects = HashMap::new();
+    for hash in manifest.referenced_hashes() {
+        objects.insert(format!("blobs/{}", hash), blobs::read_blob(hash).unwrap());
+    }
+    objects.insert(
+        "manifests/model.vekt.json".to_string(),
+        serde_json::to_vec(&manifest).unwrap(),
+    );
+    let local = manifest.tensors["b"].hash.clone();
+    for entry in fs::read_dir(root.join(".vekt/blobs")).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name().to_string_lossy() != local {
+            fs::remove_file(entry.path()).unwrap();
+        }
+    }
+
+    let client = RemoteClient::new(&serve_objects(objects)).unwrap();
+    let fetched = client.fetch_manifest("model.vekt.json").await.unwrap();
+    let output = root.join("out/model.safetensors");
+    fs::create_dir_all(output.parent().unwrap()).unwrap();
+    let restore = client
+        .restore_thin(&fetched, &output, &TensorFilter::default(), false)
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&expected).unwrap());
+    assert_eq!(
+        restore,
+        ThinRestore {
+            bytes: fs::metadata(&expected).unwrap().len(),
+            downloaded: 1,
+            local: 1,
+        }
+    );
+    let dir = output.parent().unwrap();
+    assert_eq!(
+        client
+            .restore_thin_attachments(&fetched, dir, false)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(fs::read(dir.join("tokenizer.json")).unwrap(), b"{}");
+
+    // Nothing was cached, so the blobs are still only on the remote, and the files
+    // they were streamed through are gone
+    let remote_only = &manifest.tensors["a"].hash;
+    assert!(!blobs::blob_exists(remote_only));
+    let stored: Vec<String> = fs::read_dir(root.join(".vekt/blobs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(stored, vec![local.clone()]);
+    let mut streamed = Vec::new();
+    client
+        .restore_thin_to_writer(&fetched, &mut streamed, &TensorFilter::default(), true)
+        .await
+        .unwrap();
+    assert_eq!(streamed, fs::read(&expected).unwrap());
+    assert!(blobs::blob_exists(remote_only));
+
+    // A missing manifest fails, and a failed restore leaves no partial output
+    assert!(client.fetch_manifest("missing.vekt.json").await.is_err());
+    let mut broken = fetched.clone();
+    broken.tensors.get_mut("b").unwrap().hash = "0".repeat(64);
+    let failed = root.join("failed.safetensors");
+    assert!(
+        client
+            .restore_thin(&broken, &failed, &TensorFilter::default(), false)
+            .await
+            .is_err()
+    );
+    assert!(!failed.exists());
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,115 @@
+use std::env;
+use std::fs;
+
+use vekt_core::storage::VektManifest;
+use vekt_core::three_way::{Change, EntryKind, Favor, MergeConflict, merge_three_way};
+
+#[test]
+fn test_three_way_merge() {
+    let root = env::temp_dir().join(format!("vekt_three_way_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut base = VektManifest::new();
+    base.add_tensor("embed", vec![2], "F32", &[1; 8])
+        .unwrap()
+        .add_tensor("layer.0", vec![2], "F32", &[2; 8])
+        .unwrap()
+        .add_tensor("layer.1", vec![2], "F32", &[3; 8])
+        .unwrap()
+        .add_tensor("head", vec![2], "F32", &[4; 8])
+        .unwrap()
+        .set_metadata("step", "100");
+
+    // We fine-tune layer.0 and drop head; they fine-tune layer.1 and add an adapter
+    let mut ours = base.clone();
+    ours.remove_tensor("layer.0").unwrap();
+    ours.add_tensor("layer.0", vec![2], "F32", &[5; 8]).unwrap();
+    ours.remove_tensor("head").unwrap();
+    let mut theirs = base.clone();
+    theirs.remove_tensor("layer.1").unwrap();
+    theirs
+        .add_tensor("layer.1", vec![2], "F32", &[6; 8])
+        .unwrap();
+    theirs
+        .add_tensor("adapter", vec![2], "F32", &[7; 8])
+        .unwrap();
+
+    let merge = merge_three_way(&base, &ours, &theirs, None).unwrap();
+    assert!(merge.conflicts.is_empty(), "{:?}", merge.conflicts);
+    let merged = &merge.manifest;
+    assert_eq!(merged.tensors["layer.0"].hash, ours.tensors["layer.0"].hash);
+    assert_eq!(
+        merged.tensors["layer.1"].hash,
+        theirs.tensors["layer.1"].hash
+    );
+    assert_eq!(
+        merged.tensors["adapter"].hash,
+        theirs.tensors["adapter"].hash
+    );
+    assert!(!merged.tensors.contains_key("head"));
+    assert_eq!(merge.from_ours, vec!["head", "layer.0"]);
+    assert_eq!(merge.from_theirs, vec!["adapter", "layer.1"]);
+    merged.validate().unwrap();
+    assert_eq!(merged.tensors["embed"].index, 0);
+    assert_eq!(merged.tensors["adapter"].index, 3);
+    assert_eq!(merged.total_size, {
+        let mut expected = merged.clone();
+        expected.recompute_total_size().unwrap()
+    });
+
+    // Both sides changing the same tensor differently is a conflict, reported by name
+    let mut theirs = theirs.clone();
+    theirs.remove_tensor("layer.0").unwrap();
+    theirs
+        .add_tensor("layer.0", vec![2], "F32", &[8; 8])
+        .unwrap();
+    theirs.remove_tensor("head").unwrap();
+    theirs.add_tensor("head", vec![2], "F32", &[9; 8]).unwrap();
+    theirs.set_metadata("step", "200");
+    ours.set_metadata("step", "300");
+    let merge = merge_three_way(&base, &ours, &theirs, None).unwrap();
+    assert_eq!(
+        merge.conflicts,
+        vec![
+            MergeConflict {
+                kind: EntryKind::Tensor,
+                name: "head".to_string(),
+                ours: Change::Removed,
+                theirs: Change::Modified,
+            },
+            MergeConflict {
+                kind: EntryKind::Tensor,
+                name: "layer.0".to_string(),
+                ours: Change::Modified,
+                theirs: Change::Modified,
+            },
+            MergeConflict {
+                kind: EntryKind::Metadata,
+                name: "step".to_string(),
+                ours: Change::Modified,
+                theirs: Change::Modified,
+            },
+        ]
+    );
+    assert_eq!(
+        merge.conflicts[0].to_string(),
+        "tensor 'head': removed in ours, modified in theirs"
+    );
+
+    // Favoring a side resolves conflicts with its versions
+    let merge = merge_three_way(&base, &ours, &theirs, Some(Favor::Theirs)).unwrap();
+    assert_eq!(
+        merge.manifest.tensors["layer.0"].hash,
+        theirs.tensors["layer.0"].hash
+    );
+    assert!(merge.manifest.tensors.contains_key("head"));
+    assert_eq!(merge.manifest.metadata["step"], "200");
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::checksum;
+use vekt_core::errors::VektError;
+use vekt_core::gc;
+use vekt_core::refs;
+use vekt_core::storage::VektManifest;
+use vekt_core::tombstones;
+
+#[test]
+fn test_rm_leaves_blobs_to_gc_removed() {
+    let root = env::temp_dir().join(format!("vekt_rm_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let mut old = VektManifest::new();
+    old.add_tensor("w", vec![4], "U8", &[1, 2, 3, 4]).unwrap();
+    let old_hash = old.tensors["w"].hash.clone();
+    let model = root.join("old.safetensors");
+    fs::write(&model, b"weights").unwrap();
+    old.checksum = Some(checksum::file_checksum(&model).unwrap());
+    let old_path = root.join("old.vekt.json");
+    old.save(&old_path).unwrap();
+    // Same stem and size, different contents: not the model, so it's kept
+    let other = root.join("old.bin");
+    fs::write(&other, b"WEIGHTS").unwrap();
+
+    // An orphaned blob no removed manifest references
+    let (stray, _) = blobs::save_blob_deduplicated(&[9, 9, 9, 9]).unwrap();
+
+    let mut tagged = VektManifest::new();
+    tagged
+        .add_tensor("w", vec![4], "U8", &[5, 6, 7, 8])
+        .unwrap();
+    let tagged_hash = tagged.tensors["w"].hash.clone();
+    refs::create_tag("v1", &tagged, false).unwrap();
+
+    let removal = tombstones::remove(old_path.to_str().unwrap(), false).unwrap();
+    assert_eq!(removal.model.as_deref(), Some(model.as_path()));
+    assert!(!old_path.exists() && !model.exists());
+    assert!(other.exists());
+    assert_eq!(removal.tombstone.blobs, vec![old_hash.clone()]);
+    tombstones::remove("v1", true).unwrap();
+    assert!(refs::read_tag("v1").is_err());
+    assert_eq!(tombstones::list_tombstones().unwrap().len(), 2);
+    assert!(matches!(
+        tombstones::remove("v1", true),
+        Err(VektError::RefNotFound(_))
+    ));
+
+    let plan = gc::plan_gc_removed(&root).unwrap();
+    assert_eq!(plan.delete.len(), 2);
+    assert_eq!(plan.tombstones.len(), 2);
+    let stats = plan.execute().unwrap();
+    assert_eq!(stats.settled, 2);
+    assert!(!blobs::blob_exists(&old_hash));
+    assert!(!blobs::blob_exists(&tagged_hash));
+    assert!(blobs::blob_exists(&stray));
+    assert!(tombstones::list_tombstones().unwrap().is_empty());
+
+    // Without a checksum the model file can't be told apart, so it stays
+    let mut unchecked = VektManifest::new();
+    unchecked.add_tensor("w", vec![1], "U8", &[1]).unwrap();
+    let unchecked_path = root.join("unchecked.vekt.json");
+    unchecked.save(&unchecked_path).unwrap();
+    let unchecked_model = root.join("unchecked.safetensors");
+    fs::write(&unchecked_model, b"weights").unwrap();
+    let removal = tombstones::remove(unchecked_path.to_str().unwrap(), false).unwrap();
+    assert_eq!(removal.model, None);
+    assert!(!unchecked_path.exists() && unchecked_model.exists());
+
+    let _ = fs::remove_dir_all(&root);
+}
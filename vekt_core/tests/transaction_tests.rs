@@ -0,0 +1,90 @@
+use std::env;
+use std::fs;
+
+use vekt_core::blobs;
+use vekt_core::gc;
+use vekt_core::transaction::{Rollback, Transaction};
+
+/// A reader whose process dies after the first chunk
+struct DiesMidway;
+
+impl std::io::Read for DiesMidway {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        static READ: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if READ.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            panic!("process killed");
+        }
+        buf[0] = 1;
+        Ok(1)
+    }
+}
+
+#[test]
+fn test_transaction_rolls_back_new_blobs() {
+    let root = env::temp_dir().join(format!("vekt_transaction_{}", rand::random::<u64>()));
+    let store = root.join(".vekt/blobs");
+    fs::create_dir_all(&store).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+    let index = root.join(".vekt/index");
+    fs::write(&index, "{}").unwrap();
+    let (kept, _) = blobs::save_blob_deduplicated(b"already stored").unwrap();
+
+    // A failed add: one new blob, a re-used one and an index update that points at
+    // the new blob. Meanwhile another process uploads a blob and starts a temp file.
+    let transaction = Transaction::begin("add").unwrap();
+    let (added, _) = blobs::save_blob_deduplicated(b"new blob").unwrap();
+    blobs::save_blob_deduplicated(b"already stored").unwrap();
+    let uploaded = blobs::compute_blob_hash(b"uploaded");
+    fs::write(blobs::get_blob_path(&uploaded), b"uploaded").unwrap();
+    fs::write(store.join("partial.tmp"), b"half").unwrap();
+    fs::write(&index, "{\"changed\": true}").unwrap();
+    assert_eq!(transaction.rollback().unwrap(), Rollback { removed: 1 });
+
+    assert!(!blobs::blob_exists(&added));
+    assert!(blobs::blob_exists(&kept));
+    assert!(blobs::blob_exists(&uploaded));
+    assert!(store.join("partial.tmp").exists());
+    assert_eq!(fs::read_to_string(&index).unwrap(), "{}");
+    assert_eq!(fs::read_dir(root.join(".vekt/journal")).unwrap().count(), 0);
+
+    // Early returns drop the transaction, which rolls it back too
+    {
+        let _transaction = Transaction::begin("add").unwrap();
+        blobs::save_blob_deduplicated(b"new blob").unwrap();
+    }
+    assert!(!blobs::blob_exists(&added));
+
+    let transaction = Transaction::begin("add").unwrap();
+    blobs::save_blob_deduplicated(b"new blob").unwrap();
+    transaction.commit().unwrap();
+    assert!(blobs::blob_exists(&added));
+
+    // A process that dies mid-add leaves its journal and a half-written blob; gc
+    // finishes the rollback but leaves other writers' temp files alone
+    let transaction = Transaction::begin("add").unwrap();
+    let (orphan, _) = blobs::save_blob_deduplicated(b"orphan").unwrap();
+    let died = std::panic::catch_unwind(|| blobs::write_blob_from_reader(DiesMidway));
+    assert!(died.is_err());
+    std::mem::forget(transaction);
+    let temps = || {
+        fs::read_dir(&store)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name() != "partial.tmp")
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().ends_with(".tmp")
+            })
+            .count()
+    };
+    assert_eq!(temps(), 1);
+    let stats = gc::run_gc(&root).unwrap();
+    assert_eq!(stats.recovered, 1);
+    assert!(!blobs::blob_exists(&orphan));
+    assert_eq!(temps(), 0);
+    assert!(store.join("partial.tmp").exists());
+    assert_eq!(fs::read_dir(root.join(".vekt/journal")).unwrap().count(), 0);
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use vekt_core::errors::VektError;
+use vekt_core::refs;
+use vekt_core::remote::RemoteClient;
+use vekt_core::serve::BlobServer;
+use vekt_core::storage::VektManifest;
+use vekt_core::upstream;
+
+fn manifest(fill: u8) -> VektManifest {
+    let mut manifest = VektManifest::new();
+    manifest
+        .add_tensor("w", vec![2], "F32", &[fill; 8])
+        .unwrap();
+    manifest
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_push_requires_fast_forward() {
+    let root = env::temp_dir().join(format!("vekt_upstream_{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt/blobs")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(BlobServer::new(None).unwrap().run(listener));
+    let client = RemoteClient::new(&url).unwrap();
+    let name = "model.vekt.json";
+
+    // Pushing on top of our own previous push is a fast-forward
+    let v1 = manifest(1);
+    client.push(&v1, name).await.unwrap();
+    assert_eq!(
+        upstream::read(&url, name).unwrap(),
+        Some(upstream::manifest_hash(&v1).unwrap())
+    );
+    client.push(&manifest(2), name).await.unwrap();
+    client.push(&manifest(2), name).await.unwrap();
+
+    // A colleague replaces it behind our back
+    let theirs = manifest(3);
+    reqwest::Client::new()
+        .put(format!("{}/manifests/{}", url, name))
+        .body(theirs.to_json().unwrap())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let ours = manifest(4);
+    assert!(matches!(
+        client.push(&ours, name).await,
+        Err(VektError::NotFastForward(_))
+    ));
+    assert!(client.push_manifest(&ours, name).await.is_err());
+    assert!(matches!(
+        client.push_since(&ours, name, &HashSet::new()).await,
+        Err(VektError::NotFastForward(_))
+    ));
+    assert!(client.has_manifest(&theirs, Some(name)).await.unwrap());
+    assert!(!client.has_manifest(&ours, Some(name)).await.unwrap());
+    assert!(!client.has_manifest(&theirs, None).await.unwrap());
+    let served = VektManifest::load(&root.join(name)).unwrap();
+    assert_eq!(served.tensors["w"].hash, theirs.tensors["w"].hash);
+
+    // A manifest object that isn't what its hash says is refused, even just to peek.
+    // The server shares this root, so corrupting it on disk corrupts what it serves.
+    let claimed = upstream::manifest_hash(&ours).unwrap();
+    fs::create_dir_all(refs::manifest_objects_dir()).unwrap();
+    fs::write(
+        refs::manifest_objects_dir().join(&claimed),
+        theirs.to_json().unwrap(),
+    )
+    .unwrap();
+    assert!(matches!(
+        client.peek_tag(&claimed).await,
+        Err(VektError::HashMismatch { .. })
+    ));
+
+    fs::remove_file(refs::manifest_objects_dir().join(&claimed)).unwrap();
+
+    // Pulling their version makes it the base of the next push
+    let pulled = client.pull(name).await.unwrap();
+    assert_eq!(pulled.tensors["w"].hash, theirs.tensors["w"].hash);
+    client.push(&ours, name).await.unwrap();
+
+    // A clone that never pulled the manifest can only overwrite it with --force
+    let other = RemoteClient::new(&format!("{}/", url)).unwrap();
+    assert!(other.push(&manifest(5), name).await.is_err());
+    other
+        .with_force(true)
+        .push(&manifest(5), name)
+        .await
+        .unwrap();
+
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+    let _ = fs::remove_dir_all(&root);
+}
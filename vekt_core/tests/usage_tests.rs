@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+use indexmap::IndexMap;
+use vekt_core::blobs;
+use vekt_core::storage::{ManifestTensor, VektManifest};
+use vekt_core::usage::{SharedGroup, compute_dedup, compute_usage};
+
+fn manifest(hashes: &[&str]) -> VektManifest {
+    VektManifest {
+        tensors: hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| {
+                let tensor = ManifestTensor {
+                    shape: vec![4],
+                    dtype: "U8".to_string(),
+                    hash: hash.to_string(),
+                    index: i,
+                    extra: IndexMap::new(),
+                    ranges: None,
+                    stats: None,
+                };
+                (format!("t{}", i), tensor)
+            })
+            .collect(),
+        version: VektManifest::CURRENT_VERSION.to_string(),
+        total_size: 0,
+        checksum: None,
+        attachments: BTreeMap::new(),
+        metadata: BTreeMap::new(),
+        layout: None,
+        placement: Default::default(),
+    }
+}
+
+#[test]
+fn test_usage_report() {
+    let mut root = env::temp_dir();
+    root.push("vekt_usage_test");
+    root.push(format!("{}", rand::random::<u64>()));
+    fs::create_dir_all(root.join(".vekt")).unwrap();
+    unsafe {
+        env::set_var("VEKT_ROOT", &root);
+    }
+
+    let shared = blobs::write_blob_atomic(&[1, 2, 3, 4]).unwrap();
+    let only_a = blobs::write_blob_atomic(&[5, 6, 7, 8]).unwrap();
+    let garbage = blobs::write_blob_atomic(&[9; 10]).unwrap();
+
+    let manifests = vec![
+        ("a".to_string(), manifest(&[&shared, &only_a, &shared])),
+        ("b".to_string(), manifest(&[&shared])),
+    ];
+    let report = compute_usage(&manifests, 2).unwrap();
+
+    assert_eq!(report.blob_count, 3);
+    assert_eq!(report.store_bytes, 18);
+    assert_eq!(report.logical_bytes, 16);
+    assert_eq!(report.referenced_bytes, 8);
+    assert_eq!(report.unreferenced_bytes, 10);
+    assert_eq!(report.dedup_ratio(), 2.0);
+    assert_eq!(report.largest_blobs[0].hash, garbage);
+    assert_eq!(report.largest_blobs[0].references, 0);
+
+    assert_eq!(report.manifests[0].label, "a");
+    assert_eq!(report.manifests[0].unique_bytes, 4);
+    assert_eq!(report.manifests[1].unique_bytes, 0);
+
+    // A fine-tune sharing the base's first tensor, and another model sharing nothing
+    let only_ft = blobs::write_blob_atomic(&[3; 4]).unwrap();
+    let only_c = blobs::write_blob_atomic(&[7; 4]).unwrap();
+    let manifests = vec![
+        ("base".to_string(), manifest(&[&shared, &only_a])),
+        ("ft".to_string(), manifest(&[&shared, &only_ft])),
+        ("other".to_string(), manifest(&[&only_c])),
+    ];
+    let dedup = compute_dedup(&manifests).unwrap();
+    assert_eq!(
+        dedup.groups,
+        vec![SharedGroup {
+            manifests: vec!["base".to_string(), "ft".to_string()],
+            blobs: 1,
+            bytes: 4,
+            entries: vec!["t0".to_string()],
+        }]
+    );
+    assert_eq!(dedup.manifests[0].shared_bytes, 4);
+    assert_eq!(dedup.manifests[0].unique_bytes, 4);
+    assert_eq!(dedup.manifests[2].shared_bytes, 0);
+    assert_eq!(dedup.manifests[2].unique_bytes, 4);
+    assert_eq!(dedup.logical_bytes, 20);
+    assert_eq!(dedup.stored_bytes, 16);
+    assert_eq!(dedup.saved_bytes(), 4);
+
+    let _ = fs::remove_dir_all(root);
+    unsafe {
+        env::remove_var("VEKT_ROOT");
+    }
+}